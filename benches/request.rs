@@ -0,0 +1,55 @@
+use katana::request::Request;
+use std::hint::black_box;
+use std::io::{BufReader, Cursor};
+use std::time::{Duration, Instant};
+
+// parses the heads of pipelined browser-like requests, the way a keep-alive connection reads
+// them one after the other: `cargo bench --bench request`
+const HEAD: &[u8] = b"GET /assets/app.js?v=3 HTTP/1.1\r\n\
+Host: www.example.com\r\n\
+User-Agent: Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0\r\n\
+Accept: */*\r\n\
+Accept-Language: en-US,en;q=0.5\r\n\
+Accept-Encoding: gzip, deflate, br\r\n\
+Referer: https://www.example.com/\r\n\
+Cookie: session=4f1c2b; theme=dark\r\n\
+Connection: keep-alive\r\n\
+If-None-Match: W/\"1a2b-3c4d\"\r\n\
+Sec-Fetch-Dest: script\r\n\
+Sec-Fetch-Mode: no-cors\r\n\
+Sec-Fetch-Site: same-origin\r\n\
+\r\n";
+
+const REQUESTS: usize = 200_000;
+
+fn main() {
+    let stream = HEAD.repeat(REQUESTS);
+    for _ in 0..3 {
+        let mut reader = BufReader::new(Cursor::new(&stream));
+        let started = Instant::now();
+        for _ in 0..REQUESTS {
+            black_box(Request::read_head(&mut reader).unwrap());
+        }
+        let elapsed = started.elapsed();
+        report("read_head", elapsed);
+
+        // a line buffer kept for the whole connection, as the server does
+        let mut reader = BufReader::new(Cursor::new(&stream));
+        let mut buffer = Vec::new();
+        let started = Instant::now();
+        for _ in 0..REQUESTS {
+            black_box(Request::read_head_with(&mut reader, &mut buffer).unwrap());
+        }
+        report("read_head_with", started.elapsed());
+    }
+}
+
+fn report(name: &str, elapsed: Duration) {
+    println!(
+        "{}: {} requests in {:.2?}, {:.0} ns per request",
+        name,
+        REQUESTS,
+        elapsed,
+        elapsed.as_nanos() as f64 / REQUESTS as f64
+    );
+}
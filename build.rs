@@ -0,0 +1,39 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// stamps the binary with the commit it was built from and when, shown by `katana --version`
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=KATANA_GIT_COMMIT={}", commit);
+
+    // SOURCE_DATE_EPOCH keeps reproducible builds reproducible
+    let built = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|secs| secs.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()));
+    println!("cargo:rustc-env=KATANA_BUILD_TIME={}", built);
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    // a new commit moves the branch HEAD points to, a missing file would rerun every build
+    let git = Path::new(".git");
+    if git.join("HEAD").is_file() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        let head = fs::read_to_string(git.join("HEAD")).unwrap_or_default();
+        if let Some(branch) = head.trim().strip_prefix("ref: ").filter(|branch| git.join(branch).is_file()) {
+            println!("cargo:rerun-if-changed=.git/{}", branch);
+        }
+        if git.join("packed-refs").is_file() {
+            println!("cargo:rerun-if-changed=.git/packed-refs");
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,79 @@
+use crate::filetype::FileType;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// a request for /data that has no file of its own is answered by data.json, data.xml or
+// data.html, whichever content type the Accept header weighs highest
+pub struct Accept;
+
+impl Accept {
+    // the weight of a content type, the most specific matching media range decides (RFC 9110
+    // 12.5.1) and a missing header accepts everything
+    pub fn quality(accept: Option<&str>, content_type: &str) -> f32 {
+        let Some(accept) = accept.filter(|accept| !accept.trim().is_empty()) else {
+            return 1.0;
+        };
+        let (main, sub) = content_type.split_once('/').unwrap_or((content_type, ""));
+
+        let mut best: Option<(u8, f32)> = None;
+        for entry in accept.split(',') {
+            let mut parts = entry.split(';').map(str::trim);
+            let range = parts.next().unwrap_or_default().to_lowercase();
+            let Some((range_main, range_sub)) = range.split_once('/') else {
+                continue;
+            };
+            let specificity = match (range_main, range_sub) {
+                ("*", "*") => 0,
+                (range_main, "*") if range_main.eq_ignore_ascii_case(main) => 1,
+                (range_main, range_sub) if range_main.eq_ignore_ascii_case(main) && range_sub.eq_ignore_ascii_case(sub) => 2,
+                _ => continue,
+            };
+            let q = parts
+                .filter_map(|parameter| parameter.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                .map_or(Some(1.0), |(_, value)| value.trim().parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q)));
+            let Some(q) = q else {
+                continue;
+            };
+            if best.is_none_or(|(best_specificity, _)| specificity > best_specificity) {
+                best = Some((specificity, q));
+            }
+        }
+        best.map_or(0.0, |(_, q)| q)
+    }
+
+    // files named <name>.<extension> next to the missing <name>, sorted by extension so
+    // equally acceptable variants are picked in a stable order
+    pub fn variants(path: &Path) -> Vec<(PathBuf, FileType)> {
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|name| name.to_str())) else {
+            return Vec::new();
+        };
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let mut variants: Vec<(PathBuf, FileType)> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_str()?.to_string();
+                let extension = file_name.strip_prefix(name)?.strip_prefix('.')?;
+                Some((entry.path(), FileType::from_extension(extension)?))
+            })
+            .collect();
+        variants.sort_by(|a, b| a.1.extension.cmp(&b.1.extension));
+        variants
+    }
+
+    // the index of the variant with the highest weight, None when every one is refused
+    pub fn select(accept: Option<&str>, variants: &[(PathBuf, FileType)]) -> Option<usize> {
+        let mut best: Option<(usize, f32)> = None;
+        for (index, (_, file_type)) in variants.iter().enumerate() {
+            let q = Self::quality(accept, &file_type.content_type);
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((index, q));
+            }
+        }
+        best.map(|(index, _)| index)
+    }
+}
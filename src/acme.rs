@@ -0,0 +1,49 @@
+use crate::request::Request;
+use std::fs;
+use std::path::Path;
+
+// answers ACME HTTP-01 challenges (RFC 8555 8.3) on behalf of an external client such as
+// certbot or lego, which writes the key authorization for each token into --acme-dir;
+// Katana has no TLS client of its own to talk to the CA
+pub struct Acme;
+
+impl Acme {
+    pub const CHALLENGE_PATH: &'static str = "/.well-known/acme-challenge/";
+
+    // tokens are base64url without padding, anything else could escape the directory
+    pub fn token(path: &str) -> Option<&str> {
+        let token = path.strip_prefix(Self::CHALLENGE_PATH)?;
+        let valid = !token.is_empty()
+            && token
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        if valid {
+            Some(token)
+        } else {
+            None
+        }
+    }
+
+    // the Host header without its port, challenges for other names are not ours to answer
+    pub fn serves_host(domains: &[String], host: Option<&str>) -> bool {
+        if domains.is_empty() {
+            return true;
+        }
+        let Some(host) = host else {
+            return false;
+        };
+        let name = Request::host_name(host);
+        domains.iter().any(|domain| domain.eq_ignore_ascii_case(name))
+    }
+
+    // the key authorization stored for a token, clients often write it with a trailing newline
+    pub fn key_authorization(dir: &Path, token: &str) -> Option<String> {
+        let content = fs::read_to_string(dir.join(token)).ok()?;
+        let content = content.trim();
+        if content.starts_with(&format!("{}.", token)) {
+            Some(content.to_string())
+        } else {
+            None
+        }
+    }
+}
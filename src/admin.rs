@@ -0,0 +1,263 @@
+use crate::config::Config;
+use crate::connection::{Connection, Listener};
+use crate::crypto::Crypto;
+use crate::http::{HttpMethod, HttpStatus};
+use crate::json::Json;
+use crate::logger::{LogFormat, LogLevel, Logger};
+use crate::pool::BufferPool;
+use crate::proxy::Upstream;
+use crate::request::Request;
+use crate::response::Response;
+use crate::server::Server;
+use crate::signal::Signal;
+use crate::stats::Stats;
+use crate::utils::Utils;
+use std::io::{BufReader, Error};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// JSON endpoints for operators on their own listener (--admin-listen), every request needs
+// the --admin-token as a bearer token:
+//
+//   GET  /status      uptime, connections and requests served
+//   GET  /metrics     the same counters in the Prometheus text format
+//   GET  /config      the settings in effect, secrets left out
+//   POST /log-level   {"level": "debug"} until the next reload
+//   POST /reload      same as SIGHUP
+//   POST /shutdown    same as SIGTERM
+pub struct Admin;
+
+impl Admin {
+    pub const MAX_BODY_SIZE: usize = 4096;
+    pub const METRICS_TYPE: &'static str = "text/plain; version=0.0.4";
+    pub const READ_TIMEOUT: Duration = Duration::from_secs(5);
+    // a unix socket only the owner can connect to
+    const SOCKET_MODE: u32 = 0o600;
+
+    pub fn bind(addr: &str) -> Result<Listener, Error> {
+        Listener::bind(addr, Some(Self::SOCKET_MODE))
+    }
+
+    // one request per connection, answered on a thread of its own
+    pub fn spawn(server: Server, listener: Listener) {
+        thread::spawn(move || loop {
+            let Ok(stream) = listener.accept() else {
+                continue;
+            };
+            let server = server.clone();
+            thread::spawn(move || Self::handle_connection(&server, stream));
+        });
+    }
+
+    fn handle_connection(server: &Server, mut stream: Connection) {
+        let _ = stream.set_read_timeout(Some(Self::READ_TIMEOUT));
+        let Ok(read_half) = stream.try_clone() else {
+            return;
+        };
+        let mut reader = BufReader::new(read_half);
+        let mut request = match Request::read_head(&mut reader) {
+            Ok(request) => request,
+            Err(e) => {
+                Logger::warn(format!("Rejected admin request: {}", e).as_str());
+                return;
+            }
+        };
+        request.set_peer(stream.peer_addr());
+        request.assign_id(false);
+        let status = match request.read_body(&mut reader, Self::MAX_BODY_SIZE) {
+            Ok(()) => None,
+            Err(e) => Some(e.status().unwrap_or(HttpStatus::BadRequest)),
+        };
+
+        let Some(mut response) = Response::new(request, server.templates().to_owned()) else {
+            return;
+        };
+        match status {
+            Some(status) => Self::serve_json(&mut response, status, &Self::error_json(status)),
+            None => Self::route(server, &mut response),
+        }
+        server.send_response(&mut response, &mut stream, false);
+
+        // answered first so the caller learns the request was taken
+        if response.status_code == HttpStatus::Accepted && response.request.path == "/shutdown" {
+            Signal::request_shutdown();
+        }
+    }
+
+    pub fn route(server: &Server, response: &mut Response) {
+        let config = server.config();
+        if !Self::authorized(config.admin_token.as_deref(), &response.request) {
+            Self::serve_json(response, HttpStatus::Unauthorized, &Self::error_json(HttpStatus::Unauthorized));
+            response.set_header("WWW-Authenticate", "Bearer realm=\"katana-admin\"");
+            return;
+        }
+
+        let request = &response.request;
+        let (status, body) = match (request.method, request.path.as_str()) {
+            (HttpMethod::GET, "/status") => (HttpStatus::Ok, Self::status_json()),
+            (HttpMethod::GET, "/config") => (HttpStatus::Ok, Self::config_json(server, &config)),
+            (HttpMethod::GET, "/metrics") => {
+                response.serve_body(Self::METRICS_TYPE, Self::metrics(&config).into_bytes());
+                response.set_header("Cache-Control", "no-store");
+                return;
+            }
+            (HttpMethod::POST, "/log-level") => Self::set_log_level(&request.body),
+            (HttpMethod::POST, "/reload") => {
+                Signal::request_reload();
+                (HttpStatus::Accepted, "{\"status\":\"reloading\"}".to_string())
+            }
+            (HttpMethod::POST, "/shutdown") => (HttpStatus::Accepted, "{\"status\":\"shutting down\"}".to_string()),
+            (_, "/status" | "/config" | "/metrics" | "/log-level" | "/reload" | "/shutdown") => {
+                (HttpStatus::MethodNotAllowed, Self::error_json(HttpStatus::MethodNotAllowed))
+            }
+            _ => (HttpStatus::NotFound, Self::error_json(HttpStatus::NotFound)),
+        };
+        Self::serve_json(response, status, &body);
+    }
+
+    // without a token the API stays closed, config validation makes sure one is set
+    fn authorized(token: Option<&str>, request: &Request) -> bool {
+        let Some(token) = token.filter(|token| !token.is_empty()) else {
+            return false;
+        };
+        let given = request.header("Authorization").and_then(|value| {
+            let (scheme, credentials) = value.trim().split_once(' ')?;
+            scheme.eq_ignore_ascii_case("Bearer").then(|| credentials.trim())
+        });
+        given.is_some_and(|given| Crypto::constant_time_eq(given.as_bytes(), token.as_bytes()))
+    }
+
+    fn set_log_level(body: &[u8]) -> (HttpStatus, String) {
+        let level = Json::parse(&String::from_utf8_lossy(body))
+            .and_then(|json| json.get("level").and_then(Json::as_str).and_then(LogLevel::from_str));
+        match level {
+            Some(level) => {
+                Logger::set_level(level);
+                Logger::info(format!("Log level set to {} through the admin API.", level.as_str()).as_str());
+                (HttpStatus::Ok, format!("{{\"level\":{}}}", Utils::json_string(&level.as_str().to_lowercase())))
+            }
+            None => (
+                HttpStatus::BadRequest,
+                "{\"error\":\"expected {\\\"level\\\": \\\"debug|info|warn|error\\\"}\"}".to_string(),
+            ),
+        }
+    }
+
+    pub fn status_json() -> String {
+        let pool = BufferPool::stats();
+        format!(
+            "{{\"version\":{},\"pid\":{},\"uptime_seconds\":{},\"active_connections\":{},\"requests\":{},\
+             \"accepted_connections\":{},\"reading\":{},\"writing\":{},\"bytes_received\":{},\"bytes_sent\":{},\
+             \"buffers\":{{\"in_use\":{},\"idle\":{},\"reused\":{},\"allocated\":{}}}}}",
+            Utils::json_string(Server::SERVER_VERSION),
+            process::id(),
+            Stats::uptime().as_secs(),
+            Stats::active_connections(),
+            Stats::requests(),
+            Stats::accepted_connections(),
+            Stats::reading(),
+            Stats::writing(),
+            Stats::bytes_received(),
+            Stats::bytes_sent(),
+            pool.in_use,
+            pool.idle,
+            pool.reused,
+            pool.allocated
+        )
+    }
+
+    // counters as scraped by Prometheus, gauges for what is open now
+    // the proxy upstreams of every site and location are labelled by address, an address
+    // listed twice is reported once
+    pub fn metrics(config: &Config) -> String {
+        let pool = BufferPool::stats();
+        let metrics: [(&str, &str, &str, String); 10] = [
+            ("katana_uptime_seconds", "gauge", "Seconds since the server started", Stats::uptime().as_secs().to_string()),
+            ("katana_connections_accepted_total", "counter", "Connections accepted", Stats::accepted_connections().to_string()),
+            ("katana_connections_active", "gauge", "Connections open", Stats::active_connections().to_string()),
+            ("katana_connections_reading", "gauge", "Connections reading a request", Stats::reading().to_string()),
+            ("katana_connections_writing", "gauge", "Connections handling a request", Stats::writing().to_string()),
+            ("katana_requests_total", "counter", "Responses sent", Stats::requests().to_string()),
+            ("katana_received_bytes_total", "counter", "Bytes read from clients", Stats::bytes_received().to_string()),
+            ("katana_sent_bytes_total", "counter", "Bytes written to clients", Stats::bytes_sent().to_string()),
+            ("katana_buffers_in_use", "gauge", "Pooled buffers taken", pool.in_use.to_string()),
+            ("katana_buffers_idle", "gauge", "Pooled buffers waiting", pool.idle.to_string()),
+        ];
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n{} {}\n", name, help, name, kind, name, value));
+        }
+
+        let mut upstreams: Vec<Arc<Upstream>> = Vec::new();
+        for upstream in config.proxies().into_iter().flat_map(|proxy| proxy.upstreams.iter()) {
+            if !upstreams.iter().any(|seen| seen.address == upstream.address) {
+                upstreams.push(upstream.clone());
+            }
+        }
+        if upstreams.is_empty() {
+            return text;
+        }
+        let labelled = [
+            ("katana_upstream_up", "Proxy upstreams passing their health checks"),
+            ("katana_upstream_active_requests", "Requests sent to proxy upstreams and not answered yet"),
+        ];
+        for (name, help) in labelled {
+            text.push_str(&format!("# HELP {} {}\n# TYPE {} gauge\n", name, help, name));
+            for upstream in &upstreams {
+                let label = upstream.address.replace('\\', "\\\\").replace('"', "\\\"");
+                let value = if name == "katana_upstream_up" { usize::from(upstream.is_up()) } else { upstream.active() };
+                text.push_str(&format!("{}{{upstream=\"{}\"}} {}\n", name, label, value));
+            }
+        }
+        text
+    }
+
+    // keys, secrets and tokens never leave the process
+    pub fn config_json(server: &Server, config: &Config) -> String {
+        let strings = |values: &[String]| {
+            format!("[{}]", values.iter().map(|value| Utils::json_string(value)).collect::<Vec<_>>().join(","))
+        };
+        let optional = |value: Option<&str>| value.map_or("null".to_string(), Utils::json_string);
+        let methods: Vec<String> = config.allowed_methods.iter().map(|method| method.as_str().to_string()).collect();
+        format!(
+            "{{\"listen\":{},\"root_dir\":{},\"config_file\":{},\"worker\":{},\"keep_alive_timeout\":{},\
+             \"max_requests\":{},\"max_body_size\":{},\"allowed_methods\":{},\"writable\":{},\"read_only\":{},\"listing\":{},\"log_level\":{},\
+             \"log_format\":{},\"watch\":{},\"maintenance\":{},\"compression_level\":{},\"https_redirect\":{},\
+             \"jwt_protected\":{},\"signed_protected\":{},\"tus_endpoint\":{},\"locations\":{},\"virtual_hosts\":{}}}",
+            strings(&server.listen_addrs()),
+            Utils::json_string(&config.root_dir.to_string_lossy()),
+            optional(config.config_file.as_ref().map(|path| path.to_string_lossy()).as_deref()),
+            config.worker,
+            config.keep_alive_timeout,
+            config.max_requests,
+            config.max_body_size,
+            strings(&methods),
+            config.writable,
+            config.read_only,
+            config.listing,
+            Utils::json_string(&Logger::level().as_str().to_lowercase()),
+            Utils::json_string(if config.log_format == LogFormat::Json { "json" } else { "plain" }),
+            config.watch,
+            config.maintenance.active(&config.root_dir),
+            config.compression.level,
+            config.https_redirect,
+            strings(&config.jwt.protected),
+            strings(&config.signed_urls.protected),
+            optional(config.tus.endpoint.as_deref()),
+            strings(&config.locations.iter().map(|location| location.prefix.clone()).collect::<Vec<_>>()),
+            strings(&config.vhosts.iter().flat_map(|vhost| vhost.names.clone()).collect::<Vec<_>>()),
+        )
+    }
+
+    fn error_json(status: HttpStatus) -> String {
+        format!("{{\"error\":{}}}", Utils::json_string(status.to_message()))
+    }
+
+    fn serve_json(response: &mut Response, status: HttpStatus, body: &str) {
+        response.serve_body("application/json", body.as_bytes().to_vec());
+        response.status_code = status;
+        response.set_header("Cache-Control", "no-store");
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// `katana bench <url>`: keep-alive GET requests from a number of connections for a while, to
+// compare settings such as --workers without installing a load generator. Plain http only,
+// and the client shares the machine with the server when pointed at localhost
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bench {
+    pub url: Option<String>,
+    pub connections: usize,
+    pub duration: Duration,
+}
+
+impl Default for Bench {
+    fn default() -> Self {
+        Self {
+            url: None,
+            connections: Self::DEFAULT_CONNECTIONS,
+            duration: Self::DEFAULT_DURATION,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BenchReport {
+    pub elapsed: Duration,
+    // of every answered request, sorted
+    pub latencies: Vec<Duration>,
+    pub statuses: BTreeMap<u16, usize>,
+    // connections refused or cut and responses that could not be read
+    pub errors: usize,
+}
+
+impl Bench {
+    pub const DEFAULT_CONNECTIONS: usize = 10;
+    pub const DEFAULT_DURATION: Duration = Duration::from_secs(10);
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // http://host[:port][/path], the authority and the request target
+    pub fn target(url: &str) -> Result<(String, String), String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("only http:// URLs can be benchmarked: {}", url))?;
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(index) => (&rest[..index], rest[index..].to_string()),
+            None => (rest, "/".to_string()),
+        };
+        let path = if path.starts_with('?') { format!("/{}", path) } else { path };
+        if authority.is_empty() {
+            return Err(format!("missing host: {}", url));
+        }
+        // [::1] has colons but no port
+        let has_port = authority.rsplit_once(':').is_some_and(|(_, port)| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()));
+        let authority = if has_port { authority.to_string() } else { format!("{}:80", authority) };
+        Ok((authority, path))
+    }
+
+    pub fn run(&self) -> Result<BenchReport, String> {
+        let url = self.url.as_deref().ok_or("nothing to benchmark, give a URL")?;
+        let (authority, path) = Self::target(url)?;
+        let host = authority.strip_suffix(":80").unwrap_or(&authority);
+        let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: katana-bench\r\n\r\n", path, host);
+
+        let started = Instant::now();
+        let deadline = started + self.duration;
+        let workers: Vec<_> = (0..self.connections.max(1))
+            .map(|_| {
+                let (authority, request) = (authority.clone(), request.clone());
+                thread::spawn(move || Self::load(&authority, request.as_bytes(), deadline))
+            })
+            .collect();
+
+        let mut report = BenchReport::default();
+        for worker in workers {
+            let (latencies, statuses, errors) = worker.join().map_err(|_| "a benchmark thread panicked")?;
+            report.latencies.extend(latencies);
+            for (status, count) in statuses {
+                *report.statuses.entry(status).or_default() += count;
+            }
+            report.errors += errors;
+        }
+        report.elapsed = started.elapsed();
+        report.latencies.sort();
+        Ok(report)
+    }
+
+    // one connection, opened again whenever the server closes it
+    fn load(authority: &str, request: &[u8], deadline: Instant) -> (Vec<Duration>, BTreeMap<u16, usize>, usize) {
+        let (mut latencies, mut statuses, mut errors) = (Vec::new(), BTreeMap::new(), 0);
+        let mut connection: Option<BufReader<TcpStream>> = None;
+        while Instant::now() < deadline {
+            let reader = match connection.as_mut() {
+                Some(reader) => reader,
+                None => match TcpStream::connect(authority) {
+                    Ok(stream) => {
+                        let _ = stream.set_read_timeout(Some(Self::TIMEOUT));
+                        let _ = stream.set_nodelay(true);
+                        connection.insert(BufReader::new(stream))
+                    }
+                    Err(_) => {
+                        errors += 1;
+                        // a refused connection comes back at once, do not spin on it
+                        thread::sleep(Duration::from_millis(10));
+                        continue;
+                    }
+                },
+            };
+
+            let sent = Instant::now();
+            match Self::exchange(reader, request) {
+                Ok((status, keep_alive)) => {
+                    latencies.push(sent.elapsed());
+                    *statuses.entry(status).or_default() += 1;
+                    if !keep_alive {
+                        connection = None;
+                    }
+                }
+                Err(_) => {
+                    errors += 1;
+                    connection = None;
+                }
+            }
+        }
+        (latencies, statuses, errors)
+    }
+
+    // sends the request and reads the whole response, the status and whether the connection
+    // can be used again
+    fn exchange(reader: &mut BufReader<TcpStream>, request: &[u8]) -> Result<(u16, bool), Error> {
+        reader.get_mut().write_all(request)?;
+        let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_string());
+
+        let mut line = String::new();
+        let status = loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "connection closed"));
+            }
+            let status: u16 = line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|code| code.parse().ok())
+                .ok_or_else(|| invalid("malformed status line"))?;
+            // interim responses are followed by the real one
+            if status >= 200 {
+                break status;
+            }
+            Self::read_headers(reader)?;
+        };
+
+        let headers = Self::read_headers(reader)?;
+        let header = |name: &str| headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str());
+        let mut keep_alive = !header("Connection").is_some_and(|value| value.eq_ignore_ascii_case("close"));
+        if status == 204 || status == 304 {
+            return Ok((status, keep_alive));
+        }
+
+        if header("Transfer-Encoding").is_some_and(|value| value.to_lowercase().contains("chunked")) {
+            loop {
+                line.clear();
+                reader.read_line(&mut line)?;
+                let size = line.trim().split(';').next().unwrap_or_default();
+                let size = usize::from_str_radix(size, 16).map_err(|_| invalid("malformed chunk size"))?;
+                if size == 0 {
+                    // trailers, up to the empty line
+                    Self::read_headers(reader)?;
+                    break;
+                }
+                Self::discard(reader, size + 2)?;
+            }
+        } else if let Some(length) = header("Content-Length") {
+            let length = length.parse().map_err(|_| invalid("malformed Content-Length"))?;
+            Self::discard(reader, length)?;
+        } else {
+            // the body ends with the connection
+            std::io::copy(reader, &mut std::io::sink())?;
+            keep_alive = false;
+        }
+        Ok((status, keep_alive))
+    }
+
+    fn read_headers(reader: &mut BufReader<TcpStream>) -> Result<Vec<(String, String)>, Error> {
+        let mut headers = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                return Ok(headers);
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                headers.push((key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+    }
+
+    fn discard(reader: &mut BufReader<TcpStream>, length: usize) -> Result<(), Error> {
+        let copied = std::io::copy(&mut reader.take(length as u64), &mut std::io::sink())?;
+        if copied < length as u64 {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "body cut short"));
+        }
+        Ok(())
+    }
+}
+
+impl BenchReport {
+    pub fn requests(&self) -> usize {
+        self.latencies.len()
+    }
+
+    pub fn throughput(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => self.requests() as f64 / secs,
+            _ => 0.0,
+        }
+    }
+
+    // nearest rank, p between 0 and 100
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = ((p / 100.0) * self.latencies.len() as f64).ceil() as usize;
+        self.latencies[rank.clamp(1, self.latencies.len()) - 1]
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        writeln!(f, "{} requests in {:.2}s, {:.1} requests/s", self.requests(), self.elapsed.as_secs_f64(), self.throughput())?;
+        writeln!(
+            f,
+            "latency p50 {:.2}ms, p90 {:.2}ms, p99 {:.2}ms, max {:.2}ms",
+            ms(self.percentile(50.0)),
+            ms(self.percentile(90.0)),
+            ms(self.percentile(99.0)),
+            ms(self.percentile(100.0))
+        )?;
+        let statuses: Vec<String> = self.statuses.iter().map(|(status, count)| format!("{} x{}", status, count)).collect();
+        writeln!(f, "statuses {}", if statuses.is_empty() { "none".to_string() } else { statuses.join(", ") })?;
+        write!(f, "errors {}", self.errors)
+    }
+}
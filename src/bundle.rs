@@ -0,0 +1,82 @@
+use crate::utils::Utils;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+
+// a site compiled into the binary, so a tool shipping a web UI is a single executable. The
+// build script of the embedding crate writes the bundle and includes it:
+//
+//   // build.rs
+//   let source = katana::bundle::Bundle::generate("SITE", Path::new("ui/dist")).unwrap();
+//   fs::write(Path::new(&env::var("OUT_DIR").unwrap()).join("site.rs"), source).unwrap();
+//   println!("cargo:rerun-if-changed=ui/dist");
+//
+//   // main.rs
+//   include!(concat!(env!("OUT_DIR"), "/site.rs"));
+//   let config = Config { bundle: Some(&SITE), ..Config::default() };
+#[derive(Debug)]
+pub struct Bundle {
+    // request paths such as /assets/app.js, sorted
+    files: &'static [(&'static str, &'static [u8])],
+}
+
+impl Bundle {
+    pub const fn new(files: &'static [(&'static str, &'static [u8])]) -> Self {
+        Self { files }
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    // the file a request path names, a directory is answered by its index.html
+    pub fn get(&self, path: &str) -> Option<(&'static str, &'static [u8])> {
+        let find = |wanted: &str| self.files.iter().find(|(name, _)| *name == wanted).copied();
+        let index = format!("{}/index.html", path.trim_end_matches('/'));
+        if path.ends_with('/') {
+            find(&index)
+        } else {
+            find(path).or_else(|| find(&index))
+        }
+    }
+
+    // Rust source declaring `pub static <name>: Bundle` with every file under dir, hidden
+    // ones left out as they would be when served from disk
+    pub fn generate(name: &str, dir: &Path) -> Result<String, Error> {
+        let root = dir.canonicalize()?;
+        let mut files = Vec::new();
+        Self::collect(&root, &root, &mut files)?;
+        files.sort();
+
+        let mut source = format!("pub static {}: katana::bundle::Bundle = katana::bundle::Bundle::new(&[\n", name);
+        for (request_path, file) in files {
+            let _ = writeln!(source, "    ({:?}, include_bytes!({:?})),", request_path, file);
+        }
+        source.push_str("]);\n");
+        Ok(source)
+    }
+
+    fn collect(root: &Path, dir: &Path, files: &mut Vec<(String, String)>) -> Result<(), Error> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if !Utils::is_valid_entry(name) {
+                continue;
+            }
+            if path.is_dir() {
+                Self::collect(root, &path, files)?;
+            } else if let Ok(relative) = path.strip_prefix(root) {
+                let request_path = format!("/{}", relative.to_string_lossy().replace('\\', "/"));
+                files.push((request_path, path.to_string_lossy().into_owned()));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+use crate::http::HttpStatus;
+use crate::logger::Logger;
+use crate::request::Request;
+use crate::server::Server;
+use std::env;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// what a script answered: the Status and Location it set, its other headers and the body
+#[derive(Debug, Clone, PartialEq)]
+pub struct CgiOutput {
+    pub status: HttpStatus,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+// CGI/1.1 (RFC 3875): the script is run once per request with the request described in its
+// environment and the body on its standard input, and writes headers, a blank line and the
+// body on its standard output. Standard error goes to the log
+pub struct Cgi;
+
+impl Cgi {
+    pub const TIMEOUT: Duration = Duration::from_secs(30);
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+    // set by the server itself from the body it read, or not to be trusted from the client
+    // (httpoxy: HTTP_PROXY would be taken for the proxy by the script's HTTP client)
+    const SKIPPED_HEADERS: &'static [&'static str] = &["Content-Length", "Content-Type", "Proxy"];
+
+    // the meta-variables of RFC 3875 4.1, plus the ones PHP and most frameworks look for
+    pub fn environment(request: &Request, script: &Path, root_dir: &Path) -> Vec<(String, String)> {
+        let (host, port) = match request.domain.trim().rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host.to_string(), port.to_string()),
+            _ => (request.host().unwrap_or("localhost").to_string(), if request.scheme == "https" { "443" } else { "80" }.to_string()),
+        };
+        let query = request.target.split_once('?').map(|(_, query)| query).unwrap_or_default();
+        let mut variables = vec![
+            ("GATEWAY_INTERFACE", "CGI/1.1".to_string()),
+            ("SERVER_SOFTWARE", format!("{}/{}", Server::SERVER_NAME, Server::SERVER_VERSION)),
+            ("SERVER_PROTOCOL", request.version.as_str().to_string()),
+            ("SERVER_NAME", host),
+            ("SERVER_PORT", port),
+            ("REQUEST_METHOD", request.method.as_str().to_string()),
+            ("REQUEST_URI", request.target.clone()),
+            ("SCRIPT_NAME", request.path.clone()),
+            ("SCRIPT_FILENAME", script.to_string_lossy().to_string()),
+            ("DOCUMENT_ROOT", root_dir.to_string_lossy().to_string()),
+            ("QUERY_STRING", query.to_string()),
+            // php-cgi refuses to run without it, as a guard against being called directly
+            ("REDIRECT_STATUS", "200".to_string()),
+            ("PATH", env::var("PATH").unwrap_or_else(|_| "/usr/local/bin:/usr/bin:/bin".to_string())),
+        ];
+        if let Some(ip) = request.client_ip {
+            variables.push(("REMOTE_ADDR", ip.to_string()));
+        }
+        if let Some(peer) = request.peer.filter(|peer| Some(peer.ip()) == request.client_ip) {
+            variables.push(("REMOTE_PORT", peer.port().to_string()));
+        }
+        if !request.body.is_empty() {
+            variables.push(("CONTENT_LENGTH", request.body.len().to_string()));
+        }
+        if let Some(content_type) = request.header("Content-Type") {
+            variables.push(("CONTENT_TYPE", content_type.to_string()));
+        }
+        if request.scheme == "https" {
+            variables.push(("HTTPS", "on".to_string()));
+        }
+
+        let mut variables: Vec<(String, String)> = variables.into_iter().map(|(name, value)| (name.to_string(), value)).collect();
+        for (name, value) in &request.headers {
+            if Self::SKIPPED_HEADERS.iter().any(|skipped| skipped.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            variables.push((format!("HTTP_{}", name.to_uppercase().replace('-', "_")), value.clone()));
+        }
+        variables
+    }
+
+    // a script that cannot be started or answers garbage is a 502, one still running after
+    // the timeout is killed and a 504
+    pub fn run(script: &Path, request: &Request, root_dir: &Path) -> Result<CgiOutput, HttpStatus> {
+        let mut command = Command::new(script);
+        command
+            .env_clear()
+            .envs(Self::environment(request, script, root_dir))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(dir) = script.parent() {
+            command.current_dir(dir);
+        }
+        let mut child = command.spawn().map_err(|e| {
+            Logger::error(format!("Failed to run CGI script {}: {}", script.display(), e).as_str());
+            HttpStatus::BadGateway
+        })?;
+
+        // written and read on threads of their own so that neither side waits on a full pipe
+        let (stdin, stdout, stderr) = (child.stdin.take(), child.stdout.take(), child.stderr.take());
+        let body = request.body.clone();
+        thread::spawn(move || stdin.map(|mut stdin| stdin.write_all(&body)));
+        let output = thread::spawn(move || {
+            let mut output = Vec::new();
+            stdout.map(|mut stdout| stdout.read_to_end(&mut output));
+            output
+        });
+        let name = script.display().to_string();
+        thread::spawn(move || {
+            let mut errors = String::new();
+            if stderr.is_some_and(|mut stderr| stderr.read_to_string(&mut errors).is_ok()) {
+                for line in errors.lines().filter(|line| !line.trim().is_empty()) {
+                    Logger::warn(format!("CGI {}: {}", name, line).as_str());
+                }
+            }
+        });
+
+        let deadline = Instant::now() + Self::TIMEOUT;
+        loop {
+            match child.try_wait() {
+                Ok(Some(_)) => break,
+                Ok(None) if Instant::now() < deadline => thread::sleep(Self::POLL_INTERVAL),
+                _ => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    Logger::error(format!("CGI script {} killed after {}s", script.display(), Self::TIMEOUT.as_secs()).as_str());
+                    return Err(HttpStatus::GatewayTimeout);
+                }
+            }
+        }
+        let output = output.join().unwrap_or_default();
+        Self::parse_output(&output).ok_or_else(|| {
+            Logger::error(format!("CGI script {} sent no valid headers", script.display()).as_str());
+            HttpStatus::BadGateway
+        })
+    }
+
+    // headers up to the first blank line, with LF or CRLF line ends (RFC 3875 6.2). A
+    // Location without a Status is a 302, an unknown status code a 502
+    pub fn parse_output(output: &[u8]) -> Option<CgiOutput> {
+        let (head, body) = [&b"\r\n\r\n"[..], b"\n\n"]
+            .iter()
+            .filter_map(|separator| {
+                let end = output.windows(separator.len()).position(|window| window == *separator)?;
+                Some((end, end + separator.len()))
+            })
+            .min()
+            .map(|(end, start)| (&output[..end], &output[start..]))?;
+        let head = std::str::from_utf8(head).ok()?;
+
+        let mut status = None;
+        let mut headers = Vec::new();
+        for line in head.lines() {
+            let (name, value) = line.split_once(':')?;
+            let (name, value) = (name.trim(), value.trim());
+            if name.eq_ignore_ascii_case("Status") {
+                let code = value.split_whitespace().next()?.parse().ok()?;
+                status = Some(HttpStatus::from_code(code)?);
+            } else {
+                headers.push((name.to_string(), value.to_string()));
+            }
+        }
+        let redirects = headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("Location"));
+        let status = status.unwrap_or(if redirects { HttpStatus::Found } else { HttpStatus::Ok });
+        Some(CgiOutput {
+            status,
+            headers,
+            body: body.to_vec(),
+        })
+    }
+}
+
+// a FastCGI client (the FastCGI 1.0 specification) in the responder role, one request per
+// connection: the CGI environment goes as params, the body as stdin, and what comes back on
+// stdout is parsed as CGI output
+pub struct FastCgi;
+
+impl FastCgi {
+    const VERSION: u8 = 1;
+    const BEGIN_REQUEST: u8 = 1;
+    const END_REQUEST: u8 = 3;
+    const PARAMS: u8 = 4;
+    const STDIN: u8 = 5;
+    const STDOUT: u8 = 6;
+    const STDERR: u8 = 7;
+    const RESPONDER: u16 = 1;
+    const REQUEST_ID: u16 = 1;
+    // the most a record can carry
+    const MAX_CONTENT: usize = 65535;
+
+    // address is host:port or unix:/path
+    pub fn run(address: &str, script: &Path, request: &Request, root_dir: &Path) -> Result<CgiOutput, HttpStatus> {
+        let params = Cgi::environment(request, script, root_dir);
+        let exchanged = match address.strip_prefix("unix:") {
+            #[cfg(unix)]
+            Some(path) => UnixStream::connect(path).and_then(|stream| {
+                stream.set_read_timeout(Some(Cgi::TIMEOUT))?;
+                Self::exchange(stream, &params, &request.body)
+            }),
+            #[cfg(not(unix))]
+            Some(_) => Err(Error::new(ErrorKind::Unsupported, "unix sockets are not supported here")),
+            None => TcpStream::connect(address).and_then(|stream| {
+                stream.set_read_timeout(Some(Cgi::TIMEOUT))?;
+                Self::exchange(stream, &params, &request.body)
+            }),
+        };
+        match exchanged {
+            Ok(output) => Cgi::parse_output(&output).ok_or_else(|| {
+                Logger::error(format!("FastCGI responder {} sent no valid headers", address).as_str());
+                HttpStatus::BadGateway
+            }),
+            Err(e) => {
+                Logger::error(format!("FastCGI responder {} failed: {}", address, e).as_str());
+                match e.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut => Err(HttpStatus::GatewayTimeout),
+                    _ => Err(HttpStatus::BadGateway),
+                }
+            }
+        }
+    }
+
+    fn exchange<S: Read + Write>(mut stream: S, params: &[(String, String)], body: &[u8]) -> Result<Vec<u8>, Error> {
+        // role, flags without FCGI_KEEP_CONN and five reserved bytes
+        let mut begin = Self::RESPONDER.to_be_bytes().to_vec();
+        begin.extend_from_slice(&[0; 6]);
+        let mut records = Self::record(Self::BEGIN_REQUEST, &begin);
+        // each stream ends with an empty record
+        for (kind, content) in [(Self::PARAMS, Self::encode_params(params)), (Self::STDIN, body.to_vec())] {
+            for chunk in content.chunks(Self::MAX_CONTENT) {
+                records.extend(Self::record(kind, chunk));
+            }
+            records.extend(Self::record(kind, &[]));
+        }
+        stream.write_all(&records)?;
+        stream.flush()?;
+
+        let mut stdout = Vec::new();
+        loop {
+            let mut header = [0; 8];
+            stream.read_exact(&mut header)?;
+            let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+            let mut content = vec![0; length + header[6] as usize];
+            stream.read_exact(&mut content)?;
+            content.truncate(length);
+            match header[1] {
+                Self::STDOUT => stdout.extend_from_slice(&content),
+                Self::STDERR => {
+                    let errors = String::from_utf8_lossy(&content);
+                    for line in errors.lines().filter(|line| !line.trim().is_empty()) {
+                        Logger::warn(format!("FastCGI: {}", line).as_str());
+                    }
+                }
+                Self::END_REQUEST => return Ok(stdout),
+                _ => {}
+            }
+        }
+    }
+
+    // name-value pairs, each length in one byte below 128 and in four with the high bit set
+    // otherwise
+    pub fn encode_params(params: &[(String, String)]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        let length = |encoded: &mut Vec<u8>, length: usize| match length {
+            0..=127 => encoded.push(length as u8),
+            _ => encoded.extend_from_slice(&(length as u32 | 0x8000_0000).to_be_bytes()),
+        };
+        for (name, value) in params {
+            length(&mut encoded, name.len());
+            length(&mut encoded, value.len());
+            encoded.extend_from_slice(name.as_bytes());
+            encoded.extend_from_slice(value.as_bytes());
+        }
+        encoded
+    }
+
+    fn record(kind: u8, content: &[u8]) -> Vec<u8> {
+        let mut record = vec![Self::VERSION, kind];
+        record.extend_from_slice(&Self::REQUEST_ID.to_be_bytes());
+        record.extend_from_slice(&(content.len() as u16).to_be_bytes());
+        // no padding, one reserved byte
+        record.extend_from_slice(&[0, 0]);
+        record.extend_from_slice(content);
+        record
+    }
+}
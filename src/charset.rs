@@ -0,0 +1,89 @@
+// what the bytes of a text file most likely are, told by a byte order mark or, without one,
+// by whether the start of the file is valid UTF-8. Anything else is taken for Latin-1, which
+// every byte sequence is, so old ISO-8859-1 and Windows-1252 pages come out right
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Charset {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+// --charset picks what is done with text/* files:
+//
+//   detect     the charset parameter is added to the Content-Type
+//   transcode  files that are not UTF-8 are also sent converted to UTF-8, up to the size
+//              read at once, bigger ones are only labelled
+//   off        the Content-Type is left as it is
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CharsetMode {
+    #[default]
+    Detect,
+    Transcode,
+    Off,
+}
+
+impl Charset {
+    // bytes looked at, enough for the head of a page and cheap to read on every request
+    pub const SAMPLE_SIZE: usize = 4096;
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Charset::Utf8 => "utf-8",
+            Charset::Utf16Le => "utf-16le",
+            Charset::Utf16Be => "utf-16be",
+            Charset::Latin1 => "iso-8859-1",
+        }
+    }
+
+    // the sample may end in the middle of a character, only a complete invalid sequence counts
+    pub fn detect(sample: &[u8]) -> Self {
+        if sample.starts_with(b"\xEF\xBB\xBF") {
+            return Charset::Utf8;
+        }
+        if sample.starts_with(b"\xFF\xFE") {
+            return Charset::Utf16Le;
+        }
+        if sample.starts_with(b"\xFE\xFF") {
+            return Charset::Utf16Be;
+        }
+        match std::str::from_utf8(sample) {
+            Ok(_) => Charset::Utf8,
+            Err(e) if e.error_len().is_none() && sample.len() - e.valid_up_to() < 4 => Charset::Utf8,
+            Err(_) => Charset::Latin1,
+        }
+    }
+
+    // the whole content as UTF-8, the byte order mark of UTF-16 dropped; unpaired surrogates
+    // and an odd trailing byte become U+FFFD
+    pub fn to_utf8(&self, bytes: &[u8]) -> Vec<u8> {
+        match self {
+            Charset::Utf8 => bytes.to_vec(),
+            Charset::Latin1 => bytes.iter().map(|b| *b as char).collect::<String>().into_bytes(),
+            Charset::Utf16Le | Charset::Utf16Be => {
+                let bytes = bytes.get(2..).unwrap_or_default();
+                let units = bytes.chunks(2).map(|pair| match (pair, self) {
+                    ([low, high], Charset::Utf16Le) => u16::from_le_bytes([*low, *high]),
+                    ([high, low], _) => u16::from_be_bytes([*high, *low]),
+                    _ => 0xFFFD,
+                });
+                char::decode_utf16(units)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect::<String>()
+                    .into_bytes()
+            }
+        }
+    }
+}
+
+impl CharsetMode {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "detect" => Some(CharsetMode::Detect),
+            "transcode" => Some(CharsetMode::Transcode),
+            "off" => Some(CharsetMode::Off),
+            _ => None,
+        }
+    }
+}
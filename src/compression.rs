@@ -0,0 +1,363 @@
+use crate::filetype::FileType;
+use crate::ignore::Ignore;
+use crate::utils::Utils;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+// which responses are worth compressing: tiny bodies grow with the gzip framing and images,
+// archives or video are already compressed, so both only cost CPU
+#[derive(Debug, Clone)]
+pub struct Compression {
+    pub level: u32,
+    pub min_size: usize,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+// what `katana precompress` did: sidecars written, and ones left as they were because they
+// were not older than their file
+#[derive(Debug, Default, PartialEq)]
+pub struct Precompressed {
+    pub written: usize,
+    pub fresh: usize,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            level: Self::DEFAULT_LEVEL,
+            min_size: Self::DEFAULT_MIN_SIZE,
+            include: Self::DEFAULT_TYPES.iter().map(|t| t.to_string()).collect(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl Compression {
+    pub const DEFAULT_LEVEL: u32 = 6;
+    pub const MAX_LEVEL: u32 = 9;
+    pub const DEFAULT_MIN_SIZE: usize = 1024; // 1KB
+    pub const SIDECAR_SUFFIX: &'static str = ".gz";
+    pub const DEFAULT_TYPES: &'static [&'static str] = &[
+        "text/*",
+        "application/json",
+        "application/javascript",
+        "application/xml",
+        "application/wasm",
+        "image/svg+xml",
+    ];
+
+    pub fn enabled(&self) -> bool {
+        self.level > 0
+    }
+
+    // content types match exactly or by a type/* wildcard, parameters such as charset are ignored
+    pub fn matches(patterns: &[String], content_type: &str) -> bool {
+        let essence = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+        patterns.iter().any(|pattern| {
+            let pattern = pattern.trim().to_lowercase();
+            match pattern.strip_suffix("/*") {
+                Some(kind) => essence.split_once('/').is_some_and(|(main, _)| main == kind),
+                None => pattern == "*" || pattern == essence,
+            }
+        })
+    }
+
+    pub fn should_compress(&self, content_type: &str, size: usize) -> bool {
+        self.enabled()
+            && size >= self.min_size
+            && Self::matches(&self.include, content_type)
+            && !Self::matches(&self.exclude, content_type)
+    }
+
+    // RFC 1952 member holding a single fixed-Huffman DEFLATE block
+    pub fn gzip(data: &[u8], level: u32) -> Vec<u8> {
+        let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+        out.extend(Deflate::compress(data, level));
+        out.extend_from_slice(&Self::crc32(data).to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out
+    }
+
+    pub fn crc32(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for byte in data {
+            crc ^= *byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+            }
+        }
+        !crc
+    }
+
+    // the sidecar written by `katana precompress` next to a file, used while it is not older
+    // than the file
+    pub fn sidecar(path: &Path) -> Option<PathBuf> {
+        let sidecar = Self::sidecar_path(path);
+        let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        (modified(&sidecar)? >= modified(path)?).then_some(sidecar)
+    }
+
+    fn sidecar_path(path: &Path) -> PathBuf {
+        let mut name = OsString::from(path.as_os_str());
+        name.push(Self::SIDECAR_SUFFIX);
+        PathBuf::from(name)
+    }
+
+    // gzips once every file under dir that would be compressed when served, hidden and
+    // ignored ones left out; a sidecar no smaller than its file is not written
+    pub fn precompress(&self, dir: &Path, ignore: &Ignore) -> Result<Precompressed, Error> {
+        let mut report = Precompressed::default();
+        self.precompress_dir(dir, dir, ignore, &mut report)?;
+        Ok(report)
+    }
+
+    fn precompress_dir(&self, root: &Path, dir: &Path, ignore: &Ignore, report: &mut Precompressed) -> Result<(), Error> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let relative = path.strip_prefix(root).map(|relative| relative.to_string_lossy().into_owned()).unwrap_or_default();
+            let is_dir = path.is_dir();
+            if !Utils::is_valid_entry(name) || ignore.is_ignored(&relative, is_dir) {
+                continue;
+            }
+            if is_dir {
+                self.precompress_dir(root, &path, ignore, report)?;
+                continue;
+            }
+
+            let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+            let content_type = FileType::from_extension(extension).map_or("application/octet-stream".to_string(), |file_type| file_type.content_type);
+            let size = fs::metadata(&path)?.len() as usize;
+            if name.ends_with(Self::SIDECAR_SUFFIX) || !self.should_compress(&content_type, size) {
+                continue;
+            }
+            if Self::sidecar(&path).is_some() {
+                report.fresh += 1;
+                continue;
+            }
+            let data = fs::read(&path)?;
+            let gzipped = Self::gzip(&data, self.level);
+            if gzipped.len() < data.len() {
+                fs::write(Self::sidecar_path(&path), gzipped)?;
+                report.written += 1;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn parse_level(value: &str) -> Option<u32> {
+        value.trim().parse::<u32>().ok().filter(|level| *level <= Self::MAX_LEVEL)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentEncoding {
+    Gzip,
+    Identity,
+}
+
+impl ContentEncoding {
+    // in order of preference when the client weighs several the same
+    pub const SUPPORTED: &'static [ContentEncoding] = &[ContentEncoding::Gzip, ContentEncoding::Identity];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Identity => "identity",
+        }
+    }
+
+    // RFC 9110 12.5.3: the supported coding with the highest weight, None when the client
+    // refused every one of them, identity included
+    pub fn negotiate(accept_encoding: Option<&str>) -> Option<ContentEncoding> {
+        if accept_encoding.is_none() {
+            return Some(ContentEncoding::Identity);
+        }
+        let mut best: Option<(ContentEncoding, f32)> = None;
+        for encoding in Self::SUPPORTED {
+            let q = Self::weight(accept_encoding, encoding.as_str());
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((*encoding, q));
+            }
+        }
+        best.map(|(encoding, _)| encoding)
+    }
+
+    // "gzip;q=0.8", a missing weight is 1 and an unreadable one drops the entry
+    // the q the client gives a coding, by name or through *; identity is acceptable unless
+    // refused, anything else needs to be asked for
+    pub fn weight(accept_encoding: Option<&str>, coding: &str) -> f32 {
+        let is_named = |name: &str| {
+            name.eq_ignore_ascii_case(coding) || (coding.eq_ignore_ascii_case("gzip") && name.eq_ignore_ascii_case("x-gzip"))
+        };
+        let weights: Vec<(&str, f32)> = accept_encoding.unwrap_or_default().split(',').filter_map(Self::parse_weight).collect();
+        let named = weights.iter().find(|(name, _)| is_named(name));
+        let wildcard = weights.iter().find(|(name, _)| *name == "*");
+        match (named, wildcard) {
+            (Some((_, q)), _) | (None, Some((_, q))) => *q,
+            (None, None) if coding.eq_ignore_ascii_case("identity") => 1.0,
+            (None, None) => 0.0,
+        }
+    }
+
+    fn parse_weight(entry: &str) -> Option<(&str, f32)> {
+        let mut parts = entry.split(';').map(str::trim);
+        let coding = parts.next().filter(|coding| !coding.is_empty())?;
+        let mut q = 1.0;
+        for parameter in parts {
+            if let Some((name, value)) = parameter.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("q") {
+                    q = value.trim().parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q))?;
+                }
+            }
+        }
+        Some((coding, q))
+    }
+}
+
+// LZ77 over a 32KB window with hash chains, the level bounds how many earlier positions are
+// tried for each match (RFC 1951)
+struct Deflate {
+    out: Vec<u8>,
+    bits: u64,
+    bit_count: u32,
+}
+
+impl Deflate {
+    const WINDOW: usize = 32768;
+    const MIN_MATCH: usize = 3;
+    const MAX_MATCH: usize = 258;
+    const HASH_BITS: u32 = 15;
+    const CHAIN_LIMITS: [usize; 10] = [0, 4, 8, 16, 32, 64, 128, 256, 1024, 4096];
+
+    const LENGTH_BASE: [u16; 29] = [
+        3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+    ];
+    const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+    const DISTANCE_BASE: [u16; 30] = [
+        1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+        6145, 8193, 12289, 16385, 24577,
+    ];
+    const DISTANCE_EXTRA: [u8; 30] = [
+        0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+    ];
+
+    fn compress(data: &[u8], level: u32) -> Vec<u8> {
+        let mut deflate = Deflate { out: Vec::with_capacity(data.len() / 2), bits: 0, bit_count: 0 };
+        deflate.write_bits(1, 1); // final block
+        deflate.write_bits(1, 2); // fixed Huffman codes
+
+        let chain_limit = Self::CHAIN_LIMITS[level.min(Compression::MAX_LEVEL) as usize];
+        let mut head = vec![usize::MAX; 1 << Self::HASH_BITS];
+        let mut previous = vec![usize::MAX; data.len()];
+        let hash = |at: usize| {
+            let value = (data[at] as u32) << 16 | (data[at + 1] as u32) << 8 | data[at + 2] as u32;
+            (value.wrapping_mul(2654435761) >> (32 - Self::HASH_BITS)) as usize
+        };
+        let insert = |at: usize, head: &mut [usize], previous: &mut [usize]| {
+            if at + Self::MIN_MATCH <= data.len() {
+                let h = hash(at);
+                previous[at] = head[h];
+                head[h] = at;
+            }
+        };
+
+        let mut position = 0;
+        while position < data.len() {
+            let (length, distance) = Self::longest_match(data, position, &head, &previous, chain_limit, hash);
+            if length >= Self::MIN_MATCH {
+                deflate.write_match(length, distance);
+                for at in position..position + length {
+                    insert(at, &mut head, &mut previous);
+                }
+                position += length;
+            } else {
+                deflate.write_literal(data[position] as u16);
+                insert(position, &mut head, &mut previous);
+                position += 1;
+            }
+        }
+        deflate.write_literal(256); // end of block
+        if deflate.bit_count > 0 {
+            deflate.out.push(deflate.bits as u8);
+        }
+        deflate.out
+    }
+
+    fn longest_match(
+        data: &[u8],
+        position: usize,
+        head: &[usize],
+        previous: &[usize],
+        chain_limit: usize,
+        hash: impl Fn(usize) -> usize,
+    ) -> (usize, usize) {
+        if chain_limit == 0 || position + Self::MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+        let max_length = Self::MAX_MATCH.min(data.len() - position);
+        let (mut best_length, mut best_distance) = (0, 0);
+        let mut candidate = head[hash(position)];
+        for _ in 0..chain_limit {
+            if candidate == usize::MAX || position - candidate > Self::WINDOW {
+                break;
+            }
+            let length = data[candidate..]
+                .iter()
+                .zip(&data[position..position + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best_length {
+                best_length = length;
+                best_distance = position - candidate;
+                if length == max_length {
+                    break;
+                }
+            }
+            candidate = previous[candidate];
+        }
+        (best_length, best_distance)
+    }
+
+    fn write_literal(&mut self, value: u16) {
+        // RFC 1951 3.2.6
+        let (code, length) = match value {
+            0..=143 => (0x30 + value, 8),
+            144..=255 => (0x190 + value - 144, 9),
+            256..=279 => (value - 256, 7),
+            _ => (0xc0 + value - 280, 8),
+        };
+        self.write_code(code, length);
+    }
+
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let index = Self::LENGTH_BASE.iter().rposition(|base| *base as usize <= length).unwrap_or(0);
+        self.write_literal(257 + index as u16);
+        self.write_bits((length - Self::LENGTH_BASE[index] as usize) as u32, Self::LENGTH_EXTRA[index] as u32);
+
+        let index = Self::DISTANCE_BASE.iter().rposition(|base| *base as usize <= distance).unwrap_or(0);
+        self.write_code(index as u16, 5);
+        self.write_bits((distance - Self::DISTANCE_BASE[index] as usize) as u32, Self::DISTANCE_EXTRA[index] as u32);
+    }
+
+    // Huffman codes are packed starting from their most significant bit
+    fn write_code(&mut self, code: u16, length: u32) {
+        let reversed = (code.reverse_bits() >> (16 - length)) as u32;
+        self.write_bits(reversed, length);
+    }
+
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+}
@@ -1,18 +1,273 @@
-use crate::logger::Logger;
+#[cfg(feature = "embed")]
+use crate::bundle::Bundle;
+use crate::bench::Bench;
+use crate::charset::CharsetMode;
+use crate::cidr::Cidr;
+use crate::compression::Compression;
+use crate::connection::Listener;
+use crate::etag::ETag;
+use crate::handler::Handlers;
+use crate::hooks::Hooks;
+use crate::hotlink::Hotlink;
+use crate::http::HttpMethod;
+use crate::ignore::Ignore;
+use crate::jwt::{JwtAuth, JwtKey};
+use crate::language::Language;
+use crate::location::Location;
+use crate::logger::{LogFormat, LogLevel, Logger};
+use crate::maintenance::Maintenance;
+use crate::mdns::Mdns;
+use crate::plugin::WasmModule;
+use crate::privileges::Privileges;
+use crate::proxy::{Balance, ForwardProxy, Proxy};
+use crate::redirect::Redirects;
+use crate::response::Response;
+use crate::secret::Secret;
+use crate::server::Server;
+use crate::service::{Service, ServiceAction};
+use crate::signed::SignedUrls;
+use crate::syslog::Syslog;
+use crate::templates::Templates;
+use crate::tls::{Certificate, ClientAuth, TlsPolicy, TlsVersion};
+use crate::tus::Tus;
+use crate::userdir::UserDir;
+use crate::utils::Utils;
+use crate::vhost::VirtualHost;
+use crate::wellknown::{Favicon, Robots, SecurityTxt};
+use std::collections::HashMap;
 use std::env::args;
+use std::fs;
+use std::net::IpAddr;
 use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub host: String,
     pub port: u16,
+    pub port_retry: u16,
+    pub listen: Vec<String>,
+    pub socket_mode: Option<u32>,
     pub root_dir: PathBuf,
+    // set by code embedding Katana, the bundle is served instead of root_dir
+    #[cfg(feature = "embed")]
+    pub bundle: Option<&'static Bundle>,
+    pub default_language: Option<String>,
+    pub locale: Option<String>,
+    pub templates_dir: Option<PathBuf>,
+    // --messages, by language
+    pub messages: Vec<(String, HashMap<String, String>)>,
     pub worker: i32,
+    pub reuse_port: bool,
+    pub keep_alive_timeout: u64,
+    pub max_requests: usize,
+    pub max_body_size: usize,
+    pub allowed_methods: Vec<HttpMethod>,
+    pub writable: bool,
+    pub read_only: bool,
+    pub userdir: UserDir,
+    pub listing: bool,
+    pub listing_page_size: usize,
+    pub etag: ETag,
+    pub charset: CharsetMode,
+    pub checksums: bool,
+    pub headers: Vec<(String, String)>,
+    pub spa_fallback: Option<String>,
+    pub inject_head: Option<String>,
+    pub inject_body: Option<String>,
+    pub inject_files: bool,
+    pub ignore: Ignore,
+    pub handlers: Handlers,
+    pub proxy: Proxy,
+    pub forward_proxy: ForwardProxy,
+    pub locations: Vec<Location>,
+    pub vhosts: Vec<VirtualHost>,
+    pub compression: Compression,
+    pub trusted_proxies: Vec<Cidr>,
+    pub method_override: Vec<Cidr>,
+    pub config_file: Option<PathBuf>,
+    pub watch_config: bool,
+    pub watch: bool,
+    pub daemon: bool,
+    pub container: bool,
+    pub service: Service,
+    pub privileges: Privileges,
+    #[cfg(feature = "landlock")]
+    pub sandbox: bool,
+    pub pid_file: PathBuf,
+    pub log_file: PathBuf,
+    pub log_format: LogFormat,
+    pub log_level: LogLevel,
+    pub quiet: bool,
+    pub no_color: bool,
+    pub no_qr: bool,
+    pub mdns: bool,
+    pub mdns_name: String,
+    pub tls_certs: Vec<Certificate>,
+    pub client_ca: Option<PathBuf>,
+    pub client_auth: ClientAuth,
+    pub ocsp_stapling: bool,
+    pub tls_policy: TlsPolicy,
+    pub https_redirect: bool,
+    pub https_port: u16,
+    pub jwt: JwtAuth,
+    pub signed_urls: SignedUrls,
+    pub hotlink: Hotlink,
+    pub redirects: Redirects,
+    pub robots: Option<Robots>,
+    pub favicon: Favicon,
+    pub security_txt: SecurityTxt,
+    pub sign_path: Option<String>,
+    pub precompress: bool,
+    pub init: bool,
+    pub init_templates: bool,
+    pub check: bool,
+    pub version: bool,
+    pub build_header: bool,
+    pub bench: Bench,
+    pub grace_period: Duration,
+    pub sign_lifetime: Duration,
+    pub maintenance: Maintenance,
+    pub tus: Tus,
+    pub status_page: bool,
+    pub plugins_dir: Option<PathBuf>,
+    pub hooks: Hooks,
+    pub admin_listen: Option<String>,
+    pub admin_token: Option<String>,
+    pub acme_dir: Option<PathBuf>,
+    pub acme_domains: Vec<String>,
+    pub error_log: Option<PathBuf>,
+    pub slow_request: Option<Duration>,
+    pub syslog: Option<String>,
+    pub syslog_facility: u8,
+    #[cfg(feature = "otel")]
+    pub otlp_endpoint: Option<String>,
+    pub args: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: if cfg!(target_family = "windows") {
+                "127.0.0.1".to_string()
+            } else {
+                "0.0.0.0".to_string()
+            },
+            port: 8080,
+            port_retry: 0,
+            listen: Vec::new(),
+            socket_mode: None,
+            root_dir: PathBuf::from("public"),
+            #[cfg(feature = "embed")]
+            bundle: None,
+            default_language: None,
+            locale: None,
+            templates_dir: None,
+            messages: Vec::new(),
+            worker: 4,
+            reuse_port: false,
+            keep_alive_timeout: Self::DEFAULT_KEEP_ALIVE_TIMEOUT,
+            max_requests: Self::DEFAULT_MAX_REQUESTS,
+            max_body_size: Self::DEFAULT_MAX_BODY_SIZE,
+            allowed_methods: Self::DEFAULT_ALLOWED_METHODS.to_vec(),
+            writable: false,
+            read_only: true,
+            userdir: UserDir::default(),
+            listing: true,
+            listing_page_size: Response::LISTING_PAGE_SIZE,
+            etag: ETag::default(),
+            charset: CharsetMode::default(),
+            checksums: false,
+            headers: Vec::new(),
+            spa_fallback: None,
+            inject_head: None,
+            inject_body: None,
+            inject_files: false,
+            ignore: Ignore::default(),
+            handlers: Handlers::default(),
+            proxy: Proxy::default(),
+            forward_proxy: ForwardProxy::default(),
+            locations: Vec::new(),
+            vhosts: Vec::new(),
+            compression: Compression::default(),
+            trusted_proxies: Vec::new(),
+            method_override: Vec::new(),
+            config_file: None,
+            watch_config: false,
+            watch: false,
+            daemon: false,
+            container: false,
+            service: Service::default(),
+            privileges: Privileges::default(),
+            #[cfg(feature = "landlock")]
+            sandbox: false,
+            pid_file: PathBuf::from("katana.pid"),
+            log_file: PathBuf::from("katana.log"),
+            log_format: LogFormat::Plain,
+            log_level: LogLevel::INFO,
+            quiet: false,
+            no_color: false,
+            no_qr: false,
+            mdns: false,
+            mdns_name: Mdns::DEFAULT_INSTANCE.to_string(),
+            tls_certs: Vec::new(),
+            client_ca: None,
+            client_auth: ClientAuth::Off,
+            ocsp_stapling: false,
+            tls_policy: TlsPolicy::default(),
+            https_redirect: false,
+            https_port: 443,
+            jwt: JwtAuth::default(),
+            signed_urls: SignedUrls::default(),
+            hotlink: Hotlink::default(),
+            redirects: Redirects::default(),
+            robots: None,
+            favicon: Favicon::BuiltIn,
+            security_txt: SecurityTxt::default(),
+            sign_path: None,
+            precompress: false,
+            init: false,
+            init_templates: false,
+            check: false,
+            version: false,
+            build_header: false,
+            bench: Bench::default(),
+            grace_period: Self::DEFAULT_GRACE_PERIOD,
+            sign_lifetime: SignedUrls::DEFAULT_LIFETIME,
+            maintenance: Maintenance::default(),
+            tus: Tus::default(),
+            status_page: false,
+            plugins_dir: None,
+            hooks: Hooks::default(),
+            admin_listen: None,
+            admin_token: None,
+            acme_dir: None,
+            acme_domains: Vec::new(),
+            error_log: None,
+            slow_request: None,
+            syslog: None,
+            syslog_facility: Syslog::DEFAULT_FACILITY,
+            #[cfg(feature = "otel")]
+            otlp_endpoint: None,
+            args: Vec::new(),
+        }
+    }
 }
 
 impl Config {
     pub const MIN_WORKER: i32 = 1;
     pub const CHUNK_SIZE: usize = 8192;
+    pub const DEFAULT_KEEP_ALIVE_TIMEOUT: u64 = 5; // seconds
+    pub const DEFAULT_MAX_REQUESTS: usize = 100;
+    pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(10);
+    // well within the 10s Docker and the 30s Kubernetes wait before they kill the process
+    pub const CONTAINER_GRACE_PERIOD: Duration = Duration::from_secs(5);
+    // sysexits(3), next to those of BindError
+    pub const EXIT_CONFIG: i32 = 78; // EX_CONFIG
+    pub const DEFAULT_MAX_BODY_SIZE: usize = 10485760; // 10MB
+    pub const DEFAULT_ALLOWED_METHODS: &'static [HttpMethod] = &[HttpMethod::GET, HttpMethod::HEAD, HttpMethod::OPTIONS];
 
     pub fn load_args() -> Self {
         let env_args: Vec<String> = args().collect();
@@ -20,58 +275,1319 @@ impl Config {
     }
 
     pub fn parse_args(args: Vec<String>) -> Self {
-        let mut host = if cfg!(target_family = "windows") {
-            "127.0.0.1".to_string()
+        let (config, errors) = Self::parse(args);
+        // `katana check` reports them along with its own findings
+        if !config.check {
+            // the server sets up logging later, these already belong to the container's log
+            if config.container {
+                Logger::set_format(config.log_format);
+                Logger::set_stdout_only(true);
+            }
+            for error in &errors {
+                Logger::error(error);
+            }
+            // a container serving half its configuration looks healthy, one that exits shows
+            // up in the orchestrator's restarts
+            if config.container && !errors.is_empty() {
+                process::exit(Self::EXIT_CONFIG);
+            }
+        }
+        config
+    }
+
+    // what parsing cannot tell: files that are missing or unreadable, certificates and keys
+    // that are not PEM, plugins that are not WebAssembly and listeners given twice. Servers
+    // start without these checks, one missing file only fails the requests that need it
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let pem = |path: &PathBuf, label: &str, problems: &mut Vec<String>| match fs::read_to_string(path) {
+            Ok(content) if content.contains("-----BEGIN ") && content.contains(label) => {}
+            Ok(_) => problems.push(format!("{} has no PEM {}", path.display(), label.trim_end_matches('-'))),
+            Err(e) => problems.push(format!("cannot read {}: {}", path.display(), e)),
+        };
+        let readable = |path: &PathBuf, problems: &mut Vec<String>| {
+            if let Err(e) = fs::read(path) {
+                problems.push(format!("cannot read {}: {}", path.display(), e));
+            }
+        };
+
+        #[cfg(feature = "embed")]
+        let embedded = self.bundle.is_some();
+        #[cfg(not(feature = "embed"))]
+        let embedded = false;
+        if !embedded && !self.root_dir.is_dir() {
+            problems.push(format!("root directory not found: {}", self.root_dir.display()));
+        }
+        for cert in &self.tls_certs {
+            pem(&cert.cert, "CERTIFICATE-----", &mut problems);
+            pem(&cert.key, "PRIVATE KEY-----", &mut problems);
+        }
+        if let Some(client_ca) = &self.client_ca {
+            pem(client_ca, "CERTIFICATE-----", &mut problems);
+        }
+        if let Some(page) = &self.maintenance.page {
+            readable(page, &mut problems);
+        }
+        if let Some(Robots::File(path)) = &self.robots {
+            readable(path, &mut problems);
+        }
+        if let Favicon::File(path) = &self.favicon {
+            readable(path, &mut problems);
+        }
+        if let Some(fallback) = &self.spa_fallback {
+            let path = self.root_dir.join(fallback.trim_start_matches('/'));
+            if !embedded && !path.is_file() {
+                problems.push(format!("SPA fallback not found: {}", path.display()));
+            }
+        }
+        if let Some(dir) = &self.plugins_dir {
+            if !dir.is_dir() {
+                problems.push(format!("plugins directory not found: {}", dir.display()));
+            }
+            for path in WasmModule::discover(dir) {
+                if let Err(e) = fs::read(&path).map_err(|e| e.to_string()).and_then(|bytes| WasmModule::hooks(&bytes)) {
+                    problems.push(format!("invalid plugin {}: {}", path.display(), e));
+                }
+            }
+        }
+
+        let mut listen = self.listen.clone();
+        listen.sort();
+        for pair in listen.windows(2).filter(|pair| pair[0] == pair[1]) {
+            problems.push(format!("{} is listened on twice", pair[0]));
+        }
+        if self.admin_listen.as_ref().is_some_and(|admin| self.listen.contains(admin)) {
+            problems.push("the admin API cannot share a listen address with the site".to_string());
+        }
+
+        // sites inherit the global options, their problems are only told once
+        let global = problems.clone();
+        for vhost in &self.vhosts {
+            for problem in vhost.config.check().into_iter().filter(|problem| !global.contains(problem)) {
+                problems.push(format!("virtual host {}: {}", vhost.names.join(","), problem));
+            }
+        }
+        problems
+    }
+
+    // re-read the same command line (and the config file it points to), used on reload
+    pub fn reload(&self) -> Result<Self, Vec<String>> {
+        let (config, errors) = Self::parse(self.args.clone());
+        if errors.is_empty() {
+            Ok(config)
         } else {
-            "0.0.0.0".to_string()
+            Err(errors)
+        }
+    }
+
+    pub fn parse(args: Vec<String>) -> (Self, Vec<String>) {
+        let mut config = Self {
+            args: args.clone(),
+            ..Self::default()
         };
-        let mut port = 8080;
-        let mut root_dir = PathBuf::from("public");
-        let mut worker = 4;
+        let mut errors = Vec::new();
+
+        // options from the config file come first so the command line can override them
+        let mut all_args = vec![args.first().cloned().unwrap_or_default()];
+        let mut i = 1;
+        while i < args.len() {
+            if args[i] == "--config" && i + 1 < args.len() {
+                let path = PathBuf::from(&args[i + 1]);
+                match Self::read_file(&path) {
+                    Ok(file_args) => all_args.extend(file_args),
+                    Err(error) => errors.push(error),
+                }
+                config.config_file = Some(path);
+            }
+            i += 1;
+        }
+        all_args.extend(args.into_iter().skip(1));
+        let args = all_args;
 
+        let mut compress_types_given = false;
+        let mut log_format_given = false;
+        let mut grace_period_given = false;
+        let mut tls_min_version = None;
+        let mut tls_ciphers = Vec::new();
+        let mut tls_curves = Vec::new();
+        let mut tls_policy_given = false;
+        let mut locations = Vec::new();
+        let mut vhosts = Vec::new();
+        let mut ignore_patterns = Vec::new();
         let mut i = 1;
         while i < args.len() {
             match args[i].as_str() {
-                "--port" => {
-                    if i + 1 < args.len() {
-                        port = args[i + 1].parse().unwrap_or(8080);
-                        i += 1;
+                "--config" if i + 1 < args.len() => {
+                    i += 1;
+                }
+                "--watch-config" => {
+                    config.watch_config = true;
+                }
+                "--watch" => {
+                    // live reload: browsers refresh when something under the root changes
+                    config.watch = true;
+                }
+                "--daemon" => {
+                    config.daemon = true;
+                }
+                "--container" => {
+                    // JSON lines on stdout, a short shutdown and exit codes orchestrators can tell apart
+                    config.container = true;
+                }
+                "service" if i + 1 < args.len() => {
+                    // `katana service install|uninstall|run`, a Windows service
+                    match ServiceAction::from_str(&args[i + 1]) {
+                        Some(action) => config.service.action = Some(action),
+                        None => errors.push(format!("unknown service action: {}, use install, uninstall or run", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--service-name" if i + 1 < args.len() => {
+                    config.service.name = args[i + 1].clone();
+                    i += 1;
+                }
+                "--service-dir" if i + 1 < args.len() => {
+                    // set by install, relative paths are resolved from there
+                    config.service.dir = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                }
+                "--user" if i + 1 < args.len() => {
+                    // a name or uid to switch to once the listeners are bound
+                    config.privileges.user = Some(args[i + 1].clone());
+                    i += 1;
+                }
+                "--group" if i + 1 < args.len() => {
+                    config.privileges.group = Some(args[i + 1].clone());
+                    i += 1;
+                }
+                "--chroot" => {
+                    // into the root directory once the listeners are bound
+                    config.privileges.chroot = true;
+                }
+                #[cfg(feature = "landlock")]
+                "--sandbox" => {
+                    // only the served files once the listeners are bound
+                    config.sandbox = true;
+                }
+                "--pid-file" if i + 1 < args.len() => {
+                    config.pid_file = PathBuf::from(&args[i + 1]);
+                    i += 1;
+                }
+                "--log-file" if i + 1 < args.len() => {
+                    config.log_file = PathBuf::from(&args[i + 1]);
+                    i += 1;
+                }
+                "--log-format" if i + 1 < args.len() => {
+                    match LogFormat::from_str(&args[i + 1]) {
+                        Some(format) => config.log_format = format,
+                        None => errors.push(format!("log format must be plain or json: {}", args[i + 1])),
                     }
+                    log_format_given = true;
+                    i += 1;
+                }
+                "--log-level" if i + 1 < args.len() => {
+                    match LogLevel::from_str(&args[i + 1]) {
+                        Some(level) => config.log_level = level,
+                        None => errors.push(format!("log level must be debug, info, warn or error: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--quiet" => {
+                    config.quiet = true;
+                }
+                "--no-color" => {
+                    config.no_color = true;
+                }
+                "--no-qr" => {
+                    config.no_qr = true;
+                }
+                "--mdns" => {
+                    config.mdns = true;
+                }
+                "--mdns-name" if i + 1 < args.len() => {
+                    // the instance name shown in service browsers, naming it implies --mdns
+                    match Mdns::instance_name(&args[i + 1]) {
+                        Some(name) => {
+                            config.mdns_name = name;
+                            config.mdns = true;
+                        }
+                        None => errors.push("mDNS name cannot be empty".to_string()),
+                    }
+                    i += 1;
+                }
+                "--tls-cert" if i + 1 < args.len() => {
+                    // repeatable, picked by the SNI hostname of each connection
+                    match Certificate::from_str(&args[i + 1]) {
+                        Some(cert) => config.tls_certs.push(cert),
+                        None => errors.push(format!("TLS certificate must be [name,...=]cert.pem,key.pem: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--client-ca" if i + 1 < args.len() => {
+                    // PEM bundle client certificates are verified against, requires them by default
+                    config.client_ca = Some(PathBuf::from(&args[i + 1]));
+                    if config.client_auth == ClientAuth::Off {
+                        config.client_auth = ClientAuth::Required;
+                    }
+                    i += 1;
+                }
+                "--client-cert" if i + 1 < args.len() => {
+                    match ClientAuth::from_str(&args[i + 1]) {
+                        Some(mode) => config.client_auth = mode,
+                        None => errors.push(format!("client certificate mode must be off, optional or required: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--ocsp-stapling" => {
+                    // the certificate authority's signed word that the certificates are not revoked,
+                    // fetched in the background and handed to clients with the chain
+                    config.ocsp_stapling = true;
+                }
+                "--tls-policy" if i + 1 < args.len() => {
+                    tls_policy_given = true;
+                    match TlsPolicy::preset(&args[i + 1]) {
+                        Some(policy) => config.tls_policy = policy,
+                        None => errors.push(format!("TLS policy must be modern or intermediate: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--tls-min-version" if i + 1 < args.len() => {
+                    tls_policy_given = true;
+                    match TlsVersion::from_str(&args[i + 1]) {
+                        Some(version) => tls_min_version = Some(version),
+                        None => errors.push(format!("TLS minimum version must be 1.2 or 1.3: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--tls-ciphers" if i + 1 < args.len() => {
+                    // repeatable, or colon separated as OpenSSL lists them, most preferred first
+                    tls_policy_given = true;
+                    for name in args[i + 1].split(':').filter(|name| !name.trim().is_empty()) {
+                        match TlsPolicy::cipher(name) {
+                            Some(cipher) if !tls_ciphers.contains(&cipher) => tls_ciphers.push(cipher),
+                            Some(_) => {}
+                            None => errors.push(format!("unknown TLS cipher suite: {}", name.trim())),
+                        }
+                    }
+                    i += 1;
+                }
+                "--tls-curves" if i + 1 < args.len() => {
+                    tls_policy_given = true;
+                    for name in args[i + 1].split(':').filter(|name| !name.trim().is_empty()) {
+                        match TlsPolicy::curve(name) {
+                            Some(curve) if !tls_curves.contains(&curve) => tls_curves.push(curve),
+                            Some(_) => {}
+                            None => errors.push(format!("unknown TLS curve: {}", name.trim())),
+                        }
+                    }
+                    i += 1;
+                }
+                "--https-redirect" => {
+                    // plain HTTP requests are sent to https://, e.g. behind a TLS terminating proxy
+                    config.https_redirect = true;
                 }
-                "--dir" => {
-                    if i + 1 < args.len() {
-                        root_dir = PathBuf::from(&args[i + 1]);
-                        i += 1;
+                "--https-port" if i + 1 < args.len() => {
+                    match args[i + 1].parse::<u16>() {
+                        Ok(port) if port > 0 => config.https_port = port,
+                        _ => errors.push(format!("invalid HTTPS port: {}", args[i + 1])),
                     }
+                    i += 1;
+                }
+                "--jwt-secret" if i + 1 < args.len() => {
+                    // HS256 shared secret, repeatable to rotate secrets without downtime
+                    match Secret::resolve(&args[i + 1]) {
+                        Ok(secret) => config.jwt.keys.push(JwtKey::Hmac(secret.into_bytes())),
+                        Err(error) => errors.push(error),
+                    }
+                    i += 1;
+                }
+                "--jwt-jwks" if i + 1 < args.len() => {
+                    // RS256 public keys from a JWK Set file, e.g. a copy of the issuer's jwks_uri
+                    match JwtKey::load_jwks(&PathBuf::from(&args[i + 1])) {
+                        Ok(keys) => config.jwt.keys.extend(keys),
+                        Err(error) => errors.push(error),
+                    }
+                    i += 1;
+                }
+                "--jwt-audience" if i + 1 < args.len() => {
+                    config.jwt.audience = Some(args[i + 1].clone());
+                    i += 1;
+                }
+                "--jwt-issuer" if i + 1 < args.len() => {
+                    config.jwt.issuer = Some(args[i + 1].clone());
+                    i += 1;
+                }
+                "--jwt-protect" if i + 1 < args.len() => {
+                    // repeatable path prefix that requires a bearer token
+                    config.jwt.protected.push(args[i + 1].clone());
+                    i += 1;
+                }
+                "--sign-secret" if i + 1 < args.len() => {
+                    // HMAC key for ?expires=...&sig=... links, a valid one also passes JWT checks
+                    match Secret::resolve(&args[i + 1]) {
+                        Ok(secret) => config.signed_urls.secret = Some(secret.into_bytes()),
+                        Err(error) => errors.push(error),
+                    }
+                    i += 1;
+                }
+                "--sign-protect" if i + 1 < args.len() => {
+                    // repeatable path prefix only reachable through a signed link
+                    config.signed_urls.protected.push(args[i + 1].clone());
+                    i += 1;
+                }
+                "--sign-expires" if i + 1 < args.len() => {
+                    match Utils::parse_duration(&args[i + 1]) {
+                        Some(lifetime) if lifetime.as_secs() > 0 => config.sign_lifetime = lifetime,
+                        _ => errors.push("signed link lifetime must be a duration such as 30m or 7d".to_string()),
+                    }
+                    i += 1;
+                }
+                "sign" if i + 1 < args.len() => {
+                    // `katana sign <path>` prints a signed link instead of starting the server
+                    config.sign_path = Some(args[i + 1].clone());
+                    i += 1;
+                }
+                "precompress" => {
+                    // `katana precompress` writes the .gz sidecars of the root instead of starting the server
+                    config.precompress = true;
+                }
+                "bench" if i + 1 < args.len() => {
+                    // `katana bench <url>` loads a running server instead of starting one
+                    config.bench.url = Some(args[i + 1].clone());
+                    i += 1;
+                }
+                "--bench-connections" if i + 1 < args.len() => {
+                    match args[i + 1].parse::<usize>() {
+                        Ok(connections) if connections > 0 => config.bench.connections = connections,
+                        _ => errors.push(format!("bench connections must be a positive number: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--bench-duration" if i + 1 < args.len() => {
+                    match Utils::parse_duration(&args[i + 1]) {
+                        Some(duration) if !duration.is_zero() => config.bench.duration = duration,
+                        _ => errors.push("bench duration must be a duration such as 10s or 1m".to_string()),
+                    }
+                    i += 1;
+                }
+                "--version" | "-V" => {
+                    // prints the version, commit, build date and features, then exits
+                    config.version = true;
+                }
+                "--build-header" => {
+                    // X-Katana-Build on every response, to tell which build a server runs
+                    config.build_header = true;
+                }
+                "check" | "-t" => {
+                    // `katana check` validates the configuration instead of starting the server
+                    config.check = true;
+                }
+                "init" => {
+                    // `katana init` writes a commented katana.toml into the current directory
+                    config.init = true;
+                }
+                "--with-templates" => {
+                    // init also copies the built-in pages into templates/
+                    config.init_templates = true;
+                }
+                "--hotlink-protect" if i + 1 < args.len() => {
+                    // extensions such as jpg,png,mp4 only served to pages of this site
+                    config.hotlink.extensions.extend(
+                        args[i + 1]
+                            .split(',')
+                            .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+                            .filter(|extension| !extension.is_empty()),
+                    );
+                    i += 1;
+                }
+                "--hotlink-allow" if i + 1 < args.len() => {
+                    // repeatable, other sites whose pages may embed them
+                    config.hotlink.allowed.push(args[i + 1].trim().trim_end_matches('.').to_lowercase());
+                    i += 1;
+                }
+                "--hotlink-block-empty" => {
+                    config.hotlink.block_empty = true;
+                }
+                "--hotlink-redirect" if i + 1 < args.len() => {
+                    config.hotlink.redirect = Some(args[i + 1].clone());
+                    i += 1;
+                }
+                "--redirect" if i + 1 < args.len() => {
+                    // repeatable, `FROM TO [STATUS]` e.g. `/blog/* /posts/{rest} 308`
+                    match Redirects::parse(&args[i + 1]) {
+                        Ok(redirect) => config.redirects.rules.push(redirect),
+                        Err(e) => errors.push(e),
+                    }
+                    i += 1;
+                }
+                "--maintenance" => {
+                    config.maintenance.enabled = true;
+                }
+                "--maintenance-page" if i + 1 < args.len() => {
+                    // HTML served with the 503, the error page is used when it cannot be read
+                    config.maintenance.page = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                }
+                "--retry-after" if i + 1 < args.len() => {
+                    match args[i + 1].parse::<u64>() {
+                        Ok(seconds) => config.maintenance.retry_after = seconds,
+                        Err(_) => errors.push("retry after must be a number of seconds".to_string()),
+                    }
+                    i += 1;
+                }
+                "--header" if i + 1 < args.len() => {
+                    // repeatable, added to every response
+                    match Self::parse_header(&args[i + 1]) {
+                        Some(header) => config.headers.push(header),
+                        None => errors.push(format!("header must be `Name: value`: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--listing" => {
+                    // back on for a location when the site turned it off
+                    config.listing = true;
+                }
+                "--no-listing" => {
+                    // directories without an index.html are refused instead of listed
+                    config.listing = false;
+                }
+                "--listing-page-size" if i + 1 < args.len() => {
+                    // entries per listing page, bigger directories get ?page= links
+                    match args[i + 1].parse::<usize>() {
+                        Ok(size) if size > 0 => config.listing_page_size = size,
+                        _ => errors.push(format!("listing page size must be a positive number: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--etag" if i + 1 < args.len() => {
+                    // weak (size and mtime), strong (content hash) or off
+                    match ETag::from_str(&args[i + 1]) {
+                        Some(etag) => config.etag = etag,
+                        None => errors.push(format!("etag must be weak, strong or off: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--charset" if i + 1 < args.len() => {
+                    // detect (label text files), transcode (and convert them to UTF-8) or off
+                    match CharsetMode::from_str(&args[i + 1]) {
+                        Some(charset) => config.charset = charset,
+                        None => errors.push(format!("charset must be detect, transcode or off: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--checksums" => {
+                    // ?checksum=sha256 and file.sha256 answer with the digest of the file
+                    config.checksums = true;
+                }
+                "--spa-fallback" if i + 1 < args.len() => {
+                    // e.g. /index.html, answers GET requests for missing paths without an extension
+                    // so client-side routes of a single page app survive a reload
+                    config.spa_fallback = Some(format!("/{}", args[i + 1].trim_start_matches('/')));
+                    i += 1;
+                }
+                "--inject-head" | "--inject-body" if i + 1 < args.len() => {
+                    // an HTML file added before </head> or </body> of listings and error pages,
+                    // e.g. custom CSS, a banner or analytics
+                    match fs::read_to_string(&args[i + 1]) {
+                        Ok(snippet) if args[i] == "--inject-head" => config.inject_head = Some(snippet),
+                        Ok(snippet) => config.inject_body = Some(snippet),
+                        Err(e) => errors.push(format!("cannot read {}: {}", args[i + 1], e)),
+                    }
+                    i += 1;
+                }
+                "--inject-files" => {
+                    // served .html files get the snippets too
+                    config.inject_files = true;
+                }
+                "--ignore" if i + 1 < args.len() => {
+                    // repeatable .gitignore pattern, e.g. node_modules/ or *.log, read before
+                    // the .katanaignore of the root
+                    ignore_patterns.push(args[i + 1].clone());
+                    i += 1;
+                }
+                "--handler" if i + 1 < args.len() => {
+                    // repeatable ext=static|cgi|fastcgi:address|deny, e.g. php=fastcgi:127.0.0.1:9000
+                    if let Err(e) = config.handlers.add(&args[i + 1]) {
+                        errors.push(e);
+                    }
+                    i += 1;
+                }
+                "--proxy" if i + 1 < args.len() => {
+                    // repeatable upstream, requests are balanced between them
+                    if let Err(e) = config.proxy.add(&args[i + 1]) {
+                        errors.push(e);
+                    }
+                    i += 1;
+                }
+                "--proxy-balance" if i + 1 < args.len() => {
+                    match Balance::from_str(&args[i + 1]) {
+                        Some(balance) => config.proxy.balance = balance,
+                        None => errors.push(format!("proxy balance must be round-robin or least-connections: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--proxy-max-fails" if i + 1 < args.len() => {
+                    match args[i + 1].parse::<u32>() {
+                        Ok(max_fails) if max_fails > 0 => config.proxy.max_fails = max_fails,
+                        _ => errors.push(format!("proxy max fails must be a positive number: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--proxy-fail-timeout" if i + 1 < args.len() => {
+                    match Utils::parse_duration(&args[i + 1]) {
+                        Some(timeout) if !timeout.is_zero() => config.proxy.fail_timeout = timeout,
+                        _ => errors.push("proxy fail timeout must be a duration such as 500ms or 10s".to_string()),
+                    }
+                    i += 1;
+                }
+                "--proxy-timeout" if i + 1 < args.len() => {
+                    match Utils::parse_duration(&args[i + 1]) {
+                        Some(timeout) if !timeout.is_zero() => config.proxy.timeout = timeout,
+                        _ => errors.push("proxy timeout must be a duration such as 500ms or 10s".to_string()),
+                    }
+                    i += 1;
+                }
+                "--proxy-health-path" if i + 1 < args.len() => {
+                    // probed on every upstream, which is down once it fails
+                    if args[i + 1].starts_with('/') {
+                        config.proxy.health.path = Some(args[i + 1].clone());
+                    } else {
+                        errors.push(format!("proxy health path must start with /: {}", args[i + 1]));
+                    }
+                    i += 1;
+                }
+                "--proxy-health-interval" if i + 1 < args.len() => {
+                    match Utils::parse_duration(&args[i + 1]) {
+                        Some(interval) if !interval.is_zero() => config.proxy.health.interval = interval,
+                        _ => errors.push("proxy health interval must be a duration such as 500ms or 10s".to_string()),
+                    }
+                    i += 1;
+                }
+                "--proxy-healthy-threshold" if i + 1 < args.len() => {
+                    match args[i + 1].parse::<u32>() {
+                        Ok(threshold) if threshold > 0 => config.proxy.health.healthy_threshold = threshold,
+                        _ => errors.push(format!("proxy healthy threshold must be a positive number: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--proxy-unhealthy-threshold" if i + 1 < args.len() => {
+                    match args[i + 1].parse::<u32>() {
+                        Ok(threshold) if threshold > 0 => config.proxy.health.unhealthy_threshold = threshold,
+                        _ => errors.push(format!("proxy unhealthy threshold must be a positive number: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--forward-proxy" => {
+                    // absolute-form requests and CONNECT tunnels, to --forward-allow destinations only
+                    config.forward_proxy.enabled = true;
+                }
+                "--forward-allow" if i + 1 < args.len() => {
+                    // repeatable host:port, e.g. *.lab.internal:443 or 10.0.0.5:*
+                    if let Err(e) = config.forward_proxy.allow(&args[i + 1]) {
+                        errors.push(e);
+                    }
+                    i += 1;
+                }
+                "--location" if i + 1 < args.len() => {
+                    match Location::parse(&args[i + 1]) {
+                        Some(location) => locations.push(location),
+                        None => errors.push(format!("location must be /prefix=file: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--vhost" if i + 1 < args.len() => {
+                    match VirtualHost::parse(&args[i + 1]) {
+                        Some(vhost) => vhosts.push(vhost),
+                        None => errors.push(format!("virtual host must be name[,name...]=file: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--tus" if i + 1 < args.len() => {
+                    // path of the upload endpoint, e.g. /uploads, each PATCH is still capped by
+                    // --max-body-size so clients have to send chunks below it
+                    config.tus.endpoint = Some(args[i + 1].trim_end_matches('/').to_string());
+                    i += 1;
+                }
+                "--tus-dir" if i + 1 < args.len() => {
+                    config.tus.dir = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                }
+                "--tus-max-size" if i + 1 < args.len() => {
+                    match Utils::parse_size(&args[i + 1]) {
+                        Some(size) => config.tus.max_size = Some(size as u64),
+                        None => errors.push("upload max size must be a size such as 1073741824 or 1G".to_string()),
+                    }
+                    i += 1;
+                }
+                "--robots" if i + 1 < args.len() => {
+                    // allow, deny or a file with the body, only when the root has no robots.txt
+                    config.robots = Some(Robots::from_str(&args[i + 1]));
+                    i += 1;
+                }
+                "--favicon" if i + 1 < args.len() => {
+                    // a file, or off to answer /favicon.ico with a 404 like any missing file
+                    config.favicon = Favicon::from_str(&args[i + 1]);
+                    i += 1;
+                }
+                "--security-contact" if i + 1 < args.len() => {
+                    // repeatable, e.g. mailto:security@example.com, turns on /.well-known/security.txt
+                    config.security_txt.contacts.push(args[i + 1].trim().to_string());
+                    i += 1;
+                }
+                "--security-policy" if i + 1 < args.len() => {
+                    config.security_txt.policy = Some(args[i + 1].trim().to_string());
+                    i += 1;
                 }
-                "--host" => {
-                    if i + 1 < args.len() {
-                        host = args[i + 1].clone();
-                        i += 1;
+                "--security-expires" if i + 1 < args.len() => {
+                    match Utils::parse_duration(&args[i + 1]) {
+                        Some(expires) if !expires.is_zero() => config.security_txt.expires = expires,
+                        _ => errors.push("security.txt expiry must be a duration such as 90d".to_string()),
                     }
+                    i += 1;
                 }
-                "--worker" => {
-                    if i + 1 < args.len() {
-                        if let Ok(parsed_worker) = args[i + 1].parse::<i32>() {
-                            if parsed_worker > Self::MIN_WORKER {
-                                worker = parsed_worker;
-                            } else {
-                                Logger::error("worker cannot be less than 1");
+                "--status-page" => {
+                    // HTML dashboard at /_katana/status, anyone who can reach the site can read it
+                    config.status_page = true;
+                }
+                "--plugins-dir" if i + 1 < args.len() => {
+                    // *.wasm modules exporting on_request and/or on_response
+                    config.plugins_dir = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                }
+                "--hook" if i + 1 < args.len() => {
+                    // repeatable, event=command with request, upload or server_error as event
+                    match Hooks::parse(&args[i + 1]) {
+                        Ok(hook) => config.hooks.commands.push(hook),
+                        Err(e) => errors.push(e),
+                    }
+                    i += 1;
+                }
+                "--hook-timeout" if i + 1 < args.len() => {
+                    match Utils::parse_duration(&args[i + 1]) {
+                        Some(timeout) if !timeout.is_zero() => config.hooks.timeout = timeout,
+                        _ => errors.push("hook timeout must be a duration such as 500ms or 10s".to_string()),
+                    }
+                    i += 1;
+                }
+                "--admin-listen" if i + 1 < args.len() => {
+                    // a separate listener for the admin API, keep it on loopback or a unix socket
+                    match Self::parse_listen_addr(&args[i + 1]) {
+                        Some(addr) => config.admin_listen = Some(addr),
+                        None => errors.push(format!("invalid admin listen address: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--admin-token" if i + 1 < args.len() => {
+                    match Secret::resolve(&args[i + 1]) {
+                        Ok(token) => config.admin_token = Some(token),
+                        Err(error) => errors.push(error),
+                    }
+                    i += 1;
+                }
+                "--acme-dir" if i + 1 < args.len() => {
+                    // where the ACME client drops HTTP-01 key authorizations, one file per token
+                    config.acme_dir = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                }
+                "--acme-domain" if i + 1 < args.len() => {
+                    // repeatable, challenges are only answered for these hosts when given
+                    config.acme_domains.push(args[i + 1].trim_end_matches('.').to_lowercase());
+                    i += 1;
+                }
+                "--error-log" if i + 1 < args.len() => {
+                    config.error_log = Some(PathBuf::from(&args[i + 1]));
+                    i += 1;
+                }
+                "--slow-request" if i + 1 < args.len() => {
+                    // warn about requests slower than this, 0 turns it off
+                    match Utils::parse_duration(&args[i + 1]) {
+                        Some(threshold) if threshold.is_zero() => config.slow_request = None,
+                        Some(threshold) => config.slow_request = Some(threshold),
+                        None => errors.push("slow request threshold must be a duration such as 500ms or 2s".to_string()),
+                    }
+                    i += 1;
+                }
+                "--syslog" if i + 1 < args.len() => {
+                    // local, unix:/path, udp://host:port or tcp://host:port
+                    config.syslog = Some(args[i + 1].clone());
+                    i += 1;
+                }
+                #[cfg(feature = "otel")]
+                "--otlp-endpoint" if i + 1 < args.len() => {
+                    // e.g. http://localhost:4318, spans are posted to /v1/traces
+                    config.otlp_endpoint = Some(args[i + 1].clone());
+                    i += 1;
+                }
+                "--syslog-facility" if i + 1 < args.len() => {
+                    match Syslog::facility(&args[i + 1]) {
+                        Some(facility) => config.syslog_facility = facility,
+                        None => errors.push(format!("unknown syslog facility: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--port" if i + 1 < args.len() => {
+                    match args[i + 1].parse::<u16>() {
+                        Ok(port) => config.port = port,
+                        Err(_) => errors.push(format!("invalid port: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--port-retry" if i + 1 < args.len() => {
+                    // how many following ports to try when the requested one is busy
+                    match args[i + 1].parse::<u16>() {
+                        Ok(retries) => config.port_retry = retries,
+                        Err(_) => errors.push(format!("invalid port retry count: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--dir" if i + 1 < args.len() => {
+                    config.root_dir = PathBuf::from(&args[i + 1]);
+                    i += 1;
+                }
+                "--default-language" if i + 1 < args.len() => {
+                    // turns on Accept-Language negotiation between index.<language>.html pages
+                    if Language::is_tag(&args[i + 1]) {
+                        config.default_language = Some(args[i + 1].clone());
+                    } else {
+                        errors.push(format!("invalid language tag: {}", args[i + 1]));
+                    }
+                    i += 1;
+                }
+                "--locale" if i + 1 < args.len() => {
+                    // language of the built-in pages when Accept-Language names none of them
+                    if Language::is_tag(&args[i + 1]) {
+                        config.locale = Some(args[i + 1].clone());
+                    } else {
+                        errors.push(format!("invalid language tag: {}", args[i + 1]));
+                    }
+                    i += 1;
+                }
+                "--templates" if i + 1 < args.len() => {
+                    // a directory with error.html, directory.html, status.html, banner.txt or
+                    // messages/<language>.txt replacing the built-in ones
+                    let dir = PathBuf::from(&args[i + 1]);
+                    match Templates::load_dir(&dir) {
+                        Ok(_) if dir.is_dir() => config.templates_dir = Some(dir),
+                        Ok(_) => errors.push(format!("templates directory not found: {}", args[i + 1])),
+                        Err(e) => errors.push(format!("cannot read templates: {}", e)),
+                    }
+                    i += 1;
+                }
+                "--messages" if i + 1 < args.len() => {
+                    // language=file of `key = value` texts for the built-in pages, a new
+                    // language or replacements for some texts of a built-in one
+                    match args[i + 1].split_once('=') {
+                        Some((language, file)) if Language::is_tag(language.trim()) => {
+                            match fs::read_to_string(file.trim())
+                                .map_err(|e| e.to_string())
+                                .and_then(|content| Templates::parse_messages(&content))
+                            {
+                                Ok(messages) => config.messages.push((language.trim().to_string(), messages)),
+                                Err(e) => errors.push(format!("cannot read messages {}: {}", file.trim(), e)),
                             }
                         }
-                        i += 1;
+                        _ => errors.push(format!("messages must be language=file: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--host" if i + 1 < args.len() => {
+                    config.host = args[i + 1].clone();
+                    i += 1;
+                }
+                "--listen" if i + 1 < args.len() => {
+                    // repeatable, every address is served by the same pipeline
+                    match Self::parse_listen_addr(&args[i + 1]) {
+                        Some(addr) => config.listen.push(addr),
+                        None => errors.push(format!("invalid listen address: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--socket-mode" if i + 1 < args.len() => {
+                    // octal permissions applied to unix socket files, e.g. 660
+                    match u32::from_str_radix(&args[i + 1], 8) {
+                        Ok(mode) if mode <= 0o777 => config.socket_mode = Some(mode),
+                        _ => errors.push(format!("invalid socket mode: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--worker" if i + 1 < args.len() => {
+                    if let Ok(parsed_worker) = args[i + 1].parse::<i32>() {
+                        if parsed_worker >= Self::MIN_WORKER {
+                            config.worker = parsed_worker;
+                        } else {
+                            errors.push("worker cannot be less than 1".to_string());
+                        }
+                    }
+                    i += 1;
+                }
+                "--workers" if i + 1 < args.len() => {
+                    // N accept loops per listener, each with its own SO_REUSEPORT socket on Linux
+                    match args[i + 1].parse::<i32>() {
+                        Ok(parsed_worker) if parsed_worker >= Self::MIN_WORKER => {
+                            config.worker = parsed_worker;
+                            config.reuse_port = true;
+                        }
+                        _ => errors.push("workers cannot be less than 1".to_string()),
+                    }
+                    i += 1;
+                }
+                "--keep-alive-timeout" if i + 1 < args.len() => {
+                    // 0 disables persistent connections entirely
+                    match args[i + 1].parse::<u64>() {
+                        Ok(timeout) => config.keep_alive_timeout = timeout,
+                        Err(_) => errors.push("keep-alive timeout must be a number of seconds".to_string()),
                     }
+                    i += 1;
+                }
+                "--grace-period" if i + 1 < args.len() => {
+                    // how long a shutdown waits for requests in progress, 0 closes them at once
+                    match Utils::parse_duration(&args[i + 1]) {
+                        Some(grace_period) => config.grace_period = grace_period,
+                        None => errors.push("grace period must be a duration such as 10s or 1m".to_string()),
+                    }
+                    grace_period_given = true;
+                    i += 1;
+                }
+                "--max-requests" if i + 1 < args.len() => {
+                    match args[i + 1].parse::<usize>() {
+                        Ok(parsed) if parsed > 0 => config.max_requests = parsed,
+                        _ => errors.push("max requests per connection cannot be less than 1".to_string()),
+                    }
+                    i += 1;
+                }
+                "--max-body-size" if i + 1 < args.len() => {
+                    // accepts plain bytes or a K/M/G suffixed size, e.g. 512K
+                    match Utils::parse_size(&args[i + 1]) {
+                        Some(size) => config.max_body_size = size,
+                        None => errors.push("max body size must be a size such as 1048576 or 10M".to_string()),
+                    }
+                    i += 1;
+                }
+                "--allowed-methods" if i + 1 < args.len() => {
+                    // comma separated, e.g. GET,HEAD, everything else gets a 405
+                    match Self::parse_methods(&args[i + 1]) {
+                        Ok(methods) => config.allowed_methods = methods,
+                        Err(error) => errors.push(error),
+                    }
+                    i += 1;
+                }
+                "--writable" => {
+                    // DELETE removes files and empty directories under the root, MOVE renames
+                    // them, and a form POSTed to a directory uploads files or creates a folder
+                    config.writable = true;
+                }
+                "--userdir" => {
+                    // /~user/ is served from public_html in the home directory of each user
+                    config.userdir.enabled = true;
+                }
+                "--userdir-dir" if i + 1 < args.len() => {
+                    if let Err(e) = config.userdir.set_dir(&args[i + 1]) {
+                        errors.push(e);
+                    }
+                    i += 1;
+                }
+                "--read-only" | "--read-only=true" => {
+                    // refuses every write with a 405, the default until it is turned off
+                    config.read_only = true;
+                }
+                "--read-only=false" => {
+                    // lets --writable and --tus add their write methods
+                    config.read_only = false;
+                }
+                "--compression-level" if i + 1 < args.len() => {
+                    // 1 is fastest, 9 compresses best and 0 turns compression off
+                    match Compression::parse_level(&args[i + 1]) {
+                        Some(level) => config.compression.level = level,
+                        None => errors.push(format!("compression level must be between 0 and 9: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--compression-min-size" if i + 1 < args.len() => {
+                    match Utils::parse_size(&args[i + 1]) {
+                        Some(size) => config.compression.min_size = size,
+                        None => errors.push("compression min size must be a size such as 1024 or 1K".to_string()),
+                    }
+                    i += 1;
+                }
+                "--compress-type" if i + 1 < args.len() => {
+                    // repeatable content type or type/* wildcard, the first one replaces the defaults
+                    if !compress_types_given {
+                        config.compression.include.clear();
+                        compress_types_given = true;
+                    }
+                    config.compression.include.push(args[i + 1].clone());
+                    i += 1;
+                }
+                "--no-compress-type" if i + 1 < args.len() => {
+                    config.compression.exclude.push(args[i + 1].clone());
+                    i += 1;
+                }
+                "--trusted-proxy" if i + 1 < args.len() => {
+                    // repeatable address or CIDR block, only these peers may set X-Request-Id,
+                    // X-Forwarded-* and Forwarded
+                    match Cidr::from_str(&args[i + 1]) {
+                        Some(cidr) => config.trusted_proxies.push(cidr),
+                        None => errors.push(format!("invalid trusted proxy address: {}", args[i + 1])),
+                    }
+                    i += 1;
+                }
+                "--method-override" if i + 1 < args.len() => {
+                    // repeatable address or CIDR block, POSTs from these clients may ask for
+                    // another method with X-HTTP-Method-Override or a _method form field
+                    match Cidr::from_str(&args[i + 1]) {
+                        Some(cidr) => config.method_override.push(cidr),
+                        None => errors.push(format!("invalid method override address: {}", args[i + 1])),
+                    }
+                    i += 1;
                 }
                 _ => {}
             }
             i += 1;
         }
 
-        Config {
-            host,
-            port,
-            root_dir,
-            worker,
+        // keys alone protect everything rather than silently protecting nothing
+        if !config.jwt.keys.is_empty() && config.jwt.protected.is_empty() {
+            config.jwt.protected.push("/".to_string());
+        }
+        if config.jwt.enabled() && config.jwt.keys.is_empty() {
+            errors.push("protected paths need a --jwt-secret or --jwt-jwks to verify tokens".to_string());
+        }
+        if (!config.signed_urls.protected.is_empty() || config.sign_path.is_some()) && config.signed_urls.secret.is_none() {
+            errors.push("signed links need a --sign-secret".to_string());
+        }
+        if (!config.hotlink.allowed.is_empty() || config.hotlink.block_empty || config.hotlink.redirect.is_some())
+            && !config.hotlink.enabled()
+        {
+            errors.push("hotlink settings need extensions set with --hotlink-protect".to_string());
+        }
+        if config.security_txt.policy.is_some() && !config.security_txt.enabled() {
+            errors.push("security.txt needs a --security-contact".to_string());
+        }
+        if config.tus.endpoint.as_deref().is_some_and(|endpoint| !endpoint.starts_with('/')) {
+            errors.push("the upload endpoint must be a path such as /uploads".to_string());
+        }
+        if (config.tus.dir.is_some() || config.tus.max_size.is_some()) && !config.tus.enabled() {
+            errors.push("upload settings need an endpoint set with --tus".to_string());
+        }
+        // nothing outside the root can be read from a chroot
+        if config.privileges.chroot && (!config.vhosts.is_empty() || config.userdir.enabled) {
+            errors.push("--chroot cannot be used with virtual hosts or --userdir, their files are outside the root".to_string());
+        }
+        // settings given one by one override those of the preset, whatever their order
+        if let Some(version) = tls_min_version {
+            config.tls_policy.min_version = version;
+        }
+        if tls_ciphers.is_empty() {
+            // those of the preset a higher minimum version leaves unused
+            let min_version = config.tls_policy.min_version;
+            config.tls_policy.ciphers.retain(|cipher| TlsPolicy::version(cipher) >= Some(min_version));
+        } else {
+            config.tls_policy.ciphers = tls_ciphers;
+        }
+        if !tls_curves.is_empty() {
+            config.tls_policy.curves = tls_curves;
+        }
+        errors.extend(config.tls_policy.validate());
+        // no handshake would be made with them, the policy would be enforced nowhere
+        if tls_policy_given {
+            errors.push("TLS policy settings need TLS termination, which this build does not have".to_string());
+        }
+        // the profile only changes what was not given explicitly
+        if config.container {
+            if !log_format_given {
+                config.log_format = LogFormat::Json;
+            }
+            if !grace_period_given {
+                config.grace_period = Self::CONTAINER_GRACE_PERIOD;
+            }
+            config.no_color = true;
+            config.no_qr = true;
+            if config.daemon {
+                errors.push("--daemon cannot be used with --container, the container runtime keeps it running".to_string());
+            }
+        }
+        if config.service.action.is_some() && config.daemon {
+            errors.push("--daemon cannot be used with a service, the service manager runs it in the background".to_string());
+        }
+        if config.service.name.trim().is_empty() || config.service.name.contains(['/', '\\']) {
+            errors.push(format!("invalid service name: {}", config.service.name));
+        }
+        // a service has no console, it logs to the event log unless told otherwise
+        if config.service.action == Some(ServiceAction::Run) && config.syslog.is_none() {
+            config.syslog = Some(Syslog::EVENT_LOG.to_string());
+        }
+        #[cfg(feature = "landlock")]
+        if config.sandbox && config.userdir.enabled {
+            errors.push("--sandbox cannot be used with --userdir, home directories are outside the sandbox".to_string());
+        }
+        // the sandbox never grants the right to execute a file
+        #[cfg(feature = "landlock")]
+        if config.sandbox && config.handlers.runs_programs() {
+            errors.push("--sandbox cannot be used with cgi or fastcgi handlers, the sandbox cannot run scripts".to_string());
+        }
+        #[cfg(feature = "landlock")]
+        if config.sandbox && !config.hooks.commands.is_empty() {
+            errors.push("--sandbox cannot be used with --hook, the sandbox cannot run commands".to_string());
+        }
+        if config.userdir.dir != UserDir::DEFAULT_DIR && !config.userdir.enabled {
+            errors.push("the user directory needs --userdir".to_string());
+        }
+        // one switch keeps the share immutable: without the write methods they would add,
+        // uploads, renames and deletions all get a 405
+        if config.read_only {
+            if config.writable {
+                errors.push("--writable needs --read-only=false, the share is read only by default".to_string());
+            }
+            if config.tus.enabled() {
+                errors.push("--tus needs --read-only=false, the share is read only by default".to_string());
+            }
+            config.writable = false;
+            config.tus = Tus::default();
+        }
+        if config.admin_listen.is_some() && config.admin_token.as_deref().is_none_or(str::is_empty) {
+            errors.push("the admin API needs an --admin-token".to_string());
+        }
+        // serving them would mean plain HTTP on a port clients expect to speak TLS to
+        if !config.tls_certs.is_empty() {
+            errors.push("--tls-cert needs TLS termination, which this build does not have".to_string());
+        }
+        // no handshake would hand the responses out, fetching them would be for nothing
+        if config.ocsp_stapling {
+            errors.push("--ocsp-stapling needs TLS termination, which this build does not have".to_string());
+        }
+        // nothing could verify them, and handlers would be told of a certificate no one checked
+        if config.client_auth != ClientAuth::Off {
+            errors.push("client certificates need TLS termination, which this build does not have".to_string());
+        }
+
+        config.ignore = Ignore::load(&config.root_dir, &ignore_patterns);
+
+        // locations start from the options of the site, sites from the global ones, errors in
+        // those are reported once
+        if errors.is_empty() && !locations.is_empty() {
+            let site_args = Self::without(&args, &["--config", "--vhost", "--location"]);
+            for (prefix, file) in locations {
+                match Location::load(prefix, file, &site_args) {
+                    Ok(location) => config.locations.push(location),
+                    Err(location_errors) => errors.extend(location_errors),
+                }
+            }
+        }
+        if errors.is_empty() && !vhosts.is_empty() {
+            let global_args = Self::without(&args, &["--config", "--vhost"]);
+            let global_certs = config.tls_certs.len();
+            for (names, file) in vhosts {
+                match VirtualHost::load(names, file, &global_args, global_certs) {
+                    Ok(vhost) => {
+                        // served through SNI like the global ones
+                        config.tls_certs.extend(vhost.config.tls_certs.iter().skip(global_certs).cloned());
+                        config.vhosts.push(vhost);
+                    }
+                    Err(vhost_errors) => errors.extend(vhost_errors),
+                }
+            }
+        }
+
+        (config, errors)
+    }
+
+    // the file holds the same options as the command line, one `key = value` per line:
+    //
+    //   # comments and blank lines are ignored
+    //   port = 8080
+    //   dir = "public"
+    //   listen = ["127.0.0.1:8080", "[::1]:8080"]
+    //   watch_config = true
+    //
+    // keys are the long flag names (underscores or dashes), values may be quoted,
+    // `true` turns on a switch, `false` leaves it off (`read_only = false` turns off the one
+    // that is on by default) and a list repeats the option.
+    // A `[section]` line prefixes the keys after it, `min_version` under `[tls]` is
+    // --tls-min-version, up to the next section or the end of the file
+    pub fn read_file(path: &PathBuf) -> Result<Vec<String>, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read config file {}: {}", path.display(), e))?;
+        Self::parse_file(&content).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse_file(content: &str) -> Result<Vec<String>, String> {
+        let mut args = Vec::new();
+        let mut section = String::new();
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                section = name.trim().replace('_', "-");
+                if !section.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+                    return Err(format!("line {}: invalid section: {}", number + 1, name));
+                }
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", number + 1))?;
+            let key = key.trim().replace('_', "-");
+            let value = value.trim();
+            if key.is_empty() {
+                return Err(format!("line {}: missing key", number + 1));
+            }
+            let key = if section.is_empty() { key } else { format!("{}-{}", section, key) };
+
+            match value {
+                "true" => args.push(format!("--{}", key)),
+                // the one switch that is on by default has to be turned off explicitly
+                "false" if key == "read-only" => args.push("--read-only=false".to_string()),
+                "false" => {}
+                _ if value.starts_with('[') && value.ends_with(']') => {
+                    let items = value[1..value.len() - 1]
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty());
+                    for item in items {
+                        args.push(format!("--{}", key));
+                        args.push(Self::unquote(item));
+                    }
+                }
+                _ => {
+                    args.push(format!("--{}", key));
+                    args.push(Self::unquote(value));
+                }
+            }
+        }
+
+        Ok(args)
+    }
+
+    // Name: value, framing headers are Katana's to set
+    pub fn parse_header(value: &str) -> Option<(String, String)> {
+        let (name, value) = value.split_once(':')?;
+        let (name, value) = (name.trim(), value.trim());
+        let reserved = ["Content-Length", "Transfer-Encoding", "Connection"];
+        if name.is_empty()
+            || !name.bytes().all(|b| b.is_ascii_graphic())
+            || reserved.iter().any(|header| header.eq_ignore_ascii_case(name))
+            || value.contains(['\r', '\n'])
+        {
+            return None;
+        }
+        Some((name.to_string(), value.to_string()))
+    }
+
+    // the site a Host header names, None for the global one
+    pub fn for_host(&self, host: &str) -> Option<Arc<Config>> {
+        VirtualHost::select(&self.vhosts, host).map(|vhost| vhost.config.clone())
+    }
+
+    // the proxies of the site, its locations and virtual hosts, those with upstreams only
+    pub fn proxies(&self) -> Vec<&Proxy> {
+        let mut proxies: Vec<&Proxy> = vec![&self.proxy];
+        proxies.extend(self.locations.iter().map(|location| &location.config.proxy));
+        proxies.retain(|proxy| proxy.enabled());
+        for vhost in &self.vhosts {
+            proxies.extend(vhost.config.proxies());
+        }
+        proxies
+    }
+
+    // the location a request path falls under, None for the site itself
+    pub fn for_path(&self, path: &str) -> Option<Arc<Config>> {
+        Location::select(&self.locations, path).map(|location| location.config.clone())
+    }
+
+    // args without the given options and their values
+    fn without(args: &[String], options: &[&str]) -> Vec<String> {
+        let mut kept = Vec::new();
+        let mut i = 0;
+        while i < args.len() {
+            if options.contains(&args[i].as_str()) && i + 1 < args.len() {
+                i += 2;
+                continue;
+            }
+            kept.push(args[i].clone());
+            i += 1;
+        }
+        kept
+    }
+
+    // host:port, where the host may be empty (all interfaces) or a bracketed IPv6 literal,
+    // or unix:/path/to/katana.sock for a unix domain socket
+    pub fn parse_listen_addr(value: &str) -> Option<String> {
+        let value = value.trim();
+        if let Some(path) = value.strip_prefix(Listener::UNIX_PREFIX) {
+            return if path.is_empty() { None } else { Some(value.to_string()) };
+        }
+
+        let (host, port) = value.rsplit_once(':')?;
+        let port = port.parse::<u16>().ok()?;
+        let host = if host.is_empty() { Self::default().host } else { host.to_string() };
+        Some(Utils::host_port(&host, port))
+    }
+
+    // --writable adds POST, MOVE and DELETE and the upload endpoint takes the tus methods on
+    // top of the allowed ones
+    pub fn methods_for(&self, path: &str) -> Vec<HttpMethod> {
+        let mut methods = self.allowed_methods.clone();
+        if self.writable {
+            for method in [HttpMethod::POST, HttpMethod::MOVE, HttpMethod::DELETE] {
+                if !methods.contains(&method) {
+                    methods.push(method);
+                }
+            }
+        }
+        // OPTIONS * asks about the whole server, uploads included
+        if self.tus.handles(path) || (path == "*" && self.tus.endpoint.is_some()) {
+            for method in Tus::METHODS {
+                if !methods.contains(method) {
+                    methods.push(*method);
+                }
+            }
+        }
+        methods
+    }
+
+    pub fn parse_methods(value: &str) -> Result<Vec<HttpMethod>, String> {
+        let mut methods = Vec::new();
+        for name in value.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            match HttpMethod::from_str(&name.to_uppercase()) {
+                Some(method) if Server::SUPPORTED_HTTP_METHODS.contains(&method) => {
+                    if !methods.contains(&method) {
+                        methods.push(method);
+                    }
+                }
+                Some(HttpMethod::TRACE) => return Err("TRACE cannot be allowed, it enables cross-site tracing".to_string()),
+                _ => return Err(format!("unsupported method: {}", name)),
+            }
+        }
+        if methods.is_empty() {
+            return Err("at least one method must be allowed".to_string());
         }
+        Ok(methods)
+    }
+
+    fn unquote(value: &str) -> String {
+        let quoted = value.len() >= 2
+            && ((value.starts_with('"') && value.ends_with('"'))
+                || (value.starts_with('\'') && value.ends_with('\'')));
+        if quoted {
+            value[1..value.len() - 1].to_string()
+        } else {
+            value.to_string()
+        }
+    }
+
+    pub fn keep_alive_enabled(&self) -> bool {
+        self.keep_alive_timeout > 0
+    }
+
+    pub fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    pub fn allows_method_override(&self, ip: IpAddr) -> bool {
+        self.method_override.iter().any(|cidr| cidr.contains(ip))
     }
 }
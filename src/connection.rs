@@ -0,0 +1,518 @@
+use crate::stats::Stats;
+use crate::utils::Utils;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+#[derive(Debug)]
+pub enum BindError {
+    PermissionDenied(String),
+    AddrInUse(String),
+    InvalidAddress(String, String),
+    Other(String, Error),
+}
+
+impl BindError {
+    // sysexits(3) codes, so scripts can tell the failures apart
+    pub const EXIT_INVALID_ADDRESS: i32 = 64; // EX_USAGE
+    pub const EXIT_ADDR_IN_USE: i32 = 69; // EX_UNAVAILABLE
+    pub const EXIT_OTHER: i32 = 71; // EX_OSERR
+    pub const EXIT_PERMISSION_DENIED: i32 = 77; // EX_NOPERM
+
+    pub fn from_io(addr: &str, error: Error) -> Self {
+        match error.kind() {
+            ErrorKind::PermissionDenied => BindError::PermissionDenied(addr.to_string()),
+            ErrorKind::AddrInUse => BindError::AddrInUse(addr.to_string()),
+            ErrorKind::AddrNotAvailable | ErrorKind::InvalidInput | ErrorKind::NotFound => {
+                BindError::InvalidAddress(addr.to_string(), error.to_string())
+            }
+            _ => BindError::Other(addr.to_string(), error),
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            BindError::PermissionDenied(_) => Self::EXIT_PERMISSION_DENIED,
+            BindError::AddrInUse(_) => Self::EXIT_ADDR_IN_USE,
+            BindError::InvalidAddress(_, _) => Self::EXIT_INVALID_ADDRESS,
+            BindError::Other(_, _) => Self::EXIT_OTHER,
+        }
+    }
+}
+
+impl fmt::Display for BindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindError::PermissionDenied(addr) => write!(
+                f,
+                "Permission denied binding {}. Ports below 1024 need root or CAP_NET_BIND_SERVICE, try --port 8080.",
+                addr
+            ),
+            BindError::AddrInUse(addr) => write!(
+                f,
+                "Address {} is already in use. Stop the other server, pick another --port or add --port-retry 10.",
+                addr
+            ),
+            BindError::InvalidAddress(addr, reason) => write!(
+                f,
+                "Cannot listen on {}: {}. Check --host, --port and --listen.",
+                addr, reason
+            ),
+            BindError::Other(addr, error) => write!(f, "Cannot listen on {}: {}", addr, error),
+        }
+    }
+}
+
+pub enum Listener {
+    Tcp(TcpListener),
+    // the path is only kept when the socket file is ours to remove on shutdown
+    #[cfg(unix)]
+    Unix(UnixListener, Option<PathBuf>),
+}
+
+impl Listener {
+    pub const UNIX_PREFIX: &'static str = "unix:";
+
+    pub fn bind(addr: &str, socket_mode: Option<u32>) -> Result<Self, Error> {
+        match addr.strip_prefix(Self::UNIX_PREFIX) {
+            Some(path) => Self::bind_unix(Path::new(path), socket_mode),
+            None => {
+                // resolve first, lookup failures otherwise surface as an opaque error kind
+                let resolved: Vec<SocketAddr> = addr
+                    .to_socket_addrs()
+                    .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?
+                    .collect();
+                Ok(Listener::Tcp(TcpListener::bind(resolved.as_slice())?))
+            }
+        }
+    }
+
+    // SO_REUSEPORT lets several sockets bind the same port and the kernel spreads
+    // incoming connections across them, std cannot set it before bind so this goes
+    // through the raw socket calls
+    #[cfg(target_os = "linux")]
+    pub fn bind_reuse_port(addr: &SocketAddr) -> Result<Self, Error> {
+        use std::os::unix::io::FromRawFd;
+
+        let fd = unsafe { reuse_port::bind_listener(addr) }?;
+        Ok(Listener::Tcp(unsafe { TcpListener::from_raw_fd(fd) }))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn bind_reuse_port(_addr: &SocketAddr) -> Result<Self, Error> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "SO_REUSEPORT is only used on Linux",
+        ))
+    }
+
+    // a UDP socket sharing its port with other processes, e.g. 5353 with the system
+    // mDNS responder
+    #[cfg(target_os = "linux")]
+    pub fn bind_shared_udp(addr: &SocketAddr) -> Result<UdpSocket, Error> {
+        use std::os::unix::io::FromRawFd;
+
+        let fd = unsafe { reuse_port::bind_datagram(addr) }?;
+        Ok(unsafe { UdpSocket::from_raw_fd(fd) })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn bind_shared_udp(addr: &SocketAddr) -> Result<UdpSocket, Error> {
+        UdpSocket::bind(addr)
+    }
+
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        match self {
+            Listener::Tcp(listener) => listener.try_clone().map(Listener::Tcp),
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => listener.try_clone().map(|l| Listener::Unix(l, None)),
+        }
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr().ok(),
+            #[cfg(unix)]
+            Listener::Unix(_, _) => None,
+        }
+    }
+
+    // an IPv6 wildcard socket also accepts IPv4 where the system allows it (Linux, BSD);
+    // elsewhere an extra IPv4 listener on the same port covers the gap
+    pub fn ipv4_fallback(&self) -> Option<Self> {
+        // use the bound port, an ephemeral :0 must not pick a second random one
+        let addr = self.local_addr().filter(|addr| addr.is_ipv6() && addr.ip().is_unspecified())?;
+        match TcpListener::bind(format!("0.0.0.0:{}", addr.port())) {
+            Ok(listener) => Some(Listener::Tcp(listener)),
+            Err(_) => None, // already covered by the dual-stack socket
+        }
+    }
+
+    #[cfg(unix)]
+    fn bind_unix(path: &Path, socket_mode: Option<u32>) -> Result<Self, Error> {
+        use std::os::unix::fs::PermissionsExt;
+
+        // a socket file left behind by a crashed instance would make bind fail
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(Error::new(ErrorKind::AddrInUse, "socket is already served"));
+            }
+            fs::remove_file(path)?;
+        }
+
+        let listener = UnixListener::bind(path)?;
+        if let Some(mode) = socket_mode {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+        Ok(Listener::Unix(listener, Some(path.to_path_buf())))
+    }
+
+    #[cfg(not(unix))]
+    fn bind_unix(_path: &Path, _socket_mode: Option<u32>) -> Result<Self, Error> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "unix sockets are not supported on this platform",
+        ))
+    }
+
+    pub fn accept(&self) -> Result<Connection, Error> {
+        match self {
+            Listener::Tcp(listener) => listener.accept().map(|(stream, _)| Connection::Tcp(stream)),
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => listener.accept().map(|(stream, _)| Connection::Unix(stream)),
+        }
+    }
+
+    pub fn socket_path(&self) -> Option<PathBuf> {
+        match self {
+            Listener::Tcp(_) => None,
+            #[cfg(unix)]
+            Listener::Unix(_, path) => path.to_owned(),
+        }
+    }
+
+    // sockets passed by systemd (LISTEN_FDS), starting at file descriptor 3
+    // @see: https://www.freedesktop.org/software/systemd/man/latest/sd_listen_fds.html
+    #[cfg(unix)]
+    pub fn from_systemd() -> Option<Vec<Self>> {
+        use std::os::unix::io::{FromRawFd, IntoRawFd};
+
+        const LISTEN_FDS_START: i32 = 3;
+
+        let pid = env::var("LISTEN_PID").ok()?.parse::<u32>().ok()?;
+        if pid != process::id() {
+            return None; // meant for another process
+        }
+        let count = env::var("LISTEN_FDS").ok()?.parse::<i32>().ok()?;
+
+        // do not pass them on to anything we spawn
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_FDNAMES");
+
+        let mut listeners = Vec::new();
+        for fd in LISTEN_FDS_START..LISTEN_FDS_START + count {
+            let tcp = unsafe { TcpListener::from_raw_fd(fd) };
+            if tcp.local_addr().is_ok() {
+                listeners.push(Listener::Tcp(tcp));
+                continue;
+            }
+
+            let unix = unsafe { UnixListener::from_raw_fd(tcp.into_raw_fd()) };
+            if unix.local_addr().is_ok() {
+                listeners.push(Listener::Unix(unix, None));
+            }
+        }
+
+        if listeners.is_empty() {
+            None
+        } else {
+            Some(listeners)
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_systemd() -> Option<Vec<Self>> {
+        None
+    }
+
+    // a URL a browser can open, wildcard addresses are reached through loopback
+    pub fn url(&self) -> Option<String> {
+        let addr = self.local_addr()?;
+        let host = match addr.ip() {
+            ip if ip.is_unspecified() && ip.is_ipv4() => "127.0.0.1".to_string(),
+            ip if ip.is_unspecified() => "[::1]".to_string(),
+            _ => Utils::format_addr(&addr).rsplit_once(':')?.0.to_string(),
+        };
+        Some(format!("http://{}:{}", host, addr.port()))
+    }
+
+    // the address other devices on the network would use, None for loopback-only listeners
+    pub fn lan_url(&self) -> Option<String> {
+        Some(format!("http://{}", Utils::format_addr(&self.lan_addr()?)))
+    }
+
+    // the address other devices on the network can reach, None for loopback-only listeners
+    pub fn lan_addr(&self) -> Option<SocketAddr> {
+        let addr = self.local_addr()?;
+        let ip = match addr.ip() {
+            ip if ip.is_loopback() => return None,
+            ip if ip.is_unspecified() => Utils::lan_ip()?,
+            ip => ip,
+        };
+        Some(SocketAddr::new(ip, addr.port()))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Listener::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => format!("http://{}", Utils::format_addr(&addr)),
+                Err(_) => "http://(unknown)".to_string(),
+            },
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => {
+                let addr = listener.local_addr().ok();
+                let path = addr.as_ref().and_then(|addr| addr.as_pathname());
+                match path {
+                    Some(path) => format!("{} (unix socket)", path.display()),
+                    None => "(unnamed unix socket)".to_string(),
+                }
+            }
+        }
+    }
+
+    pub fn cleanup(path: &Path) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+pub enum Connection {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Connection {
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        match self {
+            Connection::Tcp(stream) => stream.try_clone().map(Connection::Tcp),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.try_clone().map(Connection::Unix),
+        }
+    }
+
+    // unix socket peers have no address worth logging
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Connection::Tcp(stream) => stream.peer_addr().ok(),
+            #[cfg(unix)]
+            Connection::Unix(_) => None,
+        }
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), Error> {
+        match self {
+            Connection::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    // ends the clones too, a thread blocked reading one of them returns
+    pub fn shutdown(&self, how: Shutdown) -> Result<(), Error> {
+        match self {
+            Connection::Tcp(stream) => stream.shutdown(how),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.shutdown(how),
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let read = match self {
+            Connection::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.read(buf),
+        }?;
+        Stats::received(read);
+        Ok(read)
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let written = match self {
+            Connection::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.write(buf),
+        }?;
+        Stats::sent(written);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        match self {
+            Connection::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+// counts the bytes that really reach the client after the blank line ending the
+// headers, which differs from the body length on HEAD, ranges and aborted transfers
+pub struct BodyCounter<'a, W: Write> {
+    inner: &'a mut W,
+    matched: usize,
+    pub body_bytes: usize,
+}
+
+impl<'a, W: Write> BodyCounter<'a, W> {
+    const HEADERS_END: &'static [u8] = b"\r\n\r\n";
+
+    pub fn new(inner: &'a mut W) -> Self {
+        Self { inner, matched: 0, body_bytes: 0 }
+    }
+}
+
+impl<W: Write> Write for BodyCounter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let written = self.inner.write(buf)?;
+        let mut body_start = written;
+        if self.matched < Self::HEADERS_END.len() {
+            for (i, byte) in buf[..written].iter().enumerate() {
+                self.matched = match *byte {
+                    b if b == Self::HEADERS_END[self.matched] => self.matched + 1,
+                    b'\r' => 1,
+                    _ => 0,
+                };
+                if self.matched == Self::HEADERS_END.len() {
+                    body_start = i + 1;
+                    break;
+                }
+            }
+        } else {
+            body_start = 0;
+        }
+        self.body_bytes += written - body_start;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod reuse_port {
+    use std::io::Error;
+    use std::mem::size_of;
+    use std::net::SocketAddr;
+    use std::os::raw::{c_int, c_void};
+
+    const AF_INET: u16 = 2;
+    const AF_INET6: u16 = 10;
+    const SOCK_STREAM: c_int = 1;
+    const SOCK_DGRAM: c_int = 2;
+    const SOCK_CLOEXEC: c_int = 0o2000000;
+    const SOL_SOCKET: c_int = 1;
+    const SO_REUSEADDR: c_int = 2;
+    const SO_REUSEPORT: c_int = 15;
+    const BACKLOG: c_int = 128;
+
+    #[repr(C)]
+    struct SockAddrIn {
+        family: u16,
+        port: u16,
+        addr: [u8; 4],
+        zero: [u8; 8],
+    }
+
+    #[repr(C)]
+    struct SockAddrIn6 {
+        family: u16,
+        port: u16,
+        flowinfo: u32,
+        addr: [u8; 16],
+        scope_id: u32,
+    }
+
+    extern "C" {
+        fn socket(domain: c_int, kind: c_int, protocol: c_int) -> c_int;
+        fn setsockopt(fd: c_int, level: c_int, name: c_int, value: *const c_void, len: u32) -> c_int;
+        fn bind(fd: c_int, addr: *const c_void, len: u32) -> c_int;
+        fn listen(fd: c_int, backlog: c_int) -> c_int;
+        fn close(fd: c_int) -> c_int;
+    }
+
+    pub unsafe fn bind_listener(addr: &SocketAddr) -> Result<c_int, Error> {
+        let fd = bind_shared(addr, SOCK_STREAM)?;
+        if listen(fd, BACKLOG) < 0 {
+            let error = Error::last_os_error();
+            close(fd);
+            return Err(error);
+        }
+        Ok(fd)
+    }
+
+    pub unsafe fn bind_datagram(addr: &SocketAddr) -> Result<c_int, Error> {
+        bind_shared(addr, SOCK_DGRAM)
+    }
+
+    unsafe fn bind_shared(addr: &SocketAddr, kind: c_int) -> Result<c_int, Error> {
+        let family = if addr.is_ipv4() { AF_INET } else { AF_INET6 };
+        let fd = socket(family as c_int, kind | SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let enable: c_int = 1;
+        let enable_ptr = &enable as *const c_int as *const c_void;
+        let enable_len = size_of::<c_int>() as u32;
+
+        let result = if setsockopt(fd, SOL_SOCKET, SO_REUSEADDR, enable_ptr, enable_len) < 0
+            || setsockopt(fd, SOL_SOCKET, SO_REUSEPORT, enable_ptr, enable_len) < 0
+        {
+            -1
+        } else {
+            match addr {
+                SocketAddr::V4(v4) => {
+                    let raw = SockAddrIn {
+                        family: AF_INET,
+                        port: v4.port().to_be(),
+                        addr: v4.ip().octets(),
+                        zero: [0; 8],
+                    };
+                    bind(fd, &raw as *const SockAddrIn as *const c_void, size_of::<SockAddrIn>() as u32)
+                }
+                SocketAddr::V6(v6) => {
+                    let raw = SockAddrIn6 {
+                        family: AF_INET6,
+                        port: v6.port().to_be(),
+                        flowinfo: v6.flowinfo(),
+                        addr: v6.ip().octets(),
+                        scope_id: v6.scope_id(),
+                    };
+                    bind(fd, &raw as *const SockAddrIn6 as *const c_void, size_of::<SockAddrIn6>() as u32)
+                }
+            }
+        };
+
+        if result < 0 {
+            let error = Error::last_os_error();
+            close(fd);
+            return Err(error);
+        }
+
+        Ok(fd)
+    }
+}
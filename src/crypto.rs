@@ -0,0 +1,326 @@
+use std::io::{self, Read};
+
+// the handful of primitives token verification and content ETags need, kept in-tree to stay
+// dependency free
+pub struct Crypto;
+
+impl Crypto {
+    const SHA256_INIT: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    ];
+    const SHA256_K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+    ];
+    // DER encoded AlgorithmIdentifier for SHA-256 that prefixes the digest in PKCS#1 v1.5
+    const SHA256_DIGEST_INFO: [u8; 19] = [
+        0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00,
+        0x04, 0x20,
+    ];
+    const MAX_RSA_BYTES: usize = 1024; // 8192 bit keys
+
+    pub fn sha256(data: &[u8]) -> [u8; 32] {
+        let mut state = Self::SHA256_INIT;
+        let blocks = data.len() / 64 * 64;
+        for block in data[..blocks].chunks(64) {
+            Self::sha256_block(&mut state, block);
+        }
+        Self::sha256_finish(state, &data[blocks..], data.len() as u64)
+    }
+
+    // the same digest read a block at a time, for files too big to hold in memory
+    pub fn sha256_reader<R: Read>(mut reader: R) -> io::Result<[u8; 32]> {
+        let mut state = Self::SHA256_INIT;
+        let mut buffer = vec![0u8; 65536];
+        let (mut filled, mut length) = (0, 0u64);
+        loop {
+            let read = reader.read(&mut buffer[filled..])?;
+            if read == 0 {
+                break;
+            }
+            filled += read;
+            length += read as u64;
+            let blocks = filled / 64 * 64;
+            for block in buffer[..blocks].chunks(64) {
+                Self::sha256_block(&mut state, block);
+            }
+            buffer.copy_within(blocks..filled, 0);
+            filled -= blocks;
+        }
+        Ok(Self::sha256_finish(state, &buffer[..filled], length))
+    }
+
+    // pads the last partial block with the message length in bits
+    fn sha256_finish(mut state: [u32; 8], rest: &[u8], length: u64) -> [u8; 32] {
+        let mut message = rest.to_vec();
+        message.push(0x80);
+        while message.len() % 64 != 56 {
+            message.push(0);
+        }
+        message.extend_from_slice(&(length * 8).to_be_bytes());
+        for block in message.chunks(64) {
+            Self::sha256_block(&mut state, block);
+        }
+
+        let mut digest = [0u8; 32];
+        for (chunk, value) in digest.chunks_mut(4).zip(state) {
+            chunk.copy_from_slice(&value.to_be_bytes());
+        }
+        digest
+    }
+
+    fn sha256_block(state: &mut [u32; 8], block: &[u8]) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+        for (word, k) in w.iter().zip(Self::SHA256_K) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    // RFC 3174, broken for signatures but still what OCSP responders expect to identify a
+    // certificate with
+    pub fn sha1(data: &[u8]) -> [u8; 20] {
+        let mut state: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+        let mut message = data.to_vec();
+        message.push(0x80);
+        while message.len() % 64 != 56 {
+            message.push(0);
+        }
+        message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+        for block in message.chunks(64) {
+            let mut w = [0u32; 80];
+            for (i, word) in block.chunks(4).enumerate() {
+                w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+            }
+            for i in 16..80 {
+                w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+            }
+            let [mut a, mut b, mut c, mut d, mut e] = state;
+            for (i, word) in w.iter().enumerate() {
+                let (f, k) = match i {
+                    0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                    20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                    40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                    _ => (b ^ c ^ d, 0xca62c1d6),
+                };
+                let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+                e = d;
+                d = c;
+                c = b.rotate_left(30);
+                b = a;
+                a = t;
+            }
+            for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+                *value = value.wrapping_add(add);
+            }
+        }
+
+        let mut digest = [0u8; 20];
+        for (chunk, value) in digest.chunks_mut(4).zip(state) {
+            chunk.copy_from_slice(&value.to_be_bytes());
+        }
+        digest
+    }
+
+    // RFC 2104
+    pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+        let mut block = [0u8; 64];
+        if key.len() > 64 {
+            block[..32].copy_from_slice(&Self::sha256(key));
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+        inner.extend_from_slice(data);
+        let mut outer: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+        outer.extend_from_slice(&Self::sha256(&inner));
+        Self::sha256(&outer)
+    }
+
+    // comparing signatures must not stop at the first differing byte
+    pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+    }
+
+    // RSASSA-PKCS1-v1_5 with SHA-256 (RFC 8017 8.2.2), modulus and exponent big-endian
+    pub fn rsa_verify_sha256(modulus: &[u8], exponent: &[u8], message: &[u8], signature: &[u8]) -> bool {
+        let modulus = Self::strip_zeros(modulus);
+        let size = modulus.len();
+        if !(64..=Self::MAX_RSA_BYTES).contains(&size) || signature.len() != size || exponent.is_empty() {
+            return false;
+        }
+        let n = BigUint::from_be_bytes(modulus);
+        let s = BigUint::from_be_bytes(signature);
+        if s.cmp(&n) != std::cmp::Ordering::Less {
+            return false;
+        }
+        let decrypted = s.mod_pow(&BigUint::from_be_bytes(exponent), &n).to_be_bytes(size);
+
+        let digest = Self::sha256(message);
+        let padding = size - 3 - Self::SHA256_DIGEST_INFO.len() - digest.len();
+        let mut expected = vec![0x00, 0x01];
+        expected.extend(std::iter::repeat_n(0xff, padding));
+        expected.push(0x00);
+        expected.extend_from_slice(&Self::SHA256_DIGEST_INFO);
+        expected.extend_from_slice(&digest);
+        Self::constant_time_eq(&decrypted, &expected)
+    }
+
+    fn strip_zeros(bytes: &[u8]) -> &[u8] {
+        let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+        &bytes[start..]
+    }
+}
+
+// little-endian 32 bit limbs, only what modular exponentiation needs
+#[derive(Clone)]
+struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        let mut limbs: Vec<u32> = bytes
+            .rchunks(4)
+            .map(|chunk| chunk.iter().fold(0u32, |limb, b| (limb << 8) | *b as u32))
+            .collect();
+        Self::normalize(&mut limbs);
+        Self { limbs }
+    }
+
+    fn to_be_bytes(&self, size: usize) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self.limbs.iter().rev().flat_map(|limb| limb.to_be_bytes()).collect();
+        if bytes.len() > size {
+            bytes.drain(..bytes.len() - size);
+        }
+        let mut padded = vec![0; size - bytes.len()];
+        padded.extend(bytes);
+        padded
+    }
+
+    fn normalize(limbs: &mut Vec<u32>) {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+    }
+
+    fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(top) => self.limbs.len() * 32 - top.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        self.limbs.get(index / 32).is_some_and(|limb| limb >> (index % 32) & 1 == 1)
+    }
+
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+        for (i, a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.limbs.iter().enumerate() {
+                let product = (*a as u64) * (*b as u64) + limbs[i + j] as u64 + carry;
+                limbs[i + j] = product as u32;
+                carry = product >> 32;
+            }
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+        Self::normalize(&mut limbs);
+        Self { limbs }
+    }
+
+    // shift-and-subtract, slow but simple and only run a couple dozen times per signature
+    fn rem(&self, modulus: &Self) -> Self {
+        let mut remainder = Self { limbs: Vec::with_capacity(modulus.limbs.len() + 1) };
+        for index in (0..self.bits()).rev() {
+            remainder.shift_left_one(self.bit(index));
+            if remainder.cmp(modulus) != std::cmp::Ordering::Less {
+                remainder.sub_assign(modulus);
+            }
+        }
+        remainder
+    }
+
+    fn shift_left_one(&mut self, low_bit: bool) {
+        let mut carry = low_bit as u32;
+        for limb in self.limbs.iter_mut() {
+            let next = *limb >> 31;
+            *limb = (*limb << 1) | carry;
+            carry = next;
+        }
+        if carry != 0 {
+            self.limbs.push(carry);
+        }
+    }
+
+    fn sub_assign(&mut self, other: &Self) {
+        let mut borrow = 0i64;
+        for i in 0..self.limbs.len() {
+            let mut difference = self.limbs[i] as i64 - borrow - *other.limbs.get(i).unwrap_or(&0) as i64;
+            borrow = 0;
+            if difference < 0 {
+                difference += 1 << 32;
+                borrow = 1;
+            }
+            self.limbs[i] = difference as u32;
+        }
+        Self::normalize(&mut self.limbs);
+    }
+
+    fn mod_pow(&self, exponent: &Self, modulus: &Self) -> Self {
+        let mut result = Self { limbs: vec![1] };
+        let base = self.rem(modulus);
+        for index in (0..exponent.bits()).rev() {
+            result = result.mul(&result).rem(modulus);
+            if exponent.bit(index) {
+                result = result.mul(&base).rem(modulus);
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,71 @@
+use crate::crypto::Crypto;
+use crate::multipart::Multipart;
+use crate::request::Request;
+use crate::utils::Utils;
+
+// double-submit tokens for the file manager of --writable listings: the page hands out a
+// random token both in a cookie and in its forms, and a change made from a browser must send
+// it back in both. Another site can make a browser post a form here, but it can read neither
+// the page nor the cookie, which SameSite keeps off cross-site requests anyway. Clients that
+// are not browsers, such as curl, send neither Origin nor Sec-Fetch-Site and need no token
+pub struct Csrf;
+
+impl Csrf {
+    pub const COOKIE: &'static str = "katana_csrf";
+    pub const FIELD: &'static str = "_csrf";
+    pub const HEADER: &'static str = "X-CSRF-Token";
+    // hex digits
+    const LENGTH: usize = 32;
+
+    // the token of the cookie the browser already has, a new one otherwise
+    pub fn token(request: &Request) -> String {
+        match request.cookie(Self::COOKIE).filter(|token| Self::is_valid(token)) {
+            Some(token) => token.to_string(),
+            None => format!("{}{}", Utils::request_id(), Utils::request_id()),
+        }
+    }
+
+    // the Set-Cookie value, without the name, for a token the browser does not have yet
+    pub fn cookie(token: &str, request: &Request) -> Option<String> {
+        if request.cookie(Self::COOKIE) == Some(token) {
+            return None;
+        }
+        let secure = if request.scheme == "https" { "; Secure" } else { "" };
+        Some(format!("{}; Path=/; HttpOnly; SameSite=Strict{}", token, secure))
+    }
+
+    // browsers send Origin with every request that may change something, and Sec-Fetch-Site
+    // since 2020
+    pub fn from_browser(request: &Request) -> bool {
+        request.header("Origin").is_some() || request.header("Sec-Fetch-Site").is_some()
+    }
+
+    // the token is looked for in the header scripts set, then in the fields of a posted form
+    pub fn verify(request: &Request) -> bool {
+        if !Self::from_browser(request) {
+            return true;
+        }
+        let Some(expected) = request.cookie(Self::COOKIE).filter(|token| Self::is_valid(token)) else {
+            return false;
+        };
+        let sent = request
+            .header(Self::HEADER)
+            .map(str::to_string)
+            .or_else(|| request.form_field(Self::FIELD))
+            .or_else(|| Self::multipart_field(request));
+        sent.is_some_and(|sent| Crypto::constant_time_eq(sent.trim().as_bytes(), expected.as_bytes()))
+    }
+
+    fn multipart_field(request: &Request) -> Option<String> {
+        let boundary = request.header("Content-Type").and_then(Multipart::boundary)?;
+        Multipart::parse(&request.body, &boundary)
+            .ok()?
+            .into_iter()
+            .find(|part| part.name == Self::FIELD && part.filename.is_none())
+            .map(|part| String::from_utf8_lossy(&part.data).to_string())
+    }
+
+    fn is_valid(token: &str) -> bool {
+        token.len() == Self::LENGTH && token.bytes().all(|b| b.is_ascii_hexdigit())
+    }
+}
@@ -0,0 +1,83 @@
+use crate::config::Config;
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind};
+use std::process;
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::c_int;
+
+    extern "C" {
+        pub fn fork() -> c_int;
+        pub fn setsid() -> c_int;
+        pub fn dup2(old_fd: c_int, new_fd: c_int) -> c_int;
+    }
+}
+
+pub struct Daemon;
+
+impl Daemon {
+    // must run before any thread is spawned, only the calling thread survives fork()
+    #[cfg(unix)]
+    pub fn start(config: &Config) -> Result<(), Error> {
+        use std::os::unix::io::AsRawFd;
+
+        // open everything first so errors still reach the terminal
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.log_file)?;
+        let null = OpenOptions::new().read(true).open("/dev/null")?;
+
+        match unsafe { sys::fork() } {
+            -1 => return Err(Error::last_os_error()),
+            0 => {}
+            pid => {
+                println!("Katana is running in the background (pid {}), logging to {}", pid, config.log_file.display());
+                process::exit(0);
+            }
+        }
+
+        // new session without a controlling terminal
+        if unsafe { sys::setsid() } == -1 {
+            return Err(Error::last_os_error());
+        }
+
+        unsafe {
+            sys::dup2(null.as_raw_fd(), 0);
+            sys::dup2(log.as_raw_fd(), 1);
+            sys::dup2(log.as_raw_fd(), 2);
+        }
+
+        fs::write(&config.pid_file, format!("{}\n", process::id()))?;
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn start(_config: &Config) -> Result<(), Error> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "daemon mode is only available on unix systems",
+        ))
+    }
+
+    pub fn cleanup(config: &Config) {
+        if config.daemon {
+            let _ = fs::remove_file(&config.pid_file);
+        }
+    }
+
+    pub fn not_running(config: &Config) -> Result<(), Error> {
+        // a live pid file means another daemon owns this log and pid file
+        if let Ok(pid) = fs::read_to_string(&config.pid_file) {
+            let pid = pid.trim();
+            if !pid.is_empty() && fs::metadata(format!("/proc/{}", pid)).is_ok() {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("already running with pid {} ({})", pid, config.pid_file.display()),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
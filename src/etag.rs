@@ -0,0 +1,95 @@
+use crate::crypto::Crypto;
+use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// SHA-256 digests by file, recomputed when the size or modification time changes
+type Hashes = HashMap<PathBuf, (u64, SystemTime, String)>;
+static HASHES: Mutex<Option<Hashes>> = Mutex::new(None);
+
+// how file validators are made, --etag picks one:
+//
+//   weak    W/"size-mtime", free to compute, but the same file on two NFS clients or
+//           rebuilt with a new mtime gets another tag
+//   strong  "sha256 prefix" of the content, read once per change of the file and cached
+//   off     no ETag, clients revalidate with nothing
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ETag {
+    #[default]
+    Weak,
+    Strong,
+    Off,
+}
+
+impl ETag {
+    // bounds the cache, it starts over once full
+    pub const MAX_CACHED: usize = 4096;
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "weak" => Some(ETag::Weak),
+            "strong" => Some(ETag::Strong),
+            "off" => Some(ETag::Off),
+            _ => None,
+        }
+    }
+
+    pub fn for_file(&self, path: &Path, metadata: &Metadata) -> Option<String> {
+        let modified = metadata.modified().ok()?;
+        match self {
+            ETag::Off => None,
+            ETag::Weak => {
+                let mtime = modified.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+                Some(format!("W/\"{:x}-{:x}\"", metadata.len(), mtime))
+            }
+            ETag::Strong => Self::sha256(path, metadata).map(|hash| format!("\"{}\"", &hash[..32])),
+        }
+    }
+
+    // the hex SHA-256 of a file, also served as its checksum
+    pub fn sha256(path: &Path, metadata: &Metadata) -> Option<String> {
+        let (size, modified) = (metadata.len(), metadata.modified().ok()?);
+        if let Some(cache) = HASHES.lock().unwrap().as_ref() {
+            if let Some((_, _, hash)) = cache.get(path).filter(|(s, m, _)| *s == size && *m == modified) {
+                return Some(hash.clone());
+            }
+        }
+        // hashed without the lock, two requests racing on a new file both read it
+        let digest = Crypto::sha256_reader(File::open(path).ok()?).ok()?;
+        let hash: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+
+        let mut cache = HASHES.lock().unwrap();
+        let cache = cache.get_or_insert_with(HashMap::new);
+        if cache.len() >= Self::MAX_CACHED {
+            cache.clear();
+        }
+        cache.insert(path.to_path_buf(), (size, modified, hash.clone()));
+        Some(hash)
+    }
+
+    // a body rewritten on the way out, gzipped or with a snippet added, is no longer the
+    // same bytes as the file but still means the same
+    pub fn weaken(tag: &str) -> String {
+        if tag.starts_with("W/") {
+            tag.to_string()
+        } else {
+            format!("W/{}", tag)
+        }
+    }
+
+    // If-Match uses the strong comparison, a weak tag never matches (RFC 9110 13.1.1), so
+    // conditional writes need --etag strong
+    pub fn matches_strong(if_match: &str, tag: &str) -> bool {
+        let strong = |tag: &str| !tag.trim().starts_with("W/");
+        if_match.trim() == "*" || (strong(tag) && if_match.split(',').any(|candidate| strong(candidate) && candidate.trim() == tag.trim()))
+    }
+
+    // If-None-Match uses the weak comparison, W/ is ignored on both sides (RFC 9110 13.1.2)
+    pub fn matches(if_none_match: &str, tag: &str) -> bool {
+        let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        if_none_match.trim() == "*" || if_none_match.split(',').any(|candidate| opaque(candidate) == opaque(tag))
+    }
+}
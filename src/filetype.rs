@@ -64,6 +64,24 @@ impl FileType {
         Self::from_extension(extension).is_some()
     }
 
+    // shown next to the name in directory listings
+    pub fn icon(&self) -> &'static str {
+        let content_type = self.content_type.as_str();
+        match content_type.split('/').next().unwrap_or_default() {
+            "image" => "\u{1F5BC}",
+            "video" => "\u{1F3AC}",
+            "audio" => "\u{1F3B5}",
+            "font" => "\u{1F524}",
+            "text" => "\u{1F4DD}",
+            _ => match content_type {
+                "application/zip" | "application/x-tar" | "application/x-iso9660-image" => "\u{1F4E6}",
+                "application/pdf" => "\u{1F4D5}",
+                "application/json" | "application/xml" | "application/javascript" => "\u{1F4DD}",
+                _ => "\u{1F4C4}",
+            },
+        }
+    }
+
     pub fn content_disposition(&self) -> &'static str {
         // why ? see https://stackoverflow.com/a/1395173/13158370
         "inline"
@@ -0,0 +1,81 @@
+// what answers a request for a file, chosen by its extension with --handler ext=handler:
+//
+//   static             the file is sent as it is, the default
+//   cgi                the file is run as a CGI/1.1 script
+//   fastcgi:<address>  the request goes to a FastCGI responder, such as php-fpm, on
+//                      host:port or unix:/path
+//   deny               always a 403, whether the file exists or not
+//
+//   --handler php=fastcgi:127.0.0.1:9000 --handler cgi=cgi --handler bak=deny
+//
+// The extension is what follows the last dot of the file name, so .env and production.env
+// both have env, which is denied unless a rule says otherwise
+#[derive(Debug, Clone, PartialEq)]
+pub enum Handler {
+    Static,
+    Cgi,
+    FastCgi(String),
+    Deny,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Handlers {
+    // later rules win, the defaults come first
+    rules: Vec<(String, Handler)>,
+}
+
+impl Default for Handlers {
+    fn default() -> Self {
+        Self {
+            rules: Self::DENIED.iter().map(|extension| (extension.to_string(), Handler::Deny)).collect(),
+        }
+    }
+}
+
+impl Handlers {
+    pub const DENIED: &'static [&'static str] = &["env"];
+
+    // ext=handler, the extension without its dot
+    pub fn add(&mut self, rule: &str) -> Result<(), String> {
+        let (extension, handler) = rule
+            .split_once('=')
+            .map(|(extension, handler)| (extension.trim().trim_start_matches('.').to_lowercase(), handler.trim()))
+            .filter(|(extension, _)| !extension.is_empty() && !extension.contains(['.', '/']))
+            .ok_or_else(|| format!("handler must be ext=static|cgi|fastcgi:address|deny: {}", rule))?;
+        let handler = match handler.split_once(':') {
+            Some((kind, address)) if kind.eq_ignore_ascii_case("fastcgi") && !address.trim().is_empty() => {
+                Handler::FastCgi(address.trim().to_string())
+            }
+            _ => match handler.to_lowercase().as_str() {
+                "static" => Handler::Static,
+                "cgi" => Handler::Cgi,
+                "deny" => Handler::Deny,
+                _ => return Err(format!("handler must be static, cgi, fastcgi:address or deny: {}", handler)),
+            },
+        };
+        self.rules.push((extension, handler));
+        Ok(())
+    }
+
+    // whether an extension is left to a CGI script or a FastCGI server, the rule of an
+    // extension being the last one naming it
+    pub fn runs_programs(&self) -> bool {
+        self.rules.iter().enumerate().any(|(i, (extension, handler))| {
+            matches!(handler, Handler::Cgi | Handler::FastCgi(_))
+                && !self.rules[i + 1..].iter().any(|(later, _)| later == extension)
+        })
+    }
+
+    // for the last segment of a request path, static when no rule names its extension
+    pub fn for_path(&self, path: &str) -> &Handler {
+        let name = path.rsplit('/').next().unwrap_or_default();
+        let Some((_, extension)) = name.rsplit_once('.') else {
+            return &Handler::Static;
+        };
+        self.rules
+            .iter()
+            .rev()
+            .find(|(rule, _)| rule.eq_ignore_ascii_case(extension))
+            .map_or(&Handler::Static, |(_, handler)| handler)
+    }
+}
@@ -0,0 +1,123 @@
+use crate::logger::Logger;
+use std::io::Write;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookEvent {
+    Request,
+    Upload,
+    ServerError,
+}
+
+impl HookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookEvent::Request => "request",
+            HookEvent::Upload => "upload",
+            HookEvent::ServerError => "server_error",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "request" => Some(HookEvent::Request),
+            "upload" => Some(HookEvent::Upload),
+            "server_error" | "error" => Some(HookEvent::ServerError),
+            _ => None,
+        }
+    }
+}
+
+// commands run through the shell on events, with the event as a JSON object on stdin, e.g.
+// --hook 'upload=./scan.sh'. They run in the background so no request waits for them, and
+// are killed once --hook-timeout is up
+#[derive(Debug, Clone)]
+pub struct Hooks {
+    pub commands: Vec<(HookEvent, String)>,
+    pub timeout: Duration,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Self {
+            commands: Vec::new(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+impl Hooks {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+    const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+    // event=command
+    pub fn parse(value: &str) -> Result<(HookEvent, String), String> {
+        let (event, command) = value
+            .split_once('=')
+            .ok_or_else(|| format!("hook must be event=command: {}", value))?;
+        let event = HookEvent::from_str(event)
+            .ok_or_else(|| format!("hook event must be request, upload or server_error: {}", event))?;
+        if command.trim().is_empty() {
+            return Err(format!("hook for {} has no command", event.as_str()));
+        }
+        Ok((event, command.trim().to_string()))
+    }
+
+    pub fn has(&self, event: HookEvent) -> bool {
+        self.commands.iter().any(|(hooked, _)| *hooked == event)
+    }
+
+    // the payload is only built when a command listens, see has()
+    pub fn fire(&self, event: HookEvent, payload: &str) {
+        for (_, command) in self.commands.iter().filter(|(hooked, _)| *hooked == event) {
+            let (command, payload, timeout) = (command.clone(), payload.to_string(), self.timeout);
+            thread::spawn(move || match Self::run(&command, &payload, timeout) {
+                Ok(status) if status.success() => {}
+                Ok(status) => Logger::warn(format!("Hook `{}` exited with {}", command, status).as_str()),
+                Err(e) => Logger::warn(format!("Hook `{}` failed: {}", command, e).as_str()),
+            });
+        }
+    }
+
+    // blocks until the command exits or the timeout kills it
+    pub fn run(command: &str, payload: &str, timeout: Duration) -> Result<ExitStatus, String> {
+        let mut child = Self::shell(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| e.to_string())?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // a command that does not read its input is not an error
+            let _ = stdin.write_all(payload.as_bytes());
+        }
+
+        let started = Instant::now();
+        loop {
+            if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+                return Ok(status);
+            }
+            if started.elapsed() >= timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("killed after {}ms", timeout.as_millis()));
+            }
+            thread::sleep(Self::POLL_INTERVAL);
+        }
+    }
+
+    fn shell(command: &str) -> Command {
+        if cfg!(target_family = "windows") {
+            let mut shell = Command::new("cmd");
+            shell.args(["/C", command]);
+            shell
+        } else {
+            let mut shell = Command::new("sh");
+            shell.args(["-c", command]);
+            shell
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use crate::request::Request;
+
+// media files only shown to pages of this site and the allowed ones, so other sites cannot
+// embed them straight from a public share. Requests for the configured extensions whose
+// Referer names another host get a 403, or a redirect to --hotlink-redirect
+#[derive(Debug, Clone, Default)]
+pub struct Hotlink {
+    // lowercase, without the dot
+    pub extensions: Vec<String>,
+    // hosts besides the requested one, *.example.com covers a single label
+    pub allowed: Vec<String>,
+    // requests without a Referer are refused too, some privacy tools strip it
+    pub block_empty: bool,
+    pub redirect: Option<String>,
+}
+
+impl Hotlink {
+    pub fn enabled(&self) -> bool {
+        !self.extensions.is_empty()
+    }
+
+    pub fn protects(&self, path: &str) -> bool {
+        let name = path.rsplit('/').next().unwrap_or_default();
+        name.rsplit_once('.')
+            .is_some_and(|(_, extension)| self.extensions.iter().any(|protected| protected.eq_ignore_ascii_case(extension)))
+    }
+
+    // whether a request for a protected file may be answered
+    pub fn allows(&self, request: &Request) -> bool {
+        let Some(referer) = request.header("Referer").map(str::trim).filter(|referer| !referer.is_empty()) else {
+            return !self.block_empty;
+        };
+        let Some(referer_host) = Self::host(referer) else {
+            return false;
+        };
+        let own = request.host().unwrap_or_default().to_lowercase();
+        referer_host == own
+            || self.allowed.iter().any(|allowed| {
+                allowed == &referer_host
+                    || allowed
+                        .strip_prefix("*.")
+                        .is_some_and(|parent| referer_host.split_once('.').is_some_and(|(_, rest)| rest == parent))
+            })
+    }
+
+    // the host of an absolute URL, lowercase and without port or credentials
+    pub fn host(url: &str) -> Option<String> {
+        let (_, rest) = url.split_once("://")?;
+        let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        let authority = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+        let host = Request::host_name(authority).to_lowercase();
+        (!host.is_empty()).then_some(host)
+    }
+}
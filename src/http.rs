@@ -16,6 +16,7 @@ impl HttpVersion {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(version_str: &str) -> Option<HttpVersion> {
         match version_str {
             "1.0" => Some(HttpVersion::Http10),
@@ -27,7 +28,7 @@ impl HttpVersion {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum HttpStatus {
     // Informational responses (100–199)
     Continue = 100,
@@ -106,6 +107,74 @@ impl HttpStatus {
         *self as u16
     }
 
+    // only the codes listed above, e.g. what a CGI script puts in its Status header
+    pub fn from_code(code: u16) -> Option<Self> {
+        match code {
+            100 => Some(HttpStatus::Continue),
+            101 => Some(HttpStatus::SwitchingProtocols),
+            102 => Some(HttpStatus::Processing),
+            103 => Some(HttpStatus::EarlyHints),
+            200 => Some(HttpStatus::Ok),
+            201 => Some(HttpStatus::Created),
+            202 => Some(HttpStatus::Accepted),
+            203 => Some(HttpStatus::NonAuthoritativeInformation),
+            204 => Some(HttpStatus::NoContent),
+            205 => Some(HttpStatus::ResetContent),
+            206 => Some(HttpStatus::PartialContent),
+            207 => Some(HttpStatus::MultiStatus),
+            208 => Some(HttpStatus::AlreadyReported),
+            226 => Some(HttpStatus::IMUsed),
+            301 => Some(HttpStatus::MovedPermanently),
+            302 => Some(HttpStatus::Found),
+            303 => Some(HttpStatus::SeeOther),
+            304 => Some(HttpStatus::NotModified),
+            305 => Some(HttpStatus::UseProxy),
+            307 => Some(HttpStatus::TemporaryRedirect),
+            308 => Some(HttpStatus::PermanentRedirect),
+            400 => Some(HttpStatus::BadRequest),
+            401 => Some(HttpStatus::Unauthorized),
+            402 => Some(HttpStatus::PaymentRequired),
+            403 => Some(HttpStatus::Forbidden),
+            404 => Some(HttpStatus::NotFound),
+            405 => Some(HttpStatus::MethodNotAllowed),
+            406 => Some(HttpStatus::NotAcceptable),
+            407 => Some(HttpStatus::ProxyAuthenticationRequired),
+            408 => Some(HttpStatus::RequestTimeout),
+            409 => Some(HttpStatus::Conflict),
+            410 => Some(HttpStatus::Gone),
+            411 => Some(HttpStatus::LengthRequired),
+            412 => Some(HttpStatus::PreconditionFailed),
+            413 => Some(HttpStatus::PayloadTooLarge),
+            414 => Some(HttpStatus::URITooLong),
+            415 => Some(HttpStatus::UnsupportedMediaType),
+            416 => Some(HttpStatus::RangeNotSatisfiable),
+            417 => Some(HttpStatus::ExpectationFailed),
+            418 => Some(HttpStatus::ImATeapot),
+            421 => Some(HttpStatus::MisdirectedRequest),
+            422 => Some(HttpStatus::UnprocessableEntity),
+            423 => Some(HttpStatus::Locked),
+            424 => Some(HttpStatus::FailedDependency),
+            425 => Some(HttpStatus::TooEarly),
+            426 => Some(HttpStatus::UpgradeRequired),
+            428 => Some(HttpStatus::PreconditionRequired),
+            429 => Some(HttpStatus::TooManyRequests),
+            431 => Some(HttpStatus::RequestHeaderFieldsTooLarge),
+            451 => Some(HttpStatus::UnavailableForLegalReasons),
+            500 => Some(HttpStatus::InternalServerError),
+            501 => Some(HttpStatus::NotImplemented),
+            502 => Some(HttpStatus::BadGateway),
+            503 => Some(HttpStatus::ServiceUnavailable),
+            504 => Some(HttpStatus::GatewayTimeout),
+            505 => Some(HttpStatus::HTTPVersionNotSupported),
+            506 => Some(HttpStatus::VariantAlsoNegotiates),
+            507 => Some(HttpStatus::InsufficientStorage),
+            508 => Some(HttpStatus::LoopDetected),
+            510 => Some(HttpStatus::NotExtended),
+            511 => Some(HttpStatus::NetworkAuthenticationRequired),
+            _ => None,
+        }
+    }
+
     pub fn to_message(&self) -> &str {
         match self {
             // Informational responses (100–199)
@@ -193,6 +262,8 @@ pub enum HttpMethod {
     OPTIONS,
     TRACE,
     PATCH,
+    // WebDAV (RFC 4918 9.9), renames and moves in --writable mode
+    MOVE,
 }
 
 impl HttpMethod {
@@ -207,9 +278,11 @@ impl HttpMethod {
             Self::OPTIONS => "OPTIONS",
             Self::TRACE => "TRACE",
             Self::PATCH => "PATCH",
+            Self::MOVE => "MOVE",
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(method_str: &str) -> Option<Self> {
         match method_str {
             "GET" => Some(Self::GET),
@@ -221,6 +294,7 @@ impl HttpMethod {
             "OPTIONS" => Some(Self::OPTIONS),
             "TRACE" => Some(Self::TRACE),
             "PATCH" => Some(Self::PATCH),
+            "MOVE" => Some(Self::MOVE),
             _ => None, // none for unsupported version
         }
     }
@@ -236,6 +310,7 @@ impl HttpMethod {
             Self::OPTIONS,
             Self::TRACE,
             Self::PATCH,
+            Self::MOVE,
         ]
     }
 
@@ -250,6 +325,7 @@ impl HttpMethod {
             Self::DELETE,
             Self::PATCH,
             Self::CONNECT,
+            Self::MOVE,
         ]
     }
 
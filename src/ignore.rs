@@ -0,0 +1,150 @@
+use std::path::Path;
+
+// files kept out of listings and searches and answered with a 404, in the format of
+// .gitignore: patterns from --ignore come first, then the lines of .katanaignore at the root
+//
+//   # .katanaignore
+//   node_modules/
+//   *.log
+//   /build
+//   secrets/**
+//   !keep.log
+//
+// a pattern with a slash is anchored to the root, one without matches names at any depth, a
+// trailing slash only matches directories and ! brings a file back. As with git, nothing below
+// an ignored directory can be brought back
+#[derive(Debug, Clone, Default)]
+pub struct Ignore {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl Ignore {
+    pub const FILE: &'static str = ".katanaignore";
+
+    pub fn new(patterns: &[String]) -> Self {
+        let mut ignore = Self::default();
+        for pattern in patterns {
+            ignore.add(pattern);
+        }
+        ignore
+    }
+
+    // the patterns given and those of the ignore file in root, if there is one
+    pub fn load(root: &Path, patterns: &[String]) -> Self {
+        let mut ignore = Self::new(patterns);
+        if let Ok(content) = std::fs::read_to_string(root.join(Self::FILE)) {
+            for line in content.lines() {
+                ignore.add(line);
+            }
+        }
+        ignore
+    }
+
+    // blank lines and # comments are skipped, \# and \! escape a leading character
+    pub fn add(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let dir_only = line.ends_with('/');
+        let line = line.trim_end_matches('/');
+        let anchored = line.contains('/');
+        let pattern = line.trim_start_matches('/').to_string();
+        if pattern.is_empty() {
+            return;
+        }
+        self.rules.push(Rule {
+            pattern,
+            negated,
+            dir_only,
+            anchored,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // path is relative to the root, with or without a leading slash
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let path = path.replace('\\', "/");
+        let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+        (1..=segments.len()).any(|length| {
+            let last = length == segments.len();
+            self.matches(&segments[..length].join("/"), segments[length - 1], !last || is_dir)
+        })
+    }
+
+    // the last matching rule decides
+    fn matches(&self, path: &str, name: &str, is_dir: bool) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && Self::glob(&rule.pattern, if rule.anchored { path } else { name }))
+            .is_some_and(|rule| !rule.negated)
+    }
+
+    // * and ? stop at slashes, ** crosses them, [abc] and [a-z] match one character
+    pub fn glob(pattern: &str, text: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+        Self::glob_at(&pattern, &text)
+    }
+
+    fn glob_at(pattern: &[char], text: &[char]) -> bool {
+        match pattern.first() {
+            None => text.is_empty(),
+            Some('*') if pattern.get(1) == Some(&'*') => {
+                // **/ also matches no directory at all
+                let rest = &pattern[2..];
+                let rest_after_slash = rest.strip_prefix(&['/']).unwrap_or(rest);
+                (0..=text.len()).any(|skip| Self::glob_at(rest, &text[skip..]) || Self::glob_at(rest_after_slash, &text[skip..]))
+            }
+            Some('*') => (0..=text.len())
+                .take_while(|skip| *skip == 0 || text[skip - 1] != '/')
+                .any(|skip| Self::glob_at(&pattern[1..], &text[skip..])),
+            Some('?') => text.first().is_some_and(|c| *c != '/') && Self::glob_at(&pattern[1..], &text[1..]),
+            Some('[') => {
+                let Some(close) = pattern.iter().skip(1).position(|c| *c == ']').map(|index| index + 1) else {
+                    return text.first() == Some(&'[') && Self::glob_at(&pattern[1..], &text[1..]);
+                };
+                let Some(c) = text.first() else {
+                    return false;
+                };
+                let class = &pattern[1..close];
+                let (negated, class) = match class.first() {
+                    Some('!' | '^') => (true, &class[1..]),
+                    _ => (false, class),
+                };
+                let mut found = false;
+                let mut index = 0;
+                while index < class.len() {
+                    if index + 2 < class.len() && class[index + 1] == '-' {
+                        found |= (class[index]..=class[index + 2]).contains(c);
+                        index += 3;
+                    } else {
+                        found |= class[index] == *c;
+                        index += 1;
+                    }
+                }
+                found != negated && *c != '/' && Self::glob_at(&pattern[close + 1..], &text[1..])
+            }
+            Some(literal) => text.first() == Some(literal) && Self::glob_at(&pattern[1..], &text[1..]),
+        }
+    }
+}
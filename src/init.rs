@@ -0,0 +1,49 @@
+use crate::templates::Templates;
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+// `katana init`: a katana.toml listing the options, all commented out, to start a site from,
+// and with --with-templates copies of the built-in pages to edit. Files already there are
+// never overwritten
+pub struct Init;
+
+impl Init {
+    pub const CONFIG_FILE: &'static str = "katana.toml";
+    pub const CONFIG: &'static str = include_str!("../templates/katana.toml");
+    pub const TEMPLATES_DIR: &'static str = "templates";
+
+    // the files created under dir, then those left as they were
+    pub fn run(dir: &Path, with_templates: bool) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Error> {
+        let mut files = vec![(dir.join(Self::CONFIG_FILE), Self::CONFIG.to_string())];
+        if with_templates {
+            let templates = Templates::load();
+            let templates_dir = dir.join(Self::TEMPLATES_DIR);
+            files.push((templates_dir.join("banner.txt"), templates.banner));
+            files.push((templates_dir.join("error.html"), templates.error));
+            files.push((templates_dir.join("directory.html"), templates.directory));
+            files.push((templates_dir.join("status.html"), templates.status));
+            for (language, content) in Templates::BUILT_IN_MESSAGES {
+                if *language == Templates::DEFAULT_LANGUAGE {
+                    files.push((templates_dir.join("messages").join(format!("{}.txt", language)), content.to_string()));
+                }
+            }
+        }
+
+        let (mut created, mut kept) = (Vec::new(), Vec::new());
+        for (path, content) in files {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(content.as_bytes())?;
+                    created.push(path);
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => kept.push(path),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok((created, kept))
+    }
+}
@@ -0,0 +1,213 @@
+// just enough JSON to read tokens and key sets, objects keep their keys in order
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    const MAX_DEPTH: usize = 32;
+
+    pub fn parse(input: &str) -> Option<Json> {
+        let mut parser = Parser { bytes: input.as_bytes(), position: 0 };
+        let value = parser.value(0)?;
+        parser.whitespace();
+        if parser.position == parser.bytes.len() {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self, depth: usize) -> Option<Json> {
+        if depth > Json::MAX_DEPTH {
+            return None;
+        }
+        self.whitespace();
+        match *self.bytes.get(self.position)? {
+            b'{' => self.object(depth),
+            b'[' => self.array(depth),
+            b'"' => self.string().map(Json::String),
+            b't' => self.literal("true", Json::Bool(true)),
+            b'f' => self.literal("false", Json::Bool(false)),
+            b'n' => self.literal("null", Json::Null),
+            _ => self.number(),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Option<Json> {
+        self.position += 1;
+        let mut members = Vec::new();
+        self.whitespace();
+        if self.eat(b'}') {
+            return Some(Json::Object(members));
+        }
+        loop {
+            self.whitespace();
+            if self.bytes.get(self.position) != Some(&b'"') {
+                return None;
+            }
+            let key = self.string()?;
+            self.whitespace();
+            if !self.eat(b':') {
+                return None;
+            }
+            members.push((key, self.value(depth + 1)?));
+            self.whitespace();
+            if self.eat(b'}') {
+                return Some(Json::Object(members));
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Option<Json> {
+        self.position += 1;
+        let mut values = Vec::new();
+        self.whitespace();
+        if self.eat(b']') {
+            return Some(Json::Array(values));
+        }
+        loop {
+            values.push(self.value(depth + 1)?);
+            self.whitespace();
+            if self.eat(b']') {
+                return Some(Json::Array(values));
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            let start = self.position;
+            while !matches!(self.bytes.get(self.position), Some(b'"' | b'\\') | None) {
+                self.position += 1;
+            }
+            value.push_str(std::str::from_utf8(&self.bytes[start..self.position]).ok()?);
+            match *self.bytes.get(self.position)? {
+                b'"' => {
+                    self.position += 1;
+                    return Some(value);
+                }
+                _ => {
+                    let escape = *self.bytes.get(self.position + 1)?;
+                    self.position += 2;
+                    match escape {
+                        b'"' => value.push('"'),
+                        b'\\' => value.push('\\'),
+                        b'/' => value.push('/'),
+                        b'b' => value.push('\u{8}'),
+                        b'f' => value.push('\u{c}'),
+                        b'n' => value.push('\n'),
+                        b'r' => value.push('\r'),
+                        b't' => value.push('\t'),
+                        b'u' => value.push(self.unicode_escape()?),
+                        _ => return None,
+                    }
+                }
+            }
+        }
+    }
+
+    // \uXXXX, with surrogate pairs for characters outside the basic plane
+    fn unicode_escape(&mut self) -> Option<char> {
+        let high = self.hex4()?;
+        if !(0xd800..0xdc00).contains(&high) {
+            return char::from_u32(high);
+        }
+        if self.bytes.get(self.position..self.position + 2) != Some(b"\\u") {
+            return None;
+        }
+        self.position += 2;
+        let low = self.hex4()?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return None;
+        }
+        char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = std::str::from_utf8(self.bytes.get(self.position..self.position + 4)?).ok()?;
+        self.position += 4;
+        u32::from_str_radix(digits, 16).ok()
+    }
+
+    fn number(&mut self) -> Option<Json> {
+        let start = self.position;
+        while matches!(self.bytes.get(self.position), Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E')) {
+            self.position += 1;
+        }
+        let number = std::str::from_utf8(&self.bytes[start..self.position]).ok()?;
+        number.parse::<f64>().ok().filter(|n| n.is_finite()).map(Json::Number)
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Option<Json> {
+        if self.bytes[self.position..].starts_with(word.as_bytes()) {
+            self.position += word.len();
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.bytes.get(self.position) == Some(&byte) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.bytes.get(self.position), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.position += 1;
+        }
+    }
+}
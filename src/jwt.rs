@@ -0,0 +1,203 @@
+use crate::crypto::Crypto;
+use crate::http::HttpStatus;
+use crate::json::Json;
+use crate::utils::Utils;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// a verification key, the algorithm is tied to the key so an RSA public key can never be
+// used as an HMAC secret
+#[derive(Debug, Clone, PartialEq)]
+pub enum JwtKey {
+    Hmac(Vec<u8>),
+    Rsa {
+        kid: Option<String>,
+        modulus: Vec<u8>,
+        exponent: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JwtError {
+    Missing,
+    Malformed,
+    UnsupportedAlgorithm(String),
+    BadSignature,
+    Expired,
+    NotYetValid,
+    WrongIssuer,
+    WrongAudience,
+}
+
+// the settings protected paths are checked against
+#[derive(Debug, Clone, Default)]
+pub struct JwtAuth {
+    pub keys: Vec<JwtKey>,
+    pub audience: Option<String>,
+    pub issuer: Option<String>,
+    pub protected: Vec<String>,
+}
+
+impl JwtKey {
+    // a JWK Set (RFC 7517 5), only RSA signing keys are kept
+    pub fn from_jwks(content: &str) -> Result<Vec<JwtKey>, String> {
+        let jwks = Json::parse(content).ok_or("not valid JSON")?;
+        let keys = jwks.get("keys").and_then(Json::as_array).ok_or("no \"keys\" array")?;
+
+        let mut rsa = Vec::new();
+        for key in keys {
+            let is_signing = key.get("use").and_then(Json::as_str).is_none_or(|usage| usage == "sig");
+            if key.get("kty").and_then(Json::as_str) != Some("RSA") || !is_signing {
+                continue;
+            }
+            let field = |name: &str| key.get(name).and_then(Json::as_str).and_then(Utils::base64url_decode);
+            match (field("n"), field("e")) {
+                (Some(modulus), Some(exponent)) => rsa.push(JwtKey::Rsa {
+                    kid: key.get("kid").and_then(Json::as_str).map(str::to_string),
+                    modulus,
+                    exponent,
+                }),
+                _ => return Err("RSA key without a valid \"n\" or \"e\"".to_string()),
+            }
+        }
+        if rsa.is_empty() {
+            return Err("no RSA signing key".to_string());
+        }
+        Ok(rsa)
+    }
+
+    pub fn load_jwks(path: &Path) -> Result<Vec<JwtKey>, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("cannot read JWKS {}: {}", path.display(), e))?;
+        Self::from_jwks(&content).map_err(|e| format!("invalid JWKS {}: {}", path.display(), e))
+    }
+}
+
+impl JwtError {
+    // RFC 6750 3.1: a token for someone else is valid but not enough, everything else is invalid
+    pub fn status(&self) -> HttpStatus {
+        match self {
+            JwtError::WrongAudience => HttpStatus::Forbidden,
+            _ => HttpStatus::Unauthorized,
+        }
+    }
+
+    pub fn challenge(&self) -> String {
+        match self {
+            JwtError::Missing => "Bearer realm=\"katana\"".to_string(),
+            JwtError::WrongAudience => {
+                format!("Bearer realm=\"katana\", error=\"insufficient_scope\", error_description=\"{}\"", self)
+            }
+            _ => format!("Bearer realm=\"katana\", error=\"invalid_token\", error_description=\"{}\"", self),
+        }
+    }
+}
+
+impl fmt::Display for JwtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JwtError::Missing => write!(f, "no bearer token"),
+            JwtError::Malformed => write!(f, "malformed token"),
+            JwtError::UnsupportedAlgorithm(alg) => write!(f, "unsupported algorithm {}", alg),
+            JwtError::BadSignature => write!(f, "invalid signature"),
+            JwtError::Expired => write!(f, "token expired"),
+            JwtError::NotYetValid => write!(f, "token not valid yet"),
+            JwtError::WrongIssuer => write!(f, "unexpected issuer"),
+            JwtError::WrongAudience => write!(f, "token not meant for this audience"),
+        }
+    }
+}
+
+impl JwtAuth {
+    // accepted clock difference between the issuer and us, in seconds
+    pub const LEEWAY: f64 = 60.0;
+
+    pub fn enabled(&self) -> bool {
+        !self.protected.is_empty()
+    }
+
+    pub fn protects(&self, path: &str) -> bool {
+        self.protected.iter().any(|prefix| Utils::under_prefix(path, prefix))
+    }
+
+    // the Authorization header value, returns the claims of a token that passed every check
+    pub fn authorize(&self, authorization: Option<&str>, now: u64) -> Result<Json, JwtError> {
+        let token = authorization
+            .and_then(|value| value.trim().split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim())
+            .ok_or(JwtError::Missing)?;
+        let claims = self.verify(token)?;
+        self.check_claims(&claims, now as f64)?;
+        Ok(claims)
+    }
+
+    pub fn verify(&self, token: &str) -> Result<Json, JwtError> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(JwtError::Malformed);
+        };
+        let decode = |part: &str| {
+            let bytes = Utils::base64url_decode(part)?;
+            Json::parse(std::str::from_utf8(&bytes).ok()?)
+        };
+        let header_json = decode(header).ok_or(JwtError::Malformed)?;
+        let claims = decode(payload).filter(|claims| matches!(claims, Json::Object(_))).ok_or(JwtError::Malformed)?;
+        let signature = Utils::base64url_decode(signature).ok_or(JwtError::Malformed)?;
+
+        let signed = &token[..header.len() + 1 + payload.len()];
+        let kid = header_json.get("kid").and_then(Json::as_str);
+        let valid = match header_json.get("alg").and_then(Json::as_str) {
+            Some("HS256") => self.keys.iter().any(|key| match key {
+                JwtKey::Hmac(secret) => Crypto::constant_time_eq(&Crypto::hmac_sha256(secret, signed.as_bytes()), &signature),
+                _ => false,
+            }),
+            Some("RS256") => self.keys.iter().any(|key| match key {
+                JwtKey::Rsa { kid: key_id, modulus, exponent } => {
+                    // a token naming its key is only checked against that key
+                    let candidate = kid.is_none() || key_id.is_none() || key_id.as_deref() == kid;
+                    candidate && Crypto::rsa_verify_sha256(modulus, exponent, signed.as_bytes(), &signature)
+                }
+                _ => false,
+            }),
+            Some(alg) => return Err(JwtError::UnsupportedAlgorithm(alg.to_string())),
+            None => return Err(JwtError::Malformed),
+        };
+        if valid {
+            Ok(claims)
+        } else {
+            Err(JwtError::BadSignature)
+        }
+    }
+
+    fn check_claims(&self, claims: &Json, now: f64) -> Result<(), JwtError> {
+        let time = |name: &str| match claims.get(name) {
+            None => Ok(None),
+            Some(value) => value.as_f64().map(Some).ok_or(JwtError::Malformed),
+        };
+        if time("exp")?.is_some_and(|exp| now >= exp + Self::LEEWAY) {
+            return Err(JwtError::Expired);
+        }
+        if time("nbf")?.is_some_and(|nbf| now + Self::LEEWAY < nbf) {
+            return Err(JwtError::NotYetValid);
+        }
+        if let Some(issuer) = &self.issuer {
+            if claims.get("iss").and_then(Json::as_str) != Some(issuer.as_str()) {
+                return Err(JwtError::WrongIssuer);
+            }
+        }
+        if let Some(audience) = &self.audience {
+            // aud is a single string or an array of them (RFC 7519 4.1.3)
+            let matches = match claims.get("aud") {
+                Some(Json::String(aud)) => aud == audience,
+                Some(Json::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience.as_str())),
+                _ => false,
+            };
+            if !matches {
+                return Err(JwtError::WrongAudience);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// localized copies of a page sit next to it as <stem>.<language>.<extension>, e.g.
+// index.en.html and index.fr-CA.html for index.html
+pub struct Language;
+
+impl Language {
+    // only pages, app.min.js is a minified script and not Minangkabau
+    pub const PAGE_EXTENSIONS: &'static [&'static str] = &["html", "htm"];
+
+    // language-range "en-US;q=0.8" entries, highest weight first, refused ones left out
+    pub fn parse_accept(accept_language: &str) -> Vec<(String, f32)> {
+        let mut ranges: Vec<(String, f32)> = accept_language
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';').map(str::trim);
+                let range = parts.next().filter(|range| !range.is_empty())?;
+                let q = parts
+                    .filter_map(|parameter| parameter.split_once('='))
+                    .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                    .map_or(Some(1.0), |(_, value)| value.trim().parse::<f32>().ok())?;
+                Some((range.to_lowercase(), q))
+            })
+            .filter(|(_, q)| *q > 0.0 && *q <= 1.0)
+            .collect();
+        // stable, so equal weights keep the client's order
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranges
+    }
+
+    pub fn is_tag(value: &str) -> bool {
+        let mut subtags = value.split('-');
+        let primary = subtags.next().unwrap_or_default();
+        (2..=3).contains(&primary.len())
+            && primary.bytes().all(|b| b.is_ascii_alphabetic())
+            && subtags.all(|subtag| (2..=8).contains(&subtag.len()) && subtag.bytes().all(|b| b.is_ascii_alphanumeric()))
+    }
+
+    // the localized copies of a file, sorted by language so the choice is stable
+    pub fn variants(path: &Path) -> Vec<(String, PathBuf)> {
+        let (Some(dir), Some(stem), Some(extension)) = (
+            path.parent(),
+            path.file_stem().and_then(|stem| stem.to_str()),
+            path.extension().and_then(|extension| extension.to_str()).filter(|extension| {
+                Self::PAGE_EXTENSIONS.iter().any(|page| extension.eq_ignore_ascii_case(page))
+            }),
+        ) else {
+            return Vec::new();
+        };
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let mut variants: Vec<(String, PathBuf)> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_str()?.to_string();
+                let tag = name.strip_prefix(stem)?.strip_prefix('.')?.strip_suffix(extension)?.strip_suffix('.')?;
+                Self::is_tag(tag).then(|| (tag.to_string(), entry.path()))
+            })
+            .collect();
+        variants.sort();
+        variants
+    }
+
+    // RFC 4647 lookup: each range is tried as is, then shortened one subtag at a time, and
+    // a bare "en" also takes the first regional variant such as en-GB
+    pub fn select<'a>(accept_language: Option<&str>, available: &'a [String], default: &str) -> Option<&'a String> {
+        let find = |tag: &str| available.iter().find(|language| language.eq_ignore_ascii_case(tag));
+        for (range, _) in Self::parse_accept(accept_language.unwrap_or_default()) {
+            if range == "*" {
+                break;
+            }
+            let mut prefix = range.as_str();
+            loop {
+                if let Some(language) = find(prefix) {
+                    return Some(language);
+                }
+                match prefix.rsplit_once('-') {
+                    Some((shorter, _)) => prefix = shorter,
+                    None => break,
+                }
+            }
+            let regional = available.iter().find(|language| {
+                language.to_lowercase().strip_prefix(&range).is_some_and(|rest| rest.starts_with('-'))
+            });
+            if regional.is_some() {
+                return regional;
+            }
+        }
+        find(default)
+    }
+}
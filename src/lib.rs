@@ -1,18 +1,76 @@
 use crate::config::Config;
+use crate::daemon::Daemon;
+use crate::init::Init;
 use crate::logger::Logger;
 use crate::server::Server;
+use crate::service::ServiceAction;
+use crate::signed::SignedUrls;
 use crate::templates::{Templates, TemplatesPage};
 use std::collections::HashMap;
+use std::path::Path;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+pub mod accept;
+pub mod acme;
+pub mod admin;
+pub mod bench;
+#[cfg(feature = "embed")]
+pub mod bundle;
+pub mod cgi;
+pub mod charset;
+pub mod cidr;
+pub mod compression;
 pub mod config;
+pub mod connection;
+pub mod crypto;
+pub mod csrf;
+pub mod daemon;
+pub mod etag;
 pub mod filetype;
+pub mod handler;
+pub mod hooks;
+pub mod hotlink;
 pub mod http;
+pub mod ignore;
+pub mod init;
+pub mod json;
+pub mod jwt;
+pub mod language;
+pub mod livereload;
+pub mod location;
 pub mod logger;
+pub mod maintenance;
+pub mod markdown;
+pub mod mdns;
+pub mod multipart;
+pub mod ocsp;
+pub mod plugin;
+pub mod pool;
+pub mod privileges;
+pub mod proxy;
+pub mod qrcode;
+pub mod redirect;
+#[cfg(feature = "otel")]
+pub mod otel;
 pub mod request;
 pub mod response;
+#[cfg(feature = "landlock")]
+pub mod sandbox;
+pub mod secret;
 pub mod server;
+pub mod service;
+pub mod signed;
+pub mod signal;
+pub mod stats;
+pub mod syslog;
 pub mod templates;
+pub mod tls;
+pub mod tus;
+pub mod userdir;
 pub mod utils;
+pub mod vhost;
+pub mod wellknown;
 
 pub struct Katana {
     pub config: Config,
@@ -27,21 +85,160 @@ impl Default for Katana {
 
 impl Katana {
     pub fn new() -> Self {
-        Self {
-            config: Config::load_args(),
-            templates: Templates::load(),
+        let mut config = Config::load_args();
+        // the service manager starts in the system directory, options such as --config and
+        // --dir are relative to the one install was run from
+        if let (Some(ServiceAction::Run), Some(dir)) = (config.service.action, &config.service.dir) {
+            if let Err(e) = std::env::set_current_dir(dir) {
+                Logger::error(format!("Cannot change to {}: {}", dir.display(), e).as_str());
+                process::exit(1);
+            }
+            config = Config::load_args();
         }
+        let templates = match &config.templates_dir {
+            Some(dir) => Templates::load_dir(dir).unwrap_or_else(|e| {
+                Logger::error(format!("Cannot read templates, using the built-in ones: {}", e).as_str());
+                Templates::load()
+            }),
+            None => Templates::load(),
+        };
+        Self { config, templates }
     }
 
     pub fn start(&self) {
-        self.show_banner();
+        if let Some(path) = &self.config.sign_path {
+            self.print_signed_link(path);
+            return;
+        }
+        if self.config.bench.url.is_some() {
+            self.bench();
+            return;
+        }
+        if self.config.version {
+            println!("{} {}", Server::SERVER_NAME, Server::build_info());
+            return;
+        }
+        if self.config.check {
+            self.check();
+            return;
+        }
+        if self.config.init {
+            self.init();
+            return;
+        }
+        if self.config.precompress {
+            self.precompress();
+            return;
+        }
+        if let Some(action) = self.config.service.action {
+            self.service(action);
+            return;
+        }
+        // plain text in the middle of JSON lines would trip log collectors
+        if !self.config.container {
+            self.show_banner();
+        }
+
+        if self.config.daemon {
+            if let Err(e) = Daemon::not_running(&self.config).and_then(|_| Daemon::start(&self.config)) {
+                Logger::error(format!("Cannot start in the background: {}", e).as_str());
+                process::exit(1);
+            }
+        }
+
         let server = Server::new(self.config.to_owned(), self.templates.to_owned());
-        Logger::info(
-            format!("Server starting on {}", server.addr_with_protocol()).as_str(),
-        );
         server.serve();
     }
 
+    // `katana sign <path>`, reads the secret from the same flags or config file as the server
+    fn print_signed_link(&self, path: &str) {
+        let Some(secret) = &self.config.signed_urls.secret else {
+            process::exit(1);
+        };
+        let path = format!("/{}", path.trim_start_matches('/'));
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        println!("{}", SignedUrls::sign(secret, &path, now + self.config.sign_lifetime.as_secs()));
+    }
+
+    // `katana precompress`, gzips with the same --compression-* options the server would use, run
+    // again after a deploy only rewrites the sidecars of changed files
+    fn precompress(&self) {
+        match self.config.compression.precompress(&self.config.root_dir, &self.config.ignore) {
+            Ok(report) => println!("{} files precompressed, {} already up to date", report.written, report.fresh),
+            Err(e) => {
+                Logger::error(format!("Cannot precompress {}: {}", self.config.root_dir.display(), e).as_str());
+                process::exit(1);
+            }
+        }
+    }
+
+    // `katana service install|uninstall|run`, install passes the rest of the command line on
+    fn service(&self, action: ServiceAction) {
+        let service = &self.config.service;
+        let result = match action {
+            ServiceAction::Install => service.install(&self.config.args).map(|_| {
+                println!("Installed the {} service, start it with `sc start {}`", service.name, service.name);
+            }),
+            ServiceAction::Uninstall => service.uninstall().map(|_| println!("Removed the {} service", service.name)),
+            ServiceAction::Run => {
+                let server = Server::new(self.config.to_owned(), self.templates.to_owned());
+                service.run(move || server.serve())
+            }
+        };
+        if let Err(e) = result {
+            Logger::error(format!("Cannot {} the {} service: {}", action.as_str(), service.name, e).as_str());
+            process::exit(1);
+        }
+    }
+
+    // `katana bench <url>`, --bench-connections and --bench-duration shape the load
+    fn bench(&self) {
+        let bench = &self.config.bench;
+        println!("{} connections for {}s against {}", bench.connections, bench.duration.as_secs_f64(), bench.url.as_deref().unwrap_or_default());
+        match bench.run() {
+            Ok(report) => println!("{}", report),
+            Err(e) => {
+                Logger::error(format!("Cannot benchmark: {}", e).as_str());
+                process::exit(1);
+            }
+        }
+    }
+
+    // `katana check` or `katana -t`, parses the same command line and config file as the server
+    // would and exits with 1 when anything is wrong, before a restart takes the site down
+    fn check(&self) {
+        let (config, mut problems) = Config::parse(self.config.args.clone());
+        problems.extend(config.check());
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        match problems.len() {
+            0 => println!("configuration ok"),
+            count => {
+                println!("{} problem(s) found", count);
+                process::exit(1);
+            }
+        }
+    }
+
+    // `katana init`, reports each file so a second run shows what it left alone
+    fn init(&self) {
+        match Init::run(Path::new("."), self.config.init_templates) {
+            Ok((created, kept)) => {
+                for path in created {
+                    println!("created {}", path.display());
+                }
+                for path in kept {
+                    println!("kept {}, it already exists", path.display());
+                }
+            }
+            Err(e) => {
+                Logger::error(format!("Cannot write the config: {}", e).as_str());
+                process::exit(1);
+            }
+        }
+    }
+
     fn show_banner(&self) {
         let mut params = HashMap::new();
         params.insert(
@@ -0,0 +1,114 @@
+use crate::logger::Logger;
+use crate::server::Server;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{Error, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// bumped on every change under root_dir, browsers waiting on the event stream reload
+static GENERATION: Mutex<u64> = Mutex::new(0);
+static CHANGED: Condvar = Condvar::new();
+
+pub struct LiveReload;
+
+impl LiveReload {
+    pub const EVENTS_PATH: &'static str = "/_katana/livereload";
+    // injected into every HTML page served while --watch is on
+    pub const SCRIPT: &'static str = "<script>(function(){var s=new EventSource(\"/_katana/livereload\");s.onmessage=function(){location.reload()};})();</script>";
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(300);
+    // comments keep idle connections from being dropped by proxies
+    const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+    // polls modification times, which works the same on every platform without bindings
+    pub fn watch(root_dir: PathBuf) {
+        thread::spawn(move || {
+            let mut last = Self::fingerprint(&root_dir);
+            loop {
+                thread::sleep(Self::POLL_INTERVAL);
+                let current = Self::fingerprint(&root_dir);
+                if current != last {
+                    last = current;
+                    Logger::info(format!("Change detected in {}, reloading browsers.", root_dir.display()).as_str());
+                    Self::notify();
+                }
+            }
+        });
+    }
+
+    pub fn fingerprint(dir: &Path) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        Self::hash_dir(dir, &mut hasher);
+        hasher.finish()
+    }
+
+    fn hash_dir(dir: &Path, hasher: &mut DefaultHasher) {
+        let mut entries: Vec<_> = match fs::read_dir(dir) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok()).collect(),
+            Err(_) => return,
+        };
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            // dot entries are never served, and .git alone would make every poll slow
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            entry.file_name().hash(hasher);
+            metadata.len().hash(hasher);
+            metadata.modified().ok().hash(hasher);
+            if metadata.is_dir() {
+                Self::hash_dir(&entry.path(), hasher);
+            }
+        }
+    }
+
+    pub fn notify() {
+        let mut generation = GENERATION.lock().unwrap_or_else(|e| e.into_inner());
+        *generation += 1;
+        CHANGED.notify_all();
+    }
+
+    pub fn generation() -> u64 {
+        *GENERATION.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // blocks until a change newer than `seen` or the timeout, returns the latest generation
+    pub fn wait(seen: u64, timeout: Duration) -> u64 {
+        let generation = GENERATION.lock().unwrap_or_else(|e| e.into_inner());
+        let (generation, _) = CHANGED
+            .wait_timeout_while(generation, timeout, |generation| *generation == seen)
+            .unwrap_or_else(|e| e.into_inner());
+        *generation
+    }
+
+    // server-sent events, held open until the browser goes away
+    // @see: https://html.spec.whatwg.org/multipage/server-sent-events.html
+    pub fn stream_events<W: Write>(stream: &mut W) -> Result<(), Error> {
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nServer: {}\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n: connected\n\n",
+            Server::version()
+        )?;
+        stream.flush()?;
+
+        let mut seen = Self::generation();
+        loop {
+            let generation = Self::wait(seen, Self::PING_INTERVAL);
+            if generation != seen {
+                seen = generation;
+                stream.write_all(b"data: reload\n\n")?;
+            } else {
+                stream.write_all(b": ping\n\n")?;
+            }
+            stream.flush()?;
+        }
+    }
+}
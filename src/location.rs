@@ -0,0 +1,123 @@
+use crate::config::Config;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// settings for the requests under a path prefix, nginx's location blocks in the usual
+// `key = value` format, layered over the options of the site:
+//
+//   --location /downloads=downloads.conf --location /app=app.conf
+//
+//   # downloads.conf
+//   listing = true
+//   header = ["Content-Disposition: attachment", "Cache-Control: max-age=86400"]
+//
+//   # app.conf
+//   spa_fallback = "/app/index.html"
+//
+// the longest matching prefix wins
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub prefix: String,
+    pub file: PathBuf,
+    pub config: Arc<Config>,
+}
+
+impl Location {
+    // where requests go and who may make them stay with the site
+    pub const OPTIONS: &'static [&'static str] = &[
+        "--header",
+        "--listing",
+        "--no-listing",
+        "--listing-page-size",
+        "--etag",
+        "--charset",
+        "--checksums",
+        "--spa-fallback",
+        "--ignore",
+        "--handler",
+        "--proxy",
+        "--proxy-balance",
+        "--proxy-max-fails",
+        "--proxy-fail-timeout",
+        "--proxy-timeout",
+        "--proxy-health-path",
+        "--proxy-health-interval",
+        "--proxy-healthy-threshold",
+        "--proxy-unhealthy-threshold",
+        "--inject-head",
+        "--inject-body",
+        "--inject-files",
+        "--default-language",
+        "--locale",
+        "--compression-level",
+        "--compression-min-size",
+        "--compress-type",
+        "--no-compress-type",
+        "--jwt-secret",
+        "--jwt-jwks",
+        "--jwt-audience",
+        "--jwt-issuer",
+        "--jwt-protect",
+        "--sign-secret",
+        "--sign-protect",
+        "--sign-expires",
+        "--redirect",
+        "--hotlink-protect",
+        "--hotlink-allow",
+        "--hotlink-block-empty",
+        "--hotlink-redirect",
+    ];
+
+    // prefix=file, e.g. /downloads=downloads.conf
+    pub fn parse(value: &str) -> Option<(String, PathBuf)> {
+        let (prefix, file) = value.split_once('=')?;
+        let prefix = prefix.trim();
+        let prefix = if prefix == "/" { prefix } else { prefix.trim_end_matches('/') };
+        let file = file.trim();
+        if !prefix.starts_with('/') || file.is_empty() {
+            return None;
+        }
+        Some((prefix.to_string(), PathBuf::from(file)))
+    }
+
+    // site_args are the options of the site without --config, --vhost and --location
+    pub fn load(prefix: String, file: PathBuf, site_args: &[String]) -> Result<Self, Vec<String>> {
+        let file_args = Config::read_file(&file).map_err(|e| vec![e])?;
+        let refused: Vec<String> = file_args
+            .iter()
+            .filter(|arg| arg.starts_with("--") && !Self::OPTIONS.contains(&arg.as_str()))
+            .map(|arg| format!("{}: {} cannot be set per location", file.display(), arg))
+            .collect();
+        if !refused.is_empty() {
+            return Err(refused);
+        }
+
+        let mut args = site_args.to_vec();
+        args.extend(file_args);
+        let (config, errors) = Config::parse(args);
+        if !errors.is_empty() {
+            return Err(errors.into_iter().map(|e| format!("{}: {}", file.display(), e)).collect());
+        }
+        Ok(Self {
+            prefix,
+            file,
+            config: Arc::new(config),
+        })
+    }
+
+    // /downloads covers /downloads and /downloads/file.zip, not /downloads2
+    pub fn matches(&self, path: &str) -> bool {
+        if self.prefix == "/" {
+            return true;
+        }
+        path.strip_prefix(&self.prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    pub fn select<'a>(locations: &'a [Location], path: &str) -> Option<&'a Location> {
+        locations
+            .iter()
+            .filter(|location| location.matches(path))
+            .max_by_key(|location| location.prefix.len())
+    }
+}
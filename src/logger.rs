@@ -1,8 +1,14 @@
-use std::io::Write;
+use std::cell::RefCell;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Mutex;
+use crate::syslog::Syslog;
 use crate::utils::Utils;
 
-#[derive(Debug)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum LogLevel {
     DEBUG,
     INFO,
@@ -11,7 +17,37 @@ pub enum LogLevel {
 }
 
 impl LogLevel {
-    fn as_str(&self) -> &'static str {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "debug" => Some(LogLevel::DEBUG),
+            "info" => Some(LogLevel::INFO),
+            "warn" | "warning" => Some(LogLevel::WARN),
+            "error" => Some(LogLevel::ERROR),
+            _ => None,
+        }
+    }
+
+    // ANSI SGR color codes
+    fn color(&self) -> u8 {
+        match self {
+            LogLevel::DEBUG => 90,
+            LogLevel::INFO => 32,
+            LogLevel::WARN => 33,
+            LogLevel::ERROR => 31,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => LogLevel::DEBUG,
+            1 => LogLevel::INFO,
+            2 => LogLevel::WARN,
+            _ => LogLevel::ERROR,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
         match self {
             LogLevel::DEBUG => "DEBUG",
             LogLevel::INFO => "INFO",
@@ -21,43 +57,244 @@ impl LogLevel {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Plain,
+    Json,
+}
+
+impl LogFormat {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "plain" => Some(LogFormat::Plain),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+static JSON_FORMAT: AtomicBool = AtomicBool::new(false);
+static MIN_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::INFO as u8);
+static QUIET: AtomicBool = AtomicBool::new(false);
+static COLOR: AtomicBool = AtomicBool::new(false);
+// WARN and ERROR lines go here instead of stderr when set
+static ERROR_LOG: Mutex<Option<File>> = Mutex::new(None);
+// WARN and ERROR lines go to stdout too, the one stream container runtimes collect in order
+static STDOUT_ONLY: AtomicBool = AtomicBool::new(false);
+// replaces the console and error log entirely when set
+static SYSLOG: Mutex<Option<Syslog>> = Mutex::new(None);
+
+thread_local! {
+    // every connection has its own thread, so this is the request being served right now
+    static REQUEST_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
 pub struct Logger;
 
 impl Logger {
     pub fn debug(message: &str) {
-        let log_message = Self::build_log_message(LogLevel::DEBUG, message);
-        println!("{}", log_message);
+        Self::log(LogLevel::DEBUG, message);
     }
 
     pub fn info(message: &str) {
-        let log_message = Self::build_log_message(LogLevel::INFO, message);
-        println!("{}", log_message);
+        Self::log(LogLevel::INFO, message);
     }
 
     pub fn warn(message: &str) {
-        let log_message = Self::build_log_message(LogLevel::WARN, message);
-        println!("{}", log_message);
+        Self::log(LogLevel::WARN, message);
     }
 
     pub fn error(message: &str) {
-        let log_message = Self::build_log_message(LogLevel::ERROR, message);
-        println!("{}", log_message);
+        Self::log(LogLevel::ERROR, message);
     }
 
     pub fn log(level: LogLevel, message: &str) {
-        let log_message = Self::build_log_message(level, message);
-        println!("{}", log_message);
+        if !Self::enabled(level) {
+            return;
+        }
+        if Self::to_syslog(level, || Self::build_syslog_message(level, message)) {
+            return;
+        }
+        if level < LogLevel::WARN {
+            println!("{}", Self::build_log_message(level, message, Self::colored()));
+            return;
+        }
+
+        // the error stream is kept apart from the access log so it can be alerted on
+        let mut error_log = ERROR_LOG.lock().unwrap_or_else(|e| e.into_inner());
+        match error_log.as_mut() {
+            Some(file) => {
+                let _ = writeln!(file, "{}", Self::build_log_message(level, message, false));
+            }
+            None if STDOUT_ONLY.load(Ordering::Relaxed) => {
+                println!("{}", Self::build_log_message(level, message, Self::colored()));
+            }
+            None => {
+                let colored = Self::colored() && io::stderr().is_terminal();
+                eprintln!("{}", Self::build_log_message(level, message, colored));
+            }
+        }
+    }
+
+    pub fn set_syslog(syslog: Option<Syslog>) {
+        *SYSLOG.lock().unwrap_or_else(|e| e.into_inner()) = syslog;
+    }
+
+    // false when no syslog is configured, a failed send falls back to stderr
+    fn to_syslog<F: FnOnce() -> String>(level: LogLevel, message: F) -> bool {
+        let mut syslog = SYSLOG.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(syslog) = syslog.as_mut() else {
+            return false;
+        };
+        let message = message();
+        if let Err(e) = syslog.send(level, &message) {
+            eprintln!("syslog unavailable ({}): {}", e, message);
+        }
+        true
+    }
+
+    // syslog records time and severity itself, only the request ID is kept in front
+    fn build_syslog_message(level: LogLevel, message: &str) -> String {
+        if Self::format() == LogFormat::Json {
+            return Self::build_log_message(level, message, false);
+        }
+        REQUEST_ID.with(|id| match id.borrow().as_deref() {
+            Some(id) => format!("[{}] {}", id, message),
+            None => message.to_string(),
+        })
+    }
+
+    pub fn set_error_log(path: Option<&Path>) -> Result<(), io::Error> {
+        let file = match path {
+            Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+            None => None,
+        };
+        *ERROR_LOG.lock().unwrap_or_else(|e| e.into_inner()) = file;
+        Ok(())
+    }
+
+    pub fn set_level(level: LogLevel) {
+        MIN_LEVEL.store(level as u8, Ordering::Relaxed);
+    }
+
+    pub fn level() -> LogLevel {
+        LogLevel::from_u8(MIN_LEVEL.load(Ordering::Relaxed))
+    }
+
+    pub fn enabled(level: LogLevel) -> bool {
+        level >= Self::level()
+    }
+
+    // colors are only worth it for a person watching a terminal, see https://no-color.org
+    pub fn detect_color() -> bool {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        !no_color && io::stdout().is_terminal()
+    }
+
+    pub fn set_color(color: bool) {
+        COLOR.store(color, Ordering::Relaxed);
+    }
+
+    fn colored() -> bool {
+        COLOR.load(Ordering::Relaxed) && Self::format() == LogFormat::Plain
+    }
+
+    // green for success, cyan for redirects, yellow for client and red for server errors
+    pub fn paint_status(code: u16) -> String {
+        if !Self::colored() {
+            return code.to_string();
+        }
+        let color = match code {
+            500.. => 31,
+            400..=499 => 33,
+            300..=399 => 36,
+            _ => 32,
+        };
+        format!("\x1b[{}m{}\x1b[0m", color, code)
+    }
+
+    // quiet drops the per-request access lines, everything else follows the level
+    pub fn set_quiet(quiet: bool) {
+        QUIET.store(quiet, Ordering::Relaxed);
+    }
+
+    pub fn set_stdout_only(stdout_only: bool) {
+        STDOUT_ONLY.store(stdout_only, Ordering::Relaxed);
     }
 
     pub fn writer<W: Write>(level: LogLevel, message: &str, writer: &mut W) {
-        let log_message = Self::build_log_message(level, message);
+        let log_message = Self::build_log_message(level, message, false);
         let _ = writer.write_all(log_message.as_bytes()); // ignoring errors for simplicity
     }
 
-    fn build_log_message(level: LogLevel, message: &str) -> String {
+    pub fn set_format(format: LogFormat) {
+        JSON_FORMAT.store(format == LogFormat::Json, Ordering::Relaxed);
+    }
+
+    pub fn format() -> LogFormat {
+        if JSON_FORMAT.load(Ordering::Relaxed) {
+            LogFormat::Json
+        } else {
+            LogFormat::Plain
+        }
+    }
+
+    // an INFO line whose fields become keys of their own in the JSON format,
+    // values are JSON fragments (numbers as is, strings through Utils::json_string)
+    pub fn access(message: &str, fields: &[(&str, String)]) {
+        if QUIET.load(Ordering::Relaxed) || !Self::enabled(LogLevel::INFO) {
+            return;
+        }
+        let to_syslog = Self::to_syslog(LogLevel::INFO, || match Self::format() {
+            LogFormat::Plain => Self::build_syslog_message(LogLevel::INFO, message),
+            LogFormat::Json => Self::build_access_message(message, fields, false),
+        });
+        if to_syslog {
+            return;
+        }
+        println!("{}", Self::build_access_message(message, fields, Self::colored()));
+    }
+
+    pub fn access_writer<W: Write>(message: &str, fields: &[(&str, String)], writer: &mut W) {
+        let _ = writer.write_all(Self::build_access_message(message, fields, false).as_bytes());
+    }
+
+    fn build_access_message(message: &str, fields: &[(&str, String)], colored: bool) -> String {
+        if Self::format() == LogFormat::Plain {
+            return Self::build_log_message(LogLevel::INFO, message, colored);
+        }
+        let mut json = Self::build_log_message(LogLevel::INFO, message, false);
+        json.pop(); // closing brace
+        for (key, value) in fields {
+            json.push_str(&format!(",{}:{}", Utils::json_string(key), value));
+        }
+        json.push('}');
+        json
+    }
+
+    // tags every following line logged from this thread, until cleared with None
+    pub fn set_request_id(id: Option<&str>) {
+        REQUEST_ID.with(|current| *current.borrow_mut() = id.map(str::to_string));
+    }
+
+    fn build_log_message(level: LogLevel, message: &str, colored: bool) -> String {
         let at = Utils::log_datetime();
-        let level_str = level.as_str();
-        let log_message = format!("[{}] [{}] {}", at, level_str, message);
-        log_message
+        let level_str = if colored {
+            format!("\x1b[{}m{}\x1b[0m", level.color(), level.as_str())
+        } else {
+            level.as_str().to_string()
+        };
+        REQUEST_ID.with(|id| match (Self::format(), id.borrow().as_deref()) {
+            (LogFormat::Json, id) => format!(
+                "{{\"time\":{},\"level\":\"{}\",\"request_id\":{},\"message\":{}}}",
+                Utils::json_string(&at),
+                level_str,
+                id.map(Utils::json_string).unwrap_or("null".to_string()),
+                Utils::json_string(message)
+            ),
+            (LogFormat::Plain, Some(id)) => format!("[{}] [{}] [{}] {}", at, level_str, id, message),
+            (LogFormat::Plain, None) => format!("[{}] [{}] {}", at, level_str, message),
+        })
     }
 }
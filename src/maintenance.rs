@@ -0,0 +1,36 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// maintenance mode turns every request into a 503, either from --maintenance (picked up on
+// reload) or while a .maintenance file exists in the served directory, which needs no restart
+#[derive(Debug, Clone)]
+pub struct Maintenance {
+    pub enabled: bool,
+    pub page: Option<PathBuf>,
+    pub retry_after: u64,
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            page: None,
+            retry_after: Self::DEFAULT_RETRY_AFTER,
+        }
+    }
+}
+
+impl Maintenance {
+    pub const FLAG_FILE: &'static str = ".maintenance";
+    pub const DEFAULT_RETRY_AFTER: u64 = 300; // seconds
+
+    pub fn active(&self, root_dir: &Path) -> bool {
+        self.enabled || root_dir.join(Self::FLAG_FILE).is_file()
+    }
+
+    // read on every request so the page can be edited while it is up, None falls back to the
+    // error template
+    pub fn page(&self) -> Option<Vec<u8>> {
+        fs::read(self.page.as_ref()?).ok()
+    }
+}
@@ -0,0 +1,182 @@
+use crate::utils::Utils;
+
+// the CommonMark subset READMEs actually use: headings, paragraphs, lists, quotes, rules,
+// fenced code, and inline code, emphasis, links and images. Raw HTML is escaped, never passed
+// through, so a README cannot script the listing page
+pub struct Markdown;
+
+enum Block {
+    Paragraph,
+    Quote,
+    List(&'static str),
+}
+
+impl Markdown {
+    pub fn to_html(source: &str) -> String {
+        let mut html = String::new();
+        let mut open: Option<Block> = None;
+        let mut text: Vec<String> = Vec::new();
+        let mut lines = source.lines();
+
+        while let Some(line) = lines.next() {
+            let trimmed = line.trim();
+            if let Some(fence) = ["```", "~~~"].into_iter().find(|fence| trimmed.starts_with(fence)) {
+                Self::close(&mut html, &mut open, &mut text);
+                let code: Vec<&str> = lines.by_ref().take_while(|line| !line.trim_start().starts_with(fence)).collect();
+                html.push_str(&format!("<pre><code>{}</code></pre>\n", Utils::escape_html(&code.join("\n"))));
+                continue;
+            }
+            if trimmed.is_empty() {
+                Self::close(&mut html, &mut open, &mut text);
+                continue;
+            }
+            if let Some((level, title)) = Self::heading(trimmed) {
+                Self::close(&mut html, &mut open, &mut text);
+                html.push_str(&format!("<h{0}>{1}</h{0}>\n", level, Self::inline(title)));
+                continue;
+            }
+            if Self::is_rule(trimmed) {
+                Self::close(&mut html, &mut open, &mut text);
+                html.push_str("<hr>\n");
+                continue;
+            }
+            if let Some((tag, item)) = Self::list_item(trimmed) {
+                match &open {
+                    Some(Block::List(current)) if *current == tag => Self::flush_item(&mut html, &mut text),
+                    _ => {
+                        Self::close(&mut html, &mut open, &mut text);
+                        html.push_str(&format!("<{}>\n", tag));
+                        open = Some(Block::List(tag));
+                    }
+                }
+                text.push(item.to_string());
+                continue;
+            }
+            if let Some(quoted) = trimmed.strip_prefix('>') {
+                if !matches!(open, Some(Block::Quote)) {
+                    Self::close(&mut html, &mut open, &mut text);
+                    open = Some(Block::Quote);
+                }
+                text.push(quoted.trim().to_string());
+                continue;
+            }
+            // a lazy continuation of the open paragraph, list item or quote
+            if open.is_none() {
+                open = Some(Block::Paragraph);
+            }
+            text.push(trimmed.to_string());
+        }
+        Self::close(&mut html, &mut open, &mut text);
+        html
+    }
+
+    fn heading(line: &str) -> Option<(usize, &str)> {
+        let level = line.chars().take_while(|c| *c == '#').count();
+        let title = line[level..].strip_prefix(' ')?;
+        (1..=6).contains(&level).then(|| (level, title.trim().trim_end_matches('#').trim_end()))
+    }
+
+    // ---, *** or ___, spaces allowed in between
+    fn is_rule(line: &str) -> bool {
+        let marks: Vec<char> = line.chars().filter(|c| *c != ' ').collect();
+        marks.len() >= 3 && ['-', '*', '_'].contains(&marks[0]) && marks.iter().all(|c| *c == marks[0])
+    }
+
+    fn list_item(line: &str) -> Option<(&'static str, &str)> {
+        if let Some(item) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")).or_else(|| line.strip_prefix("+ ")) {
+            return Some(("ul", item));
+        }
+        let digits = line.chars().take_while(char::is_ascii_digit).count();
+        let item = line[digits..].strip_prefix(". ").or_else(|| line[digits..].strip_prefix(") "))?;
+        (digits > 0).then_some(("ol", item))
+    }
+
+    fn flush_item(html: &mut String, text: &mut Vec<String>) {
+        if !text.is_empty() {
+            html.push_str(&format!("<li>{}</li>\n", Self::inline(&text.join(" "))));
+            text.clear();
+        }
+    }
+
+    fn close(html: &mut String, open: &mut Option<Block>, text: &mut Vec<String>) {
+        match open.take() {
+            Some(Block::Paragraph) => html.push_str(&format!("<p>{}</p>\n", Self::inline(&text.join(" ")))),
+            Some(Block::Quote) => {
+                html.push_str(&format!("<blockquote><p>{}</p></blockquote>\n", Self::inline(&text.join(" "))))
+            }
+            Some(Block::List(tag)) => {
+                Self::flush_item(html, text);
+                html.push_str(&format!("</{}>\n", tag));
+            }
+            None => {}
+        }
+        text.clear();
+    }
+
+    // code spans first so nothing inside them is formatted
+    pub fn inline(text: &str) -> String {
+        let mut html = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('`') {
+            let Some(length) = rest[start + 1..].find('`') else {
+                break;
+            };
+            html.push_str(&Self::format(&rest[..start]));
+            html.push_str(&format!("<code>{}</code>", Utils::escape_html(&rest[start + 1..start + 1 + length])));
+            rest = &rest[start + 2 + length..];
+        }
+        html.push_str(&Self::format(rest));
+        html
+    }
+
+    fn format(text: &str) -> String {
+        let mut html = String::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            let image = rest.starts_with("![");
+            if image || rest.starts_with('[') {
+                if let Some((label, url, length)) = Self::link(&rest[usize::from(image)..]) {
+                    let url = Utils::escape_html(url);
+                    if image {
+                        html.push_str(&format!("<img src=\"{}\" alt=\"{}\">", url, Utils::escape_html(label)));
+                    } else {
+                        html.push_str(&format!("<a href=\"{}\">{}</a>", url, Self::format(label)));
+                    }
+                    rest = &rest[usize::from(image) + length..];
+                    continue;
+                }
+            }
+            // underscores inside words, as in snake_case, stay as they are
+            let in_word = text[..text.len() - rest.len()].chars().last().is_some_and(char::is_alphanumeric);
+            if let Some((marker, tag)) = [("**", "strong"), ("__", "strong"), ("*", "em"), ("_", "em")]
+                .into_iter()
+                .find(|(marker, _)| rest.starts_with(marker) && !(in_word && marker.starts_with('_')))
+            {
+                if let Some(end) = rest[marker.len()..].find(marker).filter(|end| *end > 0) {
+                    let inner = &rest[marker.len()..marker.len() + end];
+                    html.push_str(&format!("<{0}>{1}</{0}>", tag, Self::format(inner)));
+                    rest = &rest[2 * marker.len() + end..];
+                    continue;
+                }
+            }
+            let next = rest.char_indices().nth(1).map_or(rest.len(), |(index, _)| index);
+            html.push_str(&Utils::escape_html(&rest[..next]));
+            rest = &rest[next..];
+        }
+        html
+    }
+
+    // [label](url), javascript: and other schemes a page could be scripted with are refused
+    fn link(text: &str) -> Option<(&str, &str, usize)> {
+        let close = text.find("](")?;
+        let end = text[close + 2..].find(')')? + close + 2;
+        let (label, url) = (&text[1..close], text[close + 2..end].trim());
+        let scheme = url.split_once(':').map(|(scheme, _)| scheme.to_lowercase());
+        let safe = match scheme.as_deref() {
+            Some("http" | "https" | "mailto") => true,
+            Some(scheme) => scheme.contains(['/', '?', '#']),
+            None => true,
+        };
+        safe.then_some((label, url, end + 1))
+    }
+}
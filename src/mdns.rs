@@ -0,0 +1,317 @@
+use crate::connection::Listener;
+use std::env;
+use std::fs;
+use std::io::Error;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+// the socket and goodbye packet of the running responder, so shutdown can withdraw the records
+static ACTIVE: Mutex<Option<(UdpSocket, Vec<u8>)>> = Mutex::new(None);
+
+// advertises the server as a DNS-SD _http._tcp service over multicast DNS (RFC 6762/6763)
+pub struct Mdns {
+    instance: String,
+    host: String,
+    port: u16,
+    ip: Ipv4Addr,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Record {
+    Services,
+    Pointer,
+    Service,
+    Text,
+    Address,
+}
+
+impl Mdns {
+    pub const DEFAULT_INSTANCE: &'static str = "Katana";
+    pub const GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+    pub const PORT: u16 = 5353;
+
+    const SERVICE: [&'static str; 3] = ["_http", "_tcp", "local"];
+    const SERVICES: [&'static str; 4] = ["_services", "_dns-sd", "_udp", "local"];
+    const MAX_LABEL: usize = 63;
+    const MAX_JUMPS: usize = 16;
+
+    // RFC 6762 10: records tied to the host expire sooner than the others
+    const HOST_TTL: u32 = 120;
+    const SERVICE_TTL: u32 = 4500;
+    // RFC 6762 6.7: answers to legacy unicast queries must not be cached for long
+    const LEGACY_TTL: u32 = 10;
+    const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+    const TYPE_A: u16 = 1;
+    const TYPE_PTR: u16 = 12;
+    const TYPE_TXT: u16 = 16;
+    const TYPE_SRV: u16 = 33;
+    const TYPE_ANY: u16 = 255;
+    const CLASS_IN: u16 = 1;
+    const CACHE_FLUSH: u16 = 0x8000;
+    const FLAGS_RESPONSE: u16 = 0x8400;
+
+    pub fn new(instance: &str, host: &str, port: u16, ip: Ipv4Addr) -> Self {
+        Self {
+            instance: Self::instance_name(instance).unwrap_or_else(|| Self::DEFAULT_INSTANCE.to_string()),
+            host: Self::host_label(host),
+            port,
+            ip,
+        }
+    }
+
+    // instance names are free-form UTF-8 but must fit a single DNS label
+    pub fn instance_name(name: &str) -> Option<String> {
+        let name = name.trim();
+        let mut end = name.len().min(Self::MAX_LABEL);
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            None
+        } else {
+            Some(name[..end].to_string())
+        }
+    }
+
+    // prefixed so it never clashes with the <hostname>.local already announced by the OS
+    pub fn host_label(hostname: &str) -> String {
+        let hostname: String = hostname
+            .trim()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+            .collect();
+        let hostname = hostname.trim_matches('-');
+        let mut label = if hostname.is_empty() { "katana".to_string() } else { format!("katana-{}", hostname) };
+        label.truncate(Self::MAX_LABEL);
+        label.trim_end_matches('-').to_string()
+    }
+
+    pub fn hostname() -> String {
+        fs::read_to_string("/etc/hostname")
+            .ok()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .or_else(|| env::var("HOSTNAME").ok())
+            .or_else(|| env::var("COMPUTERNAME").ok())
+            .unwrap_or_default()
+    }
+
+    pub fn host(&self) -> String {
+        format!("{}.local", self.host)
+    }
+
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    // joins the multicast group, announces twice as RFC 6762 8.3 asks, then answers queries
+    // until the process exits
+    pub fn start(self) -> Result<(), Error> {
+        let socket = Listener::bind_shared_udp(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, Self::PORT)))?;
+        socket.join_multicast_v4(&Self::GROUP, &self.ip)?;
+        socket.set_multicast_ttl_v4(255)?;
+        let group = SocketAddr::from((Self::GROUP, Self::PORT));
+
+        let announcer = socket.try_clone()?;
+        let announcement = self.announcement();
+        thread::spawn(move || {
+            for _ in 0..2 {
+                let _ = announcer.send_to(&announcement, group);
+                thread::sleep(Self::ANNOUNCE_INTERVAL);
+            }
+        });
+        *ACTIVE.lock().unwrap() = Some((socket.try_clone()?, self.goodbye()));
+
+        thread::spawn(move || {
+            let mut buffer = [0u8; 9000];
+            loop {
+                let Ok((len, from)) = socket.recv_from(&mut buffer) else {
+                    continue;
+                };
+                // anything not sent from 5353 is a plain resolver expecting a direct answer
+                let legacy = from.port() != Self::PORT;
+                if let Some(reply) = self.reply(&buffer[..len], legacy) {
+                    let _ = socket.send_to(&reply, if legacy { from } else { group });
+                }
+            }
+        });
+        Ok(())
+    }
+
+    // withdraws the records so browsers drop the service right away instead of after the TTL
+    pub fn stop() {
+        if let Some((socket, goodbye)) = ACTIVE.lock().unwrap().take() {
+            let _ = socket.send_to(&goodbye, SocketAddr::from((Self::GROUP, Self::PORT)));
+        }
+    }
+
+    pub fn announcement(&self) -> Vec<u8> {
+        self.response(0, 0, &[], &Self::all_records(), None)
+    }
+
+    pub fn goodbye(&self) -> Vec<u8> {
+        self.response(0, 0, &[], &Self::all_records(), Some(0))
+    }
+
+    // None when the packet is not a query or asks about nothing we own
+    pub fn reply(&self, query: &[u8], legacy: bool) -> Option<Vec<u8>> {
+        if query.len() < 12 || query[2] & 0x80 != 0 {
+            return None;
+        }
+        let id = u16::from_be_bytes([query[0], query[1]]);
+        let count = u16::from_be_bytes([query[4], query[5]]);
+
+        let mut records = Vec::new();
+        let mut offset = 12;
+        for _ in 0..count {
+            let (name, next) = Self::read_name(query, offset)?;
+            let kind = u16::from_be_bytes([*query.get(next)?, *query.get(next + 1)?]);
+            offset = next + 4;
+            if offset > query.len() {
+                return None;
+            }
+            for record in self.answers(&name, kind) {
+                if !records.contains(&record) {
+                    records.push(record);
+                }
+            }
+        }
+        if records.is_empty() {
+            return None;
+        }
+
+        if legacy {
+            // legacy resolvers match answers against the echoed id and questions
+            Some(self.response(id, count, &query[12..offset], &records, Some(Self::LEGACY_TTL)))
+        } else {
+            Some(self.response(0, 0, &[], &records, None))
+        }
+    }
+
+    fn all_records() -> [Record; 5] {
+        [Record::Services, Record::Pointer, Record::Service, Record::Text, Record::Address]
+    }
+
+    fn answers(&self, name: &[String], kind: u16) -> Vec<Record> {
+        let wants = |wanted: u16| kind == wanted || kind == Self::TYPE_ANY;
+        let instance = self.instance_labels();
+        let host = [self.host.as_str(), "local"];
+
+        if Self::same_name(name, &Self::SERVICES) && wants(Self::TYPE_PTR) {
+            vec![Record::Services]
+        } else if Self::same_name(name, &Self::SERVICE) && wants(Self::TYPE_PTR) {
+            // additional records spare the browser three more round trips
+            vec![Record::Pointer, Record::Service, Record::Text, Record::Address]
+        } else if Self::same_name(name, &instance) {
+            let mut records = Vec::new();
+            if wants(Self::TYPE_SRV) {
+                records.extend([Record::Service, Record::Address]);
+            }
+            if wants(Self::TYPE_TXT) {
+                records.push(Record::Text);
+            }
+            records
+        } else if Self::same_name(name, &host) && wants(Self::TYPE_A) {
+            vec![Record::Address]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn instance_labels(&self) -> [&str; 4] {
+        [self.instance.as_str(), Self::SERVICE[0], Self::SERVICE[1], Self::SERVICE[2]]
+    }
+
+    fn response(&self, id: u16, question_count: u16, questions: &[u8], records: &[Record], ttl: Option<u32>) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(512);
+        packet.extend_from_slice(&id.to_be_bytes());
+        packet.extend_from_slice(&Self::FLAGS_RESPONSE.to_be_bytes());
+        packet.extend_from_slice(&question_count.to_be_bytes());
+        packet.extend_from_slice(&(records.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0, 0]);
+        packet.extend_from_slice(questions);
+
+        let host = [self.host.as_str(), "local"];
+        let instance = self.instance_labels();
+        let legacy = !questions.is_empty();
+        for record in records {
+            // the cache flush bit is not allowed in legacy unicast answers
+            let unique = if legacy { Self::CLASS_IN } else { Self::CLASS_IN | Self::CACHE_FLUSH };
+            let (name, kind, class, default_ttl, data): (&[&str], u16, u16, u32, Vec<u8>) = match record {
+                Record::Services => (
+                    &Self::SERVICES,
+                    Self::TYPE_PTR,
+                    Self::CLASS_IN,
+                    Self::SERVICE_TTL,
+                    Self::encode_name(&Self::SERVICE),
+                ),
+                Record::Pointer => (
+                    &Self::SERVICE,
+                    Self::TYPE_PTR,
+                    Self::CLASS_IN,
+                    Self::SERVICE_TTL,
+                    Self::encode_name(&instance),
+                ),
+                Record::Service => {
+                    let mut data = vec![0, 0, 0, 0];
+                    data.extend_from_slice(&self.port.to_be_bytes());
+                    data.extend(Self::encode_name(&host));
+                    (&instance, Self::TYPE_SRV, unique, Self::HOST_TTL, data)
+                }
+                // the share is served from the root, browsers that honour path= open it directly
+                Record::Text => (&instance, Self::TYPE_TXT, unique, Self::SERVICE_TTL, b"\x06path=/".to_vec()),
+                Record::Address => (&host, Self::TYPE_A, unique, Self::HOST_TTL, self.ip.octets().to_vec()),
+            };
+            packet.extend(Self::encode_name(name));
+            packet.extend_from_slice(&kind.to_be_bytes());
+            packet.extend_from_slice(&class.to_be_bytes());
+            packet.extend_from_slice(&ttl.map_or(default_ttl, |ttl| ttl.min(default_ttl)).to_be_bytes());
+            packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            packet.extend(data);
+        }
+        packet
+    }
+
+    pub fn encode_name(labels: &[&str]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        for label in labels {
+            let label = &label.as_bytes()[..label.len().min(Self::MAX_LABEL)];
+            encoded.push(label.len() as u8);
+            encoded.extend_from_slice(label);
+        }
+        encoded.push(0);
+        encoded
+    }
+
+    // returns the labels and the offset right after the name, following compression pointers
+    pub fn read_name(packet: &[u8], mut offset: usize) -> Option<(Vec<String>, usize)> {
+        let mut labels = Vec::new();
+        let mut end = None;
+        let mut jumps = 0;
+        loop {
+            let len = *packet.get(offset)? as usize;
+            if len == 0 {
+                return Some((labels, end.unwrap_or(offset + 1)));
+            }
+            if len & 0xc0 == 0xc0 {
+                jumps += 1;
+                if jumps > Self::MAX_JUMPS {
+                    return None;
+                }
+                end.get_or_insert(offset + 2);
+                offset = ((len & 0x3f) << 8) | *packet.get(offset + 1)? as usize;
+                continue;
+            }
+            let label = packet.get(offset + 1..offset + 1 + len)?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            offset += 1 + len;
+        }
+    }
+
+    fn same_name(name: &[String], expected: &[&str]) -> bool {
+        name.len() == expected.len() && name.iter().zip(expected).all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
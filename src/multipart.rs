@@ -0,0 +1,113 @@
+// multipart/form-data bodies (RFC 7578), what a browser form with a file input sends: parts
+// separated by a boundary line, each with its own headers, the file ones naming the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Part {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+pub struct Multipart;
+
+impl Multipart {
+    pub const CONTENT_TYPE: &'static str = "multipart/form-data";
+    // RFC 2046 5.1.1
+    const MAX_BOUNDARY: usize = 70;
+
+    // the boundary parameter of a multipart/form-data Content-Type, quoted or not
+    pub fn boundary(content_type: &str) -> Option<String> {
+        let mut params = content_type.split(';');
+        if !params.next()?.trim().eq_ignore_ascii_case(Self::CONTENT_TYPE) {
+            return None;
+        }
+        params
+            .filter_map(|param| param.split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+            .map(|(_, value)| value.trim().trim_matches('"').to_string())
+            .filter(|boundary| !boundary.is_empty() && boundary.len() <= Self::MAX_BOUNDARY)
+    }
+
+    // every part up to the closing delimiter, the preamble before the first one is skipped
+    pub fn parse(body: &[u8], boundary: &str) -> Result<Vec<Part>, String> {
+        let delimiter = format!("--{}", boundary).into_bytes();
+        let separator = [b"\r\n".as_slice(), &delimiter].concat();
+        let mut rest = if body.starts_with(&delimiter) {
+            &body[delimiter.len()..]
+        } else {
+            let start = Self::find(body, &separator).ok_or("multipart body has no boundary")?;
+            &body[start + separator.len()..]
+        };
+
+        let mut parts = Vec::new();
+        loop {
+            if rest.starts_with(b"--") {
+                return Ok(parts);
+            }
+            rest = rest.strip_prefix(b"\r\n").ok_or("multipart boundary is not followed by a line break")?;
+            let end = Self::find(rest, b"\r\n\r\n").ok_or("multipart part has no end of headers")?;
+            let head = String::from_utf8_lossy(&rest[..end]).to_string();
+            rest = &rest[end + 4..];
+            let end = Self::find(rest, &separator).ok_or("multipart body is not closed")?;
+            parts.push(Self::part(&head, rest[..end].to_vec())?);
+            rest = &rest[end + separator.len()..];
+        }
+    }
+
+    // the last segment of a name given by the client, which some browsers send with the
+    // directories it came from; None for names that would be hidden or cannot be a file
+    pub fn safe_name(filename: &str) -> Option<&str> {
+        let name = filename.rsplit(['/', '\\']).next()?.trim();
+        (!name.is_empty() && !name.starts_with('.') && !name.contains(|c: char| c.is_control())).then_some(name)
+    }
+
+    fn part(head: &str, data: Vec<u8>) -> Result<Part, String> {
+        let mut disposition = None;
+        let mut content_type = None;
+        for line in head.split("\r\n") {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            if name.trim().eq_ignore_ascii_case("Content-Disposition") {
+                disposition = Some(value.trim());
+            } else if name.trim().eq_ignore_ascii_case("Content-Type") {
+                content_type = Some(value.trim().to_string());
+            }
+        }
+        let disposition = disposition.ok_or("multipart part has no Content-Disposition")?;
+        let params = Self::params(disposition);
+        let param = |key: &str| {
+            params.iter().find(|(name, _)| name.eq_ignore_ascii_case(key)).map(|(_, value)| value.clone())
+        };
+        Ok(Part {
+            name: param("name").ok_or("multipart part has no name")?,
+            filename: param("filename"),
+            content_type,
+            data,
+        })
+    }
+
+    // the key=value pairs after form-data, a quoted value may hold a ; but no quote, which
+    // browsers send as %22
+    fn params(disposition: &str) -> Vec<(String, String)> {
+        let mut params = Vec::new();
+        let mut chars = disposition.chars().skip_while(|c| *c != ';').skip(1).peekable();
+        while chars.peek().is_some() {
+            let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+            while chars.next_if(|c| *c == ' ').is_some() {}
+            let value = if chars.next_if_eq(&'"').is_some() {
+                let value = chars.by_ref().take_while(|c| *c != '"').collect();
+                chars.by_ref().take_while(|c| *c != ';').for_each(drop);
+                value
+            } else {
+                chars.by_ref().take_while(|c| *c != ';').collect::<String>().trim().to_string()
+            };
+            params.push((key.trim().to_string(), value));
+        }
+        params
+    }
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|window| window == needle)
+    }
+}
@@ -0,0 +1,332 @@
+use crate::crypto::Crypto;
+use crate::utils::Utils;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+// a DER element, its content and the whole of its encoding
+struct Der<'a> {
+    tag: u8,
+    content: &'a [u8],
+    raw: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    // the first element of data and what follows it
+    fn read(data: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        let tag = *data.first()?;
+        let first = *data.get(1)? as usize;
+        let (length, header) = if first < 0x80 {
+            (first, 2)
+        } else {
+            let count = first & 0x7f;
+            if count == 0 || count > 4 {
+                return None;
+            }
+            let length = data.get(2..2 + count)?.iter().fold(0usize, |length, b| length << 8 | *b as usize);
+            (length, 2 + count)
+        };
+        let end = header.checked_add(length)?;
+        let element = Self { tag, content: data.get(header..end)?, raw: &data[..end] };
+        Some((element, &data[end..]))
+    }
+
+    fn children(&self) -> Option<Vec<Der<'a>>> {
+        let mut children = Vec::new();
+        let mut rest = self.content;
+        while !rest.is_empty() {
+            let (child, next) = Self::read(rest)?;
+            children.push(child);
+            rest = next;
+        }
+        Some(children)
+    }
+
+    fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![tag];
+        let length = content.len().to_be_bytes();
+        match content.len() {
+            0..=0x7f => encoded.push(content.len() as u8),
+            _ => {
+                let significant: Vec<u8> = length.iter().copied().skip_while(|b| *b == 0).collect();
+                encoded.push(0x80 | significant.len() as u8);
+                encoded.extend(significant);
+            }
+        }
+        encoded.extend_from_slice(content);
+        encoded
+    }
+}
+
+// how a responder identifies a certificate: hashes of its issuer's name and key, and its
+// serial number (RFC 6960 4.1.1)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertId {
+    pub issuer_name_hash: [u8; 20],
+    pub issuer_key_hash: [u8; 20],
+    pub serial: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CertStatus {
+    Good,
+    Revoked,
+    Unknown,
+}
+
+// a response to hand clients during the handshake, exactly as the responder signed it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Staple {
+    pub response: Vec<u8>,
+    pub status: CertStatus,
+    pub this_update: u64,
+    pub next_update: Option<u64>,
+}
+
+impl Staple {
+    // responses without a next update are good for as long as the responder says, which it
+    // does not; asked again after this long
+    const REFRESH_WITHOUT_NEXT_UPDATE: u64 = 3600;
+
+    // halfway through its validity, leaving the other half to retry a responder that is down
+    pub fn refresh_at(&self) -> u64 {
+        match self.next_update {
+            Some(next_update) => self.this_update + next_update.saturating_sub(self.this_update) / 2,
+            None => self.this_update + Self::REFRESH_WITHOUT_NEXT_UPDATE,
+        }
+    }
+
+    // clients reject an expired staple, serving none is better
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.next_update.is_some_and(|next_update| next_update <= now)
+    }
+}
+
+// OCSP stapling, RFC 6960 with the profile of RFC 5019: the server asks the certificate
+// authority whether its own certificate is still good and hands the signed answer to clients,
+// which then need not ask themselves. The answer is not verified here, clients do that with
+// the issuer they already have
+pub struct Ocsp;
+
+impl Ocsp {
+    pub const CONTENT_TYPE: &'static str = "application/ocsp-request";
+    const TIMEOUT: Duration = Duration::from_secs(10);
+    // responses are a few kilobytes, a larger one is not a response
+    const MAX_RESPONSE_SIZE: u64 = 64 * 1024;
+
+    const OID_AUTHORITY_INFO_ACCESS: &'static [u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x01, 0x01];
+    const OID_OCSP: &'static [u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01];
+    const OID_OCSP_BASIC: &'static [u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+    const OID_SHA1: &'static [u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+
+    // the DER certificates of a PEM file, in order
+    pub fn pem_certificates(pem: &[u8]) -> Vec<Vec<u8>> {
+        let text = String::from_utf8_lossy(pem);
+        text.split("-----BEGIN CERTIFICATE-----")
+            .skip(1)
+            .filter_map(|block| {
+                let (base64, _) = block.split_once("-----END CERTIFICATE-----")?;
+                let base64: String = base64
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(|c| match c {
+                        '+' => '-',
+                        '/' => '_',
+                        c => c,
+                    })
+                    .collect();
+                Utils::base64url_decode(&base64)
+            })
+            .collect()
+    }
+
+    // the responder and the id of the first certificate of a chain, the certificate after it
+    // must be its issuer as in the fullchain.pem of certbot
+    pub fn cert_id(chain: &[u8]) -> Result<(String, CertId), String> {
+        let certificates = Self::pem_certificates(chain);
+        let (Some(leaf), Some(issuer)) = (certificates.first(), certificates.get(1)) else {
+            return Err("OCSP needs the issuer certificate after the server's own, use the full chain".to_string());
+        };
+        let leaf = Self::tbs_fields(leaf).ok_or("cannot parse the certificate")?;
+        let issuer = Self::tbs_fields(issuer).ok_or("cannot parse the issuer certificate")?;
+        // [version] serial signature issuer validity subject subjectPublicKeyInfo ...
+        let (serial, issuer_name) = (&leaf[1], &leaf[3]);
+        if issuer[5].raw != issuer_name.raw {
+            return Err("the second certificate of the chain did not issue the first".to_string());
+        }
+        let key = issuer[6].children().and_then(|key| key.into_iter().nth(1)).filter(|key| key.tag == 0x03);
+        let key = key.and_then(|key| key.content.get(1..)).ok_or("cannot read the issuer's public key")?;
+
+        let responder = leaf
+            .iter()
+            .find(|field| field.tag == 0xa3)
+            .and_then(Self::responder)
+            .ok_or("the certificate names no OCSP responder")?;
+        let id = CertId {
+            issuer_name_hash: Crypto::sha1(issuer_name.raw),
+            issuer_key_hash: Crypto::sha1(key),
+            serial: serial.content.to_vec(),
+        };
+        Ok((responder, id))
+    }
+
+    // an OCSPRequest for one certificate, without the nonce RFC 5019 responders ignore
+    pub fn request(id: &CertId) -> Vec<u8> {
+        let algorithm = Der::encode(0x30, &[Der::encode(0x06, Self::OID_SHA1), Der::encode(0x05, &[])].concat());
+        let cert_id = Der::encode(
+            0x30,
+            &[
+                algorithm,
+                Der::encode(0x04, &id.issuer_name_hash),
+                Der::encode(0x04, &id.issuer_key_hash),
+                Der::encode(0x02, &id.serial),
+            ]
+            .concat(),
+        );
+        let request = Der::encode(0x30, &cert_id);
+        let tbs_request = Der::encode(0x30, &Der::encode(0x30, &request));
+        Der::encode(0x30, &tbs_request)
+    }
+
+    // the staple for id in an OCSPResponse, an error when the responder refused or answered
+    // for another certificate
+    pub fn parse(response: &[u8], id: &CertId) -> Result<Staple, String> {
+        let invalid = || "invalid OCSP response".to_string();
+        let (outer, _) = Der::read(response).ok_or_else(invalid)?;
+        let fields = outer.children().ok_or_else(invalid)?;
+        match fields.first() {
+            Some(status) if status.tag == 0x0a && status.content == [0] => {}
+            Some(status) if status.tag == 0x0a => {
+                return Err(format!("OCSP responder refused the request with status {}", status.content.first().unwrap_or(&0)))
+            }
+            _ => return Err(invalid()),
+        }
+        // [0] { responseType, response }
+        let bytes = fields.get(1).filter(|bytes| bytes.tag == 0xa0).and_then(Der::children).ok_or_else(invalid)?;
+        let bytes = bytes.first().and_then(Der::children).ok_or_else(invalid)?;
+        if bytes.len() != 2 || bytes[0].content != Self::OID_OCSP_BASIC {
+            return Err("OCSP response is not a basic response".to_string());
+        }
+        let (basic, _) = Der::read(bytes[1].content).ok_or_else(invalid)?;
+        let tbs = basic.children().and_then(|basic| basic.into_iter().next()).ok_or_else(invalid)?;
+        let tbs = tbs.children().ok_or_else(invalid)?;
+        // [version] responderID producedAt responses [extensions]
+        let responses = tbs.iter().find(|field| field.tag == 0x30).and_then(Der::children).ok_or_else(invalid)?;
+
+        for single in responses {
+            let single = single.children().ok_or_else(invalid)?;
+            let [cert_id, status, this_update, rest @ ..] = &single[..] else {
+                return Err(invalid());
+            };
+            let cert_id = cert_id.children().ok_or_else(invalid)?;
+            let matches = cert_id.len() == 4
+                && cert_id[0].children().and_then(|algorithm| algorithm.first().map(|oid| oid.content == Self::OID_SHA1)) == Some(true)
+                && cert_id[1].content == id.issuer_name_hash
+                && cert_id[2].content == id.issuer_key_hash
+                && cert_id[3].content == id.serial.as_slice();
+            if !matches {
+                continue;
+            }
+            let status = match status.tag {
+                0x80 => CertStatus::Good,
+                0xa1 => CertStatus::Revoked,
+                _ => CertStatus::Unknown,
+            };
+            let this_update = Self::generalized_time(this_update.content).ok_or_else(invalid)?;
+            let next_update = match rest.iter().find(|field| field.tag == 0xa0) {
+                Some(next_update) => {
+                    let (time, _) = Der::read(next_update.content).ok_or_else(invalid)?;
+                    Some(Self::generalized_time(time.content).ok_or_else(invalid)?)
+                }
+                None => None,
+            };
+            return Ok(Staple { response: response.to_vec(), status, this_update, next_update });
+        }
+        Err("OCSP response is for another certificate".to_string())
+    }
+
+    // POSTed as RFC 5019 allows, responders are plain http since their answers are signed
+    pub fn fetch(url: &str, id: &CertId) -> Result<Staple, String> {
+        let target = url.strip_prefix("http://").ok_or_else(|| format!("only http:// OCSP responders are supported: {}", url))?;
+        let (authority, path) = match target.find('/') {
+            Some(index) => (&target[..index], &target[index..]),
+            None => (target, "/"),
+        };
+        let address = if authority.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok()) {
+            authority.to_string()
+        } else {
+            format!("{}:80", authority)
+        };
+        let error = |e: std::io::Error| format!("cannot reach OCSP responder {}: {}", url, e);
+
+        let addr = address.to_socket_addrs().map_err(error)?.next().ok_or_else(|| format!("cannot resolve {}", authority))?;
+        let mut stream = TcpStream::connect_timeout(&addr, Self::TIMEOUT).map_err(error)?;
+        stream.set_read_timeout(Some(Self::TIMEOUT)).map_err(error)?;
+        stream.set_write_timeout(Some(Self::TIMEOUT)).map_err(error)?;
+        let body = Self::request(id);
+        let head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            path,
+            authority,
+            Self::CONTENT_TYPE,
+            body.len()
+        );
+        stream.write_all(&[head.as_bytes(), &body].concat()).map_err(error)?;
+
+        let mut answer = Vec::new();
+        stream.take(Self::MAX_RESPONSE_SIZE).read_to_end(&mut answer).map_err(error)?;
+        let end = answer.windows(4).position(|window| window == b"\r\n\r\n").ok_or("incomplete answer from the OCSP responder")?;
+        let head = String::from_utf8_lossy(&answer[..end]);
+        let status = head.lines().next().unwrap_or_default();
+        if status.split_whitespace().nth(1) != Some("200") {
+            return Err(format!("OCSP responder answered {}", status));
+        }
+        if head.lines().any(|line| line.to_ascii_lowercase().starts_with("transfer-encoding:")) {
+            return Err("OCSP responder answered with a chunked body".to_string());
+        }
+        Self::parse(&answer[end + 4..], id)
+    }
+
+    // the fields of a certificate's TBSCertificate, the optional version first so the serial
+    // is always the second
+    fn tbs_fields(certificate: &[u8]) -> Option<Vec<Der<'_>>> {
+        let (certificate, _) = Der::read(certificate)?;
+        let tbs = certificate.children()?.into_iter().next()?;
+        let mut fields = tbs.children()?;
+        if fields.first()?.tag != 0xa0 {
+            fields.insert(0, Der { tag: 0xa0, content: &[], raw: &[] });
+        }
+        (fields.len() >= 7).then_some(fields)
+    }
+
+    // the OCSP access location of the Authority Information Access extension
+    fn responder(extensions: &Der) -> Option<String> {
+        let extensions = extensions.children()?.into_iter().next()?.children()?;
+        let extension = extensions.iter().find_map(|extension| {
+            let fields = extension.children()?;
+            (fields.first()?.content == Self::OID_AUTHORITY_INFO_ACCESS).then(|| fields.last().map(|value| value.content))?
+        })?;
+        let (access, _) = Der::read(extension)?;
+        access.children()?.iter().find_map(|description| {
+            let fields = description.children()?;
+            let [method, location] = &fields[..] else {
+                return None;
+            };
+            // a uniformResourceIdentifier
+            (method.content == Self::OID_OCSP && location.tag == 0x86).then(|| String::from_utf8_lossy(location.content).to_string())
+        })
+    }
+
+    // YYYYMMDDHHMMSS[.fff]Z, read as the HTTP date it is the same instant as
+    fn generalized_time(value: &[u8]) -> Option<u64> {
+        let value = std::str::from_utf8(value).ok()?.strip_suffix('Z')?;
+        let value = value.split('.').next()?;
+        if value.len() != 14 || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let months = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+        let month = months.get(value[4..6].parse::<usize>().ok()?.checked_sub(1)?)?;
+        let date = format!("Mon, {} {} {} {}:{}:{} GMT", &value[6..8], month, &value[..4], &value[8..10], &value[10..12], &value[12..14]);
+        Utils::parse_http_date(&date)
+    }
+}
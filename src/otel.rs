@@ -0,0 +1,229 @@
+use crate::logger::Logger;
+use crate::server::Server;
+use crate::utils::Utils;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// the W3C trace context carried by an inbound `traceparent` header
+// @see: https://www.w3.org/TR/trace-context/#traceparent-header
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceParent {
+    pub trace_id: String,
+    pub parent_id: String,
+    pub flags: u8,
+}
+
+impl TraceParent {
+    pub fn parse(header: &str) -> Option<Self> {
+        let parts: Vec<&str> = header.trim().split('-').collect();
+        if parts.len() < 4 || parts[0].len() != 2 || parts[0] == "ff" || !Self::is_hex(parts[0]) {
+            return None;
+        }
+        // version 00 has exactly four fields, later versions may append more
+        if parts[0] == "00" && parts.len() != 4 {
+            return None;
+        }
+        let (trace_id, parent_id, flags) = (parts[1], parts[2], parts[3]);
+        let valid = trace_id.len() == 32
+            && parent_id.len() == 16
+            && flags.len() == 2
+            && Self::is_hex(trace_id)
+            && Self::is_hex(parent_id)
+            && trace_id.bytes().any(|b| b != b'0')
+            && parent_id.bytes().any(|b| b != b'0');
+        if !valid {
+            return None;
+        }
+        Some(Self {
+            trace_id: trace_id.to_lowercase(),
+            parent_id: parent_id.to_lowercase(),
+            flags: u8::from_str_radix(flags, 16).ok()?,
+        })
+    }
+
+    fn is_hex(value: &str) -> bool {
+        value.bytes().all(|b| b.is_ascii_hexdigit())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Str(String),
+    Int(i64),
+}
+
+#[derive(Debug, Clone)]
+pub struct Span {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    pub start_unix_nano: u128,
+    pub end_unix_nano: u128,
+    pub attributes: Vec<(&'static str, AttributeValue)>,
+    pub error: bool,
+}
+
+impl Span {
+    // a server span continuing the caller's trace, or starting a new one
+    pub fn server(name: &str, parent: Option<TraceParent>, started: Instant) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let elapsed = started.elapsed().as_nanos();
+        let (trace_id, parent_span_id) = match parent {
+            Some(parent) => (parent.trace_id, Some(parent.parent_id)),
+            None => (format!("{}{}", Utils::request_id(), Utils::request_id()), None),
+        };
+        Self {
+            trace_id,
+            span_id: Utils::request_id(),
+            parent_span_id,
+            name: name.to_string(),
+            start_unix_nano: now.saturating_sub(elapsed),
+            end_unix_nano: now,
+            attributes: Vec::new(),
+            error: false,
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let attributes: Vec<String> = self
+            .attributes
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    AttributeValue::Str(s) => format!("{{\"stringValue\":{}}}", Utils::json_string(s)),
+                    // 64 bit integers are strings in the OTLP JSON mapping
+                    AttributeValue::Int(i) => format!("{{\"intValue\":\"{}\"}}", i),
+                };
+                format!("{{\"key\":{},\"value\":{}}}", Utils::json_string(key), value)
+            })
+            .collect();
+        format!(
+            "{{\"traceId\":\"{}\",\"spanId\":\"{}\",{}\"name\":{},\"kind\":2,\"startTimeUnixNano\":\"{}\",\"endTimeUnixNano\":\"{}\",\"attributes\":[{}],\"status\":{{\"code\":{}}}}}",
+            self.trace_id,
+            self.span_id,
+            self.parent_span_id
+                .as_ref()
+                .map(|id| format!("\"parentSpanId\":\"{}\",", id))
+                .unwrap_or_default(),
+            Utils::json_string(&self.name),
+            self.start_unix_nano,
+            self.end_unix_nano,
+            attributes.join(","),
+            if self.error { 2 } else { 0 },
+        )
+    }
+}
+
+static EXPORTER: Mutex<Option<(String, Sender<Span>)>> = Mutex::new(None);
+
+pub struct Tracer;
+
+impl Tracer {
+    const BATCH_SIZE: usize = 64;
+    const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+    const TRACES_PATH: &'static str = "/v1/traces";
+
+    // starts (or replaces) the background exporter, None stops tracing
+    pub fn configure(endpoint: Option<&str>) {
+        let mut exporter = EXPORTER.lock().unwrap_or_else(|e| e.into_inner());
+        match (endpoint, exporter.as_ref()) {
+            (Some(endpoint), Some((current, _))) if current == endpoint => {}
+            (Some(endpoint), _) => {
+                let (sender, receiver) = mpsc::channel();
+                let target = endpoint.to_string();
+                thread::spawn(move || Self::export_loop(&target, receiver));
+                // dropping the previous sender ends its exporter after a final flush
+                *exporter = Some((endpoint.to_string(), sender));
+            }
+            (None, _) => *exporter = None,
+        }
+    }
+
+    pub fn enabled() -> bool {
+        EXPORTER.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+    }
+
+    pub fn record(span: Span) {
+        if let Some((_, sender)) = EXPORTER.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            let _ = sender.send(span);
+        }
+    }
+
+    fn export_loop(endpoint: &str, receiver: Receiver<Span>) {
+        let mut batch = Vec::new();
+        loop {
+            let disconnected = match receiver.recv_timeout(Self::FLUSH_INTERVAL) {
+                Ok(span) => {
+                    batch.push(span);
+                    if batch.len() < Self::BATCH_SIZE {
+                        continue;
+                    }
+                    false
+                }
+                Err(RecvTimeoutError::Timeout) => false,
+                Err(RecvTimeoutError::Disconnected) => true,
+            };
+
+            if !batch.is_empty() {
+                if let Err(e) = Self::export(endpoint, &batch) {
+                    Logger::warn(format!("Failed to export {} span(s) to {}: {}", batch.len(), endpoint, e).as_str());
+                }
+                batch.clear();
+            }
+            if disconnected {
+                return;
+            }
+        }
+    }
+
+    pub fn encode(spans: &[Span]) -> String {
+        let spans: Vec<String> = spans.iter().map(Span::to_json).collect();
+        format!(
+            "{{\"resourceSpans\":[{{\"resource\":{{\"attributes\":[{{\"key\":\"service.name\",\"value\":{{\"stringValue\":\"katana\"}}}}]}},\"scopeSpans\":[{{\"scope\":{{\"name\":\"katana\",\"version\":{}}},\"spans\":[{}]}}]}}]}}",
+            Utils::json_string(Server::SERVER_VERSION),
+            spans.join(",")
+        )
+    }
+
+    // OTLP/HTTP with JSON encoding, plain http only
+    // @see: https://opentelemetry.io/docs/specs/otlp/#otlphttp
+    pub fn export(endpoint: &str, spans: &[Span]) -> Result<(), Error> {
+        let target = endpoint.strip_prefix("http://").ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, "only http:// OTLP endpoints are supported")
+        })?;
+        // a bare collector address gets the standard traces path
+        let (authority, path) = match target.find('/') {
+            Some(index) => (&target[..index], &target[index..]),
+            None => (target, "/"),
+        };
+        let path = if path == "/" { Self::TRACES_PATH } else { path };
+
+        let body = Self::encode(spans);
+        let mut stream = TcpStream::connect(authority)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            path,
+            authority,
+            body.len(),
+            body
+        )?;
+        stream.flush()?;
+
+        let mut status_line = String::new();
+        BufReader::new(stream).read_line(&mut status_line)?;
+        match status_line.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(Error::other(format!("collector answered {}", status_line.trim()))),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::http::HttpStatus;
+use crate::request::Request;
+use crate::response::Response;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+// custom auth or rewriting without patching Katana: on_request runs before any handler and
+// may answer on its own, on_response runs before the response is sent. Plugins only get the
+// views below, never the socket, the filesystem or the configuration
+pub trait Plugin: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn on_request(&self, _request: &mut RequestView) -> PluginAction {
+        PluginAction::Continue
+    }
+
+    fn on_response(&self, _response: &mut ResponseView) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PluginAction {
+    Continue,
+    // answered with the error page for this status, later plugins and handlers are skipped
+    Respond(HttpStatus),
+}
+
+pub struct RequestView<'a> {
+    request: &'a mut Request,
+}
+
+impl<'a> RequestView<'a> {
+    pub fn new(request: &'a mut Request) -> Self {
+        Self { request }
+    }
+
+    pub fn method(&self) -> &str {
+        self.request.method.as_str()
+    }
+
+    pub fn path(&self) -> &str {
+        &self.request.path
+    }
+
+    // the handlers serve the new path, e.g. /old/page answered by /new/page
+    pub fn set_path(&mut self, path: &str) {
+        if path.starts_with('/') {
+            self.request.path = path.to_string();
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.request.header(name)
+    }
+
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.request.queries.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.request.client_ip
+    }
+
+    // the connection's own address, a trusted proxy's when the client is behind one
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.request.peer
+    }
+}
+
+pub struct ResponseView<'a> {
+    response: &'a mut Response,
+}
+
+impl<'a> ResponseView<'a> {
+    pub fn new(response: &'a mut Response) -> Self {
+        Self { response }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.response.request.path
+    }
+
+    pub fn status(&self) -> u16 {
+        self.response.status_code.to_code()
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.response
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // framing headers stay under Katana's control
+    pub fn set_header(&mut self, name: &str, value: &str) -> bool {
+        let reserved = ["Content-Length", "Transfer-Encoding", "Connection"];
+        if reserved.iter().any(|header| header.eq_ignore_ascii_case(name)) || value.contains(['\r', '\n']) {
+            return false;
+        }
+        self.response.set_header(name, value);
+        true
+    }
+}
+
+// WebAssembly plugins in --plugins-dir are checked for the hooks they export, running them
+// needs a WebAssembly runtime this build does not have
+pub struct WasmModule;
+
+impl WasmModule {
+    pub const EXTENSION: &'static str = "wasm";
+    pub const HOOKS: &'static [&'static str] = &["on_request", "on_response"];
+    const MAGIC: &'static [u8] = b"\0asm";
+    const VERSION: &'static [u8] = &[1, 0, 0, 0];
+    const EXPORT_SECTION: u8 = 7;
+    const FUNCTION_EXPORT: u8 = 0;
+
+    pub fn discover(dir: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut modules: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension == Self::EXTENSION))
+            .collect();
+        modules.sort();
+        modules
+    }
+
+    // the hooks a module exports as functions, an error when it is not a WebAssembly 1.0
+    // module or exports none of them
+    pub fn hooks(bytes: &[u8]) -> Result<Vec<String>, String> {
+        if bytes.get(..4) != Some(Self::MAGIC) || bytes.get(4..8) != Some(Self::VERSION) {
+            return Err("not a WebAssembly 1.0 module".to_string());
+        }
+        let mut position = 8;
+        let mut hooks = Vec::new();
+        while position < bytes.len() {
+            let id = bytes[position];
+            position += 1;
+            let size = Self::read_leb(bytes, &mut position).ok_or("truncated section")? as usize;
+            let end = position.checked_add(size).filter(|end| *end <= bytes.len()).ok_or("truncated section")?;
+            if id == Self::EXPORT_SECTION {
+                let mut at = position;
+                let count = Self::read_leb(bytes, &mut at).ok_or("malformed export section")?;
+                for _ in 0..count {
+                    let length = Self::read_leb(bytes, &mut at).ok_or("malformed export section")? as usize;
+                    let name = bytes.get(at..at + length).ok_or("malformed export section")?;
+                    at += length;
+                    let kind = *bytes.get(at).ok_or("malformed export section")?;
+                    at += 1;
+                    Self::read_leb(bytes, &mut at).ok_or("malformed export section")?;
+                    let name = String::from_utf8_lossy(name);
+                    if kind == Self::FUNCTION_EXPORT && Self::HOOKS.contains(&name.as_ref()) {
+                        hooks.push(name.into_owned());
+                    }
+                }
+            }
+            position = end;
+        }
+        if hooks.is_empty() {
+            return Err(format!("exports none of {}", Self::HOOKS.join(", ")));
+        }
+        Ok(hooks)
+    }
+
+    // unsigned LEB128, at most 32 bits
+    fn read_leb(bytes: &[u8], position: &mut usize) -> Option<u32> {
+        let mut value: u32 = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = *bytes.get(*position)?;
+            *position += 1;
+            value |= ((byte & 0x7f) as u32).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,91 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+static IDLE: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static REUSED: AtomicU64 = AtomicU64::new(0);
+
+// byte buffers shared by every connection: request lines, response heads with small bodies
+// and file chunks are written into one taken from here, and it goes back when dropped, so a
+// busy server serving small files stops asking the allocator for a fresh Vec per request
+pub struct BufferPool;
+
+// a buffer out of the pool, empty when taken, handed back on drop even while unwinding
+pub struct PooledBuffer(Vec<u8>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoolStats {
+    // waiting in the pool
+    pub idle: usize,
+    // taken and not given back yet
+    pub in_use: usize,
+    // taken with none idle, a new Vec was made
+    pub allocated: u64,
+    // taken from the idle ones
+    pub reused: u64,
+}
+
+impl BufferPool {
+    // beyond this many idle buffers the ones given back are freed
+    pub const MAX_IDLE: usize = 256;
+    // a buffer grown past this, a large file read at once, is freed rather than kept around
+    pub const MAX_CAPACITY: usize = 64 * 1024;
+
+    pub fn take() -> PooledBuffer {
+        IN_USE.fetch_add(1, Ordering::Relaxed);
+        let idle = IDLE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).pop();
+        match idle {
+            Some(buffer) => {
+                REUSED.fetch_add(1, Ordering::Relaxed);
+                PooledBuffer(buffer)
+            }
+            None => {
+                ALLOCATED.fetch_add(1, Ordering::Relaxed);
+                PooledBuffer(Vec::new())
+            }
+        }
+    }
+
+    pub fn stats() -> PoolStats {
+        PoolStats {
+            idle: IDLE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).len(),
+            in_use: IN_USE.load(Ordering::Relaxed),
+            allocated: ALLOCATED.load(Ordering::Relaxed),
+            reused: REUSED.load(Ordering::Relaxed),
+        }
+    }
+
+    fn give_back(mut buffer: Vec<u8>) {
+        IN_USE.fetch_sub(1, Ordering::Relaxed);
+        if buffer.capacity() == 0 || buffer.capacity() > Self::MAX_CAPACITY {
+            return;
+        }
+        buffer.clear();
+        let mut idle = IDLE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if idle.len() < Self::MAX_IDLE {
+            idle.push(buffer);
+        }
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        BufferPool::give_back(std::mem::take(&mut self.0));
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::{c_char, c_int};
+
+    extern "C" {
+        pub fn chroot(path: *const c_char) -> c_int;
+        pub fn chdir(path: *const c_char) -> c_int;
+        pub fn setgroups(size: usize, list: *const u32) -> c_int;
+        pub fn setgid(gid: u32) -> c_int;
+        pub fn setuid(uid: u32) -> c_int;
+        pub fn geteuid() -> u32;
+    }
+}
+
+// started as root to bind ports 80 and 443, the server gives root up once its listeners are
+// open: --chroot locks it into the root directory, so even compromised serving code cannot
+// read a file outside, and --user and --group switch to an account that owns nothing else.
+// Names are looked up before the chroot, in /etc/passwd and /etc/group
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Privileges {
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub chroot: bool,
+}
+
+impl Privileges {
+    pub const PASSWD: &'static str = "/etc/passwd";
+    pub const GROUP: &'static str = "/etc/group";
+
+    pub fn enabled(&self) -> bool {
+        self.user.is_some() || self.group.is_some() || self.chroot
+    }
+
+    // the uid and gid to switch to, numbers or names; without --group a user keeps its own
+    // primary group
+    pub fn resolve(&self, passwd: &str, group: &str) -> Result<(Option<u32>, Option<u32>), String> {
+        let number = |field: &str| field.parse::<u32>().ok();
+
+        let user = self.user.as_deref().map(str::trim);
+        let account = user.and_then(|user| Self::entry(passwd, user, 4));
+        let uid = match user {
+            Some(user) => Some(
+                number(user)
+                    .or_else(|| account.as_ref().and_then(|fields| number(fields[2])))
+                    .ok_or_else(|| format!("no such user: {}", user))?,
+            ),
+            None => None,
+        };
+        let gid = match self.group.as_deref().map(str::trim) {
+            Some(name) => Some(
+                number(name)
+                    .or_else(|| Self::entry(group, name, 3).and_then(|fields| number(fields[2])))
+                    .ok_or_else(|| format!("no such group: {}", name))?,
+            ),
+            None => account.and_then(|fields| number(fields[3])),
+        };
+        match (user, gid) {
+            (Some(user), None) => Err(format!("--group is needed, user {} has no entry in {}", user, Self::PASSWD)),
+            _ => Ok((uid, gid)),
+        }
+    }
+
+    // name:password:id:... in both files, found by its name or id
+    fn entry<'a>(file: &'a str, wanted: &str, min_fields: usize) -> Option<Vec<&'a str>> {
+        file.lines()
+            .map(|line| line.split(':').collect::<Vec<_>>())
+            .find(|fields| fields.len() >= min_fields && (fields[0] == wanted || fields[2] == wanted))
+    }
+
+    // after binding and before the first worker thread; the group goes first, a process that
+    // is no longer root cannot change it
+    #[cfg(unix)]
+    pub fn apply(&self, root_dir: &Path) -> Result<(), Error> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let read = |path: &str| std::fs::read_to_string(path).unwrap_or_default();
+        let (uid, gid) =
+            self.resolve(&read(Self::PASSWD), &read(Self::GROUP)).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        let check = |result: i32| if result == 0 { Ok(()) } else { Err(Error::last_os_error()) };
+        unsafe {
+            if self.chroot {
+                let root = CString::new(root_dir.canonicalize()?.as_os_str().as_bytes())?;
+                check(sys::chroot(root.as_ptr()))?;
+                check(sys::chdir(c"/".as_ptr()))?;
+            }
+            if let Some(gid) = gid {
+                check(sys::setgroups(0, std::ptr::null()))?;
+                check(sys::setgid(gid))?;
+            }
+            if let Some(uid) = uid {
+                check(sys::setuid(uid))?;
+                // root that can be taken back was never given up
+                if uid != 0 && (sys::setuid(0) == 0 || sys::geteuid() == 0) {
+                    return Err(Error::new(ErrorKind::PermissionDenied, "root privileges could be regained"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply(&self, _root_dir: &Path) -> Result<(), Error> {
+        Err(Error::new(ErrorKind::Unsupported, "dropping privileges is only available on unix systems"))
+    }
+}
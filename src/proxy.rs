@@ -0,0 +1,552 @@
+use crate::connection::Connection;
+use crate::http::HttpStatus;
+use crate::logger::Logger;
+use crate::request::Request;
+use crate::server::Server;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how the next upstream is picked:
+//
+//   round-robin        each one in turn, the default
+//   least-connections  the one with the fewest requests in flight, in turn among equals
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Balance {
+    #[default]
+    RoundRobin,
+    LeastConnections,
+}
+
+// a backend requests are sent to, host:port
+#[derive(Debug)]
+pub struct Upstream {
+    pub address: String,
+    // requests sent and not answered yet
+    active: AtomicUsize,
+    // failures in a row, back to 0 on a success or once it is ejected
+    failures: AtomicU32,
+    ejected_until: Mutex<Option<Instant>>,
+    // what the health checks last decided, up until probes say otherwise
+    up: AtomicBool,
+    // probes in a row that passed or failed, the other count goes back to 0
+    passes: AtomicU32,
+    fails: AtomicU32,
+    probing: AtomicBool,
+}
+
+// --proxy-health-path turns on probes: every --proxy-health-interval each upstream is asked
+// for the path, a 2xx or 3xx passes. One that fails --proxy-unhealthy-threshold probes in a
+// row is down and gets no requests until it passes --proxy-healthy-threshold in a row
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    pub path: Option<String>,
+    pub interval: Duration,
+    pub healthy_threshold: u32,
+    pub unhealthy_threshold: u32,
+}
+
+// what an upstream answered
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProxyResponse {
+    pub status: HttpStatus,
+    // the HTTP version of the status line, e.g. 1.1
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+// requests sent on to other servers instead of being served from the root, usually for a
+// location, e.g. an app with two instances so one can restart while the other answers:
+//
+//   # app.conf, with --location /api=app.conf
+//   proxy = ["127.0.0.1:3000", "127.0.0.1:3001"]
+//   proxy_balance = "least-connections"
+//
+// an upstream that cannot be connected to or fails to answer --proxy-max-fails times in a row
+// is left out for --proxy-fail-timeout; a request it refused goes to the next one. When all
+// of them are out they are tried anyway, the first one back answers
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    pub upstreams: Vec<Arc<Upstream>>,
+    pub balance: Balance,
+    pub max_fails: u32,
+    pub fail_timeout: Duration,
+    pub timeout: Duration,
+    pub health: HealthCheck,
+    // shared by the clones of the config a location keeps
+    next: Arc<AtomicUsize>,
+    last_check: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Default for Proxy {
+    fn default() -> Self {
+        Self {
+            upstreams: Vec::new(),
+            balance: Balance::default(),
+            max_fails: Self::DEFAULT_MAX_FAILS,
+            fail_timeout: Self::DEFAULT_FAIL_TIMEOUT,
+            timeout: Self::DEFAULT_TIMEOUT,
+            health: HealthCheck::default(),
+            next: Arc::new(AtomicUsize::new(0)),
+            last_check: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            path: None,
+            interval: Self::DEFAULT_INTERVAL,
+            healthy_threshold: Self::DEFAULT_HEALTHY_THRESHOLD,
+            unhealthy_threshold: Self::DEFAULT_UNHEALTHY_THRESHOLD,
+        }
+    }
+}
+
+impl HealthCheck {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+    pub const DEFAULT_HEALTHY_THRESHOLD: u32 = 2;
+    pub const DEFAULT_UNHEALTHY_THRESHOLD: u32 = 3;
+    // a probe waits no longer than this, nor than the interval
+    const MAX_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub fn timeout(&self) -> Duration {
+        self.interval.min(Self::MAX_TIMEOUT)
+    }
+}
+
+impl Balance {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "round-robin" | "round_robin" => Some(Balance::RoundRobin),
+            "least-connections" | "least_connections" => Some(Balance::LeastConnections),
+            _ => None,
+        }
+    }
+}
+
+impl Upstream {
+    pub fn new(address: &str) -> Self {
+        Self {
+            address: address.to_string(),
+            active: AtomicUsize::new(0),
+            failures: AtomicU32::new(0),
+            ejected_until: Mutex::new(None),
+            up: AtomicBool::new(true),
+            passes: AtomicU32::new(0),
+            fails: AtomicU32::new(0),
+            probing: AtomicBool::new(false),
+        }
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn is_ejected(&self) -> bool {
+        let ejected_until = self.ejected_until.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        ejected_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.up.load(Ordering::Relaxed)
+    }
+
+    // up, down or left out after failed requests, as shown on the status page
+    pub fn state(&self) -> &'static str {
+        if !self.is_up() {
+            "down"
+        } else if self.is_ejected() {
+            "left out"
+        } else {
+            "up"
+        }
+    }
+
+    // GET path, true for a 2xx or 3xx answer within the timeout
+    pub fn probe(&self, path: &str, timeout: Duration) -> bool {
+        let head = format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: {}/{} health check\r\nConnection: close\r\n\r\n",
+            path,
+            self.address,
+            Server::SERVER_NAME,
+            Server::SERVER_VERSION
+        );
+        let answer = Proxy::connect(&self.address, timeout).and_then(|mut stream| {
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+            stream.write_all(head.as_bytes())?;
+            let mut raw = Vec::new();
+            stream.read_to_end(&mut raw)?;
+            Ok(raw)
+        });
+        answer
+            .ok()
+            .and_then(|raw| Proxy::parse_response(&raw))
+            .is_some_and(|response| (200..400).contains(&response.status.to_code()))
+    }
+
+    fn record_probe(&self, passed: bool, health: &HealthCheck) {
+        if passed {
+            self.fails.store(0, Ordering::Relaxed);
+            let passes = self.passes.fetch_add(1, Ordering::Relaxed) + 1;
+            if !self.is_up() && passes >= health.healthy_threshold {
+                self.up.store(true, Ordering::Relaxed);
+                Logger::info(format!("Upstream {} is up", self.address).as_str());
+            }
+        } else {
+            self.passes.store(0, Ordering::Relaxed);
+            let fails = self.fails.fetch_add(1, Ordering::Relaxed) + 1;
+            if self.is_up() && fails >= health.unhealthy_threshold {
+                self.up.store(false, Ordering::Relaxed);
+                Logger::warn(format!("Upstream {} is down, {} health checks failed", self.address, fails).as_str());
+            }
+        }
+    }
+}
+
+impl Proxy {
+    pub const DEFAULT_MAX_FAILS: u32 = 1;
+    pub const DEFAULT_FAIL_TIMEOUT: Duration = Duration::from_secs(10);
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+    // about one connection only, dropped both ways along with the fields Connection names
+    // (RFC 9110 7.6.1)
+    pub const HOP_HEADERS: &'static [&'static str] = &[
+        "Connection",
+        "Keep-Alive",
+        "Proxy-Connection",
+        "Proxy-Authenticate",
+        "Proxy-Authorization",
+        "TE",
+        "Trailer",
+        "Transfer-Encoding",
+        "Upgrade",
+    ];
+    // set again for the upstream rather than passed on
+    const REPLACED_HEADERS: &'static [&'static str] = &[
+        "Content-Length",
+        "Expect",
+        // bodies come back as they are and are compressed here like any other
+        "Accept-Encoding",
+        "X-Forwarded-For",
+        "X-Forwarded-Host",
+        "X-Forwarded-Proto",
+        "Via",
+    ];
+
+    // host:port or http://host:port, there is no TLS to upstreams
+    pub fn add(&mut self, value: &str) -> Result<(), String> {
+        let address = value.trim();
+        let address = address.strip_prefix("http://").unwrap_or(address).trim_end_matches('/');
+        let valid = address
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && !host.contains('/') && port.parse::<u16>().is_ok());
+        if !valid {
+            return Err(format!("proxy upstream must be host:port or http://host:port: {}", value));
+        }
+        self.upstreams.push(Arc::new(Upstream::new(address)));
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.upstreams.is_empty()
+    }
+
+    // the upstreams in the order they are tried, the down or ejected ones only when none is left
+    pub fn order(&self) -> Vec<Arc<Upstream>> {
+        let available: Vec<_> = self
+            .upstreams
+            .iter()
+            .filter(|upstream| upstream.is_up() && !upstream.is_ejected())
+            .cloned()
+            .collect();
+        let mut order = if available.is_empty() { self.upstreams.clone() } else { available };
+        if !order.is_empty() {
+            let start = self.next.fetch_add(1, Ordering::Relaxed) % order.len();
+            order.rotate_left(start);
+        }
+        if self.balance == Balance::LeastConnections {
+            // stable, the rotation still takes turns among equally busy ones
+            order.sort_by_key(|upstream| upstream.active());
+        }
+        order
+    }
+
+    // probes every upstream in the background once the interval is up since the last time,
+    // an upstream still busy with the previous probe is skipped
+    pub fn check(&self) {
+        let Some(path) = &self.health.path else {
+            return;
+        };
+        {
+            let mut last_check = self.last_check.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if last_check.is_some_and(|last| last.elapsed() < self.health.interval) {
+                return;
+            }
+            *last_check = Some(Instant::now());
+        }
+        for upstream in &self.upstreams {
+            if upstream.probing.swap(true, Ordering::Relaxed) {
+                continue;
+            }
+            let (upstream, path, health) = (upstream.clone(), path.clone(), self.health.clone());
+            thread::spawn(move || {
+                let passed = upstream.probe(&path, health.timeout());
+                upstream.record_probe(passed, &health);
+                upstream.probing.store(false, Ordering::Relaxed);
+            });
+        }
+    }
+
+    // the answer of the first upstream that takes the request; one that cannot be connected
+    // to hands the request to the next, one that fails after it was sent ends it, the
+    // request may not be safe to send twice
+    pub fn forward(&self, request: &Request) -> Result<ProxyResponse, HttpStatus> {
+        let head = Self::request_head(request);
+        for upstream in self.order() {
+            let mut stream = match Self::connect(&upstream.address, Self::CONNECT_TIMEOUT) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    Logger::warn(format!("Cannot connect to upstream {}: {}", upstream.address, e).as_str());
+                    self.record_failure(&upstream);
+                    continue;
+                }
+            };
+            upstream.active.fetch_add(1, Ordering::Relaxed);
+            let answer = Self::exchange(&mut stream, &head, &request.body, self.timeout);
+            upstream.active.fetch_sub(1, Ordering::Relaxed);
+            return match answer {
+                Ok(raw) => {
+                    upstream.failures.store(0, Ordering::Relaxed);
+                    Self::parse_response(&raw).ok_or_else(|| {
+                        Logger::warn(format!("Invalid response from upstream {}", upstream.address).as_str());
+                        HttpStatus::BadGateway
+                    })
+                }
+                Err(e) => {
+                    Logger::warn(format!("Upstream {} failed: {}", upstream.address, e).as_str());
+                    self.record_failure(&upstream);
+                    match e.kind() {
+                        ErrorKind::WouldBlock | ErrorKind::TimedOut => Err(HttpStatus::GatewayTimeout),
+                        _ => Err(HttpStatus::BadGateway),
+                    }
+                }
+            };
+        }
+        Err(HttpStatus::BadGateway)
+    }
+
+    // HTTP/1.0 so the answer is never chunked and ends when the upstream closes
+    pub fn request_head(request: &Request) -> Vec<u8> {
+        let mut head = format!("{} {} HTTP/1.0\r\n", request.method.as_str(), request.target);
+        let connection = request.header("Connection");
+        for (name, value) in &request.headers {
+            let replaced = Self::REPLACED_HEADERS.iter().any(|replaced| replaced.eq_ignore_ascii_case(name));
+            if !replaced && !Self::is_hop_by_hop(name, connection) {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        let peer = request.peer.map(|peer| peer.ip().to_string());
+        let forwarded_for = match (request.header("X-Forwarded-For"), peer) {
+            (Some(chain), Some(peer)) => Some(format!("{}, {}", chain, peer)),
+            (chain, peer) => peer.or(chain.map(String::from)),
+        };
+        if let Some(forwarded_for) = forwarded_for {
+            head.push_str(&format!("X-Forwarded-For: {}\r\n", forwarded_for));
+        }
+        // the port too, for the absolute URLs the upstream makes; a trusted proxy in front
+        // already put the original one in the domain
+        if !request.domain.trim().is_empty() {
+            head.push_str(&format!("X-Forwarded-Host: {}\r\n", request.domain.trim()));
+        }
+        head.push_str(&format!("X-Forwarded-Proto: {}\r\n", request.scheme));
+        let version = request.version.as_str().trim_start_matches("HTTP/");
+        head.push_str(&format!("Via: {}\r\n", Self::via(request.header("Via"), version)));
+        if !request.body.is_empty() || request.header("Content-Length").is_some() {
+            head.push_str(&format!("Content-Length: {}\r\n", request.body.len()));
+        }
+        head.push_str("Connection: close\r\n\r\n");
+        head.into_bytes()
+    }
+
+    // a field of this hop, listed in HOP_HEADERS or named by the Connection header
+    pub fn is_hop_by_hop(name: &str, connection: Option<&str>) -> bool {
+        let listed = connection.is_some_and(|value| value.split(',').any(|option| option.trim().eq_ignore_ascii_case(name)));
+        listed || Self::HOP_HEADERS.iter().any(|hop| hop.eq_ignore_ascii_case(name))
+    }
+
+    // the Via received, with this server added as the hop the message went through in the
+    // given HTTP version, e.g. "1.0 squid, 1.1 katana"
+    pub fn via(received: Option<&str>, version: &str) -> String {
+        let hop = format!("{} {}", version, Server::SERVER_NAME.to_lowercase());
+        match received.map(str::trim).filter(|received| !received.is_empty()) {
+            Some(received) => format!("{}, {}", received, hop),
+            None => hop,
+        }
+    }
+
+    // status line, headers, a blank line and the body up to its Content-Length
+    pub fn parse_response(raw: &[u8]) -> Option<ProxyResponse> {
+        let end = raw.windows(4).position(|window| window == b"\r\n\r\n")?;
+        let head = std::str::from_utf8(&raw[..end]).ok()?;
+        let mut lines = head.lines();
+        let mut status_line = lines.next()?.splitn(3, ' ');
+        let version = status_line.next()?.strip_prefix("HTTP/")?.to_string();
+        let status = HttpStatus::from_code(status_line.next()?.parse().ok()?)?;
+        let mut headers = Vec::new();
+        for line in lines {
+            let (name, value) = line.split_once(':')?;
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+        let mut body = raw[end + 4..].to_vec();
+        let length = headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("Content-Length"));
+        if let Some(length) = length.and_then(|(_, value)| value.parse::<usize>().ok()) {
+            body.truncate(length);
+        }
+        Some(ProxyResponse {
+            status,
+            version,
+            headers,
+            body,
+        })
+    }
+
+    fn connect(address: &str, timeout: Duration) -> std::io::Result<TcpStream> {
+        let addrs: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+        let mut last_error = std::io::Error::new(ErrorKind::NotFound, "no address");
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    fn exchange(stream: &mut TcpStream, head: &[u8], body: &[u8], timeout: Duration) -> std::io::Result<Vec<u8>> {
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        stream.write_all(head)?;
+        stream.write_all(body)?;
+        let mut raw = Vec::new();
+        stream.read_to_end(&mut raw)?;
+        Ok(raw)
+    }
+
+    fn record_failure(&self, upstream: &Upstream) {
+        if upstream.failures.fetch_add(1, Ordering::Relaxed) + 1 < self.max_fails {
+            return;
+        }
+        upstream.failures.store(0, Ordering::Relaxed);
+        *upstream.ejected_until.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Instant::now() + self.fail_timeout);
+        Logger::warn(format!("Upstream {} left out for {}s", upstream.address, self.fail_timeout.as_secs()).as_str());
+    }
+}
+
+// --forward-proxy: clients set up to use the server as their HTTP proxy, e.g. on a jump host
+// in a lab. Plain http:// requests in absolute-form are sent on and CONNECT opens a tunnel for
+// anything else, TLS or SSH, but only to the destinations --forward-allow lists:
+//
+//   --forward-allow '*.lab.internal:443' --forward-allow 10.0.0.5:22 --forward-allow 'wiki:*'
+//
+// *.lab.internal covers the hosts below lab.internal, not lab.internal itself
+#[derive(Debug, Clone, Default)]
+pub struct ForwardProxy {
+    pub enabled: bool,
+    // host and port patterns, lowercase, * for any
+    pub allowed: Vec<(String, String)>,
+}
+
+impl ForwardProxy {
+    // a tunnel quiet for this long either way is closed
+    pub const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+    pub fn allow(&mut self, pattern: &str) -> Result<(), String> {
+        let (host, port) = pattern
+            .trim()
+            .rsplit_once(':')
+            .filter(|(host, port)| !host.is_empty() && (*port == "*" || port.parse::<u16>().is_ok()))
+            .ok_or_else(|| format!("forward proxy destination must be host:port, with * for any host or port: {}", pattern))?;
+        self.allowed.push((host.to_lowercase(), port.to_string()));
+        Ok(())
+    }
+
+    // authority is host:port, as Request::authority has it
+    pub fn allows(&self, authority: &str) -> bool {
+        let Some((host, port)) = authority.rsplit_once(':') else {
+            return false;
+        };
+        let host = host.to_lowercase();
+        self.allowed.iter().any(|(allowed_host, allowed_port)| {
+            let host_matches = match allowed_host.strip_prefix("*.") {
+                _ if allowed_host == "*" => true,
+                Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+                None => *allowed_host == host,
+            };
+            host_matches && (allowed_port == "*" || allowed_port == port)
+        })
+    }
+
+    pub fn open(authority: &str) -> std::io::Result<TcpStream> {
+        Proxy::connect(authority, Proxy::CONNECT_TIMEOUT)
+    }
+
+    // an absolute-form request, sent on with its origin-form target like a proxied one
+    pub fn forward(authority: &str, request: &Request) -> Result<ProxyResponse, HttpStatus> {
+        let mut stream = Self::open(authority).map_err(|e| {
+            Logger::warn(format!("Cannot connect to {}: {}", authority, e).as_str());
+            HttpStatus::BadGateway
+        })?;
+        let raw = Proxy::exchange(&mut stream, &Proxy::request_head(request), &request.body, Proxy::DEFAULT_TIMEOUT)
+            .map_err(|e| match e.kind() {
+                ErrorKind::WouldBlock | ErrorKind::TimedOut => HttpStatus::GatewayTimeout,
+                _ => HttpStatus::BadGateway,
+            })?;
+        Proxy::parse_response(&raw).ok_or(HttpStatus::BadGateway)
+    }
+
+    // copies both ways, what the client sent past its CONNECT head first, until the
+    // destination is done or either side stays quiet for IDLE_TIMEOUT; returns the bytes
+    // sent to the destination and back to the client
+    pub fn tunnel(client: &mut Connection, destination: TcpStream, buffered: &[u8]) -> (u64, u64) {
+        let clones = client.try_clone().and_then(|reader| Ok((reader, destination.try_clone()?)));
+        let Ok((mut client_reader, mut destination_writer)) = clones else {
+            return (0, 0);
+        };
+        let _ = client.set_read_timeout(Some(Self::IDLE_TIMEOUT));
+        let _ = destination.set_read_timeout(Some(Self::IDLE_TIMEOUT));
+        let buffered = buffered.to_vec();
+        let up = thread::spawn(move || {
+            if destination_writer.write_all(&buffered).is_err() {
+                return 0;
+            }
+            let sent = Self::pipe(&mut client_reader, &mut destination_writer);
+            // the destination learns the client is done and can finish its answer
+            let _ = destination_writer.shutdown(Shutdown::Write);
+            buffered.len() as u64 + sent
+        });
+        let down = Self::pipe(&mut &destination, client);
+        let _ = client.shutdown(Shutdown::Both);
+        let _ = destination.shutdown(Shutdown::Both);
+        (up.join().unwrap_or(0), down)
+    }
+
+    fn pipe<R: Read, W: Write>(from: &mut R, to: &mut W) -> u64 {
+        let mut buffer = [0; 16 * 1024];
+        let mut copied = 0;
+        while let Ok(read) = from.read(&mut buffer) {
+            if read == 0 || to.write_all(&buffer[..read]).is_err() {
+                break;
+            }
+            copied += read as u64;
+        }
+        copied
+    }
+}
@@ -0,0 +1,406 @@
+// a small QR code encoder: byte mode, low error correction, versions 1 to 10,
+// which is plenty for a URL printed in a terminal
+// @see: ISO/IEC 18004, and https://www.nayuki.io/page/creating-a-qr-code-step-by-step
+pub struct QrCode {
+    pub version: usize,
+    pub size: usize,
+    modules: Vec<bool>,
+    function: Vec<bool>,
+}
+
+impl QrCode {
+    const MAX_VERSION: usize = 10;
+    // error correction codewords per block and number of blocks at level L
+    const ECC_PER_BLOCK: [usize; 10] = [7, 10, 15, 20, 26, 18, 20, 24, 30, 18];
+    const BLOCKS: [usize; 10] = [1, 1, 1, 1, 1, 2, 2, 2, 2, 4];
+    const FORMAT_LEVEL_L: u32 = 1;
+
+    pub fn encode(data: &[u8]) -> Option<Self> {
+        let version = (1..=Self::MAX_VERSION).find(|&v| Self::data_bits(v, data.len()) <= Self::data_codewords(v) * 8)?;
+
+        let mut qr = Self {
+            version,
+            size: version * 4 + 17,
+            modules: vec![false; (version * 4 + 17).pow(2)],
+            function: vec![false; (version * 4 + 17).pow(2)],
+        };
+        qr.draw_function_patterns();
+        let codewords = qr.add_ecc(&Self::data_codewords_for(version, data));
+        qr.draw_codewords(&codewords);
+
+        let mask = (0..8)
+            .min_by_key(|&mask| {
+                qr.apply_mask(mask);
+                qr.draw_format_bits(mask);
+                let penalty = qr.penalty();
+                qr.apply_mask(mask); // masks are XOR, applying twice undoes it
+                penalty
+            })
+            .unwrap_or(0);
+        qr.apply_mask(mask);
+        qr.draw_format_bits(mask);
+        Some(qr)
+    }
+
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    // two modules per character with half blocks, light modules are drawn so the code
+    // reads correctly on the usual dark terminal background
+    pub fn to_terminal(&self) -> String {
+        const BORDER: i32 = 2;
+        let size = self.size as i32;
+        let light = |x: i32, y: i32| {
+            x < 0 || y < 0 || x >= size || y >= size || !self.is_dark(x as usize, y as usize)
+        };
+
+        let mut result = String::new();
+        let mut y = -BORDER;
+        while y < size + BORDER {
+            for x in -BORDER..size + BORDER {
+                result.push(match (light(x, y), light(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            result.push('\n');
+            y += 2;
+        }
+        result
+    }
+
+    fn data_bits(version: usize, len: usize) -> usize {
+        4 + Self::count_bits(version) + len * 8
+    }
+
+    fn count_bits(version: usize) -> usize {
+        if version < 10 { 8 } else { 16 }
+    }
+
+    fn raw_codewords(version: usize) -> usize {
+        let mut modules = (16 * version + 128) * version + 64;
+        if version >= 2 {
+            let align = version / 7 + 2;
+            modules -= (25 * align - 10) * align - 55;
+            if version >= 7 {
+                modules -= 36;
+            }
+        }
+        modules / 8
+    }
+
+    fn data_codewords(version: usize) -> usize {
+        Self::raw_codewords(version) - Self::ECC_PER_BLOCK[version - 1] * Self::BLOCKS[version - 1]
+    }
+
+    fn data_codewords_for(version: usize, data: &[u8]) -> Vec<u8> {
+        let mut bits: Vec<bool> = Vec::new();
+        let mut push = |value: usize, count: usize| {
+            for i in (0..count).rev() {
+                bits.push((value >> i) & 1 == 1);
+            }
+        };
+        push(0b0100, 4); // byte mode
+        push(data.len(), Self::count_bits(version));
+        for byte in data {
+            push(*byte as usize, 8);
+        }
+
+        let capacity = Self::data_codewords(version) * 8;
+        let terminator = (capacity - bits.len()).min(4);
+        bits.extend(std::iter::repeat_n(false, terminator));
+        while !bits.len().is_multiple_of(8) {
+            bits.push(false);
+        }
+
+        let mut codewords: Vec<u8> = bits
+            .chunks(8)
+            .map(|chunk| chunk.iter().fold(0, |byte, bit| (byte << 1) | *bit as u8))
+            .collect();
+        for pad in [0xEC, 0x11].iter().cycle() {
+            if codewords.len() * 8 >= capacity {
+                break;
+            }
+            codewords.push(*pad);
+        }
+        codewords
+    }
+
+    // splits into blocks, appends Reed-Solomon codewords and interleaves everything
+    fn add_ecc(&self, data: &[u8]) -> Vec<u8> {
+        let blocks = Self::BLOCKS[self.version - 1];
+        let ecc = Self::ECC_PER_BLOCK[self.version - 1];
+        let raw = Self::raw_codewords(self.version);
+        let short_blocks = blocks - raw % blocks;
+        let short_len = raw / blocks - ecc;
+        let divisor = Self::rs_divisor(ecc);
+
+        let mut split = Vec::with_capacity(blocks);
+        let mut offset = 0;
+        for i in 0..blocks {
+            let len = short_len + usize::from(i >= short_blocks);
+            let block = &data[offset..offset + len];
+            split.push((block.to_vec(), Self::rs_remainder(block, &divisor)));
+            offset += len;
+        }
+
+        let mut result = Vec::with_capacity(raw);
+        for i in 0..=short_len {
+            for (block, _) in &split {
+                if let Some(byte) = block.get(i) {
+                    result.push(*byte);
+                }
+            }
+        }
+        for i in 0..ecc {
+            for (_, remainder) in &split {
+                result.push(remainder[i]);
+            }
+        }
+        result
+    }
+
+    fn gf_multiply(x: u8, y: u8) -> u8 {
+        let mut z: u16 = 0;
+        for i in (0..8).rev() {
+            z = (z << 1) ^ ((z >> 7) * 0x11D);
+            z ^= ((y as u16 >> i) & 1) * x as u16;
+        }
+        z as u8
+    }
+
+    fn rs_divisor(degree: usize) -> Vec<u8> {
+        let mut result = vec![0; degree];
+        result[degree - 1] = 1;
+        let mut root = 1;
+        for _ in 0..degree {
+            for j in 0..degree {
+                result[j] = Self::gf_multiply(result[j], root);
+                if j + 1 < degree {
+                    result[j] ^= result[j + 1];
+                }
+            }
+            root = Self::gf_multiply(root, 0x02);
+        }
+        result
+    }
+
+    fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+        let mut result = vec![0; divisor.len()];
+        for byte in data {
+            let factor = byte ^ result.remove(0);
+            result.push(0);
+            for (r, d) in result.iter_mut().zip(divisor) {
+                *r ^= Self::gf_multiply(*d, factor);
+            }
+        }
+        result
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn draw_function_patterns(&mut self) {
+        for i in 0..self.size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+
+        let far = self.size - 4;
+        for (x, y) in [(3, 3), (far, 3), (3, far)] {
+            self.draw_finder(x, y);
+        }
+
+        let positions = self.alignment_positions();
+        let last = positions.len().saturating_sub(1);
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                // the three corners are taken by finder patterns
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                self.draw_alignment(x, y);
+            }
+        }
+
+        self.draw_format_bits(0); // reserves the area, overwritten once the mask is known
+        self.draw_version();
+    }
+
+    fn draw_finder(&mut self, x: usize, y: usize) {
+        for dy in -4i32..=4 {
+            for dx in -4i32..=4 {
+                let (xx, yy) = (x as i32 + dx, y as i32 + dy);
+                if xx >= 0 && yy >= 0 && (xx as usize) < self.size && (yy as usize) < self.size {
+                    let distance = dx.abs().max(dy.abs());
+                    self.set_function(xx as usize, yy as usize, distance != 2 && distance != 4);
+                }
+            }
+        }
+    }
+
+    fn draw_alignment(&mut self, x: usize, y: usize) {
+        for dy in -2i32..=2 {
+            for dx in -2i32..=2 {
+                let dark = dx.abs().max(dy.abs()) != 1;
+                self.set_function((x as i32 + dx) as usize, (y as i32 + dy) as usize, dark);
+            }
+        }
+    }
+
+    fn alignment_positions(&self) -> Vec<usize> {
+        if self.version == 1 {
+            return Vec::new();
+        }
+        let count = self.version / 7 + 2;
+        let step = (self.version * 4 + count * 2 + 1) / (count * 2 - 2) * 2;
+        let mut positions = vec![6];
+        let mut position = self.size - 7;
+        for _ in 0..count - 1 {
+            positions.insert(1, position);
+            position -= step;
+        }
+        positions
+    }
+
+    fn draw_format_bits(&mut self, mask: u32) {
+        let data = (Self::FORMAT_LEVEL_L << 3) | mask;
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = ((data << 10) | remainder) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 == 1;
+
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        for i in 0..8 {
+            self.set_function(self.size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, self.size - 15 + i, bit(i));
+        }
+        self.set_function(8, self.size - 8, true);
+    }
+
+    fn draw_version(&mut self) {
+        if self.version < 7 {
+            return;
+        }
+        let mut remainder = self.version as u32;
+        for _ in 0..12 {
+            remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+        }
+        let bits = ((self.version as u32) << 12) | remainder;
+        for i in 0..18 {
+            let dark = (bits >> i) & 1 == 1;
+            let (a, b) = (self.size - 11 + i % 3, i / 3);
+            self.set_function(a, b, dark);
+            self.set_function(b, a, dark);
+        }
+    }
+
+    // zigzags up and down two columns at a time from the bottom right corner
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let mut i = 0;
+        let mut right = self.size as i32 - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5; // skips the vertical timing pattern
+            }
+            for vertical in 0..self.size {
+                for j in 0..2 {
+                    let x = (right - j) as usize;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward { self.size - 1 - vertical } else { vertical };
+                    if !self.function[y * self.size + x] && i < codewords.len() * 8 {
+                        self.modules[y * self.size + x] = (codewords[i >> 3] >> (7 - (i & 7))) & 1 == 1;
+                        i += 1;
+                    }
+                }
+            }
+            right -= 2;
+        }
+    }
+
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                if invert && !self.function[y * self.size + x] {
+                    self.modules[y * self.size + x] ^= true;
+                }
+            }
+        }
+    }
+
+    // the four penalty rules used to choose between masks
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let mut penalty = 0;
+        let finder_like = [true, false, true, true, true, false, true, false, false, false, false];
+
+        for horizontal in [true, false] {
+            for a in 0..size {
+                let line: Vec<bool> = (0..size)
+                    .map(|b| if horizontal { self.is_dark(b, a) } else { self.is_dark(a, b) })
+                    .collect();
+
+                let mut run = 1;
+                for b in 1..=size {
+                    if b < size && line[b] == line[b - 1] {
+                        run += 1;
+                        continue;
+                    }
+                    if run >= 5 {
+                        penalty += run - 2;
+                    }
+                    run = 1;
+                }
+
+                for window in line.windows(finder_like.len()) {
+                    if window == finder_like || window.iter().rev().eq(finder_like.iter()) {
+                        penalty += 40;
+                    }
+                }
+            }
+        }
+
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let dark = self.is_dark(x, y);
+                if dark == self.is_dark(x + 1, y) && dark == self.is_dark(x, y + 1) && dark == self.is_dark(x + 1, y + 1) {
+                    penalty += 3;
+                }
+            }
+        }
+
+        let total = size * size;
+        let dark = self.modules.iter().filter(|dark| **dark).count();
+        let deviation = (dark * 20).abs_diff(total * 10);
+        penalty += (deviation.div_ceil(total)).saturating_sub(1) * 10;
+        penalty
+    }
+}
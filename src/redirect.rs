@@ -0,0 +1,80 @@
+use crate::http::HttpStatus;
+use crate::utils::Utils;
+
+// moved pages and shortlinks, answered before any file is looked up. A rule is
+// `FROM TO [STATUS]`: FROM is an exact path, or a prefix when it ends in /*, whose rest of the
+// path replaces {rest} in TO. TO is a path or an absolute URL, the status one of 301 (the
+// default), 302, 307 or 308
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    // without the /* of a prefix
+    pub from: String,
+    pub prefix: bool,
+    pub to: String,
+    pub status: HttpStatus,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Redirects {
+    pub rules: Vec<Redirect>,
+}
+
+impl Redirects {
+    pub const REST: &'static str = "{rest}";
+
+    pub fn parse(value: &str) -> Result<Redirect, String> {
+        let parts: Vec<&str> = value.split_whitespace().collect();
+        let (from, to, status) = match parts[..] {
+            [from, to] => (from, to, HttpStatus::MovedPermanently),
+            [from, to, status] => (from, to, Self::parse_status(status)?),
+            _ => return Err(format!("redirect must be `FROM TO [STATUS]`: {}", value)),
+        };
+        if !from.starts_with('/') {
+            return Err(format!("redirect must start from a path: {}", from));
+        }
+        let (from, prefix) = match from.strip_suffix("/*") {
+            Some(from) => (from, true),
+            None => (from, false),
+        };
+        if to.contains(Self::REST) && !prefix {
+            return Err(format!("only a redirect from a /* prefix has a {} to fill: {}", Self::REST, value));
+        }
+        Ok(Redirect { from: from.to_string(), prefix, to: to.to_string(), status })
+    }
+
+    fn parse_status(status: &str) -> Result<HttpStatus, String> {
+        match status {
+            "301" => Ok(HttpStatus::MovedPermanently),
+            "302" => Ok(HttpStatus::Found),
+            "307" => Ok(HttpStatus::TemporaryRedirect),
+            "308" => Ok(HttpStatus::PermanentRedirect),
+            _ => Err(format!("redirect status must be 301, 302, 307 or 308: {}", status)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // an exact path wins over the prefixes, then the longest prefix; the query of the request
+    // is kept unless the target has its own
+    pub fn resolve(&self, path: &str, query: Option<&str>) -> Option<(HttpStatus, String)> {
+        let exact = self.rules.iter().find(|rule| !rule.prefix && rule.from == path);
+        let rule = exact.or_else(|| {
+            self.rules
+                .iter()
+                .filter(|rule| rule.prefix && Utils::under_prefix(path, &rule.from))
+                .max_by_key(|rule| rule.from.trim_end_matches('/').len())
+        })?;
+
+        let mut location = rule.to.clone();
+        if rule.prefix {
+            let rest = path[rule.from.trim_end_matches('/').len()..].trim_start_matches('/');
+            location = location.replace(Self::REST, &Utils::encode_url_path(rest));
+        }
+        match query.filter(|query| !query.is_empty()) {
+            Some(query) if !location.contains('?') => Some((rule.status, format!("{}?{}", location, query))),
+            _ => Some((rule.status, location)),
+        }
+    }
+}
@@ -1,125 +1,549 @@
-use crate::http::{HttpMethod, HttpVersion};
+use crate::compression::ContentEncoding;
+use crate::http::{HttpMethod, HttpStatus, HttpVersion};
 use crate::logger::Logger;
-use crate::server::Server;
-use std::io::{BufRead, BufReader, Read};
-use std::net::TcpStream;
+use crate::utils::Utils;
+use std::fmt;
+use std::io::{BufRead, BufReader, Error, Read};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::time::Instant;
+
+#[derive(Debug)]
+pub enum RequestError {
+    // the connection ended cleanly before a new request started
+    Closed,
+    BadRequest(String),
+    UriTooLong,
+    HeadersTooLarge,
+    NotImplemented(String),
+    // a Transfer-Encoding other than chunked, e.g. gzip, chunked
+    UnsupportedTransferCoding(String),
+    VersionNotSupported,
+    PayloadTooLarge,
+    // an Expect other than 100-continue
+    ExpectationFailed(String),
+    Io(Error),
+}
+
+impl RequestError {
+    // the answer owed to the client, None when there is nobody left to answer
+    pub fn status(&self) -> Option<HttpStatus> {
+        match self {
+            RequestError::Closed | RequestError::Io(_) => None,
+            RequestError::BadRequest(_) => Some(HttpStatus::BadRequest),
+            RequestError::UriTooLong => Some(HttpStatus::URITooLong),
+            RequestError::HeadersTooLarge => Some(HttpStatus::RequestHeaderFieldsTooLarge),
+            RequestError::NotImplemented(_) | RequestError::UnsupportedTransferCoding(_) => Some(HttpStatus::NotImplemented),
+            RequestError::VersionNotSupported => Some(HttpStatus::HTTPVersionNotSupported),
+            RequestError::PayloadTooLarge => Some(HttpStatus::PayloadTooLarge),
+            RequestError::ExpectationFailed(_) => Some(HttpStatus::ExpectationFailed),
+        }
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Closed => write!(f, "connection closed"),
+            RequestError::BadRequest(reason) => write!(f, "{}", reason),
+            RequestError::UriTooLong => write!(f, "request line too long"),
+            RequestError::HeadersTooLarge => write!(f, "request headers too large"),
+            RequestError::NotImplemented(method) => write!(f, "method {} not implemented", method),
+            RequestError::UnsupportedTransferCoding(coding) => write!(f, "transfer coding {} not implemented", coding),
+            RequestError::VersionNotSupported => write!(f, "HTTP version not supported"),
+            RequestError::PayloadTooLarge => write!(f, "payload too large"),
+            RequestError::ExpectationFailed(expectation) => write!(f, "expectation {} not supported", expectation),
+            RequestError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// what a header sent more than once becomes, so every handler reads the same single value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Duplicate {
+    // list-valued fields, joined in the order they came with the separator (RFC 9110 5.3)
+    Merge(&'static str),
+    // single values, later copies are dropped like header() always did
+    First,
+    // fields two parties could read differently, e.g. a proxy in front taking the other
+    // Content-Length, refused with a 400
+    Reject,
+}
+
+#[derive(Debug, Default)]
+struct ForwardedElement {
+    for_node: Option<String>,
+    proto: Option<String>,
+    host: Option<String>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Request {
     pub version: HttpVersion,
     pub domain: String,
     pub path: String,
+    // the request target exactly as sent, e.g. to rebuild the URL of a redirect
+    pub target: String,
+    // host:port of an http:// absolute-form target or a CONNECT, where a forward proxy goes
+    pub authority: Option<String>,
     pub method: HttpMethod,
     pub queries: Vec<(String, String)>,
     pub headers: Vec<(String, String)>,
     pub cookies: Vec<(String, String)>,
-    pub body: String,
+    pub body: Vec<u8>,
+    // the fields sent after a chunked body, kept apart from the headers
+    pub trailers: Vec<(String, String)>,
+    pub id: String,
+    // when the first byte of the request was available
+    pub received_at: Instant,
+    // the socket peer, and the client behind it once trusted proxy headers are applied
+    pub peer: Option<SocketAddr>,
+    pub client_ip: Option<IpAddr>,
+    pub scheme: String,
+}
+
+// stands in for a request whose head could not be parsed, so it can still be answered
+impl Default for Request {
+    fn default() -> Self {
+        Self {
+            version: HttpVersion::Http11,
+            domain: String::new(),
+            path: "/".to_string(),
+            target: "/".to_string(),
+            authority: None,
+            method: HttpMethod::GET,
+            queries: Vec::new(),
+            headers: Vec::new(),
+            cookies: Vec::new(),
+            body: Vec::new(),
+            trailers: Vec::new(),
+            id: String::new(),
+            received_at: Instant::now(),
+            peer: None,
+            client_ip: None,
+            scheme: "http".to_string(),
+        }
+    }
 }
 
 impl Request {
+    const MAX_ID_LENGTH: usize = 128;
+    const MAX_REQUEST_LINE: usize = 8192;
+    const MAX_HEADER_LINE: usize = 8192;
+    const MAX_HEADERS: usize = 100;
+    const MAX_EMPTY_LINES: usize = 4;
+    // what a browser sends, room made for them up front
+    const USUAL_HEADERS: usize = 16;
+    // a hex size with chunk extensions, which are read and ignored
+    const MAX_CHUNK_LINE: usize = 4096;
+    pub const REJECTED_DUPLICATES: &'static [&'static str] =
+        &["Host", "Content-Length", "Authorization", "Content-Type", "Range"];
+    pub const LIST_HEADERS: &'static [&'static str] = &[
+        "Accept",
+        "Accept-Charset",
+        "Accept-Encoding",
+        "Accept-Language",
+        "Cache-Control",
+        "Connection",
+        "Expect",
+        "Forwarded",
+        "If-Match",
+        "If-None-Match",
+        "Pragma",
+        "TE",
+        "Trailer",
+        "Transfer-Encoding",
+        "Upgrade",
+        "Via",
+        "X-Forwarded-For",
+    ];
+
+    pub fn duplicate(name: &str) -> Duplicate {
+        let is = |names: &[&str]| names.iter().any(|known| known.eq_ignore_ascii_case(name));
+        if is(Self::REJECTED_DUPLICATES) {
+            Duplicate::Reject
+        } else if name.eq_ignore_ascii_case("Cookie") {
+            Duplicate::Merge("; ")
+        } else if is(Self::LIST_HEADERS) {
+            Duplicate::Merge(", ")
+        } else {
+            Duplicate::First
+        }
+    }
+
     pub fn from_stream(mut stream: &TcpStream) -> Option<Self> {
         let mut reader = BufReader::new(&mut stream);
+        Self::from_reader(&mut reader)
+    }
 
-        // read the request line (e.g., "GET /path?foo=bar HTTP/1.1")
-        let mut request_line = String::new();
-        if reader.read_line(&mut request_line).ok()? == 0 {
-            return None;
-        }
-        let request_line = request_line.trim_end();
-        let parts: Vec<&str> = request_line.split_whitespace().collect();
-        if parts.len() < 3 {
-            return None; // invalid request
-        }
+    // the reader is kept by the caller so buffered bytes of pipelined requests
+    // survive between two calls on the same persistent connection
+    pub fn from_reader<R: BufRead>(reader: &mut R) -> Option<Self> {
+        let mut request = Self::read_head(reader).ok()?;
+        request.read_body(reader, usize::MAX).ok()?;
+        Some(request)
+    }
+
+    pub fn read_head<R: BufRead>(reader: &mut R) -> Result<Self, RequestError> {
+        Self::read_head_with(reader, &mut Vec::new())
+    }
 
-        let method = HttpMethod::from_str(parts[0]).unwrap();
-        let raw_path = parts[1];
-        let mut path = Self::decode_url(raw_path);
-        let version = HttpVersion::from_str(&parts[2].replace("HTTP/", "")).unwrap();
+    // lines are read into a buffer the caller keeps for the whole connection, so only what
+    // the request holds on to is allocated: the target, the path and each header name and value
+    pub fn read_head_with<R: BufRead>(reader: &mut R, buffer: &mut Vec<u8>) -> Result<Self, RequestError> {
+        let received_at = Instant::now();
+
+        // read the request line (e.g., "GET /path?foo=bar HTTP/1.1"), empty lines left over
+        // from a previous request are skipped (RFC 9112 2.2)
+        let mut skipped = 0;
+        let (method, raw_path, version, authority) = loop {
+            let request_line = Self::read_line_into(reader, buffer, Self::MAX_REQUEST_LINE, RequestError::UriTooLong)?
+                .ok_or(RequestError::Closed)?;
+            if !request_line.is_empty() || skipped >= Self::MAX_EMPTY_LINES {
+                let (method, target, version) = Self::parse_request_line(request_line)?;
+                break (method, target, version, Self::authority(request_line));
+            }
+            skipped += 1;
+        };
+        let mut path = Self::decode_url(&raw_path);
 
         let mut domain = String::new();
         let mut queries = Vec::new();
-        let mut headers = Vec::new();
+        let mut headers: Vec<(String, String)> = Vec::with_capacity(Self::USUAL_HEADERS);
         let mut cookies = Vec::new();
-        let mut body = String::new(); // you the correct type
 
         // extract queries from the path (if any)
-        if let Some((path_part, query_part)) = path.clone().split_once('?') {
-            path = path_part.to_string();
-            queries = query_part
+        if let Some(start) = path.find('?') {
+            queries = path[start + 1..]
                 .split('&')
                 .filter_map(|pair| pair.split_once('='))
                 .map(|(k, v)| (k.to_string(), v.to_string()))
                 .collect();
+            path.truncate(start);
         }
 
-        // read headers line by line until an empty line is encountered
-        loop {
-            let mut line = String::new();
-            let bytes_read = reader.read_line(&mut line).ok()?;
-            if bytes_read == 0 {
-                break; // end of stream reached unexpectedly
-            }
-            let line = line.trim_end();
+        // read headers line by line until an empty line is encountered, repeated ones count too
+        for count in 0.. {
+            let line = Self::read_line_into(reader, buffer, Self::MAX_HEADER_LINE, RequestError::HeadersTooLarge)?
+                .ok_or_else(|| RequestError::BadRequest("connection closed inside the headers".to_string()))?;
             if line.is_empty() {
                 break; // end of headers
             }
-            if let Some((key, value)) = line.split_once(": ") {
-                let key = key.to_string();
-                let value = value.to_string();
-                headers.push((key.clone(), value.clone()));
-
-                if key.to_lowercase() == "host" {
-                    domain = value;
-                } else if key.to_lowercase() == "cookie" {
-                    cookies = value
-                        .split("; ")
-                        .filter_map(|cookie| cookie.split_once('='))
-                        .map(|(k, v)| (k.to_string(), v.to_string()))
-                        .collect();
-                }
+            if count >= Self::MAX_HEADERS {
+                return Err(RequestError::HeadersTooLarge);
+            }
+            // no whitespace is allowed between the name and the colon (RFC 9112 5.1)
+            let (key, value) = line
+                .split_once(':')
+                .filter(|(key, _)| !key.is_empty() && key.bytes().all(Self::is_token_byte))
+                .ok_or_else(|| RequestError::BadRequest(format!("malformed header line: {}", line)))?;
+            let value = value.trim();
+            // a list like "5, 5" is a repeated Content-Length too; every value let through
+            // parses, one too large for a usize included
+            let is_length = key.eq_ignore_ascii_case("Content-Length");
+            if is_length && (!value.bytes().all(|b| b.is_ascii_digit()) || value.parse::<usize>().is_err()) {
+                return Err(RequestError::BadRequest(format!("invalid Content-Length: {}", value)));
+            }
+            match headers.iter_mut().find(|(name, _)| name.eq_ignore_ascii_case(key)) {
+                None => headers.push((key.to_string(), value.to_string())),
+                Some((_, existing)) => match Self::duplicate(key) {
+                    Duplicate::Merge(separator) => {
+                        if existing.is_empty() {
+                            existing.push_str(value);
+                        } else if !value.is_empty() {
+                            existing.push_str(separator);
+                            existing.push_str(value);
+                        }
+                    }
+                    Duplicate::First => {}
+                    Duplicate::Reject => return Err(RequestError::BadRequest(format!("repeated {} header", key))),
+                },
             }
         }
 
-        // process body only if method is allowed
-        if Server::SUPPORTED_HTTP_METHODS.contains(&method) {
-            // check for a content-length header and read the body if provided
-            if let Some((_, cl_value)) = headers
-                .iter()
-                .find(|(key, _)| key.to_lowercase() == "content-length")
-            {
-                if let Ok(content_length) = cl_value.trim().parse::<usize>() {
-                    let mut buf = vec![0; content_length];
-                    if let Err(e) = reader.read_exact(&mut buf) {
-                        Logger::warn(&format!("Error reading body: {}", e));
-                        return None;
-                    }
-                    // assuming the body is UTF-8 encoded text
-                    body = String::from_utf8_lossy(&buf).to_string();
-                }
+        for (key, value) in &headers {
+            if key.eq_ignore_ascii_case("host") {
+                domain = value.clone();
+            } else if key.eq_ignore_ascii_case("cookie") {
+                cookies = value
+                    .split(';')
+                    .filter_map(|cookie| cookie.trim().split_once('='))
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect();
             }
-        } else {
-            Logger::warn(
-                &format!(
-                    "Method '{}' on '{}' is disable",
-                    method.as_str(),
-                    path.as_str()
-                ),
-            );
         }
 
-        Some(Self {
+        Ok(Self {
             method,
             path,
+            target: raw_path,
+            authority,
             version,
             domain,
             queries,
             headers,
             cookies,
-            body,
+            body: Vec::new(),
+            trailers: Vec::new(),
+            id: String::new(),
+            received_at,
+            peer: None,
+            client_ip: None,
+            scheme: "http".to_string(),
         })
     }
 
+    // method SP request-target SP HTTP-version, with exactly one space between them
+    // @see: https://www.rfc-editor.org/rfc/rfc9112#section-3
+    pub fn parse_request_line(line: &str) -> Result<(HttpMethod, String, HttpVersion), RequestError> {
+        let bad = |reason: &str| RequestError::BadRequest(format!("{}: {}", reason, line));
+        let mut parts = line.split(' ');
+        let (Some(method), Some(target), Some(version), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(bad("malformed request line"));
+        };
+
+        if method.is_empty() || !method.bytes().all(Self::is_token_byte) {
+            return Err(bad("invalid method"));
+        }
+        let number = version
+            .strip_prefix("HTTP/")
+            .filter(|number| matches!(number.as_bytes(), [major, b'.', minor] if major.is_ascii_digit() && minor.is_ascii_digit()))
+            .ok_or_else(|| bad("invalid HTTP version"))?;
+        // HTTP/2 and later never arrive as a request line
+        let version = match number {
+            "1.0" | "1.1" => HttpVersion::from_str(number),
+            _ => None,
+        }
+        .ok_or(RequestError::VersionNotSupported)?;
+        // a well-formed method we do not know of
+        let method = HttpMethod::from_str(method).ok_or_else(|| RequestError::NotImplemented(method.to_string()))?;
+
+        let target = match target.strip_prefix("http://").or_else(|| target.strip_prefix("https://")) {
+            // absolute-form, only the path and query are kept
+            Some(rest) => match rest.find(['/', '?']) {
+                Some(start) if rest.as_bytes()[start] == b'/' => rest[start..].to_string(),
+                Some(start) => format!("/{}", &rest[start..]),
+                None => "/".to_string(),
+            },
+            None => target.to_string(),
+        };
+        // authority-form is for CONNECT only, e.g. CONNECT example.com:443
+        let is_authority = method == HttpMethod::CONNECT
+            && target.rsplit_once(':').is_some_and(|(host, port)| !host.is_empty() && !host.contains('/') && port.parse::<u16>().is_ok());
+        let valid_target = (target.starts_with('/') || (target == "*" && method == HttpMethod::OPTIONS) || is_authority)
+            && target.bytes().all(|b| b.is_ascii_graphic());
+        if !valid_target {
+            return Err(bad("invalid request target"));
+        }
+        Ok((method, target, version))
+    }
+
+    // host:port of the target of a request line parse_request_line accepted, the port of an
+    // http:// URI is 80 when it has none
+    fn authority(line: &str) -> Option<String> {
+        let mut parts = line.split(' ');
+        let (method, target) = (parts.next()?, parts.next()?);
+        if method == HttpMethod::CONNECT.as_str() && !target.starts_with('/') {
+            return Some(target.to_string());
+        }
+        let rest = target.strip_prefix("http://")?;
+        let host = &rest[..rest.find(['/', '?']).unwrap_or(rest.len())];
+        match host.rsplit_once(':') {
+            _ if host.is_empty() => None,
+            Some((_, port)) if !port.ends_with(']') => Some(host.to_string()),
+            _ => Some(format!("{}:80", host)),
+        }
+    }
+
+    fn is_token_byte(b: u8) -> bool {
+        b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+    }
+
+    // a line without its CRLF (or bare LF), None when the connection closed before it started
+    fn read_line<R: BufRead>(reader: &mut R, limit: usize, too_long: RequestError) -> Result<Option<String>, RequestError> {
+        let mut buffer = Vec::new();
+        Ok(Self::read_line_into(reader, &mut buffer, limit, too_long)?.map(str::to_string))
+    }
+
+    // the line without its CRLF, borrowed from the buffer it replaced the content of
+    fn read_line_into<'a, R: BufRead>(
+        reader: &mut R,
+        buffer: &'a mut Vec<u8>,
+        limit: usize,
+        too_long: RequestError,
+    ) -> Result<Option<&'a str>, RequestError> {
+        buffer.clear();
+        reader
+            .take(limit as u64 + 2)
+            .read_until(b'\n', buffer)
+            .map_err(RequestError::Io)?;
+        if buffer.is_empty() {
+            return Ok(None);
+        }
+        if buffer.last() != Some(&b'\n') {
+            return if buffer.len() > limit {
+                Err(too_long)
+            } else {
+                Err(RequestError::BadRequest("connection closed inside a line".to_string()))
+            };
+        }
+        let mut line = &buffer[..buffer.len() - 1];
+        if line.last() == Some(&b'\r') {
+            line = &line[..line.len() - 1];
+        }
+        if line.len() > limit {
+            return Err(too_long);
+        }
+        std::str::from_utf8(line)
+            .map(Some)
+            .map_err(|_| RequestError::BadRequest("request head is not valid UTF-8".to_string()))
+    }
+
+    // whether the client waits for a 100 Continue before sending its body; HTTP/1.0 clients
+    // cannot ask for one and a body-less request has nothing to wait for (RFC 9110 10.1.1)
+    pub fn expects_continue(&self) -> Result<bool, RequestError> {
+        let Some(expectation) = self.header("Expect") else {
+            return Ok(false);
+        };
+        if matches!(self.version, HttpVersion::Http10) {
+            return Ok(false);
+        }
+        if !expectation.eq_ignore_ascii_case("100-continue") {
+            return Err(RequestError::ExpectationFailed(expectation.to_string()));
+        }
+        Ok(self.header("Transfer-Encoding").is_some() || self.content_length().is_some_and(|length| length > 0))
+    }
+
+    pub fn content_length(&self) -> Option<usize> {
+        self.header("Content-Length")
+            .and_then(|value| value.trim().parse::<usize>().ok())
+    }
+
+    // whether the response may be sent with this coding, going by the weights of Accept-Encoding
+    pub fn accepts_encoding(&self, coding: &str) -> bool {
+        ContentEncoding::weight(self.header("Accept-Encoding"), coding) > 0.0
+    }
+
+    pub fn if_none_match(&self) -> Option<&str> {
+        self.header("If-None-Match")
+    }
+
+    pub fn if_match(&self) -> Option<&str> {
+        self.header("If-Match")
+    }
+
+    // the dates as seconds since the epoch, a value that does not parse is as good as none
+    pub fn if_modified_since(&self) -> Option<u64> {
+        self.header("If-Modified-Since").and_then(Utils::parse_http_date)
+    }
+
+    pub fn if_unmodified_since(&self) -> Option<u64> {
+        self.header("If-Unmodified-Since").and_then(Utils::parse_http_date)
+    }
+
+    pub fn range(&self) -> Option<&str> {
+        self.header("Range")
+    }
+
+    pub fn read_body<R: BufRead>(&mut self, reader: &mut R, limit: usize) -> Result<(), RequestError> {
+        // Transfer-Encoding wins over Content-Length, a request with both is refused since the
+        // two may be read differently by a proxy in front (RFC 9112 6.3)
+        if let Some(codings) = self.header("Transfer-Encoding").map(str::to_string) {
+            if self.header("Content-Length").is_some() {
+                return Err(RequestError::BadRequest("both Transfer-Encoding and Content-Length".to_string()));
+            }
+            let codings: Vec<String> = codings.split(',').map(|coding| coding.trim().to_lowercase()).collect();
+            if codings != ["chunked"] {
+                return Err(RequestError::UnsupportedTransferCoding(codings.join(", ")));
+            }
+            self.body = self.read_chunked(reader, limit)?;
+            // handlers see a plain body of known length from here on (RFC 9112 7.1.3)
+            self.headers.retain(|(key, _)| !key.eq_ignore_ascii_case("Transfer-Encoding"));
+            self.headers.push(("Content-Length".to_string(), self.body.len().to_string()));
+            return Ok(());
+        }
+
+        // check for a content-length header and read the body if provided; one that does not
+        // parse is refused, taking it for no body would read the body as the next request
+        if let Some(value) = self.header("Content-Length") {
+            let value = value.trim();
+            // an overflowing one included, as read_head_with refuses it
+            let content_length = value
+                .parse::<usize>()
+                .map_err(|_| RequestError::BadRequest(format!("invalid Content-Length: {}", value)))?;
+            // refuse before allocating anything for the body
+            if content_length > limit {
+                return Err(RequestError::PayloadTooLarge);
+            }
+
+            let mut buf = vec![0; content_length];
+            if let Err(e) = reader.read_exact(&mut buf) {
+                Logger::warn(&format!("Error reading body: {}", e));
+                return Err(RequestError::Io(e));
+            }
+            self.body = buf;
+        }
+
+        Ok(())
+    }
+
+    // chunks of `<hex size>[;extensions]` CRLF data CRLF until a zero size, then the trailer
+    // fields and an empty line; the limit is checked before each chunk is read
+    fn read_chunked<R: BufRead>(&mut self, reader: &mut R, limit: usize) -> Result<Vec<u8>, RequestError> {
+        let closed = || RequestError::BadRequest("connection closed inside a chunked body".to_string());
+        let mut body = Vec::new();
+        loop {
+            let line = Self::read_line(reader, Self::MAX_CHUNK_LINE, RequestError::BadRequest("chunk size line too long".to_string()))?
+                .ok_or_else(closed)?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16)
+                .ok()
+                .filter(|_| !size.is_empty() && size.bytes().all(|b| b.is_ascii_hexdigit()))
+                .ok_or_else(|| RequestError::BadRequest(format!("invalid chunk size: {}", line)))?;
+            if size == 0 {
+                break;
+            }
+            if size > limit.saturating_sub(body.len()) {
+                return Err(RequestError::PayloadTooLarge);
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..]).map_err(RequestError::Io)?;
+            let end = Self::read_line(reader, 0, RequestError::BadRequest("chunk data longer than its size".to_string()))?;
+            if end.is_none() {
+                return Err(closed());
+            }
+        }
+
+        loop {
+            let line = Self::read_line(reader, Self::MAX_HEADER_LINE, RequestError::HeadersTooLarge)?.ok_or_else(closed)?;
+            if line.is_empty() {
+                break;
+            }
+            if self.trailers.len() >= Self::MAX_HEADERS {
+                return Err(RequestError::HeadersTooLarge);
+            }
+            let (key, value) = line
+                .split_once(':')
+                .filter(|(key, _)| !key.is_empty() && key.bytes().all(Self::is_token_byte))
+                .ok_or_else(|| RequestError::BadRequest(format!("malformed trailer line: {}", line)))?;
+            self.trailers.push((key.to_string(), value.trim().to_string()));
+        }
+        Ok(body)
+    }
+
+    pub fn trailer(&self, name: &str) -> Option<&str> {
+        self.trailers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
     pub fn decode_url(url: &str) -> String {
+        // most paths have nothing to decode
+        if !url.contains(['%', '+']) {
+            return url.to_string();
+        }
         let mut result = String::with_capacity(url.len());
         let mut chars = url.chars().peekable();
 
@@ -179,7 +603,222 @@ impl Request {
         result
     }
 
-    pub fn to_string(&self) -> String {
-        self.http_description()
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // picks the ID used in logs and the X-Request-Id header, an incoming one is only
+    // kept when the peer is a trusted proxy and the value is safe to log
+    pub fn assign_id(&mut self, trusted: bool) {
+        let incoming = self
+            .header("X-Request-Id")
+            .map(str::trim)
+            .filter(|id| trusted && Self::is_valid_id(id));
+        self.id = match incoming {
+            Some(id) => id.to_string(),
+            None => Utils::request_id(),
+        };
+    }
+
+    fn is_valid_id(id: &str) -> bool {
+        !id.is_empty() && id.len() <= Self::MAX_ID_LENGTH && id.bytes().all(|b| b.is_ascii_graphic())
+    }
+
+    // the method a POST stands in for, from X-HTTP-Method-Override or a _method form field,
+    // for clients behind proxies that only let GET and POST through. Only methods that change
+    // something can be asked for, a POST never turns into a GET
+    pub fn method_override(&self) -> Option<HttpMethod> {
+        if self.method != HttpMethod::POST {
+            return None;
+        }
+        let method = match self.header("X-HTTP-Method-Override") {
+            Some(method) => Some(method.to_string()),
+            None => self.form_field("_method"),
+        }?;
+        HttpMethod::from_str(&method.trim().to_uppercase())
+            .filter(|method| matches!(method, HttpMethod::PUT | HttpMethod::PATCH | HttpMethod::DELETE))
+    }
+
+    // a field of an application/x-www-form-urlencoded body, decoded
+    pub fn form_field(&self, name: &str) -> Option<String> {
+        let is_form = self
+            .header("Content-Type")
+            .is_some_and(|content_type| content_type.to_lowercase().starts_with("application/x-www-form-urlencoded"));
+        if !is_form {
+            return None;
+        }
+        String::from_utf8_lossy(&self.body)
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| Self::decode_url(value))
+    }
+
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_peer(&mut self, peer: Option<SocketAddr>) {
+        self.peer = peer;
+        self.client_ip = peer.map(|addr| addr.ip());
+    }
+
+    // only for peers that are trusted proxies: walks the forwarding chain from the right
+    // and stops at the first hop that is not trusted, that one is the real client. The scheme
+    // and host come from the element the outermost trusted proxy added, what the client sent
+    // itself is never believed
+    // @see: https://www.rfc-editor.org/rfc/rfc7239
+    pub fn apply_forwarded<F: Fn(IpAddr) -> bool>(&mut self, is_trusted: F) {
+        let elements = match self.header("Forwarded") {
+            Some(value) => Self::parse_forwarded(value),
+            None => self.x_forwarded(),
+        };
+
+        let mut client = match self.client_ip {
+            Some(ip) => ip,
+            None => return,
+        };
+        // the elements from here on were added by trusted proxies
+        let mut outermost = elements.len();
+        for (i, element) in elements.iter().enumerate().rev() {
+            if !is_trusted(client) {
+                break;
+            }
+            outermost = i;
+            match element.for_node.as_deref().and_then(Self::parse_node) {
+                Some(ip) => client = ip,
+                None => break, // unknown or obfuscated hop
+            }
+        }
+        self.client_ip = Some(client);
+
+        // the outermost trusted proxy saw the client's request, those behind it only repeat
+        // what they were told when they say nothing themselves
+        let trusted = &elements[outermost..];
+        let proto = trusted
+            .iter()
+            .filter_map(|element| element.proto.as_ref().map(|proto| proto.to_lowercase()))
+            .find(|proto| proto == "http" || proto == "https");
+        if let Some(proto) = proto {
+            self.scheme = proto;
+        }
+        if let Some(host) = trusted.iter().filter_map(|element| element.host.as_ref()).find(|host| !host.is_empty()) {
+            self.domain = host.clone();
+        }
+    }
+
+    // the X-Forwarded-* headers as Forwarded elements; proxies append to each of them or
+    // set the last value alone, so the lists are lined up from the right
+    fn x_forwarded(&self) -> Vec<ForwardedElement> {
+        let values = |name: &str| -> Vec<String> {
+            self.header(name)
+                .map(|value| value.split(',').map(|value| value.trim().to_string()).collect())
+                .unwrap_or_default()
+        };
+        let (nodes, protos, hosts) = (values("X-Forwarded-For"), values("X-Forwarded-Proto"), values("X-Forwarded-Host"));
+        let length = nodes.len().max(protos.len()).max(hosts.len());
+        let at = |list: &Vec<String>, i: usize| {
+            (i + list.len()).checked_sub(length).and_then(|i| list.get(i)).filter(|value| !value.is_empty()).cloned()
+        };
+        (0..length)
+            .map(|i| ForwardedElement { for_node: at(&nodes, i), proto: at(&protos, i), host: at(&hosts, i) })
+            .collect()
+    }
+
+    fn parse_forwarded(value: &str) -> Vec<ForwardedElement> {
+        value
+            .split(',')
+            .map(|element| {
+                let mut parsed = ForwardedElement::default();
+                for pair in element.split(';') {
+                    if let Some((key, value)) = pair.split_once('=') {
+                        let value = value.trim().trim_matches('"').to_string();
+                        match key.trim().to_lowercase().as_str() {
+                            "for" => parsed.for_node = Some(value),
+                            "proto" => parsed.proto = Some(value),
+                            "host" => parsed.host = Some(value),
+                            _ => {}
+                        }
+                    }
+                }
+                parsed
+            })
+            .collect()
+    }
+
+    // 192.0.2.60, 192.0.2.60:4711, [2001:db8::17]:4711 or 2001:db8::17
+    fn parse_node(node: &str) -> Option<IpAddr> {
+        if let Ok(ip) = node.parse::<IpAddr>() {
+            return Some(ip);
+        }
+        if let Some(rest) = node.strip_prefix('[') {
+            return rest.split_once(']')?.0.parse().ok();
+        }
+        node.parse::<SocketAddr>().ok().map(|addr| addr.ip())
+    }
+
+    // the client as logged: the forwarded address when a proxy is in between, the peer otherwise
+    pub fn client_addr(&self) -> Option<String> {
+        match (self.peer, self.client_ip) {
+            (Some(peer), Some(ip)) if peer.ip() == ip => Some(Utils::format_addr(&peer)),
+            (_, Some(ip)) => Some(ip.to_string()),
+            (peer, None) => peer.map(|peer| Utils::format_addr(&peer)),
+        }
+    }
+
+    // the socket the request came in on, the proxy when there is one, with its port
+    pub fn peer_addr(&self) -> Option<String> {
+        self.peer.map(|peer| Utils::format_addr(&peer))
+    }
+
+    // the host the request is for, from Host or a trusted X-Forwarded-Host, without the port
+    pub fn host(&self) -> Option<&str> {
+        Some(Self::host_name(&self.domain)).filter(|host| !host.is_empty())
+    }
+
+    // a host as matched against configured names: no port and no trailing dot, an IPv6
+    // literal keeps its brackets
+    pub fn host_name(host: &str) -> &str {
+        let host = host.trim();
+        let name = match host.rfind(':') {
+            Some(colon) if !host.ends_with(']') => &host[..colon],
+            _ => host,
+        };
+        name.trim_end_matches('.')
+    }
+
+    // for redirects, honors the scheme and host the client originally used
+    pub fn absolute_url(&self, path: &str) -> String {
+        format!("{}://{}{}", self.scheme, self.domain, path)
+    }
+
+    // the same host and target over https, None without a Host to send the client back to
+    pub fn https_url(&self, port: u16) -> Option<String> {
+        let name = self.host()?;
+        let target = if self.target.starts_with('/') { self.target.as_str() } else { "/" };
+        match port {
+            443 => Some(format!("https://{}{}", name, target)),
+            port => Some(format!("https://{}:{}{}", name, port, target)),
+        }
+    }
+
+    pub fn keep_alive(&self) -> bool {
+        // @see: https://www.rfc-editor.org/rfc/rfc9112#section-9.3
+        let connection = self.header("Connection").unwrap_or("").to_lowercase();
+        let has_token = |token: &str| connection.split(',').any(|t| t.trim() == token);
+
+        match self.version {
+            HttpVersion::Http10 => has_token("keep-alive"),
+            _ => !has_token("close"),
+        }
+    }
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.http_description())
     }
 }
@@ -1,15 +1,29 @@
 use std::cmp::min;
+use crate::accept::Accept;
+#[cfg(feature = "embed")]
+use crate::bundle::Bundle;
+use crate::cgi::CgiOutput;
+use crate::charset::{Charset, CharsetMode};
+use crate::compression::Compression;
+use crate::crypto::Crypto;
+use crate::csrf::Csrf;
+use crate::etag::ETag;
 use crate::filetype::FileType;
-use crate::http::{HttpStatus, HttpVersion};
+use crate::http::{HttpMethod, HttpStatus, HttpVersion};
+use crate::ignore::Ignore;
+use crate::language::Language;
+use crate::proxy::{Proxy, ProxyResponse};
 use crate::request::Request;
-use crate::templates::{Templates, TemplatesPage};
+use crate::templates::{Context, Templates, TemplatesPage};
 use crate::utils::Utils;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::io::{Error, Read, Seek, SeekFrom, Write};
-use std::net::TcpStream;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use crate::logger::Logger;
+use crate::markdown::Markdown;
+use crate::pool::BufferPool;
 
 #[derive(Debug)]
 pub struct Response {
@@ -19,16 +33,44 @@ pub struct Response {
     pub status_code: HttpStatus,
     pub headers: Vec<(String, String)>,
     pub cookies: Vec<(String, String)>,
+    // directories without an index.html are listed, refused otherwise
+    pub listing: bool,
+    pub listing_page_size: usize,
+    // listings carry an upload form
+    pub writable: bool,
+    // the URL prefix the root is served under, /~alice for a user directory, empty otherwise
+    pub mount: String,
+    // hidden from listings and searches, a 404 when asked for
+    pub ignore: Ignore,
+    // language of the built-in pages when Accept-Language names none of them
+    pub locale: Option<String>,
+    // how served files are tagged for If-None-Match
+    pub etag: ETag,
+    // what is done about the encoding of text files
+    pub charset: CharsetMode,
     pub body: Vec<u8>,
     pub _size: usize,
     pub _path: PathBuf,
     pub _need_stream: bool,
     pub _is_compiled: bool,
+    // a page of Katana's own, listings and errors, rather than a file
+    pub _is_generated: bool,
 }
 
 impl Response {
     pub const CHUNK_SIZE: usize = 1024; // 1 KB
     pub const MAX_SIZE_ALL_AT_ONCE: usize = 1048576; // 1MB
+    pub const SEARCH_PARAM: &'static str = "q";
+    // a search in a big tree stays a bounded walk and a bounded page
+    pub const SEARCH_MAX_DEPTH: usize = 8;
+    pub const SEARCH_MAX_RESULTS: usize = 500;
+    pub const PAGE_PARAM: &'static str = "page";
+    pub const LISTING_PAGE_SIZE: usize = 1000;
+    // checked in this order, bigger READMEs are left to their link in the listing
+    pub const README_NAMES: &'static [&'static str] = &["readme.md", "readme.markdown", "readme.txt", "readme"];
+    pub const README_MAX_SIZE: u64 = 262144; // 256 KB
+    pub const CHECKSUM_PARAM: &'static str = "checksum";
+    pub const CHECKSUM_SUFFIX: &'static str = ".sha256";
 
     pub fn new(request: Request, templates: Templates) -> Option<Self> {
         let response = Self {
@@ -38,19 +80,41 @@ impl Response {
             status_code: HttpStatus::Ok,       // default to 200 OK
             headers: Vec::new(),
             cookies: Vec::new(),
+            listing: true,
+            listing_page_size: Self::LISTING_PAGE_SIZE,
+            writable: false,
+            mount: String::new(),
+            ignore: Ignore::default(),
+            locale: None,
+            etag: ETag::default(),
+            charset: CharsetMode::default(),
             body: Vec::new(),
             _size: 0,
             _path: PathBuf::new(),
             _need_stream: false,
             _is_compiled: false,
+            _is_generated: false,
         };
 
         Some(response)
     }
 
-    pub fn serve(&mut self, root_dir: &PathBuf) -> &mut Response {
+    pub fn serve(&mut self, root_dir: &Path) -> &mut Response {
+        self.serve_localized(root_dir, None)
+    }
+
+    // with a default language, a page that has localized copies is answered by the one
+    // Accept-Language prefers
+    pub fn serve_localized(&mut self, root_dir: &Path, default_language: Option<&str>) -> &mut Response {
         let file_path = root_dir.join(&self.request.path[1..]); // Remove leading "/"
 
+        if let Some(default_language) = default_language {
+            let page = if file_path.is_dir() { file_path.join("index.html") } else { file_path.clone() };
+            if self.serve_language_variant(root_dir, &page, default_language) {
+                return self;
+            }
+        }
+
         if file_path.is_dir() {
             let index_html = file_path.join("index.html");
             if index_html.is_file() {
@@ -61,13 +125,120 @@ impl Response {
         } else if file_path.is_file() {
             self.serve_file(root_dir, file_path);
         } else {
-            self.serve_error_response(HttpStatus::NotFound);
+            self.serve_accept_variant(root_dir, &file_path);
+        }
+
+        self
+    }
+
+    // a `sha256sum -c` line for ?checksum=sha256, or for file.sha256 when only file exists;
+    // false when the request is for neither and the file is served as usual
+    pub fn serve_checksum(&mut self, root_dir: &Path) -> bool {
+        let algorithm = self
+            .request
+            .queries
+            .iter()
+            .find(|(key, _)| key == Self::CHECKSUM_PARAM)
+            .map(|(_, value)| value.to_lowercase());
+        let requested = self.request.path.clone();
+        let file = match algorithm.as_deref() {
+            Some("sha256") => requested.clone(),
+            Some(_) => {
+                self.serve_error_response(HttpStatus::BadRequest);
+                return true;
+            }
+            None => match requested.strip_suffix(Self::CHECKSUM_SUFFIX) {
+                Some(file) if !root_dir.join(&requested[1..]).exists() => file.to_string(),
+                _ => return false,
+            },
+        };
+        // directories and missing files go the usual way
+        let file_path = root_dir.join(&file[1..]);
+        if !file_path.is_file() {
+            return false;
+        }
+
+        // the same rules as the file itself, hidden and ignored ones stay out of reach
+        self.request.path = file;
+        self.serve_file(root_dir, file_path);
+        self.request.path = requested;
+        if self.status_code != HttpStatus::Ok {
+            return true;
         }
+        let path = self._path.clone();
+        match std::fs::metadata(&path).ok().and_then(|metadata| ETag::sha256(&path, &metadata)) {
+            Some(digest) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                self.serve_body("text/plain", format!("{}  {}\n", digest, name).into_bytes());
+            }
+            None => self.serve_error_response(HttpStatus::InternalServerError),
+        }
+        true
+    }
 
+    // files compiled into the binary, there is nothing to list so a directory without an
+    // index.html is a 404
+    #[cfg(feature = "embed")]
+    pub fn serve_bundle(&mut self, bundle: &Bundle) -> &mut Response {
+        let Some((name, content)) = bundle.get(&self.request.path) else {
+            self.serve_error_response(HttpStatus::NotFound);
+            return self;
+        };
+        let file_type = Path::new(name)
+            .extension()
+            .and_then(|extension| FileType::from_extension(&extension.to_string_lossy()))
+            .unwrap_or_else(|| FileType::new("bin", "application/octet-stream"));
+        self.serve_body(&file_type.content_type, content.to_vec());
+        self.set_header("Content-Disposition", file_type.content_disposition());
         self
     }
 
-    fn serve_file(&mut self, root_path: &PathBuf, path: PathBuf) {
+    // /data without a file of its own: data.json, data.xml... chosen by Accept, 406 when the
+    // client refuses all of them
+    fn serve_accept_variant(&mut self, root_dir: &Path, path: &Path) {
+        let variants = Accept::variants(path);
+        if variants.is_empty() {
+            self.serve_error_response(HttpStatus::NotFound);
+            return;
+        }
+        match Accept::select(self.request.header("Accept"), &variants) {
+            Some(index) => self.serve_file(root_dir, variants[index].0.to_owned()),
+            None => self.serve_error_response(HttpStatus::NotAcceptable),
+        }
+        self.add_vary("Accept");
+    }
+
+    // false when the page has no localized copy, the unlocalized page is the fallback when
+    // no language matches, then the first copy
+    fn serve_language_variant(&mut self, root_dir: &Path, page: &Path, default_language: &str) -> bool {
+        let variants = Language::variants(page);
+        if variants.is_empty() {
+            return false;
+        }
+
+        let languages: Vec<String> = variants.iter().map(|(language, _)| language.clone()).collect();
+        let accept_language = self.request.header("Accept-Language").map(str::to_string);
+        let chosen = Language::select(accept_language.as_deref(), &languages, default_language)
+            .and_then(|language| variants.iter().find(|(tag, _)| tag == language));
+        let chosen = match chosen {
+            Some(variant) => Some(variant),
+            None if page.is_file() => None,
+            None => variants.first(),
+        };
+        match chosen {
+            Some((language, path)) => {
+                self.serve_file(root_dir, path.to_owned());
+                if self.status_code == HttpStatus::Ok {
+                    self.set_header("Content-Language", language);
+                }
+            }
+            None => self.serve_file(root_dir, page.to_owned()),
+        }
+        self.add_vary("Accept-Language");
+        true
+    }
+
+    fn serve_file(&mut self, root_path: &Path, path: PathBuf) {
         let name = path.file_name().unwrap().to_string_lossy().to_string();
 
         let root_dir = root_path.to_str().unwrap();
@@ -90,11 +261,16 @@ impl Response {
             return;
         }
 
+        if self.ignore.is_ignored(&relative_path, false) {
+            self.serve_error_response(HttpStatus::NotFound);
+            return;
+        }
+
         self._path = path.to_owned();
 
         match File::open(&path) {
-            Ok(_file) => {
-                let extension = path.extension().unwrap().to_str().unwrap();
+            Ok(mut file) => {
+                let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
 
                 let file_type = FileType::from_extension(extension)
                     .unwrap_or_else(|| FileType::new("bin", "application/octet-stream"));
@@ -105,13 +281,12 @@ impl Response {
                 // get file size without reading
                 let metadata = std::fs::metadata(&path).expect("Unable to read metadata"); // self.body.len().to_string()
                 let file_size = metadata.len();
-                let is_readable = metadata.permissions().readonly();
-
-                if !is_readable {
-                    self.serve_error_response(HttpStatus::InternalServerError);
-                }
 
                 self._size = file_size as usize;
+                // read when sent, whatever an earlier attempt left behind is dropped
+                self.body = Vec::new();
+                self._is_compiled = false;
+                self._is_generated = false;
 
                 if self._size > Response::MAX_SIZE_ALL_AT_ONCE {
                     self._need_stream = true;
@@ -127,15 +302,22 @@ impl Response {
                     "Content-Disposition".to_string(),
                     content_disposition.to_string(),
                 ));
+                if let Some(tag) = self.etag.for_file(&path, &metadata) {
+                    self.headers.push(("ETag".to_string(), tag));
+                }
+                self.apply_charset(&mut file);
             }
             Err(_) => self.serve_error_response(HttpStatus::NotFound),
         }
     }
 
-    fn serve_directory(&mut self, root_path: &PathBuf, path: PathBuf) {
+    fn serve_directory(&mut self, root_path: &Path, path: PathBuf) {
+        if !self.listing {
+            self.serve_error_response(HttpStatus::Forbidden);
+            return;
+        }
         self._is_compiled = true;
-
-        let mut listing_html = String::new();
+        self._is_generated = true;
 
         let root_dir = root_path.to_str().unwrap();
         let binding = root_dir.replace('\\', "/");
@@ -152,10 +334,31 @@ impl Response {
             self.serve_error_response(HttpStatus::Forbidden);
             return;
         }
+        if self.ignore.is_ignored(&relative_path, true) {
+            self.serve_error_response(HttpStatus::NotFound);
+            return;
+        }
 
         self._path = path.to_owned();
 
-        let entries = Utils::walk_dir(&path);
+        // ?q= searches the tree below for matching names instead of listing this directory,
+        // forms send spaces as +
+        let query = self
+            .request
+            .queries
+            .iter()
+            .find(|(key, _)| key == Self::SEARCH_PARAM)
+            .map(|(_, value)| value.replace('+', " ").trim().to_string())
+            .filter(|query| !query.is_empty());
+        let ignore = self.ignore.clone();
+        let keep = |name: &str, is_dir: bool| !ignore.is_ignored(&format!("{}/{}", relative_path, name), is_dir);
+        let entries = match &query {
+            Some(query) => Utils::search_dir(&path, query, Self::SEARCH_MAX_DEPTH, Self::SEARCH_MAX_RESULTS, keep),
+            None => Utils::walk_dir(&path)
+                .into_iter()
+                .filter(|(entry_type, name, _)| keep(name, entry_type == "directory"))
+                .collect(),
+        };
         let mut folders = Vec::new();
         let mut files = Vec::new();
 
@@ -167,129 +370,545 @@ impl Response {
             }
         }
 
-        if relative_path != "/" {
-            listing_html.push_str("<li><a href='../'>..</a></li>");
+        // folders first, then files, one page at a time so huge directories stay cheap to render
+        let page_size = self.listing_page_size.max(1);
+        let pages = entries.len().div_ceil(page_size).max(1);
+        let page = self
+            .request
+            .queries
+            .iter()
+            .find(|(key, _)| key == Self::PAGE_PARAM)
+            .and_then(|(_, value)| value.parse::<usize>().ok())
+            .map_or(1, |page| page.clamp(1, pages));
+        let page_entries: Vec<(&String, &String)> =
+            folders.into_iter().chain(files).skip((page - 1) * page_size).take(page_size).collect();
+
+        // revalidated without rendering: a match ends here with a 304
+        let language = self.language();
+        // the file manager of --writable listings, its forms carry the token of the cookie
+        let csrf = self.writable.then(|| Csrf::token(&self.request));
+        let validators = (self.etag != ETag::Off).then(|| {
+            let mut described = format!(
+                "{}{}\n{:?}\n{}/{}/{}\n{}\n{:?}\n",
+                self.mount, relative_path, query, page, pages, page_size, language, csrf
+            );
+            for (entry_type, entry_name, _) in &entries {
+                described.push_str(&format!("{} {}\n", entry_type, entry_name));
+            }
+            let readmes = entries.iter().filter(|(_, name, _)| Self::README_NAMES.contains(&name.to_lowercase().as_str()));
+            let on_page = page_entries.iter().map(|(name, path)| (*name, *path));
+            let mut newest = std::fs::metadata(&path).map_or(0, |metadata| Self::modified_secs(&metadata));
+            for (entry_name, entry_path) in on_page.chain(readmes.map(|(_, name, path)| (name, path))) {
+                let (size, modified) = std::fs::metadata(entry_path.trim_end_matches('/'))
+                    .map_or((0, 0), |metadata| (metadata.len(), Self::modified_secs(&metadata)));
+                newest = newest.max(modified);
+                described.push_str(&format!("{} {} {}\n", entry_name, size, modified));
+            }
+            let digest = Crypto::sha256(described.as_bytes());
+            let tag = format!("W/\"{}\"", digest[..16].iter().map(|b| format!("{:02x}", b)).collect::<String>());
+            (tag, Utils::http_date(newest))
+        });
+        if let Some((tag, last_modified)) = &validators {
+            self.status_code = HttpStatus::Ok;
+            self.headers.clear();
+            self.set_header("ETag", tag);
+            self.set_header("Last-Modified", last_modified);
+            self.set_localized(&language);
+            if self.not_modified() {
+                return;
+            }
         }
 
-        if entries.is_empty() {
-            listing_html.push_str("<li><b>Empty Folder</b></li>");
+        let shown: Vec<Context> = page_entries
+            .into_iter()
+            .map(|(entry_name, entry_path)| {
+                let href = format!("{}{}", self.mount, entry_path.strip_prefix(root_dir_normalized).unwrap());
+                Self::listing_entry(&href, entry_name, entry_path)
+            })
+            .collect();
+
+        let mut context = Context::new();
+        let folder = format!("{}{}", self.mount, relative_path);
+        context.insert("folder".to_string(), Utils::escape_html(&folder).into());
+        context.insert("breadcrumbs".to_string(), Self::breadcrumbs(&folder).into());
+        context.insert("entries".to_string(), shown.into());
+        if let Some(query) = &query {
+            context.insert("query".to_string(), Utils::escape_html(query).into());
         }
-
-        for (entry_name, entry_path) in folders {
-            let li_href = entry_path.strip_prefix(root_dir_normalized).unwrap();
-            listing_html.push_str(&format!(
-                "<li><a href='{}'>{}</a></li>",
-                li_href, entry_name
-            ));
+        if let Some(token) = &csrf {
+            context.insert("writable".to_string(), true.into());
+            context.insert("csrf".to_string(), token.as_str().into());
         }
-
-        for (entry_name, entry_path) in files {
-            let li_href = entry_path.strip_prefix(root_dir_normalized).unwrap();
-            listing_html.push_str(&format!(
-                "<li><a href='{}'>{}</a></li>",
-                li_href, entry_name
-            ));
+        if pages > 1 {
+            let position = self.templates.message(&language, "directory.page").unwrap_or("{page} / {pages}");
+            let position = position.replace("{page}", &page.to_string()).replace("{pages}", &pages.to_string());
+            context.insert("pagination".to_string(), true.into());
+            context.insert("position".to_string(), Utils::escape_html(&position).into());
+            if page > 1 {
+                context.insert("previous".to_string(), Self::page_href(query.as_deref(), page - 1).into());
+            }
+            if page < pages {
+                context.insert("next".to_string(), Self::page_href(query.as_deref(), page + 1).into());
+            }
+        }
+        if let Some((name, readme)) = query.is_none().then(|| Self::readme(&path, |name| keep(name, false))).flatten() {
+            context.insert("readme_name".to_string(), Utils::escape_html(&name).into());
+            context.insert("readme".to_string(), readme.into());
         }
 
-        let mut params = HashMap::new();
-        params.insert("folder".to_string(), relative_path);
-        params.insert("directory_content".to_string(), listing_html);
-
-        self.body = self
-            .templates
-            .render(TemplatesPage::DIRECTORY, params)
-            .into_bytes();
+        self.body = self.templates.render_context(TemplatesPage::DIRECTORY, &context, &language).into_bytes();
         self.status_code = HttpStatus::Ok;
         self.headers.clear();
         self.headers
             .push(("Content-Type".to_string(), "text/html".to_string()));
+        self.set_localized(&language);
+        if let Some((tag, last_modified)) = validators {
+            self.set_header("ETag", &tag);
+            self.set_header("Last-Modified", &last_modified);
+        }
+        if let Some(cookie) = csrf.and_then(|token| Csrf::cookie(&token, &self.request)) {
+            self.cookies.push((Csrf::COOKIE.to_string(), cookie));
+        }
 
         self._size = self.body.len()
     }
 
-    fn serve_error_response(&mut self, status: HttpStatus) {
+    fn modified_secs(metadata: &std::fs::Metadata) -> u64 {
+        metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |modified| modified.as_secs())
+    }
+
+    // a link to each ancestor below the root, e.g. docs and api for /docs/api
+    fn breadcrumbs(relative_path: &str) -> Vec<Context> {
+        let mut href = String::from("/");
+        relative_path
+            .split(['/', '\\'])
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                href.push_str(segment);
+                href.push('/');
+                Context::from([
+                    ("href".to_string(), Utils::encode_url_path(&href).into()),
+                    ("name".to_string(), Utils::escape_html(segment).into()),
+                ])
+            })
+            .collect()
+    }
+
+    // the README of a directory with its name, Markdown rendered and anything else as text
+    fn readme(directory: &Path, keep: impl Fn(&str) -> bool) -> Option<(String, String)> {
+        let names: Vec<String> = std::fs::read_dir(directory)
+            .ok()?
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| keep(name))
+            .collect();
+        let name = Self::README_NAMES
+            .iter()
+            .find_map(|readme| names.iter().find(|name| name.eq_ignore_ascii_case(readme)))?;
+        let path = directory.join(name);
+        if std::fs::metadata(&path).ok()?.len() > Self::README_MAX_SIZE {
+            return None;
+        }
+        let source = String::from_utf8_lossy(&std::fs::read(&path).ok()?).to_string();
+        let content = if name.to_lowercase().ends_with(".md") || name.to_lowercase().ends_with(".markdown") {
+            Markdown::to_html(&source)
+        } else {
+            format!("<pre>{}</pre>", Utils::escape_html(&source))
+        };
+        Some((name.to_string(), content))
+    }
+
+    // an icon for the type, the name and the size of files, only read for the entries shown
+    fn listing_entry(href: &str, name: &str, entry_path: &str) -> Context {
+        let (entry_type, icon, size) = if entry_path.ends_with('/') {
+            ("directory", "\u{1F4C1}", String::new())
+        } else {
+            let icon = Path::new(name)
+                .extension()
+                .and_then(|extension| FileType::from_extension(&extension.to_string_lossy()))
+                .map_or("\u{1F4C4}", |file_type| file_type.icon());
+            let size = std::fs::metadata(entry_path).map_or(String::new(), |metadata| Utils::human_size(metadata.len()));
+            ("file", icon, size)
+        };
+        Context::from([
+            ("type".to_string(), entry_type.into()),
+            ("href".to_string(), Utils::escape_html(href).into()),
+            ("icon".to_string(), icon.into()),
+            ("name".to_string(), Utils::escape_html(name).into()),
+            ("size".to_string(), size.into()),
+        ])
+    }
+
+    // keeps the search, e.g. ?q=report&page=3
+    fn page_href(query: Option<&str>, page: usize) -> String {
+        let search = query.map_or(String::new(), |query| {
+            format!("{}={}&amp;", Self::SEARCH_PARAM, Utils::encode_url_path(query).replace('/', "%2F"))
+        });
+        format!("?{}{}={}", search, Self::PAGE_PARAM, page)
+    }
+
+    pub fn serve_error_response(&mut self, status: HttpStatus) {
+        let language = self.language();
+        let code = status.to_code().to_string();
+        let text = |key: String| self.templates.message(&language, &key).map(Utils::escape_html);
         let mut params = HashMap::new();
-        params.insert("status_code".to_string(), status.to_code().to_string());
-        params.insert("status_text".to_string(), status.to_message().to_string());
+        params.insert(
+            "status_text".to_string(),
+            text(format!("status.{}", code)).unwrap_or_else(|| status.to_message().to_string()),
+        );
         params.insert(
             "error_message".to_string(),
-            "Something went wrong !".to_string(),
-        ); //
+            text(format!("error.{}", code)).or_else(|| text("error.message".to_string())).unwrap_or_default(),
+        );
+        params.insert("status_code".to_string(), code);
 
         self.status_code = status;
         self.body = self
             .templates
-            .render(TemplatesPage::ERROR, params)
+            .render_in(TemplatesPage::ERROR, params, &language)
             .into_bytes();
         self.headers.clear();
         self.headers
             .push(("Content-Type".to_string(), "text/html".to_string()));
+        self.set_localized(&language);
 
-        self._size = self.body.len()
+        self._size = self.body.len();
+        self._is_compiled = true;
+        self._is_generated = true;
+        self.set_header("Content-Length", &self._size.to_string());
     }
 
-    pub fn http_description(&self) -> String {
-        let mut result = String::new();
-
-        // format the status line
-        result.push_str(&format!(
-            "{} {} {}\r\n",
-            self.http_version.as_str(),
-            self.status_code.to_code(),
-            self.status_code.to_message()
-        ));
-
-        // format headers
-        let headers = self
+    // the language of the built-in pages for this request
+    pub fn language(&self) -> String {
+        self.templates.language(self.request.header("Accept-Language"), self.locale.as_deref())
+    }
+
+    fn set_localized(&mut self, language: &str) {
+        self.set_header("Content-Language", language);
+        self.add_vary("Accept-Language");
+    }
+
+    pub fn serve_redirect(&mut self, status: HttpStatus, location: &str) {
+        self.status_code = status;
+        self.body = Vec::new();
+        self.headers.clear();
+        self.headers.push(("Location".to_string(), location.to_string()));
+
+        self._size = 0;
+        self._is_compiled = true;
+        self._is_generated = false;
+    }
+
+    // nothing but a status and the headers set afterwards, e.g. 204 No Content
+    pub fn serve_status(&mut self, status: HttpStatus) {
+        self.status_code = status;
+        self.body = Vec::new();
+        self.headers.clear();
+
+        self._size = 0;
+        self._is_compiled = true;
+        self._is_generated = false;
+    }
+
+    // a generated body instead of a file, e.g. ACME key authorizations
+    pub fn serve_body(&mut self, content_type: &str, body: Vec<u8>) {
+        self.status_code = HttpStatus::Ok;
+        self.body = body;
+        self.headers.clear();
+        self.headers
+            .push(("Content-Type".to_string(), content_type.to_string()));
+
+        self._size = self.body.len();
+        self._is_compiled = true;
+        self._is_generated = false;
+    }
+
+    // what a CGI script or FastCGI responder answered, the length is ours to set
+    pub fn serve_cgi(&mut self, output: CgiOutput) {
+        self.serve_upstream("text/html", output.status, output.headers, output.body);
+    }
+
+    // what an upstream of a proxy answered, without the fields of its connection to us; our
+    // own Server and Date replace its own and Via tells the client it went through here
+    pub fn serve_proxied(&mut self, output: ProxyResponse) {
+        let header = |name: &str| output.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.clone());
+        let (connection, via) = (header("Connection"), header("Via"));
+        let mut headers: Vec<(String, String)> = output
+            .headers
+            .into_iter()
+            .filter(|(name, _)| !Proxy::is_hop_by_hop(name, connection.as_deref()))
+            .filter(|(name, _)| !["Server", "Date", "Via"].iter().any(|skipped| skipped.eq_ignore_ascii_case(name)))
+            .collect();
+        headers.push(("Via".to_string(), Proxy::via(via.as_deref(), &output.version)));
+        self.serve_upstream("application/octet-stream", output.status, headers, output.body);
+    }
+
+    fn serve_upstream(&mut self, default_type: &str, status: HttpStatus, headers: Vec<(String, String)>, body: Vec<u8>) {
+        let content_type = headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("Content-Type"));
+        let content_type = content_type.map_or(default_type, |(_, value)| value.as_str()).to_string();
+        self.serve_body(&content_type, body);
+        self.status_code = status;
+        const SKIPPED: [&str; 4] = ["Content-Type", "Content-Length", "Transfer-Encoding", "Connection"];
+        for (name, value) in headers {
+            if !SKIPPED.iter().any(|skipped| skipped.eq_ignore_ascii_case(&name)) {
+                self.headers.push((name, value));
+            }
+        }
+    }
+
+    // adds a snippet before </body> of an HTML page (or at its end), files that are
+    // streamed by chunk are left untouched
+    pub fn inject_html(&mut self, snippet: &str) -> bool {
+        self.status_code == HttpStatus::Ok && self.inject_before("</body>", snippet)
+    }
+
+    // adds a snippet before the last closing tag of an HTML body, e.g. </head>, whatever the
+    // status; a page without the tag gets it at the start for </head> and at the end otherwise
+    pub fn inject_before(&mut self, tag: &str, snippet: &str) -> bool {
+        let is_html = self
             .headers
             .iter()
-            .map(|(k, v)| format!("{}: {}\r\n", k.trim(), v.trim()))
-            .collect::<String>();
-        result.push_str(&headers);
+            .any(|(key, value)| key == "Content-Type" && value.starts_with("text/html"));
+        let no_body = matches!(self.status_code, HttpStatus::NoContent | HttpStatus::NotModified);
+        if !is_html || self._need_stream || no_body {
+            return false;
+        }
 
-        // format cookies
-        let cookies = self
-            .cookies
+        if !self.load_body() {
+            return false;
+        }
+
+        let position = self
+            .body
+            .windows(tag.len())
+            .rposition(|window| window.eq_ignore_ascii_case(tag.as_bytes()))
+            .unwrap_or(if tag.eq_ignore_ascii_case("</head>") { 0 } else { self.body.len() });
+        self.body.splice(position..position, snippet.bytes());
+        self._size = self.body.len();
+        self.weaken_etag();
+        if self.headers.iter().any(|(key, _)| key.eq_ignore_ascii_case("Content-Length")) {
+            self.set_header("Content-Length", &self._size.to_string());
+        }
+        true
+    }
+
+    // gzips a body that is worth it, files streamed by chunk are sent as they are
+    pub fn compress(&mut self, compression: &Compression) -> bool {
+        let content_type = self
+            .headers
             .iter()
-            .map(|(k, v)| format!("Set-Cookie: {}={}\r\n", k.trim(), v.trim()))
-            .collect::<String>();
-        result.push_str(&cookies);
+            .find(|(key, _)| key == "Content-Type")
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default();
+        let encoded = self.headers.iter().any(|(key, _)| key.eq_ignore_ascii_case("Content-Encoding"));
+        if self._need_stream
+            || encoded
+            || self.status_code != HttpStatus::Ok
+            || !compression.should_compress(&content_type, self._size)
+        {
+            return false;
+        }
+
+        // a file read when sent may have a sidecar from `katana precompress`, gzipped already
+        let precompressed = match self._is_compiled {
+            false => Compression::sidecar(&self._path).and_then(|sidecar| std::fs::read(sidecar).ok()),
+            true => None,
+        };
+        if precompressed.is_none() && !self.load_body() {
+            return false;
+        }
 
-        result
+        // the cache must not hand this body to clients that did not ask for gzip
+        self.add_vary("Accept-Encoding");
+        let gzipped = match precompressed {
+            Some(gzipped) => gzipped,
+            None => {
+                let gzipped = Compression::gzip(&self.body, compression.level);
+                if gzipped.len() >= self.body.len() {
+                    return false;
+                }
+                gzipped
+            }
+        };
+        self._is_compiled = true;
+        self.body = gzipped;
+        self._size = self.body.len();
+        self.set_header("Content-Encoding", "gzip");
+        self.weaken_etag();
+        true
     }
 
-    pub fn to_string(&self) -> String {
-        let mut result = self.http_description();
+    // a text file gets the charset it appears to be in, with --charset transcode it is sent as
+    // UTF-8 instead when it fits in memory
+    fn apply_charset(&mut self, file: &mut File) {
+        let Some(index) = self.headers.iter().position(|(key, value)| {
+            key.eq_ignore_ascii_case("Content-Type") && value.starts_with("text/") && !value.contains("charset=")
+        }) else {
+            return;
+        };
+        if self.charset == CharsetMode::Off {
+            return;
+        }
+        let mut sample = Vec::with_capacity(Charset::SAMPLE_SIZE);
+        if Read::by_ref(file).take(Charset::SAMPLE_SIZE as u64).read_to_end(&mut sample).is_err() {
+            return;
+        }
 
-        result.push_str("\r\n"); // add a blank line between headers and body
-        result.push_str(String::from_utf8_lossy(self.body.as_slice()).as_ref());
+        let mut charset = Charset::detect(&sample);
+        if self.charset == CharsetMode::Transcode && charset != Charset::Utf8 && !self._need_stream {
+            let mut content = Vec::with_capacity(self._size);
+            if file.rewind().and_then(|_| file.read_to_end(&mut content)).is_ok() {
+                self.body = charset.to_utf8(&content);
+                self._size = self.body.len();
+                self._is_compiled = true;
+                self.weaken_etag();
+                charset = Charset::Utf8;
+            }
+        }
+        let content_type = &mut self.headers[index].1;
+        *content_type = format!("{}; charset={}", content_type, charset.as_str());
+    }
 
-        result
+    fn weaken_etag(&mut self) {
+        if let Some((_, tag)) = self.headers.iter_mut().find(|(key, _)| key.eq_ignore_ascii_case("ETag")) {
+            *tag = ETag::weaken(tag);
+        }
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+    // a 304 for a GET or HEAD whose If-None-Match names the tag of the response, with only the
+    // headers a cache needs to refresh its copy (RFC 9110 15.4.5)
+    // If-Modified-Since is only looked at without If-None-Match
+    pub fn not_modified(&mut self) -> bool {
+        let header = |name: &str| self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value);
+        let unchanged = match (self.request.if_none_match(), self.request.if_modified_since()) {
+            (Some(if_none_match), _) => header("ETag").is_some_and(|tag| ETag::matches(if_none_match, tag)),
+            (None, Some(since)) => {
+                let modified = header("Last-Modified").and_then(|date| Utils::parse_http_date(date));
+                modified.is_some_and(|modified| modified <= since)
+            }
+            (None, None) => false,
+        };
+        let method = &self.request.method;
+        if !unchanged || self.status_code != HttpStatus::Ok || !matches!(method, HttpMethod::GET | HttpMethod::HEAD) {
+            return false;
+        }
+
+        const KEPT: [&str; 6] = ["ETag", "Vary", "Cache-Control", "Content-Location", "Expires", "Content-Language"];
+        self.headers.retain(|(key, _)| KEPT.iter().any(|kept| key.eq_ignore_ascii_case(kept)));
+        self.status_code = HttpStatus::NotModified;
+        self.body = Vec::new();
+        self._size = 0;
+        self._need_stream = false;
+        self._is_compiled = true;
+        self._is_generated = false;
+        true
+    }
+
+    // reads a file body into memory so it can be rewritten before sending
+    fn load_body(&mut self) -> bool {
+        if !self._is_compiled {
+            match std::fs::read(&self._path) {
+                Ok(content) => self.body = content,
+                Err(_) => return false,
+            }
+            self._size = self.body.len();
+            self._is_compiled = true;
+        }
+        true
+    }
 
-        bytes.extend_from_slice(self.http_description().as_bytes());
-        bytes.extend_from_slice("\r\n".as_bytes()); // add a blank line between headers and body
-        bytes.extend_from_slice(&self.body);
+    pub fn http_description(&self) -> String {
+        let mut head = Vec::new();
+        self.head_into(&mut head);
+        // built from Strings only, so always valid UTF-8
+        String::from_utf8(head).unwrap_or_default()
+    }
 
+    // the status line, headers and cookies, appended as bytes without a String in between
+    fn head_into(&self, buffer: &mut Vec<u8>) {
+        let status = self.status_code.to_code().to_string();
+        for part in [self.http_version.as_str(), " ", &status, " ", self.status_code.to_message(), "\r\n"] {
+            buffer.extend_from_slice(part.as_bytes());
+        }
+        for (key, value) in &self.headers {
+            for part in [key.trim(), ": ", value.trim(), "\r\n"] {
+                buffer.extend_from_slice(part.as_bytes());
+            }
+        }
+        for (key, value) in &self.cookies {
+            for part in ["Set-Cookie: ", key.trim(), "=", value.trim(), "\r\n"] {
+                buffer.extend_from_slice(part.as_bytes());
+            }
+        }
+    }
+
+    // the head and the blank line ending it in one write, the body follows separately or not at all
+    pub fn write_head<W: Write>(&self, stream: &mut W) -> Result<(), Error> {
+        let mut buffer = BufferPool::take();
+        self.head_into(&mut buffer);
+        buffer.extend_from_slice(b"\r\n");
+        stream.write_all(&buffer)
+    }
+
+    // head and body as they are, binary bodies included, assembled in a pooled buffer so the
+    // whole response goes out in one write
+    pub fn write<W: Write>(&self, stream: &mut W) -> Result<(), Error> {
+        let mut buffer = BufferPool::take();
+        self.head_into(&mut buffer);
+        buffer.extend_from_slice(b"\r\n");
+        buffer.extend_from_slice(&self.body);
+        stream.write_all(&buffer)
+    }
+
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+
+    // Vary lists every request header that chose this response
+    pub fn add_vary(&mut self, name: &str) {
+        match self.headers.iter_mut().find(|(key, _)| key.eq_ignore_ascii_case("Vary")) {
+            Some((_, value)) if value.split(',').any(|field| field.trim().eq_ignore_ascii_case(name)) => {}
+            Some((_, value)) => value.push_str(&format!(", {}", name)),
+            None => self.headers.push(("Vary".to_string(), name.to_string())),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.body.len() + 256);
+        // writing to a Vec cannot fail
+        let _ = self.write(&mut bytes);
         bytes
     }
 
-    pub fn stream(&mut self, stream: &mut TcpStream) -> Result<(), Error> {
-        self.headers.push(("Content-Length".to_string(), self._size.to_string()));
+    pub fn stream<W: Write>(&mut self, stream: &mut W) -> Result<(), Error> {
+        // a 304 has no body and its length would have to be the one of the 200
+        if self.status_code != HttpStatus::NotModified {
+            self.set_header("Content-Length", &self._size.to_string());
+        }
+
+        if self.request.method == HttpMethod::HEAD {
+            // the length still describes the resource, but no body may follow
+            self.write_head(stream)?;
+            stream.flush()?;
+            return Ok(());
+        }
 
         if self._is_compiled {
-            if self.body.len() == 0 {
-                Logger::error("Body is empty while expecting body to have compiled content");
+            if self.body.len() != self._size {
+                Logger::error("Compiled body does not match its announced size");
                 self.serve_error_response(HttpStatus::InternalServerError);
-                stream.write_all(self.to_bytes().as_slice())?;
+                self.write(stream)?;
                 stream.flush()?;
                 return Ok(());
             }
 
-            stream.write_all(self.to_bytes().as_slice())?;
+            self.write(stream)?;
             stream.flush()?;
             return Ok(());
         }
@@ -300,18 +919,21 @@ impl Response {
                 Err(_) => {
                     Logger::error(format!("Failed to open file: {}", self._path.display()).as_str());
                     self.serve_error_response(HttpStatus::NotFound);
-                    stream.write_all(self.to_bytes().as_slice())?;
+                    self.write(stream)?;
                     stream.flush()?;
                     return Ok(());
                 }
             };
 
-            // read into a buffer
-            let mut buffer = vec![0; self._size];
-            file.read_exact(&mut buffer)?;
-            self.body = buffer;
+            // read straight after the head, the whole response goes out in one write
+            let mut buffer = BufferPool::take();
+            self.head_into(&mut buffer);
+            buffer.extend_from_slice(b"\r\n");
+            let start = buffer.len();
+            buffer.resize(start + self._size, 0);
+            file.read_exact(&mut buffer[start..])?;
 
-            stream.write_all(self.to_bytes().as_slice()).unwrap();
+            stream.write_all(&buffer)?;
             stream.flush()?;
             return Ok(());
         }
@@ -321,7 +943,7 @@ impl Response {
             Err(error) => {
                 Logger::error(format!("Error while streaming by chunk: {}", error).as_str());
                 self.serve_error_response(HttpStatus::InternalServerError);
-                stream.write_all(self.to_bytes().as_slice())?;
+                self.write(stream)?;
                 return Ok(());
             },
         };
@@ -329,7 +951,7 @@ impl Response {
         Ok(())
     }
 
-    fn stream_by_chunk(&mut self, stream: &mut TcpStream) -> Result<(), Error> {
+    fn stream_by_chunk<W: Write>(&mut self, stream: &mut W) -> Result<(), Error> {
         // @see: https://developer.mozilla.org/fr/docs/Web/HTTP/Reference/Status/206
         // @see: https://www.rfc-editor.org/rfc/rfc2616.html#section-14.35
 
@@ -339,7 +961,7 @@ impl Response {
             Err(_) => {
                 Logger::error(format!("Failed to open file: {}", self._path.display()).as_str());
                 self.serve_error_response(HttpStatus::NotFound);
-                stream.write_all(self.to_bytes().as_slice())?;
+                self.write(stream)?;
                 stream.flush()?;
                 return Ok(());
             }
@@ -347,17 +969,15 @@ impl Response {
 
         Logger::debug(format!("[Response] Sending response in chunks with size: {}", self._size).as_str());
 
-        self.headers.push(("Content-Length".to_string(), self._size.to_string()));
-
         // @see: https://datatracker.ietf.org/doc/html/rfc7233
         self.headers.push(("Accept-Ranges".to_string(), "bytes".to_string()));
 
         // check if range header is present
-        if let Some(range) = self.request.headers.iter().find(|(k, _)| k == "Range").map(|(_, v)| v) {
+        if let Some(range) = self.request.range() {
             // parse range header value and extract bytes start, end
             if !range.starts_with("bytes=") {
                 self.serve_error_response(HttpStatus::BadRequest);
-                stream.write_all(self.to_bytes().as_slice())?;
+                self.write(stream)?;
                 stream.flush()?;
                 return Ok(());
             }
@@ -365,7 +985,7 @@ impl Response {
             let range_values: Vec<&str> = range[6..].split('-').collect();
             if range_values.len() != 2 {
                 self.serve_error_response(HttpStatus::BadRequest);
-                stream.write_all(self.to_bytes().as_slice())?;
+                self.write(stream)?;
                 stream.flush()?;
                 return Ok(());
             }
@@ -378,8 +998,8 @@ impl Response {
                 // @see: https://http.dev/416
                 self.status_code = HttpStatus::RangeNotSatisfiable;
                 self.headers.push(("Content-Range".to_string(), format!("bytes */{}", self._size)));
-                stream.write_all(self.http_description().as_bytes())?;
-                stream.write_all(b"\r\n")?;
+                self.set_header("Content-Length", "0");
+                self.write_head(stream)?;
                 stream.flush()?;
                 return Ok(());
             }
@@ -388,18 +1008,17 @@ impl Response {
             self.status_code = HttpStatus::PartialContent;
             self.headers.push(("Content-Range".to_string(),
                                format!("bytes {}-{}/{}", start, end, self._size)));
-            self.headers.push(("Content-Length".to_string(),
-                               (end - start + 1).to_string()));
+            self.set_header("Content-Length", &(end - start + 1).to_string());
 
-            stream.write_all(self.http_description().as_bytes())?;
-            stream.write_all(b"\r\n")?;
+            self.write_head(stream)?;
 
             // set start position to avoid reading the whole file
             file.seek(SeekFrom::Start(start as u64))?;
 
             // stream the requested range in chunks
             let mut remaining = end - start + 1;
-            let mut buffer = vec![0; min(Response::CHUNK_SIZE, remaining)];
+            let mut buffer = BufferPool::take();
+            buffer.resize(min(Response::CHUNK_SIZE, remaining), 0);
 
             while remaining > 0 {
                 let to_read = min(buffer.len(), remaining);
@@ -412,11 +1031,11 @@ impl Response {
             }
         } else {
             // no range header, stream entire file
-            stream.write_all(self.http_description().as_bytes())?;
-            stream.write_all(b"\r\n")?;
+            self.write_head(stream)?;
 
             // stream the file in chunks
-            let mut buffer = vec![0; Response::CHUNK_SIZE];
+            let mut buffer = BufferPool::take();
+            buffer.resize(Response::CHUNK_SIZE, 0);
             loop {
                 let bytes_read = file.read(&mut buffer)?;
                 if bytes_read == 0 {
@@ -431,3 +1050,14 @@ impl Response {
         Ok(())
     }
 }
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\r\n", self.http_description())?; // add a blank line between headers and body
+        // for reading only, the bytes sent are the ones of write
+        match std::str::from_utf8(&self.body) {
+            Ok(body) => write!(f, "{}", body),
+            Err(_) => write!(f, "[{} bytes of binary body]", self.body.len()),
+        }
+    }
+}
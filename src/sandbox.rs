@@ -0,0 +1,207 @@
+use crate::config::Config;
+use crate::wellknown::{Favicon, Robots};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::os::raw::{c_int, c_long};
+
+    pub const CREATE_RULESET: c_long = 444;
+    pub const ADD_RULE: c_long = 445;
+    pub const RESTRICT_SELF: c_long = 446;
+    pub const CREATE_RULESET_VERSION: usize = 1;
+    pub const RULE_PATH_BENEATH: usize = 1;
+    pub const PR_SET_NO_NEW_PRIVS: c_int = 38;
+
+    #[repr(C)]
+    pub struct RulesetAttr {
+        pub handled_access_fs: u64,
+    }
+
+    #[repr(C, packed)]
+    pub struct PathBeneathAttr {
+        pub allowed_access: u64,
+        pub parent_fd: c_int,
+    }
+
+    extern "C" {
+        pub fn syscall(number: c_long, ...) -> c_long;
+        pub fn prctl(option: c_int, ...) -> c_int;
+    }
+}
+
+// what the process may still do below a path once it is sandboxed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    // read files and list directories
+    Read,
+    // read, and create, change, rename and remove what is inside
+    Write,
+    // create and append to files, without reading them back
+    Log,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub path: PathBuf,
+    pub access: Access,
+}
+
+// --sandbox, Linux Landlock: once the listeners are bound, the process and everything it
+// starts lose access to every file but those it serves and a few it needs, so a path handling
+// bug cannot disclose /etc/shadow or a key next to the share. Files opened before, such as
+// the logs, keep working
+pub struct Sandbox;
+
+impl Sandbox {
+    // read at runtime by name resolution for proxies and by local time formatting
+    const SYSTEM_FILES: &'static [&'static str] = &["/etc/hosts", "/etc/resolv.conf", "/etc/nsswitch.conf", "/etc/localtime"];
+
+    // Landlock access rights, the first version of the ABI handles the thirteen up to
+    // MAKE_SYM, REFER came with the second and TRUNCATE with the third
+    const EXECUTE: u64 = 1 << 0;
+    const WRITE_FILE: u64 = 1 << 1;
+    const READ_FILE: u64 = 1 << 2;
+    const READ_DIR: u64 = 1 << 3;
+    const REMOVE_DIR: u64 = 1 << 4;
+    const REMOVE_FILE: u64 = 1 << 5;
+    const MAKE_DIR: u64 = 1 << 7;
+    const MAKE_REG: u64 = 1 << 8;
+    const REFER: u64 = 1 << 13;
+    const TRUNCATE: u64 = 1 << 14;
+    const ABI_1: u64 = (1 << 13) - 1;
+    // the only rights a rule on a file rather than a directory may hold
+    const FILE_RIGHTS: u64 = Self::EXECUTE | Self::WRITE_FILE | Self::READ_FILE | Self::TRUNCATE;
+
+    // every path the configuration serves or reads again on a reload; paths that do not exist
+    // are skipped when the sandbox is applied
+    pub fn rules(config: &Config) -> Vec<Rule> {
+        let mut rules = Vec::new();
+        let mut add = |path: &Path, access: Access| {
+            if !rules.iter().any(|rule: &Rule| rule.path == path && rule.access == access) {
+                rules.push(Rule { path: path.to_path_buf(), access });
+            }
+        };
+
+        let sites = std::iter::once(config).chain(config.vhosts.iter().map(|vhost| vhost.config.as_ref()));
+        for site in sites {
+            add(&site.root_dir, if site.writable { Access::Write } else { Access::Read });
+            if site.tus.endpoint.is_some() {
+                add(&site.tus.dir(&site.root_dir), Access::Write);
+            }
+            if let Some(page) = &site.maintenance.page {
+                add(page, Access::Read);
+            }
+            if let Some(Robots::File(path)) = &site.robots {
+                add(path, Access::Read);
+            }
+            if let Favicon::File(path) = &site.favicon {
+                add(path, Access::Read);
+            }
+            for location in &site.locations {
+                add(&location.file, Access::Read);
+            }
+        }
+        for vhost in &config.vhosts {
+            add(&vhost.file, Access::Read);
+        }
+        for path in [&config.config_file, &config.templates_dir, &config.plugins_dir, &config.acme_dir].into_iter().flatten() {
+            add(path, Access::Read);
+        }
+        // reopened on every reload, and rotated away in between
+        if let Some(error_log) = &config.error_log {
+            let dir = error_log.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            add(dir, Access::Log);
+        }
+        for path in Self::SYSTEM_FILES {
+            add(Path::new(path), Access::Read);
+        }
+        rules
+    }
+
+    // for this thread and those it starts afterwards, so before the workers; false when the
+    // kernel has no Landlock, which was added in Linux 5.13 and may be left out of a build
+    #[cfg(target_os = "linux")]
+    pub fn apply(rules: &[Rule]) -> Result<bool, Error> {
+        use std::fs::File;
+        use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+        let abi = unsafe {
+            sys::syscall(sys::CREATE_RULESET, std::ptr::null::<sys::RulesetAttr>(), 0usize, sys::CREATE_RULESET_VERSION)
+        };
+        if abi < 1 {
+            return match Error::last_os_error().raw_os_error() {
+                // ENOSYS, EOPNOTSUPP
+                Some(38) | Some(95) => Ok(false),
+                _ => Err(Error::last_os_error()),
+            };
+        }
+        let mut handled = Self::ABI_1;
+        if abi >= 2 {
+            handled |= Self::REFER;
+        }
+        if abi >= 3 {
+            handled |= Self::TRUNCATE;
+        }
+
+        let attr = sys::RulesetAttr { handled_access_fs: handled };
+        let fd = unsafe { sys::syscall(sys::CREATE_RULESET, &attr, std::mem::size_of::<sys::RulesetAttr>(), 0usize) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        let ruleset = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+        for rule in rules {
+            let file = match File::open(&rule.path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(Error::new(e.kind(), format!("{}: {}", rule.path.display(), e))),
+            };
+            let mut allowed = Self::rights(rule.access) & handled;
+            if !file.metadata()?.is_dir() {
+                allowed &= Self::FILE_RIGHTS;
+            }
+            let beneath = sys::PathBeneathAttr { allowed_access: allowed, parent_fd: file.as_raw_fd() };
+            let result =
+                unsafe { sys::syscall(sys::ADD_RULE, ruleset.as_raw_fd() as usize, sys::RULE_PATH_BENEATH, &beneath, 0usize) };
+            if result < 0 {
+                let e = Error::last_os_error();
+                return Err(Error::new(e.kind(), format!("{}: {}", rule.path.display(), e)));
+            }
+        }
+
+        // a sandboxed process must not gain rights back by running a setuid program
+        unsafe {
+            if sys::prctl(sys::PR_SET_NO_NEW_PRIVS, 1u64, 0u64, 0u64, 0u64) != 0 {
+                return Err(Error::last_os_error());
+            }
+            if sys::syscall(sys::RESTRICT_SELF, ruleset.as_raw_fd() as usize, 0usize) != 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+        Ok(true)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply(_rules: &[Rule]) -> Result<bool, Error> {
+        Err(Error::new(ErrorKind::Unsupported, "the sandbox needs Linux Landlock"))
+    }
+
+    fn rights(access: Access) -> u64 {
+        let read = Self::READ_FILE | Self::READ_DIR;
+        match access {
+            Access::Read => read,
+            Access::Write => {
+                read | Self::WRITE_FILE
+                    | Self::REMOVE_DIR
+                    | Self::REMOVE_FILE
+                    | Self::MAKE_DIR
+                    | Self::MAKE_REG
+                    | Self::REFER
+                    | Self::TRUNCATE
+            }
+            Access::Log => Self::WRITE_FILE | Self::MAKE_REG | Self::TRUNCATE,
+        }
+    }
+}
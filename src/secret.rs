@@ -0,0 +1,26 @@
+use std::env;
+use std::fs;
+
+// secret options given as env:NAME or file:/path rather than inline, where ps, shell history
+// and `docker inspect` would show them: orchestrators hand secrets over in the environment
+// or as mounted files, such as /run/secrets/ for Docker and Kubernetes
+pub struct Secret;
+
+impl Secret {
+    pub const ENV_PREFIX: &'static str = "env:";
+    pub const FILE_PREFIX: &'static str = "file:";
+
+    // anything else is the secret itself; a file keeps everything but its final line break,
+    // which editors and `echo` add
+    pub fn resolve(value: &str) -> Result<String, String> {
+        if let Some(name) = value.strip_prefix(Self::ENV_PREFIX) {
+            env::var(name).map_err(|_| format!("environment variable {} is not set", name))
+        } else if let Some(path) = value.strip_prefix(Self::FILE_PREFIX) {
+            let content = fs::read_to_string(path).map_err(|e| format!("cannot read secret file {}: {}", path, e))?;
+            let secret = content.strip_suffix('\n').unwrap_or(&content);
+            Ok(secret.strip_suffix('\r').unwrap_or(secret).to_string())
+        } else {
+            Ok(value.to_string())
+        }
+    }
+}
@@ -1,94 +1,1587 @@
+use crate::acme::Acme;
+use crate::admin::Admin;
+use crate::cgi::{Cgi, FastCgi};
+use crate::compression::ContentEncoding;
 use crate::config::Config;
+use crate::connection::{BindError, BodyCounter, Connection, Listener};
+use crate::csrf::Csrf;
+use crate::daemon::Daemon;
+use crate::etag::ETag;
+use crate::handler::Handler;
+use crate::hooks::HookEvent;
 use crate::http::{HttpMethod, HttpStatus};
+use crate::ignore::Ignore;
+use crate::jwt::JwtError;
+use crate::livereload::LiveReload;
 use crate::logger::Logger;
+use crate::mdns::Mdns;
+use crate::multipart::Multipart;
+use crate::ocsp::{CertStatus, Ocsp};
+use crate::plugin::{Plugin, PluginAction, RequestView, ResponseView, WasmModule};
+use crate::pool::BufferPool;
+use crate::proxy::ForwardProxy;
+use crate::qrcode::QrCode;
 use crate::request::Request;
 use crate::response::Response;
-use crate::templates::Templates;
+use crate::service::Service;
+use crate::signal::Signal;
+use crate::signed::{SignatureError, SignedUrls};
+use crate::stats::{Phase, Stats};
+use crate::syslog::Syslog;
+use crate::templates::{Templates, TemplatesPage};
+use crate::tls::CertificateStore;
+use crate::userdir::UserDir;
 use crate::utils::Utils;
-use std::net::{TcpListener, TcpStream};
-use std::ops::DerefMut;
+use crate::wellknown::{Favicon, Robots, SecurityTxt};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Error, ErrorKind, IsTerminal, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, RwLock};
 use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+#[derive(Clone)]
 pub struct Server {
-    config: Config,
+    // each request reads a snapshot, so a reload never changes settings mid-request
+    config: Arc<RwLock<Arc<Config>>>,
     templates: Templates,
+    plugins: Vec<Arc<dyn Plugin>>,
+    certificates: Arc<CertificateStore>,
 }
 
 impl Server {
-    const SERVER_NAME: &'static str = "Katana";
-    const SERVER_VERSION: &'static str = "0.1.0";
-    pub const SUPPORTED_HTTP_METHODS: &'static [HttpMethod] = &[
-        HttpMethod::GET,
-        HttpMethod::HEAD,
-        HttpMethod::OPTIONS,
-        HttpMethod::TRACE,
-    ];
+    pub const SERVER_NAME: &'static str = "Katana";
+    pub const SERVER_VERSION: &'static str = env!("CARGO_PKG_VERSION");
+    // set by build.rs, the commit is empty when built outside a git checkout
+    pub const GIT_COMMIT: &'static str = env!("KATANA_GIT_COMMIT");
+    pub const BUILD_TIME: &'static str = env!("KATANA_BUILD_TIME");
+    // the methods Katana has a handler for, --allowed-methods picks among them; TRACE is left
+    // out since echoing the request hands cookies and credentials to scripts (cross-site tracing)
+    pub const SUPPORTED_HTTP_METHODS: &'static [HttpMethod] = &[HttpMethod::GET, HttpMethod::HEAD, HttpMethod::OPTIONS];
+
+    pub const HEALTH_PATH: &'static str = "/healthz";
+    pub const STATUS_PATH: &'static str = "/_katana/status";
+
+    // how many numbered copies an upload may add next to a file of the same name
+    const UPLOAD_MAX_COPIES: usize = 100;
+
+    pub const SUPERVISE_INTERVAL: Duration = Duration::from_millis(250);
+    // how often staples are looked at with --ocsp-stapling; responses last days and are
+    // refreshed halfway, a responder that is down is asked again on every round
+    pub const OCSP_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+    // how soon idle keep-alive connections notice a shutdown, and how often draining looks
+    // at the connections left
+    pub const DRAIN_INTERVAL: Duration = Duration::from_millis(100);
+    // sysexits(3), failures once the listeners are bound, apart from Config::EXIT_CONFIG and
+    // the codes of BindError
+    pub const EXIT_RUNTIME: i32 = 70; // EX_SOFTWARE
 
     pub fn new(config: Config, templates: Templates) -> Self {
-        Self { config, templates }
+        Self::configure_logger(&config);
+        let (certificates, errors) = CertificateStore::load(&config.tls_certs);
+        for error in errors {
+            Logger::error(format!("Cannot load certificate: {}", error).as_str());
+        }
+        Self {
+            config: Arc::new(RwLock::new(Arc::new(config))),
+            templates,
+            plugins: Vec::new(),
+            certificates: Arc::new(certificates),
+        }
+    }
+
+    // plugins run in the order they were added, before listen() so every connection sees them
+    pub fn add_plugin(&mut self, plugin: impl Plugin + 'static) {
+        Logger::info(format!("Loaded plugin {}.", plugin.name()).as_str());
+        self.plugins.push(Arc::new(plugin));
+    }
+
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap().clone()
+    }
+
+    pub fn templates(&self) -> &Templates {
+        &self.templates
+    }
+
+    // fetches the OCSP responses that are missing or due for a refresh and drops the expired
+    // ones; returns how many were fetched
+    pub fn refresh_staples(&self) -> usize {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let mut fetched = 0;
+        for pair in self.certificates.current().iter() {
+            let name = pair.certificate.cert.display();
+            if pair.staple.as_ref().is_some_and(|staple| now < staple.refresh_at()) {
+                continue;
+            }
+            let staple = Ocsp::cert_id(&pair.chain).and_then(|(url, id)| Ocsp::fetch(&url, &id));
+            match staple {
+                Ok(staple) => {
+                    if staple.status == CertStatus::Revoked {
+                        Logger::error(format!("Certificate {} has been revoked by its issuer.", name).as_str());
+                    }
+                    self.certificates.set_staple(&pair.certificate, &pair.chain, Some(staple));
+                    fetched += 1;
+                }
+                Err(e) => {
+                    Logger::warn(format!("Cannot fetch OCSP response for {}: {}", name, e).as_str());
+                    if pair.staple.as_ref().is_some_and(|staple| staple.is_expired(now)) {
+                        self.certificates.set_staple(&pair.certificate, &pair.chain, None);
+                    }
+                }
+            }
+        }
+        if fetched > 0 {
+            Logger::info(format!("Fetched {} OCSP response(s).", fetched).as_str());
+        }
+        fetched
     }
 
     pub fn serve(&self) {
-        let listener = TcpListener::bind(self.addr().as_str()).unwrap();
+        match self.listen() {
+            Ok(listeners) => {
+                if let Err(e) = self.drop_privileges() {
+                    Logger::error(format!("Cannot drop privileges: {}", e).as_str());
+                    process::exit(Self::EXIT_RUNTIME);
+                }
+                #[cfg(feature = "landlock")]
+                if let Err(e) = self.sandbox() {
+                    Logger::error(format!("Cannot sandbox the server: {}", e).as_str());
+                    process::exit(Self::EXIT_RUNTIME);
+                }
+                self.run(listeners)
+            }
+            Err(e) => {
+                Logger::error(e.to_string().as_str());
+                process::exit(e.exit_code());
+            }
+        }
+    }
+
+    // once the listeners are bound and before any worker thread, they stay open whatever the
+    // process may no longer do; in a chroot the root directory is /
+    fn drop_privileges(&self) -> Result<(), Error> {
+        let config = self.config();
+        if !config.privileges.enabled() {
+            return Ok(());
+        }
+        config.privileges.apply(&config.root_dir)?;
+        if config.privileges.chroot {
+            Logger::info(format!("Locked into {}.", config.root_dir.display()).as_str());
+            let mut chrooted = (*config).clone();
+            chrooted.root_dir = PathBuf::from("/");
+            *self.config.write().unwrap() = Arc::new(chrooted);
+        }
+        if let Some(user) = &config.privileges.user {
+            Logger::info(format!("Running as {}.", user).as_str());
+        }
+        Ok(())
+    }
+
+    // after the chroot, whose paths the rules then use, and before any thread is started:
+    // only those started afterwards inherit it, the OTLP exporter included
+    #[cfg(feature = "landlock")]
+    fn sandbox(&self) -> Result<(), Error> {
+        let config = self.config();
+        if !config.sandbox {
+            return Ok(());
+        }
+        let rules = crate::sandbox::Sandbox::rules(&config);
+        if crate::sandbox::Sandbox::apply(&rules)? {
+            Logger::info(format!("Sandboxed to {} path(s).", rules.len()).as_str());
+        } else {
+            Logger::warn("This kernel has no Landlock, running without a sandbox.");
+        }
+        Ok(())
+    }
+
+    // binds (or inherits) every listener and reports where it ended up, which is the
+    // only way to learn the port chosen for --port 0; callers embedding the server can
+    // read Listener::url() before handing the listeners to run()
+    pub fn listen(&self) -> Result<Vec<Listener>, BindError> {
+        let listeners = match Listener::from_systemd() {
+            Some(listeners) => {
+                Logger::info(format!("Using {} socket(s) passed by systemd.", listeners.len()).as_str());
+                listeners
+            }
+            None => self.bind()?,
+        };
+
+        for listener in &listeners {
+            let describe = listener.describe();
+            match listener.url().filter(|url| *url != describe) {
+                Some(url) => Logger::info(format!("Server starting on {} (open {})", describe, url).as_str()),
+                None => Logger::info(format!("Server starting on {}", describe).as_str()),
+            }
+        }
+        self.show_qr_code(&listeners);
+        let config = self.config();
+        if let Some(dir) = &config.plugins_dir {
+            Self::inspect_wasm_plugins(dir);
+        }
+
+        Ok(listeners)
+    }
+
+    fn inspect_wasm_plugins(dir: &Path) {
+        let mut valid = 0;
+        for path in WasmModule::discover(dir) {
+            match fs::read(&path).map_err(|e| e.to_string()).and_then(|bytes| WasmModule::hooks(&bytes)) {
+                Ok(_) => valid += 1,
+                Err(e) => Logger::error(format!("Invalid plugin {}: {}", path.display(), e).as_str()),
+            }
+        }
+        if valid > 0 {
+            Logger::warn(
+                format!(
+                    "Found {} WebAssembly plugin(s) in {} but this build has no WebAssembly runtime, they are not loaded.",
+                    valid,
+                    dir.display()
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    // lets a phone on the same network open the share, only for people at a terminal
+    fn show_qr_code(&self, listeners: &[Listener]) {
+        let config = self.config();
+        if config.no_qr || config.daemon || !io::stdout().is_terminal() {
+            return;
+        }
+        let Some(url) = listeners.iter().find_map(Listener::lan_url) else {
+            return;
+        };
+        if let Some(qr) = QrCode::encode(url.as_bytes()) {
+            println!("\n{}Scan to open {}\n", qr.to_terminal(), url);
+        }
+    }
+
+    pub fn run(&self, mut listeners: Vec<Listener>) {
+        #[cfg(feature = "otel")]
+        Self::configure_tracer(&self.config());
+        Stats::start();
+        let mut socket_paths: Vec<PathBuf> = listeners.iter().filter_map(Listener::socket_path).collect();
+        socket_paths.extend(self.start_admin());
+        self.supervise(socket_paths);
+        self.check_upstreams();
+        if self.config().ocsp_stapling {
+            self.staple_certificates();
+        }
+        if self.config().watch {
+            Logger::info(format!("Watching {} for changes.", self.config().root_dir.display()).as_str());
+            LiveReload::watch(self.config().root_dir.clone());
+        }
+        if self.config().mdns {
+            self.advertise(&listeners);
+        }
+
+        let workers = if self.config().reuse_port { self.config().worker.max(1) as usize } else { 1 };
+        if workers > 1 {
+            Logger::info(format!("Accepting connections with {} workers per listener.", workers).as_str());
+        }
+        let mut listeners: Vec<Listener> = listeners
+            .drain(..)
+            .flat_map(|listener| self.workers(listener, workers))
+            .collect();
+
+        // every extra listener gets its own accept loop, the last one runs on this thread
+        let last = listeners.pop().unwrap();
+        for listener in listeners {
+            let server = self.clone();
+            thread::spawn(move || server.accept(listener));
+        }
+        self.accept(last);
+    }
+
+    // a failing admin listener is logged but does not keep the site down, returns the socket
+    // file to clean up on shutdown
+    fn start_admin(&self) -> Option<PathBuf> {
+        let addr = self.config().admin_listen.clone()?;
+        match Admin::bind(&addr) {
+            Ok(listener) => {
+                Logger::info(format!("Admin API listening on {}", listener.describe()).as_str());
+                let socket_path = listener.socket_path();
+                Admin::spawn(self.clone(), listener);
+                socket_path
+            }
+            Err(e) => {
+                Logger::error(format!("Cannot start the admin API on {}: {}", addr, e).as_str());
+                None
+            }
+        }
+    }
 
-        for stream in listener.incoming() {
-            if let Ok(stream) = stream {
-                // spawn a new thread for each connection
-                let config = self.config.clone();
-                let templates = self.templates.clone();
+    // mDNS answers carry an IPv4 A record, so the first listener reachable over IPv4 is announced
+    fn advertise(&self, listeners: &[Listener]) {
+        let addr = listeners.iter().filter_map(Listener::lan_addr).find_map(|addr| match addr.ip() {
+            IpAddr::V4(ip) => Some((ip, addr.port())),
+            IpAddr::V6(ip) => ip.to_ipv4_mapped().map(|ip| (ip, addr.port())),
+        });
+        let Some((ip, port)) = addr else {
+            Logger::warn("Not advertising over mDNS, no listener is reachable from the local network.");
+            return;
+        };
 
-                thread::spawn(move || {
-                    // create a new server instance for the thread with the necessary data
-                    let server = Server::new(config, templates);
-                    server.handle_request(stream);
-                });
+        let mdns = Mdns::new(&self.config().mdns_name, &Mdns::hostname(), port, ip);
+        let message = format!("Advertising \"{}\" on the local network as http://{}:{}", mdns.instance(), mdns.host(), port);
+        match mdns.start() {
+            Ok(()) => Logger::info(message.as_str()),
+            Err(e) => Logger::warn(format!("Cannot advertise over mDNS: {}", e).as_str()),
+        }
+    }
+
+    pub fn bind(&self) -> Result<Vec<Listener>, BindError> {
+        let socket_mode = self.config().socket_mode;
+        let mut listeners = Vec::new();
+        for addr in self.listen_addrs() {
+            let listener = self
+                .bind_addr(&addr, socket_mode)
+                .map_err(|e| BindError::from_io(&addr, e))?;
+            listeners.push(listener);
+            if let Some(ipv4) = listeners.last().and_then(Listener::ipv4_fallback) {
+                listeners.push(ipv4);
             }
         }
+        Ok(listeners)
     }
 
-    pub fn handle_request(&self, mut stream: TcpStream) {
-        if let Some(request) = Request::from_stream(&stream) {
-            self.handle_response(request, &mut stream);
+    // with --port-retry, a busy port moves on to the next ones instead of failing
+    fn bind_addr(&self, addr: &str, socket_mode: Option<u32>) -> Result<Listener, Error> {
+        let mut attempt = addr.to_string();
+        let mut retries = self.config().port_retry;
+
+        loop {
+            let result = match self.bind_reuse_port(&attempt) {
+                Some(listener) => Ok(listener),
+                None => Listener::bind(&attempt, socket_mode),
+            };
+
+            match result {
+                Err(e) if e.kind() == ErrorKind::AddrInUse && retries > 0 => {
+                    let next = match Self::next_port(&attempt) {
+                        Some(next) => next,
+                        None => return Err(e),
+                    };
+                    Logger::warn(format!("Address {} is in use, trying {}", attempt, next).as_str());
+                    attempt = next;
+                    retries -= 1;
+                }
+                Ok(listener) => {
+                    if attempt != addr {
+                        Logger::info(format!("Using {} instead of the busy {}", attempt, addr).as_str());
+                    }
+                    return Ok(listener);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn next_port(addr: &str) -> Option<String> {
+        if addr.starts_with(Listener::UNIX_PREFIX) {
+            return None;
+        }
+        let (host, port) = addr.rsplit_once(':')?;
+        let port = port.parse::<u16>().ok().filter(|port| *port != 0)?.checked_add(1)?;
+        Some(format!("{}:{}", host, port))
+    }
+
+    fn bind_reuse_port(&self, addr: &str) -> Option<Listener> {
+        if !self.config().reuse_port || addr.starts_with(Listener::UNIX_PREFIX) {
+            return None;
+        }
+        let resolved = addr.to_socket_addrs().ok()?.next()?;
+        Listener::bind_reuse_port(&resolved).ok()
+    }
+
+    // one socket per worker when SO_REUSEPORT is available, otherwise the workers
+    // share clones of the same listener and take turns in accept()
+    fn workers(&self, listener: Listener, count: usize) -> Vec<Listener> {
+        let mut workers = Vec::with_capacity(count);
+        for _ in 1..count {
+            let reuse_port = listener
+                .local_addr()
+                .filter(|_| self.config().reuse_port)
+                .and_then(|addr| Listener::bind_reuse_port(&addr).ok());
+            match reuse_port.or_else(|| listener.try_clone().ok()) {
+                Some(worker) => workers.push(worker),
+                None => break,
+            }
+        }
+        workers.push(listener);
+        workers
+    }
+
+    pub fn accept(&self, listener: Listener) {
+        loop {
+            let stream = match listener.accept() {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            // draining, new connections are closed right away
+            if Signal::shutdown_requested() {
+                continue;
+            }
+            // spawn a new thread for each connection, sharing the same configuration handle
+            let server = self.clone();
+
+            thread::spawn(move || {
+                server.handle_connection(stream);
+            });
+        }
+    }
+
+    // probes the proxy upstreams of the current configuration in the background, those of a
+    // reloaded one from the next round on
+    fn check_upstreams(&self) {
+        let server = self.clone();
+        thread::spawn(move || loop {
+            let config = server.config();
+            for proxy in config.proxies() {
+                proxy.check();
+            }
+            thread::sleep(Self::SUPERVISE_INTERVAL);
+        });
+    }
+
+    // keeps the OCSP staples fresh in the background
+    fn staple_certificates(&self) {
+        let server = self.clone();
+        thread::spawn(move || loop {
+            server.refresh_staples();
+            thread::sleep(Self::OCSP_REFRESH_INTERVAL);
+        });
+    }
+
+    // handles signals in the background: SIGHUP (or a changed file with --watch-config)
+    // reloads the configuration, SIGINT/SIGTERM remove unix socket and pid files and exit
+    fn supervise(&self, socket_paths: Vec<PathBuf>) {
+        Signal::listen();
+        let server = self.clone();
+
+        thread::spawn(move || {
+            let mut last_modified = server.config_file_modified();
+            loop {
+                thread::sleep(Self::SUPERVISE_INTERVAL);
+
+                if Signal::shutdown_requested() {
+                    for path in &socket_paths {
+                        Listener::cleanup(path);
+                    }
+                    let cut_off = server.drain(server.config().grace_period);
+                    if cut_off > 0 {
+                        Logger::warn(format!("Closed {} connection(s) still busy after the grace period.", cut_off).as_str());
+                    }
+                    Daemon::cleanup(&server.config());
+                    Mdns::stop();
+                    Logger::info(format!("Server stopped, {}.", Stats::summary()).as_str());
+                    Service::stopped();
+                    process::exit(0);
+                }
+
+                let mut reload = Signal::take_reload();
+                if server.config().watch_config {
+                    let modified = server.config_file_modified();
+                    if modified != last_modified {
+                        last_modified = modified;
+                        reload = true;
+                    }
+                }
+
+                if reload {
+                    Logger::info(format!("Serving stats: {}.", Stats::summary()).as_str());
+                    server.reload_config();
+                }
+            }
+        });
+    }
+
+    // once a shutdown is requested, waits for the connections to finish the request they are
+    // on, each one is answered with Connection: close and idle ones close at once; returns how
+    // many were still open when the grace period ran out
+    pub fn drain(&self, grace_period: Duration) -> usize {
+        let deadline = Instant::now() + grace_period;
+        let active = Stats::active_connections();
+        if active > 0 {
+            Logger::info(format!("Draining {} connection(s) for up to {}s.", active, grace_period.as_secs_f64()).as_str());
+        }
+        while Stats::active_connections() > 0 && Instant::now() < deadline {
+            thread::sleep(Self::DRAIN_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+        }
+        Stats::active_connections()
+    }
+
+    fn configure_logger(config: &Config) {
+        Logger::set_format(config.log_format);
+        Logger::set_level(config.log_level);
+        Logger::set_quiet(config.quiet);
+        Logger::set_stdout_only(config.container);
+        Logger::set_color(!config.no_color && Logger::detect_color());
+        // reopened on every reload, which is also how rotated files get picked up
+        if let Err(e) = Logger::set_error_log(config.error_log.as_deref()) {
+            Logger::error(format!("Cannot open error log, using stderr: {}", e).as_str());
+        }
+        match config.syslog.as_deref().map(|target| Syslog::connect(target, config.syslog_facility)) {
+            Some(Ok(syslog)) => Logger::set_syslog(Some(syslog)),
+            Some(Err(e)) => {
+                Logger::set_syslog(None);
+                Logger::error(format!("Cannot reach syslog, logging to the console: {}", e).as_str());
+            }
+            None => Logger::set_syslog(None),
+        }
+    }
+
+    // the exporter is a thread of its own, started from run() so that it is sandboxed like
+    // every other: Landlock only restricts the threads started after it is applied
+    #[cfg(feature = "otel")]
+    fn configure_tracer(config: &Config) {
+        crate::otel::Tracer::configure(config.otlp_endpoint.as_deref());
+    }
+
+    fn config_file_modified(&self) -> Option<SystemTime> {
+        let config = self.config();
+        let path = config.config_file.as_ref()?;
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    pub fn reload_config(&self) -> bool {
+        let current = self.config();
+        match current.reload() {
+            Ok(mut config) => {
+                if config.host != current.host
+                    || config.port != current.port
+                    || config.listen != current.listen
+                {
+                    Logger::warn("Changing host or port requires a restart, keeping the current listener.");
+                }
+                // the paths of a chroot, whatever the file says
+                if current.privileges.chroot {
+                    config.root_dir = PathBuf::from("/");
+                }
+                Self::configure_logger(&config);
+                #[cfg(feature = "otel")]
+                Self::configure_tracer(&config);
+                *self.config.write().unwrap() = Arc::new(config);
+                Logger::info("Configuration reloaded.");
+                true
+            }
+            Err(errors) => {
+                for error in &errors {
+                    Logger::error(error);
+                }
+                Logger::error("Configuration is invalid, keeping the current one.");
+                false
+            }
+        }
+    }
+
+    pub fn handle_connection(&self, mut stream: Connection) {
+        let mut active = Stats::connection();
+        // the idle timeout applies between requests as well as while waiting for the first one
+        let timeout = Duration::from_secs(self.config().keep_alive_timeout.max(1));
+        if stream.set_read_timeout(Some(timeout)).is_err() {
+            Logger::warn("Failed to set connection read timeout.");
+        }
+
+        let mut reader = match stream.try_clone() {
+            Ok(read_half) => BufReader::new(read_half),
+            Err(e) => {
+                Logger::error(format!("Failed to clone connection: {}", e).as_str());
+                return;
+            }
+        };
+
+        // reused by every request of the connection, and by the next connection after it
+        let mut buffer = BufferPool::take();
+        let mut served = 0;
+        loop {
+            if !Self::wait_for_request(&mut reader, &stream, timeout) {
+                break;
+            }
+            active.set_phase(Phase::Reading);
+
+            let mut request = match Request::read_head_with(&mut reader, &mut buffer) {
+                Ok(request) => request,
+                Err(e) => {
+                    // the rest of a malformed head cannot be trusted, the connection is closed
+                    if let Some(status) = e.status() {
+                        Logger::warn(format!("Rejected request: {}", e).as_str());
+                        let mut request = Request::default();
+                        request.set_peer(stream.peer_addr());
+                        request.assign_id(false);
+                        Logger::set_request_id(Some(&request.id));
+                        self.reject_request(request, &mut stream, status);
+                    }
+                    break;
+                }
+            };
+
+            let config = self.config();
+            request.set_peer(stream.peer_addr());
+            let trusted = request.peer.is_some_and(|addr| config.is_trusted_proxy(addr.ip()));
+            request.assign_id(trusted);
+            if trusted {
+                request.apply_forwarded(|ip| config.is_trusted_proxy(ip));
+            }
+            Logger::set_request_id(Some(&request.id));
+            // the connection becomes a tunnel, starting with what the client sent past the head
+            if request.method == HttpMethod::CONNECT && config.forward_proxy.enabled {
+                active.set_phase(Phase::Writing);
+                self.serve_tunnel(&config, request, &mut stream, reader.buffer());
+                break;
+            }
+            // the body of a refused method is left unread and the connection closed below, a
+            // POST that may stand in for another method is read for its _method field
+            let overridable = request.method == HttpMethod::POST
+                && request.client_ip.is_some_and(|ip| config.allows_method_override(ip));
+            let allowed = config.methods_for(&request.path).contains(&request.method) || overridable;
+            let continues = if allowed { request.expects_continue() } else { Ok(false) };
+            let continues = match continues {
+                Ok(continues) => continues,
+                Err(e) => {
+                    Logger::warn(format!("Rejected request: {}", e).as_str());
+                    self.reject_request(request, &mut stream, e.status().unwrap_or(HttpStatus::ExpectationFailed));
+                    break;
+                }
+            };
+            // a client waiting for a 100 Continue is answered without its body when the answer
+            // does not depend on it, the same way as a refused method
+            let refused = continues && Self::refuses_early(&config, &request);
+            if continues && !refused && request.content_length().unwrap_or(0) <= config.max_body_size {
+                if let Err(e) = stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").and_then(|_| stream.flush()) {
+                    Logger::warn(format!("Failed to send 100 Continue: {}", e).as_str());
+                    break;
+                }
+            }
+            let body = if allowed && !refused { request.read_body(&mut reader, config.max_body_size) } else { Ok(()) };
+            if let Err(e) = body {
+                // an oversized or malformed body is never read to its end, so the connection
+                // cannot be reused
+                if let Some(status) = e.status() {
+                    Logger::warn(format!("Rejected request body: {}", e).as_str());
+                    self.reject_request(request, &mut stream, status);
+                }
+                break;
+            }
+
+            if let Some(method) = request.method_override().filter(|_| overridable) {
+                Logger::debug(format!("POST {} overridden as {}", request.path, method.as_str()).as_str());
+                request.method = method;
+            }
+
+            served += 1;
+            let keep_alive = config.keep_alive_enabled()
+                && served < config.max_requests
+                && request.keep_alive()
+                && !Signal::shutdown_requested()
+                // a refused method may leave its body unread on the socket
+                && allowed
+                && !refused;
+
+            active.set_phase(Phase::Writing);
+            let completed = self.handle_isolated(request, &mut stream, keep_alive);
+            active.set_phase(Phase::Idle);
+            Logger::set_request_id(None);
+
+            if !keep_alive || !completed {
+                break;
+            }
+        }
+        Logger::set_request_id(None);
+    }
+
+    // nothing buffered and nothing arriving before the timeout: the client is idle or gone.
+    // Waited for in slices so that idle connections are closed as soon as the server drains
+    fn wait_for_request(reader: &mut BufReader<Connection>, stream: &Connection, timeout: Duration) -> bool {
+        let started = Instant::now();
+        if stream.set_read_timeout(Some(timeout.min(Self::DRAIN_INTERVAL))).is_err() {
+            return false;
+        }
+        loop {
+            match reader.fill_buf() {
+                Ok(buffer) if !buffer.is_empty() => break,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+                    && !Signal::shutdown_requested()
+                    && started.elapsed() < timeout => {}
+                _ => return false,
+            }
+        }
+        // a request on its way gets the whole timeout again
+        stream.set_read_timeout(Some(timeout)).is_ok()
+    }
+
+    pub fn handle_request(&self, stream: TcpStream) {
+        if let Some(mut request) = Request::from_stream(&stream) {
+            request.set_peer(stream.peer_addr().ok());
+            self.handle_isolated(request, &mut Connection::Tcp(stream), false);
         } else {
             Logger::warn("Failed to read request.")
         }
     }
 
-    pub fn handle_response(&self, request: Request, mut stream: &mut TcpStream) {
+    // a panic in a handler answers 500 instead of taking the worker down with a hung client,
+    // false tells the caller to close the connection since the stream may hold half a response
+    pub fn handle_isolated(&self, request: Request, stream: &mut Connection, keep_alive: bool) -> bool {
+        let fallback = request.clone();
+        let handled = panic::catch_unwind(AssertUnwindSafe(|| self.handle_response(request, stream, keep_alive)));
+        let Err(payload) = handled else {
+            return true;
+        };
+
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Logger::error(format!("Panic while handling {} {}: {}", fallback.method.as_str(), fallback.path, message).as_str());
+        self.reject_request(fallback, stream, HttpStatus::InternalServerError);
+        false
+    }
+
+    pub fn handle_response(&self, request: Request, stream: &mut Connection, keep_alive: bool) {
+        let config = self.config();
+        if config.forward_proxy.enabled && request.authority.is_some() && request.method != HttpMethod::CONNECT {
+            self.serve_forward(&config, request, stream, keep_alive);
+            return;
+        }
+        // the site and location the request is for, resolved before anything else looks at
+        // the config
+        let config = config.for_host(&request.domain).unwrap_or(config);
+        let config = config.for_path(&request.path).unwrap_or(config);
+        if config.watch && request.path == LiveReload::EVENTS_PATH {
+            // only returns once the browser has gone away
+            let _ = LiveReload::stream_events(stream);
+            return;
+        }
+
         if let Some(mut response) = Response::new(request, self.templates.to_owned()) {
-            response.serve(&self.config.root_dir);
+            response.listing = config.listing;
+            response.listing_page_size = config.listing_page_size;
+            response.writable = config.writable;
+            response.etag = config.etag;
+            response.charset = config.charset;
+            response.ignore = config.ignore.clone();
+            response.locale = config.locale.clone();
+            for (language, messages) in &config.messages {
+                response.templates.add_messages(language, messages.clone());
+            }
+            Self::fire_hook(&config, HookEvent::Request, &response, &[]);
+            if Self::reject_method(&config, &mut response) {
+                self.send_response(&mut response, stream, keep_alive);
+                return;
+            }
+            if let Some(status) = self.plugins_on_request(&mut response.request) {
+                response.serve_error_response(status);
+            } else if response.request.path == "*" {
+                // the Allow header method_handle adds is the answer
+                response.serve_status(HttpStatus::Ok);
+                response.set_header("Accept-Ranges", "bytes");
+            } else if let Some(authorization) = Self::acme_challenge(&config, &response.request) {
+                response.serve_body("text/plain", authorization.into_bytes());
+            } else if response.request.path == Self::HEALTH_PATH {
+                Self::serve_health(&config, &mut response);
+            } else if config.maintenance.active(&config.root_dir) {
+                Self::serve_maintenance(&config, &mut response);
+            } else if Self::needs_https_redirect(&config, &response.request) {
+                match response.request.https_url(config.https_port) {
+                    Some(location) => response.serve_redirect(HttpStatus::MovedPermanently, &location),
+                    None => response.serve_error_response(HttpStatus::BadRequest),
+                }
+            } else if let Some((status, location)) = Self::redirect(&config, &response.request) {
+                response.serve_redirect(status, &location);
+            } else if config.hotlink.protects(&response.request.path) && !config.hotlink.allows(&response.request) {
+                Logger::debug(
+                    format!("Refused hotlink to {} from {}", response.request.path, response.request.header("Referer").unwrap_or("-"))
+                        .as_str(),
+                );
+                match &config.hotlink.redirect {
+                    Some(location) => response.serve_redirect(HttpStatus::Found, location),
+                    None => response.serve_error_response(HttpStatus::Forbidden),
+                }
+            } else if let Err(e) = Self::check_signature(&config, &response.request) {
+                Logger::debug(format!("Rejected {}: {}", response.request.path, e).as_str());
+                response.serve_error_response(e.status());
+            } else if let Err(e) = Self::authorize(&config, &response.request) {
+                Logger::debug(format!("Rejected {}: {}", response.request.path, e).as_str());
+                response.serve_error_response(e.status());
+                response.set_header("WWW-Authenticate", &e.challenge());
+            } else if config.status_page && response.request.path == Self::STATUS_PATH {
+                self.serve_status_page(&mut response);
+            } else if config.proxy.enabled() {
+                match config.proxy.forward(&response.request) {
+                    Ok(output) => response.serve_proxied(output),
+                    Err(status) => response.serve_error_response(status),
+                }
+            } else if config.tus.handles(&response.request.path) {
+                if let Some(file) = config.tus.serve(&config.root_dir, &mut response) {
+                    let size = fs::metadata(&file).map_or(0, |metadata| metadata.len());
+                    let file = Utils::json_string(&file.to_string_lossy());
+                    Self::fire_hook(&config, HookEvent::Upload, &response, &[("file", file), ("size", size.to_string())]);
+                }
+            } else if Self::is_file_change(&config, &response.request) && !Csrf::verify(&response.request) {
+                Logger::debug(format!("Rejected {}: missing or wrong CSRF token", response.request.path).as_str());
+                response.serve_error_response(HttpStatus::Forbidden);
+            } else if response.request.method == HttpMethod::DELETE {
+                Self::serve_delete(&config, &mut response);
+            } else if response.request.method == HttpMethod::MOVE {
+                Self::serve_move(&config, &mut response);
+            } else if config.writable && response.request.method == HttpMethod::POST && response.request.form_field("folder").is_some() {
+                Self::serve_new_folder(&config, &mut response);
+            } else if config.writable && response.request.method == HttpMethod::POST {
+                for file in Self::serve_upload(&config, &mut response) {
+                    let size = fs::metadata(&file).map_or(0, |metadata| metadata.len());
+                    let file = Utils::json_string(&file.to_string_lossy());
+                    Self::fire_hook(&config, HookEvent::Upload, &response, &[("file", file), ("size", size.to_string())]);
+                }
+            } else if config.userdir.enabled && UserDir::split(&response.request.path).is_some() {
+                Self::serve_userdir(&config, &mut response);
+            } else {
+                self.serve_files(&config, &mut response);
+            }
+            for (name, value) in &config.headers {
+                response.set_header(name, value);
+            }
+            if config.hotlink.protects(&response.request.path) {
+                response.add_vary("Referer");
+            }
+            if response._is_generated || config.inject_files {
+                if let Some(snippet) = &config.inject_head {
+                    response.inject_before("</head>", snippet);
+                }
+                if let Some(snippet) = &config.inject_body {
+                    response.inject_before("</body>", snippet);
+                }
+            }
+            if config.watch {
+                response.inject_html(LiveReload::SCRIPT);
+            }
+            match ContentEncoding::negotiate(response.request.header("Accept-Encoding")) {
+                Some(ContentEncoding::Gzip) => {
+                    response.compress(&config.compression);
+                }
+                Some(ContentEncoding::Identity) => {}
+                // identity;q=0 without any coding we can produce
+                None if response.status_code == HttpStatus::Ok => response.serve_error_response(HttpStatus::NotAcceptable),
+                None => {}
+            }
+            response.not_modified();
             self.method_handle(&mut response);
-            self.server_transformation(&mut response);
+            for plugin in &self.plugins {
+                plugin.on_response(&mut ResponseView::new(&mut response));
+            }
+            self.send_response(&mut response, stream, keep_alive);
+        } else {
+            Logger::warn("Failed to send response.")
+        }
+    }
 
-            let result = response.stream(stream.deref_mut());
-            match result {
-                Ok(_response) => { Self::log_response(&response) },
-                Err(e) => {
-                    Logger::error(e.to_string().as_str())
-                },
+    // an absolute-form request in --forward-proxy mode, none of the site settings apply
+    fn serve_forward(&self, config: &Config, request: Request, stream: &mut Connection, keep_alive: bool) {
+        let Some(mut response) = Response::new(request, self.templates.to_owned()) else {
+            Logger::warn("Failed to send response.");
+            return;
+        };
+        let authority = response.request.authority.clone().unwrap_or_default();
+        if !config.forward_proxy.allows(&authority) {
+            let client = response.request.client_addr();
+            Logger::warn(format!("Refused to forward to {} (client {})", authority, client.as_deref().unwrap_or("-")).as_str());
+            response.serve_error_response(HttpStatus::Forbidden);
+        } else {
+            match ForwardProxy::forward(&authority, &response.request) {
+                Ok(output) => response.serve_proxied(output),
+                Err(status) => response.serve_error_response(status),
             }
+        }
+        self.send_response(&mut response, stream, keep_alive);
+    }
+
+    // CONNECT in --forward-proxy mode, the bytes go both ways untouched until either side is done
+    fn serve_tunnel(&self, config: &Config, request: Request, stream: &mut Connection, buffered: &[u8]) {
+        let authority = request.authority.clone().unwrap_or_default();
+        let client = request.client_addr().unwrap_or_else(|| "-".to_string());
+        if !config.forward_proxy.allows(&authority) {
+            Logger::warn(format!("Refused tunnel to {} (client {})", authority, client).as_str());
+            self.reject_request(request, stream, HttpStatus::Forbidden);
+            return;
+        }
+        let destination = match ForwardProxy::open(&authority) {
+            Ok(destination) => destination,
+            Err(e) => {
+                Logger::warn(format!("Cannot open tunnel to {}: {}", authority, e).as_str());
+                self.reject_request(request, stream, HttpStatus::BadGateway);
+                return;
+            }
+        };
+        if stream.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n").is_err() {
+            return;
+        }
+        Logger::info(format!("Tunnel to {} opened (client {})", authority, client).as_str());
+        let (sent, received) = ForwardProxy::tunnel(stream, destination, buffered);
+        Logger::info(
+            format!(
+                "Tunnel to {} closed, {} sent and {} received (client {})",
+                authority,
+                Utils::human_size(sent),
+                Utils::human_size(received),
+                client
+            )
+            .as_str(),
+        );
+    }
+
+    // from the embedded bundle when there is one, otherwise from the root directory, unless
+    // the extension has a handler of its own
+    fn serve_files(&self, config: &Config, response: &mut Response) {
+        match config.handlers.for_path(&response.request.path) {
+            Handler::Static => {}
+            Handler::Deny => {
+                response.serve_error_response(HttpStatus::Forbidden);
+                return;
+            }
+            handler => {
+                if let Some(script) = Self::script(config, response) {
+                    let output = match handler {
+                        Handler::FastCgi(address) => FastCgi::run(address, &script, &response.request, &config.root_dir),
+                        _ => Cgi::run(&script, &response.request, &config.root_dir),
+                    };
+                    match output {
+                        Ok(output) => response.serve_cgi(output),
+                        Err(status) => response.serve_error_response(status),
+                    }
+                    return;
+                }
+            }
+        }
+        // the embedding program sets the bundle on the global config only
+        #[cfg(feature = "embed")]
+        if let Some(bundle) = self.config().bundle {
+            response.serve_bundle(bundle);
+            return;
+        }
+        if config.checksums && response.serve_checksum(&config.root_dir) {
+            return;
+        }
+        response.serve_localized(&config.root_dir, config.default_language.as_deref());
+        if response.status_code == HttpStatus::NotFound {
+            if let Some((content_type, body)) = Self::well_known_file(config, &response.request.path) {
+                response.serve_body(content_type, body);
+                return;
+            }
+        }
+        if let Some(fallback) = &config.spa_fallback {
+            if Self::wants_spa_fallback(response) {
+                let path = std::mem::replace(&mut response.request.path, fallback.clone());
+                response.serve_localized(&config.root_dir, config.default_language.as_deref());
+                response.request.path = path;
+            }
+        }
+    }
+
+    // /~user/... from the user's own directory: static files only, never scripts or the file
+    // manager, and the user's .katanaignore rather than the site's
+    fn serve_userdir(config: &Config, response: &mut Response) {
+        let path = response.request.path.clone();
+        let Some((user, rest)) = UserDir::split(&path) else {
+            return;
+        };
+        if rest.is_empty() {
+            let mut location = Utils::encode_url_path(&format!("{}/", path));
+            if let Some((_, query)) = response.request.target.split_once('?') {
+                location = format!("{}?{}", location, query);
+            }
+            response.serve_redirect(HttpStatus::MovedPermanently, &location);
+            return;
+        }
+        if rest.split('/').any(|segment| segment.starts_with('.')) {
+            response.serve_error_response(HttpStatus::Forbidden);
+            return;
+        }
+        let Some(root) = config.userdir.root(user) else {
+            response.serve_error_response(HttpStatus::NotFound);
+            return;
+        };
+        response.request.path = rest.to_string();
+        response.mount = format!("/~{}", user);
+        response.writable = false;
+        response.ignore = Ignore::load(&root, &[]);
+        response.serve_localized(&root, config.default_language.as_deref());
+        response.request.path = path;
+    }
+
+    // the script a request names, under the root and neither hidden nor ignored; None leaves
+    // the request to the static files, which answer the 404
+    fn script(config: &Config, response: &Response) -> Option<PathBuf> {
+        let path = Utils::resolve_under(&config.root_dir, &response.request.path).ok()?;
+        let hidden = response.request.path.split('/').any(|segment| segment.starts_with('.'));
+        (path.is_file() && !hidden && !config.ignore.is_ignored(&response.request.path, false)).then_some(path)
+    }
+
+    // robots.txt, security.txt and the favicon from the settings when the root has none
+    fn well_known_file(config: &Config, path: &str) -> Option<(&'static str, Vec<u8>)> {
+        if path == Robots::PATH {
+            let robots = config.robots.as_ref()?;
+            let body = robots.body();
+            if let (None, Robots::File(file)) = (&body, robots) {
+                Logger::warn(format!("Cannot read the robots.txt body from {}", file.display()).as_str());
+            }
+            body.map(|body| ("text/plain; charset=utf-8", body.into_bytes()))
+        } else if path == SecurityTxt::PATH && config.security_txt.enabled() {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+            Some(("text/plain; charset=utf-8", config.security_txt.body(now).into_bytes()))
+        } else if path == Favicon::PATH {
+            let body = config.favicon.body();
+            if let (None, Favicon::File(file)) = (&body, &config.favicon) {
+                Logger::warn(format!("Cannot read the favicon from {}", file.display()).as_str());
+            }
+            body.map(|body| ("image/x-icon", body))
+        } else {
+            None
+        }
+    }
+
+    // client-side routes look like /users/42, a missing /app.js stays a 404
+    fn wants_spa_fallback(response: &Response) -> bool {
+        let request = &response.request;
+        let last = request.path.rsplit('/').next().unwrap_or_default();
+        response.status_code == HttpStatus::NotFound
+            && matches!(request.method, HttpMethod::GET | HttpMethod::HEAD)
+            && !last.contains('.')
+    }
+
+    // the first plugin that answers wins, the others and every handler are skipped
+    fn plugins_on_request(&self, request: &mut Request) -> Option<HttpStatus> {
+        for plugin in &self.plugins {
+            if let PluginAction::Respond(status) = plugin.on_request(&mut RequestView::new(request)) {
+                Logger::debug(format!("Plugin {} answered {} with {}", plugin.name(), request.path, status.to_code()).as_str());
+                return Some(status);
+            }
+        }
+        None
+    }
+
+    // challenges come from the CA over plain HTTP, so they are answered before anything else
+    fn acme_challenge(config: &Config, request: &Request) -> Option<String> {
+        let dir = config.acme_dir.as_ref()?;
+        let token = Acme::token(&request.path)?;
+        if !Acme::serves_host(&config.acme_domains, request.header("Host")) {
+            return None;
+        }
+        Acme::key_authorization(dir, token)
+    }
+
+    // answered even during maintenance, a node in maintenance reports it is out of rotation
+    fn serve_health(config: &Config, response: &mut Response) {
+        if config.maintenance.active(&config.root_dir) {
+            response.serve_body("application/json", b"{\"status\":\"maintenance\"}".to_vec());
+            response.status_code = HttpStatus::ServiceUnavailable;
+            response.set_header("Retry-After", &config.maintenance.retry_after.to_string());
+        } else {
+            response.serve_body("application/json", b"{\"status\":\"ok\"}".to_vec());
+        }
+        response.set_header("Cache-Control", "no-store");
+    }
+
+    fn redirect(config: &Config, request: &Request) -> Option<(HttpStatus, String)> {
+        if config.redirects.is_empty() {
+            return None;
+        }
+        let query = request.target.split_once('?').map(|(_, query)| query);
+        config.redirects.resolve(&request.path, query)
+    }
+
+    fn serve_maintenance(config: &Config, response: &mut Response) {
+        match config.maintenance.page() {
+            Some(page) => {
+                response.serve_body("text/html", page);
+                response.status_code = HttpStatus::ServiceUnavailable;
+            }
+            None => response.serve_error_response(HttpStatus::ServiceUnavailable),
+        }
+        response.set_header("Retry-After", &config.maintenance.retry_after.to_string());
+    }
+
+    // --status-page: the counters the admin API reports, as a page that refreshes itself
+    fn serve_status_page(&self, response: &mut Response) {
+        let rows = |rows: Vec<String>, empty: &str| {
+            if rows.is_empty() {
+                format!("<tr><td class=\"empty\">{}</td></tr>", empty)
+            } else {
+                rows.concat()
+            }
+        };
+        let statuses = Stats::statuses()
+            .into_iter()
+            .map(|(status, count)| format!("<tr><td>{}</td><td class=\"count\">{}</td></tr>", status, count))
+            .collect();
+        let top_paths = Stats::top_paths(10)
+            .into_iter()
+            .map(|(path, count)| format!("<tr><td>{}</td><td class=\"count\">{}</td></tr>", Utils::escape_html(&path), count))
+            .collect();
+        let recent_errors = Stats::recent_errors()
+            .into_iter()
+            .map(|error| {
+                format!(
+                    "<tr><td>{}</td><td>{}</td><td>{} {}</td></tr>",
+                    Utils::escape_html(&error.time),
+                    error.status,
+                    error.method,
+                    Utils::escape_html(&error.path)
+                )
+            })
+            .collect();
+        let config = self.config();
+        let upstreams = config
+            .proxies()
+            .into_iter()
+            .flat_map(|proxy| proxy.upstreams.iter())
+            .map(|upstream| {
+                format!(
+                    "<tr><td>{}</td><td>{}</td><td class=\"count\">{}</td></tr>",
+                    Utils::escape_html(&upstream.address),
+                    upstream.state(),
+                    upstream.active()
+                )
+            })
+            .collect();
+        let uptime = Stats::uptime().as_secs();
+
+        let mut params = HashMap::new();
+        params.insert("version".to_string(), Self::SERVER_VERSION.to_string());
+        params.insert(
+            "uptime".to_string(),
+            format!("{}d {}h {}m {}s", uptime / 86400, uptime / 3600 % 24, uptime / 60 % 60, uptime % 60),
+        );
+        params.insert("requests".to_string(), Stats::requests().to_string());
+        params.insert("active_connections".to_string(), Stats::active_connections().to_string());
+        params.insert("accepted_connections".to_string(), Stats::accepted_connections().to_string());
+        params.insert("reading".to_string(), Stats::reading().to_string());
+        params.insert("writing".to_string(), Stats::writing().to_string());
+        params.insert("bytes_received".to_string(), Utils::human_size(Stats::bytes_received()));
+        params.insert("bytes_sent".to_string(), Utils::human_size(Stats::bytes_sent()));
+        let pool = BufferPool::stats();
+        params.insert("buffers_in_use".to_string(), pool.in_use.to_string());
+        params.insert("buffers_idle".to_string(), pool.idle.to_string());
+        params.insert("statuses".to_string(), rows(statuses, "No requests yet"));
+        params.insert("top_paths".to_string(), rows(top_paths, "No requests yet"));
+        params.insert("recent_errors".to_string(), rows(recent_errors, "No errors"));
+        params.insert("upstreams".to_string(), rows(upstreams, "No proxy upstreams"));
+
+        let page = self.templates.render(TemplatesPage::STATUS, params);
+        response.serve_body("text/html", page.into_bytes());
+        response._is_generated = true;
+        response.set_header("Cache-Control", "no-store");
+    }
+
+    // what --writable lets clients do to the files under the root
+    fn is_file_change(config: &Config, request: &Request) -> bool {
+        config.writable && matches!(request.method, HttpMethod::POST | HttpMethod::MOVE | HttpMethod::DELETE)
+    }
+
+    // only reached in --writable mode, every removal is logged with the client that asked
+    fn serve_delete(config: &Config, response: &mut Response) {
+        let request = &response.request;
+        let removed = Utils::resolve_under(&config.root_dir, &request.path).and_then(|path| {
+            if !Self::preconditions_hold(config, request, &path) {
+                return Err(HttpStatus::PreconditionFailed);
+            }
+            let is_dir = path.symlink_metadata().is_ok_and(|metadata| metadata.is_dir());
+            let result = if is_dir { fs::remove_dir(&path) } else { fs::remove_file(&path) };
+            result.map_err(|e| match e.kind() {
+                ErrorKind::NotFound => HttpStatus::NotFound,
+                // a directory that is not empty or a file we may not touch
+                _ => {
+                    Logger::warn(format!("Failed to delete {}: {}", path.display(), e).as_str());
+                    HttpStatus::Forbidden
+                }
+            })
+        });
+        match removed {
+            Ok(()) => {
+                Logger::info(
+                    format!("Deleted {} (client {})", request.path, request.client_addr().as_deref().unwrap_or("-")).as_str(),
+                );
+                response.serve_status(HttpStatus::NoContent);
+            }
+            Err(status) => response.serve_error_response(status),
+        }
+    }
+
+    // only reached in --writable mode: the files of a form posted to a directory are written
+    // into it, next to what is there rather than over it, and the browser is sent back to the
+    // listing; a script asking for JSON gets the names instead
+    fn serve_upload(config: &Config, response: &mut Response) -> Vec<PathBuf> {
+        let request = &response.request;
+        let saved: Vec<PathBuf> = match Self::save_upload(config, request) {
+            Ok(saved) => saved,
+            Err(status) => {
+                response.serve_error_response(status);
+                return Vec::new();
+            }
+        };
+
+        let client = request.client_addr().unwrap_or_else(|| "-".to_string());
+        let mut hrefs = Vec::new();
+        for file in &saved {
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            let href = Utils::encode_url_path(&format!("{}/{}", request.path.trim_end_matches('/'), name));
+            Logger::info(format!("Uploaded {} (client {})", href, client).as_str());
+            hrefs.push(Utils::json_string(&href));
+        }
+        let json = format!("{{\"files\":[{}]}}", hrefs.join(","));
+        Self::serve_changed(response, json);
+        saved
+    }
+
+    // a form from the listing leads back to it, a script gets a 201 with what was made
+    fn serve_changed(response: &mut Response, json: String) {
+        let request = &response.request;
+        if request.header("Accept").is_some_and(|accept| accept.contains("application/json")) {
+            response.serve_body("application/json", json.into_bytes());
+            response.status_code = HttpStatus::Created;
+        } else {
+            let location = Utils::encode_url_path(&format!("{}/", request.path.trim_end_matches('/')));
+            response.serve_redirect(HttpStatus::SeeOther, &location);
+        }
+    }
+
+    // only reached in --writable mode: a form posted to a directory with a folder field
+    // creates that folder in it
+    fn serve_new_folder(config: &Config, response: &mut Response) {
+        let request = &response.request;
+        let created = Self::upload_dir(config, &request.path).and_then(|dir| {
+            let name = request.form_field("folder").unwrap_or_default();
+            let name = Self::writable_name(config, &request.path, &name, true)?;
+            fs::create_dir(dir.join(name)).map_err(|e| match e.kind() {
+                ErrorKind::AlreadyExists => HttpStatus::Conflict,
+                _ => {
+                    Logger::warn(format!("Failed to create {}: {}", dir.join(name).display(), e).as_str());
+                    HttpStatus::Forbidden
+                }
+            })?;
+            Ok(Utils::encode_url_path(&format!("{}/{}/", request.path.trim_end_matches('/'), name)))
+        });
+        match created {
+            Ok(href) => {
+                let client = request.client_addr().unwrap_or_else(|| "-".to_string());
+                Logger::info(format!("Created {} (client {})", href, client).as_str());
+                Self::serve_changed(response, format!("{{\"folder\":{}}}", Utils::json_string(&href)));
+            }
+            Err(status) => response.serve_error_response(status),
+        }
+    }
+
+    // only reached in --writable mode: renames or moves a file or directory to the path of
+    // the Destination header (RFC 4918 9.9). Nothing is ever replaced, a destination that
+    // exists is a 412 as with Overwrite: F, and one in a missing directory a 409
+    fn serve_move(config: &Config, response: &mut Response) {
+        let request = &response.request;
+        let moved = Utils::resolve_under(&config.root_dir, &request.path).and_then(|source| {
+            let is_dir = source.symlink_metadata().is_ok_and(|metadata| metadata.is_dir());
+            let (href, destination) = Self::move_destination(config, request, is_dir)?;
+            if destination.symlink_metadata().is_ok() {
+                return Err(HttpStatus::PreconditionFailed);
+            }
+            if destination.starts_with(&source) {
+                return Err(HttpStatus::Conflict);
+            }
+            fs::rename(&source, &destination).map_err(|e| {
+                Logger::warn(format!("Failed to move {} to {}: {}", source.display(), destination.display(), e).as_str());
+                HttpStatus::Forbidden
+            })?;
+            Ok(href)
+        });
+        match moved {
+            Ok(href) => {
+                Logger::info(
+                    format!("Moved {} to {} (client {})", request.path, href, request.client_addr().as_deref().unwrap_or("-"))
+                        .as_str(),
+                );
+                let location = Utils::encode_url_path(&href);
+                response.serve_status(HttpStatus::Created);
+                response.set_header("Location", &location);
+            }
+            Err(status) => response.serve_error_response(status),
+        }
+    }
+
+    // the Destination header, an absolute URL or path, as its decoded path and the file it
+    // names in a directory that exists
+    fn move_destination(config: &Config, request: &Request, is_dir: bool) -> Result<(String, PathBuf), HttpStatus> {
+        let destination = request.header("Destination").ok_or(HttpStatus::BadRequest)?.trim();
+        let path = match destination.split_once("://") {
+            Some((_, rest)) => rest.find('/').map_or("/", |start| &rest[start..]),
+            None => destination,
+        };
+        let path = Request::decode_url(path.split(['?', '#']).next().unwrap_or_default());
+        let path = path.trim_end_matches('/');
+        let (parent, name) = path.rsplit_once('/').ok_or(HttpStatus::BadRequest)?;
+        let dir = Self::upload_dir(config, parent).map_err(|status| match status {
+            HttpStatus::NotFound => HttpStatus::Conflict,
+            status => status,
+        })?;
+        let name = Self::writable_name(config, parent, name, is_dir)?;
+        Ok((format!("{}{}", path, if is_dir { "/" } else { "" }), dir.join(name)))
+    }
+
+    // a name a client may give to something in dir: no path, nothing hidden or ignored, and
+    // no extension that would have it run as a script
+    fn writable_name<'a>(config: &Config, dir: &str, name: &'a str, is_dir: bool) -> Result<&'a str, HttpStatus> {
+        Multipart::safe_name(name)
+            .filter(|safe| *safe == name)
+            .filter(|name| !config.ignore.is_ignored(&format!("{}/{}", dir.trim_end_matches('/'), name), is_dir))
+            .filter(|name| *config.handlers.for_path(name) == Handler::Static)
+            .ok_or(HttpStatus::Forbidden)
+    }
+
+    // every name is checked before anything is written, a script would run once served
+    fn save_upload(config: &Config, request: &Request) -> Result<Vec<PathBuf>, HttpStatus> {
+        let boundary = request.header("Content-Type").and_then(Multipart::boundary).ok_or(HttpStatus::UnsupportedMediaType)?;
+        let dir = Self::upload_dir(config, &request.path)?;
+        let parts = Multipart::parse(&request.body, &boundary).map_err(|e| {
+            Logger::debug(format!("Rejected upload to {}: {}", request.path, e).as_str());
+            HttpStatus::BadRequest
+        })?;
+        // an empty file input is still sent, with no file name
+        let files: Vec<_> = parts.iter().filter(|part| part.filename.as_deref().is_some_and(|name| !name.is_empty())).collect();
+        if files.is_empty() {
+            return Err(HttpStatus::BadRequest);
+        }
+        let mut names = Vec::new();
+        for part in &files {
+            let name = part.filename.as_deref().and_then(Multipart::safe_name).ok_or(HttpStatus::Forbidden)?;
+            names.push(Self::writable_name(config, &request.path, name, false)?);
+        }
+        names.iter().zip(&files).map(|(name, part)| Self::write_upload(&dir, name, &part.data)).collect()
+    }
+
+    // the directory a form was posted to, the root included, which resolve_under leaves out
+    fn upload_dir(config: &Config, path: &str) -> Result<PathBuf, HttpStatus> {
+        let dir = match path.trim_matches('/') {
+            "" => config.root_dir.clone(),
+            _ => Utils::resolve_under(&config.root_dir, path)?,
+        };
+        let (Ok(root), Ok(dir)) = (config.root_dir.canonicalize(), dir.canonicalize()) else {
+            return Err(HttpStatus::NotFound);
+        };
+        if !dir.starts_with(&root) || config.ignore.is_ignored(path.trim_end_matches('/'), true) {
+            return Err(HttpStatus::NotFound);
+        }
+        if !dir.is_dir() {
+            return Err(HttpStatus::Conflict);
+        }
+        Ok(dir)
+    }
+
+    // notes.txt that already exists is kept and the upload becomes notes (1).txt
+    fn write_upload(dir: &Path, name: &str, data: &[u8]) -> Result<PathBuf, HttpStatus> {
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+            _ => (name, String::new()),
+        };
+        for copy in 0..Self::UPLOAD_MAX_COPIES {
+            let path = match copy {
+                0 => dir.join(name),
+                _ => dir.join(format!("{} ({}){}", stem, copy, extension)),
+            };
+            let written = match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Ok(mut file) => file.write_all(data),
+                Err(e) => Err(e),
+            };
+            return written.map(|_| path.clone()).map_err(|e| {
+                Logger::warn(format!("Failed to write upload {}: {}", path.display(), e).as_str());
+                HttpStatus::Forbidden
+            });
+        }
+        Err(HttpStatus::Conflict)
+    }
+
+    // If-Match, or If-Unmodified-Since without it, so a client only removes the version of the
+    // file it last saw (RFC 9110 13.2.2); a date that does not parse is ignored
+    fn preconditions_hold(config: &Config, request: &Request, path: &Path) -> bool {
+        let metadata = fs::metadata(path).ok();
+        if let Some(if_match) = request.if_match() {
+            let tag = metadata.as_ref().and_then(|metadata| match config.etag {
+                ETag::Off => None,
+                etag => etag.for_file(path, metadata),
+            });
+            return match tag {
+                Some(tag) => ETag::matches_strong(if_match, &tag),
+                None => if_match.trim() == "*" && metadata.is_some(),
+            };
+        }
+        let since = request.if_unmodified_since();
+        let modified = metadata
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_secs());
+        match (since, modified) {
+            (Some(since), Some(modified)) => modified <= since,
+            _ => true,
+        }
+    }
+
+    // the scheme is https when a trusted proxy terminated TLS in front of us, and challenge
+    // paths stay reachable for CAs that refuse to follow redirects
+    fn needs_https_redirect(config: &Config, request: &Request) -> bool {
+        config.https_redirect && request.scheme != "https" && Acme::token(&request.path).is_none()
+    }
+
+    // a link carrying a signature must be valid even where none is required
+    fn check_signature(config: &Config, request: &Request) -> Result<(), SignatureError> {
+        if !config.signed_urls.protects(&request.path) && !SignedUrls::is_signed(&request.queries) {
+            return Ok(());
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        config.signed_urls.verify(&request.path, &request.queries, now)
+    }
+
+    // the checks of handle_response that only look at the head, for the site and location
+    // the request is for
+    fn refuses_early(config: &Arc<Config>, request: &Request) -> bool {
+        let config = config.for_host(&request.domain).unwrap_or_else(|| config.clone());
+        let config = config.for_path(&request.path).unwrap_or_else(|| config.clone());
+        Self::check_signature(&config, request).is_err() || Self::authorize(&config, request).is_err()
+    }
+
+    // CORS preflights never carry credentials and signed links stand in for them, so both
+    // are let through
+    fn authorize(config: &Config, request: &Request) -> Result<(), JwtError> {
+        let signed = config.signed_urls.secret.is_some() && SignedUrls::is_signed(&request.queries);
+        if !config.jwt.protects(&request.path) || request.method == HttpMethod::OPTIONS || signed {
+            return Ok(());
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        config.jwt.authorize(request.header("Authorization"), now).map(|_| ())
+    }
+
+    pub fn reject_request(&self, request: Request, stream: &mut Connection, status: HttpStatus) {
+        if let Some(mut response) = Response::new(request, self.templates.to_owned()) {
+            response.serve_error_response(status);
+            // a method the server does not know of is told what it can use, like a refused one
+            if status == HttpStatus::NotImplemented {
+                let methods = self.config().methods_for(&response.request.path);
+                response.set_header("Allow", &HttpMethod::comma_separated(&methods));
+            }
+            self.send_response(&mut response, stream, false);
         } else {
             Logger::warn("Failed to send response.")
         }
     }
 
+    pub fn send_response(&self, response: &mut Response, stream: &mut Connection, keep_alive: bool) {
+        self.server_transformation(response);
+        self.connection_transformation(response, keep_alive);
+
+        let mut counter = BodyCounter::new(stream);
+        if let Err(e) = response.stream(&mut counter) {
+            Logger::error(e.to_string().as_str());
+        }
+        // logged even for aborted transfers, the byte count tells how far it got
+        Self::log_response(response, counter.body_bytes);
+        Stats::record(response.status_code.to_code(), response.request.method.as_str(), &response.request.path);
+        let elapsed = response.request.received_at.elapsed();
+        if self.config().slow_request.is_some_and(|threshold| elapsed > threshold) {
+            Self::log_slow_request(response, elapsed);
+        }
+        if response.status_code.to_code() >= 500 {
+            Self::log_server_error(response);
+            let status = response.status_code.to_code().to_string();
+            Self::fire_hook(&self.config(), HookEvent::ServerError, response, &[("status", status)]);
+        }
+        #[cfg(feature = "otel")]
+        Self::trace_response(response);
+    }
+
     pub fn addr(&self) -> String {
-        format!("{}:{}", self.config.host, self.config.port)
+        let config = self.config();
+        Utils::host_port(&config.host, config.port)
     }
 
     pub fn addr_with_protocol(&self) -> String {
         format!("http://{}", self.addr())
     }
 
+    // explicit --listen addresses replace the single host:port pair
+    pub fn listen_addrs(&self) -> Vec<String> {
+        let config = self.config();
+        if config.listen.is_empty() {
+            vec![self.addr()]
+        } else {
+            config.listen.clone()
+        }
+    }
+
     pub fn version() -> String {
         format!("{} {}", Self::SERVER_NAME, Self::SERVER_VERSION)
     }
 
+    pub fn features() -> Vec<&'static str> {
+        let mut features = Vec::new();
+        if cfg!(feature = "otel") {
+            features.push("otel");
+        }
+        if cfg!(feature = "embed") {
+            features.push("embed");
+        }
+        if cfg!(feature = "landlock") {
+            features.push("landlock");
+        }
+        features
+    }
+
+    // e.g. 0.1.0 (commit 2b3f4a9c1d0e, built 2026-10-15T09:30:00Z, features: otel), what
+    // --version prints and X-Katana-Build carries
+    pub fn build_info() -> String {
+        let mut details = Vec::new();
+        if !Self::GIT_COMMIT.is_empty() {
+            details.push(format!("commit {}", Self::GIT_COMMIT));
+        }
+        if let Ok(secs) = Self::BUILD_TIME.parse::<u64>() {
+            details.push(format!("built {}", Utils::datetime_rfc_3339(secs)));
+        }
+        match Self::features() {
+            features if features.is_empty() => details.push("no features".to_string()),
+            features => details.push(format!("features: {}", features.join(", "))),
+        }
+        format!("{} ({})", Self::SERVER_VERSION, details.join(", "))
+    }
+
     pub fn server_transformation(&self, response: &mut Response) {
         // add to headers server name
         response
             .headers
             .push(("Server".to_string(), Self::version()));
+        if self.config().build_header {
+            response.set_header("X-Katana-Build", &Self::build_info());
+        }
+
+        if !response.request.id.is_empty() {
+            let id = response.request.id.clone();
+            response.set_header("X-Request-Id", &id);
+        }
+    }
+
+    pub fn connection_transformation(&self, response: &mut Response, keep_alive: bool) {
+        // @see: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Keep-Alive
+        if keep_alive {
+            response.set_header("Connection", "keep-alive");
+            response.set_header(
+                "Keep-Alive",
+                &format!(
+                    "timeout={}, max={}",
+                    self.config().keep_alive_timeout,
+                    self.config().max_requests
+                ),
+            );
+        } else {
+            response.set_header("Connection", "close");
+        }
     }
 
     pub fn method_handle(&self, response: &mut Response) {
@@ -102,71 +1595,131 @@ impl Server {
         }
 
         if response.request.method == HttpMethod::OPTIONS {
+            let methods = HttpMethod::comma_separated(&self.config().methods_for(&response.request.path));
             // do not return body
             response.body = Vec::new();
+            response._is_compiled = true;
+            response._size = 0;
 
             // headers
             response
                 .headers
                 .push(("Date".to_string(), Utils::datetime_rfc_1123().to_string()));
-            response.headers.push((
-                "Allow".to_string(),
-                HttpMethod::comma_separated(Self::SUPPORTED_HTTP_METHODS),
-            ));
+            response.headers.push(("Allow".to_string(), methods.clone()));
             // @see: https://developer.mozilla.org/en-US/docs/Web/HTTP/CORS
             response
                 .headers
                 .push(("Access-Control-Allow-Origin".to_string(), "*".to_string()));
-            response.headers.push((
-                "Access-Control-Allow-Methods".to_string(),
-                HttpMethod::comma_separated(Self::SUPPORTED_HTTP_METHODS),
-            ));
+            response.headers.push(("Access-Control-Allow-Methods".to_string(), methods));
             // response.headers.push(("Access-Control-Allow-Headers".to_string(), "content-type, accept".to_string()));
         }
+    }
 
-        if response.request.method == HttpMethod::TRACE {
-            // do not return body
-            response.body = Vec::new();
-
-            // We supports TRACE universally (ignoring route existence), so it will always be 200 OK
-            // @see: https://developer.mozilla.org/en-US/docs/Web/HTTP/Methods/TRACE#successful_trace_request
-            response.status_code = HttpStatus::Ok;
+    // checked before any handler, so methods that are off never reach one
+    fn reject_method(config: &Config, response: &mut Response) -> bool {
+        let methods = config.methods_for(&response.request.path);
+        if methods.contains(&response.request.method) {
+            return false;
+        }
+        response.body = Vec::new();
+        response._is_compiled = true;
+        response._size = 0;
+        response.headers.clear();
+        response.set_header("Allow", &HttpMethod::comma_separated(&methods));
+        response.status_code = HttpStatus::MethodNotAllowed;
+        true
+    }
 
-            // flush headers
-            response.headers.clear();
+    fn log_slow_request(response: &Response, elapsed: Duration) {
+        let request = &response.request;
+        Logger::warn(
+            format!(
+                "Slow request: {} {} took {:.3}ms (client {})",
+                request.method.as_str(),
+                request.path,
+                elapsed.as_secs_f64() * 1000.0,
+                request.client_addr().as_deref().unwrap_or("-"),
+            )
+            .as_str(),
+        );
+    }
 
-            // correct type
-            response
-                .headers
-                .push(("Content-Type".to_string(), "message/http".to_string()));
+    // the event as one JSON object on the hook's stdin, extra values are already JSON
+    fn fire_hook(config: &Config, event: HookEvent, response: &Response, extra: &[(&str, String)]) {
+        if !config.hooks.has(event) {
+            return;
+        }
+        let request = &response.request;
+        let mut fields = vec![
+            ("event", Utils::json_string(event.as_str())),
+            ("time", Utils::json_string(&Utils::log_datetime())),
+            ("id", Utils::json_string(&request.id)),
+            ("method", Utils::json_string(request.method.as_str())),
+            ("path", Utils::json_string(&request.path)),
+            ("client", request.client_addr().map_or("null".to_string(), |client| Utils::json_string(&client))),
+        ];
+        fields.extend(extra.iter().cloned());
+        let fields: Vec<String> = fields.iter().map(|(key, value)| format!("{}:{}", Utils::json_string(key), value)).collect();
+        config.hooks.fire(event, &format!("{{{}}}", fields.join(",")));
+    }
 
-            // new body
-            let body = format!("\r\n{}", response.request.http_description());
+    // the access line only says that something failed, this gives the error stream what
+    // is needed to reproduce it
+    fn log_server_error(response: &Response) {
+        let request = &response.request;
+        let headers: Vec<String> = request
+            .headers
+            .iter()
+            .map(|(key, value)| match key.to_lowercase().as_str() {
+                // credentials have no business in a log file
+                "authorization" | "proxy-authorization" | "cookie" => format!("{}: <redacted>", key),
+                _ => format!("{}: {}", key, value),
+            })
+            .collect();
+        Logger::error(
+            format!(
+                "{} {} {} failed with {} (client {}, file {}, headers [{}])",
+                request.method.as_str(),
+                request.path,
+                request.version.as_str(),
+                response.status_code.to_code(),
+                request.client_addr().as_deref().unwrap_or("-"),
+                response._path.display(),
+                headers.join(", "),
+            )
+            .as_str(),
+        );
+    }
 
-            // new body length
-            response
-                .headers
-                .push(("Content-Length".to_string(), body.len().to_string()));
+    #[cfg(feature = "otel")]
+    fn trace_response(response: &Response) {
+        use crate::otel::{AttributeValue, Span, TraceParent, Tracer};
 
-            // set new body
-            response.body = body.into_bytes();
+        if !Tracer::enabled() {
+            return;
         }
-
-        if !Self::SUPPORTED_HTTP_METHODS.contains(&response.request.method) {
-            // do not return body
-            response.body = Vec::new();
-            // headers
-            response.headers.clear();
-            response.headers.push((
-                "Allow".to_string(),
-                HttpMethod::comma_separated(Self::SUPPORTED_HTTP_METHODS),
-            ));
-            // status
-            response.status_code = HttpStatus::MethodNotAllowed;
+        let request = &response.request;
+        let parent = request.header("traceparent").and_then(TraceParent::parse);
+        let mut span = Span::server(request.method.as_str(), parent, request.received_at);
+        let code = response.status_code.to_code();
+        span.error = code >= 500;
+        span.attributes = vec![
+            ("http.request.method", AttributeValue::Str(request.method.as_str().to_string())),
+            ("url.path", AttributeValue::Str(request.path.clone())),
+            ("http.response.status_code", AttributeValue::Int(code as i64)),
+            ("katana.request_id", AttributeValue::Str(request.id.clone())),
+        ];
+        if let Some(ip) = request.client_ip {
+            span.attributes.push(("client.address", AttributeValue::Str(ip.to_string())));
+        }
+        if let Some(peer) = request.peer {
+            span.attributes.push(("network.peer.address", AttributeValue::Str(peer.ip().to_string())));
+            span.attributes.push(("network.peer.port", AttributeValue::Int(peer.port() as i64)));
         }
+        Tracer::record(span);
     }
 
-    pub fn log_response(response: &Response) {
+    pub fn log_response(response: &Response, sent: usize) {
         let status_line = response
             .request
             .to_string()
@@ -174,12 +1727,32 @@ impl Server {
             .next()
             .unwrap()
             .to_string();
+        let client = response.request.client_addr();
+        let duration = response.request.received_at.elapsed().as_secs_f64() * 1000.0;
+        // browsers ask on every visit, a site without a favicon would fill the log with these
+        let noise = response.status_code == HttpStatus::NotFound && response.request.path == Favicon::PATH;
         let log_message = &format!(
-            "\"{}\" {} {}",
+            "{} \"{}\" {} {} {:.3}ms",
+            client.as_deref().unwrap_or("-"),
             status_line,
-            response.status_code.to_code(),
-            response._size,
+            Logger::paint_status(response.status_code.to_code()),
+            sent,
+            duration,
+        );
+        if noise {
+            Logger::debug(log_message);
+            return;
+        }
+        Logger::access(
+            log_message,
+            &[
+                ("client", client.as_deref().map(Utils::json_string).unwrap_or("null".to_string())),
+                ("peer", response.request.peer_addr().as_deref().map(Utils::json_string).unwrap_or("null".to_string())),
+                ("request", Utils::json_string(&status_line)),
+                ("status", response.status_code.to_code().to_string()),
+                ("bytes", sent.to_string()),
+                ("duration_ms", format!("{:.3}", duration)),
+            ],
         );
-        Logger::info(log_message);
     }
 }
@@ -0,0 +1,358 @@
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+
+    pub type Handle = *mut c_void;
+
+    pub const SC_MANAGER_CONNECT: u32 = 0x0001;
+    pub const SC_MANAGER_CREATE_SERVICE: u32 = 0x0002;
+    pub const SERVICE_QUERY_STATUS: u32 = 0x0004;
+    pub const SERVICE_STOP: u32 = 0x0020;
+    pub const DELETE: u32 = 0x0001_0000;
+    pub const SERVICE_WIN32_OWN_PROCESS: u32 = 0x10;
+    pub const SERVICE_AUTO_START: u32 = 2;
+    pub const SERVICE_ERROR_NORMAL: u32 = 1;
+    pub const SERVICE_STOPPED: u32 = 1;
+    pub const SERVICE_STOP_PENDING: u32 = 3;
+    pub const SERVICE_RUNNING: u32 = 4;
+    pub const SERVICE_ACCEPT_STOP: u32 = 0x1;
+    pub const SERVICE_ACCEPT_SHUTDOWN: u32 = 0x4;
+    pub const SERVICE_CONTROL_STOP: u32 = 1;
+    pub const SERVICE_CONTROL_INTERROGATE: u32 = 4;
+    pub const SERVICE_CONTROL_SHUTDOWN: u32 = 5;
+    pub const NO_ERROR: u32 = 0;
+    pub const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+
+    #[repr(C)]
+    pub struct ServiceStatus {
+        pub service_type: u32,
+        pub current_state: u32,
+        pub controls_accepted: u32,
+        pub win32_exit_code: u32,
+        pub service_specific_exit_code: u32,
+        pub check_point: u32,
+        pub wait_hint: u32,
+    }
+
+    #[repr(C)]
+    pub struct ServiceTableEntry {
+        pub name: *const u16,
+        pub main: Option<extern "system" fn(u32, *mut *mut u16)>,
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        pub fn OpenSCManagerW(machine: *const u16, database: *const u16, access: u32) -> Handle;
+        pub fn CreateServiceW(
+            manager: Handle,
+            name: *const u16,
+            display_name: *const u16,
+            access: u32,
+            service_type: u32,
+            start_type: u32,
+            error_control: u32,
+            binary_path: *const u16,
+            load_order_group: *const u16,
+            tag_id: *mut u32,
+            dependencies: *const u16,
+            start_name: *const u16,
+            password: *const u16,
+        ) -> Handle;
+        pub fn OpenServiceW(manager: Handle, name: *const u16, access: u32) -> Handle;
+        pub fn ControlService(service: Handle, control: u32, status: *mut ServiceStatus) -> i32;
+        pub fn DeleteService(service: Handle) -> i32;
+        pub fn CloseServiceHandle(handle: Handle) -> i32;
+        pub fn StartServiceCtrlDispatcherW(table: *const ServiceTableEntry) -> i32;
+        pub fn RegisterServiceCtrlHandlerExW(
+            name: *const u16,
+            handler: extern "system" fn(u32, u32, *mut c_void, *mut c_void) -> u32,
+            context: *mut c_void,
+        ) -> Handle;
+        pub fn SetServiceStatus(status_handle: Handle, status: *const ServiceStatus) -> i32;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServiceAction {
+    Install,
+    Uninstall,
+    Run,
+}
+
+impl ServiceAction {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(action: &str) -> Option<Self> {
+        match action.to_lowercase().as_str() {
+            "install" => Some(Self::Install),
+            "uninstall" => Some(Self::Uninstall),
+            "run" => Some(Self::Run),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Install => "install",
+            Self::Uninstall => "uninstall",
+            Self::Run => "run",
+        }
+    }
+}
+
+// `katana service install [options]` registers a Windows service that starts with the machine
+// and serves with the same options, `katana service uninstall` stops and removes it. The
+// service manager starts `katana service run`, which only works when started by it. Services
+// start in the system directory, so the one install was run from goes along as --service-dir
+#[derive(Debug, Clone)]
+pub struct Service {
+    pub action: Option<ServiceAction>,
+    pub name: String,
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for Service {
+    fn default() -> Self {
+        Self {
+            action: None,
+            name: Self::DEFAULT_NAME.to_string(),
+            dir: None,
+        }
+    }
+}
+
+#[cfg(windows)]
+mod state {
+    use std::sync::atomic::AtomicPtr;
+    use std::sync::{Mutex, OnceLock};
+
+    // what the service manager's thread serves with, and how it reports back
+    pub static SERVE: Mutex<Option<Box<dyn FnOnce() + Send>>> = Mutex::new(None);
+    pub static NAME: OnceLock<Vec<u16>> = OnceLock::new();
+    pub static STATUS: AtomicPtr<std::ffi::c_void> = AtomicPtr::new(std::ptr::null_mut());
+}
+
+impl Service {
+    pub const DEFAULT_NAME: &'static str = "Katana";
+    // how long stopping may take before the service manager gives up, beyond the grace period
+    #[cfg(windows)]
+    const STOP_WAIT_HINT: u32 = 5000; // ms
+
+    // the command line the service manager starts: this executable with `service run`, the
+    // directory install was run from and every other argument given to install
+    pub fn command_line(exe: &Path, dir: &Path, args: &[String]) -> String {
+        let mut line = vec![
+            Self::quote(&exe.to_string_lossy()),
+            "service".to_string(),
+            "run".to_string(),
+            "--service-dir".to_string(),
+            Self::quote(&dir.to_string_lossy()),
+        ];
+        let mut i = 1;
+        while i < args.len() {
+            match args[i].as_str() {
+                "service" | "--service-dir" => i += 1,
+                arg => line.push(Self::quote(arg)),
+            }
+            i += 1;
+        }
+        line.join(" ")
+    }
+
+    // as CommandLineToArgvW splits it: backslashes are only special in front of a quote
+    pub fn quote(arg: &str) -> String {
+        if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+            return arg.to_string();
+        }
+        let mut quoted = String::from("\"");
+        let mut backslashes = 0;
+        for c in arg.chars() {
+            match c {
+                '\\' => backslashes += 1,
+                '"' => {
+                    quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                    backslashes = 0;
+                }
+                _ => {
+                    quoted.push_str(&"\\".repeat(backslashes));
+                    backslashes = 0;
+                }
+            }
+            if c != '\\' {
+                quoted.push(c);
+            }
+        }
+        quoted.push_str(&"\\".repeat(backslashes * 2));
+        quoted.push('"');
+        quoted
+    }
+
+    #[cfg(windows)]
+    pub fn install(&self, args: &[String]) -> Result<(), Error> {
+        let command = Self::command_line(&std::env::current_exe()?, &std::env::current_dir()?, args);
+        let manager = Self::open_manager(sys::SC_MANAGER_CONNECT | sys::SC_MANAGER_CREATE_SERVICE)?;
+        let name = Self::wide(&self.name);
+        let command = Self::wide(&command);
+        let service = unsafe {
+            sys::CreateServiceW(
+                manager,
+                name.as_ptr(),
+                name.as_ptr(),
+                sys::SERVICE_QUERY_STATUS,
+                sys::SERVICE_WIN32_OWN_PROCESS,
+                sys::SERVICE_AUTO_START,
+                sys::SERVICE_ERROR_NORMAL,
+                command.as_ptr(),
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        let result = if service.is_null() { Err(Error::last_os_error()) } else { Ok(()) };
+        unsafe {
+            if !service.is_null() {
+                sys::CloseServiceHandle(service);
+            }
+            sys::CloseServiceHandle(manager);
+        }
+        result
+    }
+
+    // a running service is asked to stop first, it is only removed once it has
+    #[cfg(windows)]
+    pub fn uninstall(&self) -> Result<(), Error> {
+        let manager = Self::open_manager(sys::SC_MANAGER_CONNECT)?;
+        let name = Self::wide(&self.name);
+        let service = unsafe { sys::OpenServiceW(manager, name.as_ptr(), sys::SERVICE_STOP | sys::DELETE) };
+        let result = if service.is_null() {
+            Err(Error::last_os_error())
+        } else {
+            let mut status = Self::status(sys::SERVICE_STOPPED, 0);
+            unsafe {
+                sys::ControlService(service, sys::SERVICE_CONTROL_STOP, &mut status);
+                let deleted = if sys::DeleteService(service) != 0 { Ok(()) } else { Err(Error::last_os_error()) };
+                sys::CloseServiceHandle(service);
+                deleted
+            }
+        };
+        unsafe { sys::CloseServiceHandle(manager) };
+        result
+    }
+
+    // hands the main thread to the service manager, which calls back on a thread of its own
+    // that serves; returns once the service has stopped
+    #[cfg(windows)]
+    pub fn run(&self, serve: impl FnOnce() + Send + 'static) -> Result<(), Error> {
+        *state::SERVE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(serve));
+        let name = state::NAME.get_or_init(|| Self::wide(&self.name));
+        let table = [
+            sys::ServiceTableEntry { name: name.as_ptr(), main: Some(Self::service_main) },
+            sys::ServiceTableEntry { name: std::ptr::null(), main: None },
+        ];
+        if unsafe { sys::StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    // right before the process exits, the service manager would report a crash otherwise
+    pub fn stopped() {
+        #[cfg(windows)]
+        Self::report(sys::SERVICE_STOPPED, 0);
+    }
+
+    #[cfg(not(windows))]
+    pub fn install(&self, _args: &[String]) -> Result<(), Error> {
+        Err(Self::unsupported())
+    }
+
+    #[cfg(not(windows))]
+    pub fn uninstall(&self) -> Result<(), Error> {
+        Err(Self::unsupported())
+    }
+
+    #[cfg(not(windows))]
+    pub fn run(&self, _serve: impl FnOnce() + Send + 'static) -> Result<(), Error> {
+        Err(Self::unsupported())
+    }
+
+    #[cfg(not(windows))]
+    fn unsupported() -> Error {
+        Error::new(std::io::ErrorKind::Unsupported, "services are only available on Windows, use --daemon or systemd elsewhere")
+    }
+
+    #[cfg(windows)]
+    extern "system" fn service_main(_argc: u32, _argv: *mut *mut u16) {
+        let Some(name) = state::NAME.get() else {
+            return;
+        };
+        let handle = unsafe { sys::RegisterServiceCtrlHandlerExW(name.as_ptr(), Self::control, std::ptr::null_mut()) };
+        if handle.is_null() {
+            return;
+        }
+        state::STATUS.store(handle, std::sync::atomic::Ordering::SeqCst);
+        Self::report(sys::SERVICE_RUNNING, 0);
+        let serve = state::SERVE.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(serve) = serve {
+            serve();
+        }
+        Self::report(sys::SERVICE_STOPPED, 0);
+    }
+
+    // stopping goes through the same graceful shutdown as SIGTERM
+    #[cfg(windows)]
+    extern "system" fn control(control: u32, _event: u32, _data: *mut std::ffi::c_void, _context: *mut std::ffi::c_void) -> u32 {
+        match control {
+            sys::SERVICE_CONTROL_STOP | sys::SERVICE_CONTROL_SHUTDOWN => {
+                Self::report(sys::SERVICE_STOP_PENDING, Self::STOP_WAIT_HINT);
+                crate::signal::Signal::request_shutdown();
+                sys::NO_ERROR
+            }
+            sys::SERVICE_CONTROL_INTERROGATE => sys::NO_ERROR,
+            _ => sys::ERROR_CALL_NOT_IMPLEMENTED,
+        }
+    }
+
+    #[cfg(windows)]
+    fn report(state: u32, wait_hint: u32) {
+        let handle = state::STATUS.load(std::sync::atomic::Ordering::SeqCst);
+        if !handle.is_null() {
+            unsafe { sys::SetServiceStatus(handle, &Self::status(state, wait_hint)) };
+        }
+    }
+
+    #[cfg(windows)]
+    fn status(state: u32, wait_hint: u32) -> sys::ServiceStatus {
+        sys::ServiceStatus {
+            service_type: sys::SERVICE_WIN32_OWN_PROCESS,
+            current_state: state,
+            controls_accepted: if state == sys::SERVICE_RUNNING {
+                sys::SERVICE_ACCEPT_STOP | sys::SERVICE_ACCEPT_SHUTDOWN
+            } else {
+                0
+            },
+            win32_exit_code: 0,
+            service_specific_exit_code: 0,
+            check_point: 0,
+            wait_hint,
+        }
+    }
+
+    #[cfg(windows)]
+    fn open_manager(access: u32) -> Result<sys::Handle, Error> {
+        let manager = unsafe { sys::OpenSCManagerW(std::ptr::null(), std::ptr::null(), access) };
+        if manager.is_null() {
+            return Err(Error::last_os_error());
+        }
+        Ok(manager)
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+}
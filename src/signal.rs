@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::c_int;
+
+    pub const SIGHUP: c_int = 1;
+    pub const SIGINT: c_int = 2;
+    pub const SIGTERM: c_int = 15;
+
+    extern "C" {
+        // handlers only flip an atomic flag, which is async-signal-safe
+        pub fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    }
+}
+
+pub struct Signal;
+
+impl Signal {
+    pub fn listen() {
+        #[cfg(unix)]
+        unsafe {
+            sys::signal(sys::SIGHUP, Self::on_reload);
+            sys::signal(sys::SIGINT, Self::on_shutdown);
+            sys::signal(sys::SIGTERM, Self::on_shutdown);
+        }
+    }
+
+    #[cfg(unix)]
+    extern "C" fn on_reload(_signum: std::os::raw::c_int) {
+        RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+    }
+
+    #[cfg(unix)]
+    extern "C" fn on_shutdown(_signum: std::os::raw::c_int) {
+        SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+    }
+
+    pub fn request_reload() {
+        RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+    }
+
+    // returns true once per received SIGHUP
+    pub fn take_reload() -> bool {
+        RELOAD_REQUESTED.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn request_shutdown() {
+        SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+    }
+
+    pub fn shutdown_requested() -> bool {
+        SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,200 @@
+use crate::utils::Utils;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+static ACTIVE_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+static ACCEPTED: AtomicU64 = AtomicU64::new(0);
+static READING: AtomicUsize = AtomicUsize::new(0);
+static WRITING: AtomicUsize = AtomicUsize::new(0);
+static BYTES_RECEIVED: AtomicU64 = AtomicU64::new(0);
+static BYTES_SENT: AtomicU64 = AtomicU64::new(0);
+static REQUESTS: AtomicU64 = AtomicU64::new(0);
+static RESPONSES: Mutex<Responses> = Mutex::new(Responses {
+    statuses: BTreeMap::new(),
+    paths: None,
+    errors: VecDeque::new(),
+});
+
+// process-wide counters, shared by every listener and read by the admin API and the status page
+pub struct Stats;
+
+// held for the life of a connection, dropping it gives the slot back even while unwinding
+pub struct ActiveConnection {
+    phase: Phase,
+}
+
+// what an open connection is busy with: waiting for the next request, reading one in, or
+// handling it and writing the response
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Idle,
+    Reading,
+    Writing,
+}
+
+impl ActiveConnection {
+    pub fn set_phase(&mut self, phase: Phase) {
+        if let Some(counter) = Self::counter(self.phase) {
+            counter.fetch_sub(1, Ordering::Relaxed);
+        }
+        if let Some(counter) = Self::counter(phase) {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+        self.phase = phase;
+    }
+
+    fn counter(phase: Phase) -> Option<&'static AtomicUsize> {
+        match phase {
+            Phase::Idle => None,
+            Phase::Reading => Some(&READING),
+            Phase::Writing => Some(&WRITING),
+        }
+    }
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        self.set_phase(Phase::Idle);
+        ACTIVE_CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecentError {
+    pub time: String,
+    pub status: u16,
+    pub method: String,
+    pub path: String,
+}
+
+struct Responses {
+    statuses: BTreeMap<u16, u64>,
+    // created on first use, HashMap::new is not const
+    paths: Option<HashMap<String, u64>>,
+    errors: VecDeque<RecentError>,
+}
+
+impl Stats {
+    // paths beyond this many distinct ones are not counted, so scanners cannot grow the map
+    pub const MAX_PATHS: usize = 1000;
+    pub const MAX_ERRORS: usize = 20;
+
+    pub fn start() {
+        STARTED.get_or_init(Instant::now);
+    }
+
+    pub fn uptime() -> Duration {
+        STARTED.get().map_or(Duration::ZERO, Instant::elapsed)
+    }
+
+    pub fn connection() -> ActiveConnection {
+        ACCEPTED.fetch_add(1, Ordering::Relaxed);
+        ACTIVE_CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+        ActiveConnection { phase: Phase::Idle }
+    }
+
+    pub fn active_connections() -> usize {
+        ACTIVE_CONNECTIONS.load(Ordering::Relaxed)
+    }
+
+    // since the start, closed ones included
+    pub fn accepted_connections() -> u64 {
+        ACCEPTED.load(Ordering::Relaxed)
+    }
+
+    pub fn reading() -> usize {
+        READING.load(Ordering::Relaxed)
+    }
+
+    pub fn writing() -> usize {
+        WRITING.load(Ordering::Relaxed)
+    }
+
+    // bytes as they cross the socket, heads, bodies and the admin API alike
+    pub fn received(bytes: usize) {
+        BYTES_RECEIVED.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn sent(bytes: usize) {
+        BYTES_SENT.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn bytes_received() -> u64 {
+        BYTES_RECEIVED.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_sent() -> u64 {
+        BYTES_SENT.load(Ordering::Relaxed)
+    }
+
+    // one line for the log, written on reload and shutdown
+    pub fn summary() -> String {
+        format!(
+            "{} connection(s) accepted, {} open ({} reading, {} writing), {} request(s), {} in, {} out",
+            Self::accepted_connections(),
+            Self::active_connections(),
+            Self::reading(),
+            Self::writing(),
+            Self::requests(),
+            Utils::human_size(Self::bytes_received()),
+            Utils::human_size(Self::bytes_sent())
+        )
+    }
+
+    pub fn record(status: u16, method: &str, path: &str) {
+        REQUESTS.fetch_add(1, Ordering::Relaxed);
+        let mut responses = RESPONSES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *responses.statuses.entry(status).or_default() += 1;
+
+        let paths = responses.paths.get_or_insert_with(HashMap::new);
+        if let Some(count) = paths.get_mut(path) {
+            *count += 1;
+        } else if paths.len() < Self::MAX_PATHS {
+            paths.insert(path.to_string(), 1);
+        }
+
+        if status >= 500 {
+            if responses.errors.len() == Self::MAX_ERRORS {
+                responses.errors.pop_front();
+            }
+            responses.errors.push_back(RecentError {
+                time: Utils::log_datetime(),
+                status,
+                method: method.to_string(),
+                path: path.to_string(),
+            });
+        }
+    }
+
+    pub fn requests() -> u64 {
+        REQUESTS.load(Ordering::Relaxed)
+    }
+
+    pub fn statuses() -> Vec<(u16, u64)> {
+        let responses = RESPONSES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        responses.statuses.iter().map(|(status, count)| (*status, *count)).collect()
+    }
+
+    // most requested first, ties by path so the order is stable
+    pub fn top_paths(limit: usize) -> Vec<(String, u64)> {
+        let responses = RESPONSES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut paths: Vec<(String, u64)> = responses
+            .paths
+            .iter()
+            .flatten()
+            .map(|(path, count)| (path.clone(), *count))
+            .collect();
+        paths.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        paths.truncate(limit);
+        paths
+    }
+
+    // newest first
+    pub fn recent_errors() -> Vec<RecentError> {
+        let responses = RESPONSES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        responses.errors.iter().rev().cloned().collect()
+    }
+}
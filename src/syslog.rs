@@ -0,0 +1,212 @@
+use crate::logger::LogLevel;
+use crate::utils::Utils;
+use std::io::{Error, ErrorKind, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::path::Path;
+use std::process;
+
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+
+    pub const EVENTLOG_ERROR_TYPE: u16 = 0x1;
+    pub const EVENTLOG_WARNING_TYPE: u16 = 0x2;
+    pub const EVENTLOG_INFORMATION_TYPE: u16 = 0x4;
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        pub fn RegisterEventSourceW(server: *const u16, source: *const u16) -> *mut c_void;
+        pub fn ReportEventW(
+            event_log: *mut c_void,
+            event_type: u16,
+            category: u16,
+            event_id: u32,
+            user_sid: *mut c_void,
+            num_strings: u16,
+            data_size: u32,
+            strings: *const *const u16,
+            raw_data: *mut c_void,
+        ) -> i32;
+    }
+}
+
+enum Transport {
+    #[cfg(unix)]
+    Local(UnixDatagram),
+    // the Application log, kept as an address so the logger can share it between threads
+    #[cfg(windows)]
+    EventLog(usize),
+    Udp(UdpSocket),
+    Tcp(String, Option<TcpStream>),
+}
+
+pub struct Syslog {
+    transport: Transport,
+    facility: u8,
+}
+
+impl Syslog {
+    pub const DEFAULT_FACILITY: u8 = 3; // daemon
+    pub const EVENT_LOG: &'static str = "eventlog";
+    const APP_NAME: &'static str = "katana";
+    const LOCAL_SOCKETS: &'static [&'static str] = &["/dev/log", "/var/run/syslog", "/var/run/log"];
+
+    // `local` for the machine's syslog socket, `unix:/path` for another one,
+    // `udp://host:port` or `tcp://host:port` for a remote collector (a bare host:port is UDP),
+    // `eventlog` for the Windows Application log
+    pub fn connect(target: &str, facility: u8) -> Result<Self, Error> {
+        let transport = if target == Self::EVENT_LOG {
+            Self::connect_event_log()?
+        } else if target == "local" {
+            let path = Self::LOCAL_SOCKETS
+                .iter()
+                .find(|path| Path::new(path).exists())
+                .ok_or_else(|| Error::new(ErrorKind::NotFound, "no local syslog socket found"))?;
+            Self::connect_local(Path::new(path))?
+        } else if let Some(path) = target.strip_prefix("unix:") {
+            Self::connect_local(Path::new(path))?
+        } else if let Some(addr) = target.strip_prefix("tcp://") {
+            Transport::Tcp(addr.to_string(), Some(TcpStream::connect(addr)?))
+        } else {
+            let addr = target.strip_prefix("udp://").unwrap_or(target);
+            let socket = UdpSocket::bind(if addr.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" })?;
+            socket.connect(addr)?;
+            Transport::Udp(socket)
+        };
+        Ok(Self { transport, facility })
+    }
+
+    #[cfg(unix)]
+    fn connect_local(path: &Path) -> Result<Transport, Error> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(Transport::Local(socket))
+    }
+
+    #[cfg(not(unix))]
+    fn connect_local(_path: &Path) -> Result<Transport, Error> {
+        Err(Error::new(ErrorKind::Unsupported, "local syslog is only available on unix systems"))
+    }
+
+    // events show up with katana as their source; it registers no message file, so Event
+    // Viewer says so before showing the message as is
+    #[cfg(windows)]
+    fn connect_event_log() -> Result<Transport, Error> {
+        let source: Vec<u16> = Self::APP_NAME.encode_utf16().chain(std::iter::once(0)).collect();
+        let handle = unsafe { sys::RegisterEventSourceW(std::ptr::null(), source.as_ptr()) };
+        if handle.is_null() {
+            return Err(Error::last_os_error());
+        }
+        Ok(Transport::EventLog(handle as usize))
+    }
+
+    #[cfg(not(windows))]
+    fn connect_event_log() -> Result<Transport, Error> {
+        Err(Error::new(ErrorKind::Unsupported, "the event log is only available on Windows"))
+    }
+
+    // @see: https://www.rfc-editor.org/rfc/rfc5424#section-6.2.1
+    pub fn facility(name: &str) -> Option<u8> {
+        match name.to_lowercase().as_str() {
+            "kern" => Some(0),
+            "user" => Some(1),
+            "mail" => Some(2),
+            "daemon" => Some(3),
+            "auth" => Some(4),
+            "syslog" => Some(5),
+            "lpr" => Some(6),
+            "news" => Some(7),
+            "uucp" => Some(8),
+            "cron" => Some(9),
+            "authpriv" => Some(10),
+            "ftp" => Some(11),
+            name => match name.strip_prefix("local")?.parse::<u8>() {
+                Ok(n) if n <= 7 => Some(16 + n),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn severity(level: LogLevel) -> u8 {
+        match level {
+            LogLevel::DEBUG => 7,
+            LogLevel::INFO => 6,
+            LogLevel::WARN => 4,
+            LogLevel::ERROR => 3,
+        }
+    }
+
+    pub fn priority(&self, level: LogLevel) -> u8 {
+        self.facility * 8 + Self::severity(level)
+    }
+
+    pub fn send(&mut self, level: LogLevel, message: &str) -> Result<(), Error> {
+        let priority = self.priority(level);
+        match &mut self.transport {
+            // the local daemon stamps time and host itself and expects the traditional format
+            #[cfg(unix)]
+            Transport::Local(socket) => {
+                let line = format!("<{}>{}[{}]: {}", priority, Self::APP_NAME, process::id(), message);
+                socket.send(line.as_bytes()).map(|_| ())
+            }
+            #[cfg(windows)]
+            Transport::EventLog(handle) => {
+                let event_type = match level {
+                    LogLevel::ERROR => sys::EVENTLOG_ERROR_TYPE,
+                    LogLevel::WARN => sys::EVENTLOG_WARNING_TYPE,
+                    _ => sys::EVENTLOG_INFORMATION_TYPE,
+                };
+                let text: Vec<u16> = message.encode_utf16().chain(std::iter::once(0)).collect();
+                let strings = [text.as_ptr()];
+                let reported = unsafe {
+                    sys::ReportEventW(
+                        *handle as *mut std::ffi::c_void,
+                        event_type,
+                        0,
+                        0,
+                        std::ptr::null_mut(),
+                        1,
+                        0,
+                        strings.as_ptr(),
+                        std::ptr::null_mut(),
+                    )
+                };
+                if reported == 0 {
+                    return Err(Error::last_os_error());
+                }
+                Ok(())
+            }
+            Transport::Udp(socket) => socket.send(Self::rfc5424(priority, message).as_bytes()).map(|_| ()),
+            Transport::Tcp(addr, stream) => {
+                // octet counting framing, see https://www.rfc-editor.org/rfc/rfc6587#section-3.4.1
+                let line = Self::rfc5424(priority, message);
+                let frame = format!("{} {}", line.len(), line);
+                if let Some(connected) = stream.as_mut() {
+                    if connected.write_all(frame.as_bytes()).is_ok() {
+                        return Ok(());
+                    }
+                }
+                // the collector may have restarted, reconnect once before giving up
+                *stream = None;
+                let mut reconnected = TcpStream::connect(addr.as_str())?;
+                reconnected.write_all(frame.as_bytes())?;
+                *stream = Some(reconnected);
+                Ok(())
+            }
+        }
+    }
+
+    fn rfc5424(priority: u8, message: &str) -> String {
+        format!(
+            "<{}>1 {} - {} {} - - {}",
+            priority,
+            Utils::datetime_rfc_8601(),
+            Self::APP_NAME,
+            process::id(),
+            message
+        )
+    }
+}
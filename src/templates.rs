@@ -1,10 +1,80 @@
+use crate::language::Language;
+use crate::utils::Utils;
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub enum TemplatesPage {
     BANNER,
     ERROR,
     DIRECTORY,
+    STATUS,
+}
+
+// the texts of the built-in pages by language, pages name them {{t.key}} and {{lang}} is the
+// language they were rendered in. Languages come from templates/messages/<language>.txt,
+// --messages adds others or replaces some of their texts
+pub type Messages = HashMap<String, HashMap<String, String>>;
+
+// what a page is rendered with:
+//
+//   {{name}}                          the value, as given, callers escape what needs it
+//   {{#if name}}...{{else}}...{{/if}}  a value that is not empty, false or an empty list
+//   {{#unless name}}...{{/unless}}
+//   {{#each name}}...{{else}}...{{/each}}  once per item, whose values hide those outside
+//
+// a name without a value is left as it is, so a typo shows up on the page
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    Bool(bool),
+    List(Vec<Context>),
+}
+
+pub type Context = HashMap<String, Value>;
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::Text(text)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(text.to_string())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(flag: bool) -> Self {
+        Value::Bool(flag)
+    }
+}
+
+impl From<Vec<Context>> for Value {
+    fn from(items: Vec<Context>) -> Self {
+        Value::List(items)
+    }
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Text(text) => !text.is_empty(),
+            Value::Bool(flag) => *flag,
+            Value::List(items) => !items.is_empty(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Node<'a> {
+    Text(&'a str),
+    Var(&'a str),
+    If(&'a str, bool, Vec<Node<'a>>, Vec<Node<'a>>),
+    Each(&'a str, Vec<Node<'a>>, Vec<Node<'a>>),
 }
 
 #[derive(Debug, Clone)]
@@ -12,40 +82,252 @@ pub struct Templates {
     pub banner: String,
     pub error: String,
     pub directory: String,
+    pub status: String,
+    // shared, every response gets a copy of the templates
+    pub messages: Arc<Messages>,
 }
 
 impl Templates {
+    pub const DEFAULT_LANGUAGE: &'static str = "en";
+    pub const BUILT_IN_MESSAGES: &'static [(&'static str, &'static str)] = &[
+        ("en", include_str!("../templates/messages/en.txt")),
+        ("fr", include_str!("../templates/messages/fr.txt")),
+        ("de", include_str!("../templates/messages/de.txt")),
+        ("es", include_str!("../templates/messages/es.txt")),
+    ];
+
+    // the files a --templates directory may replace, along with messages/<language>.txt
+    pub const FILES: &'static [&'static str] = &["banner.txt", "error.html", "directory.html", "status.html"];
+
     pub fn load() -> Self {
+        let mut messages = Messages::new();
+        for (language, content) in Self::BUILT_IN_MESSAGES {
+            let catalog = Self::parse_messages(content).expect("Cannot parse built-in messages");
+            messages.insert(language.to_string(), catalog);
+        }
         Templates {
             banner: String::from(include_str!("../templates/banner.txt")),
             error: String::from(include_str!("../templates/error.html")),
             directory: String::from(include_str!("../templates/directory.html")),
+            status: String::from(include_str!("../templates/status.html")),
+            messages: Arc::new(messages),
         }
     }
 
+    // the built-in templates, with those the directory has instead
+    pub fn load_dir(dir: &Path) -> Result<Self, String> {
+        let mut templates = Self::load();
+        for name in Self::FILES {
+            let path = dir.join(name);
+            if !path.is_file() {
+                continue;
+            }
+            let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            match *name {
+                "banner.txt" => templates.banner = content,
+                "error.html" => templates.error = content,
+                "directory.html" => templates.directory = content,
+                _ => templates.status = content,
+            }
+        }
+        if let Ok(entries) = fs::read_dir(dir.join("messages")) {
+            for path in entries.flatten().map(|entry| entry.path()) {
+                let Some(language) = path
+                    .file_name()
+                    .and_then(|name| name.to_str()?.strip_suffix(".txt"))
+                    .filter(|language| Language::is_tag(language))
+                else {
+                    continue;
+                };
+                let messages = fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|content| Self::parse_messages(&content))
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                templates.add_messages(language, messages);
+            }
+        }
+        Ok(templates)
+    }
+
+    // `key = value` lines, # starts a comment and quotes around a value are dropped
+    pub fn parse_messages(content: &str) -> Result<HashMap<String, String>, String> {
+        let mut messages = HashMap::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected `key = value`", number + 1));
+            };
+            let value = value.trim();
+            let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+            messages.insert(key.trim().to_string(), value.to_string());
+        }
+        Ok(messages)
+    }
+
+    // texts for a language, over the ones it already has
+    pub fn add_messages(&mut self, language: &str, messages: HashMap<String, String>) {
+        Arc::make_mut(&mut self.messages)
+            .entry(language.to_lowercase())
+            .or_default()
+            .extend(messages);
+    }
+
+    pub fn languages(&self) -> Vec<String> {
+        let mut languages: Vec<String> = self.messages.keys().cloned().collect();
+        languages.sort();
+        languages
+    }
+
+    // the language of the client a catalog exists for, then the locale, then English
+    pub fn language(&self, accept_language: Option<&str>, locale: Option<&str>) -> String {
+        let languages = self.languages();
+        let fallback = locale
+            .and_then(|locale| Language::select(Some(locale), &languages, Self::DEFAULT_LANGUAGE))
+            .map_or(Self::DEFAULT_LANGUAGE, String::as_str);
+        Language::select(accept_language, &languages, fallback)
+            .map_or(Self::DEFAULT_LANGUAGE, String::as_str)
+            .to_string()
+    }
+
+    // en-GB falls back to en, then to English
+    pub fn message(&self, language: &str, key: &str) -> Option<&str> {
+        let language = language.to_lowercase();
+        let primary = language.split('-').next().unwrap_or_default();
+        [language.as_str(), primary, Self::DEFAULT_LANGUAGE]
+            .iter()
+            .find_map(|language| self.messages.get(*language)?.get(key))
+            .map(String::as_str)
+    }
+
     pub fn from_enum(template_page: TemplatesPage) -> Option<String> {
-        let templates = Self::load();
+        Self::load().get(template_page)
+    }
 
+    pub fn get(&self, template_page: TemplatesPage) -> Option<String> {
         match template_page {
-            TemplatesPage::BANNER => Some(templates.banner),
-            TemplatesPage::ERROR => Some(templates.error),
-            TemplatesPage::DIRECTORY => Some(templates.directory),
+            TemplatesPage::BANNER => Some(self.banner.to_owned()),
+            TemplatesPage::ERROR => Some(self.error.to_owned()),
+            TemplatesPage::DIRECTORY => Some(self.directory.to_owned()),
+            TemplatesPage::STATUS => Some(self.status.to_owned()),
         }
     }
 
     pub fn render(&self, template: TemplatesPage, params: HashMap<String, String>) -> String {
-        let mut content = Self::from_enum(template).expect("Cannot load unregistered template");
+        self.render_in(template, params, Self::DEFAULT_LANGUAGE)
+    }
 
-        for (key, value) in params {
-            let placeholder = "{{".to_string() + &key + "}}";
+    // params with an empty value are left out, their placeholders stay
+    pub fn render_in(&self, template: TemplatesPage, params: HashMap<String, String>, language: &str) -> String {
+        let context: Context = params
+            .into_iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| (key, Value::Text(value)))
+            .collect();
+        self.render_context(template, &context, language)
+    }
 
-            if value.is_empty() {
-                continue;
+    // texts are filled in first and values are never parsed, so a folder named {{t.key}} or
+    // {{#if x}} stays as it is
+    pub fn render_context(&self, template: TemplatesPage, context: &Context, language: &str) -> String {
+        let content = self.localize(&self.get(template).expect("Cannot load unregistered template"), language);
+        let (nodes, _) = Self::parse(&content, None);
+        let mut rendered = String::with_capacity(content.len());
+        Self::render_nodes(&nodes, &mut vec![context], &mut rendered);
+        rendered
+    }
+
+    // nodes up to the end or the {{else}} or {{/...}} closing the block, which is returned
+    fn parse<'a>(content: &'a str, block: Option<&str>) -> (Vec<Node<'a>>, &'a str) {
+        let mut nodes = Vec::new();
+        let mut rest = content;
+        while let Some(start) = rest.find("{{") {
+            let Some(length) = rest[start..].find("}}") else {
+                break;
+            };
+            let tag = rest[start + 2..start + length].trim();
+            let (text, after) = (&rest[..start], &rest[start + length + 2..]);
+            if block.is_some() && (tag == "else" || tag.starts_with('/')) {
+                nodes.push(Node::Text(text));
+                return (nodes, &rest[start..]);
             }
+            let (keyword, name) = tag.split_once(' ').map_or((tag, ""), |(keyword, name)| (keyword, name.trim()));
+            nodes.push(Node::Text(text));
+            rest = match keyword {
+                "#if" | "#unless" | "#each" if !name.is_empty() => {
+                    let (body, after) = Self::parse(after, Some(keyword));
+                    let (otherwise, after) = match after.strip_prefix("{{else}}") {
+                        Some(after) => Self::parse(after, Some(keyword)),
+                        None => (Vec::new(), after),
+                    };
+                    // the closing tag, or the end of a template that forgot it
+                    let after = after.find("}}").map_or("", |end| &after[end + 2..]);
+                    nodes.push(match keyword {
+                        "#each" => Node::Each(name, body, otherwise),
+                        _ => Node::If(name, keyword == "#unless", body, otherwise),
+                    });
+                    after
+                }
+                _ => {
+                    nodes.push(Node::Var(&rest[start..start + length + 2]));
+                    after
+                }
+            };
+        }
+        nodes.push(Node::Text(rest));
+        (nodes, "")
+    }
+
+    fn lookup<'a>(scopes: &[&'a Context], name: &str) -> Option<&'a Value> {
+        scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
 
-            content = content.replace(&placeholder, &value);
+    fn render_nodes(nodes: &[Node], scopes: &mut Vec<&Context>, rendered: &mut String) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => rendered.push_str(text),
+                Node::Var(placeholder) => {
+                    let name = placeholder[2..placeholder.len() - 2].trim();
+                    match Self::lookup(scopes, name) {
+                        Some(Value::Text(text)) => rendered.push_str(text),
+                        Some(Value::Bool(flag)) => rendered.push_str(&flag.to_string()),
+                        _ => rendered.push_str(placeholder),
+                    }
+                }
+                Node::If(name, negated, body, otherwise) => {
+                    let truthy = Self::lookup(scopes, name).is_some_and(Value::is_truthy);
+                    Self::render_nodes(if truthy != *negated { body } else { otherwise }, scopes, rendered);
+                }
+                Node::Each(name, body, otherwise) => match Self::lookup(scopes, name) {
+                    Some(Value::List(items)) if !items.is_empty() => {
+                        for item in items {
+                            scopes.push(item);
+                            Self::render_nodes(body, scopes, rendered);
+                            scopes.pop();
+                        }
+                    }
+                    _ => Self::render_nodes(otherwise, scopes, rendered),
+                },
+            }
         }
+    }
 
-        content
+    fn localize(&self, content: &str, language: &str) -> String {
+        let mut localized = String::with_capacity(content.len());
+        let mut rest = content;
+        while let Some(start) = rest.find("{{t.") {
+            let Some(length) = rest[start..].find("}}") else {
+                break;
+            };
+            localized.push_str(&rest[..start]);
+            // a key nobody translated is shown as is
+            let key = &rest[start + 4..start + length];
+            localized.push_str(&Utils::escape_html(self.message(language, key).unwrap_or(key)));
+            rest = &rest[start + length + 2..];
+        }
+        localized.push_str(rest);
+        localized.replace("{{lang}}", &Utils::escape_html(language))
     }
 }
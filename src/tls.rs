@@ -0,0 +1,260 @@
+use crate::ocsp::Staple;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+// whether connections must present a client certificate signed by the --client-ca bundle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClientAuth {
+    Off,
+    Optional,
+    Required,
+}
+
+impl ClientAuth {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "off" | "none" => Some(ClientAuth::Off),
+            "optional" | "request" => Some(ClientAuth::Optional),
+            "required" | "require" => Some(ClientAuth::Required),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum TlsVersion {
+    Tls12,
+    Tls13,
+}
+
+impl TlsVersion {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().trim_start_matches("tls").trim_start_matches(['v', ' ']) {
+            "1.2" => Some(TlsVersion::Tls12),
+            "1.3" => Some(TlsVersion::Tls13),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TlsVersion::Tls12 => "1.2",
+            TlsVersion::Tls13 => "1.3",
+        }
+    }
+}
+
+// the protocol versions, cipher suites and key exchange groups handshakes may use, in order
+// of preference; the presets are those of Mozilla's server side TLS guidelines. Older
+// versions than TLS 1.2 and suites without forward secrecy are not offered at all
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsPolicy {
+    pub min_version: TlsVersion,
+    pub ciphers: Vec<&'static str>,
+    pub curves: Vec<&'static str>,
+}
+
+impl Default for TlsPolicy {
+    fn default() -> Self {
+        Self::preset(Self::INTERMEDIATE).unwrap()
+    }
+}
+
+impl TlsPolicy {
+    pub const MODERN: &'static str = "modern";
+    pub const INTERMEDIATE: &'static str = "intermediate";
+
+    // IANA names and the version each suite belongs to, the TLS 1.3 ones first
+    pub const CIPHERS: &'static [(&'static str, TlsVersion)] = &[
+        ("TLS_AES_128_GCM_SHA256", TlsVersion::Tls13),
+        ("TLS_AES_256_GCM_SHA384", TlsVersion::Tls13),
+        ("TLS_CHACHA20_POLY1305_SHA256", TlsVersion::Tls13),
+        ("TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256", TlsVersion::Tls12),
+        ("TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256", TlsVersion::Tls12),
+        ("TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384", TlsVersion::Tls12),
+        ("TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384", TlsVersion::Tls12),
+        ("TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256", TlsVersion::Tls12),
+        ("TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256", TlsVersion::Tls12),
+        ("TLS_DHE_RSA_WITH_AES_128_GCM_SHA256", TlsVersion::Tls12),
+        ("TLS_DHE_RSA_WITH_AES_256_GCM_SHA384", TlsVersion::Tls12),
+        ("TLS_DHE_RSA_WITH_CHACHA20_POLY1305_SHA256", TlsVersion::Tls12),
+    ];
+    pub const CURVES: &'static [&'static str] = &["X25519MLKEM768", "X25519", "secp256r1", "secp384r1", "secp521r1"];
+
+    pub fn preset(name: &str) -> Option<Self> {
+        let (min_version, ciphers) = match name.to_lowercase().as_str() {
+            Self::MODERN => (TlsVersion::Tls13, 3),
+            Self::INTERMEDIATE => (TlsVersion::Tls12, Self::CIPHERS.len()),
+            _ => return None,
+        };
+        Some(Self {
+            min_version,
+            ciphers: Self::CIPHERS[..ciphers].iter().map(|(name, _)| *name).collect(),
+            curves: vec!["X25519", "secp256r1", "secp384r1"],
+        })
+    }
+
+    // the known name, matched without regard to case
+    pub fn cipher(name: &str) -> Option<&'static str> {
+        Self::CIPHERS.iter().map(|(known, _)| *known).find(|known| known.eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn curve(name: &str) -> Option<&'static str> {
+        Self::CURVES.iter().copied().find(|known| known.eq_ignore_ascii_case(name.trim()))
+    }
+
+    // the version a suite belongs to
+    pub fn version(cipher: &str) -> Option<TlsVersion> {
+        Self::CIPHERS.iter().find(|(known, _)| *known == cipher).map(|(_, version)| *version)
+    }
+
+    // what would leave a version without a suite or name a suite no handshake can pick
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for cipher in &self.ciphers {
+            if Self::version(cipher).is_some_and(|version| version < self.min_version) {
+                errors.push(format!("cipher {} is for TLS 1.2, which --tls-min-version {} turns off", cipher, self.min_version.as_str()));
+            }
+        }
+        for needed in [TlsVersion::Tls12, TlsVersion::Tls13].into_iter().filter(|needed| *needed >= self.min_version) {
+            if !self.ciphers.iter().any(|cipher| Self::version(cipher) == Some(needed)) {
+                errors.push(format!("no cipher for TLS {} in --tls-ciphers", needed.as_str()));
+            }
+        }
+        errors
+    }
+}
+
+// a certificate chain and its private key, served to clients asking for one of the names
+// through SNI; without names it is the default for every other hostname
+#[derive(Debug, Clone, PartialEq)]
+pub struct Certificate {
+    pub names: Vec<String>,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl Certificate {
+    // [name,name=]cert.pem,key.pem, e.g. example.com,*.example.com=site.crt,site.key
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        let (names, files) = match s.split_once('=') {
+            Some((names, files)) => (names, files),
+            None => ("", s),
+        };
+        let names: Vec<String> = names
+            .split(',')
+            .map(|name| name.trim().trim_end_matches('.').to_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect();
+        let (cert, key) = files.split_once(',')?;
+        let (cert, key) = (cert.trim(), key.trim());
+        if cert.is_empty() || key.is_empty() {
+            return None;
+        }
+        Some(Self {
+            names,
+            cert: PathBuf::from(cert),
+            key: PathBuf::from(key),
+        })
+    }
+
+    pub fn load(&self) -> Result<LoadedCertificate, String> {
+        let read = |path: &PathBuf, label: &str| {
+            let content = fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+            let text = String::from_utf8_lossy(&content);
+            if !text.contains("-----BEGIN ") || !text.contains(label) {
+                return Err(format!("{} has no PEM {}", path.display(), label.trim_end_matches('-')));
+            }
+            Ok(content)
+        };
+        Ok(LoadedCertificate {
+            certificate: self.clone(),
+            chain: read(&self.cert, "CERTIFICATE-----")?,
+            key: read(&self.key, "PRIVATE KEY-----")?,
+            staple: None,
+        })
+    }
+
+    // exact names win over wildcards, which only cover a single label as in RFC 6125 6.4.3
+    pub fn select<'a>(certificates: &'a [Certificate], server_name: Option<&str>) -> Option<&'a Certificate> {
+        let name = server_name.map(|name| name.trim_end_matches('.').to_lowercase());
+        if let Some(name) = &name {
+            let exact = certificates.iter().find(|cert| cert.names.iter().any(|n| n == name));
+            let wildcard = || {
+                let (_, parent) = name.split_once('.')?;
+                certificates
+                    .iter()
+                    .find(|cert| cert.names.iter().any(|n| n.strip_prefix("*.") == Some(parent)))
+            };
+            if let Some(cert) = exact.or_else(wildcard) {
+                return Some(cert);
+            }
+        }
+        // clients without SNI, or asking for an unknown name, get the default (or first) pair
+        certificates
+            .iter()
+            .find(|cert| cert.names.is_empty())
+            .or(certificates.first())
+    }
+}
+
+// the PEM files of a certificate as they were last read
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedCertificate {
+    pub certificate: Certificate,
+    pub chain: Vec<u8>,
+    pub key: Vec<u8>,
+    // the OCSP response handed out with the chain, with --ocsp-stapling
+    pub staple: Option<Staple>,
+}
+
+// the certificates handshakes would be made with, read once when the server starts: without
+// TLS termination there is no handshake to hand them to, and nothing to rotate them for
+#[derive(Debug, Default)]
+pub struct CertificateStore {
+    loaded: RwLock<Arc<Vec<LoadedCertificate>>>,
+}
+
+impl CertificateStore {
+    // reads the configured pairs, one that cannot be read is left out and reported
+    pub fn load(certificates: &[Certificate]) -> (Self, Vec<String>) {
+        let mut loaded = Vec::with_capacity(certificates.len());
+        let mut errors = Vec::new();
+        for certificate in certificates {
+            match certificate.load() {
+                Ok(certificate) => loaded.push(certificate),
+                Err(error) => errors.push(error),
+            }
+        }
+        let store = Self {
+            loaded: RwLock::new(Arc::new(loaded)),
+        };
+        (store, errors)
+    }
+
+    pub fn current(&self) -> Arc<Vec<LoadedCertificate>> {
+        self.loaded.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    // the pair for a handshake, picked as Certificate::select does
+    pub fn select(&self, server_name: Option<&str>) -> Option<LoadedCertificate> {
+        let loaded = self.current();
+        let certificates: Vec<Certificate> = loaded.iter().map(|loaded| loaded.certificate.clone()).collect();
+        let selected = Certificate::select(&certificates, server_name)?;
+        loaded.iter().find(|loaded| &loaded.certificate == selected).cloned()
+    }
+
+    // the staple of a pair, kept only while the pair still has the chain it was fetched for
+    pub fn set_staple(&self, certificate: &Certificate, chain: &[u8], staple: Option<Staple>) {
+        let mut loaded = self.loaded.write().unwrap_or_else(|e| e.into_inner());
+        let mut updated = loaded.as_ref().clone();
+        if let Some(pair) = updated.iter_mut().find(|pair| &pair.certificate == certificate && pair.chain == chain) {
+            pair.staple = staple;
+            *loaded = Arc::new(updated);
+        }
+    }
+}
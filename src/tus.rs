@@ -0,0 +1,210 @@
+use crate::http::{HttpMethod, HttpStatus};
+use crate::logger::Logger;
+use crate::response::Response;
+use crate::utils::Utils;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// tus 1.0 resumable uploads (https://tus.io/protocols/resumable-upload): POST creates an
+// upload, HEAD tells how much of it arrived and PATCH appends from there, so a client on a
+// flaky link resumes where it stopped instead of starting over
+#[derive(Debug, Clone, Default)]
+pub struct Tus {
+    pub endpoint: Option<String>,
+    pub dir: Option<PathBuf>,
+    pub max_size: Option<u64>,
+}
+
+// appends check the offset and write under this lock, so two PATCHes racing on the same
+// upload cannot both land at the same offset
+static APPEND_LOCK: Mutex<()> = Mutex::new(());
+
+impl Tus {
+    pub const VERSION: &'static str = "1.0.0";
+    pub const EXTENSIONS: &'static str = "creation";
+    pub const METHODS: &'static [HttpMethod] = &[HttpMethod::OPTIONS, HttpMethod::HEAD, HttpMethod::POST, HttpMethod::PATCH];
+    pub const OFFSET_CONTENT_TYPE: &'static str = "application/offset+octet-stream";
+    // inside the served directory by default, dot directories are never served
+    pub const DEFAULT_DIR: &'static str = ".tus";
+    // <id>.part grows until complete and is then renamed to <id>, <id>.info holds the length
+    // and metadata given at creation
+    const PART_EXTENSION: &'static str = "part";
+    const INFO_EXTENSION: &'static str = "info";
+
+    pub fn enabled(&self) -> bool {
+        self.endpoint.is_some()
+    }
+
+    pub fn handles(&self, path: &str) -> bool {
+        self.endpoint.as_deref().is_some_and(|endpoint| Utils::under_prefix(path, endpoint))
+    }
+
+    pub fn dir(&self, root_dir: &Path) -> PathBuf {
+        self.dir.clone().unwrap_or_else(|| root_dir.join(Self::DEFAULT_DIR))
+    }
+
+    pub fn is_endpoint(&self, path: &str) -> bool {
+        self.endpoint.as_deref().is_some_and(|endpoint| endpoint.trim_end_matches('/') == path.trim_end_matches('/'))
+    }
+
+    // ids are only ever generated here, so anything that is not one is unknown rather than a
+    // path to resolve
+    pub fn upload_id<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let endpoint = self.endpoint.as_deref()?.trim_end_matches('/');
+        let id = path.strip_prefix(endpoint)?.strip_prefix('/')?;
+        (id.len() == 16 && id.bytes().all(|b| b.is_ascii_hexdigit())).then_some(id)
+    }
+
+    // the finished file when this request completed an upload
+    pub fn serve(&self, root_dir: &Path, response: &mut Response) -> Option<PathBuf> {
+        let method = response.request.method;
+        let result = if method != HttpMethod::OPTIONS
+            && response.request.header("Tus-Resumable").map(str::trim) != Some(Self::VERSION)
+        {
+            Err(HttpStatus::PreconditionFailed)
+        } else {
+            let dir = self.dir(root_dir);
+            let path = response.request.path.clone();
+            match (method, self.upload_id(&path)) {
+                (HttpMethod::OPTIONS, _) => {
+                    self.options(response);
+                    Ok(None)
+                }
+                (HttpMethod::POST, None) if self.is_endpoint(&path) => self.create(&dir, response),
+                (HttpMethod::HEAD, Some(id)) => Self::status(&dir, id, response).map(|_| None),
+                (HttpMethod::PATCH, Some(id)) => Self::append(&dir, id, response),
+                _ => Err(HttpStatus::NotFound),
+            }
+        };
+
+        if let Err(status) = result {
+            response.serve_error_response(status);
+            if status == HttpStatus::PreconditionFailed {
+                response.set_header("Tus-Version", Self::VERSION);
+            }
+        }
+        response.set_header("Tus-Resumable", Self::VERSION);
+        result.ok().flatten()
+    }
+
+    fn options(&self, response: &mut Response) {
+        response.serve_status(HttpStatus::NoContent);
+        response.set_header("Tus-Version", Self::VERSION);
+        response.set_header("Tus-Extension", Self::EXTENSIONS);
+        if let Some(max_size) = self.max_size {
+            response.set_header("Tus-Max-Size", &max_size.to_string());
+        }
+    }
+
+    fn create(&self, dir: &Path, response: &mut Response) -> Result<Option<PathBuf>, HttpStatus> {
+        let length = response
+            .request
+            .header("Upload-Length")
+            .and_then(|length| length.trim().parse::<u64>().ok())
+            .ok_or(HttpStatus::BadRequest)?;
+        if self.max_size.is_some_and(|max_size| length > max_size) {
+            return Err(HttpStatus::PayloadTooLarge);
+        }
+        let metadata = response.request.header("Upload-Metadata").unwrap_or_default().trim().to_string();
+
+        let id = Utils::request_id();
+        let created = fs::create_dir_all(dir)
+            .and_then(|_| fs::write(Self::file(dir, &id, Self::INFO_EXTENSION), format!("{}\n{}\n", length, metadata)))
+            .and_then(|_| File::create(Self::file(dir, &id, Self::PART_EXTENSION)).map(|_| ()));
+        if let Err(e) = created {
+            Logger::error(format!("Failed to create upload in {}: {}", dir.display(), e).as_str());
+            return Err(HttpStatus::InternalServerError);
+        }
+        let completed = if length == 0 { Some(Self::complete(dir, &id)?) } else { None };
+
+        let endpoint = self.endpoint.as_deref().unwrap_or_default().trim_end_matches('/');
+        response.serve_status(HttpStatus::Created);
+        response.set_header("Location", &format!("{}/{}", endpoint, id));
+        Ok(completed)
+    }
+
+    fn status(dir: &Path, id: &str, response: &mut Response) -> Result<(), HttpStatus> {
+        let (length, metadata) = Self::info(dir, id).ok_or(HttpStatus::NotFound)?;
+        let offset = Self::offset(dir, id).ok_or(HttpStatus::NotFound)?;
+
+        response.serve_status(HttpStatus::Ok);
+        response.set_header("Upload-Offset", &offset.to_string());
+        response.set_header("Upload-Length", &length.to_string());
+        if !metadata.is_empty() {
+            response.set_header("Upload-Metadata", &metadata);
+        }
+        // the offset changes with every PATCH
+        response.set_header("Cache-Control", "no-store");
+        Ok(())
+    }
+
+    fn append(dir: &Path, id: &str, response: &mut Response) -> Result<Option<PathBuf>, HttpStatus> {
+        let request = &response.request;
+        if !request
+            .header("Content-Type")
+            .is_some_and(|content_type| content_type.trim().eq_ignore_ascii_case(Self::OFFSET_CONTENT_TYPE))
+        {
+            return Err(HttpStatus::UnsupportedMediaType);
+        }
+        let offset = request
+            .header("Upload-Offset")
+            .and_then(|offset| offset.trim().parse::<u64>().ok())
+            .ok_or(HttpStatus::BadRequest)?;
+
+        let _lock = APPEND_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (length, _) = Self::info(dir, id).ok_or(HttpStatus::NotFound)?;
+        let current = Self::offset(dir, id).ok_or(HttpStatus::NotFound)?;
+        if offset != current {
+            return Err(HttpStatus::Conflict);
+        }
+        let end = current + request.body.len() as u64;
+        if end > length {
+            return Err(HttpStatus::PayloadTooLarge);
+        }
+
+        let part = Self::file(dir, id, Self::PART_EXTENSION);
+        let written = OpenOptions::new()
+            .append(true)
+            .open(&part)
+            .and_then(|mut file| file.write_all(&request.body).and_then(|_| file.sync_data()));
+        if let Err(e) = written {
+            Logger::error(format!("Failed to append to {}: {}", part.display(), e).as_str());
+            return Err(HttpStatus::InternalServerError);
+        }
+        let completed = if end == length { Some(Self::complete(dir, id)?) } else { None };
+
+        response.serve_status(HttpStatus::NoContent);
+        response.set_header("Upload-Offset", &end.to_string());
+        Ok(completed)
+    }
+
+    // the finished file loses its .part suffix, so a half-written one is never mistaken for it
+    fn complete(dir: &Path, id: &str) -> Result<PathBuf, HttpStatus> {
+        let file = dir.join(id);
+        fs::rename(Self::file(dir, id, Self::PART_EXTENSION), &file).map(|_| file).map_err(|e| {
+            Logger::error(format!("Failed to complete upload {}: {}", id, e).as_str());
+            HttpStatus::InternalServerError
+        })
+    }
+
+    fn info(dir: &Path, id: &str) -> Option<(u64, String)> {
+        let info = fs::read_to_string(Self::file(dir, id, Self::INFO_EXTENSION)).ok()?;
+        let mut lines = info.lines();
+        let length = lines.next()?.parse::<u64>().ok()?;
+        Some((length, lines.next().unwrap_or_default().to_string()))
+    }
+
+    // what arrived so far is the size of the partial file, or all of it once complete
+    fn offset(dir: &Path, id: &str) -> Option<u64> {
+        fs::metadata(Self::file(dir, id, Self::PART_EXTENSION))
+            .or_else(|_| fs::metadata(dir.join(id)))
+            .map(|metadata| metadata.len())
+            .ok()
+    }
+
+    fn file(dir: &Path, id: &str, extension: &str) -> PathBuf {
+        dir.join(format!("{}.{}", id, extension))
+    }
+}
@@ -0,0 +1,72 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// per-user sites on shared shell hosts, as Apache's mod_userdir: with --userdir,
+// /~alice/notes.html is public_html/notes.html in the home directory /etc/passwd gives alice.
+// Only static files are served from there, hidden ones never, and system accounts, root
+// among them, have no user directory
+#[derive(Debug, Clone)]
+pub struct UserDir {
+    pub enabled: bool,
+    // relative to each home directory
+    pub dir: String,
+    pub passwd: PathBuf,
+}
+
+impl Default for UserDir {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: Self::DEFAULT_DIR.to_string(),
+            passwd: PathBuf::from(Self::PASSWD),
+        }
+    }
+}
+
+impl UserDir {
+    pub const DEFAULT_DIR: &'static str = "public_html";
+    pub const PASSWD: &'static str = "/etc/passwd";
+    // where the accounts of people start on most Linux and BSD systems
+    pub const MIN_UID: u32 = 1000;
+    const MAX_NAME: usize = 32;
+
+    // the user and the rest of a /~user/... path, which is empty for /~user itself; None for
+    // other paths and names no account can have
+    pub fn split(path: &str) -> Option<(&str, &str)> {
+        let rest = path.strip_prefix("/~")?;
+        let (user, rest) = rest.find('/').map_or((rest, ""), |end| rest.split_at(end));
+        let valid = !user.is_empty()
+            && user.len() <= Self::MAX_NAME
+            && !user.starts_with(['.', '-'])
+            && user.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'));
+        valid.then_some((user, rest))
+    }
+
+    // --userdir-dir, a path inside each home directory with nothing hidden along it
+    pub fn set_dir(&mut self, dir: &str) -> Result<(), String> {
+        let dir = dir.trim().trim_end_matches('/');
+        if dir.is_empty() || dir.starts_with('/') || dir.split('/').any(|segment| segment.is_empty() || segment.starts_with('.')) {
+            return Err(format!("the user directory must be a path inside the home directory: {}", dir));
+        }
+        self.dir = dir.to_string();
+        Ok(())
+    }
+
+    // the directory served for a user, None when there is no such account, it is a system
+    // one or its home has no such directory
+    pub fn root(&self, user: &str) -> Option<PathBuf> {
+        let passwd = fs::read_to_string(&self.passwd).ok()?;
+        let home = passwd.lines().find_map(|line| {
+            // name:password:uid:gid:gecos:home:shell
+            let fields: Vec<&str> = line.split(':').collect();
+            let uid = fields.get(2)?.parse::<u32>().ok()?;
+            (fields[0] == user && uid >= Self::MIN_UID).then(|| fields.get(5).copied()).flatten()
+        })?;
+        let home = Path::new(home);
+        if !home.is_absolute() || home.parent().is_none() {
+            return None;
+        }
+        let root = home.join(&self.dir);
+        root.is_dir().then_some(root)
+    }
+}
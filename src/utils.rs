@@ -1,7 +1,13 @@
+use crate::http::HttpStatus;
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
 use std::env;
 use std::fs::{self, ReadDir};
-use std::path::{Component, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug)]
 pub struct Utils;
@@ -32,6 +38,39 @@ impl Utils {
         results
     }
 
+    // entries below path whose name contains query, ignoring case, breadth first so shallow
+    // matches come first; hidden entries and everything under them are left out like in a
+    // listing, as is whatever keep refuses. Names are relative to path, e.g. docs/report.pdf
+    pub fn search_dir(
+        path: &Path,
+        query: &str,
+        max_depth: usize,
+        max_results: usize,
+        keep: impl Fn(&str, bool) -> bool,
+    ) -> Vec<(String, String, String)> {
+        let query = query.to_lowercase();
+        let mut results = Vec::new();
+        let mut queue = VecDeque::from([(path.to_path_buf(), String::new(), 1)]);
+        while let Some((dir, prefix, depth)) = queue.pop_front() {
+            for (entry_type, name, entry_path) in Self::walk_dir(&dir) {
+                let relative = format!("{}{}", prefix, name);
+                if !keep(&relative, entry_type == "directory") {
+                    continue;
+                }
+                if entry_type == "directory" && depth < max_depth {
+                    queue.push_back((PathBuf::from(&entry_path), format!("{}/", relative), depth + 1));
+                }
+                if name.to_lowercase().contains(&query) {
+                    results.push((entry_type, relative, entry_path));
+                    if results.len() == max_results {
+                        return results;
+                    }
+                }
+            }
+        }
+        results
+    }
+
     pub fn collect_entries(entries: ReadDir) -> Vec<fs::DirEntry> {
         entries.filter_map(|entry| entry.ok()).collect()
     }
@@ -54,6 +93,216 @@ impl Utils {
         PathBuf::from(normalized.to_string_lossy().replace('\\', "/"))
     }
 
+    // the existing entry a request path names, refused when it is the root itself, hidden, or
+    // reaches outside the root through .. or a symlinked directory; a symlink is itself the entry
+    pub fn resolve_under(root_dir: &Path, request_path: &str) -> Result<PathBuf, HttpStatus> {
+        let relative = request_path.trim_matches('/');
+        if relative.is_empty() || relative.split('/').any(|segment| segment.starts_with('.')) {
+            return Err(HttpStatus::Forbidden);
+        }
+        let path = root_dir.join(relative);
+        let (Ok(root), Some(name), Some(Ok(parent))) =
+            (root_dir.canonicalize(), path.file_name(), path.parent().map(Path::canonicalize))
+        else {
+            return Err(HttpStatus::NotFound);
+        };
+        if !parent.starts_with(&root) {
+            return Err(HttpStatus::Forbidden);
+        }
+        let path = parent.join(name);
+        if path.symlink_metadata().is_err() {
+            return Err(HttpStatus::NotFound);
+        }
+        Ok(path)
+    }
+
+    pub fn parse_size(value: &str) -> Option<usize> {
+        let value = value.trim();
+        let (digits, multiplier) = match value.chars().last()?.to_ascii_uppercase() {
+            'K' => (&value[..value.len() - 1], 1024),
+            'M' => (&value[..value.len() - 1], 1024 * 1024),
+            'G' => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+            _ => (value, 1),
+        };
+        digits.trim().parse::<usize>().ok()?.checked_mul(multiplier)
+    }
+
+    // plain milliseconds, or a ms/s/m/h/d suffixed duration such as 250ms or 2s
+    pub fn parse_duration(value: &str) -> Option<Duration> {
+        let value = value.trim().to_lowercase();
+        let (digits, unit) = if let Some(digits) = value.strip_suffix("ms") {
+            (digits, 1)
+        } else if let Some(digits) = value.strip_suffix('s') {
+            (digits, 1000)
+        } else if let Some(digits) = value.strip_suffix('m') {
+            (digits, 60 * 1000)
+        } else if let Some(digits) = value.strip_suffix('h') {
+            (digits, 60 * 60 * 1000)
+        } else if let Some(digits) = value.strip_suffix('d') {
+            (digits, 24 * 60 * 60 * 1000)
+        } else {
+            (value.as_str(), 1)
+        };
+        let millis = digits.trim().parse::<u64>().ok()?.checked_mul(unit)?;
+        Some(Duration::from_millis(millis))
+    }
+
+    // a prefix covers itself and everything below it, /api covers /api/x but not /apix
+    pub fn under_prefix(path: &str, prefix: &str) -> bool {
+        let prefix = prefix.trim_end_matches('/');
+        prefix.is_empty() || path == prefix || path.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))
+    }
+
+    // 1024 based, e.g. 512 B, 1.5 KB, 3.2 GB
+    pub fn human_size(bytes: u64) -> String {
+        const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+        let mut size = bytes as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 {
+            format!("{} {}", bytes, UNITS[0])
+        } else {
+            format!("{:.1} {}", size, UNITS[unit])
+        }
+    }
+
+    // percent-encodes everything but unreserved characters and the path separators
+    pub fn encode_url_path(path: &str) -> String {
+        let mut encoded = String::with_capacity(path.len());
+        for b in path.bytes() {
+            if b.is_ascii_alphanumeric() || matches!(b, b'/' | b'-' | b'_' | b'.' | b'~') {
+                encoded.push(b as char);
+            } else {
+                encoded.push_str(&format!("%{:02X}", b));
+            }
+        }
+        encoded
+    }
+
+    // IPv6 literals must be bracketed before a port can be appended
+    pub fn host_port(host: &str, port: u16) -> String {
+        if host.contains(':') && !host.starts_with('[') {
+            format!("[{}]:{}", host, port)
+        } else {
+            format!("{}:{}", host, port)
+        }
+    }
+
+    // the address of the interface holding the default route: connecting a UDP socket
+    // sends nothing but makes the OS pick the outgoing interface
+    pub fn lan_ip() -> Option<IpAddr> {
+        let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+        socket.connect("192.0.2.1:9").ok()?; // TEST-NET-1, never actually reached
+        let ip = socket.local_addr().ok()?.ip();
+        if ip.is_unspecified() || ip.is_loopback() {
+            None
+        } else {
+            Some(ip)
+        }
+    }
+
+    // dual-stack sockets report IPv4 clients as ::ffff:a.b.c.d, show them as plain IPv4
+    pub fn format_addr(addr: &SocketAddr) -> String {
+        match addr {
+            SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+                Some(v4) => format!("{}:{}", v4, v6.port()),
+                None => format!("[{}]:{}", v6.ip(), v6.port()),
+            },
+            SocketAddr::V4(v4) => v4.to_string(),
+        }
+    }
+
+    // 16 hex digits, unique within the process thanks to the counter and
+    // unpredictable across restarts thanks to the randomly keyed hasher
+    pub fn request_id() -> String {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        if let Ok(duration) = SystemTime::now().duration_since(UNIX_EPOCH) {
+            hasher.write_u128(duration.as_nanos());
+        }
+        format!("{:016x}", hasher.finish())
+    }
+
+    // RFC 4648 5 without padding, as used by JWTs and signed URLs
+    pub fn base64url_encode(data: &[u8]) -> String {
+        const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+        let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+        for chunk in data.chunks(3) {
+            let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, b)| bits | (*b as u32) << (16 - 8 * i));
+            for i in 0..=chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            }
+        }
+        encoded
+    }
+
+    // padding is tolerated, any other character outside the alphabet is not
+    pub fn base64url_decode(value: &str) -> Option<Vec<u8>> {
+        let value = value.trim_end_matches('=');
+        let mut decoded = Vec::with_capacity(value.len() * 3 / 4);
+        let mut bits = 0u32;
+        let mut count = 0;
+        for c in value.bytes() {
+            let digit = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'-' => 62,
+                b'_' => 63,
+                _ => return None,
+            };
+            bits = (bits << 6) | digit as u32;
+            count += 6;
+            if count >= 8 {
+                count -= 8;
+                decoded.push((bits >> count) as u8);
+            }
+        }
+        // a single leftover character cannot encode a full byte
+        if count >= 6 {
+            return None;
+        }
+        Some(decoded)
+    }
+
+    // text placed inside HTML elements or quoted attributes
+    pub fn escape_html(value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                _ => escaped.push(c),
+            }
+        }
+        escaped
+    }
+
+    pub fn json_string(value: &str) -> String {
+        let mut json = String::with_capacity(value.len() + 2);
+        json.push('"');
+        for c in value.chars() {
+            match c {
+                '"' => json.push_str("\\\""),
+                '\\' => json.push_str("\\\\"),
+                '\n' => json.push_str("\\n"),
+                '\r' => json.push_str("\\r"),
+                '\t' => json.push_str("\\t"),
+                c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+                c => json.push(c),
+            }
+        }
+        json.push('"');
+        json
+    }
+
     pub fn timezone_from_env() -> String {
         env::var("TZ").unwrap_or("00:00".to_string())
     }
@@ -195,6 +444,80 @@ impl Utils {
         }
     }
 
+    // 2025-01-31T12:00:00Z for a unix time, e.g. the Expires of security.txt
+    pub fn datetime_rfc_3339(secs: u64) -> String {
+        let (year, month, day) = Self::civil_from_secs(secs);
+        let secs_of_day = secs % 86400;
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year,
+            month,
+            day,
+            secs_of_day / 3600,
+            (secs_of_day % 3600) / 60,
+            secs_of_day % 60
+        )
+    }
+
+    // Fri, 31 Jan 2025 12:00:00 GMT for a unix time, e.g. a Last-Modified
+    pub fn http_date(secs: u64) -> String {
+        const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+        const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+        let (year, month, day) = Self::civil_from_secs(secs);
+        let secs_of_day = secs % 86400;
+        format!(
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+            WEEKDAYS[(secs / 86400 % 7) as usize],
+            day,
+            MONTHS[month as usize - 1],
+            year,
+            secs_of_day / 3600,
+            (secs_of_day % 3600) / 60,
+            secs_of_day % 60
+        )
+    }
+
+    // days to a civil date, http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    fn civil_from_secs(secs: u64) -> (i64, i64, i64) {
+        let days = (secs / 86400) as i64 + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days.rem_euclid(146097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+        (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+    }
+
+    // the unix time of an IMF-fixdate such as Sun, 06 Nov 1994 08:49:37 GMT, the only form
+    // senders may still generate (RFC 9110 5.6.7); the weekday is not checked
+    pub fn parse_http_date(value: &str) -> Option<u64> {
+        let parts: Vec<&str> = value.split_whitespace().collect();
+        let [_, day, month, year, time, "GMT"] = parts[..] else {
+            return None;
+        };
+        let months = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+        let month = months.iter().position(|name| *name == month)? as i64 + 1;
+        let (day, year) = (day.parse::<i64>().ok()?, year.parse::<i64>().ok()?);
+        let time: Vec<u64> = time.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+        let [hours, minutes, seconds] = time[..] else {
+            return None;
+        };
+        if !(1..=31).contains(&day) || year < 1970 || hours > 23 || minutes > 59 || seconds > 60 {
+            return None;
+        }
+
+        // days from a civil date, http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = u64::try_from(era * 146097 + day_of_era - 719468).ok()?;
+        Some(days * 86400 + hours * 3600 + minutes * 60 + seconds)
+    }
+
     pub fn log_datetime() -> String {
         let now = SystemTime::now().duration_since(UNIX_EPOCH);
         let seconds = now.unwrap().as_secs();
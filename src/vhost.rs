@@ -0,0 +1,135 @@
+use crate::config::Config;
+use crate::request::Request;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// a site picked by the Host of each request, with its own config file in the usual
+// `key = value` format layered over the global options:
+//
+//   --vhost example.com,*.example.com=sites/example.conf
+//
+//   # sites/example.conf
+//   dir = "/srv/example"
+//   header = ["X-Frame-Options: DENY"]
+//   no_listing = true
+//
+// a certificate without names is served for the names of the site
+#[derive(Debug, Clone)]
+pub struct VirtualHost {
+    pub names: Vec<String>,
+    pub file: PathBuf,
+    pub config: Arc<Config>,
+}
+
+impl VirtualHost {
+    // what a site may change, listeners, logging and everything else stay global
+    pub const OPTIONS: &'static [&'static str] = &[
+        "--dir",
+        "--default-language",
+        "--locale",
+        "--messages",
+        "--tls-cert",
+        "--header",
+        "--listing",
+        "--no-listing",
+        "--listing-page-size",
+        "--etag",
+        "--charset",
+        "--checksums",
+        "--spa-fallback",
+        "--ignore",
+        "--handler",
+        "--proxy",
+        "--proxy-balance",
+        "--proxy-max-fails",
+        "--proxy-fail-timeout",
+        "--proxy-timeout",
+        "--proxy-health-path",
+        "--proxy-health-interval",
+        "--proxy-healthy-threshold",
+        "--proxy-unhealthy-threshold",
+        "--inject-head",
+        "--inject-body",
+        "--inject-files",
+        "--location",
+        "--jwt-secret",
+        "--jwt-jwks",
+        "--jwt-audience",
+        "--jwt-issuer",
+        "--jwt-protect",
+        "--sign-secret",
+        "--sign-protect",
+        "--sign-expires",
+        "--redirect",
+        "--hotlink-protect",
+        "--hotlink-allow",
+        "--hotlink-block-empty",
+        "--hotlink-redirect",
+        "--robots",
+        "--favicon",
+        "--security-contact",
+        "--security-policy",
+        "--security-expires",
+    ];
+
+    // names=file, e.g. example.com,www.example.com=sites/example.conf
+    pub fn parse(value: &str) -> Option<(Vec<String>, PathBuf)> {
+        let (names, file) = value.split_once('=')?;
+        let names: Vec<String> = names
+            .split(',')
+            .map(|name| name.trim().trim_end_matches('.').to_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect();
+        let file = file.trim();
+        if names.is_empty() || file.is_empty() {
+            return None;
+        }
+        Some((names, PathBuf::from(file)))
+    }
+
+    // global_args are the options of the server itself without --config and --vhost, and
+    // global_certs the number of certificates they give
+    pub fn load(names: Vec<String>, file: PathBuf, global_args: &[String], global_certs: usize) -> Result<Self, Vec<String>> {
+        let file_args = Config::read_file(&file).map_err(|e| vec![e])?;
+        let refused: Vec<String> = file_args
+            .iter()
+            .filter(|arg| arg.starts_with("--") && !Self::OPTIONS.contains(&arg.as_str()))
+            .map(|arg| format!("{}: {} cannot be set per virtual host", file.display(), arg))
+            .collect();
+        if !refused.is_empty() {
+            return Err(refused);
+        }
+
+        let mut args = global_args.to_vec();
+        args.extend(file_args);
+        let (mut config, errors) = Config::parse(args);
+        if !errors.is_empty() {
+            return Err(errors.into_iter().map(|e| format!("{}: {}", file.display(), e)).collect());
+        }
+        for cert in config.tls_certs.iter_mut().skip(global_certs) {
+            if cert.names.is_empty() {
+                cert.names = names.clone();
+            }
+        }
+        Ok(Self {
+            names,
+            file,
+            config: Arc::new(config),
+        })
+    }
+
+    // exact names win over wildcards, which only cover a single label
+    pub fn select<'a>(hosts: &'a [VirtualHost], host: &str) -> Option<&'a VirtualHost> {
+        let name = Request::host_name(host).to_lowercase();
+        if name.is_empty() {
+            return None;
+        }
+        let exact = hosts.iter().find(|vhost| vhost.names.contains(&name));
+        exact.or_else(|| {
+            let (_, parent) = name.split_once('.')?;
+            hosts
+                .iter()
+                .find(|vhost| vhost.names.iter().any(|n| n.strip_prefix("*.") == Some(parent)))
+        })
+    }
+}
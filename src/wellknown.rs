@@ -0,0 +1,108 @@
+use crate::utils::Utils;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// files quick shares routinely forget, answered only when the root has none of its own
+
+// --robots allow|deny|<file>
+#[derive(Debug, Clone, PartialEq)]
+pub enum Robots {
+    AllowAll,
+    DenyAll,
+    File(PathBuf),
+}
+
+impl Robots {
+    pub const PATH: &'static str = "/robots.txt";
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Self {
+        match s.trim().to_lowercase().as_str() {
+            "allow" | "all" => Robots::AllowAll,
+            "deny" | "none" => Robots::DenyAll,
+            _ => Robots::File(PathBuf::from(s.trim())),
+        }
+    }
+
+    // None when the custom file cannot be read
+    pub fn body(&self) -> Option<String> {
+        match self {
+            Robots::AllowAll => Some("User-agent: *\nDisallow:\n".to_string()),
+            Robots::DenyAll => Some("User-agent: *\nDisallow: /\n".to_string()),
+            Robots::File(path) => fs::read_to_string(path).ok(),
+        }
+    }
+}
+
+// browsers ask for /favicon.ico on every visit, --favicon off|<file>
+#[derive(Debug, Clone, PartialEq)]
+pub enum Favicon {
+    BuiltIn,
+    File(PathBuf),
+    Off,
+}
+
+impl Favicon {
+    pub const PATH: &'static str = "/favicon.ico";
+    pub const BUILT_IN: &'static [u8] = include_bytes!("../templates/favicon.ico");
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Self {
+        match s.trim().to_lowercase().as_str() {
+            "off" | "none" => Favicon::Off,
+            "builtin" | "built-in" | "default" => Favicon::BuiltIn,
+            _ => Favicon::File(PathBuf::from(s.trim())),
+        }
+    }
+
+    // None when turned off or the configured file cannot be read
+    pub fn body(&self) -> Option<Vec<u8>> {
+        match self {
+            Favicon::BuiltIn => Some(Self::BUILT_IN.to_vec()),
+            Favicon::File(path) => fs::read(path).ok(),
+            Favicon::Off => None,
+        }
+    }
+}
+
+// RFC 9116, at least one contact and an expiry kept a fixed time ahead of every request
+#[derive(Debug, Clone)]
+pub struct SecurityTxt {
+    pub contacts: Vec<String>,
+    pub policy: Option<String>,
+    pub expires: Duration,
+}
+
+impl Default for SecurityTxt {
+    fn default() -> Self {
+        Self {
+            contacts: Vec::new(),
+            policy: None,
+            expires: Self::DEFAULT_EXPIRES,
+        }
+    }
+}
+
+impl SecurityTxt {
+    pub const PATH: &'static str = "/.well-known/security.txt";
+    // the RFC recommends less than a year
+    pub const DEFAULT_EXPIRES: Duration = Duration::from_secs(180 * 24 * 60 * 60);
+
+    pub fn enabled(&self) -> bool {
+        !self.contacts.is_empty()
+    }
+
+    // now as a unix time
+    pub fn body(&self, now: u64) -> String {
+        let mut body = String::new();
+        for contact in &self.contacts {
+            body.push_str(&format!("Contact: {}\n", contact));
+        }
+        body.push_str(&format!("Expires: {}\n", Utils::datetime_rfc_3339(now + self.expires.as_secs())));
+        if let Some(policy) = &self.policy {
+            body.push_str(&format!("Policy: {}\n", policy));
+        }
+        body
+    }
+}
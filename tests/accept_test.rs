@@ -0,0 +1,43 @@
+use katana::accept::Accept;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    /// Test that the most specific media range decides the weight
+    #[test]
+    fn test_quality() {
+        let accept = Some("text/*;q=0.5, text/html, application/json;q=0.8, */*;q=0.1");
+        assert_eq!(Accept::quality(accept, "text/html"), 1.0);
+        assert_eq!(Accept::quality(accept, "text/plain"), 0.5);
+        assert_eq!(Accept::quality(accept, "application/json"), 0.8);
+        assert_eq!(Accept::quality(accept, "image/png"), 0.1);
+        assert_eq!(Accept::quality(Some("application/json"), "application/xml"), 0.0);
+        assert_eq!(Accept::quality(Some("*/*, application/xml;q=0"), "application/xml"), 0.0);
+        assert_eq!(Accept::quality(None, "application/xml"), 1.0);
+    }
+
+    /// Test variant discovery and selection for a path without its own file
+    #[test]
+    fn test_variants_and_select() {
+        let dir = env::temp_dir().join("accept_test_variants");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["data.json", "data.xml", "data.html", "data.unknown", "database.json"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+
+        let variants = Accept::variants(&dir.join("data"));
+        let extensions: Vec<&str> = variants.iter().map(|(_, file_type)| file_type.extension.as_str()).collect();
+        assert_eq!(extensions, vec!["html", "json", "xml"]);
+
+        let pick = |accept: Option<&str>| Accept::select(accept, &variants).map(|index| extensions[index]);
+        assert_eq!(pick(Some("application/json")), Some("json"));
+        assert_eq!(pick(Some("application/xml;q=0.9, application/json;q=0.5")), Some("xml"));
+        assert_eq!(pick(Some("text/html,application/xhtml+xml,*/*;q=0.8")), Some("html"));
+        assert_eq!(pick(None), Some("html"));
+        assert_eq!(pick(Some("image/png")), None);
+    }
+}
@@ -0,0 +1,43 @@
+use katana::acme::Acme;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    /// Test that only base64url tokens under the challenge path are accepted
+    #[test]
+    fn test_token() {
+        assert_eq!(Acme::token("/.well-known/acme-challenge/LoqXcYV8q5ONbJQx-_wa"), Some("LoqXcYV8q5ONbJQx-_wa"));
+        assert_eq!(Acme::token("/.well-known/acme-challenge/"), None);
+        assert_eq!(Acme::token("/.well-known/acme-challenge/../secret"), None);
+        assert_eq!(Acme::token("/.well-known/acme-challenge/a/b"), None);
+        assert_eq!(Acme::token("/acme-challenge/abc"), None);
+    }
+
+    /// Test the Host header matching, with and without a port
+    #[test]
+    fn test_serves_host() {
+        let domains = vec!["example.com".to_string(), "www.example.com".to_string()];
+        assert!(Acme::serves_host(&domains, Some("example.com")));
+        assert!(Acme::serves_host(&domains, Some("WWW.Example.com:80")));
+        assert!(Acme::serves_host(&domains, Some("example.com.")));
+        assert!(!Acme::serves_host(&domains, Some("other.example.com")));
+        assert!(!Acme::serves_host(&domains, None));
+        assert!(Acme::serves_host(&[], None), "No configured domain means any host");
+    }
+
+    /// Test that stored key authorizations are returned trimmed and checked against the token
+    #[test]
+    fn test_key_authorization() {
+        let dir = env::temp_dir().join("acme_test_challenges");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("token1"), "token1.thumbprint\n").unwrap();
+        fs::write(dir.join("token2"), "something else").unwrap();
+
+        assert_eq!(Acme::key_authorization(&dir, "token1"), Some("token1.thumbprint".to_string()));
+        assert_eq!(Acme::key_authorization(&dir, "token2"), None);
+        assert_eq!(Acme::key_authorization(&dir, "missing"), None);
+    }
+}
@@ -0,0 +1,107 @@
+use katana::admin::Admin;
+use katana::config::Config;
+use katana::logger::{LogLevel, Logger};
+use katana::server::Server;
+use katana::templates::Templates;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    const TOKEN: &str = "let-me-in";
+
+    /// Helper function that starts the admin API alone and returns its address
+    fn start_admin() -> String {
+        let args = vec!["", "--admin-listen", "127.0.0.1:0", "--admin-token", TOKEN, "--jwt-secret", "hush"];
+        let server = Server::new(Config::parse_args(args.into_iter().map(String::from).collect()), Templates::load());
+        let listener = Admin::bind("127.0.0.1:0").expect("Admin API should bind");
+        let url = listener.url().unwrap();
+        Admin::spawn(server, listener);
+        url.trim_start_matches("http://").to_string()
+    }
+
+    /// Helper function that sends one request with an optional token and body
+    fn send(addr: &str, method: &str, path: &str, token: Option<&str>, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let authorization = token.map(|token| format!("Authorization: Bearer {}\r\n", token)).unwrap_or_default();
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: admin\r\n{}Content-Length: {}\r\n\r\n{}",
+            method, path, authorization, body.len(), body
+        );
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    /// Test that every endpoint needs the token
+    #[test]
+    fn test_token_required() {
+        let addr = start_admin();
+        let response = send(&addr, "GET", "/status", None, "");
+        assert!(response.starts_with("HTTP/1.1 401"), "Got '{}'", response);
+        assert!(response.contains("WWW-Authenticate: Bearer"));
+        assert!(send(&addr, "GET", "/status", Some("wrong"), "").starts_with("HTTP/1.1 401"));
+        assert!(send(&addr, "GET", "/status", Some(TOKEN), "").starts_with("HTTP/1.1 200"));
+    }
+
+    /// Test that status and config are JSON and the config leaves secrets out
+    #[test]
+    fn test_status_and_config() {
+        let addr = start_admin();
+        let status = send(&addr, "GET", "/status", Some(TOKEN), "");
+        assert!(status.contains("Content-Type: application/json"));
+        assert!(status.contains("\"uptime_seconds\":"));
+        assert!(status.contains("\"active_connections\":"));
+        assert!(status.contains("\"buffers\":{\"in_use\":"));
+
+        let config = send(&addr, "GET", "/config", Some(TOKEN), "");
+        assert!(config.contains("\"root_dir\":\"public\""), "Got '{}'", config);
+        assert!(config.contains("\"jwt_protected\":[\"/\"]"));
+        assert!(!config.contains(TOKEN) && !config.contains("hush"));
+    }
+
+    /// Test that the metrics are in the Prometheus text format and count the admin traffic too
+    #[test]
+    fn test_metrics() {
+        let addr = start_admin();
+        assert!(send(&addr, "GET", "/metrics", None, "").starts_with("HTTP/1.1 401"));
+        let metrics = send(&addr, "GET", "/metrics", Some(TOKEN), "");
+        assert!(metrics.starts_with("HTTP/1.1 200"), "Got '{}'", metrics);
+        assert!(metrics.contains("Content-Type: text/plain; version=0.0.4"));
+        assert!(metrics.contains("# TYPE katana_connections_accepted_total counter\n"));
+        let received = metrics
+            .lines()
+            .find_map(|line| line.strip_prefix("katana_received_bytes_total "))
+            .and_then(|value| value.parse::<u64>().ok());
+        assert!(received.is_some_and(|received| received > 0), "Got '{}'", metrics);
+        assert!(send(&addr, "POST", "/metrics", Some(TOKEN), "").starts_with("HTTP/1.1 405"));
+    }
+
+    /// Test that proxy upstreams are reported once per address with their state
+    #[test]
+    fn test_upstream_metrics() {
+        assert!(!Admin::metrics(&Config::parse_args(vec!["".to_string()])).contains("katana_upstream"));
+        let args = vec!["", "--proxy", "127.0.0.1:3000", "--proxy", "127.0.0.1:3001", "--proxy", "127.0.0.1:3000"];
+        let metrics = Admin::metrics(&Config::parse_args(args.into_iter().map(String::from).collect()));
+        assert!(metrics.contains("# TYPE katana_upstream_up gauge\nkatana_upstream_up{upstream=\"127.0.0.1:3000\"} 1\n"), "Got '{}'", metrics);
+        assert!(metrics.contains("katana_upstream_active_requests{upstream=\"127.0.0.1:3001\"} 0\n"));
+        assert_eq!(metrics.matches("katana_upstream_up{").count(), 2);
+    }
+
+    /// Test that the log level changes at runtime and bad input is refused
+    #[test]
+    fn test_log_level() {
+        let addr = start_admin();
+        let response = send(&addr, "POST", "/log-level", Some(TOKEN), "{\"level\": \"debug\"}");
+        assert!(response.ends_with("{\"level\":\"debug\"}"), "Got '{}'", response);
+        assert_eq!(Logger::level(), LogLevel::DEBUG);
+        Logger::set_level(LogLevel::INFO);
+
+        assert!(send(&addr, "POST", "/log-level", Some(TOKEN), "{\"level\": \"loud\"}").starts_with("HTTP/1.1 400"));
+        assert!(send(&addr, "GET", "/log-level", Some(TOKEN), "").starts_with("HTTP/1.1 405"));
+        assert!(send(&addr, "GET", "/nothing", Some(TOKEN), "").starts_with("HTTP/1.1 404"));
+    }
+}
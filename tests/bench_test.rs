@@ -0,0 +1,64 @@
+use katana::bench::{Bench, BenchReport};
+use katana::config::Config;
+use katana::server::Server;
+use katana::templates::Templates;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Test that URLs are split into an address to connect to and a request target
+    #[test]
+    fn test_target() {
+        assert_eq!(Bench::target("http://127.0.0.1:8080/a?b=c"), Ok(("127.0.0.1:8080".to_string(), "/a?b=c".to_string())));
+        assert_eq!(Bench::target("http://example.com"), Ok(("example.com:80".to_string(), "/".to_string())));
+        assert_eq!(Bench::target("http://[::1]?x"), Ok(("[::1]:80".to_string(), "/?x".to_string())));
+        assert_eq!(Bench::target("http://[::1]:9000/"), Ok(("[::1]:9000".to_string(), "/".to_string())));
+        assert!(Bench::target("https://example.com").is_err());
+        assert!(Bench::target("http:///path").is_err());
+    }
+
+    /// Test the nearest rank percentiles and the throughput
+    #[test]
+    fn test_report() {
+        let report = BenchReport {
+            elapsed: Duration::from_secs(2),
+            latencies: (1..=10).map(Duration::from_millis).collect(),
+            ..BenchReport::default()
+        };
+        assert_eq!(report.requests(), 10);
+        assert_eq!(report.throughput(), 5.0);
+        assert_eq!(report.percentile(50.0), Duration::from_millis(5));
+        assert_eq!(report.percentile(90.0), Duration::from_millis(9));
+        assert_eq!(report.percentile(100.0), Duration::from_millis(10));
+        assert_eq!(report.percentile(0.0), Duration::from_millis(1));
+        assert_eq!(BenchReport::default().percentile(99.0), Duration::ZERO);
+    }
+
+    /// Test a short run against a server that closes connections every few requests
+    #[test]
+    fn test_run() {
+        let root_dir = env::temp_dir().join("bench_test_run");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("index.html"), "hello").unwrap();
+        let args = ["", "--host", "127.0.0.1", "--port", "0", "--dir", &root_dir.to_string_lossy(), "--max-requests", "3"];
+        let server = Server::new(Config::parse_args(args.iter().map(|arg| arg.to_string()).collect()), Templates::load());
+        let listeners = server.listen().expect("Server should bind");
+        let url = listeners[0].url().expect("TCP listener should have a URL");
+        thread::spawn(move || server.run(listeners));
+
+        let bench = Bench { url: Some(format!("{}/", url)), connections: 2, duration: Duration::from_millis(300) };
+        let report = bench.run().unwrap();
+        assert!(report.requests() > 3, "{}", report);
+        assert_eq!(report.statuses.get(&200), Some(&report.requests()));
+        assert_eq!(report.errors, 0, "{}", report);
+        assert!(report.to_string().contains("requests/s"));
+
+        let bench = Bench { url: Some("ftp://example.com".to_string()), ..Bench::default() };
+        assert!(bench.run().is_err());
+    }
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "embed")]
+
+use katana::bundle::Bundle;
+use katana::config::Config;
+use katana::server::Server;
+use katana::templates::Templates;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::path::PathBuf;
+    use std::thread;
+
+    static SITE: Bundle = Bundle::new(&[
+        ("/app.js", b"console.log(1)"),
+        ("/docs/index.html", b"<h1>docs</h1>"),
+        ("/index.html", b"<h1>home</h1>"),
+    ]);
+
+    /// Test that directories are answered by their index.html
+    #[test]
+    fn test_get() {
+        assert_eq!(SITE.get("/app.js").map(|(_, content)| content), Some(&b"console.log(1)"[..]));
+        assert_eq!(SITE.get("/").map(|(name, _)| name), Some("/index.html"));
+        assert_eq!(SITE.get("/docs").map(|(name, _)| name), Some("/docs/index.html"));
+        assert_eq!(SITE.get("/docs/").map(|(name, _)| name), Some("/docs/index.html"));
+        assert_eq!(SITE.get("/app.js/"), None);
+        assert_eq!(SITE.get("/missing"), None);
+        assert_eq!(SITE.len(), 3);
+    }
+
+    /// Test that the generated source embeds every visible file under its request path
+    #[test]
+    fn test_generate() {
+        let dir = env::temp_dir().join("bundle_test_generate");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("assets")).unwrap();
+        fs::write(dir.join("index.html"), "home").unwrap();
+        fs::write(dir.join("assets/app.css"), "body{}").unwrap();
+        fs::write(dir.join(".env"), "SECRET=1").unwrap();
+
+        let source = Bundle::generate("SITE", &dir).unwrap();
+        let root = dir.canonicalize().unwrap();
+        assert!(source.starts_with("pub static SITE: katana::bundle::Bundle = katana::bundle::Bundle::new(&[\n"));
+        let css = format!("(\"/assets/app.css\", include_bytes!({:?}))", root.join("assets/app.css").to_string_lossy());
+        let html = format!("(\"/index.html\", include_bytes!({:?}))", root.join("index.html").to_string_lossy());
+        assert!(source.find(&css).unwrap() < source.find(&html).unwrap(), "Got '{}'", source);
+        assert!(!source.contains(".env"));
+    }
+
+    /// Test that a server with a bundle needs no root directory
+    #[test]
+    fn test_serve_bundle() {
+        let config = Config {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            root_dir: PathBuf::from("/nonexistent"),
+            bundle: Some(&SITE),
+            ..Config::default()
+        };
+        let server = Server::new(config, Templates::load());
+        let listeners = server.listen().unwrap();
+        let addr = listeners[0].url().unwrap().trim_start_matches("http://").to_string();
+        thread::spawn(move || server.run(listeners));
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(&addr).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let home = get("/");
+        assert!(home.starts_with("HTTP/1.1 200"), "Got '{}'", home);
+        assert!(home.contains("Content-Type: text/html"));
+        assert!(home.ends_with("<h1>home</h1>"));
+        assert!(get("/app.js").contains("Content-Type: application/javascript"));
+        assert!(get("/missing").starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,143 @@
+use katana::cgi::{Cgi, FastCgi};
+use katana::http::HttpStatus;
+use katana::request::Request;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::{Cursor, Read, Write};
+    use std::net::TcpListener;
+    use std::path::Path;
+    use std::thread;
+
+    /// Helper function that parses a request with a body
+    fn request(head: &str, body: &str) -> Request {
+        let raw = format!("{}\r\nContent-Length: {}\r\n\r\n{}", head, body.len(), body);
+        let mut reader = Cursor::new(raw.into_bytes());
+        let mut request = Request::read_head(&mut reader).unwrap();
+        request.read_body(&mut reader, 1024).unwrap();
+        request.set_peer(Some("203.0.113.7:51000".parse().unwrap()));
+        request
+    }
+
+    /// Test the meta-variables given to scripts
+    #[test]
+    fn test_environment() {
+        let request = request(
+            "POST /app/run.cgi?name=ada&x=1 HTTP/1.1\r\nHost: example.com:8080\r\nContent-Type: text/plain\r\nX-Trace: abc\r\nProxy: http://evil",
+            "hello",
+        );
+        let variables = Cgi::environment(&request, Path::new("/srv/www/app/run.cgi"), Path::new("/srv/www"));
+        let variable = |name: &str| variables.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+
+        assert_eq!(variable("REQUEST_METHOD"), Some("POST"));
+        assert_eq!(variable("QUERY_STRING"), Some("name=ada&x=1"));
+        assert_eq!(variable("SCRIPT_NAME"), Some("/app/run.cgi"));
+        assert_eq!(variable("SCRIPT_FILENAME"), Some("/srv/www/app/run.cgi"));
+        assert_eq!((variable("SERVER_NAME"), variable("SERVER_PORT")), (Some("example.com"), Some("8080")));
+        assert_eq!((variable("REMOTE_ADDR"), variable("REMOTE_PORT")), (Some("203.0.113.7"), Some("51000")));
+        assert_eq!((variable("CONTENT_LENGTH"), variable("CONTENT_TYPE")), (Some("5"), Some("text/plain")));
+        assert_eq!(variable("HTTP_X_TRACE"), Some("abc"));
+        assert_eq!(variable("HTTP_PROXY"), None, "httpoxy");
+        assert_eq!(variable("HTTP_CONTENT_LENGTH"), None);
+    }
+
+    /// Test the headers, status and body of script output
+    #[test]
+    fn test_parse_output() {
+        let output = Cgi::parse_output(b"Content-Type: text/plain\nStatus: 404 Not Found\n\nmissing").unwrap();
+        assert_eq!(output.status, HttpStatus::NotFound);
+        assert_eq!(output.headers, vec![("Content-Type".to_string(), "text/plain".to_string())]);
+        assert_eq!(output.body, b"missing");
+
+        let redirect = Cgi::parse_output(b"Location: /elsewhere\r\n\r\n").unwrap();
+        assert_eq!(redirect.status, HttpStatus::Found);
+        assert_eq!(Cgi::parse_output(b"Content-Type: text/html\r\n\r\n<p>\n\n</p>").unwrap().body, b"<p>\n\n</p>");
+
+        assert_eq!(Cgi::parse_output(b"no blank line"), None);
+        assert_eq!(Cgi::parse_output(b"Status: 299 Odd\n\n"), None);
+        assert_eq!(Cgi::parse_output(b"not a header\n\nbody"), None);
+    }
+
+    /// Test the name-value pair encoding, long lengths take four bytes
+    #[test]
+    fn test_encode_params() {
+        let long = "x".repeat(200);
+        let encoded = FastCgi::encode_params(&[("A".to_string(), "bc".to_string()), ("B".to_string(), long.clone())]);
+        assert_eq!(&encoded[..5], b"\x01\x02Abc");
+        assert_eq!(&encoded[5..11], b"\x01\x80\x00\x00\xC8B");
+        assert_eq!(&encoded[11..], long.as_bytes());
+    }
+
+    /// Test a script run as CGI, with its body, status and standard error
+    #[cfg(unix)]
+    #[test]
+    fn test_run() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = env::temp_dir().join("cgi_test_run");
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("echo.cgi");
+        fs::write(
+            &script,
+            "#!/bin/sh\necho 'oops' >&2\nprintf 'Status: 201 Created\\nContent-Type: text/plain\\n\\n'\n\
+             printf '%s %s ' \"$REQUEST_METHOD\" \"$QUERY_STRING\"\ncat\n",
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let output = Cgi::run(&script, &request("POST /echo.cgi?a=1 HTTP/1.1\r\nHost: test", "body"), &dir).unwrap();
+        assert_eq!(output.status, HttpStatus::Created);
+        assert_eq!(String::from_utf8_lossy(&output.body), "POST a=1 body");
+
+        let missing = Cgi::run(&dir.join("missing.cgi"), &request("GET / HTTP/1.1\r\nHost: test", ""), &dir);
+        assert_eq!(missing, Err(HttpStatus::BadGateway));
+    }
+
+    /// Test a request through a FastCGI responder
+    #[test]
+    fn test_fastcgi() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let responder = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // records up to the empty stdin one, the stdin content is answered back
+            let mut stdin = Vec::new();
+            loop {
+                let mut header = [0; 8];
+                stream.read_exact(&mut header).unwrap();
+                let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+                let mut content = vec![0; length + header[6] as usize];
+                stream.read_exact(&mut content).unwrap();
+                if header[1] == 5 {
+                    if length == 0 {
+                        break;
+                    }
+                    stdin.extend_from_slice(&content[..length]);
+                }
+            }
+            let mut reply = b"Content-Type: text/plain\r\n\r\ngot ".to_vec();
+            reply.extend(stdin);
+            let record = |kind: u8, content: &[u8]| {
+                let mut record = vec![1, kind, 0, 1];
+                record.extend_from_slice(&(content.len() as u16).to_be_bytes());
+                record.extend_from_slice(&[0, 0]);
+                record.extend_from_slice(content);
+                record
+            };
+            stream.write_all(&record(6, &reply)).unwrap();
+            stream.write_all(&record(6, &[])).unwrap();
+            stream.write_all(&record(3, &[0; 8])).unwrap();
+        });
+
+        let request = request("POST /index.php HTTP/1.1\r\nHost: test", "ping");
+        let output = FastCgi::run(&address, Path::new("/srv/index.php"), &request, Path::new("/srv")).unwrap();
+        responder.join().unwrap();
+        assert_eq!(output.status, HttpStatus::Ok);
+        assert_eq!(output.body, b"got ping");
+
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        assert_eq!(FastCgi::run(&closed, Path::new("/srv/index.php"), &request, Path::new("/srv")), Err(HttpStatus::BadGateway));
+    }
+}
@@ -0,0 +1,38 @@
+use katana::charset::{Charset, CharsetMode};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that byte order marks win and UTF-8 is told from Latin-1
+    #[test]
+    fn test_detect() {
+        assert_eq!(Charset::detect(b"\xEF\xBB\xBFplain"), Charset::Utf8);
+        assert_eq!(Charset::detect(b"\xFF\xFEh\0i\0"), Charset::Utf16Le);
+        assert_eq!(Charset::detect(b"\xFE\xFF\0h\0i"), Charset::Utf16Be);
+        assert_eq!(Charset::detect("café".as_bytes()), Charset::Utf8);
+        assert_eq!(Charset::detect(b""), Charset::Utf8);
+        assert_eq!(Charset::detect(b"caf\xE9 au lait"), Charset::Latin1);
+        // a sample cut in the middle of a character is still UTF-8
+        assert_eq!(Charset::detect(&"naïve".as_bytes()[..3]), Charset::Utf8);
+    }
+
+    /// Test the conversion to UTF-8
+    #[test]
+    fn test_to_utf8() {
+        assert_eq!(Charset::Latin1.to_utf8(b"caf\xE9"), "café".as_bytes());
+        assert_eq!(Charset::Utf16Le.to_utf8(b"\xFF\xFEh\0\xE9\0"), "hé".as_bytes());
+        assert_eq!(Charset::Utf16Be.to_utf8(b"\xFE\xFF\xD8\x3D\xDE\x00"), "😀".as_bytes());
+        assert_eq!(Charset::Utf16Le.to_utf8(b"\xFF\xFE\x00\xD8"), "\u{FFFD}".as_bytes(), "Unpaired surrogate");
+        assert_eq!(Charset::Utf8.to_utf8("déjà".as_bytes()), "déjà".as_bytes());
+    }
+
+    /// Test the --charset values
+    #[test]
+    fn test_mode() {
+        assert_eq!(CharsetMode::from_str("Transcode"), Some(CharsetMode::Transcode));
+        assert_eq!(CharsetMode::from_str("off"), Some(CharsetMode::Off));
+        assert_eq!(CharsetMode::from_str("latin1"), None);
+        assert_eq!(CharsetMode::default(), CharsetMode::Detect);
+    }
+}
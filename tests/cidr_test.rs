@@ -0,0 +1,41 @@
+use katana::cidr::Cidr;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    /// Test parsing of addresses and blocks
+    #[test]
+    fn test_parse() {
+        assert_eq!(Cidr::from_str("10.0.0.1").unwrap().to_string(), "10.0.0.1/32");
+        assert_eq!(Cidr::from_str("10.0.0.0/8").unwrap().to_string(), "10.0.0.0/8");
+        assert_eq!(Cidr::from_str("fd00::/8").unwrap().to_string(), "fd00::/8");
+        assert!(Cidr::from_str("10.0.0.0/33").is_none());
+        assert!(Cidr::from_str("::/129").is_none());
+        assert!(Cidr::from_str("proxy.local").is_none());
+        assert!(Cidr::from_str("10.0.0.0/x").is_none());
+    }
+
+    /// Test membership for IPv4, IPv6 and IPv4-mapped addresses
+    #[test]
+    fn test_contains() {
+        let block = Cidr::from_str("192.168.0.0/16").unwrap();
+        assert!(block.contains(ip("192.168.4.2")));
+        assert!(block.contains(ip("::ffff:192.168.4.2")));
+        assert!(!block.contains(ip("192.169.0.1")));
+        assert!(!block.contains(ip("fd00::1")));
+
+        let block = Cidr::from_str("fd00::/8").unwrap();
+        assert!(block.contains(ip("fd12:3456::1")));
+        assert!(!block.contains(ip("fe80::1")));
+
+        assert!(Cidr::from_str("0.0.0.0/0").unwrap().contains(ip("8.8.8.8")));
+        assert!(Cidr::from_str("127.0.0.1").unwrap().contains(ip("127.0.0.1")));
+        assert!(!Cidr::from_str("127.0.0.1").unwrap().contains(ip("127.0.0.2")));
+    }
+}
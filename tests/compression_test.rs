@@ -0,0 +1,115 @@
+use katana::compression::{Compression, ContentEncoding, Precompressed};
+use katana::ignore::Ignore;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn types(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    /// Test exact and wildcard content type patterns
+    #[test]
+    fn test_matches() {
+        let patterns = types(&["text/*", "application/json"]);
+        assert!(Compression::matches(&patterns, "text/html"));
+        assert!(Compression::matches(&patterns, "Text/CSS; charset=utf-8"));
+        assert!(Compression::matches(&patterns, "application/json"));
+        assert!(!Compression::matches(&patterns, "application/jsonp"));
+        assert!(!Compression::matches(&patterns, "image/png"));
+        assert!(Compression::matches(&types(&["*"]), "image/png"));
+    }
+
+    /// Test that the level, minimum size and type filters decide what is compressed
+    #[test]
+    fn test_should_compress() {
+        let compression = Compression::default();
+        assert!(compression.should_compress("text/html", 4096));
+        assert!(!compression.should_compress("text/html", 100), "Tiny bodies are skipped");
+        assert!(!compression.should_compress("image/png", 4096), "Compressed formats are skipped");
+
+        let excluded = Compression { exclude: types(&["text/csv"]), ..Compression::default() };
+        assert!(!excluded.should_compress("text/csv", 4096));
+        assert!(excluded.should_compress("text/plain", 4096));
+
+        let off = Compression { level: 0, ..Compression::default() };
+        assert!(!off.should_compress("text/html", 4096));
+
+        assert_eq!(Compression::parse_level("9"), Some(9));
+        assert_eq!(Compression::parse_level("10"), None);
+    }
+
+    /// Test the gzip framing and DEFLATE output against a known stream
+    #[test]
+    fn test_gzip() {
+        assert_eq!(Compression::crc32(b"123456789"), 0xcbf43926);
+
+        let gzipped = Compression::gzip(b"hello hello hello hello", 6);
+        assert_eq!(
+            gzipped,
+            vec![31, 139, 8, 0, 0, 0, 0, 0, 0, 255, 203, 72, 205, 201, 201, 87, 192, 32, 1, 227, 81, 61, 141, 23, 0, 0, 0]
+        );
+
+        let text = "<li><a href='/file.txt'>file.txt</a></li>".repeat(200);
+        let fast = Compression::gzip(text.as_bytes(), 1);
+        let best = Compression::gzip(text.as_bytes(), 9);
+        assert!(fast.len() < text.len() / 10);
+        assert!(best.len() <= fast.len());
+        assert_eq!(best[best.len() - 4..], (text.len() as u32).to_le_bytes());
+    }
+
+    /// Test Accept-Encoding negotiation with weights, wildcards and refused identity
+    #[test]
+    fn test_negotiate() {
+        let negotiate = |value: Option<&str>| ContentEncoding::negotiate(value);
+        assert_eq!(negotiate(None), Some(ContentEncoding::Identity));
+        assert_eq!(negotiate(Some("")), Some(ContentEncoding::Identity));
+        assert_eq!(negotiate(Some("gzip, deflate, br")), Some(ContentEncoding::Gzip));
+        assert_eq!(negotiate(Some("X-GZIP")), Some(ContentEncoding::Gzip));
+        assert_eq!(negotiate(Some("br;q=1.0, gzip;q=0.8, *;q=0.1")), Some(ContentEncoding::Gzip));
+        assert_eq!(negotiate(Some("gzip;q=0.5, identity")), Some(ContentEncoding::Identity));
+        assert_eq!(negotiate(Some("gzip;q=0")), Some(ContentEncoding::Identity));
+        assert_eq!(negotiate(Some("gzipped, br")), Some(ContentEncoding::Identity), "No substring matches");
+        assert_eq!(negotiate(Some("*")), Some(ContentEncoding::Gzip));
+        assert_eq!(negotiate(Some("br, identity;q=0")), None);
+        assert_eq!(negotiate(Some("*;q=0")), None);
+        assert_eq!(negotiate(Some("*;q=0, identity")), Some(ContentEncoding::Identity));
+        assert_eq!(negotiate(Some("gzip;q=2, identity;q=0.1")), Some(ContentEncoding::Identity), "Invalid weights are dropped");
+    }
+
+    /// Test that precompress writes sidecars of compressible files only and skips fresh ones
+    #[test]
+    fn test_precompress() {
+        let dir = env::temp_dir().join("compression_test_precompress");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("assets")).unwrap();
+        fs::create_dir_all(dir.join("drafts")).unwrap();
+        let text = "all work and no play ".repeat(100);
+        fs::write(dir.join("index.html"), &text).unwrap();
+        fs::write(dir.join("assets").join("app.js"), &text).unwrap();
+        fs::write(dir.join("small.css"), "body {}").unwrap();
+        fs::write(dir.join("photo.png"), &text).unwrap();
+        fs::write(dir.join(".env"), &text).unwrap();
+        fs::write(dir.join("drafts").join("post.html"), &text).unwrap();
+        let ignore = Ignore::new(&["drafts/".to_string()]);
+        let compression = Compression::default();
+
+        let report = compression.precompress(&dir, &ignore).unwrap();
+        assert_eq!(report, Precompressed { written: 2, fresh: 0 });
+        let gzipped = fs::read(dir.join("index.html.gz")).unwrap();
+        assert_eq!(gzipped, Compression::gzip(text.as_bytes(), Compression::DEFAULT_LEVEL));
+        assert!(dir.join("assets").join("app.js.gz").exists());
+        assert!(!dir.join("small.css.gz").exists());
+        assert!(!dir.join("photo.png.gz").exists());
+        assert!(!dir.join(".env.gz").exists());
+        assert!(!dir.join("drafts").join("post.html.gz").exists());
+        assert_eq!(Compression::sidecar(&dir.join("index.html")), Some(dir.join("index.html.gz")));
+        assert_eq!(Compression::sidecar(&dir.join("small.css")), None);
+
+        let report = compression.precompress(&dir, &ignore).unwrap();
+        assert_eq!(report, Precompressed { written: 0, fresh: 2 });
+    }
+}
@@ -1,18 +1,28 @@
+use katana::charset::CharsetMode;
 use katana::config::Config;
+use katana::etag::ETag;
+use katana::handler::Handler;
+use katana::hooks::HookEvent;
+use katana::http::HttpMethod;
+use katana::jwt::JwtKey;
+use katana::logger::{LogFormat, LogLevel};
+use katana::proxy::Balance;
+use katana::service::ServiceAction;
+use katana::tls::{ClientAuth, TlsPolicy, TlsVersion};
+use katana::wellknown::Robots;
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::path::PathBuf;
+    use std::time::Duration;
 
     fn get_host() -> String {
-        let host = if cfg!(target_family = "windows") {
+        if cfg!(target_family = "windows") {
             "127.0.0.1".to_string()
         } else {
             "0.0.0.0".to_string()
-        };
-
-        host
+        }
     }
 
     /// Test case for when no arguments are passed.
@@ -139,4 +149,1066 @@ mod tests {
 
         assert_eq!(config.worker, 8);
     }
+
+    /// Test case for the keep-alive defaults.
+    #[test]
+    fn test_keep_alive_defaults() {
+        let config = Config::parse_args(vec!["".to_string()]);
+
+        assert_eq!(config.keep_alive_timeout, Config::DEFAULT_KEEP_ALIVE_TIMEOUT);
+        assert_eq!(config.max_requests, Config::DEFAULT_MAX_REQUESTS);
+        assert!(config.keep_alive_enabled());
+    }
+
+    /// Test case for tuning the keep-alive timeout and max requests per connection.
+    #[test]
+    fn test_keep_alive_options() {
+        let args = vec![
+            "".to_string(),
+            "--keep-alive-timeout".to_string(),
+            "0".to_string(),
+            "--max-requests".to_string(),
+            "0".to_string(),
+        ];
+        let config = Config::parse_args(args);
+
+        assert_eq!(config.keep_alive_timeout, 0);
+        assert!(!config.keep_alive_enabled());
+        assert_eq!(config.max_requests, Config::DEFAULT_MAX_REQUESTS); // 0 is rejected
+    }
+
+    /// Test case for the shutdown grace period.
+    #[test]
+    fn test_grace_period() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert_eq!(config.grace_period, Duration::from_secs(10));
+
+        let config = Config::parse_args(vec!["".to_string(), "--grace-period".to_string(), "30s".to_string()]);
+        assert_eq!(config.grace_period, Duration::from_secs(30));
+        let config = Config::parse_args(vec!["".to_string(), "--grace-period".to_string(), "0".to_string()]);
+        assert_eq!(config.grace_period, Duration::ZERO);
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--grace-period".to_string(), "soon".to_string()]);
+        assert_eq!(errors, vec!["grace period must be a duration such as 10s or 1m"]);
+    }
+
+    /// Test case for the request body size limit.
+    #[test]
+    fn test_max_body_size() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert_eq!(config.max_body_size, Config::DEFAULT_MAX_BODY_SIZE);
+
+        let args = vec!["".to_string(), "--max-body-size".to_string(), "2M".to_string()];
+        let config = Config::parse_args(args);
+        assert_eq!(config.max_body_size, 2 * 1024 * 1024);
+
+        let args = vec!["".to_string(), "--max-body-size".to_string(), "huge".to_string()];
+        let config = Config::parse_args(args);
+        assert_eq!(config.max_body_size, Config::DEFAULT_MAX_BODY_SIZE);
+    }
+
+    /// Test case for translating config file lines into options.
+    #[test]
+    fn test_parse_file() {
+        let content = "# comment\n\nport = 9000\ndir = \"site root\"\nwatch_config = true\nunused = false\n";
+        let args = Config::parse_file(content).unwrap();
+
+        assert_eq!(
+            args,
+            vec!["--port", "9000", "--dir", "site root", "--watch-config"]
+        );
+        assert!(Config::parse_file("port 9000").is_err(), "Missing '=' should be rejected");
+    }
+
+    /// Test that a section prefixes the keys after it
+    #[test]
+    fn test_parse_file_section() {
+        let content = "port = 9000\n[tls]\nmin_version = \"1.3\"\ncurves = [\"X25519\"]\n[ocsp]\nstapling = true\n";
+        let args = Config::parse_file(content).unwrap();
+        assert_eq!(
+            args,
+            vec!["--port", "9000", "--tls-min-version", "1.3", "--tls-curves", "X25519", "--ocsp-stapling"]
+        );
+        assert!(Config::parse_file("[tls policy]\nmin_version = 1.2").is_err());
+    }
+
+    /// Test case for the language of built-in pages, extra message catalogs and templates.
+    #[test]
+    fn test_locale_and_messages() {
+        let path = std::env::temp_dir().join("katana_config_test_messages.txt");
+        std::fs::write(&path, "error.home = Torna alla home\n").unwrap();
+        let messages = format!("it={}", path.display());
+        let args = vec!["", "--locale", "fr", "--messages", &messages];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.locale.as_deref(), Some("fr"));
+        assert_eq!(config.messages.len(), 1);
+        assert_eq!(config.messages[0].0, "it");
+        assert_eq!(config.messages[0].1["error.home"], "Torna alla home");
+
+        let templates = std::env::temp_dir().join("katana_config_test_templates");
+        std::fs::create_dir_all(&templates).unwrap();
+        let templates = templates.to_string_lossy().to_string();
+        let config = Config::parse_args(vec!["".to_string(), "--templates".to_string(), templates.clone()]);
+        assert_eq!(config.templates_dir, Some(PathBuf::from(templates)));
+
+        let args = vec!["", "--locale", "not a tag", "--messages", "it", "--messages", "it=/nonexistent/messages.txt"];
+        let (_, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert_eq!(errors.len(), 3);
+        let (_, errors) = Config::parse(vec!["".to_string(), "--templates".to_string(), "/nonexistent".to_string()]);
+        assert_eq!(errors.len(), 1);
+    }
+
+    /// Test case for the snippets injected into pages.
+    #[test]
+    fn test_inject_snippets() {
+        let path = std::env::temp_dir().join("katana_config_test_snippet.html");
+        std::fs::write(&path, "<div>banner</div>").unwrap();
+        let path = path.to_string_lossy().to_string();
+        let args = vec!["", "--inject-body", &path, "--inject-files"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.inject_body.as_deref(), Some("<div>banner</div>"));
+        assert_eq!(config.inject_head, None);
+        assert!(config.inject_files);
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--inject-head".to_string(), "/nonexistent.html".to_string()]);
+        assert_eq!(errors.len(), 1);
+    }
+
+    /// Test case for loading a config file and overriding it from the command line.
+    #[test]
+    fn test_config_file_with_override() {
+        let path = std::env::temp_dir().join("katana_config_test.conf");
+        std::fs::write(&path, "port = 9000\ndir = \"from_file\"\n").unwrap();
+
+        let args = vec![
+            "".to_string(),
+            "--config".to_string(),
+            path.to_string_lossy().to_string(),
+            "--port".to_string(),
+            "9001".to_string(),
+        ];
+        let config = Config::parse_args(args);
+
+        assert_eq!(config.port, 9001); // command line wins over the file
+        assert_eq!(config.root_dir, PathBuf::from("from_file"));
+        assert_eq!(config.config_file, Some(path.clone()));
+
+        // an invalid file on reload is reported and must not replace the config
+        std::fs::write(&path, "max_requests = 0\n").unwrap();
+        assert!(config.reload().is_err());
+
+        std::fs::write(&path, "port = 9000\ndir = \"reloaded\"\n").unwrap();
+        assert_eq!(config.reload().unwrap().root_dir, PathBuf::from("reloaded"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Test case for passing several listen addresses.
+    #[test]
+    fn test_multiple_listen_addresses() {
+        let args = vec![
+            "".to_string(),
+            "--listen".to_string(),
+            "127.0.0.1:8080".to_string(),
+            "--listen".to_string(),
+            "[::1]:8080".to_string(),
+            "--listen".to_string(),
+            ":8443".to_string(),
+            "--listen".to_string(),
+            "no-port".to_string(),
+        ];
+        let (config, errors) = Config::parse(args);
+
+        assert_eq!(
+            config.listen,
+            vec![
+                "127.0.0.1:8080".to_string(),
+                "[::1]:8080".to_string(),
+                format!("{}:8443", get_host()),
+            ]
+        );
+        assert_eq!(errors.len(), 1, "Address without a port should be reported");
+    }
+
+    /// Test case for list values in a config file.
+    #[test]
+    fn test_parse_file_list() {
+        let args = Config::parse_file("listen = [\"127.0.0.1:80\", \"[::1]:80\"]").unwrap();
+
+        assert_eq!(args, vec!["--listen", "127.0.0.1:80", "--listen", "[::1]:80"]);
+    }
+
+    /// Test case for unix socket listeners and their permissions.
+    #[test]
+    fn test_unix_socket_listener() {
+        let args = vec![
+            "".to_string(),
+            "--listen".to_string(),
+            "unix:/run/katana.sock".to_string(),
+            "--listen".to_string(),
+            "unix:".to_string(),
+            "--socket-mode".to_string(),
+            "660".to_string(),
+        ];
+        let (config, errors) = Config::parse(args);
+
+        assert_eq!(config.listen, vec!["unix:/run/katana.sock".to_string()]);
+        assert_eq!(config.socket_mode, Some(0o660));
+        assert_eq!(errors.len(), 1, "Empty socket path should be reported");
+    }
+
+    /// Test case for the SO_REUSEPORT worker mode.
+    #[test]
+    fn test_workers_mode() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.reuse_port, "Worker mode should be opt-in");
+
+        let args = vec!["".to_string(), "--workers".to_string(), "8".to_string()];
+        let config = Config::parse_args(args);
+        assert_eq!(config.worker, 8);
+        assert!(config.reuse_port);
+
+        let args = vec!["".to_string(), "--workers".to_string(), "0".to_string()];
+        let (config, errors) = Config::parse(args);
+        assert!(!config.reuse_port);
+        assert_eq!(errors.len(), 1);
+    }
+
+    /// Test case for daemon mode options.
+    #[test]
+    fn test_daemon_options() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.daemon);
+        assert_eq!(config.pid_file, PathBuf::from("katana.pid"));
+        assert_eq!(config.log_file, PathBuf::from("katana.log"));
+
+        let args = vec![
+            "".to_string(),
+            "--daemon".to_string(),
+            "--pid-file".to_string(),
+            "/run/katana.pid".to_string(),
+            "--log-file".to_string(),
+            "/var/log/katana.log".to_string(),
+        ];
+        let config = Config::parse_args(args);
+        assert!(config.daemon);
+        assert_eq!(config.pid_file, PathBuf::from("/run/katana.pid"));
+        assert_eq!(config.log_file, PathBuf::from("/var/log/katana.log"));
+    }
+
+    /// Test case for trusted proxy addresses.
+    #[test]
+    fn test_trusted_proxies() {
+        let args = vec![
+            "".to_string(),
+            "--trusted-proxy".to_string(),
+            "10.0.0.1".to_string(),
+            "--trusted-proxy".to_string(),
+            "::1".to_string(),
+            "--trusted-proxy".to_string(),
+            "172.16.0.0/12".to_string(),
+            "--trusted-proxy".to_string(),
+            "proxy.local".to_string(),
+        ];
+        let (config, errors) = Config::parse(args);
+        assert_eq!(config.trusted_proxies.len(), 3);
+        assert_eq!(errors.len(), 1, "Host names are not accepted");
+
+        assert!(config.is_trusted_proxy("10.0.0.1".parse().unwrap()));
+        assert!(config.is_trusted_proxy("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(config.is_trusted_proxy("::1".parse().unwrap()));
+        assert!(!config.is_trusted_proxy("10.0.0.2".parse().unwrap()));
+        assert!(config.is_trusted_proxy("172.20.1.1".parse().unwrap()));
+
+        let args = vec!["", "--method-override", "10.0.0.0/8", "--method-override", "office"];
+        let (config, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert!(config.allows_method_override("10.1.2.3".parse().unwrap()));
+        assert!(!config.allows_method_override("192.168.1.1".parse().unwrap()));
+        assert_eq!(errors, vec!["invalid method override address: office"]);
+    }
+
+    /// Test case for the log format option.
+    #[test]
+    fn test_log_format() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert_eq!(config.log_format, LogFormat::Plain);
+
+        let args = vec!["".to_string(), "--log-format".to_string(), "JSON".to_string()];
+        assert_eq!(Config::parse_args(args).log_format, LogFormat::Json);
+
+        let args = vec!["".to_string(), "--log-format".to_string(), "xml".to_string()];
+        let (config, errors) = Config::parse(args);
+        assert_eq!(config.log_format, LogFormat::Plain);
+        assert_eq!(errors.len(), 1);
+    }
+
+    /// Test case for the log level and quiet mode.
+    #[test]
+    fn test_log_level() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert_eq!(config.log_level, LogLevel::INFO);
+        assert!(!config.quiet);
+
+        let args = vec![
+            "".to_string(),
+            "--log-level".to_string(),
+            "warn".to_string(),
+            "--quiet".to_string(),
+        ];
+        let config = Config::parse_args(args);
+        assert_eq!(config.log_level, LogLevel::WARN);
+        assert!(config.quiet);
+
+        let args = vec!["".to_string(), "--no-color".to_string()];
+        assert!(Config::parse_args(args).no_color);
+
+        let args = vec!["".to_string(), "--no-qr".to_string()];
+        assert!(Config::parse_args(args).no_qr);
+
+        let args = vec![
+            "".to_string(),
+            "--syslog".to_string(),
+            "udp://logs:514".to_string(),
+            "--syslog-facility".to_string(),
+            "local3".to_string(),
+        ];
+        let config = Config::parse_args(args);
+        assert_eq!(config.syslog.as_deref(), Some("udp://logs:514"));
+        assert_eq!(config.syslog_facility, 19);
+
+        let args = vec!["".to_string(), "--error-log".to_string(), "error.log".to_string()];
+        assert_eq!(Config::parse_args(args).error_log, Some(PathBuf::from("error.log")));
+
+        let args = vec!["".to_string(), "--log-level".to_string(), "loud".to_string()];
+        let (config, errors) = Config::parse(args);
+        assert_eq!(config.log_level, LogLevel::INFO);
+        assert_eq!(errors.len(), 1);
+    }
+
+    /// Test case for the slow request threshold.
+    #[test]
+    fn test_slow_request() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert_eq!(config.slow_request, None);
+
+        let args = vec!["".to_string(), "--slow-request".to_string(), "2s".to_string()];
+        assert_eq!(Config::parse_args(args).slow_request, Some(Duration::from_secs(2)));
+
+        let args = vec!["".to_string(), "--slow-request".to_string(), "0".to_string()];
+        assert_eq!(Config::parse_args(args).slow_request, None);
+
+        let args = vec!["".to_string(), "--slow-request".to_string(), "soon".to_string()];
+        assert_eq!(Config::parse(args).1.len(), 1);
+    }
+
+    /// Test case for live reload being separate from config watching.
+    #[test]
+    fn test_watch_mode() {
+        let config = Config::parse_args(vec!["".to_string(), "--watch".to_string()]);
+        assert!(config.watch);
+        assert!(!config.watch_config);
+    }
+
+    /// Test that naming the mDNS instance also turns advertising on
+    #[test]
+    fn test_mdns() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.mdns);
+        assert_eq!(config.mdns_name, "Katana");
+
+        let config = Config::parse_args(vec!["".to_string(), "--mdns".to_string()]);
+        assert!(config.mdns);
+
+        let config = Config::parse_args(vec!["".to_string(), "--mdns-name".to_string(), " Team share ".to_string()]);
+        assert!(config.mdns);
+        assert_eq!(config.mdns_name, "Team share");
+    }
+
+    /// Test the ACME challenge directory and domains
+    #[test]
+    fn test_acme() {
+        let args = vec!["", "--acme-dir", "/var/lib/katana/acme", "--acme-domain", "Example.com.", "--acme-domain", "www.example.com"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.acme_dir, Some(PathBuf::from("/var/lib/katana/acme")));
+        assert_eq!(config.acme_domains, vec!["example.com", "www.example.com"]);
+    }
+
+    /// Test the HTTPS redirect settings
+    #[test]
+    fn test_https_redirect() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.https_redirect);
+        assert_eq!(config.https_port, 443);
+
+        let args = vec!["", "--https-redirect", "--https-port", "8443"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert!(config.https_redirect);
+        assert_eq!(config.https_port, 8443);
+    }
+
+    /// Test the TLS policy presets and the settings overriding them, refused while no handshake would use them
+    #[test]
+    fn test_tls_policy() {
+        let parse = |args: &[&str]| Config::parse(std::iter::once("").chain(args.iter().copied()).map(String::from).collect());
+        let refused = "TLS policy settings need TLS termination, which this build does not have";
+
+        let (config, errors) = parse(&[]);
+        assert!(errors.is_empty());
+        assert_eq!(config.tls_policy, TlsPolicy::preset("intermediate").unwrap());
+
+        let (config, errors) = parse(&["--tls-policy", "modern"]);
+        assert_eq!(errors, vec![refused]);
+        assert_eq!(config.tls_policy.min_version, TlsVersion::Tls13);
+        assert_eq!(config.tls_policy.ciphers.len(), 3);
+
+        // a higher minimum leaves out the preset's suites for TLS 1.2
+        let (config, errors) = parse(&["--tls-min-version", "1.3"]);
+        assert_eq!(errors, vec![refused]);
+        assert_eq!(config.tls_policy.ciphers, TlsPolicy::preset("modern").unwrap().ciphers);
+
+        // explicit settings win over the preset whatever their order
+        let args = [
+            "--tls-ciphers",
+            "tls_aes_256_gcm_sha384:TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+            "--tls-curves",
+            "secp384r1",
+            "--tls-policy",
+            "modern",
+            "--tls-min-version",
+            "TLSv1.2",
+        ];
+        let (config, errors) = parse(&args);
+        assert_eq!(errors, vec![refused]);
+        assert_eq!(config.tls_policy.min_version, TlsVersion::Tls12);
+        assert_eq!(config.tls_policy.ciphers, vec!["TLS_AES_256_GCM_SHA384", "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384"]);
+        assert_eq!(config.tls_policy.curves, vec!["secp384r1"]);
+
+        let (_, errors) = parse(&["--tls-policy", "old", "--tls-min-version", "1.0", "--tls-ciphers", "RC4-MD5", "--tls-curves", "P-192"]);
+        assert_eq!(errors.len(), 5);
+        let (_, errors) = parse(&["--tls-policy", "modern", "--tls-ciphers", "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256"]);
+        assert_eq!(
+            errors,
+            vec![
+                "cipher TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256 is for TLS 1.2, which --tls-min-version 1.3 turns off",
+                "no cipher for TLS 1.3 in --tls-ciphers",
+                refused,
+            ]
+        );
+    }
+
+    /// Test that OCSP stapling is opt-in, and refused while no handshake would staple
+    #[test]
+    fn test_ocsp_stapling() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.ocsp_stapling);
+
+        let args = vec!["", "--ocsp-stapling"];
+        let (config, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert!(config.ocsp_stapling);
+        assert_eq!(errors, vec!["--ocsp-stapling needs TLS termination, which this build does not have"]);
+    }
+
+    /// Test that TLS certificates accumulate, bad specifications are rejected and serving them is refused
+    #[test]
+    fn test_tls_certs() {
+        let args = vec!["", "--tls-cert", "a.com=a.crt,a.key", "--tls-cert", "b.crt,b.key", "--tls-cert", "broken"];
+        let (config, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert_eq!(config.tls_certs.len(), 2);
+        assert_eq!(config.tls_certs[0].names, vec!["a.com"]);
+        assert!(config.tls_certs[1].names.is_empty());
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1], "--tls-cert needs TLS termination, which this build does not have");
+    }
+
+    /// Test that a client CA bundle requires client certificates unless told otherwise
+    #[test]
+    fn test_client_auth() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert_eq!(config.client_auth, ClientAuth::Off);
+
+        let config = Config::parse_args(vec!["".to_string(), "--client-ca".to_string(), "ca.pem".to_string()]);
+        assert_eq!(config.client_ca, Some(PathBuf::from("ca.pem")));
+        assert_eq!(config.client_auth, ClientAuth::Required);
+
+        let args = vec!["", "--client-cert", "optional", "--client-ca", "ca.pem"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.client_auth, ClientAuth::Optional);
+
+        // refused until the server terminates TLS itself
+        let (_, errors) = Config::parse(vec!["".to_string(), "--client-ca".to_string(), "ca.pem".to_string()]);
+        assert_eq!(errors, vec!["client certificates need TLS termination, which this build does not have"]);
+        let (_, errors) = Config::parse(vec!["".to_string(), "--client-cert".to_string(), "required".to_string()]);
+        assert_eq!(errors.len(), 1);
+        let (_, errors) = Config::parse(vec!["".to_string(), "--client-cert".to_string(), "off".to_string()]);
+        assert!(errors.is_empty());
+    }
+
+    /// Test that JWT keys protect everything by default and protected paths need a key
+    #[test]
+    fn test_jwt() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.jwt.enabled());
+
+        let args = vec!["", "--jwt-secret", "s3cret", "--jwt-audience", "katana", "--jwt-issuer", "https://issuer.example"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.jwt.keys, vec![JwtKey::Hmac(b"s3cret".to_vec())]);
+        assert_eq!(config.jwt.protected, vec!["/"]);
+        assert_eq!(config.jwt.audience.as_deref(), Some("katana"));
+        assert_eq!(config.jwt.issuer.as_deref(), Some("https://issuer.example"));
+
+        let args = vec!["", "--jwt-secret", "s3cret", "--jwt-protect", "/api", "--jwt-protect", "/admin"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.jwt.protected, vec!["/api", "/admin"]);
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--jwt-protect".to_string(), "/api".to_string()]);
+        assert_eq!(errors, vec!["protected paths need a --jwt-secret or --jwt-jwks to verify tokens"]);
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--jwt-jwks".to_string(), "/nonexistent/jwks.json".to_string()]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("cannot read JWKS /nonexistent/jwks.json"));
+    }
+
+    /// Test the signed link options and the `sign` command
+    #[test]
+    fn test_signed_urls() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert_eq!(config.signed_urls.secret, None);
+        assert_eq!(config.sign_path, None);
+        assert_eq!(config.sign_lifetime, Duration::from_secs(3600));
+
+        let args = vec!["", "sign", "/files/a.zip", "--sign-secret", "s3cret", "--sign-expires", "2d", "--sign-protect", "/files"];
+        let (config, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(config.sign_path.as_deref(), Some("/files/a.zip"));
+        assert_eq!(config.signed_urls.secret.as_deref(), Some(&b"s3cret"[..]));
+        assert_eq!(config.signed_urls.protected, vec!["/files"]);
+        assert_eq!(config.sign_lifetime, Duration::from_secs(2 * 24 * 3600));
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--sign-protect".to_string(), "/files".to_string()]);
+        assert_eq!(errors, vec!["signed links need a --sign-secret"]);
+        let (_, errors) = Config::parse(vec!["".to_string(), "--sign-expires".to_string(), "0".to_string()]);
+        assert_eq!(errors, vec!["signed link lifetime must be a duration such as 30m or 7d"]);
+    }
+
+    /// Test that `check` reports the files and listeners a server would trip on
+    #[test]
+    fn test_check() {
+        let dir = std::env::temp_dir().join("katana_config_test_check");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("public")).unwrap();
+        std::fs::create_dir_all(dir.join("plugins")).unwrap();
+        std::fs::write(dir.join("cert.pem"), "-----BEGIN CERTIFICATE-----\n").unwrap();
+        std::fs::write(dir.join("key.pem"), "not a key").unwrap();
+        std::fs::write(dir.join("plugins").join("broken.wasm"), "text").unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        let args = vec!["".to_string(), "-t".to_string(), "--dir".to_string(), path("public")];
+        let (config, errors) = Config::parse(args);
+        assert!(config.check);
+        assert!(errors.is_empty() && config.check().is_empty(), "{:?}", config.check());
+
+        let args = vec![
+            "".to_string(), "check".to_string(), "--dir".to_string(), path("missing"),
+            "--tls-cert".to_string(), format!("{},{}", path("cert.pem"), path("key.pem")),
+            "--plugins-dir".to_string(), path("plugins"), "--favicon".to_string(), path("favicon.ico"),
+            "--listen".to_string(), "127.0.0.1:8080".to_string(), "--listen".to_string(), "127.0.0.1:8080".to_string(),
+        ];
+        let (config, errors) = Config::parse(args);
+        assert_eq!(errors, vec!["--tls-cert needs TLS termination, which this build does not have"]);
+        let problems = config.check();
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+        assert_eq!(problems[0], format!("root directory not found: {}", path("missing")));
+        assert_eq!(problems[1], format!("{} has no PEM PRIVATE KEY", path("key.pem")));
+        assert!(problems[2].starts_with(&format!("cannot read {}", path("favicon.ico"))));
+        assert!(problems[3].starts_with("invalid plugin"));
+        assert_eq!(problems[4], "127.0.0.1:8080 is listened on twice");
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--port".to_string(), "http".to_string()]);
+        assert_eq!(errors, vec!["invalid port: http"]);
+    }
+
+    /// Test the `bench` command and its options
+    #[test]
+    fn test_bench() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert_eq!(config.bench.url, None);
+        assert_eq!(config.bench.connections, 10);
+        assert_eq!(config.bench.duration, Duration::from_secs(10));
+
+        let args = vec!["", "bench", "http://localhost:8080/", "--bench-connections", "64", "--bench-duration", "1m"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.bench.url.as_deref(), Some("http://localhost:8080/"));
+        assert_eq!(config.bench.connections, 64);
+        assert_eq!(config.bench.duration, Duration::from_secs(60));
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--bench-connections".to_string(), "0".to_string()]);
+        assert_eq!(errors, vec!["bench connections must be a positive number: 0"]);
+    }
+
+    /// Test the version options
+    #[test]
+    fn test_version() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.version && !config.build_header);
+        assert!(Config::parse_args(vec!["".to_string(), "-V".to_string()]).version);
+        let config = Config::parse_args(vec!["".to_string(), "--version".to_string(), "--build-header".to_string()]);
+        assert!(config.version && config.build_header);
+    }
+
+    /// Test the `init` command and its --with-templates option
+    #[test]
+    fn test_init() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.init && !config.init_templates);
+
+        let config = Config::parse_args(vec!["".to_string(), "init".to_string(), "--with-templates".to_string()]);
+        assert!(config.init && config.init_templates);
+    }
+
+    /// Test the maintenance mode options
+    #[test]
+    fn test_maintenance() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.maintenance.enabled);
+        assert_eq!(config.maintenance.retry_after, 300);
+
+        let args = vec!["", "--maintenance", "--maintenance-page", "down.html", "--retry-after", "60"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert!(config.maintenance.enabled);
+        assert_eq!(config.maintenance.page, Some(PathBuf::from("down.html")));
+        assert_eq!(config.maintenance.retry_after, 60);
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--retry-after".to_string(), "soon".to_string()]);
+        assert_eq!(errors, vec!["retry after must be a number of seconds"]);
+    }
+
+    /// Test that the plugins directory is taken as given
+    #[test]
+    fn test_plugins_dir() {
+        assert_eq!(Config::parse_args(vec!["".to_string()]).plugins_dir, None);
+        let config = Config::parse_args(vec!["".to_string(), "--plugins-dir".to_string(), "plugins".to_string()]);
+        assert_eq!(config.plugins_dir, Some(PathBuf::from("plugins")));
+    }
+
+    /// Test headers added to every response and the listing policy
+    #[test]
+    fn test_headers_and_listing() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(config.listing);
+        assert!(config.headers.is_empty());
+
+        let args = vec!["", "--header", "X-Frame-Options:DENY", "--header", "Cache-Control: max-age=60", "--no-listing"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert!(!config.listing);
+        assert_eq!(
+            config.headers,
+            vec![
+                ("X-Frame-Options".to_string(), "DENY".to_string()),
+                ("Cache-Control".to_string(), "max-age=60".to_string())
+            ]
+        );
+
+        let config = Config::parse_args(vec!["".to_string(), "--listing-page-size".to_string(), "50".to_string()]);
+        assert_eq!(config.listing_page_size, 50);
+        let (_, errors) = Config::parse(vec!["".to_string(), "--listing-page-size".to_string(), "0".to_string()]);
+        assert_eq!(errors.len(), 1);
+
+        let args = vec!["", "--dir", "/nonexistent", "--ignore", "*.log", "--ignore", "node_modules/"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert!(config.ignore.is_ignored("/app/debug.log", false));
+        assert!(config.ignore.is_ignored("/node_modules", true));
+        assert!(!config.ignore.is_ignored("/index.html", false));
+
+        let args = vec!["", "--header", "no colon", "--header", "Content-Length: 1", "--header", "Bad Name: x"];
+        let (_, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert_eq!(errors.len(), 3);
+    }
+
+    /// Test hotlink protection settings
+    #[test]
+    fn test_hotlink() {
+        let args = vec!["", "--hotlink-protect", ".JPG, png", "--hotlink-allow", "Partner.org.", "--hotlink-block-empty"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.hotlink.extensions, vec!["jpg".to_string(), "png".to_string()]);
+        assert_eq!(config.hotlink.allowed, vec!["partner.org".to_string()]);
+        assert!(config.hotlink.block_empty);
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--hotlink-block-empty".to_string()]);
+        assert_eq!(errors, vec!["hotlink settings need extensions set with --hotlink-protect"]);
+    }
+
+    /// Test the ETag strategy and checksums
+    #[test]
+    fn test_etag() {
+        assert_eq!(Config::parse_args(vec!["".to_string()]).etag, ETag::Weak);
+        let config = Config::parse_args(vec!["".to_string(), "--etag".to_string(), "strong".to_string()]);
+        assert_eq!(config.etag, ETag::Strong);
+        let (_, errors) = Config::parse(vec!["".to_string(), "--etag".to_string(), "md5".to_string()]);
+        assert_eq!(errors, vec!["etag must be weak, strong or off: md5"]);
+        assert!(Config::parse_args(vec!["".to_string(), "--checksums".to_string()]).checksums);
+    }
+
+    /// Test the --charset option
+    #[test]
+    fn test_charset() {
+        assert_eq!(Config::parse_args(vec!["".to_string()]).charset, CharsetMode::Detect);
+        let config = Config::parse_args(vec!["".to_string(), "--charset".to_string(), "transcode".to_string()]);
+        assert_eq!(config.charset, CharsetMode::Transcode);
+        let (_, errors) = Config::parse(vec!["".to_string(), "--charset".to_string(), "ascii".to_string()]);
+        assert_eq!(errors, vec!["charset must be detect, transcode or off: ascii"]);
+    }
+
+    /// Test the per-extension handlers, env is denied unless overridden
+    #[test]
+    fn test_handler() {
+        assert_eq!(Config::parse_args(vec!["".to_string()]).handlers.for_path("/.env"), &Handler::Deny);
+        let args = vec!["", "--handler", ".PHP=fastcgi:unix:/run/php.sock", "--handler", "env=static"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.handlers.for_path("/index.php"), &Handler::FastCgi("unix:/run/php.sock".to_string()));
+        assert_eq!(config.handlers.for_path("/.env"), &Handler::Static);
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--handler".to_string(), "php=python".to_string()]);
+        assert_eq!(errors, vec!["handler must be static, cgi, fastcgi:address or deny: python"]);
+    }
+
+    /// Test the reverse proxy settings
+    #[test]
+    fn test_proxy() {
+        let args = vec!["", "--proxy", "127.0.0.1:3000", "--proxy", "http://127.0.0.1:3001", "--proxy-balance", "least-connections"];
+        let mut args: Vec<String> = args.into_iter().map(String::from).collect();
+        args.extend(["--proxy-max-fails", "3", "--proxy-fail-timeout", "30s"].map(String::from));
+        let config = Config::parse_args(args);
+        assert_eq!(config.proxy.upstreams.len(), 2);
+        assert_eq!(config.proxy.balance, Balance::LeastConnections);
+        assert_eq!((config.proxy.max_fails, config.proxy.fail_timeout), (3, Duration::from_secs(30)));
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--proxy-balance".to_string(), "random".to_string()]);
+        assert_eq!(errors, vec!["proxy balance must be round-robin or least-connections: random"]);
+        let (_, errors) = Config::parse(vec!["".to_string(), "--proxy-max-fails".to_string(), "0".to_string()]);
+        assert_eq!(errors, vec!["proxy max fails must be a positive number: 0"]);
+    }
+
+    /// Test the upstream health check settings
+    #[test]
+    fn test_proxy_health() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert_eq!(config.proxy.health.path, None);
+        let args = vec!["", "--proxy-health-path", "/healthz", "--proxy-health-interval", "2s", "--proxy-healthy-threshold", "1"];
+        let mut args: Vec<String> = args.into_iter().map(String::from).collect();
+        args.extend(["--proxy-unhealthy-threshold", "5"].map(String::from));
+        let health = Config::parse_args(args).proxy.health;
+        assert_eq!(health.path.as_deref(), Some("/healthz"));
+        assert_eq!(health.interval, Duration::from_secs(2));
+        assert_eq!((health.healthy_threshold, health.unhealthy_threshold), (1, 5));
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--proxy-health-path".to_string(), "healthz".to_string()]);
+        assert_eq!(errors, vec!["proxy health path must start with /: healthz"]);
+        let (_, errors) = Config::parse(vec!["".to_string(), "--proxy-unhealthy-threshold".to_string(), "x".to_string()]);
+        assert_eq!(errors, vec!["proxy unhealthy threshold must be a positive number: x"]);
+    }
+
+    /// Test the forward proxy switch and its allowed destinations
+    #[test]
+    fn test_forward_proxy() {
+        assert!(!Config::parse_args(vec!["".to_string()]).forward_proxy.enabled);
+        let args = vec!["", "--forward-proxy", "--forward-allow", "*.Lab.internal:443", "--forward-allow", "10.0.0.5:*"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert!(config.forward_proxy.enabled);
+        assert_eq!(config.forward_proxy.allowed[0], ("*.lab.internal".to_string(), "443".to_string()));
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--forward-allow".to_string(), "10.0.0.5".to_string()]);
+        assert_eq!(errors, vec!["forward proxy destination must be host:port, with * for any host or port: 10.0.0.5"]);
+    }
+
+    /// Test the redirect rules
+    #[test]
+    fn test_redirects() {
+        let args = vec!["", "--redirect", "/old /new", "--redirect", "/blog/* /posts/{rest} 308"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.redirects.rules.len(), 2);
+        assert!(config.redirects.rules[1].prefix);
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--redirect".to_string(), "/old /new 200".to_string()]);
+        assert_eq!(errors, vec!["redirect status must be 301, 302, 307 or 308: 200"]);
+    }
+
+    /// Test robots.txt and security.txt settings
+    #[test]
+    fn test_well_known_files() {
+        let args = vec!["", "--robots", "deny", "--security-contact", "mailto:a@b.c", "--security-expires", "30d"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.robots, Some(Robots::DenyAll));
+        assert_eq!(config.security_txt.contacts, vec!["mailto:a@b.c".to_string()]);
+        assert_eq!(config.security_txt.expires, Duration::from_secs(30 * 86400));
+
+        let args = vec!["", "--security-policy", "https://b.c/policy", "--security-expires", "soon"];
+        let (_, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert_eq!(errors.len(), 2);
+    }
+
+    /// Test hooks parsing
+    #[test]
+    fn test_hooks() {
+        let args = vec!["", "--hook", "upload=./scan.sh \"$1\"", "--hook", "error = notify-send", "--hook-timeout", "3s"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(
+            config.hooks.commands,
+            vec![
+                (HookEvent::Upload, "./scan.sh \"$1\"".to_string()),
+                (HookEvent::ServerError, "notify-send".to_string())
+            ]
+        );
+        assert_eq!(config.hooks.timeout, Duration::from_secs(3));
+
+        let args = vec!["", "--hook", "download=true", "--hook", "request=", "--hook-timeout", "0"];
+        let (_, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert_eq!(errors.len(), 3);
+    }
+
+    /// Test that the admin API cannot be opened without a token
+    #[test]
+    fn test_admin() {
+        let args = vec!["", "--admin-listen", "127.0.0.1:9090", "--admin-token", "t0ken"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.admin_listen.as_deref(), Some("127.0.0.1:9090"));
+        assert_eq!(config.admin_token.as_deref(), Some("t0ken"));
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--admin-listen".to_string(), ":9090".to_string()]);
+        assert_eq!(errors, vec!["the admin API needs an --admin-token"]);
+    }
+
+    /// Test that --writable adds POST, MOVE and DELETE to the allowed methods
+    #[test]
+    fn test_writable() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.methods_for("/notes.txt").contains(&HttpMethod::DELETE));
+        assert!(!config.methods_for("/").contains(&HttpMethod::POST));
+
+        let config = Config::parse_args(vec!["".to_string(), "--read-only=false".to_string(), "--writable".to_string()]);
+        assert!(config.writable);
+        assert_eq!(config.methods_for("/notes.txt").last(), Some(&HttpMethod::DELETE));
+        assert!(config.methods_for("/").contains(&HttpMethod::POST));
+
+        let args = vec!["", "--read-only=false", "--writable", "--allowed-methods", "GET,HEAD"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.methods_for("/"), vec![HttpMethod::GET, HttpMethod::HEAD, HttpMethod::POST, HttpMethod::MOVE, HttpMethod::DELETE]);
+    }
+
+    /// Test that the share is read only by default and writes need it turned off explicitly
+    #[test]
+    fn test_read_only() {
+        let (config, errors) = Config::parse(vec!["".to_string()]);
+        assert!(config.read_only && errors.is_empty());
+        for path in ["/", "/notes.txt", "*"] {
+            assert_eq!(config.methods_for(path), vec![HttpMethod::GET, HttpMethod::HEAD, HttpMethod::OPTIONS]);
+        }
+
+        let args = vec!["", "--writable", "--tus", "/uploads/"];
+        let (config, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert_eq!(
+            errors,
+            vec![
+                "--writable needs --read-only=false, the share is read only by default",
+                "--tus needs --read-only=false, the share is read only by default",
+            ]
+        );
+        assert!(!config.writable && !config.tus.enabled());
+        assert_eq!(config.methods_for("/uploads/"), vec![HttpMethod::GET, HttpMethod::HEAD, HttpMethod::OPTIONS]);
+
+        let args = vec!["", "--read-only=false", "--writable", "--read-only"];
+        let (config, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert!(config.read_only && !config.writable);
+        assert_eq!(errors.len(), 1);
+
+        let args = vec!["", "--read-only=false", "--writable", "--tus", "/uploads/"];
+        let (config, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert!(!config.read_only && config.writable && config.tus.enabled() && errors.is_empty());
+
+        let args = Config::parse_file("read_only = false\nwritable = true\n").unwrap();
+        assert_eq!(args, vec!["--read-only=false", "--writable"]);
+        let args = Config::parse_file("read_only = true\n").unwrap();
+        assert_eq!(args, vec!["--read-only"]);
+    }
+
+    /// Test that user directories are opt-in and their directory stays inside the home
+    #[test]
+    fn test_userdir() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.userdir.enabled);
+
+        let args = vec!["", "--userdir", "--userdir-dir", "www"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert!(config.userdir.enabled);
+        assert_eq!(config.userdir.dir, "www");
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--userdir-dir".to_string(), "www".to_string()]);
+        assert_eq!(errors, vec!["the user directory needs --userdir"]);
+        let (_, errors) = Config::parse(vec!["".to_string(), "--userdir".to_string(), "--userdir-dir".to_string(), "../x".to_string()]);
+        assert_eq!(errors.len(), 1);
+    }
+
+    /// Test that the container profile logs JSON and shuts down fast unless told otherwise
+    #[test]
+    fn test_container() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.container);
+        assert_eq!(config.log_format, LogFormat::Plain);
+        assert_eq!(config.grace_period, Config::DEFAULT_GRACE_PERIOD);
+
+        let config = Config::parse_args(vec!["".to_string(), "--container".to_string()]);
+        assert!(config.container);
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(config.grace_period, Config::CONTAINER_GRACE_PERIOD);
+        assert!(config.no_color);
+        assert!(config.no_qr);
+
+        let args = vec!["", "--log-format", "plain", "--container", "--grace-period", "20s"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.log_format, LogFormat::Plain);
+        assert_eq!(config.grace_period, Duration::from_secs(20));
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--container".to_string(), "--daemon".to_string()]);
+        assert_eq!(errors, vec!["--daemon cannot be used with --container, the container runtime keeps it running"]);
+    }
+
+    /// Test that secret options are read from the environment or a mounted file
+    #[test]
+    fn test_secrets() {
+        std::env::set_var("CONFIG_TEST_ADMIN_TOKEN", "t0ken");
+        let path = std::env::temp_dir().join("config_test_sign_secret");
+        std::fs::write(&path, "signing key\n").unwrap();
+        let args = vec![
+            "".to_string(),
+            "--admin-token".to_string(),
+            "env:CONFIG_TEST_ADMIN_TOKEN".to_string(),
+            "--sign-secret".to_string(),
+            format!("file:{}", path.display()),
+        ];
+        let config = Config::parse_args(args);
+        assert_eq!(config.admin_token.as_deref(), Some("t0ken"));
+        assert_eq!(config.signed_urls.secret, Some(b"signing key".to_vec()));
+        std::fs::remove_file(&path).unwrap();
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--jwt-secret".to_string(), "env:CONFIG_TEST_UNSET".to_string()]);
+        assert_eq!(errors, vec!["environment variable CONFIG_TEST_UNSET is not set"]);
+    }
+
+    /// Test the service actions, their name and directory, and that a running service logs to the event log
+    #[test]
+    fn test_service() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert_eq!(config.service.action, None);
+        assert_eq!(config.service.name, "Katana");
+
+        let args = vec!["", "service", "install", "--service-name", "Docs", "--dir", "docs"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.service.action, Some(ServiceAction::Install));
+        assert_eq!(config.service.name, "Docs");
+        assert_eq!(config.syslog, None);
+
+        let args = vec!["", "service", "run", "--service-dir", "C:\\sites"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.service.action, Some(ServiceAction::Run));
+        assert_eq!(config.service.dir, Some(PathBuf::from("C:\\sites")));
+        assert_eq!(config.syslog.as_deref(), Some("eventlog"));
+
+        let args = vec!["", "service", "run", "--syslog", "udp://10.0.0.1:514"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.syslog.as_deref(), Some("udp://10.0.0.1:514"));
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "service".to_string(), "start".to_string()]);
+        assert_eq!(errors, vec!["unknown service action: start, use install, uninstall or run"]);
+        let (_, errors) = Config::parse(vec!["".to_string(), "service".to_string(), "install".to_string(), "--daemon".to_string()]);
+        assert_eq!(errors, vec!["--daemon cannot be used with a service, the service manager runs it in the background"]);
+    }
+
+    /// Test the account and chroot taken after binding, and that a chroot keeps to one root
+    #[test]
+    fn test_privileges() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.privileges.enabled());
+
+        let args = vec!["", "--user", "www-data", "--group", "www-data", "--chroot"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.privileges.user.as_deref(), Some("www-data"));
+        assert_eq!(config.privileges.group.as_deref(), Some("www-data"));
+        assert!(config.privileges.chroot);
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--chroot".to_string(), "--userdir".to_string()]);
+        assert_eq!(errors, vec!["--chroot cannot be used with virtual hosts or --userdir, their files are outside the root"]);
+    }
+
+    /// Test that the upload endpoint is a path and its settings need one
+    #[test]
+    fn test_tus() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert!(!config.tus.enabled());
+
+        let args = vec!["", "--read-only=false", "--tus", "/uploads/", "--tus-dir", "/var/uploads", "--tus-max-size", "1G"];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.tus.endpoint.as_deref(), Some("/uploads"));
+        assert_eq!(config.tus.dir, Some(PathBuf::from("/var/uploads")));
+        assert_eq!(config.tus.max_size, Some(1 << 30));
+        assert!(config.methods_for("/uploads/0123456789abcdef").contains(&HttpMethod::PATCH));
+        assert!(!config.methods_for("/index.html").contains(&HttpMethod::PATCH));
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--read-only=false".to_string(), "--tus".to_string(), "uploads".to_string()]);
+        assert_eq!(errors, vec!["the upload endpoint must be a path such as /uploads"]);
+        let (_, errors) = Config::parse(vec!["".to_string(), "--tus-dir".to_string(), "/tmp".to_string()]);
+        assert_eq!(errors, vec!["upload settings need an endpoint set with --tus"]);
+    }
+
+    /// Test that compression types replace the defaults, levels are bounded and the `precompress` command
+    #[test]
+    fn test_compression() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert_eq!(config.compression.level, 6);
+        assert_eq!(config.compression.min_size, 1024);
+        assert!(config.compression.include.contains(&"text/*".to_string()));
+        assert!(!config.precompress);
+
+        let args = vec![
+            "", "precompress", "--compression-level", "9", "--compression-min-size", "4K",
+            "--compress-type", "text/html", "--compress-type", "application/json", "--no-compress-type", "text/csv",
+        ];
+        let config = Config::parse_args(args.into_iter().map(String::from).collect());
+        assert_eq!(config.compression.level, 9);
+        assert_eq!(config.compression.min_size, 4096);
+        assert_eq!(config.compression.include, vec!["text/html", "application/json"]);
+        assert_eq!(config.compression.exclude, vec!["text/csv"]);
+        assert!(config.precompress);
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--compression-level".to_string(), "12".to_string()]);
+        assert_eq!(errors, vec!["compression level must be between 0 and 9: 12"]);
+    }
+
+    /// Test that a default language turns on negotiation and must be a language tag
+    #[test]
+    fn test_default_language() {
+        assert_eq!(Config::parse_args(vec!["".to_string()]).default_language, None);
+
+        let config = Config::parse_args(vec!["".to_string(), "--default-language".to_string(), "en-GB".to_string()]);
+        assert_eq!(config.default_language.as_deref(), Some("en-GB"));
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--default-language".to_string(), "english".to_string()]);
+        assert_eq!(errors, vec!["invalid language tag: english"]);
+    }
+
+    /// Test the allowed methods default and that only supported methods can be enabled
+    #[test]
+    fn test_allowed_methods() {
+        let config = Config::parse_args(vec!["".to_string()]);
+        assert_eq!(config.allowed_methods, vec![HttpMethod::GET, HttpMethod::HEAD, HttpMethod::OPTIONS]);
+
+        let config = Config::parse_args(vec!["".to_string(), "--allowed-methods".to_string(), "get, HEAD,options,GET".to_string()]);
+        assert_eq!(config.allowed_methods, vec![HttpMethod::GET, HttpMethod::HEAD, HttpMethod::OPTIONS]);
+
+        assert_eq!(Config::parse_methods("GET,BREW"), Err("unsupported method: BREW".to_string()));
+        assert_eq!(Config::parse_methods("GET,POST"), Err("unsupported method: POST".to_string()));
+        assert_eq!(
+            Config::parse_methods("GET,TRACE"),
+            Err("TRACE cannot be allowed, it enables cross-site tracing".to_string())
+        );
+        assert_eq!(Config::parse_methods(" , "), Err("at least one method must be allowed".to_string()));
+    }
 }
@@ -0,0 +1,39 @@
+use katana::connection::BodyCounter;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Test that only the bytes following the headers are counted
+    #[test]
+    fn test_body_counter() {
+        let mut sink = Vec::new();
+        let mut counter = BodyCounter::new(&mut sink);
+        counter.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+        assert_eq!(counter.body_bytes, 5);
+        counter.write_all(b" world").unwrap();
+        assert_eq!(counter.body_bytes, 11);
+    }
+
+    /// Test that a header terminator split across writes is still found
+    #[test]
+    fn test_body_counter_split_writes() {
+        let mut sink = Vec::new();
+        let mut counter = BodyCounter::new(&mut sink);
+        counter.write_all(b"HTTP/1.1 200 OK\r\nServer: Katana\r\n\r").unwrap();
+        counter.write_all(b"\nbody").unwrap();
+        assert_eq!(counter.body_bytes, 4);
+    }
+
+    /// Test that a HEAD-style response sends no body bytes
+    #[test]
+    fn test_body_counter_headers_only() {
+        let mut sink = Vec::new();
+        let mut counter = BodyCounter::new(&mut sink);
+        counter.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 42\r\n").unwrap();
+        counter.write_all(b"\r\n").unwrap();
+        assert_eq!(counter.body_bytes, 0);
+        assert_eq!(sink.len(), 39);
+    }
+}
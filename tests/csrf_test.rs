@@ -0,0 +1,59 @@
+use katana::csrf::Csrf;
+use katana::request::Request;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Helper function that reads a POST with the given headers and body
+    fn post(headers: &str, body: &str) -> Request {
+        let raw = format!("POST /docs/ HTTP/1.1\r\nHost: katana.local\r\n{}Content-Length: {}\r\n\r\n{}", headers, body.len(), body);
+        let mut reader = Cursor::new(raw.into_bytes());
+        let mut request = Request::read_head(&mut reader).unwrap();
+        request.read_body(&mut reader, 1024).unwrap();
+        request
+    }
+
+    /// Test that the token of a valid cookie is kept and a new one issued otherwise
+    #[test]
+    fn test_token() {
+        let token = "0123456789abcdef0123456789abcdef";
+        let request = post(&format!("Cookie: katana_csrf={}\r\n", token), "");
+        assert_eq!(Csrf::token(&request), token);
+        assert_eq!(Csrf::cookie(token, &request), None);
+
+        let request = post("Cookie: katana_csrf=forged\r\n", "");
+        let issued = Csrf::token(&request);
+        assert_eq!(issued.len(), 32);
+        assert_ne!(issued, Csrf::token(&request));
+        assert_eq!(Csrf::cookie(&issued, &request), Some(format!("{}; Path=/; HttpOnly; SameSite=Strict", issued)));
+
+        let mut request = post("", "");
+        request.scheme = "https".to_string();
+        assert!(Csrf::cookie(&issued, &request).unwrap().ends_with("; Secure"));
+    }
+
+    /// Test that browser requests must send the token of their cookie and other clients need none
+    #[test]
+    fn test_verify() {
+        let token = "0123456789abcdef0123456789abcdef";
+        let cookie = format!("Cookie: katana_csrf={}\r\n", token);
+        let origin = "Origin: http://katana.local\r\n";
+        let form = "Content-Type: application/x-www-form-urlencoded\r\n";
+
+        assert!(Csrf::verify(&post("", "")), "curl sends no Origin");
+        assert!(!Csrf::verify(&post(origin, "")));
+        assert!(!Csrf::verify(&post(&format!("{}{}", origin, cookie), "")));
+        assert!(!Csrf::verify(&post("Sec-Fetch-Site: cross-site\r\nX-CSRF-Token: guess\r\n", "")));
+        assert!(Csrf::verify(&post(&format!("{}{}X-CSRF-Token: {}\r\n", origin, cookie, token), "")));
+        assert!(Csrf::verify(&post(&format!("{}{}{}", origin, cookie, form), &format!("folder=a&_csrf={}", token))));
+        assert!(!Csrf::verify(&post(&format!("{}{}", origin, form), &format!("folder=a&_csrf={}", token))));
+
+        let multipart = "Content-Type: multipart/form-data; boundary=b\r\n";
+        let body = format!("--b\r\nContent-Disposition: form-data; name=\"_csrf\"\r\n\r\n{}\r\n--b--\r\n", token);
+        assert!(Csrf::verify(&post(&format!("{}{}{}", origin, cookie, multipart), &body)));
+        let body = format!("--b\r\nContent-Disposition: form-data; name=\"_csrf\"; filename=\"t\"\r\n\r\n{}\r\n--b--\r\n", token);
+        assert!(!Csrf::verify(&post(&format!("{}{}{}", origin, cookie, multipart), &body)));
+    }
+}
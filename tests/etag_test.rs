@@ -0,0 +1,47 @@
+use katana::etag::ETag;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    /// Test the tags of each strategy and that strong ones follow the content
+    #[test]
+    fn test_for_file() {
+        let dir = env::temp_dir().join("etag_test_for_file");
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+        fs::write(&a, "same").unwrap();
+        fs::write(&b, "same").unwrap();
+        let tag = |etag: ETag, path| etag.for_file(path, &fs::metadata(path).unwrap());
+
+        let weak = tag(ETag::Weak, &a).unwrap();
+        assert!(weak.starts_with("W/\"4-"), "Got '{}'", weak);
+        assert_eq!(tag(ETag::Off, &a), None);
+
+        let strong = tag(ETag::Strong, &a).unwrap();
+        assert_eq!(strong.len(), 34);
+        assert_eq!(tag(ETag::Strong, &b), Some(strong.clone()), "same content, same tag");
+        fs::write(&a, "changed").unwrap();
+        assert_ne!(tag(ETag::Strong, &a), Some(strong));
+    }
+
+    /// Test the weak comparison of If-None-Match and the strong one of If-Match
+    #[test]
+    fn test_matches() {
+        assert!(ETag::matches("\"abc\"", "\"abc\""));
+        assert!(ETag::matches("W/\"abc\"", "\"abc\""));
+        assert!(ETag::matches("\"x\", W/\"abc\"", "W/\"abc\""));
+        assert!(ETag::matches("*", "\"abc\""));
+        assert!(!ETag::matches("\"abcd\"", "\"abc\""));
+        assert_eq!(ETag::weaken("\"abc\""), "W/\"abc\"");
+        assert_eq!(ETag::weaken("W/\"abc\""), "W/\"abc\"");
+        assert!(ETag::matches_strong("\"x\", \"abc\"", "\"abc\""));
+        assert!(!ETag::matches_strong("W/\"abc\"", "\"abc\""));
+        assert!(!ETag::matches_strong("\"abc\"", "W/\"abc\""));
+        assert!(ETag::matches_strong("*", "W/\"abc\""));
+        assert_eq!(ETag::from_str("Strong"), Some(ETag::Strong));
+        assert_eq!(ETag::from_str("md5"), None);
+    }
+}
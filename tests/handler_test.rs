@@ -0,0 +1,50 @@
+use katana::handler::{Handler, Handlers};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that handlers are picked by the extension of the last segment
+    #[test]
+    fn test_for_path() {
+        let mut handlers = Handlers::default();
+        handlers.add("php=fastcgi:127.0.0.1:9000").unwrap();
+        handlers.add(".CGI=cgi").unwrap();
+        handlers.add("bak=deny").unwrap();
+
+        assert_eq!(handlers.for_path("/index.php"), &Handler::FastCgi("127.0.0.1:9000".to_string()));
+        assert_eq!(handlers.for_path("/bin/run.cgi"), &Handler::Cgi);
+        assert_eq!(handlers.for_path("/db.sql.BAK"), &Handler::Deny);
+        assert_eq!(handlers.for_path("/.env"), &Handler::Deny, "Denied by default");
+        assert_eq!(handlers.for_path("/config/production.env"), &Handler::Deny);
+        assert_eq!(handlers.for_path("/style.css"), &Handler::Static);
+        assert_eq!(handlers.for_path("/php.d/README"), &Handler::Static, "Only the file name counts");
+    }
+
+    /// Test that later rules win and bad ones are refused
+    #[test]
+    fn test_add() {
+        let mut handlers = Handlers::default();
+        handlers.add("env=static").unwrap();
+        assert_eq!(handlers.for_path("/.env"), &Handler::Static);
+
+        assert!(handlers.add("php").is_err());
+        assert!(handlers.add("=cgi").is_err());
+        assert!(handlers.add("php=fastcgi:").is_err());
+        assert!(handlers.add("php=proxy").is_err());
+        assert!(handlers.add("tar.gz=deny").is_err());
+    }
+
+    /// Test whether the effective rules run a program
+    #[test]
+    fn test_runs_programs() {
+        let mut handlers = Handlers::default();
+        assert!(!handlers.runs_programs());
+        handlers.add("php=fastcgi:127.0.0.1:9000").unwrap();
+        assert!(handlers.runs_programs());
+        handlers.add("PHP=static").unwrap();
+        assert!(!handlers.runs_programs(), "A later rule overrides it");
+        handlers.add("pl=cgi").unwrap();
+        assert!(handlers.runs_programs());
+    }
+}
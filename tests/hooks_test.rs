@@ -0,0 +1,103 @@
+use katana::config::Config;
+use katana::hooks::{HookEvent, Hooks};
+use katana::json::Json;
+use katana::plugin::{Plugin, PluginAction, RequestView};
+use katana::server::Server;
+use katana::templates::Templates;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::path::Path;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Helper function that waits for a hook to write its output
+    fn wait_for(path: &Path) -> String {
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(5) {
+            if let Ok(content) = fs::read_to_string(path) {
+                if content.ends_with('}') {
+                    return content;
+                }
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        panic!("{} was never written", path.display());
+    }
+
+    /// Test that events are parsed by name
+    #[test]
+    fn test_parse() {
+        assert_eq!(Hooks::parse("request=logger"), Ok((HookEvent::Request, "logger".to_string())));
+        assert_eq!(Hooks::parse("error=a=b"), Ok((HookEvent::ServerError, "a=b".to_string())));
+        assert!(Hooks::parse("logger").is_err());
+        assert!(Hooks::parse("upload= ").is_err());
+    }
+
+    /// Test that the payload reaches stdin and slow commands are killed
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_run() {
+        let out = env::temp_dir().join("hooks_test_run.json");
+        let _ = fs::remove_file(&out);
+        let command = format!("cat > {}", out.display());
+        let status = Hooks::run(&command, "{\"event\":\"request\"}", Duration::from_secs(5)).unwrap();
+        assert!(status.success());
+        assert_eq!(fs::read_to_string(&out).unwrap(), "{\"event\":\"request\"}");
+
+        assert!(!Hooks::run("exit 3", "", Duration::from_secs(5)).unwrap().success());
+
+        let started = Instant::now();
+        assert!(Hooks::run("sleep 10", "", Duration::from_millis(200)).is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    /// Plugin that panics on every request, as a buggy handler would
+    struct Faulty;
+
+    impl Plugin for Faulty {
+        fn name(&self) -> &str {
+            "faulty"
+        }
+
+        fn on_request(&self, _request: &mut RequestView) -> PluginAction {
+            panic!("faulty plugin");
+        }
+    }
+
+    /// Test that a server error runs the hook with the request in the payload
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_server_error_hook() {
+        let root_dir = env::temp_dir().join("hooks_test_server");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("LICENSE"), "license").unwrap();
+        let out = env::temp_dir().join("hooks_test_server.json");
+        let _ = fs::remove_file(&out);
+
+        let hook = format!("error=cat > {}", out.display());
+        let args = vec!["", "--host", "127.0.0.1", "--port", "0", "--dir", root_dir.to_str().unwrap(), "--hook", &hook];
+        let mut server = Server::new(Config::parse_args(args.into_iter().map(String::from).collect()), Templates::load());
+        server.add_plugin(Faulty);
+        let listeners = server.listen().unwrap();
+        let addr = listeners[0].url().unwrap().trim_start_matches("http://").to_string();
+        thread::spawn(move || server.run(listeners));
+
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        write!(stream, "GET /LICENSE HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 500"), "Got '{}'", response);
+
+        let payload = Json::parse(&wait_for(&out)).unwrap();
+        assert_eq!(payload.get("event").and_then(Json::as_str), Some("server_error"));
+        assert_eq!(payload.get("path").and_then(Json::as_str), Some("/LICENSE"));
+        assert_eq!(payload.get("method").and_then(Json::as_str), Some("GET"));
+        assert_eq!(payload.get("status").and_then(Json::as_f64), Some(500.0));
+    }
+}
@@ -0,0 +1,61 @@
+use katana::hotlink::Hotlink;
+use katana::request::Request;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Helper function that reads a request for a host with an optional Referer
+    fn request(host: &str, referer: Option<&str>) -> Request {
+        let referer = referer.map(|referer| format!("Referer: {}\r\n", referer)).unwrap_or_default();
+        let raw = format!("GET /photo.jpg HTTP/1.1\r\nHost: {}\r\n{}\r\n", host, referer);
+        Request::read_head(&mut Cursor::new(raw.into_bytes())).unwrap()
+    }
+
+    fn hotlink() -> Hotlink {
+        Hotlink {
+            extensions: vec!["jpg".to_string(), "mp4".to_string()],
+            allowed: vec!["partner.org".to_string(), "*.example.net".to_string()],
+            ..Hotlink::default()
+        }
+    }
+
+    /// Test that only the configured extensions are protected
+    #[test]
+    fn test_protects() {
+        let hotlink = hotlink();
+        assert!(hotlink.protects("/gallery/photo.JPG"));
+        assert!(hotlink.protects("/clip.mp4"));
+        assert!(!hotlink.protects("/index.html"));
+        assert!(!hotlink.protects("/jpg"));
+        assert!(!hotlink.protects("/photos.jpg/"));
+        assert!(!Hotlink::default().enabled());
+    }
+
+    /// Test that the own host and the allowed ones may embed, nobody else
+    #[test]
+    fn test_allows() {
+        let hotlink = hotlink();
+        assert!(hotlink.allows(&request("share.local:8080", Some("http://share.local:8080/gallery/"))));
+        assert!(hotlink.allows(&request("share.local", Some("https://partner.org/post"))));
+        assert!(hotlink.allows(&request("share.local", Some("https://blog.example.net/"))));
+        assert!(!hotlink.allows(&request("share.local", Some("https://example.net/"))));
+        assert!(!hotlink.allows(&request("share.local", Some("https://evil.com/?share.local"))));
+        assert!(!hotlink.allows(&request("share.local", Some("not a url"))));
+
+        assert!(hotlink.allows(&request("share.local", None)));
+        let strict = Hotlink { block_empty: true, ..hotlink };
+        assert!(!strict.allows(&request("share.local", None)));
+        assert!(strict.allows(&request("share.local", Some("http://share.local/"))));
+    }
+
+    /// Test that the host is taken from the authority only
+    #[test]
+    fn test_host() {
+        assert_eq!(Hotlink::host("https://User:pw@Example.com.:443/a?b#c"), Some("example.com".to_string()));
+        assert_eq!(Hotlink::host("http://[::1]:8080/"), Some("[::1]".to_string()));
+        assert_eq!(Hotlink::host("https:///path"), None);
+        assert_eq!(Hotlink::host("example.com/path"), None);
+    }
+}
@@ -0,0 +1,62 @@
+use katana::ignore::Ignore;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn ignore(lines: &[&str]) -> Ignore {
+        Ignore::new(&lines.iter().map(|line| line.to_string()).collect::<Vec<_>>())
+    }
+
+    /// Test the glob syntax: * and ? stay within a segment, ** crosses them
+    #[test]
+    fn test_glob() {
+        assert!(Ignore::glob("*.log", "debug.log"));
+        assert!(!Ignore::glob("*.log", "logs/debug.log"));
+        assert!(Ignore::glob("file?.txt", "file1.txt"));
+        assert!(Ignore::glob("build/**", "build/a/b.o"));
+        assert!(Ignore::glob("**/cache", "cache"));
+        assert!(Ignore::glob("**/cache", "a/b/cache"));
+        assert!(Ignore::glob("a/**/z", "a/z"));
+        assert!(Ignore::glob("[a-c]at", "bat"));
+        assert!(!Ignore::glob("[!a-c]at", "bat"));
+        assert!(!Ignore::glob("build/**", "build"));
+    }
+
+    /// Test that names match at any depth, slashes anchor and trailing slashes need a directory
+    #[test]
+    fn test_is_ignored() {
+        let ignore = ignore(&["# comment", "", "node_modules/", "*.log", "/build", "docs/private", "!keep.log"]);
+        assert!(ignore.is_ignored("/node_modules", true));
+        assert!(ignore.is_ignored("web/node_modules/react/index.js", false));
+        assert!(!ignore.is_ignored("node_modules", false), "a file named like an ignored directory");
+        assert!(ignore.is_ignored("/logs/debug.log", false));
+        assert!(!ignore.is_ignored("/logs/keep.log", false));
+        assert!(ignore.is_ignored("/build/app.js", false));
+        assert!(!ignore.is_ignored("/src/build", true), "anchored to the root");
+        assert!(ignore.is_ignored("docs/private/plan.md", false));
+        assert!(!ignore.is_ignored("/docs/public.md", false));
+        assert!(!Ignore::default().is_ignored("/anything", false));
+    }
+
+    /// Test that nothing below an ignored directory can be brought back
+    #[test]
+    fn test_negation_below_ignored_directory() {
+        let ignore = ignore(&["build/", "!build/README.md"]);
+        assert!(ignore.is_ignored("build/README.md", false));
+    }
+
+    /// Test that the ignore file of the root is read after the given patterns
+    #[test]
+    fn test_load() {
+        let root = env::temp_dir().join("ignore_test_load");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(Ignore::FILE), "# generated\n!keep.tmp\n").unwrap();
+        let ignore = Ignore::load(&root, &["*.tmp".to_string()]);
+        assert!(ignore.is_ignored("a.tmp", false));
+        assert!(!ignore.is_ignored("keep.tmp", false));
+        assert!(Ignore::load(&env::temp_dir().join("ignore_test_missing"), &[]).is_empty());
+    }
+}
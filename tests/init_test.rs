@@ -0,0 +1,48 @@
+use katana::config::Config;
+use katana::init::Init;
+use katana::templates::Templates;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    /// Test that the scaffolded config changes nothing until its lines are uncommented
+    #[test]
+    fn test_config() {
+        assert_eq!(Config::parse_file(Init::CONFIG).unwrap(), Vec::<String>::new());
+
+        let uncommented: String = Init::CONFIG
+            .lines()
+            .filter_map(|line| line.strip_prefix("# ").filter(|line| line.contains(" = ") || line.starts_with('[')))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let args = Config::parse_file(&uncommented).unwrap();
+        assert!(args.contains(&"--port".to_string()));
+        assert!(args.contains(&"--no-listing".to_string()));
+    }
+
+    /// Test that init writes the config, the templates when asked, and never overwrites
+    #[test]
+    fn test_run() {
+        let dir = env::temp_dir().join("init_test_run");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let (created, kept) = Init::run(&dir, false).unwrap();
+        assert_eq!(created, vec![dir.join("katana.toml")]);
+        assert!(kept.is_empty());
+        assert!(!dir.join("templates").exists());
+
+        fs::write(dir.join("katana.toml"), "port = 9000\n").unwrap();
+        let (created, kept) = Init::run(&dir, true).unwrap();
+        assert_eq!(kept, vec![dir.join("katana.toml")]);
+        assert_eq!(fs::read_to_string(dir.join("katana.toml")).unwrap(), "port = 9000\n");
+        assert_eq!(created.len(), 5);
+        assert!(created.contains(&dir.join("templates").join("messages").join("en.txt")));
+
+        let templates = Templates::load_dir(&dir.join("templates")).unwrap();
+        assert_eq!(templates.error, Templates::load().error);
+    }
+}
@@ -0,0 +1,71 @@
+use katana::language::Language;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn languages(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    /// Test Accept-Language parsing and ordering by weight
+    #[test]
+    fn test_parse_accept() {
+        let ranges = Language::parse_accept("fr-CH, fr;q=0.9, en;q=0.8, de;q=0.7, *;q=0.5");
+        let names: Vec<&str> = ranges.iter().map(|(range, _)| range.as_str()).collect();
+        assert_eq!(names, vec!["fr-ch", "fr", "en", "de", "*"]);
+
+        let ranges = Language::parse_accept("en;q=0.2, de, nl;q=0, es;q=oops");
+        let names: Vec<&str> = ranges.iter().map(|(range, _)| range.as_str()).collect();
+        assert_eq!(names, vec!["de", "en"]);
+    }
+
+    /// Test language tag recognition in file names
+    #[test]
+    fn test_is_tag() {
+        assert!(Language::is_tag("en"));
+        assert!(Language::is_tag("fr-CA"));
+        assert!(Language::is_tag("zh-Hant-TW"));
+        assert!(!Language::is_tag("minified"));
+        assert!(!Language::is_tag("e"));
+        assert!(!Language::is_tag("backup"));
+        assert!(!Language::is_tag("en-"));
+    }
+
+    /// Test lookup with exact matches, truncated ranges, regional variants and the default
+    #[test]
+    fn test_select() {
+        let available = languages(&["de", "en-GB", "fr"]);
+        let select = |accept: Option<&str>| Language::select(accept, &available, "fr").map(String::as_str);
+        assert_eq!(select(Some("de-AT, en;q=0.5")), Some("de"));
+        assert_eq!(select(Some("en")), Some("en-GB"));
+        assert_eq!(select(Some("es, EN-gb;q=0.5")), Some("en-GB"));
+        assert_eq!(select(Some("es, it")), Some("fr"));
+        assert_eq!(select(None), Some("fr"));
+        assert_eq!(Language::select(Some("es"), &available, "it"), None);
+    }
+
+    /// Test that only <stem>.<language>.<extension> siblings count as variants
+    #[test]
+    fn test_variants() {
+        let dir = env::temp_dir().join("language_test_variants");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["index.html", "index.fr.html", "index.en-US.html", "index.backup.html", "about.de.html"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+
+        let variants = Language::variants(&dir.join("index.html"));
+        let tags: Vec<&str> = variants.iter().map(|(tag, _)| tag.as_str()).collect();
+        assert_eq!(tags, vec!["en-US", "fr"]);
+        assert_eq!(variants[1].1, dir.join("index.fr.html"));
+        assert_eq!(Language::variants(&dir.join("about.html")).len(), 1);
+        assert!(Language::variants(&dir.join("index.fr.html")).is_empty());
+
+        fs::write(dir.join("app.js"), "").unwrap();
+        fs::write(dir.join("app.min.js"), "").unwrap();
+        assert!(Language::variants(&dir.join("app.js")).is_empty(), "Only pages are localized");
+    }
+}
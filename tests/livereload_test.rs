@@ -0,0 +1,46 @@
+use katana::livereload::LiveReload;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Test that adding or changing a file changes the fingerprint
+    #[test]
+    fn test_fingerprint() {
+        let root_dir = env::temp_dir().join("livereload_test_fingerprint");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("css")).unwrap();
+        fs::write(root_dir.join("index.html"), "<p>one</p>").unwrap();
+
+        let initial = LiveReload::fingerprint(&root_dir);
+        assert_eq!(initial, LiveReload::fingerprint(&root_dir));
+
+        fs::write(root_dir.join("css/site.css"), "p {}").unwrap();
+        let added = LiveReload::fingerprint(&root_dir);
+        assert_ne!(initial, added, "A new nested file should be noticed");
+
+        fs::write(root_dir.join(".hidden"), "ignored").unwrap();
+        assert_eq!(added, LiveReload::fingerprint(&root_dir), "Dot files are not watched");
+
+        fs::write(root_dir.join("index.html"), "<p>one, two</p>").unwrap();
+        assert_ne!(added, LiveReload::fingerprint(&root_dir));
+        let _ = fs::remove_dir_all(&root_dir);
+    }
+
+    /// Test that waiters wake up on a change
+    #[test]
+    fn test_wait_for_change() {
+        let seen = LiveReload::generation();
+        let waiter = thread::spawn(move || LiveReload::wait(seen, Duration::from_secs(5)));
+        thread::sleep(Duration::from_millis(50));
+        LiveReload::notify();
+        assert!(waiter.join().unwrap() > seen);
+
+        let seen = LiveReload::generation();
+        assert_eq!(LiveReload::wait(seen, Duration::from_millis(10)), seen, "Times out without a change");
+    }
+}
@@ -0,0 +1,65 @@
+use katana::config::Config;
+use katana::location::Location;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Helper function that writes a location file and returns its path
+    fn write(name: &str, content: &str) -> String {
+        let file = env::temp_dir().join(format!("location_test_{}.conf", name));
+        fs::write(&file, content).unwrap();
+        file.display().to_string()
+    }
+
+    /// Test the /prefix=file syntax
+    #[test]
+    fn test_parse() {
+        assert_eq!(Location::parse("/downloads/=d.conf"), Some(("/downloads".to_string(), PathBuf::from("d.conf"))));
+        assert_eq!(Location::parse("/=root.conf"), Some(("/".to_string(), PathBuf::from("root.conf"))));
+        assert_eq!(Location::parse("downloads=d.conf"), None);
+        assert_eq!(Location::parse("/downloads="), None);
+    }
+
+    /// Test that the longest prefix on a segment boundary wins
+    #[test]
+    fn test_select() {
+        let file = write("select", "listing = true\n");
+        let (root, nested) = (format!("/={}", file), format!("/files/deep={}", file));
+        let files = format!("/files={}", file);
+        let args = vec!["", "--location", &root, "--location", &files, "--location", &nested];
+        let (config, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let select = |path: &str| Location::select(&config.locations, path).map(|location| location.prefix.as_str());
+        assert_eq!(select("/files"), Some("/files"));
+        assert_eq!(select("/files/a.txt"), Some("/files"));
+        assert_eq!(select("/files/deep/a.txt"), Some("/files/deep"));
+        assert_eq!(select("/filesystem"), Some("/"));
+    }
+
+    /// Test that a location is layered over its site and only takes location settings
+    #[test]
+    fn test_load() {
+        let downloads = write("load", "listing = true\nheader = [\"Content-Disposition: attachment\"]\n");
+        let location = format!("/downloads={}", downloads);
+        let args = vec!["", "--no-listing", "--header", "X-Global: 1", "--location", &location];
+        let (config, errors) = Config::parse(args.into_iter().map(String::from).collect());
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(!config.listing);
+
+        let downloads = config.for_path("/downloads/a.zip").unwrap();
+        assert!(downloads.listing);
+        assert_eq!(downloads.headers.len(), 2);
+        assert!(downloads.locations.is_empty());
+        assert!(config.for_path("/other").is_none());
+
+        let refused = format!("/x={}", write("refused", "dir = \"/tmp\"\n"));
+        let (_, errors) = Config::parse(vec!["".to_string(), "--location".to_string(), refused]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].ends_with("--dir cannot be set per location"), "{:?}", errors);
+    }
+}
@@ -424,4 +424,76 @@ mod tests {
             );
         }
     }
+
+    /// Verify that lines logged while serving a request carry its ID.
+    #[test]
+    fn test_request_id_tag() {
+        Logger::set_request_id(Some("0123456789abcdef"));
+        let tagged = capture_log(LogLevel::WARN, "Tagged");
+        Logger::set_request_id(None);
+        let untagged = capture_log(LogLevel::WARN, "Untagged");
+
+        assert!(
+            tagged.contains("] [WARN] [0123456789abcdef] Tagged"),
+            "Output should contain the request ID, got '{}'",
+            tagged
+        );
+        assert!(
+            untagged.contains("] [WARN] Untagged"),
+            "Output should not contain a request ID, got '{}'",
+            untagged
+        );
+    }
+
+    /// Verify that access lines are plain log lines in the default format.
+    #[test]
+    fn test_access_plain_format() {
+        let mut buffer = Vec::new();
+        let message = "127.0.0.1:5000 \"GET / HTTP/1.1\" 200 5 0.250ms";
+        Logger::access_writer(message, &[("status", "200".to_string())], &mut buffer);
+        let output = String::from_utf8(buffer).expect("Output was not valid UTF-8");
+
+        assert!(
+            output.ends_with(&format!("] [INFO] {}", message)),
+            "Output should end with the access message, got '{}'",
+            output
+        );
+    }
+
+    /// Verify that levels below the minimum are filtered out.
+    #[test]
+    fn test_minimum_level() {
+        Logger::set_level(LogLevel::WARN);
+        assert!(!Logger::enabled(LogLevel::DEBUG));
+        assert!(!Logger::enabled(LogLevel::INFO));
+        assert!(Logger::enabled(LogLevel::WARN));
+        assert!(Logger::enabled(LogLevel::ERROR));
+        Logger::set_level(LogLevel::INFO);
+        assert!(Logger::enabled(LogLevel::INFO));
+    }
+
+    /// Verify that status codes stay plain when colors are off.
+    #[test]
+    fn test_paint_status_without_color() {
+        Logger::set_color(false);
+        assert_eq!(Logger::paint_status(200), "200");
+        assert_eq!(Logger::paint_status(404), "404");
+    }
+
+    /// Verify that warnings and errors can be sent to their own file.
+    #[test]
+    fn test_error_log_file() {
+        let path = std::env::temp_dir().join("logger_test_error.log");
+        let _ = std::fs::remove_file(&path);
+
+        Logger::set_error_log(Some(&path)).expect("Error log should open");
+        Logger::warn("Separate warning");
+        Logger::error("Separate error");
+        Logger::set_error_log(None).unwrap();
+
+        let output = std::fs::read_to_string(&path).unwrap();
+        assert!(output.contains("] [WARN] Separate warning\n"), "Got '{}'", output);
+        assert!(output.contains("] [ERROR] Separate error\n"), "Got '{}'", output);
+        let _ = std::fs::remove_file(&path);
+    }
 }
@@ -0,0 +1,37 @@
+use katana::markdown::Markdown;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that headings, paragraphs, lists, quotes, rules and code blocks become HTML
+    #[test]
+    fn test_blocks() {
+        let source = "# Title\n\nSome text\nwrapped here.\n\n- one\n- two\n\n1. first\n2. second\n\n> quoted\n\n---\n\n```\nlet x = <b>;\n```\n";
+        assert_eq!(
+            Markdown::to_html(source),
+            "<h1>Title</h1>\n<p>Some text wrapped here.</p>\n<ul>\n<li>one</li>\n<li>two</li>\n</ul>\n<ol>\n<li>first</li>\n<li>second</li>\n</ol>\n<blockquote><p>quoted</p></blockquote>\n<hr>\n<pre><code>let x = &lt;b&gt;;</code></pre>\n"
+        );
+        assert_eq!(Markdown::to_html("###### Deep ##"), "<h6>Deep</h6>\n");
+        assert_eq!(Markdown::to_html("#hashtag"), "<p>#hashtag</p>\n");
+    }
+
+    /// Test that inline code, emphasis, links and images are formatted
+    #[test]
+    fn test_inline() {
+        assert_eq!(Markdown::inline("**bold** and *em* and _em_"), "<strong>bold</strong> and <em>em</em> and <em>em</em>");
+        assert_eq!(Markdown::inline("run `a *b* c` now"), "run <code>a *b* c</code> now");
+        assert_eq!(Markdown::inline("snake_case_name"), "snake_case_name");
+        assert_eq!(Markdown::inline("[docs](docs/index.html)"), "<a href=\"docs/index.html\">docs</a>");
+        assert_eq!(Markdown::inline("![logo](logo.png)"), "<img src=\"logo.png\" alt=\"logo\">");
+        assert_eq!(Markdown::inline("a * b"), "a * b");
+    }
+
+    /// Test that raw HTML and script links never reach the page
+    #[test]
+    fn test_escapes() {
+        assert_eq!(Markdown::to_html("<script>alert(1)</script>"), "<p>&lt;script&gt;alert(1)&lt;/script&gt;</p>\n");
+        assert_eq!(Markdown::inline("[x](javascript:alert(1))"), "[x](javascript:alert(1))");
+        assert_eq!(Markdown::inline("[x](\"onmouseover=\")"), "<a href=\"&quot;onmouseover=&quot;\">x</a>");
+    }
+}
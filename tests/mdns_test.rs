@@ -0,0 +1,124 @@
+use katana::mdns::Mdns;
+use std::net::Ipv4Addr;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mdns() -> Mdns {
+        Mdns::new("Team share", "Dev Box", 8080, Ipv4Addr::new(192, 168, 1, 20))
+    }
+
+    fn query(id: u16, questions: &[(&[&str], u16)]) -> Vec<u8> {
+        let mut packet = id.to_be_bytes().to_vec();
+        packet.extend_from_slice(&[0, 0]);
+        packet.extend_from_slice(&(questions.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        for (name, kind) in questions {
+            packet.extend(Mdns::encode_name(name));
+            packet.extend_from_slice(&kind.to_be_bytes());
+            packet.extend_from_slice(&[0, 1]);
+        }
+        packet
+    }
+
+    // (name, type, ttl) of every answer in a response
+    fn answers(packet: &[u8]) -> Vec<(String, u16, u32)> {
+        let questions = u16::from_be_bytes([packet[4], packet[5]]);
+        let count = u16::from_be_bytes([packet[6], packet[7]]);
+        let mut offset = 12;
+        for _ in 0..questions {
+            offset = Mdns::read_name(packet, offset).unwrap().1 + 4;
+        }
+        let mut records = Vec::new();
+        for _ in 0..count {
+            let (name, next) = Mdns::read_name(packet, offset).unwrap();
+            let kind = u16::from_be_bytes([packet[next], packet[next + 1]]);
+            let ttl = u32::from_be_bytes([packet[next + 4], packet[next + 5], packet[next + 6], packet[next + 7]]);
+            let len = u16::from_be_bytes([packet[next + 8], packet[next + 9]]) as usize;
+            records.push((name.join("."), kind, ttl));
+            offset = next + 10 + len;
+        }
+        assert_eq!(offset, packet.len(), "No trailing bytes after the answers");
+        records
+    }
+
+    /// Test the instance and host names put in the records
+    #[test]
+    fn test_names() {
+        let mdns = mdns();
+        assert_eq!(mdns.instance(), "Team share");
+        assert_eq!(mdns.host(), "katana-dev-box.local");
+        assert_eq!(Mdns::host_label(""), "katana");
+        assert_eq!(Mdns::host_label(&"x".repeat(100)).len(), 63);
+        assert_eq!(Mdns::instance_name("   "), None);
+        assert_eq!(Mdns::instance_name(&"é".repeat(40)).unwrap().len(), 62);
+    }
+
+    /// Test that the announcement carries the whole service description
+    #[test]
+    fn test_announcement() {
+        let records = answers(&mdns().announcement());
+        assert_eq!(
+            records,
+            vec![
+                ("_services._dns-sd._udp.local".to_string(), 12, 4500),
+                ("_http._tcp.local".to_string(), 12, 4500),
+                ("Team share._http._tcp.local".to_string(), 33, 120),
+                ("Team share._http._tcp.local".to_string(), 16, 4500),
+                ("katana-dev-box.local".to_string(), 1, 120),
+            ]
+        );
+
+        let goodbye = answers(&mdns().goodbye());
+        assert!(goodbye.iter().all(|(_, _, ttl)| *ttl == 0), "Goodbye records have a zero TTL");
+    }
+
+    /// Test which queries get an answer
+    #[test]
+    fn test_reply() {
+        let mdns = mdns();
+        let browse = mdns.reply(&query(0, &[(&["_http", "_tcp", "local"], 12)]), false).unwrap();
+        assert_eq!(answers(&browse).len(), 4);
+        assert_eq!(&browse[..2], &[0, 0]);
+
+        let host = mdns.reply(&query(0, &[(&["KATANA-DEV-BOX", "local"], 1)]), false).unwrap();
+        assert_eq!(answers(&host), vec![("katana-dev-box.local".to_string(), 1, 120)]);
+
+        assert!(mdns.reply(&query(0, &[(&["_ipp", "_tcp", "local"], 12)]), false).is_none());
+        assert!(mdns.reply(&query(0, &[(&["katana-dev-box", "local"], 28)]), false).is_none(), "No AAAA record");
+        assert!(mdns.reply(&browse, false).is_none(), "Responses are never answered");
+        assert!(mdns.reply(&[0; 5], false).is_none());
+    }
+
+    /// Test that legacy unicast queries get their id and questions echoed with short TTLs
+    #[test]
+    fn test_legacy_reply() {
+        let packet = query(0x1234, &[(&["Team share", "_http", "_tcp", "local"], 33)]);
+        let reply = mdns().reply(&packet, true).unwrap();
+        assert_eq!(&reply[..2], &[0x12, 0x34]);
+        assert_eq!(&reply[4..6], &[0, 1]);
+        assert_eq!(&reply[12..packet.len()], &packet[12..]);
+        assert_eq!(
+            answers(&reply),
+            vec![
+                ("Team share._http._tcp.local".to_string(), 33, 10),
+                ("katana-dev-box.local".to_string(), 1, 10),
+            ]
+        );
+    }
+
+    /// Test that compressed names are followed and pointer loops rejected
+    #[test]
+    fn test_read_name() {
+        let mut packet = vec![0; 12];
+        packet.extend(Mdns::encode_name(&["_http", "_tcp", "local"]));
+        packet.extend_from_slice(&[4, b'm', b'i', b'n', b'e', 0xc0, 12]);
+        let (name, end) = Mdns::read_name(&packet, 30).unwrap();
+        assert_eq!(name, vec!["mine", "_http", "_tcp", "local"]);
+        assert_eq!(end, packet.len());
+
+        assert!(Mdns::read_name(&[0xc0, 0], 0).is_none());
+        assert!(Mdns::read_name(&[5, b'a'], 0).is_none());
+    }
+}
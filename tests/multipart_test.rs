@@ -0,0 +1,58 @@
+use katana::multipart::Multipart;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the boundary is only taken from a multipart/form-data Content-Type
+    #[test]
+    fn test_boundary() {
+        let boundary = Multipart::boundary("multipart/form-data; boundary=----WebKitFormBoundary7MA4");
+        assert_eq!(boundary.as_deref(), Some("----WebKitFormBoundary7MA4"));
+        assert_eq!(Multipart::boundary("Multipart/Form-Data;boundary=\"a b\"").as_deref(), Some("a b"));
+        assert_eq!(Multipart::boundary("multipart/form-data"), None);
+        assert_eq!(Multipart::boundary("multipart/form-data; boundary="), None);
+        assert_eq!(Multipart::boundary("application/x-www-form-urlencoded; boundary=x"), None);
+        assert_eq!(Multipart::boundary(&format!("multipart/form-data; boundary={}", "x".repeat(71))), None);
+    }
+
+    /// Test that fields and files are split at the boundary with their names and data kept
+    #[test]
+    fn test_parse() {
+        let body = b"preamble\r\n--xyz\r\n\
+            Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+            Holiday\r\n--xyz\r\n\
+            Content-Disposition: form-data; name=\"files\"; filename=\"a;b.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            line one\r\n--xy\r\nline two\r\n--xyz\r\n\
+            content-disposition: form-data; name=files; filename=\"\"\r\n\r\n\
+            \r\n--xyz--\r\nepilogue";
+        let parts = Multipart::parse(body, "xyz").unwrap();
+        assert_eq!(parts.len(), 3);
+        assert_eq!((parts[0].name.as_str(), parts[0].filename.as_deref()), ("title", None));
+        assert_eq!(parts[0].data, b"Holiday");
+        assert_eq!(parts[1].filename.as_deref(), Some("a;b.txt"));
+        assert_eq!(parts[1].content_type.as_deref(), Some("text/plain"));
+        assert_eq!(parts[1].data, b"line one\r\n--xy\r\nline two");
+        assert_eq!((parts[2].name.as_str(), parts[2].filename.as_deref()), ("files", Some("")));
+        assert!(parts[2].data.is_empty());
+
+        assert_eq!(Multipart::parse(b"--xyz--\r\n", "xyz").unwrap(), vec![]);
+        assert!(Multipart::parse(b"no boundary here", "xyz").is_err());
+        assert!(Multipart::parse(b"--xyz\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\ncut short", "xyz").is_err());
+        assert!(Multipart::parse(b"--xyz\r\nContent-Type: text/plain\r\n\r\nx\r\n--xyz--", "xyz").is_err());
+    }
+
+    /// Test that client file names are reduced to their last segment and hidden ones refused
+    #[test]
+    fn test_safe_name() {
+        assert_eq!(Multipart::safe_name("report.pdf"), Some("report.pdf"));
+        assert_eq!(Multipart::safe_name("photos/2024/beach.jpg"), Some("beach.jpg"));
+        assert_eq!(Multipart::safe_name("C:\\Users\\me\\notes.txt"), Some("notes.txt"));
+        assert_eq!(Multipart::safe_name("../../etc/passwd"), Some("passwd"));
+        assert_eq!(Multipart::safe_name(".htaccess"), None);
+        assert_eq!(Multipart::safe_name("dir/.."), None);
+        assert_eq!(Multipart::safe_name("dir/"), None);
+        assert_eq!(Multipart::safe_name("bad\nname"), None);
+    }
+}
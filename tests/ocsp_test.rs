@@ -0,0 +1,159 @@
+use katana::crypto::Crypto;
+use katana::ocsp::{CertId, CertStatus, Ocsp, Staple};
+use katana::utils::Utils;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    // example.com, serial 1234abcd, issued by the CA after it with an OCSP responder at
+    // http://ocsp.example.test/
+    const CHAIN: &str = "-----BEGIN CERTIFICATE-----
+MIIBtDCCAVygAwIBAgIEEjSrzTAKBggqhkjOPQQDAjAZMRcwFQYDVQQDDA5LYXRh
+bmEgVGVzdCBDQTAgFw0yNjEwMTUwNDU3MzlaGA8yMTI2MDkyMTA0NTczOVowFjEU
+MBIGA1UEAwwLZXhhbXBsZS5jb20wWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAAQt
+AYYa5lWsUbpufbZhs6pxQvLAUMu8nCsoIttY5pSm1hY0NDSW2Dp/DTKw15C8ma64
+fB4EDJdybdZnNGxO0/7Fo4GSMIGPMDUGCCsGAQUFBwEBBCkwJzAlBggrBgEFBQcw
+AYYZaHR0cDovL29jc3AuZXhhbXBsZS50ZXN0LzAWBgNVHREEDzANggtleGFtcGxl
+LmNvbTAdBgNVHQ4EFgQUxZRZNjpIu17b6lf9Zn2w2MBKLjowHwYDVR0jBBgwFoAU
+N7WKWzWfOUc5WJvUIC+iVdlRLDUwCgYIKoZIzj0EAwIDRgAwQwIgG/K2V4pA7TlS
+cq9CaslGeICBh7XbMs5pqKjORO+X0g8CH17ygVnCb1WwyTQrqzuarUzgm3iPQFPd
+iQKKH+6CvBM=
+-----END CERTIFICATE-----
+-----BEGIN CERTIFICATE-----
+MIIBiTCCAS+gAwIBAgIUWeNG8XwbcvIt16/LRVIB7ltjkpIwCgYIKoZIzj0EAwIw
+GTEXMBUGA1UEAwwOS2F0YW5hIFRlc3QgQ0EwIBcNMjYxMDE1MDQ1NzM5WhgPMjEy
+NjA5MjEwNDU3MzlaMBkxFzAVBgNVBAMMDkthdGFuYSBUZXN0IENBMFkwEwYHKoZI
+zj0CAQYIKoZIzj0DAQcDQgAEDci0OKqWwUa2UBgHv+dz1wPvhI3ZKcdJ6v3jksDg
+bN1F5lxRR1x2I0GmRJqqbZ0Qh4j4EAHhah6WcN7xFUDifKNTMFEwHQYDVR0OBBYE
+FDe1ils1nzlHOVib1CAvolXZUSw1MB8GA1UdIwQYMBaAFDe1ils1nzlHOVib1CAv
+olXZUSw1MA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDSAAwRQIgfCibYI/s
+l96oJ3dqtwN6vSaUXR+UVbTZDVwjixo/n18CIQDp3yBf1yBKVwu3dI1iUf49PJNu
+wjvTQYX1eGg5pcf+eg==
+-----END CERTIFICATE-----
+";
+    // the responder's answer for it: good from 2026-10-15 04:57:39 to 2026-10-22 04:57:39
+    const RESPONSE: &str = "MIICogoBAKCCApswggKXBgkrBgEFBQcwAQEEggKIMIIChDCBl6EbMBkxFzAVBgNVBAMMDkthdGFuYSBUZXN0IENBGA8yMDI2MTAxNTA0NTczOVowZzBlMD0wCQYFKw4DAhoFAAQUuS7EJYXBEB2_LD3OaR7YF3a1GLgEFDe1ils1nzlHOVib1CAvolXZUSw1AgQSNKvNgAAYDzIwMjYxMDE1MDQ1NzM5WqARGA8yMDI2MTAyMjA0NTczOVowCgYIKoZIzj0EAwIDRwAwRAIgM7D8xt530Z5uiwG3iBX7gaBcsnslCcPM3i-U38NLUXMCIDs3g3mycH3pY4Gua0ceyoEAWb-bRbr-LKTrHF649k6HoIIBkTCCAY0wggGJMIIBL6ADAgECAhRZ40bxfBty8i3Xr8tFUgHuW2OSkjAKBggqhkjOPQQDAjAZMRcwFQYDVQQDDA5LYXRhbmEgVGVzdCBDQTAgFw0yNjEwMTUwNDU3MzlaGA8yMTI2MDkyMTA0NTczOVowGTEXMBUGA1UEAwwOS2F0YW5hIFRlc3QgQ0EwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAAQNyLQ4qpbBRrZQGAe_53PXA--Ejdkpx0nq_eOSwOBs3UXmXFFHXHYjQaZEmqptnRCHiPgQAeFqHpZw3vEVQOJ8o1MwUTAdBgNVHQ4EFgQUN7WKWzWfOUc5WJvUIC-iVdlRLDUwHwYDVR0jBBgwFoAUN7WKWzWfOUc5WJvUIC-iVdlRLDUwDwYDVR0TAQH_BAUwAwEB_zAKBggqhkjOPQQDAgNIADBFAiB8KJtgj-yX3qgnd2q3A3q9JpRdH5RVtNkNXCOLGj-fXwIhAOnfIF_XIEpXC7d0jWJR_j08k27CO9NBhfV4aDmlx_56";
+    const REQUEST: &str = "304530433041303f303d300906052b0e03021a05000414b92ec42585c1101dbf2c3dce691ed81776b518b8041437b58a5b359f394739589bd4202fa255d9512c3502041234abcd";
+    const THIS_UPDATE: u64 = 1_792_040_259;
+    const NEXT_UPDATE: u64 = 1_792_645_059;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn response() -> Vec<u8> {
+        Utils::base64url_decode(RESPONSE).unwrap()
+    }
+
+    /// Helper function that starts a responder answering once with the given status and body,
+    /// and returns its URL and what it was sent
+    fn responder(status: &'static str, body: Vec<u8>) -> (String, thread::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/ocsp", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = String::new();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                head.push_str(&line);
+                line.clear();
+            }
+            let length: usize = head
+                .lines()
+                .find_map(|line| line.strip_prefix("Content-Length: "))
+                .map_or(0, |length| length.parse().unwrap());
+            let mut request = vec![0; length];
+            reader.read_exact(&mut request).unwrap();
+            assert!(head.starts_with("POST /ocsp HTTP/1.1\r\n"));
+            assert!(head.contains("Content-Type: application/ocsp-request\r\n"));
+            let reply = format!("HTTP/1.1 {}\r\nContent-Type: application/ocsp-response\r\nContent-Length: {}\r\n\r\n", status, body.len());
+            stream.write_all(&[reply.as_bytes(), &body].concat()).unwrap();
+            request
+        });
+        (url, handle)
+    }
+
+    /// Test the SHA-1 digest against the FIPS 180 vectors
+    #[test]
+    fn test_sha1() {
+        assert_eq!(hex(&Crypto::sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(&Crypto::sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(
+            hex(&Crypto::sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+        let million = vec![b'a'; 1_000_000];
+        assert_eq!(hex(&Crypto::sha1(&million)), "34aa973cd4c4daa4f61eeb2bdbad27316534016f");
+    }
+
+    /// Test that the responder and certificate id are read from the chain
+    #[test]
+    fn test_cert_id() {
+        assert_eq!(Ocsp::pem_certificates(CHAIN.as_bytes()).len(), 2);
+        let (url, id) = Ocsp::cert_id(CHAIN.as_bytes()).unwrap();
+        assert_eq!(url, "http://ocsp.example.test/");
+        assert_eq!(hex(&id.issuer_name_hash), "b92ec42585c1101dbf2c3dce691ed81776b518b8");
+        assert_eq!(hex(&id.issuer_key_hash), "37b58a5b359f394739589bd4202fa255d9512c35");
+        assert_eq!(id.serial, vec![0x12, 0x34, 0xab, 0xcd]);
+        assert_eq!(hex(&Ocsp::request(&id)), REQUEST);
+
+        // the issuer must follow the certificate
+        let certificates: Vec<&str> = CHAIN.split_inclusive("-----END CERTIFICATE-----\n").collect();
+        assert!(Ocsp::cert_id(certificates[0].as_bytes()).unwrap_err().contains("full chain"));
+        let swapped = format!("{}{}", certificates[1], certificates[0]);
+        assert!(Ocsp::cert_id(swapped.as_bytes()).is_err());
+        assert!(Ocsp::cert_id(b"not a certificate").is_err());
+    }
+
+    /// Test the parsing of responses, and which ones are refused
+    #[test]
+    fn test_parse() {
+        let (_, id) = Ocsp::cert_id(CHAIN.as_bytes()).unwrap();
+        let staple = Ocsp::parse(&response(), &id).unwrap();
+        assert_eq!(staple.status, CertStatus::Good);
+        assert_eq!(staple.this_update, THIS_UPDATE);
+        assert_eq!(staple.next_update, Some(NEXT_UPDATE));
+        assert_eq!(staple.response, response());
+
+        let other = CertId { serial: vec![0x01], ..id.clone() };
+        assert!(Ocsp::parse(&response(), &other).unwrap_err().contains("another certificate"));
+        // tryLater
+        assert!(Ocsp::parse(&[0x30, 0x03, 0x0a, 0x01, 0x03], &id).unwrap_err().contains("status 3"));
+        assert!(Ocsp::parse(&response()[..100], &id).is_err());
+        assert!(Ocsp::parse(b"", &id).is_err());
+    }
+
+    /// Test when a staple is refreshed and when it expires
+    #[test]
+    fn test_staple() {
+        let staple = Staple { response: Vec::new(), status: CertStatus::Good, this_update: 1000, next_update: Some(3000) };
+        assert_eq!(staple.refresh_at(), 2000);
+        assert!(!staple.is_expired(2999));
+        assert!(staple.is_expired(3000));
+
+        let open_ended = Staple { next_update: None, ..staple };
+        assert_eq!(open_ended.refresh_at(), 1000 + 3600);
+        assert!(!open_ended.is_expired(u64::MAX));
+    }
+
+    /// Test fetching a response from a responder
+    #[test]
+    fn test_fetch() {
+        let (_, id) = Ocsp::cert_id(CHAIN.as_bytes()).unwrap();
+        let (url, handle) = responder("200 OK", response());
+        let staple = Ocsp::fetch(&url, &id).unwrap();
+        assert_eq!(staple.next_update, Some(NEXT_UPDATE));
+        assert_eq!(hex(&handle.join().unwrap()), REQUEST);
+
+        let (url, handle) = responder("503 Service Unavailable", Vec::new());
+        assert!(Ocsp::fetch(&url, &id).unwrap_err().contains("503"));
+        handle.join().unwrap();
+
+        assert!(Ocsp::fetch("https://ocsp.example.test/", &id).unwrap_err().contains("http://"));
+    }
+}
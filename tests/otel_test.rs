@@ -0,0 +1,81 @@
+#![cfg(feature = "otel")]
+
+use katana::otel::{AttributeValue, Span, TraceParent, Tracer};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Instant;
+
+    /// Test parsing of valid and invalid traceparent headers
+    #[test]
+    fn test_traceparent_parse() {
+        let parent = TraceParent::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        assert_eq!(parent.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(parent.parent_id, "00f067aa0ba902b7");
+        assert_eq!(parent.flags, 1);
+
+        assert!(TraceParent::parse("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none());
+        assert!(TraceParent::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01").is_none());
+        assert!(TraceParent::parse("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").is_none());
+        assert!(TraceParent::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_none());
+        assert!(TraceParent::parse("00-xyz-00f067aa0ba902b7-01").is_none());
+    }
+
+    /// Test that a span continues the inbound trace
+    #[test]
+    fn test_span_continues_trace() {
+        let parent = TraceParent::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01");
+        let span = Span::server("GET", parent, Instant::now());
+        assert_eq!(span.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(span.parent_span_id.as_deref(), Some("00f067aa0ba902b7"));
+        assert_eq!(span.span_id.len(), 16);
+
+        let root = Span::server("GET", None, Instant::now());
+        assert_eq!(root.trace_id.len(), 32);
+        assert!(root.parent_span_id.is_none());
+        assert!(root.start_unix_nano <= root.end_unix_nano);
+    }
+
+    /// Test that spans are posted as OTLP JSON
+    #[test]
+    fn test_export() {
+        let collector = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", collector.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = collector.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+            (request_line, String::from_utf8(body).unwrap())
+        });
+
+        let mut span = Span::server("GET", None, Instant::now());
+        span.attributes.push(("http.response.status_code", AttributeValue::Int(200)));
+        Tracer::export(&endpoint, &[span]).unwrap();
+
+        let (request_line, body) = handle.join().unwrap();
+        assert!(request_line.starts_with("POST /v1/traces "));
+        assert!(body.starts_with("{\"resourceSpans\":["));
+        assert!(body.contains("\"kind\":2"));
+        assert!(body.contains("{\"key\":\"http.response.status_code\",\"value\":{\"intValue\":\"200\"}}"));
+    }
+}
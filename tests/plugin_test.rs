@@ -0,0 +1,111 @@
+use katana::config::Config;
+use katana::http::HttpStatus;
+use katana::plugin::{Plugin, PluginAction, RequestView, ResponseView, WasmModule};
+use katana::server::Server;
+use katana::templates::Templates;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+
+    struct Gatekeeper;
+
+    impl Plugin for Gatekeeper {
+        fn name(&self) -> &str {
+            "gatekeeper"
+        }
+
+        fn on_request(&self, request: &mut RequestView) -> PluginAction {
+            if !request.peer_addr().is_some_and(|peer| peer.ip().is_loopback() && peer.port() != 0) {
+                return PluginAction::Respond(HttpStatus::Forbidden);
+            }
+            if request.path() == "/old.txt" {
+                request.set_path("/new.txt");
+            }
+            if request.path().starts_with("/private") && request.header("X-Key") != Some("open") {
+                return PluginAction::Respond(HttpStatus::Unauthorized);
+            }
+            PluginAction::Continue
+        }
+
+        fn on_response(&self, response: &mut ResponseView) {
+            response.set_header("X-Plugin", "gatekeeper");
+            assert!(!response.set_header("Content-Length", "0"));
+        }
+    }
+
+    /// Helper function that builds a module exporting the given functions
+    fn module(exports: &[&str]) -> Vec<u8> {
+        let mut section = vec![exports.len() as u8];
+        for (index, name) in exports.iter().enumerate() {
+            section.push(name.len() as u8);
+            section.extend_from_slice(name.as_bytes());
+            section.extend_from_slice(&[0, index as u8]);
+        }
+        let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+        bytes.extend_from_slice(&[7, section.len() as u8]);
+        bytes.extend(section);
+        bytes
+    }
+
+    /// Test that only WebAssembly modules exporting a hook are accepted
+    #[test]
+    fn test_wasm_hooks() {
+        assert_eq!(WasmModule::hooks(&module(&["on_request", "alloc"])), Ok(vec!["on_request".to_string()]));
+        assert_eq!(WasmModule::hooks(&module(&["on_request", "on_response"])).unwrap().len(), 2);
+        assert!(WasmModule::hooks(&module(&["alloc"])).is_err());
+        assert!(WasmModule::hooks(b"MZ\x90\0").is_err());
+        let mut truncated = module(&["on_request"]);
+        truncated.pop();
+        assert!(WasmModule::hooks(&truncated).is_err());
+    }
+
+    /// Test that modules are found by extension in a stable order
+    #[test]
+    fn test_discover() {
+        let dir = env::temp_dir().join("plugin_test_discover");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("b.wasm"), module(&["on_response"])).unwrap();
+        fs::write(dir.join("a.wasm"), module(&["on_request"])).unwrap();
+        fs::write(dir.join("notes.txt"), "not a plugin").unwrap();
+
+        let found = WasmModule::discover(&dir);
+        assert_eq!(found, vec![dir.join("a.wasm"), dir.join("b.wasm")]);
+    }
+
+    /// Test that a plugin can rewrite, refuse and decorate requests
+    #[test]
+    fn test_native_plugin() {
+        let root_dir = env::temp_dir().join("plugin_test_native");
+        fs::create_dir_all(root_dir.join("private")).unwrap();
+        fs::write(root_dir.join("new.txt"), "new").unwrap();
+        fs::write(root_dir.join("private/data.txt"), "data").unwrap();
+
+        let args = vec!["", "--host", "127.0.0.1", "--port", "0", "--dir", root_dir.to_str().unwrap()];
+        let mut server = Server::new(Config::parse_args(args.into_iter().map(String::from).collect()), Templates::load());
+        server.add_plugin(Gatekeeper);
+        let listeners = server.listen().unwrap();
+        let addr = listeners[0].url().unwrap().trim_start_matches("http://").to_string();
+        thread::spawn(move || server.run(listeners));
+
+        let get = |path: &str, key: &str| {
+            let mut stream = TcpStream::connect(&addr).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nX-Key: {}\r\nConnection: close\r\n\r\n", path, key).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let rewritten = get("/old.txt", "");
+        assert!(rewritten.ends_with("\r\n\r\nnew"), "Got '{}'", rewritten);
+        assert!(rewritten.contains("X-Plugin: gatekeeper"));
+        assert!(get("/private/data.txt", "wrong").starts_with("HTTP/1.1 401"));
+        assert!(get("/private/data.txt", "open").ends_with("\r\n\r\ndata"));
+    }
+}
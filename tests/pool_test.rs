@@ -0,0 +1,39 @@
+use katana::pool::BufferPool;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that a buffer given back is handed out again, empty, with its capacity kept
+    #[test]
+    fn test_reuse() {
+        let mut buffer = BufferPool::take();
+        buffer.extend_from_slice(b"GET / HTTP/1.1\r\n");
+        let capacity = buffer.capacity();
+        drop(buffer);
+
+        // other tests take buffers too, the one given back is found among the next ones
+        let reused = BufferPool::stats().reused;
+        let taken: Vec<_> = (0..BufferPool::MAX_IDLE).map(|_| BufferPool::take()).collect();
+        assert!(taken.iter().all(|buffer| buffer.is_empty()));
+        assert!(taken.iter().any(|buffer| buffer.capacity() == capacity));
+        assert!(BufferPool::stats().reused > reused);
+    }
+
+    /// Test that buffers in use are counted and oversized ones are not kept
+    #[test]
+    fn test_stats() {
+        let before = BufferPool::stats();
+        let first = BufferPool::take();
+        let second = BufferPool::take();
+        assert!(BufferPool::stats().in_use >= before.in_use + 2);
+        assert!(BufferPool::stats().allocated + BufferPool::stats().reused >= before.allocated + before.reused + 2);
+        drop((first, second));
+
+        let mut large = BufferPool::take();
+        large.reserve(BufferPool::MAX_CAPACITY + 1);
+        drop(large);
+        let taken: Vec<_> = (0..BufferPool::MAX_IDLE).map(|_| BufferPool::take()).collect();
+        assert!(taken.iter().all(|buffer| buffer.capacity() <= BufferPool::MAX_CAPACITY));
+    }
+}
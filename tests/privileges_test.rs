@@ -0,0 +1,40 @@
+use katana::privileges::Privileges;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWD: &str = "root:x:0:0:root:/root:/bin/sh\nwww-data:x:33:33:www-data:/var/www:/usr/sbin/nologin\n\
+                          alice:x:1000:1001:Alice:/home/alice:/bin/sh\n";
+    const GROUP: &str = "root:x:0:\nwww-data:x:33:\nstaff:x:50:alice,bob\nalice:x:1001:\n";
+
+    fn privileges(user: Option<&str>, group: Option<&str>) -> Privileges {
+        Privileges { user: user.map(String::from), group: group.map(String::from), chroot: false }
+    }
+
+    /// Test that users and groups are found by name or id, with the primary group by default
+    #[test]
+    fn test_resolve() {
+        let resolve = |user, group| privileges(user, group).resolve(PASSWD, GROUP);
+        assert_eq!(resolve(Some("www-data"), None), Ok((Some(33), Some(33))));
+        assert_eq!(resolve(Some("alice"), None), Ok((Some(1000), Some(1001))));
+        assert_eq!(resolve(Some("1000"), None), Ok((Some(1000), Some(1001))));
+        assert_eq!(resolve(Some("alice"), Some("staff")), Ok((Some(1000), Some(50))));
+        assert_eq!(resolve(Some("4242"), Some("4343")), Ok((Some(4242), Some(4343))));
+        assert_eq!(resolve(None, Some("staff")), Ok((None, Some(50))));
+        assert_eq!(resolve(None, None), Ok((None, None)));
+
+        assert_eq!(resolve(Some("mallory"), None), Err("no such user: mallory".to_string()));
+        assert_eq!(resolve(Some("alice"), Some("wheel")), Err("no such group: wheel".to_string()));
+        assert!(resolve(Some("4242"), None).unwrap_err().starts_with("--group is needed"));
+    }
+
+    /// Test that nothing is dropped unless asked for
+    #[test]
+    fn test_enabled() {
+        assert!(!Privileges::default().enabled());
+        assert!(privileges(Some("nobody"), None).enabled());
+        assert!(privileges(None, Some("nogroup")).enabled());
+        assert!(Privileges { chroot: true, ..Privileges::default() }.enabled());
+    }
+}
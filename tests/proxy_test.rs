@@ -0,0 +1,247 @@
+use katana::http::HttpStatus;
+use katana::proxy::{Balance, ForwardProxy, Proxy, Upstream};
+use katana::request::Request;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Cursor, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Helper function that parses a request head
+    fn request(head: &str) -> Request {
+        let mut request = Request::read_head(&mut Cursor::new(format!("{}\r\n\r\n", head).into_bytes())).unwrap();
+        request.set_peer(Some("203.0.113.7:51000".parse().unwrap()));
+        request
+    }
+
+    /// Helper function that starts an upstream answering its name, after a message on the
+    /// channel when one is given, and returns its address
+    fn upstream(name: &'static str, gate: Option<mpsc::Receiver<()>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                if let Some(gate) = &gate {
+                    gate.recv().unwrap();
+                }
+                let reply = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nServer: up\r\n\r\n{}", name.len(), name);
+                stream.write_all(reply.as_bytes()).unwrap();
+            }
+        });
+        address
+    }
+
+    /// Helper function that returns an address nothing listens on
+    fn closed() -> String {
+        TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string()
+    }
+
+    /// Test the upstream addresses accepted
+    #[test]
+    fn test_add() {
+        let mut proxy = Proxy::default();
+        assert!(!proxy.enabled());
+        assert_eq!(proxy.add("127.0.0.1:3000"), Ok(()));
+        assert_eq!(proxy.add("http://localhost:3001/"), Ok(()));
+        assert_eq!(proxy.upstreams[1].address, "localhost:3001");
+        assert!(proxy.enabled());
+        assert_eq!(proxy.add("https://a:1"), Err("proxy upstream must be host:port or http://host:port: https://a:1".to_string()));
+        assert!(proxy.add("localhost").is_err());
+        assert!(proxy.add("localhost:http").is_err());
+        assert_eq!(Balance::from_str("least-connections"), Some(Balance::LeastConnections));
+        assert_eq!(Balance::from_str("random"), None);
+    }
+
+    /// Test that round-robin takes the upstreams in turn
+    #[test]
+    fn test_round_robin() {
+        let mut proxy = Proxy::default();
+        proxy.add("127.0.0.1:3000").unwrap();
+        proxy.add("127.0.0.1:3001").unwrap();
+        let first: Vec<String> = (0..4).map(|_| proxy.order()[0].address.clone()).collect();
+        assert_eq!(first, vec!["127.0.0.1:3000", "127.0.0.1:3001", "127.0.0.1:3000", "127.0.0.1:3001"]);
+    }
+
+    /// Test that least-connections skips an upstream busy with a request
+    #[test]
+    fn test_least_connections() {
+        let (release, gate) = mpsc::channel();
+        let mut proxy = Proxy::default();
+        proxy.balance = Balance::LeastConnections;
+        proxy.add(&upstream("slow", Some(gate))).unwrap();
+        proxy.add(&upstream("fast", None)).unwrap();
+
+        let busy = proxy.clone();
+        let pending = thread::spawn(move || busy.forward(&request("GET / HTTP/1.1\r\nHost: test")));
+        while proxy.upstreams.iter().all(|upstream| upstream.active() == 0) {
+            thread::yield_now();
+        }
+        let idle = proxy.upstreams.iter().find(|upstream| upstream.active() == 0).unwrap().address.clone();
+        assert!((0..3).all(|_| proxy.order()[0].address == idle));
+
+        release.send(()).unwrap();
+        assert_eq!(pending.join().unwrap().unwrap().status, HttpStatus::Ok);
+    }
+
+    /// Helper function that starts an upstream answering /healthz with 200 while the flag is
+    /// set and 503 otherwise, and returns its address
+    fn checked_upstream(healthy: Arc<AtomicBool>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut line = String::new();
+                BufReader::new(stream.try_clone().unwrap()).read_line(&mut line).unwrap();
+                let status = match (line.starts_with("GET /healthz "), healthy.load(Ordering::Relaxed)) {
+                    (true, true) => "200 OK",
+                    (true, false) => "503 Service Unavailable",
+                    _ => "404 Not Found",
+                };
+                stream.write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).as_bytes()).unwrap();
+            }
+        });
+        address
+    }
+
+    /// Test that a probe passes on a 2xx answer only
+    #[test]
+    fn test_probe() {
+        let healthy = Arc::new(AtomicBool::new(true));
+        let upstream = Upstream::new(&checked_upstream(healthy.clone()));
+        let timeout = Duration::from_secs(1);
+        assert!(upstream.probe("/healthz", timeout));
+        assert!(!upstream.probe("/missing", timeout));
+        healthy.store(false, Ordering::Relaxed);
+        assert!(!upstream.probe("/healthz", timeout));
+        assert!(!Upstream::new(&closed()).probe("/healthz", timeout));
+    }
+
+    /// Test that an upstream failing its checks is down for the balancer until it passes again
+    #[test]
+    fn test_health_check() {
+        let healthy = Arc::new(AtomicBool::new(true));
+        let mut proxy = Proxy::default();
+        proxy.add(&checked_upstream(healthy.clone())).unwrap();
+        proxy.add(&upstream("other", None)).unwrap();
+        proxy.health.path = Some("/healthz".to_string());
+        proxy.health.interval = Duration::from_millis(10);
+        proxy.health.unhealthy_threshold = 2;
+        let wait_for = |state: &str| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while proxy.upstreams[0].state() != state && Instant::now() < deadline {
+                proxy.check();
+                thread::sleep(Duration::from_millis(5));
+            }
+            proxy.upstreams[0].state()
+        };
+
+        healthy.store(false, Ordering::Relaxed);
+        assert_eq!(wait_for("down"), "down");
+        assert!(!proxy.upstreams[0].is_up());
+        assert!((0..3).all(|_| proxy.order().len() == 1));
+        assert!(proxy.upstreams[1].is_up());
+
+        healthy.store(true, Ordering::Relaxed);
+        assert_eq!(wait_for("up"), "up");
+        assert_eq!(proxy.order().len(), 2);
+    }
+
+    /// Test that a request goes to the next upstream when one is down, which is then left out
+    #[test]
+    fn test_failover() {
+        let mut proxy = Proxy::default();
+        proxy.add(&closed()).unwrap();
+        proxy.add(&upstream("up", None)).unwrap();
+        for _ in 0..3 {
+            let response = proxy.forward(&request("GET /app HTTP/1.1\r\nHost: test")).unwrap();
+            assert_eq!(response.body, b"up");
+        }
+        assert!(proxy.upstreams[0].is_ejected());
+        assert!(!proxy.upstreams[1].is_ejected());
+        assert_eq!(proxy.order().len(), 1);
+
+        let mut down = Proxy::default();
+        down.add(&closed()).unwrap();
+        assert_eq!(down.forward(&request("GET / HTTP/1.1\r\nHost: test")), Err(HttpStatus::BadGateway));
+        assert_eq!(down.order().len(), 1, "tried anyway when all are out");
+    }
+
+    /// Test the head sent upstream, without the fields about the client connection
+    #[test]
+    fn test_request_head() {
+        let request = request(
+            "GET /a?b=1 HTTP/1.1\r\nHost: example.com:8080\r\nConnection: keep-alive, X-Hop\r\nX-Hop: 1\r\nAccept-Encoding: gzip\r\nX-Forwarded-For: 198.51.100.1\r\nAccept: text/html\r\nTE: trailers\r\nVia: 1.0 edge",
+        );
+        let head = String::from_utf8(Proxy::request_head(&request)).unwrap();
+        assert!(head.starts_with("GET /a?b=1 HTTP/1.0\r\nHost: example.com:8080\r\n"), "Got '{}'", head);
+        assert!(head.contains("\r\nAccept: text/html\r\n"));
+        assert!(head.contains("\r\nX-Forwarded-For: 198.51.100.1, 203.0.113.7\r\n"));
+        assert!(head.contains("\r\nX-Forwarded-Host: example.com:8080\r\nX-Forwarded-Proto: http\r\nVia: 1.0 edge, 1.1 katana\r\n"));
+        assert!(head.ends_with("\r\nConnection: close\r\n\r\n"));
+        assert!(!head.contains("X-Hop") && !head.contains("gzip") && !head.contains("keep-alive") && !head.contains("TE:"));
+        assert!(!head.contains("Content-Length"));
+    }
+
+    /// Test the fields that only concern one connection and the Via chain
+    #[test]
+    fn test_hop_by_hop() {
+        assert!(Proxy::is_hop_by_hop("keep-alive", None));
+        assert!(Proxy::is_hop_by_hop("Transfer-Encoding", None));
+        assert!(Proxy::is_hop_by_hop("X-Session", Some("close, x-session")));
+        assert!(!Proxy::is_hop_by_hop("Content-Type", Some("close")));
+        assert_eq!(Proxy::via(None, "1.1"), "1.1 katana");
+        assert_eq!(Proxy::via(Some("1.0 cdn"), "1.0"), "1.0 cdn, 1.0 katana");
+    }
+
+    /// Test the status, headers and body of an upstream response
+    #[test]
+    fn test_parse_response() {
+        let response = Proxy::parse_response(b"HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nContent-Length: 4\r\n\r\ngone and more").unwrap();
+        assert_eq!(response.status, HttpStatus::NotFound);
+        assert_eq!(response.version, "1.1");
+        assert_eq!(response.headers[0], ("Content-Type".to_string(), "text/plain".to_string()));
+        assert_eq!(response.body, b"gone");
+        assert_eq!(Proxy::parse_response(b"HTTP/1.0 204 No Content\r\n\r\n").unwrap().body, b"");
+        assert_eq!(Proxy::parse_response(b"HTTP/1.1 200 OK\r\n"), None);
+        assert_eq!(Proxy::parse_response(b"SSH-2.0\r\n\r\n"), None);
+    }
+
+    /// Test the destinations a forward proxy reaches
+    #[test]
+    fn test_forward_allows() {
+        let mut forward = ForwardProxy::default();
+        assert!(!forward.allows("example.com:443"));
+        forward.allow("*.lab.internal:443").unwrap();
+        forward.allow("10.0.0.5:*").unwrap();
+        forward.allow("*:8080").unwrap();
+        assert!(forward.allows("wiki.LAB.internal:443"));
+        assert!(!forward.allows("lab.internal:443"));
+        assert!(!forward.allows("evil-lab.internal:443"));
+        assert!(!forward.allows("wiki.lab.internal:22"));
+        assert!(forward.allows("10.0.0.5:22") && forward.allows("anything:8080"));
+        assert!(!forward.allows("10.0.0.5"));
+        assert!(forward.allow("host:port").is_err());
+    }
+
+    /// Test that an absolute-form request is sent on with its origin-form target
+    #[test]
+    fn test_forward() {
+        let address = upstream("origin", None);
+        let request = request(&format!("GET http://{}/page?x=1 HTTP/1.1\r\nHost: {}", address, address));
+        let response = ForwardProxy::forward(request.authority.as_deref().unwrap(), &request).unwrap();
+        assert_eq!(response.body, b"origin");
+        assert_eq!(ForwardProxy::forward(&closed(), &request), Err(HttpStatus::BadGateway));
+    }
+}
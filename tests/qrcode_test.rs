@@ -0,0 +1,46 @@
+use katana::qrcode::QrCode;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the smallest version holding the data is chosen
+    #[test]
+    fn test_version_selection() {
+        assert_eq!(QrCode::encode(b"http://10.0.0.1").unwrap().version, 1);
+        let qr = QrCode::encode(b"http://192.168.100.200:65535").unwrap();
+        assert_eq!(qr.version, 2);
+        assert_eq!(qr.size, 25);
+        assert_eq!(QrCode::encode(&[b'a'; 150]).unwrap().version, 7);
+        assert!(QrCode::encode(&[b'a'; 300]).is_none(), "Versions above 10 are not supported");
+    }
+
+    /// Test the finder and timing patterns of the symbol
+    #[test]
+    fn test_function_patterns() {
+        let qr = QrCode::encode(b"http://192.168.1.20:8080").unwrap();
+        let last = qr.size - 1;
+        for (x, y) in [(0, 0), (last - 6, 0), (0, last - 6)] {
+            // outer ring dark, then a light ring, then a dark 3x3 centre
+            assert!(qr.is_dark(x, y) && qr.is_dark(x + 6, y + 6));
+            assert!(!qr.is_dark(x + 1, y + 1) && !qr.is_dark(x + 5, y + 5));
+            assert!(qr.is_dark(x + 2, y + 2) && qr.is_dark(x + 3, y + 3) && qr.is_dark(x + 4, y + 4));
+        }
+        for i in 8..qr.size - 8 {
+            assert_eq!(qr.is_dark(i, 6), i % 2 == 0, "Horizontal timing pattern at {}", i);
+            assert_eq!(qr.is_dark(6, i), i % 2 == 0, "Vertical timing pattern at {}", i);
+        }
+        assert!(qr.is_dark(8, qr.size - 8), "Dark module");
+    }
+
+    /// Test that the terminal rendering packs two rows per line with a quiet zone
+    #[test]
+    fn test_to_terminal() {
+        let qr = QrCode::encode(b"http://10.0.0.1").unwrap();
+        let rendered = qr.to_terminal();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), (qr.size + 4).div_ceil(2));
+        assert!(lines.iter().all(|line| line.chars().count() == qr.size + 4));
+        assert!(lines[0].chars().all(|c| c == '█'), "Quiet zone is light");
+    }
+}
@@ -0,0 +1,52 @@
+use katana::http::HttpStatus;
+use katana::redirect::{Redirect, Redirects};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Helper function that parses a set of rules
+    fn redirects(rules: &[&str]) -> Redirects {
+        Redirects { rules: rules.iter().map(|rule| Redirects::parse(rule).unwrap()).collect() }
+    }
+
+    /// Test the rule syntax and its errors
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Redirects::parse("/old /new"),
+            Ok(Redirect { from: "/old".to_string(), prefix: false, to: "/new".to_string(), status: HttpStatus::MovedPermanently })
+        );
+        let prefix = Redirects::parse("/blog/*  https://blog.example.com/{rest}  308").unwrap();
+        assert_eq!((prefix.from.as_str(), prefix.prefix, prefix.status), ("/blog", true, HttpStatus::PermanentRedirect));
+
+        assert!(Redirects::parse("/old").is_err());
+        assert!(Redirects::parse("/old /new 303").is_err());
+        assert!(Redirects::parse("old /new").is_err());
+        assert!(Redirects::parse("/old /new/{rest}").is_err());
+    }
+
+    /// Test that exact paths win over prefixes and the longest prefix wins over the others
+    #[test]
+    fn test_resolve() {
+        let redirects = redirects(&["/docs/* /manual/{rest} 302", "/docs/api/* /reference/{rest}", "/docs/faq /help 307", "/go/home /"]);
+        assert_eq!(redirects.resolve("/docs/faq", None), Some((HttpStatus::TemporaryRedirect, "/help".to_string())));
+        assert_eq!(redirects.resolve("/docs/intro/start.html", None), Some((HttpStatus::Found, "/manual/intro/start.html".to_string())));
+        assert_eq!(redirects.resolve("/docs", None), Some((HttpStatus::Found, "/manual/".to_string())));
+        assert_eq!(redirects.resolve("/docs/api/v1", None), Some((HttpStatus::MovedPermanently, "/reference/v1".to_string())));
+        assert_eq!(redirects.resolve("/go/home", None), Some((HttpStatus::MovedPermanently, "/".to_string())));
+        assert_eq!(redirects.resolve("/docsets", None), None);
+        assert_eq!(redirects.resolve("/go/home/more", None), None);
+    }
+
+    /// Test that the rest of the path is encoded and the query kept
+    #[test]
+    fn test_rest_and_query() {
+        let redirects = redirects(&["/files/* /archive/{rest}", "/search/* /find?q={rest}"]);
+        assert_eq!(
+            redirects.resolve("/files/my report.pdf", Some("v=2")),
+            Some((HttpStatus::MovedPermanently, "/archive/my%20report.pdf?v=2".to_string()))
+        );
+        assert_eq!(redirects.resolve("/search/cats", Some("page=2")), Some((HttpStatus::MovedPermanently, "/find?q=cats".to_string())));
+    }
+}
@@ -0,0 +1,360 @@
+use katana::http::{HttpMethod, HttpStatus};
+use katana::request::{Duplicate, Request, RequestError};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::net::{IpAddr, SocketAddr};
+
+    /// Helper function that parses a request sent by the given peer
+    fn request_from(peer: &str, headers: &str) -> Request {
+        let raw = format!("GET / HTTP/1.1\r\nHost: katana.local\r\n{}\r\n", headers);
+        let mut request = Request::read_head(&mut Cursor::new(raw.into_bytes())).unwrap();
+        request.set_peer(Some(peer.parse::<SocketAddr>().unwrap()));
+        request
+    }
+
+    fn trusted(ip: IpAddr) -> bool {
+        ip.is_loopback() || ip.to_string().starts_with("10.")
+    }
+
+    /// Test the client address taken from X-Forwarded-For
+    #[test]
+    fn test_x_forwarded_for() {
+        let mut request = request_from(
+            "127.0.0.1:4000",
+            "X-Forwarded-For: 6.6.6.6, 203.0.113.7, 10.0.0.2\r\nX-Forwarded-Proto: https\r\nX-Forwarded-Host: example.com\r\n",
+        );
+        request.apply_forwarded(trusted);
+
+        // the spoofable left-most entry is ignored, the first untrusted hop wins
+        assert_eq!(request.client_ip, Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(request.client_addr().as_deref(), Some("203.0.113.7"));
+        // the proxy it came through is still known
+        assert_eq!(request.peer_addr().as_deref(), Some("127.0.0.1:4000"));
+        assert_eq!(request.scheme, "https");
+        assert_eq!(request.domain, "example.com");
+        assert_eq!(request.absolute_url("/docs/"), "https://example.com/docs/");
+    }
+
+    /// Test the client address taken from Forwarded
+    #[test]
+    fn test_forwarded() {
+        let mut request = request_from(
+            "127.0.0.1:4000",
+            "Forwarded: for=\"[2001:db8:cafe::17]:4711\";proto=https;host=example.com, for=10.0.0.2\r\n",
+        );
+        request.apply_forwarded(trusted);
+
+        assert_eq!(request.client_ip, Some("2001:db8:cafe::17".parse().unwrap()));
+        assert_eq!(request.scheme, "https");
+        assert_eq!(request.domain, "example.com");
+    }
+
+    /// Test that the scheme and host a client sends through a trusted proxy are not believed
+    #[test]
+    fn test_forwarded_spoofing() {
+        let mut request = request_from("127.0.0.1:4000", "Forwarded: host=evil;proto=https, for=203.0.113.7\r\n");
+        request.apply_forwarded(trusted);
+        assert_eq!(request.client_ip, Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(request.scheme, "http");
+        assert_eq!(request.domain, "katana.local");
+
+        // what the proxy says itself wins
+        let mut request = request_from(
+            "127.0.0.1:4000",
+            "Forwarded: host=evil;proto=https\r\nForwarded: for=203.0.113.7;host=example.com;proto=http\r\n",
+        );
+        request.apply_forwarded(trusted);
+        assert_eq!(request.scheme, "http");
+        assert_eq!(request.domain, "example.com");
+
+        let mut request = request_from(
+            "127.0.0.1:4000",
+            "X-Forwarded-For: 203.0.113.7\r\nX-Forwarded-Proto: https, http\r\nX-Forwarded-Host: evil, example.com\r\n",
+        );
+        request.apply_forwarded(trusted);
+        assert_eq!(request.scheme, "http");
+        assert_eq!(request.domain, "example.com");
+
+        // headers of an untrusted peer are ignored altogether
+        let mut request = request_from("203.0.113.9:4000", "Forwarded: for=10.0.0.2;host=evil;proto=https\r\n");
+        request.apply_forwarded(trusted);
+        assert_eq!(request.client_ip, Some("203.0.113.9".parse().unwrap()));
+        assert_eq!(request.domain, "katana.local");
+    }
+
+    /// Test that a request without proxy headers keeps its peer
+    #[test]
+    fn test_without_forwarding() {
+        let mut request = request_from("127.0.0.1:4000", "");
+        request.apply_forwarded(trusted);
+
+        assert_eq!(request.client_addr().as_deref(), Some("127.0.0.1:4000"));
+        assert_eq!(request.scheme, "http");
+        assert_eq!(request.domain, "katana.local");
+    }
+
+    /// Test that an obfuscated hop stops the walk
+    #[test]
+    fn test_unknown_hop() {
+        let mut request = request_from("127.0.0.1:4000", "Forwarded: for=203.0.113.7, for=unknown\r\n");
+        request.apply_forwarded(trusted);
+
+        assert_eq!(request.client_ip, Some("127.0.0.1".parse().unwrap()));
+    }
+
+    /// Test the https:// equivalent of a request, keeping the raw target
+    #[test]
+    fn test_https_url() {
+        let raw = "GET /a%20b/?q=1&r HTTP/1.1\r\nHost: example.com:8080\r\n\r\n";
+        let request = Request::read_head(&mut Cursor::new(raw.as_bytes().to_vec())).unwrap();
+        assert_eq!(request.target, "/a%20b/?q=1&r");
+        assert_eq!(request.https_url(443).as_deref(), Some("https://example.com/a%20b/?q=1&r"));
+        assert_eq!(request.https_url(8443).as_deref(), Some("https://example.com:8443/a%20b/?q=1&r"));
+
+        let raw = "GET / HTTP/1.1\r\nHost: [::1]\r\n\r\n";
+        let request = Request::read_head(&mut Cursor::new(raw.as_bytes().to_vec())).unwrap();
+        assert_eq!(request.https_url(443).as_deref(), Some("https://[::1]/"));
+
+        let raw = "GET / HTTP/1.0\r\n\r\n";
+        let request = Request::read_head(&mut Cursor::new(raw.as_bytes().to_vec())).unwrap();
+        assert_eq!(request.https_url(443), None);
+    }
+
+    /// Helper function that returns the status a raw request head is rejected with
+    fn rejection(raw: &str) -> Option<HttpStatus> {
+        Request::read_head(&mut Cursor::new(raw.as_bytes().to_vec())).err().and_then(|e| e.status())
+    }
+
+    /// Test that well-formed request lines parse, including leading empty lines and absolute-form
+    #[test]
+    fn test_request_line() {
+        let raw = "\r\nPOST http://example.com/api?x=1 HTTP/1.0\nHost:example.com\n\n";
+        let request = Request::read_head(&mut Cursor::new(raw.as_bytes().to_vec())).unwrap();
+        assert_eq!(request.method, HttpMethod::POST);
+        assert_eq!(request.path, "/api");
+        assert_eq!(request.target, "/api?x=1");
+        assert_eq!(request.domain, "example.com");
+
+        let (method, target, _) = Request::parse_request_line("OPTIONS * HTTP/1.1").unwrap();
+        assert_eq!((method, target.as_str()), (HttpMethod::OPTIONS, "*"));
+    }
+
+    /// Test the destination of absolute-form and CONNECT targets
+    #[test]
+    fn test_authority() {
+        let authority = |line: &str| Request::read_head(&mut Cursor::new(format!("{}\r\n\r\n", line).into_bytes())).unwrap().authority;
+        assert_eq!(authority("GET http://example.com/a HTTP/1.1"), Some("example.com:80".to_string()));
+        assert_eq!(authority("GET http://example.com:8080?x=1 HTTP/1.1"), Some("example.com:8080".to_string()));
+        assert_eq!(authority("GET http://[::1]/ HTTP/1.1"), Some("[::1]:80".to_string()));
+        assert_eq!(authority("CONNECT example.com:443 HTTP/1.1"), Some("example.com:443".to_string()));
+        assert_eq!(authority("GET https://example.com/ HTTP/1.1"), None);
+        assert_eq!(authority("GET /a HTTP/1.1"), None);
+        assert_eq!(authority("CONNECT / HTTP/1.1"), None);
+
+        assert_eq!(rejection("CONNECT example.com HTTP/1.1\r\n\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("GET example.com:443 HTTP/1.1\r\n\r\n"), Some(HttpStatus::BadRequest));
+    }
+
+    /// Test the status of each kind of malformed request head
+    #[test]
+    fn test_malformed_requests() {
+        assert_eq!(rejection("GET  / HTTP/1.1\r\n\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("GET /\r\n\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("GET / HTTP/1.1 extra\r\n\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("G(T / HTTP/1.1\r\n\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("GET index.html HTTP/1.1\r\n\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("GET * HTTP/1.1\r\n\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("GET / HTTP/one\r\n\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("GET / HTTP/2.0\r\n\r\n"), Some(HttpStatus::HTTPVersionNotSupported));
+        assert_eq!(rejection("BREW / HTTP/1.1\r\n\r\n"), Some(HttpStatus::NotImplemented));
+        assert_eq!(rejection("GET / HTTP/1.1\r\nHost : a\r\n\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("GET / HTTP/1.1\r\nno colon\r\n\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("GET / HTTP/1.1\r\nHost: a\r\n"), Some(HttpStatus::BadRequest));
+
+        let long_target = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(10000));
+        assert_eq!(rejection(&long_target), Some(HttpStatus::URITooLong));
+        let long_header = format!("GET / HTTP/1.1\r\nX-Big: {}\r\n\r\n", "a".repeat(10000));
+        assert_eq!(rejection(&long_header), Some(HttpStatus::RequestHeaderFieldsTooLarge));
+        let many_headers = format!("GET / HTTP/1.1\r\n{}\r\n", "X-A: b\r\n".repeat(101));
+        assert_eq!(rejection(&many_headers), Some(HttpStatus::RequestHeaderFieldsTooLarge));
+
+        let closed = Request::read_head(&mut Cursor::new(Vec::new()));
+        assert!(matches!(closed, Err(RequestError::Closed)));
+    }
+
+    /// Helper function that reads a whole request with a body limit
+    fn read(raw: &str, limit: usize) -> Result<Request, RequestError> {
+        let mut reader = Cursor::new(raw.as_bytes().to_vec());
+        let mut request = Request::read_head(&mut reader)?;
+        request.read_body(&mut reader, limit).map(|_| request)
+    }
+
+    /// Test that chunked bodies are decoded, with their trailers kept apart from the headers
+    #[test]
+    fn test_chunked_body() {
+        let raw = "POST / HTTP/1.1\r\nTransfer-Encoding: Chunked\r\n\r\n4\r\nWiki\r\na;name=value\r\npedia is a\r\n0\r\nExpires: never\r\n\r\nGET";
+        let mut reader = Cursor::new(raw.as_bytes().to_vec());
+        let mut request = Request::read_head(&mut reader).unwrap();
+        request.read_body(&mut reader, 1024).unwrap();
+        assert_eq!(request.body, b"Wikipedia is a");
+        assert_eq!(request.trailer("expires"), Some("never"));
+        assert_eq!(request.header("Expires"), None);
+        assert_eq!(request.header("Transfer-Encoding"), None);
+        assert_eq!(request.content_length(), Some(14));
+        assert_eq!(reader.position() as usize, raw.len() - 3, "the next request is left unread");
+    }
+
+    /// Test that malformed, oversized and unsupported chunked bodies are refused
+    #[test]
+    fn test_chunked_body_errors() {
+        let status = |raw: &str, limit: usize| read(raw, limit).err().and_then(|e| e.status());
+        let chunked = |body: &str| format!("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n{}", body);
+        assert_eq!(status(&chunked("5\r\nhello\r\n5\r\nworld\r\n0\r\n\r\n"), 8), Some(HttpStatus::PayloadTooLarge));
+        assert!(read(&chunked("5\r\nhello\r\n0\r\n\r\n"), 5).is_ok());
+        assert_eq!(status(&chunked("+5\r\nhello\r\n0\r\n\r\n"), 64), Some(HttpStatus::BadRequest));
+        assert_eq!(status(&chunked("fffffffffffffffffffff\r\n"), 64), Some(HttpStatus::BadRequest));
+        assert_eq!(status(&chunked("3\r\nhello\r\n0\r\n\r\n"), 64), Some(HttpStatus::BadRequest));
+        assert_eq!(status(&chunked("5\r\nhel"), 64), None, "the connection went away");
+        assert_eq!(status(&chunked("0\r\nbad trailer\r\n\r\n"), 64), Some(HttpStatus::BadRequest));
+
+        let both = "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 5\r\n\r\n0\r\n\r\n";
+        assert_eq!(status(both, 64), Some(HttpStatus::BadRequest));
+        let gzip = "POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n";
+        assert_eq!(status(gzip, 64), Some(HttpStatus::NotImplemented));
+    }
+
+    /// Test that a Content-Length that does not parse fails the request instead of meaning no body
+    #[test]
+    fn test_content_length_errors() {
+        let status = |length: &str| {
+            let mut request = Request::default();
+            request.headers.push(("Content-Length".to_string(), length.to_string()));
+            let mut reader = Cursor::new(b"GET /smuggled HTTP/1.1\r\n\r\n".to_vec());
+            request.read_body(&mut reader, 1024).err().and_then(|e| e.status())
+        };
+        assert_eq!(status("99999999999999999999999"), Some(HttpStatus::BadRequest));
+        assert_eq!(status("5x"), Some(HttpStatus::BadRequest));
+        assert_eq!(status(""), Some(HttpStatus::BadRequest));
+        assert_eq!(status("26"), None);
+    }
+
+    /// Test what repeated headers become
+    #[test]
+    fn test_duplicate_headers() {
+        let request = request_from(
+            "127.0.0.1:4000",
+            "Accept-Encoding: gzip\r\naccept-encoding: br\r\nCookie: a=1\r\nCookie: b=2\r\nUser-Agent: first\r\nUser-Agent: second\r\n",
+        );
+        assert_eq!(request.header("Accept-Encoding"), Some("gzip, br"));
+        assert_eq!(request.headers.iter().filter(|(name, _)| name.eq_ignore_ascii_case("accept-encoding")).count(), 1);
+        assert_eq!(request.header("Cookie"), Some("a=1; b=2"));
+        assert_eq!(request.cookies, vec![("a".to_string(), "1".to_string()), ("b".to_string(), "2".to_string())]);
+        assert_eq!(request.header("User-Agent"), Some("first"));
+        assert_eq!(Request::duplicate("x-forwarded-for"), Duplicate::Merge(", "));
+        assert_eq!(Request::duplicate("X-Custom"), Duplicate::First);
+
+        let rejection = |headers: &str| {
+            let raw = format!("POST / HTTP/1.1\r\n{}\r\n", headers);
+            Request::read_head(&mut Cursor::new(raw.into_bytes())).err().and_then(|e| e.status())
+        };
+        assert_eq!(rejection("Content-Length: 5\r\nContent-Length: 5\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("Content-Length: 5\r\ncontent-length: 50\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("Content-Length: 5, 5\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("Content-Length: -1\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("Content-Length: 99999999999999999999999\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("Content-Length: +5\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("Host: a\r\nHost: b\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("Authorization: Basic a\r\nAuthorization: Basic b\r\n"), Some(HttpStatus::BadRequest));
+        assert_eq!(rejection("Host: a\r\nContent-Length: 5\r\n"), None);
+    }
+
+    /// Test that pipelined requests parsed with one buffer keep nothing of each other
+    #[test]
+    fn test_read_head_with() {
+        let raw = "\r\nGET /a%20b?x=1&y=2 HTTP/1.1\r\nHost: one\r\nX-Long: aaaaaaaaaaaaaaaa\r\n\r\nGET /c HTTP/1.1\r\nHost: two\r\nA: b\r\n\r\n";
+        let mut reader = Cursor::new(raw.as_bytes().to_vec());
+        let mut buffer = Vec::new();
+
+        let first = Request::read_head_with(&mut reader, &mut buffer).unwrap();
+        assert_eq!(first.path, "/a b");
+        assert_eq!(first.queries, vec![("x".to_string(), "1".to_string()), ("y".to_string(), "2".to_string())]);
+        assert_eq!(first.header("X-Long"), Some("aaaaaaaaaaaaaaaa"));
+        let second = Request::read_head_with(&mut reader, &mut buffer).unwrap();
+        assert_eq!((second.path.as_str(), second.domain.as_str()), ("/c", "two"));
+        assert_eq!(second.headers, vec![("Host".to_string(), "two".to_string()), ("A".to_string(), "b".to_string())]);
+        assert!(matches!(Request::read_head_with(&mut reader, &mut buffer), Err(RequestError::Closed)));
+    }
+
+    /// Test which requests wait for a 100 Continue
+    #[test]
+    fn test_expects_continue() {
+        let expects = |version: &str, headers: &str| {
+            let raw = format!("POST / {}\r\n{}\r\n", version, headers);
+            Request::read_head(&mut Cursor::new(raw.into_bytes())).unwrap().expects_continue().map_err(|e| e.status())
+        };
+        assert_eq!(expects("HTTP/1.1", "Expect: 100-Continue\r\nContent-Length: 5\r\n"), Ok(true));
+        assert_eq!(expects("HTTP/1.1", "Expect: 100-continue\r\nTransfer-Encoding: chunked\r\n"), Ok(true));
+        assert_eq!(expects("HTTP/1.1", "Expect: 100-continue\r\nContent-Length: 0\r\n"), Ok(false));
+        assert_eq!(expects("HTTP/1.0", "Expect: 100-continue\r\nContent-Length: 5\r\n"), Ok(false));
+        assert_eq!(expects("HTTP/1.1", "Content-Length: 5\r\n"), Ok(false));
+        assert_eq!(expects("HTTP/1.1", "Expect: teapot\r\n"), Err(Some(HttpStatus::ExpectationFailed)));
+    }
+
+    /// Test the method a POST asks to be handled as
+    #[test]
+    fn test_method_override() {
+        let method = |request_line: &str, headers: &str, body: &str| {
+            let raw = format!("{}\r\n{}Content-Length: {}\r\n\r\n{}", request_line, headers, body.len(), body);
+            let request = read(&raw, 1024).unwrap();
+            request.method_override().map(|method| method.as_str().to_string())
+        };
+        let form = "Content-Type: application/x-www-form-urlencoded; charset=utf-8\r\n";
+        assert_eq!(method("POST / HTTP/1.1", "X-HTTP-Method-Override: patch\r\n", ""), Some("PATCH".to_string()));
+        assert_eq!(method("POST / HTTP/1.1", form, "a=1&_method=DELETE"), Some("DELETE".to_string()));
+        assert_eq!(method("POST / HTTP/1.1", "", "_method=DELETE"), None, "only forms have fields");
+        assert_eq!(method("POST / HTTP/1.1", "X-HTTP-Method-Override: GET\r\n", ""), None);
+        assert_eq!(method("GET / HTTP/1.1", "X-HTTP-Method-Override: DELETE\r\n", ""), None);
+    }
+
+    /// Test that form fields are decoded from urlencoded bodies and cookies found by name
+    #[test]
+    fn test_form_field() {
+        let raw = "POST /docs/ HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n\
+                   Cookie: theme=dark; katana_csrf=abc\r\nContent-Length: 27\r\n\r\nfolder=Q3+report%21&_csrf=x";
+        let request = read(raw, 1024).unwrap();
+        assert_eq!(request.form_field("folder").as_deref(), Some("Q3 report!"));
+        assert_eq!(request.form_field("_csrf").as_deref(), Some("x"));
+        assert_eq!(request.form_field("missing"), None);
+        assert_eq!(request.cookie("katana_csrf"), Some("abc"));
+        assert_eq!(request.cookie("Theme"), None);
+
+        let raw = "POST / HTTP/1.1\r\nContent-Type: text/plain\r\nContent-Length: 8\r\n\r\nfolder=a";
+        assert_eq!(read(raw, 1024).unwrap().form_field("folder"), None);
+    }
+
+    /// Test the typed header accessors
+    #[test]
+    fn test_header_helpers() {
+        let request = request_from(
+            "127.0.0.1:4000",
+            "Accept-Encoding: br, gzip;q=0.5, identity;q=0\r\nif-none-match: \"abc\"\r\n\
+             If-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\nIf-Unmodified-Since: yesterday\r\nrange: bytes=0-9\r\n",
+        );
+        assert!(request.accepts_encoding("gzip") && request.accepts_encoding("br"));
+        assert!(!request.accepts_encoding("identity") && !request.accepts_encoding("zstd"));
+        assert_eq!(request.if_none_match(), Some("\"abc\""));
+        assert_eq!(request.if_match(), None);
+        assert_eq!(request.if_modified_since(), Some(784111777));
+        assert_eq!(request.if_unmodified_since(), None, "a date that does not parse is ignored");
+        assert_eq!(request.range(), Some("bytes=0-9"));
+        assert_eq!(request.host(), Some("katana.local"));
+
+        assert_eq!(Request::host_name("Example.com.:8080"), "Example.com");
+        assert_eq!(Request::host_name("[::1]"), "[::1]");
+        assert_eq!(Request::host_name("[::1]:8080"), "[::1]");
+        assert!(request_from("127.0.0.1:4000", "").accepts_encoding("identity"));
+    }
+}
@@ -0,0 +1,103 @@
+#![cfg(feature = "landlock")]
+
+use katana::config::Config;
+use katana::sandbox::{Access, Rule, Sandbox};
+use katana::server::Server;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::thread;
+
+    fn config(args: &[&str]) -> Config {
+        let args = std::iter::once("").chain(args.iter().copied()).map(String::from).collect();
+        let (config, errors) = Config::parse(args);
+        assert!(errors.is_empty(), "{:?}", errors);
+        config
+    }
+
+    fn access(rules: &[Rule], path: &str) -> Option<Access> {
+        rules.iter().find(|rule| rule.path.as_path() == Path::new(path)).map(|rule| rule.access)
+    }
+
+    /// Test that the share is read only unless writable, and that logs and uploads can be written
+    #[test]
+    fn test_rules() {
+        assert!(config(&["--sandbox"]).sandbox);
+        assert!(!config(&[]).sandbox);
+
+        let rules = Sandbox::rules(&config(&["--dir", "/srv/www"]));
+        assert_eq!(access(&rules, "/srv/www"), Some(Access::Read));
+        assert_eq!(access(&rules, "/etc/resolv.conf"), Some(Access::Read));
+        assert_eq!(access(&rules, "/etc/shadow"), None);
+
+        let rules = Sandbox::rules(&config(&[
+            "--dir",
+            "/srv/www",
+            "--read-only=false",
+            "--writable",
+            "--tus",
+            "/uploads",
+            "--tus-dir",
+            "/var/spool/katana",
+            "--error-log",
+            "/var/log/katana/error.log",
+        ]));
+        assert_eq!(access(&rules, "/srv/www"), Some(Access::Write));
+        assert_eq!(access(&rules, "/var/spool/katana"), Some(Access::Write));
+        assert_eq!(access(&rules, "/var/log/katana"), Some(Access::Log));
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--sandbox".to_string(), "--userdir".to_string()]);
+        assert_eq!(errors, vec!["--sandbox cannot be used with --userdir, home directories are outside the sandbox"]);
+        // nothing can be executed in the sandbox
+        let errors = |args: &[&str]| Config::parse(std::iter::once("").chain(args.iter().copied()).map(String::from).collect()).1;
+        assert_eq!(
+            errors(&["--sandbox", "--handler", "cgi=cgi"]),
+            vec!["--sandbox cannot be used with cgi or fastcgi handlers, the sandbox cannot run scripts"]
+        );
+        assert_eq!(errors(&["--sandbox", "--handler", "php=fastcgi:127.0.0.1:9000"]).len(), 1);
+        assert!(errors(&["--sandbox", "--handler", "php=fastcgi:127.0.0.1:9000", "--handler", "php=deny"]).is_empty());
+        assert_eq!(
+            errors(&["--sandbox", "--hook", "upload=./scan.sh"]),
+            vec!["--sandbox cannot be used with --hook, the sandbox cannot run commands"]
+        );
+        assert!(Server::features().contains(&"landlock"));
+    }
+
+    /// Test that a sandboxed thread reads the share and nothing next to it
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_apply() {
+        let base = env::temp_dir().join("sandbox_test_apply");
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(base.join("share")).unwrap();
+        fs::write(base.join("share/index.html"), "hello").unwrap();
+        fs::write(base.join("secret.key"), "key").unwrap();
+
+        // the sandbox holds for the thread that applies it, the test runner stays free
+        let share = base.join("share");
+        let sandboxed = thread::spawn(move || {
+            let rules = vec![Rule { path: share.clone(), access: Access::Read }];
+            if !Sandbox::apply(&rules).unwrap() {
+                return None;
+            }
+            let inside = fs::read_to_string(share.join("index.html")).ok();
+            let outside = fs::read_to_string(share.join("../secret.key")).ok();
+            let written = fs::write(share.join("new.html"), "x").is_ok();
+            Some((inside, outside, written))
+        })
+        .join()
+        .unwrap();
+
+        // kernels without Landlock run unsandboxed
+        if let Some((inside, outside, written)) = sandboxed {
+            assert_eq!(inside.as_deref(), Some("hello"));
+            assert_eq!(outside, None);
+            assert!(!written);
+        }
+        let _ = fs::remove_dir_all(&base);
+    }
+}
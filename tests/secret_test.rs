@@ -0,0 +1,30 @@
+use katana::secret::Secret;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    /// Test that secrets come from the environment, from files without their last line break, or as given
+    #[test]
+    fn test_resolve() {
+        assert_eq!(Secret::resolve("change me"), Ok("change me".to_string()));
+
+        env::set_var("SECRET_TEST_TOKEN", "s3cret");
+        assert_eq!(Secret::resolve("env:SECRET_TEST_TOKEN"), Ok("s3cret".to_string()));
+        assert_eq!(
+            Secret::resolve("env:SECRET_TEST_MISSING"),
+            Err("environment variable SECRET_TEST_MISSING is not set".to_string())
+        );
+
+        let path = env::temp_dir().join("secret_test_resolve");
+        fs::write(&path, "line one\nline two\r\n").unwrap();
+        let file = format!("file:{}", path.display());
+        assert_eq!(Secret::resolve(&file), Ok("line one\nline two".to_string()));
+        fs::write(&path, "no line break").unwrap();
+        assert_eq!(Secret::resolve(&file), Ok("no line break".to_string()));
+        fs::remove_file(&path).unwrap();
+        assert!(Secret::resolve(&file).unwrap_err().starts_with("cannot read secret file"));
+    }
+}
@@ -0,0 +1,1449 @@
+use katana::config::Config;
+use katana::crypto::Crypto;
+use katana::plugin::{Plugin, PluginAction, RequestView};
+use katana::connection::BindError;
+use katana::server::Server;
+use katana::signed::SignedUrls;
+use katana::templates::Templates;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::path::Path;
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+
+    /// Helper function that starts a server on an ephemeral port and returns its URL
+    fn start_server(root_dir: &Path) -> String {
+        start_server_with(root_dir, &["--port", "0"])
+    }
+
+    /// Helper function that starts a server with extra arguments and returns its URL
+    fn start_server_with(root_dir: &Path, extra_args: &[&str]) -> String {
+        let mut args = vec![
+            "".to_string(),
+            "--host".to_string(),
+            "127.0.0.1".to_string(),
+            "--dir".to_string(),
+            root_dir.to_string_lossy().to_string(),
+        ];
+        args.extend(extra_args.iter().map(|arg| arg.to_string()));
+        let server = Server::new(Config::parse_args(args), Templates::load());
+
+        let listeners = server.listen().expect("Server should bind");
+        let url = listeners[0].url().expect("TCP listener should have a URL");
+        thread::spawn(move || server.run(listeners));
+
+        url
+    }
+
+    /// Helper function that sends a raw request and returns the whole response
+    fn send(url: &str, request: &str) -> String {
+        let addr = url.trim_start_matches("http://");
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+
+        // compressed bodies are not UTF-8, only the head matters for those
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    /// Test that port 0 binds a real port and reports it
+    #[test]
+    fn test_ephemeral_port() {
+        let root_dir = env::temp_dir().join("server_test_ephemeral");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("hello.txt"), "hello").unwrap();
+
+        let url = start_server(&root_dir);
+        assert!(url.starts_with("http://127.0.0.1:"));
+        assert!(!url.ends_with(":0"), "Reported URL should carry the bound port");
+
+        let response = send(&url, "GET /hello.txt HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("hello"));
+    }
+
+    /// Test that a busy port falls back to the next one with --port-retry
+    #[test]
+    fn test_port_retry() {
+        let busy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let busy_port = busy.local_addr().unwrap().port();
+
+        let url = start_server_with(
+            &env::temp_dir(),
+            &["--port", &busy_port.to_string(), "--port-retry", "10"],
+        );
+
+        assert!(!url.ends_with(&format!(":{}", busy_port)), "Busy port should be skipped");
+    }
+
+    /// Test that bind failures are reported with a distinct exit code
+    #[test]
+    fn test_bind_errors() {
+        let busy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let busy_port = busy.local_addr().unwrap().port().to_string();
+
+        let config = Config::parse_args(vec![
+            "".to_string(),
+            "--host".to_string(),
+            "127.0.0.1".to_string(),
+            "--port".to_string(),
+            busy_port,
+        ]);
+        match Server::new(config, Templates::load()).bind() {
+            Err(e @ BindError::AddrInUse(_)) => assert_eq!(e.exit_code(), BindError::EXIT_ADDR_IN_USE),
+            _ => panic!("Busy port should fail with AddrInUse"),
+        }
+
+        let config = Config::parse_args(vec![
+            "".to_string(),
+            "--host".to_string(),
+            "no such host".to_string(),
+        ]);
+        match Server::new(config, Templates::load()).bind() {
+            Err(e @ BindError::InvalidAddress(_, _)) => {
+                assert_eq!(e.exit_code(), BindError::EXIT_INVALID_ADDRESS)
+            }
+            _ => panic!("Unresolvable host should fail with InvalidAddress"),
+        }
+    }
+
+    /// Helper function that returns the value of a response header
+    fn header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+        response
+            .split("\r\n\r\n")
+            .next()?
+            .lines()
+            .filter_map(|line| line.split_once(": "))
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Test that every response carries a request ID, taken from trusted proxies only
+    #[test]
+    fn test_request_id() {
+        let request = "GET / HTTP/1.1\r\nHost: test\r\nX-Request-Id: from-proxy\r\nConnection: close\r\n\r\n";
+
+        let url = start_server(&env::temp_dir());
+        let first = send(&url, request);
+        let second = send(&url, request);
+        let first_id = header(&first, "X-Request-Id").expect("Response should carry an ID");
+        let second_id = header(&second, "X-Request-Id").expect("Response should carry an ID");
+        assert_eq!(first_id.len(), 16);
+        assert_ne!(first_id, "from-proxy", "Untrusted peers cannot choose the ID");
+        assert_ne!(first_id, second_id);
+
+        let url = start_server_with(&env::temp_dir(), &["--port", "0", "--trusted-proxy", "127.0.0.1"]);
+        let response = send(&url, request);
+        assert_eq!(header(&response, "X-Request-Id"), Some("from-proxy"));
+    }
+
+    /// Test that --watch injects the live reload script into HTML pages only
+    #[test]
+    fn test_live_reload_injection() {
+        let root_dir = env::temp_dir().join("server_test_live_reload");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("index.html"), "<html><body><h1>Hi</h1></body></html>").unwrap();
+        fs::write(root_dir.join("hello.txt"), "hello").unwrap();
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--watch"]);
+        let page = send(&url, "GET / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n");
+        let body = page.split("\r\n\r\n").nth(1).unwrap();
+        assert!(body.ends_with("</script></body></html>"), "Got '{}'", body);
+        assert_eq!(header(&page, "Content-Length"), Some(body.len().to_string().as_str()));
+
+        let text = send(&url, "GET /hello.txt HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n");
+        assert!(text.ends_with("\r\n\r\nhello"));
+    }
+
+    /// Test that HTTP-01 challenges are answered from --acme-dir for the configured domains
+    #[test]
+    fn test_acme_challenge() {
+        let root_dir = env::temp_dir().join("server_test_acme_root");
+        let acme_dir = env::temp_dir().join("server_test_acme_challenges");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::create_dir_all(&acme_dir).unwrap();
+        fs::write(acme_dir.join("abc123"), "abc123.thumb\n").unwrap();
+
+        let url = start_server_with(
+            &root_dir,
+            &["--port", "0", "--acme-dir", acme_dir.to_str().unwrap(), "--acme-domain", "example.com"],
+        );
+        let challenge = "GET /.well-known/acme-challenge/abc123 HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n";
+        let response = send(&url, challenge);
+        assert!(response.starts_with("HTTP/1.1 200"), "Got '{}'", response);
+        assert_eq!(header(&response, "Content-Type"), Some("text/plain"));
+        assert!(response.ends_with("\r\n\r\nabc123.thumb"));
+
+        let other_host = send(&url, &challenge.replace("example.com", "other.org"));
+        assert!(other_host.starts_with("HTTP/1.1 404"), "Got '{}'", other_host);
+    }
+
+    /// Test that --https-redirect sends plain requests to https:// except ACME challenges
+    #[test]
+    fn test_https_redirect() {
+        let root_dir = env::temp_dir().join("server_test_https_redirect");
+        fs::create_dir_all(&root_dir).unwrap();
+        let url = start_server_with(&root_dir, &["--port", "0", "--https-redirect", "--trusted-proxy", "127.0.0.1"]);
+
+        let response = send(&url, "GET /docs/?page=2 HTTP/1.1\r\nHost: example.com:80\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 301"), "Got '{}'", response);
+        assert_eq!(header(&response, "Location"), Some("https://example.com/docs/?page=2"));
+
+        let challenge = send(&url, "GET /.well-known/acme-challenge/abc HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n");
+        assert!(challenge.starts_with("HTTP/1.1 404"), "Got '{}'", challenge);
+
+        let proxied = "GET /docs/ HTTP/1.1\r\nHost: example.com\r\nX-Forwarded-Proto: https\r\nConnection: close\r\n\r\n";
+        assert!(send(&url, proxied).starts_with("HTTP/1.1 404"), "TLS terminated by a trusted proxy is not redirected");
+    }
+
+    /// Test that --sign-protect paths need a valid signed link, which also stands in for a JWT
+    #[test]
+    fn test_signed_links() {
+        let root_dir = env::temp_dir().join("server_test_signed_links");
+        fs::create_dir_all(root_dir.join("private")).unwrap();
+        fs::write(root_dir.join("private").join("report.txt"), "numbers").unwrap();
+        let url = start_server_with(
+            &root_dir,
+            &["--port", "0", "--sign-secret", "s3cret", "--sign-protect", "/private", "--jwt-secret", "other"],
+        );
+        let get = |target: &str| send(&url, &format!("GET {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n", target));
+
+        let unsigned = get("/private/report.txt");
+        assert!(unsigned.starts_with("HTTP/1.1 403"), "Got '{}'", unsigned);
+
+        let link = SignedUrls::sign(b"s3cret", "/private/report.txt", 4_000_000_000);
+        let signed = get(&link);
+        assert!(signed.starts_with("HTTP/1.1 200"), "Got '{}'", signed);
+        assert!(signed.ends_with("\r\n\r\nnumbers"));
+
+        let tampered = get(&link.replace("report.txt", "other.txt"));
+        assert!(tampered.starts_with("HTTP/1.1 403"), "Got '{}'", tampered);
+        let expired = get(&SignedUrls::sign(b"s3cret", "/private/report.txt", 1));
+        assert!(expired.starts_with("HTTP/1.1 410"), "Got '{}'", expired);
+    }
+
+    /// Test that a .maintenance file turns requests into 503s while /healthz keeps answering
+    #[test]
+    fn test_maintenance() {
+        let root_dir = env::temp_dir().join("server_test_maintenance");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("hello.txt"), "hello").unwrap();
+        let _ = fs::remove_file(root_dir.join(".maintenance"));
+        let page = env::temp_dir().join("server_test_maintenance.html");
+        fs::write(&page, "<h1>Back soon</h1>").unwrap();
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--maintenance-page", page.to_str().unwrap(), "--retry-after", "120"]);
+        let get = |path: &str| send(&url, &format!("GET {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n", path));
+
+        assert!(get("/hello.txt").starts_with("HTTP/1.1 200"));
+        let health = get("/healthz");
+        assert!(health.starts_with("HTTP/1.1 200"), "Got '{}'", health);
+        assert!(health.ends_with("{\"status\":\"ok\"}"));
+
+        fs::write(root_dir.join(".maintenance"), "").unwrap();
+        let response = get("/hello.txt");
+        fs::remove_file(root_dir.join(".maintenance")).unwrap();
+        assert!(response.starts_with("HTTP/1.1 503"), "Got '{}'", response);
+        assert_eq!(header(&response, "Retry-After"), Some("120"));
+        assert!(response.ends_with("<h1>Back soon</h1>"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--maintenance"]);
+        let health = send(&url, "GET /healthz HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n");
+        assert!(health.starts_with("HTTP/1.1 503"), "Got '{}'", health);
+        assert!(health.ends_with("{\"status\":\"maintenance\"}"));
+    }
+
+    /// Test that text bodies are gzipped for clients asking for it, above the minimum size
+    #[test]
+    fn test_compression() {
+        let root_dir = env::temp_dir().join("server_test_compression");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("big.txt"), "all work and no play ".repeat(500)).unwrap();
+        fs::write(root_dir.join("small.txt"), "tiny").unwrap();
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--compression-min-size", "1K"]);
+        let get = |path: &str, accept: &str| {
+            send(&url, &format!("GET {} HTTP/1.1\r\nHost: test\r\nAccept-Encoding: {}\r\nConnection: close\r\n\r\n", path, accept))
+        };
+
+        let big = get("/big.txt", "gzip, deflate");
+        assert_eq!(header(&big, "Content-Encoding"), Some("gzip"));
+        assert_eq!(header(&big, "Vary"), Some("Accept-Encoding"));
+        let length: usize = header(&big, "Content-Length").unwrap().parse().unwrap();
+        assert!(length < 500, "Got {} bytes", length);
+
+        assert_eq!(header(&get("/small.txt", "gzip"), "Content-Encoding"), None);
+        assert_eq!(header(&get("/big.txt", "br"), "Content-Encoding"), None);
+        assert_eq!(header(&get("/big.txt", "gzip;q=0, identity"), "Content-Encoding"), None);
+        let refused = get("/big.txt", "br, identity;q=0");
+        assert!(refused.starts_with("HTTP/1.1 406"), "Got '{}'", refused);
+    }
+
+    /// Test that --default-language picks localized index pages from Accept-Language
+    #[test]
+    fn test_localized_index() {
+        let root_dir = env::temp_dir().join("server_test_localized");
+        fs::create_dir_all(root_dir.join("docs")).unwrap();
+        fs::write(root_dir.join("index.en.html"), "hello").unwrap();
+        fs::write(root_dir.join("index.fr.html"), "bonjour").unwrap();
+        fs::write(root_dir.join("docs").join("index.html"), "docs").unwrap();
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--default-language", "en"]);
+        let get = |path: &str, accept: &str| {
+            send(&url, &format!("GET {} HTTP/1.1\r\nHost: test\r\nAccept-Language: {}\r\nConnection: close\r\n\r\n", path, accept))
+        };
+
+        let french = get("/", "fr-CA, en;q=0.5");
+        assert!(french.ends_with("\r\n\r\nbonjour"), "Got '{}'", french);
+        assert_eq!(header(&french, "Content-Language"), Some("fr"));
+        assert_eq!(header(&french, "Vary"), Some("Accept-Language"));
+
+        let fallback = get("/index.html", "de");
+        assert!(fallback.ends_with("\r\n\r\nhello"), "Got '{}'", fallback);
+        assert_eq!(header(&fallback, "Content-Language"), Some("en"));
+
+        let unlocalized = get("/docs/", "fr");
+        assert!(unlocalized.ends_with("\r\n\r\ndocs"));
+        assert_eq!(header(&unlocalized, "Content-Language"), None);
+    }
+
+    /// Test that a path without a file of its own is answered by the variant Accept prefers
+    #[test]
+    fn test_accept_variants() {
+        let root_dir = env::temp_dir().join("server_test_accept_variants");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("data.json"), "{}").unwrap();
+        fs::write(root_dir.join("data.xml"), "<data/>").unwrap();
+
+        let url = start_server(&root_dir);
+        let get = |accept: &str| {
+            send(&url, &format!("GET /data HTTP/1.1\r\nHost: test\r\nAccept: {}\r\nConnection: close\r\n\r\n", accept))
+        };
+
+        let xml = get("application/xml, application/json;q=0.5");
+        assert!(xml.ends_with("\r\n\r\n<data/>"), "Got '{}'", xml);
+        assert_eq!(header(&xml, "Content-Type"), Some("application/xml"));
+        assert_eq!(header(&xml, "Vary"), Some("Accept"));
+        assert!(get("application/json").ends_with("\r\n\r\n{}"));
+
+        let refused = get("image/png");
+        assert!(refused.starts_with("HTTP/1.1 406"), "Got '{}'", refused);
+    }
+
+    /// Test that malformed requests are answered with an error instead of a dropped connection
+    #[test]
+    fn test_bad_requests() {
+        let url = start_server(&env::temp_dir());
+
+        let response = send(&url, "GET /  HTTP/1.1\r\nHost: test\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 400"), "Got '{}'", response);
+        assert_eq!(header(&response, "Connection"), Some("close"));
+
+        let response = send(&url, "BREW /pot HTTP/1.1\r\nHost: test\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 501"), "Got '{}'", response);
+
+        let response = send(&url, &format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(10000)));
+        assert!(response.starts_with("HTTP/1.1 414"), "Got '{}'", response);
+    }
+
+    /// Plugin that panics on /panic, as a buggy handler would
+    struct Faulty;
+
+    impl Plugin for Faulty {
+        fn name(&self) -> &str {
+            "faulty"
+        }
+
+        fn on_request(&self, request: &mut RequestView) -> PluginAction {
+            if request.path() == "/panic" {
+                panic!("faulty plugin");
+            }
+            PluginAction::Continue
+        }
+    }
+
+    /// Test that a panicking handler answers 500 and closes the connection instead of hanging
+    #[test]
+    fn test_handler_panic() {
+        let root_dir = env::temp_dir();
+        let args = vec!["", "--host", "127.0.0.1", "--port", "0", "--dir", root_dir.to_str().unwrap()];
+        let mut server = Server::new(Config::parse_args(args.into_iter().map(String::from).collect()), Templates::load());
+        server.add_plugin(Faulty);
+        let listeners = server.listen().unwrap();
+        let url = listeners[0].url().unwrap();
+        thread::spawn(move || server.run(listeners));
+
+        // keep-alive is asked for, the server still has to close for read_to_end to return
+        let response = send(&url, "GET /panic HTTP/1.1\r\nHost: test\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 500"), "Got '{}'", response);
+        assert_eq!(header(&response, "Connection"), Some("close"));
+
+        let response = send(&url, "GET / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200"), "Got '{}'", response);
+    }
+
+    /// Test that an upload is created, resumed from the offset the server reports and completed
+    #[test]
+    fn test_tus_upload() {
+        let root_dir = env::temp_dir().join("server_test_tus_upload");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(&root_dir).unwrap();
+        let url = start_server_with(&root_dir, &["--port", "0", "--read-only=false", "--tus", "/uploads"]);
+        let patch = |location: &str, offset: usize, chunk: &str| {
+            send(&url, &format!(
+                "PATCH {} HTTP/1.1\r\nTus-Resumable: 1.0.0\r\nContent-Type: application/offset+octet-stream\r\n\
+                 Upload-Offset: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                location, offset, chunk.len(), chunk
+            ))
+        };
+
+        let options = send(&url, "OPTIONS /uploads HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert!(options.starts_with("HTTP/1.1 204"), "Got '{}'", options);
+        assert_eq!(header(&options, "Tus-Version"), Some("1.0.0"));
+        assert_eq!(header(&options, "Allow"), Some("GET, HEAD, OPTIONS, POST, PATCH"));
+
+        let outdated = send(&url, "POST /uploads HTTP/1.1\r\nUpload-Length: 11\r\nConnection: close\r\n\r\n");
+        assert!(outdated.starts_with("HTTP/1.1 412"), "Got '{}'", outdated);
+
+        let created = send(
+            &url,
+            "POST /uploads HTTP/1.1\r\nTus-Resumable: 1.0.0\r\nUpload-Length: 11\r\nConnection: close\r\n\r\n",
+        );
+        assert!(created.starts_with("HTTP/1.1 201"), "Got '{}'", created);
+        let location = header(&created, "Location").unwrap().to_string();
+        let id = location.strip_prefix("/uploads/").unwrap();
+
+        let response = patch(&location, 0, "hello ");
+        assert!(response.starts_with("HTTP/1.1 204"), "Got '{}'", response);
+        assert_eq!(header(&response, "Upload-Offset"), Some("6"));
+        // a retried chunk that already arrived conflicts instead of being written twice
+        assert!(patch(&location, 0, "hello ").starts_with("HTTP/1.1 409"));
+
+        let head = send(&url, &format!("HEAD {} HTTP/1.1\r\nTus-Resumable: 1.0.0\r\nConnection: close\r\n\r\n", location));
+        assert_eq!(header(&head, "Upload-Offset"), Some("6"));
+        assert_eq!(header(&head, "Upload-Length"), Some("11"));
+
+        assert!(patch(&location, 6, "world!").starts_with("HTTP/1.1 413"));
+        assert_eq!(header(&patch(&location, 6, "world"), "Upload-Offset"), Some("11"));
+        assert_eq!(fs::read_to_string(root_dir.join(".tus").join(id)).unwrap(), "hello world");
+
+        let stored = send(&url, &format!("GET /.tus/{} HTTP/1.1\r\nConnection: close\r\n\r\n", id));
+        assert!(stored.starts_with("HTTP/1.1 403"), "Got '{}'", stored);
+        let elsewhere = send(&url, "POST / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert!(elsewhere.starts_with("HTTP/1.1 405"), "Got '{}'", elsewhere);
+    }
+
+    /// Test that chunked request bodies are decoded before handlers see them
+    #[test]
+    fn test_chunked_request_body() {
+        let root_dir = env::temp_dir().join("server_test_chunked_body");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(&root_dir).unwrap();
+        let url = start_server_with(&root_dir, &["--port", "0", "--read-only=false", "--tus", "/uploads", "--max-body-size", "16"]);
+        let patch = |location: &str, body: &str| {
+            send(&url, &format!(
+                "PATCH {} HTTP/1.1\r\nTus-Resumable: 1.0.0\r\nContent-Type: application/offset+octet-stream\r\n\
+                 Upload-Offset: 0\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n{}",
+                location, body
+            ))
+        };
+
+        let created = send(
+            &url,
+            "POST /uploads HTTP/1.1\r\nTus-Resumable: 1.0.0\r\nUpload-Length: 11\r\nConnection: close\r\n\r\n",
+        );
+        let location = header(&created, "Location").unwrap().to_string();
+        let response = patch(&location, "6;ext=1\r\nhello \r\n5\r\nworld\r\n0\r\nChecksum: abc\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 204"), "Got '{}'", response);
+        assert_eq!(header(&response, "Upload-Offset"), Some("11"));
+
+        assert!(patch(&location, "11\r\n12345678901234567\r\n0\r\n\r\n").starts_with("HTTP/1.1 413"));
+        assert!(patch(&location, "zz\r\n").starts_with("HTTP/1.1 400"));
+        let gzip = send(&url, "POST /uploads HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\nConnection: close\r\n\r\n");
+        assert!(gzip.starts_with("HTTP/1.1 501"), "Got '{}'", gzip);
+    }
+
+    /// Test that a client sending Expect: 100-continue is told to go on, or answered before its body
+    #[test]
+    fn test_expect_continue() {
+        let root_dir = env::temp_dir().join("server_test_expect_continue");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(&root_dir).unwrap();
+        let url = start_server_with(
+            &root_dir,
+            &["--port", "0", "--read-only=false", "--tus", "/uploads", "--max-body-size", "16", "--jwt-secret", "s3cret", "--jwt-protect", "/private"],
+        );
+        let head = |request: &str, length: usize, expect: &str| {
+            format!("{} HTTP/1.1\r\nTus-Resumable: 1.0.0\r\nUpload-Length: 5\r\nContent-Length: {}\r\nExpect: {}\r\nConnection: close\r\n\r\n", request, length, expect)
+        };
+
+        let mut stream = TcpStream::connect(url.trim_start_matches("http://")).unwrap();
+        stream.write_all(head("POST /uploads", 5, "100-continue").as_bytes()).unwrap();
+        let mut interim = [0; 25];
+        stream.read_exact(&mut interim).unwrap();
+        assert_eq!(&interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+        stream.write_all(b"hello").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 201"), "Got '{}'", response);
+
+        // answered without waiting for a body that is never sent
+        let unauthorized = send(&url, &head("GET /private/report.txt", 5, "100-continue"));
+        assert!(unauthorized.starts_with("HTTP/1.1 401"), "Got '{}'", unauthorized);
+        let too_large = send(&url, &head("POST /uploads", 17, "100-continue"));
+        assert!(too_large.starts_with("HTTP/1.1 413"), "Got '{}'", too_large);
+        let unknown = send(&url, &head("POST /uploads", 5, "teapot"));
+        assert!(unknown.starts_with("HTTP/1.1 417"), "Got '{}'", unknown);
+    }
+
+    /// Test that --writable deletes files and empty directories but nothing hidden or outside
+    #[test]
+    fn test_delete() {
+        let root_dir = env::temp_dir().join("server_test_delete");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("empty")).unwrap();
+        fs::create_dir_all(root_dir.join("full")).unwrap();
+        fs::write(root_dir.join("full/note.txt"), "note").unwrap();
+        fs::write(root_dir.join(".secret"), "secret").unwrap();
+        let delete = |url: &str, path: &str| send(url, &format!("DELETE {} HTTP/1.1\r\nConnection: close\r\n\r\n", path));
+
+        let url = start_server(&root_dir);
+        assert!(delete(&url, "/full/note.txt").starts_with("HTTP/1.1 405"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--read-only=false", "--writable"]);
+        let response = delete(&url, "/full/note.txt");
+        assert!(response.starts_with("HTTP/1.1 204"), "Got '{}'", response);
+        assert!(!root_dir.join("full/note.txt").exists());
+        assert!(delete(&url, "/full/note.txt").starts_with("HTTP/1.1 404"));
+
+        fs::write(root_dir.join("full/other.txt"), "other").unwrap();
+        assert!(delete(&url, "/full").starts_with("HTTP/1.1 403"));
+        assert!(delete(&url, "/empty").starts_with("HTTP/1.1 204"));
+        assert!(delete(&url, "/.secret").starts_with("HTTP/1.1 403"));
+        assert!(delete(&url, "/").starts_with("HTTP/1.1 403"));
+        assert!(root_dir.join(".secret").exists());
+    }
+
+    /// Test that --writable listings carry an upload form whose files land next to the others
+    #[test]
+    fn test_upload() {
+        let root_dir = env::temp_dir().join("server_test_upload");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("inbox")).unwrap();
+        fs::write(root_dir.join("inbox/notes.txt"), "mine").unwrap();
+        fs::write(root_dir.join("page.html"), "page").unwrap();
+        let upload = |url: &str, path: &str, accept: &str, files: &[(&str, &str)]| {
+            let mut body = String::from("--b0undary\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nhi\r\n");
+            for (name, content) in files {
+                body.push_str(&format!(
+                    "--b0undary\r\nContent-Disposition: form-data; name=\"files\"; filename=\"{}\"\r\n\r\n{}\r\n",
+                    name, content
+                ));
+            }
+            body.push_str("--b0undary--\r\n");
+            let head = format!(
+                "POST {} HTTP/1.1\r\nContent-Type: multipart/form-data; boundary=b0undary\r\n{}Content-Length: {}\r\n",
+                path,
+                accept,
+                body.len()
+            );
+            send(url, &format!("{}Connection: close\r\n\r\n{}", head, body))
+        };
+
+        let url = start_server(&root_dir);
+        assert!(!send(&url, "GET /inbox/ HTTP/1.1\r\nConnection: close\r\n\r\n").contains("class=\"upload\""));
+        assert!(upload(&url, "/inbox/", "", &[("new.txt", "new")]).starts_with("HTTP/1.1 405"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--read-only=false", "--writable"]);
+        let listing = send(&url, "GET /inbox/ HTTP/1.1\r\nConnection: close\r\n\r\n");
+        assert!(listing.contains("class=\"upload\" method=\"post\" enctype=\"multipart/form-data\""), "Got '{}'", listing);
+
+        let response = upload(&url, "/inbox/", "", &[("notes.txt", "theirs"), ("photos/pic.txt", "pic")]);
+        assert!(response.starts_with("HTTP/1.1 303"), "Got '{}'", response);
+        assert_eq!(header(&response, "Location"), Some("/inbox/"));
+        assert_eq!(fs::read_to_string(root_dir.join("inbox/notes.txt")).unwrap(), "mine");
+        assert_eq!(fs::read_to_string(root_dir.join("inbox/notes (1).txt")).unwrap(), "theirs");
+        assert_eq!(fs::read_to_string(root_dir.join("inbox/pic.txt")).unwrap(), "pic");
+
+        let response = upload(&url, "/", "Accept: application/json\r\n", &[("top.txt", "top")]);
+        assert!(response.starts_with("HTTP/1.1 201"), "Got '{}'", response);
+        assert!(response.ends_with("{\"files\":[\"/top.txt\"]}"), "Got '{}'", response);
+        assert!(root_dir.join("top.txt").exists());
+
+        assert!(upload(&url, "/inbox/", "", &[(".htaccess", "x")]).starts_with("HTTP/1.1 403"));
+        assert!(upload(&url, "/inbox/", "", &[("ok.txt", "x"), ("../.env", "x")]).starts_with("HTTP/1.1 403"));
+        assert!(!root_dir.join("inbox/ok.txt").exists(), "nothing is written when a name is refused");
+        assert!(upload(&url, "/inbox/", "", &[("", "")]).starts_with("HTTP/1.1 400"));
+        assert!(upload(&url, "/missing/", "", &[("a.txt", "a")]).starts_with("HTTP/1.1 404"));
+        assert!(upload(&url, "/page.html", "", &[("a.txt", "a")]).starts_with("HTTP/1.1 409"));
+    }
+
+    /// Test that --writable listings rename, move and create folders, with a token from browsers
+    #[test]
+    fn test_file_manager() {
+        let root_dir = env::temp_dir().join("server_test_file_manager");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("docs/archive")).unwrap();
+        fs::write(root_dir.join("docs/draft.txt"), "draft").unwrap();
+        fs::write(root_dir.join("docs/taken.txt"), "taken").unwrap();
+        let url = start_server_with(&root_dir, &["--port", "0", "--read-only=false", "--writable"]);
+        let request = |method: &str, path: &str, headers: &str, body: &str| {
+            send(
+                &url,
+                &format!("{} {} HTTP/1.1\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}", method, path, headers, body.len(), body),
+            )
+        };
+
+        // the listing hands out the token in a cookie and in its forms
+        let listing = request("GET", "/docs/", "", "");
+        let cookie = header(&listing, "Set-Cookie").unwrap().to_string();
+        assert!(cookie.starts_with("katana_csrf=") && cookie.contains("SameSite=Strict"), "Got '{}'", cookie);
+        let token = &cookie["katana_csrf=".len().."katana_csrf=".len() + 32];
+        assert!(listing.contains(&format!("name=\"_csrf\" value=\"{}\"", token)));
+        assert!(listing.contains("data-action='rename'") && listing.contains("class=\"new-folder\""));
+        let again = request("GET", "/docs/", &format!("Cookie: katana_csrf={}\r\n", token), "");
+        assert!(header(&again, "Set-Cookie").is_none());
+
+        // a browser needs the token, curl does not
+        let move_to = |destination: &str, headers: &str| {
+            request("MOVE", "/docs/draft.txt", &format!("Destination: {}\r\n{}", destination, headers), "")
+        };
+        let browser = format!("Origin: http://127.0.0.1\r\nCookie: katana_csrf={}\r\n", token);
+        assert!(move_to("/docs/final.txt", &browser).starts_with("HTTP/1.1 403"));
+        let response = move_to("http://127.0.0.1/docs/final%20v2.txt", &format!("{}X-CSRF-Token: {}\r\n", browser, token));
+        assert!(response.starts_with("HTTP/1.1 201"), "Got '{}'", response);
+        assert_eq!(header(&response, "Location"), Some("/docs/final%20v2.txt"));
+        assert_eq!(fs::read_to_string(root_dir.join("docs/final v2.txt")).unwrap(), "draft");
+
+        let move_from = |source: &str, destination: &str| request("MOVE", source, &format!("Destination: {}\r\n", destination), "");
+        assert!(move_from("/docs/final%20v2.txt", "/docs/archive/").starts_with("HTTP/1.1 412"), "never replaced");
+        assert!(move_from("/docs/final%20v2.txt", "/docs/taken.txt").starts_with("HTTP/1.1 412"));
+        assert!(move_from("/docs/final%20v2.txt", "/nowhere/final.txt").starts_with("HTTP/1.1 409"));
+        assert!(move_from("/docs/final%20v2.txt", "/docs/.hidden").starts_with("HTTP/1.1 403"));
+        assert!(move_from("/docs/final%20v2.txt", "/docs/../../escape.txt").starts_with("HTTP/1.1 403"));
+        assert!(move_from("/docs", "/docs/archive/docs").starts_with("HTTP/1.1 409"));
+        assert!(move_from("/docs/final%20v2.txt", "/docs/archive/final.txt").starts_with("HTTP/1.1 201"));
+        assert!(root_dir.join("docs/archive/final.txt").exists());
+        assert!(move_from("/docs/archive", "/old/").starts_with("HTTP/1.1 201"));
+        assert!(root_dir.join("old/final.txt").exists());
+
+        // new folders come from a plain form, which leads back to the listing
+        let form = "Content-Type: application/x-www-form-urlencoded\r\n";
+        let response = request("POST", "/docs/", form, "folder=Q3+reports");
+        assert!(response.starts_with("HTTP/1.1 303"), "Got '{}'", response);
+        assert!(root_dir.join("docs/Q3 reports").is_dir());
+        assert!(request("POST", "/docs/", form, "folder=Q3+reports").starts_with("HTTP/1.1 409"));
+        assert!(request("POST", "/docs/", form, "folder=a%2Fb").starts_with("HTTP/1.1 403"));
+        let response = request("POST", "/", &format!("{}{}", form, browser), &format!("folder=top&_csrf={}", token));
+        assert!(response.starts_with("HTTP/1.1 303"), "Got '{}'", response);
+        assert!(root_dir.join("top").is_dir());
+    }
+
+    /// Test that the share is read only by default: uploads, moves and deletions are refused
+    #[test]
+    fn test_read_only() {
+        let root_dir = env::temp_dir().join("server_test_read_only");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("docs")).unwrap();
+        fs::write(root_dir.join("docs/note.txt"), "note").unwrap();
+        let url = start_server_with(&root_dir, &["--port", "0"]);
+        let request = |head: &str, body: &str| {
+            send(&url, &format!("{}Content-Length: {}\r\nConnection: close\r\n\r\n{}", head, body.len(), body))
+        };
+
+        let listing = request("GET /docs/ HTTP/1.1\r\n", "");
+        assert!(listing.starts_with("HTTP/1.1 200"), "Got '{}'", listing);
+        assert!(!listing.contains("class=\"upload\"") && !listing.contains("data-action="));
+        let refused = [
+            request("DELETE /docs/note.txt HTTP/1.1\r\n", ""),
+            request("MOVE /docs/note.txt HTTP/1.1\r\nDestination: /docs/moved.txt\r\n", ""),
+            request("POST /docs/ HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\n", "folder=new"),
+            request("POST /uploads HTTP/1.1\r\nTus-Resumable: 1.0.0\r\nUpload-Length: 4\r\n", ""),
+            request("PUT /docs/note.txt HTTP/1.1\r\n", "new"),
+        ];
+        for response in refused {
+            assert!(response.starts_with("HTTP/1.1 405"), "Got '{}'", response);
+            assert_eq!(header(&response, "Allow"), Some("GET, HEAD, OPTIONS"));
+        }
+        assert_eq!(fs::read_to_string(root_dir.join("docs/note.txt")).unwrap(), "note");
+        assert!(!root_dir.join("docs/new").exists());
+    }
+
+    /// Test that /~user paths are only looked up with --userdir and never reach hidden files
+    #[test]
+    fn test_userdir() {
+        let root_dir = env::temp_dir().join("server_test_userdir");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("~site")).unwrap();
+        fs::write(root_dir.join("~site/page.html"), "site page").unwrap();
+        let get = |url: &str, path: &str| send(url, &format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path));
+
+        let url = start_server(&root_dir);
+        assert!(get(&url, "/~site/page.html").ends_with("site page"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--userdir"]);
+        let response = get(&url, "/~nobody-here?lang=fr");
+        assert!(response.starts_with("HTTP/1.1 301"), "Got '{}'", response);
+        assert_eq!(header(&response, "Location"), Some("/~nobody-here/?lang=fr"));
+        assert!(get(&url, "/~nobody-here/").starts_with("HTTP/1.1 404"));
+        assert!(get(&url, "/~root/").starts_with("HTTP/1.1 404"), "system accounts have no user directory");
+        assert!(get(&url, "/~nobody-here/.ssh/id_rsa").starts_with("HTTP/1.1 403"));
+        assert!(get(&url, "/~site/page.html").starts_with("HTTP/1.1 404"), "the root no longer answers /~");
+    }
+
+    /// Test that If-Match and If-Unmodified-Since keep a DELETE from removing a newer file
+    #[test]
+    fn test_delete_preconditions() {
+        let root_dir = env::temp_dir().join("server_test_delete_preconditions");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("doc.txt"), "v1").unwrap();
+        let url = start_server_with(&root_dir, &["--port", "0", "--read-only=false", "--writable", "--etag", "strong"]);
+        let request = |method: &str, headers: &str| {
+            send(&url, &format!("{} /doc.txt HTTP/1.1\r\nHost: test\r\n{}Connection: close\r\n\r\n", method, headers))
+        };
+
+        let seen = header(&request("GET", ""), "ETag").unwrap().to_string();
+        fs::write(root_dir.join("doc.txt"), "v2 by someone else").unwrap();
+        let response = request("DELETE", &format!("If-Match: {}\r\n", seen));
+        assert!(response.starts_with("HTTP/1.1 412"), "Got '{}'", response);
+        let response = request("DELETE", "If-Unmodified-Since: Thu, 01 Jan 1970 00:00:00 GMT\r\n");
+        assert!(response.starts_with("HTTP/1.1 412"), "Got '{}'", response);
+        assert!(root_dir.join("doc.txt").exists());
+
+        let current = header(&request("GET", ""), "ETag").unwrap().to_string();
+        let response = request("DELETE", &format!("If-Match: \"old\", {}\r\n", current));
+        assert!(response.starts_with("HTTP/1.1 204"), "Got '{}'", response);
+        assert!(request("DELETE", "If-Match: *\r\n").starts_with("HTTP/1.1 404"), "a missing file is not a failed precondition");
+    }
+
+    /// Test that POSTs from --method-override clients can stand in for DELETE
+    #[test]
+    fn test_method_override() {
+        let root_dir = env::temp_dir().join("server_test_method_override");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("a.txt"), "a").unwrap();
+        fs::write(root_dir.join("b.txt"), "b").unwrap();
+        let post = |url: &str, path: &str, headers: &str, body: &str| {
+            send(url, &format!("POST {} HTTP/1.1\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}", path, headers, body.len(), body))
+        };
+        let form = "Content-Type: application/x-www-form-urlencoded\r\n";
+
+        // --writable takes POST for uploads, which a request that is not a form is refused
+        let url = start_server_with(&root_dir, &["--port", "0", "--read-only=false", "--writable"]);
+        assert!(post(&url, "/a.txt", "X-HTTP-Method-Override: DELETE\r\n", "").starts_with("HTTP/1.1 415"));
+        assert!(root_dir.join("a.txt").exists());
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--read-only=false", "--writable", "--method-override", "127.0.0.0/8"]);
+        let response = post(&url, "/a.txt", "X-HTTP-Method-Override: delete\r\n", "");
+        assert!(response.starts_with("HTTP/1.1 204"), "Got '{}'", response);
+        assert!(!root_dir.join("a.txt").exists());
+        let response = post(&url, "/b.txt", form, "name=b&_method=DELETE");
+        assert!(response.starts_with("HTTP/1.1 204"), "Got '{}'", response);
+        assert!(!root_dir.join("b.txt").exists());
+        assert!(post(&url, "/b.txt", "", "").starts_with("HTTP/1.1 415"));
+        assert!(post(&url, "/b.txt", "X-HTTP-Method-Override: PUT\r\n", "").starts_with("HTTP/1.1 405"));
+    }
+
+    /// Test that each virtual host serves its own root, headers and listing policy
+    #[test]
+    fn test_virtual_hosts() {
+        let base = env::temp_dir().join("server_test_vhosts");
+        let _ = fs::remove_dir_all(&base);
+        for site in ["default", "blog"] {
+            fs::create_dir_all(base.join(site).join("files")).unwrap();
+            fs::write(base.join(site).join("index.html"), site).unwrap();
+        }
+        let blog = base.join("blog");
+        fs::write(
+            base.join("blog.conf"),
+            format!("dir = \"{}\"\nheader = [\"X-Site: blog\"]\nno_listing = true\n", blog.display()),
+        )
+        .unwrap();
+        let vhost = format!("blog.example.com,*.blog.example.com={}", base.join("blog.conf").display());
+        let url = start_server_with(&base.join("default"), &["--port", "0", "--header", "X-Served-By: katana", "--vhost", &vhost]);
+        let get = |host: &str, path: &str| send(&url, &format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, host));
+
+        let default = get("localhost", "/");
+        assert!(default.ends_with("default"), "Got '{}'", default);
+        assert!(default.contains("X-Served-By: katana"));
+        assert!(!default.contains("X-Site"));
+        assert!(get("localhost", "/files/").starts_with("HTTP/1.1 200"));
+
+        let site = get("Blog.Example.com:8080", "/");
+        assert!(site.ends_with("blog"), "Got '{}'", site);
+        assert!(site.contains("X-Served-By: katana"));
+        assert!(site.contains("X-Site: blog"));
+        assert!(get("www.blog.example.com", "/").ends_with("blog"));
+        assert!(get("blog.example.com", "/files/").starts_with("HTTP/1.1 403"));
+    }
+
+    /// Test that a location overrides listing and headers and falls back for client routes
+    #[test]
+    fn test_locations() {
+        let root_dir = env::temp_dir().join("server_test_locations");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("downloads")).unwrap();
+        fs::create_dir_all(root_dir.join("app")).unwrap();
+        fs::write(root_dir.join("downloads/report.txt"), "report").unwrap();
+        fs::write(root_dir.join("app/index.html"), "app").unwrap();
+        let downloads = root_dir.join("downloads.conf");
+        fs::write(&downloads, "listing = true\nheader = [\"Content-Disposition: attachment\"]\n").unwrap();
+        let app = root_dir.join("app.conf");
+        fs::write(&app, "spa_fallback = \"/app/index.html\"\n").unwrap();
+
+        let (downloads, app) = (format!("/downloads={}", downloads.display()), format!("/app={}", app.display()));
+        let url = start_server_with(&root_dir, &["--port", "0", "--no-listing", "--location", &downloads, "--location", &app]);
+        let get = |path: &str| send(&url, &format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path));
+
+        assert!(get("/").starts_with("HTTP/1.1 403"));
+        assert!(get("/downloads/").starts_with("HTTP/1.1 200"));
+        let report = get("/downloads/report.txt");
+        assert!(report.contains("Content-Disposition: attachment"), "Got '{}'", report);
+
+        let route = get("/app/users/42");
+        assert!(route.starts_with("HTTP/1.1 200") && route.ends_with("app"), "Got '{}'", route);
+        assert!(get("/app/missing.js").starts_with("HTTP/1.1 404"));
+        assert!(get("/users/42").starts_with("HTTP/1.1 404"));
+    }
+
+    /// Test that media embedded by other sites is refused or redirected
+    #[test]
+    fn test_hotlink() {
+        let root_dir = env::temp_dir().join("server_test_hotlink");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("photo.jpg"), "jpg").unwrap();
+        fs::write(root_dir.join("notes.txt"), "notes").unwrap();
+        let get = |url: &str, path: &str, referer: &str| {
+            send(url, &format!("GET {} HTTP/1.1\r\nHost: share.local\r\nReferer: {}\r\nConnection: close\r\n\r\n", path, referer))
+        };
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--hotlink-protect", "jpg,png"]);
+        let own = get(&url, "/photo.jpg", "http://share.local/");
+        assert!(own.starts_with("HTTP/1.1 200"), "Got '{}'", own);
+        assert!(own.contains("Vary: Referer"));
+        assert!(get(&url, "/photo.jpg", "https://evil.com/").starts_with("HTTP/1.1 403"));
+        assert!(get(&url, "/notes.txt", "https://evil.com/").starts_with("HTTP/1.1 200"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--hotlink-protect", "jpg", "--hotlink-redirect", "https://share.local/"]);
+        let redirected = get(&url, "/photo.jpg", "https://evil.com/");
+        assert!(redirected.starts_with("HTTP/1.1 302"), "Got '{}'", redirected);
+        assert!(redirected.contains("Location: https://share.local/"));
+    }
+
+    /// Test that robots.txt and security.txt are made up only when the root has none
+    #[test]
+    fn test_well_known_files() {
+        let root_dir = env::temp_dir().join("server_test_well_known");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(&root_dir).unwrap();
+        let get = |url: &str, path: &str| send(url, &format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path));
+
+        let url = start_server(&root_dir);
+        assert!(get(&url, "/robots.txt").starts_with("HTTP/1.1 404"));
+        assert!(get(&url, "/.well-known/security.txt").starts_with("HTTP/1.1 404"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--robots", "deny", "--security-contact", "mailto:sec@example.com"]);
+        let robots = get(&url, "/robots.txt");
+        assert!(robots.starts_with("HTTP/1.1 200") && robots.ends_with("Disallow: /\n"), "Got '{}'", robots);
+        let security = get(&url, "/.well-known/security.txt");
+        assert!(security.contains("Contact: mailto:sec@example.com\nExpires: "), "Got '{}'", security);
+
+        fs::write(root_dir.join("robots.txt"), "User-agent: *\nAllow: /\n").unwrap();
+        assert!(get(&url, "/robots.txt").ends_with("Allow: /\n"));
+    }
+
+    /// Test that a favicon is served unless the root has one or it is turned off
+    #[test]
+    fn test_favicon() {
+        let root_dir = env::temp_dir().join("server_test_favicon");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(&root_dir).unwrap();
+        let get = |url: &str| send(url, "GET /favicon.ico HTTP/1.1\r\nConnection: close\r\n\r\n");
+
+        let url = start_server(&root_dir);
+        let built_in = get(&url);
+        assert!(built_in.starts_with("HTTP/1.1 200"), "Got '{}'", built_in);
+        assert!(built_in.contains("Content-Type: image/x-icon"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--favicon", "off"]);
+        assert!(get(&url).starts_with("HTTP/1.1 404"));
+
+        fs::write(root_dir.join("favicon.ico"), "own").unwrap();
+        assert!(get(&url).ends_with("\r\n\r\nown"));
+    }
+
+    /// Test that ?q= lists matching names from the folders below
+    #[test]
+    fn test_listing_search() {
+        let root_dir = env::temp_dir().join("server_test_listing_search");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("photos/2024")).unwrap();
+        fs::write(root_dir.join("photos/2024/beach trip.jpg"), "jpg").unwrap();
+        fs::write(root_dir.join("notes.txt"), "notes").unwrap();
+        let get = |url: &str, path: &str| send(url, &format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path));
+
+        let url = start_server(&root_dir);
+        let listing = get(&url, "/");
+        assert!(listing.contains("name=\"q\""), "Got '{}'", listing);
+        assert!(!listing.contains("beach"));
+
+        let results = get(&url, "/?q=beach+TRIP");
+        assert!(results.contains(">photos/2024/beach trip.jpg<"), "Got '{}'", results);
+        assert!(results.contains("<span class='size'>3 B</span>"));
+        assert!(!results.contains("notes.txt"));
+        assert!(get(&url, "/?q=missing").contains("No Match"));
+    }
+
+    /// Test that big directories are listed a page at a time
+    #[test]
+    fn test_listing_pages() {
+        let root_dir = env::temp_dir().join("server_test_listing_pages");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("folder")).unwrap();
+        for i in 0..4 {
+            fs::write(root_dir.join(format!("file{}.txt", i)), "file").unwrap();
+        }
+        let get = |url: &str, path: &str| send(url, &format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path));
+        let count = |page: &str| page.matches("<span class='name'>").count();
+
+        let url = start_server(&root_dir);
+        assert!(!get(&url, "/").contains("class='pagination'"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--listing-page-size", "2"]);
+        let first = get(&url, "/");
+        assert_eq!(count(&first), 2, "Got '{}'", first);
+        assert!(first.contains(">folder<"), "folders come first");
+        assert!(first.contains("Page 1 of 3") && first.contains("href='?page=2'"));
+        assert!(!first.contains("Previous"));
+
+        let last = get(&url, "/?page=3");
+        assert_eq!(count(&last), 1);
+        assert!(last.contains("Page 3 of 3") && last.contains("href='?page=2'"));
+        assert!(get(&url, "/?page=99").contains("Page 3 of 3"));
+        assert!(get(&url, "/?page=abc").contains("Page 1 of 3"));
+        assert!(get(&url, "/?q=file&page=2").contains("href='?q=file&amp;page=1'"));
+    }
+
+    /// Test that listings link every ancestor instead of a single parent link
+    #[test]
+    fn test_listing_breadcrumbs() {
+        let root_dir = env::temp_dir().join("server_test_listing_breadcrumbs");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("docs/api v2")).unwrap();
+        let get = |url: &str, path: &str| send(url, &format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path));
+
+        let url = start_server(&root_dir);
+        let root = get(&url, "/");
+        assert!(root.contains("<nav class=\"breadcrumbs\" aria-label=\"Folder\"><a href='/'>/</a></nav>"), "Got '{}'", root);
+
+        let page = get(&url, "/docs/api%20v2/");
+        assert!(page.contains("<a href='/'>/</a><span class='separator'>/</span><a href='/docs/'>docs</a>"), "Got '{}'", page);
+        assert!(page.contains("<a href='/docs/api%20v2/'>api v2</a></nav>"));
+        assert!(!page.contains(">..<"));
+    }
+
+    /// Test that snippets go into listings and error pages, and into files with --inject-files
+    #[test]
+    fn test_inject_snippets() {
+        let root_dir = env::temp_dir().join("server_test_inject_snippets");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("docs")).unwrap();
+        fs::write(root_dir.join("page.html"), "<html><head></head><body>page</body></html>").unwrap();
+        fs::write(root_dir.join("notes.txt"), "</body>").unwrap();
+        let head = root_dir.join("head.html");
+        let body = root_dir.join("body.html");
+        fs::write(&head, "<style>/*custom*/</style>").unwrap();
+        fs::write(&body, "<div>banner</div>").unwrap();
+        let (head, body) = (head.to_string_lossy().to_string(), body.to_string_lossy().to_string());
+        let get = |url: &str, path: &str| send(url, &format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path));
+        let body_of = |response: &str| response.split_once("\r\n\r\n").unwrap().1.to_string();
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--inject-head", &head, "--inject-body", &body]);
+        let listing = get(&url, "/docs/");
+        assert!(listing.contains("<style>/*custom*/</style></head>"), "Got '{}'", listing);
+        assert!(listing.contains("<div>banner</div></body>"));
+        let length: usize = header(&listing, "Content-Length").unwrap().parse().unwrap();
+        assert_eq!(length, body_of(&listing).len());
+
+        let missing = get(&url, "/missing");
+        assert!(missing.starts_with("HTTP/1.1 404") && missing.contains("<div>banner</div></body>"), "Got '{}'", missing);
+        let length: usize = header(&missing, "Content-Length").unwrap().parse().unwrap();
+        assert_eq!(length, body_of(&missing).len());
+        assert!(get(&url, "/page.html").ends_with("<body>page</body></html>"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--inject-body", &body, "--inject-files"]);
+        let page = get(&url, "/page.html");
+        assert!(page.ends_with("<body>page<div>banner</div></body></html>"), "Got '{}'", page);
+        assert_eq!(header(&page, "Content-Length"), Some("60"));
+        assert!(get(&url, "/notes.txt").ends_with("\r\n\r\n</body>"), "only HTML is touched");
+    }
+
+    /// Test that error pages and listings speak the language of the client, or --locale
+    #[test]
+    fn test_localized_built_in_pages() {
+        let root_dir = env::temp_dir().join("server_test_localized_pages");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("empty")).unwrap();
+        let get = |url: &str, path: &str, language: &str| {
+            send(url, &format!("GET {} HTTP/1.1\r\nAccept-Language: {}\r\nConnection: close\r\n\r\n", path, language))
+        };
+
+        let url = start_server(&root_dir);
+        let missing = get(&url, "/missing", "fr-FR, en;q=0.5");
+        assert!(missing.starts_with("HTTP/1.1 404"), "Got '{}'", missing);
+        assert!(missing.contains("<html lang=\"fr\"") && missing.contains("<h1>404 Introuvable</h1>"));
+        assert!(missing.contains("Retour à l&#39;accueil"));
+        assert_eq!(header(&missing, "Content-Language"), Some("fr"));
+        assert_eq!(header(&missing, "Vary"), Some("Accept-Language"));
+        let listing = get(&url, "/empty/", "de");
+        assert!(listing.contains("<h1>Verzeichnisinhalt</h1>") && listing.contains("Leerer Ordner"), "Got '{}'", listing);
+        assert!(get(&url, "/missing", "ja").contains("<h1>404 Not Found</h1>"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--locale", "es"]);
+        assert!(get(&url, "/missing", "ja").contains("<h1>404 No encontrado</h1>"));
+        assert!(get(&url, "/missing", "fr").contains("<h1>404 Introuvable</h1>"));
+    }
+
+    /// Test that ignored files are left out of listings and searches and are a 404 when asked for
+    #[test]
+    fn test_ignore_file() {
+        let root_dir = env::temp_dir().join("server_test_ignore_file");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("node_modules/lib")).unwrap();
+        fs::write(root_dir.join(".katanaignore"), "node_modules/\nsecret.txt\n").unwrap();
+        fs::write(root_dir.join("secret.txt"), "secret").unwrap();
+        fs::write(root_dir.join("debug.log"), "log").unwrap();
+        fs::write(root_dir.join("public.txt"), "public").unwrap();
+        fs::write(root_dir.join("node_modules/lib/index.js"), "js").unwrap();
+        let get = |url: &str, path: &str| send(url, &format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--ignore", "*.log"]);
+        let listing = get(&url, "/");
+        assert!(listing.contains(">public.txt<"), "Got '{}'", listing);
+        for hidden in [">secret.txt<", ">node_modules<", ">debug.log<", "katanaignore"] {
+            assert!(!listing.contains(hidden), "{} is listed", hidden);
+        }
+        assert!(!get(&url, "/?q=index").contains(">node_modules/lib/index.js<"));
+        assert!(get(&url, "/public.txt").starts_with("HTTP/1.1 200"));
+        for path in ["/secret.txt", "/debug.log", "/node_modules/", "/node_modules/lib/index.js"] {
+            assert!(get(&url, path).starts_with("HTTP/1.1 404"), "{} is served", path);
+        }
+    }
+
+    /// Test that a README is shown below the entries, Markdown rendered
+    #[test]
+    fn test_listing_readme() {
+        let root_dir = env::temp_dir().join("server_test_listing_readme");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(root_dir.join("notes")).unwrap();
+        fs::write(root_dir.join("README.md"), "# Downloads\n\nGrab a **release**.").unwrap();
+        fs::write(root_dir.join("notes/readme.txt"), "plain <text>").unwrap();
+        let get = |url: &str, path: &str| send(url, &format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path));
+
+        let url = start_server(&root_dir);
+        let page = get(&url, "/");
+        assert!(page.contains("<section class='readme'>"), "Got '{}'", page);
+        assert!(page.contains("<h1>Downloads</h1>") && page.contains("<strong>release</strong>"));
+        assert!(page.find("class='entries'").unwrap_or(0) < page.find("<section class='readme'>").unwrap());
+        assert!(get(&url, "/notes/").contains("<pre>plain &lt;text&gt;</pre>"));
+        assert!(!get(&url, "/?q=notes").contains("class='readme'"));
+    }
+
+    /// Test that an extensionless README is listed and served as a plain file
+    #[test]
+    fn test_extensionless_readme() {
+        let root_dir = env::temp_dir().join("server_test_extensionless_readme");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("README"), "read me first").unwrap();
+        let get = |url: &str, path: &str| send(url, &format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path));
+
+        let url = start_server(&root_dir);
+        assert!(get(&url, "/").contains("read me first"));
+        let file = get(&url, "/README");
+        assert!(file.starts_with("HTTP/1.1 200"), "Got '{}'", file);
+        assert!(file.ends_with("read me first"));
+        let _ = fs::remove_dir_all(&root_dir);
+    }
+
+    /// Test that the status page is off by default and lists what was requested
+    #[test]
+    fn test_status_page() {
+        let get = |url: &str, path: &str| send(url, &format!("GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path));
+
+        let url = start_server(&env::temp_dir());
+        assert!(get(&url, "/_katana/status").starts_with("HTTP/1.1 404"));
+
+        let url = start_server_with(&env::temp_dir(), &["--port", "0", "--status-page"]);
+        // other tests share the counters, enough hits put the probe in the top paths
+        for _ in 0..20 {
+            get(&url, "/status-page-<probe>");
+        }
+        let page = get(&url, "/_katana/status");
+        assert!(page.starts_with("HTTP/1.1 200"), "Got '{}'", page);
+        assert_eq!(header(&page, "Cache-Control"), Some("no-store"));
+        assert!(page.contains("<h1>Katana status</h1>"));
+        assert!(page.contains("/status-page-&lt;probe&gt;"));
+        assert!(page.contains("No proxy upstreams"));
+        assert!(!page.contains("{{"));
+    }
+
+    /// Test that methods outside --allowed-methods get a 405, and unknown ones a 501, listing the allowed ones
+    #[test]
+    fn test_allowed_methods() {
+        let request = |method: &str| format!("{} / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n", method);
+
+        let url = start_server(&env::temp_dir());
+        let response = send(&url, &request("TRACE"));
+        assert!(response.starts_with("HTTP/1.1 405"), "Got '{}'", response);
+        assert_eq!(header(&response, "Allow"), Some("GET, HEAD, OPTIONS"));
+        let response = send(&url, &request("POST"));
+        assert!(response.starts_with("HTTP/1.1 405"), "Got '{}'", response);
+        let response = send(&url, &request("BREW"));
+        assert!(response.starts_with("HTTP/1.1 501"), "Got '{}'", response);
+        assert_eq!(header(&response, "Allow"), Some("GET, HEAD, OPTIONS"));
+
+        let url = start_server_with(&env::temp_dir(), &["--port", "0", "--allowed-methods", "GET"]);
+        let response = send(&url, &request("HEAD"));
+        assert_eq!(header(&response, "Allow"), Some("GET"));
+        let response = send(&url, &request("TRACE"));
+        assert!(response.starts_with("HTTP/1.1 405"), "Got '{}'", response);
+    }
+
+    /// Test that OPTIONS * describes the whole server and other methods cannot use the asterisk
+    #[test]
+    fn test_options_asterisk() {
+        let url = start_server(&env::temp_dir());
+        let response = send(&url, "OPTIONS * HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200"), "Got '{}'", response);
+        assert_eq!(header(&response, "Allow"), Some("GET, HEAD, OPTIONS"));
+        assert_eq!(header(&response, "Content-Length"), Some("0"));
+        let response = send(&url, "GET * HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 400"), "Got '{}'", response);
+
+        let url = start_server_with(&env::temp_dir(), &["--port", "0", "--read-only=false", "--tus", "/uploads"]);
+        let response = send(&url, "OPTIONS * HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n");
+        assert_eq!(header(&response, "Allow"), Some("GET, HEAD, OPTIONS, POST, PATCH"));
+    }
+
+    /// Test that redirect rules are answered before the files they shadow
+    #[test]
+    fn test_redirects() {
+        let root_dir = env::temp_dir().join("server_test_redirects");
+        fs::create_dir_all(root_dir.join("blog")).unwrap();
+        fs::write(root_dir.join("blog/post.html"), "old post").unwrap();
+        let url = start_server_with(&root_dir, &["--port", "0", "--redirect", "/blog/* /posts/{rest} 308", "--redirect", "/gh https://github.com/ 302"]);
+        let get = |target: &str| send(&url, &format!("GET {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n", target));
+
+        let response = get("/blog/post.html?ref=feed");
+        assert!(response.starts_with("HTTP/1.1 308"), "Got '{}'", response);
+        assert_eq!(header(&response, "Location"), Some("/posts/post.html?ref=feed"));
+        let response = get("/gh");
+        assert!(response.starts_with("HTTP/1.1 302"), "Got '{}'", response);
+        assert_eq!(header(&response, "Location"), Some("https://github.com/"));
+        assert!(get("/blogroll").starts_with("HTTP/1.1 404"));
+    }
+
+    /// Test that files carry an ETag and revalidations that match it get a 304
+    #[test]
+    fn test_etag() {
+        let root_dir = env::temp_dir().join("server_test_etag");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("page.txt"), "cached ".repeat(1000)).unwrap();
+        let get = |url: &str, headers: &str| {
+            send(url, &format!("GET /page.txt HTTP/1.1\r\nHost: test\r\n{}Connection: close\r\n\r\n", headers))
+        };
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--etag", "strong"]);
+        let response = get(&url, "");
+        let tag = header(&response, "ETag").unwrap().to_string();
+        assert!(tag.starts_with('"'), "Got '{}'", tag);
+        let response = get(&url, &format!("If-None-Match: {}\r\n", tag));
+        assert!(response.starts_with("HTTP/1.1 304"), "Got '{}'", response);
+        assert_eq!(header(&response, "ETag"), Some(tag.as_str()));
+        assert_eq!(header(&response, "Content-Length"), None);
+        assert!(response.ends_with("\r\n\r\n"));
+        assert!(get(&url, "If-None-Match: \"other\"\r\n").starts_with("HTTP/1.1 200"));
+
+        // gzipped bytes are not the file, the tag turns weak but still revalidates
+        let response = get(&url, "Accept-Encoding: gzip\r\n");
+        assert_eq!(header(&response, "ETag"), Some(format!("W/{}", tag).as_str()));
+        let response = get(&url, &format!("Accept-Encoding: gzip\r\nIf-None-Match: W/{}\r\n", tag));
+        assert!(response.starts_with("HTTP/1.1 304"), "Got '{}'", response);
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--etag", "off"]);
+        assert_eq!(header(&get(&url, ""), "ETag"), None);
+        let url = start_server(&root_dir);
+        assert!(header(&get(&url, ""), "ETag").unwrap().starts_with("W/"));
+    }
+
+    /// Test that listings carry validators that follow their entries
+    #[test]
+    fn test_listing_validators() {
+        let root_dir = env::temp_dir().join("server_test_listing_validators");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("a.txt"), "a").unwrap();
+        let url = start_server(&root_dir);
+        let get = |headers: &str| send(&url, &format!("GET / HTTP/1.1\r\nHost: test\r\n{}Connection: close\r\n\r\n", headers));
+
+        let listing = get("");
+        let tag = header(&listing, "ETag").unwrap().to_string();
+        let last_modified = header(&listing, "Last-Modified").unwrap().to_string();
+        assert!(tag.starts_with("W/"), "Got '{}'", tag);
+        let response = get(&format!("If-None-Match: {}\r\n", tag));
+        assert!(response.starts_with("HTTP/1.1 304"), "Got '{}'", response);
+        assert_eq!(header(&response, "Vary"), Some("Accept-Language"));
+        let response = get(&format!("If-Modified-Since: {}\r\n", last_modified));
+        assert!(response.starts_with("HTTP/1.1 304"), "Got '{}'", response);
+        assert!(get(&format!("If-None-Match: {}\r\nAccept-Language: fr\r\n", tag)).starts_with("HTTP/1.1 200"));
+
+        fs::write(root_dir.join("b.txt"), "b").unwrap();
+        let response = get(&format!("If-None-Match: {}\r\n", tag));
+        assert!(response.starts_with("HTTP/1.1 200"), "Got '{}'", response);
+        assert_ne!(header(&response, "ETag"), Some(tag.as_str()));
+    }
+
+    /// Test the digests --checksums serves for ?checksum=sha256 and .sha256 siblings
+    #[test]
+    fn test_checksums() {
+        let root_dir = env::temp_dir().join("server_test_checksums");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("release.tar"), "release").unwrap();
+        fs::write(root_dir.join("notes.txt"), "notes").unwrap();
+        fs::write(root_dir.join("notes.txt.sha256"), "published by hand").unwrap();
+        fs::write(root_dir.join(".secret"), "secret").unwrap();
+        let get = |url: &str, target: &str| send(url, &format!("GET {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n", target));
+        let digest: String = Crypto::sha256(b"release").iter().map(|b| format!("{:02x}", b)).collect();
+        let line = format!("{}  release.tar\n", digest);
+
+        let url = start_server(&root_dir);
+        assert!(get(&url, "/release.tar?checksum=sha256").ends_with("\r\n\r\nrelease"));
+        assert!(get(&url, "/release.tar.sha256").starts_with("HTTP/1.1 404"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--checksums"]);
+        let response = get(&url, "/release.tar?checksum=SHA256");
+        assert!(response.starts_with("HTTP/1.1 200"), "Got '{}'", response);
+        assert!(response.ends_with(&line), "Got '{}'", response);
+        assert!(get(&url, "/release.tar.sha256").ends_with(&line));
+        assert!(get(&url, "/notes.txt.sha256").ends_with("published by hand"));
+        assert!(get(&url, "/release.tar?checksum=md5").starts_with("HTTP/1.1 400"));
+        assert!(get(&url, "/.secret?checksum=sha256").starts_with("HTTP/1.1 403"));
+        assert!(get(&url, "/missing.tar.sha256").starts_with("HTTP/1.1 404"));
+    }
+
+    /// Test that a fresh .gz sidecar is sent instead of gzipping the file, and a stale one ignored
+    #[test]
+    fn test_precompressed() {
+        let root_dir = env::temp_dir().join("server_test_precompressed");
+        let _ = fs::remove_dir_all(&root_dir);
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("app.js"), "let app = 1;\n".repeat(200)).unwrap();
+        fs::write(root_dir.join("app.js.gz"), "from the sidecar").unwrap();
+        fs::write(root_dir.join("old.js"), "let old = 1;\n".repeat(200)).unwrap();
+        fs::write(root_dir.join("old.js.gz"), "from the sidecar").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(60);
+        File::options().write(true).open(root_dir.join("old.js")).unwrap().set_modified(later).unwrap();
+
+        let url = start_server(&root_dir);
+        let get = |path: &str, accept: &str| {
+            send(&url, &format!("GET {} HTTP/1.1\r\nHost: test\r\nAccept-Encoding: {}\r\nConnection: close\r\n\r\n", path, accept))
+        };
+
+        let fresh = get("/app.js", "gzip");
+        assert_eq!(header(&fresh, "Content-Encoding"), Some("gzip"));
+        assert!(fresh.ends_with("\r\n\r\nfrom the sidecar"), "Got '{}'", fresh);
+        assert!(get("/app.js", "identity").ends_with("let app = 1;\n"));
+        let stale = get("/old.js", "gzip");
+        assert_eq!(header(&stale, "Content-Encoding"), Some("gzip"));
+        assert!(!stale.ends_with("from the sidecar"));
+    }
+
+    /// Test that the version comes from Cargo.toml and --build-header tells the build
+    #[test]
+    fn test_build_header() {
+        assert_eq!(Server::SERVER_VERSION, env!("CARGO_PKG_VERSION"));
+        assert!(Server::build_info().starts_with(&format!("{} (", env!("CARGO_PKG_VERSION"))));
+        let root_dir = env::temp_dir().join("server_test_build_header");
+        fs::create_dir_all(&root_dir).unwrap();
+        let request = "GET / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n";
+
+        let response = send(&start_server(&root_dir), request);
+        assert_eq!(header(&response, "Server"), Some(Server::version().as_str()));
+        assert_eq!(header(&response, "X-Katana-Build"), None);
+
+        let response = send(&start_server_with(&root_dir, &["--port", "0", "--build-header"]), request);
+        assert_eq!(header(&response, "X-Katana-Build"), Some(Server::build_info().as_str()));
+    }
+
+    /// Test that binary bodies reach the client byte for byte, read at once or streamed
+    #[test]
+    fn test_binary_bodies() {
+        let root_dir = env::temp_dir().join("server_test_binary_bodies");
+        fs::create_dir_all(&root_dir).unwrap();
+        let small: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        let large: Vec<u8> = (0..=255u8).rev().cycle().take(1_200_000).collect();
+        fs::write(root_dir.join("small.bin"), &small).unwrap();
+        fs::write(root_dir.join("large.bin"), &large).unwrap();
+        let url = start_server(&root_dir);
+
+        let fetch = |method: &str, path: &str| {
+            let mut stream = TcpStream::connect(url.trim_start_matches("http://")).unwrap();
+            let request = format!("{} {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n", method, path);
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).unwrap();
+            let end = response.windows(4).position(|window| window == b"\r\n\r\n").unwrap() + 4;
+            response.split_off(end)
+        };
+
+        assert_eq!(fetch("GET", "/small.bin"), small);
+        assert_eq!(fetch("GET", "/large.bin"), large);
+        assert!(fetch("HEAD", "/large.bin").is_empty());
+    }
+
+    /// Test that text files are labelled with their charset and transcoded when asked to
+    #[test]
+    fn test_charset() {
+        let root_dir = env::temp_dir().join("server_test_charset");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("modern.txt"), "déjà vu").unwrap();
+        fs::write(root_dir.join("legacy.html"), b"<p>d\xE9j\xE0 vu</p>").unwrap();
+        fs::write(root_dir.join("wide.txt"), b"\xFF\xFEo\0k\0").unwrap();
+        let get = |url: &str, path: &str| send(url, &format!("GET {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n", path));
+
+        let url = start_server(&root_dir);
+        assert_eq!(header(&get(&url, "/modern.txt"), "Content-Type"), Some("text/plain; charset=utf-8"));
+        assert_eq!(header(&get(&url, "/legacy.html"), "Content-Type"), Some("text/html; charset=iso-8859-1"));
+        assert_eq!(header(&get(&url, "/wide.txt"), "Content-Type"), Some("text/plain; charset=utf-16le"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--charset", "transcode"]);
+        let legacy = get(&url, "/legacy.html");
+        assert_eq!(header(&legacy, "Content-Type"), Some("text/html; charset=utf-8"));
+        assert!(legacy.ends_with("\r\n\r\n<p>déjà vu</p>"), "Got '{}'", legacy);
+        assert!(get(&url, "/wide.txt").ends_with("\r\n\r\nok"));
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--charset", "off"]);
+        assert_eq!(header(&get(&url, "/legacy.html"), "Content-Type"), Some("text/html"));
+    }
+
+    /// Test that handlers deny .env files, run CGI scripts and deny extensions on request
+    #[cfg(unix)]
+    #[test]
+    fn test_handlers() {
+        use std::os::unix::fs::PermissionsExt;
+        let root_dir = env::temp_dir().join("server_test_handlers");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join(".env"), "SECRET=1").unwrap();
+        fs::write(root_dir.join("notes.txt"), "notes").unwrap();
+        fs::write(root_dir.join("hello.cgi"), "#!/bin/sh\nprintf 'Content-Type: text/plain\\n\\nhello %s' \"$QUERY_STRING\"\n").unwrap();
+        fs::set_permissions(root_dir.join("hello.cgi"), fs::Permissions::from_mode(0o755)).unwrap();
+        let get = |url: &str, path: &str| send(url, &format!("GET {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n", path));
+
+        let url = start_server(&root_dir);
+        assert!(get(&url, "/.env").starts_with("HTTP/1.1 403"));
+        assert!(get(&url, "/hello.cgi").contains("printf"), "scripts are static by default");
+
+        let url = start_server_with(&root_dir, &["--port", "0", "--handler", "cgi=cgi", "--handler", "txt=deny"]);
+        let hello = get(&url, "/hello.cgi?name=ada");
+        assert!(hello.starts_with("HTTP/1.1 200"), "Got '{}'", hello);
+        assert_eq!(header(&hello, "Content-Type"), Some("text/plain"));
+        assert!(hello.ends_with("\r\n\r\nhello name=ada"), "Got '{}'", hello);
+        assert!(get(&url, "/notes.txt").starts_with("HTTP/1.1 403"));
+        assert!(get(&url, "/missing.cgi").starts_with("HTTP/1.1 404"));
+    }
+
+    /// Test that a proxy location keeps answering while one of its two upstreams is down
+    #[test]
+    fn test_proxy_location() {
+        let root_dir = env::temp_dir().join("server_test_proxy");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("index.html"), "static").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let live = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut head = Vec::new();
+                let mut byte = [0; 1];
+                while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+                    head.push(byte[0]);
+                }
+                let head = String::from_utf8_lossy(&head);
+                let line = head.lines().next().unwrap_or_default().to_string();
+                let forwarded = head.lines().find(|line| line.starts_with("X-Forwarded-For")).unwrap_or_default().to_string();
+                let body = format!("{}|{}", line, forwarded);
+                let hops = "Connection: close, X-Session\r\nX-Session: 42\r\nKeep-Alive: timeout=5\r\nVia: 1.1 app";
+                let reply = format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n{}\r\nContent-Length: {}\r\n\r\n{}", hops, body.len(), body);
+                stream.write_all(reply.as_bytes()).unwrap();
+            }
+        });
+        let down = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        let conf = root_dir.join("api.conf");
+        fs::write(&conf, format!("proxy = [\"{}\", \"{}\"]\n", down, live)).unwrap();
+
+        let location = format!("/api={}", conf.display());
+        let url = start_server_with(&root_dir, &["--port", "0", "--location", &location]);
+        let get = |path: &str| send(&url, &format!("GET {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n", path));
+        for _ in 0..3 {
+            let api = get("/api/users?page=2");
+            assert!(api.starts_with("HTTP/1.1 200"), "Got '{}'", api);
+            assert!(api.ends_with("\r\n\r\nGET /api/users?page=2 HTTP/1.0|X-Forwarded-For: 127.0.0.1"), "Got '{}'", api);
+            assert_eq!(header(&api, "Via"), Some("1.1 app, 1.1 katana"));
+            assert_eq!((header(&api, "X-Session"), header(&api, "Keep-Alive")), (None, None));
+            assert_eq!(header(&api, "Connection"), Some("close"));
+        }
+        assert!(get("/").ends_with("static"));
+    }
+
+    /// Test that --forward-proxy tunnels CONNECT and sends absolute-form requests on, to allowed destinations only
+    #[test]
+    fn test_forward_proxy() {
+        let echo = TcpListener::bind("127.0.0.1:0").unwrap();
+        let destination = echo.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in echo.incoming() {
+                let mut stream = stream.unwrap();
+                let mut buffer = [0; 1024];
+                let read = stream.read(&mut buffer).unwrap();
+                if buffer[..read].starts_with(b"GET ") {
+                    stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\norigin").unwrap();
+                } else {
+                    stream.write_all(&buffer[..read]).unwrap();
+                }
+            }
+        });
+
+        let url = start_server(&env::temp_dir());
+        let connect = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n\r\n", destination, destination);
+        assert!(send(&url, &connect).starts_with("HTTP/1.1 405"));
+
+        let url = start_server_with(&env::temp_dir(), &["--port", "0", "--forward-proxy", "--forward-allow", &destination]);
+        let mut stream = TcpStream::connect(url.trim_start_matches("http://")).unwrap();
+        stream.write_all(connect.as_bytes()).unwrap();
+        let mut established = [0; 39];
+        stream.read_exact(&mut established).unwrap();
+        assert_eq!(&established, b"HTTP/1.1 200 Connection Established\r\n\r\n");
+        stream.write_all(b"ping").unwrap();
+        let mut echoed = [0; 4];
+        stream.read_exact(&mut echoed).unwrap();
+        assert_eq!(&echoed, b"ping");
+        drop(stream);
+
+        let get = format!("GET http://{}/ HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", destination, destination);
+        let forwarded = send(&url, &get);
+        assert!(forwarded.starts_with("HTTP/1.1 200") && forwarded.ends_with("\r\n\r\norigin"), "Got '{}'", forwarded);
+        assert!(send(&url, "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n").starts_with("HTTP/1.1 403"));
+        assert!(send(&url, "GET http://example.com/ HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n").starts_with("HTTP/1.1 403"));
+    }
+}
@@ -0,0 +1,61 @@
+use katana::service::{Service, ServiceAction};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// Test that arguments are quoted the way Windows splits a command line again
+    #[test]
+    fn test_quote() {
+        assert_eq!(Service::quote("--dir"), "--dir");
+        assert_eq!(Service::quote(r"C:\sites\www"), r"C:\sites\www");
+        assert_eq!(Service::quote(""), "\"\"");
+        assert_eq!(Service::quote(r"C:\My Sites"), r#""C:\My Sites""#);
+        assert_eq!(Service::quote(r"C:\My Sites\"), r#""C:\My Sites\\""#);
+        assert_eq!(Service::quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(Service::quote(r#"a\"b c"#), r#""a\\\"b c""#);
+    }
+
+    /// Test that the installed command runs the service from the install directory with the same options
+    #[test]
+    fn test_command_line() {
+        let exe = Path::new(r"C:\Program Files\Katana\katana.exe");
+        let line = Service::command_line(
+            exe,
+            Path::new(r"C:\sites"),
+            &args(&["katana", "service", "install", "--dir", "public html", "--port", "80"]),
+        );
+        assert_eq!(
+            line,
+            r#""C:\Program Files\Katana\katana.exe" service run --service-dir C:\sites --dir "public html" --port 80"#
+        );
+
+        let line = Service::command_line(exe, Path::new(r"C:\sites"), &args(&["katana", "--service-name", "Docs", "service", "install"]));
+        assert!(line.ends_with(r"service run --service-dir C:\sites --service-name Docs"));
+    }
+
+    /// Test the service actions
+    #[test]
+    fn test_action() {
+        assert_eq!(ServiceAction::from_str("install"), Some(ServiceAction::Install));
+        assert_eq!(ServiceAction::from_str("Uninstall"), Some(ServiceAction::Uninstall));
+        assert_eq!(ServiceAction::from_str("run"), Some(ServiceAction::Run));
+        assert_eq!(ServiceAction::from_str("start"), None);
+        assert_eq!(ServiceAction::Run.as_str(), "run");
+    }
+
+    /// Test that services are refused outside Windows
+    #[cfg(not(windows))]
+    #[test]
+    fn test_unsupported() {
+        let service = Service::default();
+        assert_eq!(service.install(&[]).unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+        assert_eq!(service.uninstall().unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+        assert_eq!(service.run(|| {}).unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+    }
+}
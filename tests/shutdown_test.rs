@@ -0,0 +1,71 @@
+use katana::config::Config;
+use katana::server::Server;
+use katana::signal::Signal;
+use katana::stats::Stats;
+use katana::templates::Templates;
+
+// the shutdown flag is global to the process, so this test has a binary of its own
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Test that a shutdown lets requests in progress finish with Connection: close, closes
+    /// idle connections and counts the ones still busy after the grace period
+    #[test]
+    fn test_drain() {
+        let root_dir = env::temp_dir().join("shutdown_test_drain");
+        fs::create_dir_all(&root_dir).unwrap();
+        fs::write(root_dir.join("index.html"), "hello").unwrap();
+        let args = ["", "--host", "127.0.0.1", "--port", "0", "--dir", &root_dir.to_string_lossy(), "--keep-alive-timeout", "30"];
+        let server = Server::new(Config::parse_args(args.iter().map(|arg| arg.to_string()).collect()), Templates::load());
+        let mut listeners = server.listen().expect("Server should bind");
+        let addr = listeners[0].url().unwrap().trim_start_matches("http://").to_string();
+        let accepting = server.clone();
+        thread::spawn(move || accepting.accept(listeners.remove(0)));
+
+        let mut idle = TcpStream::connect(&addr).unwrap();
+        idle.write_all(b"GET / HTTP/1.1\r\nHost: test\r\n\r\n").unwrap();
+        let mut first = [0u8; 512];
+        let read = idle.read(&mut first).unwrap();
+        assert!(String::from_utf8_lossy(&first[..read]).ends_with("hello"));
+        let mut finishing = TcpStream::connect(&addr).unwrap();
+        finishing.write_all(b"GET / HTTP/1.1\r\nHost: test\r\n").unwrap();
+        let mut stuck = TcpStream::connect(&addr).unwrap();
+        stuck.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+        wait_until(|| Stats::active_connections() == 3);
+
+        Signal::request_shutdown();
+        let draining = server.clone();
+        let drained = thread::spawn(move || draining.drain(Duration::from_millis(800)));
+
+        // idle connections are closed without waiting for their keep-alive timeout
+        assert_eq!(idle.read(&mut first).unwrap(), 0);
+        finishing.write_all(b"\r\n").unwrap();
+        let mut response = String::new();
+        finishing.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "Got '{}'", response);
+        assert!(response.contains("Connection: close\r\n"), "Got '{}'", response);
+        assert_eq!(drained.join().unwrap(), 1);
+
+        // new connections are not served any more
+        let mut late = TcpStream::connect(&addr).unwrap();
+        let _ = late.write_all(b"GET / HTTP/1.1\r\nHost: test\r\n\r\n");
+        let mut response = Vec::new();
+        let _ = late.read_to_end(&mut response);
+        assert!(response.is_empty());
+        drop(stuck);
+    }
+}
@@ -0,0 +1,71 @@
+use katana::http::HttpStatus;
+use katana::signed::{SignatureError, SignedUrls};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn signed_urls() -> SignedUrls {
+        SignedUrls {
+            secret: Some(b"s3cret".to_vec()),
+            protected: vec!["/downloads".to_string()],
+        }
+    }
+
+    /// Helper function that splits a signed link into the path and queries the server sees
+    fn parse(link: &str) -> (String, Vec<(String, String)>) {
+        let (path, query) = link.split_once('?').unwrap();
+        let queries = query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        (path.to_string(), queries)
+    }
+
+    /// Test that a signed link verifies until it expires
+    #[test]
+    fn test_sign_and_verify() {
+        let link = SignedUrls::sign(b"s3cret", "/downloads/report.pdf", NOW + 60);
+        assert!(link.starts_with("/downloads/report.pdf?expires=1700000060&sig="));
+
+        let (path, queries) = parse(&link);
+        assert!(SignedUrls::is_signed(&queries));
+        assert_eq!(signed_urls().verify(&path, &queries, NOW), Ok(()));
+        assert_eq!(signed_urls().verify(&path, &queries, NOW + 60), Err(SignatureError::Expired));
+    }
+
+    /// Test that changing the path, expiry or secret breaks the signature
+    #[test]
+    fn test_tampering() {
+        let (_, queries) = parse(&SignedUrls::sign(b"s3cret", "/downloads/report.pdf", NOW + 60));
+        let urls = signed_urls();
+        assert_eq!(urls.verify("/downloads/other.pdf", &queries, NOW), Err(SignatureError::Invalid));
+
+        let mut later = queries.clone();
+        later[0].1 = (NOW + 3600).to_string();
+        assert_eq!(urls.verify("/downloads/report.pdf", &later, NOW), Err(SignatureError::Invalid));
+
+        let other = SignedUrls { secret: Some(b"other".to_vec()), ..signed_urls() };
+        assert_eq!(other.verify("/downloads/report.pdf", &queries, NOW), Err(SignatureError::Invalid));
+
+        assert_eq!(urls.verify("/downloads/report.pdf", &[], NOW), Err(SignatureError::Missing));
+        let malformed = vec![("expires".to_string(), "soon".to_string()), ("sig".to_string(), "AA".to_string())];
+        assert_eq!(urls.verify("/downloads/report.pdf", &malformed, NOW), Err(SignatureError::Malformed));
+    }
+
+    /// Test protected prefixes and the status codes of rejected links
+    #[test]
+    fn test_protects_and_status() {
+        let urls = signed_urls();
+        assert!(urls.protects("/downloads/report.pdf"));
+        assert!(!urls.protects("/downloadsx"));
+        assert!(!SignedUrls::default().protects("/downloads"));
+
+        assert_eq!(SignatureError::Expired.status(), HttpStatus::Gone);
+        assert_eq!(SignatureError::Invalid.status(), HttpStatus::Forbidden);
+        assert_eq!(SignatureError::Missing.status(), HttpStatus::Forbidden);
+    }
+}
@@ -0,0 +1,58 @@
+use katana::stats::{Phase, Stats};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that responses are counted by status and path and server errors are kept
+    #[test]
+    fn test_record() {
+        let requests = Stats::requests();
+        for _ in 0..3 {
+            Stats::record(200, "GET", "/stats-test/popular");
+        }
+        Stats::record(404, "GET", "/stats-test/missing");
+        Stats::record(503, "HEAD", "/stats-test/<down>");
+
+        assert_eq!(Stats::requests(), requests + 5);
+        assert!(Stats::statuses().contains(&(503, 1)));
+        assert_eq!(Stats::top_paths(1), vec![("/stats-test/popular".to_string(), 3)]);
+
+        let error = &Stats::recent_errors()[0];
+        assert_eq!((error.status, error.method.as_str(), error.path.as_str()), (503, "HEAD", "/stats-test/<down>"));
+    }
+
+    /// Test that the active connection count follows the guards
+    #[test]
+    fn test_active_connections() {
+        let before = Stats::active_connections();
+        let first = Stats::connection();
+        let second = Stats::connection();
+        assert_eq!(Stats::active_connections(), before + 2);
+        drop(first);
+        drop(second);
+        assert_eq!(Stats::active_connections(), before);
+    }
+
+    /// Test that connections are counted as accepted and move between reading and writing
+    #[test]
+    fn test_phases() {
+        let accepted = Stats::accepted_connections();
+        let mut connection = Stats::connection();
+        assert!(Stats::accepted_connections() > accepted);
+
+        // other tests open connections too, the counts only go up by this one
+        connection.set_phase(Phase::Reading);
+        assert!(Stats::reading() >= 1);
+        connection.set_phase(Phase::Writing);
+        assert!(Stats::writing() >= 1);
+        drop(connection);
+
+        let (received, sent) = (Stats::bytes_received(), Stats::bytes_sent());
+        Stats::received(512);
+        Stats::sent(2048);
+        assert!(Stats::bytes_received() >= received + 512);
+        assert!(Stats::bytes_sent() >= sent + 2048);
+        assert!(Stats::summary().contains("connection(s) accepted"));
+    }
+}
@@ -0,0 +1,84 @@
+use katana::logger::LogLevel;
+use katana::syslog::Syslog;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::{TcpListener, UdpSocket};
+
+    /// Test facility names and their numeric codes
+    #[test]
+    fn test_facility() {
+        assert_eq!(Syslog::facility("user"), Some(1));
+        assert_eq!(Syslog::facility("DAEMON"), Some(3));
+        assert_eq!(Syslog::facility("local0"), Some(16));
+        assert_eq!(Syslog::facility("local7"), Some(23));
+        assert_eq!(Syslog::facility("local8"), None);
+        assert_eq!(Syslog::facility("nope"), None);
+    }
+
+    /// Test that the priority combines facility and severity
+    #[test]
+    fn test_priority() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target = format!("udp://{}", collector.local_addr().unwrap());
+        let syslog = Syslog::connect(&target, Syslog::DEFAULT_FACILITY).unwrap();
+
+        assert_eq!(syslog.priority(LogLevel::DEBUG), 31);
+        assert_eq!(syslog.priority(LogLevel::INFO), 30);
+        assert_eq!(syslog.priority(LogLevel::WARN), 28);
+        assert_eq!(syslog.priority(LogLevel::ERROR), 27);
+    }
+
+    /// Test that UDP messages are RFC 5424 formatted
+    #[test]
+    fn test_send_udp() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target = format!("udp://{}", collector.local_addr().unwrap());
+        let mut syslog = Syslog::connect(&target, Syslog::facility("local0").unwrap()).unwrap();
+        syslog.send(LogLevel::WARN, "disk is slow").unwrap();
+
+        let mut buffer = [0; 512];
+        let size = collector.recv(&mut buffer).unwrap();
+        let message = String::from_utf8_lossy(&buffer[..size]);
+        assert!(message.starts_with("<132>1 "), "Got '{}'", message);
+        assert!(message.contains(" katana "));
+        assert!(message.ends_with(" - - disk is slow"));
+    }
+
+    /// Test that TCP messages are framed with their length
+    #[test]
+    fn test_send_tcp() {
+        let collector = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = format!("tcp://{}", collector.local_addr().unwrap());
+        let mut syslog = Syslog::connect(&target, Syslog::DEFAULT_FACILITY).unwrap();
+        syslog.send(LogLevel::INFO, "hello").unwrap();
+        drop(syslog);
+
+        let (mut stream, _) = collector.accept().unwrap();
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        let (length, frame) = received.split_once(' ').unwrap();
+        assert_eq!(length.parse::<usize>().unwrap(), frame.len());
+        assert!(frame.starts_with("<30>1 "));
+    }
+
+    /// Test messages sent to a unix datagram socket
+    #[cfg(unix)]
+    #[test]
+    fn test_send_local() {
+        let path = std::env::temp_dir().join("syslog_test.sock");
+        let _ = std::fs::remove_file(&path);
+        let collector = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        let mut syslog = Syslog::connect(&format!("unix:{}", path.display()), 1).unwrap();
+        syslog.send(LogLevel::ERROR, "boom").unwrap();
+
+        let mut buffer = [0; 512];
+        let size = collector.recv(&mut buffer).unwrap();
+        let message = String::from_utf8_lossy(&buffer[..size]);
+        assert_eq!(message, format!("<11>katana[{}]: boom", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,4 +1,4 @@
-use katana::templates::{Templates, TemplatesPage};
+use katana::templates::{Context, Templates, TemplatesPage, Value};
 
 #[cfg(test)]
 mod tests {
@@ -16,6 +16,8 @@ mod tests {
                 banner: "Welcome, {{username}}!".to_string(),
                 error: "Error: {{message}}".to_string(),
                 directory: "User: {{username}}, Role: {{role}}".to_string(),
+                status: "Requests: {{requests}}".to_string(),
+                messages: Templates::load().messages,
             }
         }
     }
@@ -103,4 +105,113 @@ mod tests {
             "Empty value should not remove the placeholder"
         );
     }
+
+    /// Test that every built-in language has each text of the English catalog
+    #[test]
+    fn test_built_in_messages() {
+        let templates = Templates::load();
+        assert_eq!(templates.languages(), vec!["de", "en", "es", "fr"]);
+        let english = &templates.messages["en"];
+        for language in templates.languages() {
+            for key in english.keys() {
+                assert!(templates.messages[&language].contains_key(key), "{} misses {}", language, key);
+            }
+        }
+    }
+
+    /// Test that the language comes from Accept-Language, then the locale, then English
+    #[test]
+    fn test_language() {
+        let templates = Templates::load();
+        assert_eq!(templates.language(Some("fr-CA, en;q=0.5"), None), "fr");
+        assert_eq!(templates.language(Some("ja"), Some("de")), "de");
+        assert_eq!(templates.language(Some("ja"), Some("pt")), "en");
+        assert_eq!(templates.language(None, None), "en");
+        assert_eq!(templates.message("fr-CA", "directory.empty"), Some("Dossier vide"));
+        assert_eq!(templates.message("fr", "missing.key"), None);
+    }
+
+    /// Test that texts are filled in by language and --messages can add or replace them
+    #[test]
+    fn test_rendering_in_language() {
+        let mut templates: Templates = TemplateExtensions::new_mock();
+        templates.banner = "<html lang=\"{{lang}}\">{{t.error.home}} {{t.unknown}} {{username}}".to_string();
+        let mut params = HashMap::new();
+        params.insert("username".to_string(), "{{t.error.home}}".to_string());
+        assert_eq!(
+            templates.render_in(TemplatesPage::BANNER, params.clone(), "fr"),
+            "<html lang=\"fr\">Retour à l&#39;accueil unknown {{t.error.home}}"
+        );
+
+        let messages = Templates::parse_messages("# custom\nerror.home = \"Torna alla home\"\n").unwrap();
+        templates.add_messages("it", messages);
+        assert_eq!(templates.render_in(TemplatesPage::BANNER, params, "it"), "<html lang=\"it\">Torna alla home unknown {{t.error.home}}");
+        assert_eq!(Templates::load().message("it", "error.home"), Some("Go back to home"), "the built-in catalog is left alone");
+        assert!(Templates::parse_messages("no equals sign").is_err());
+    }
+
+    /// Helper function that renders the banner template with a context
+    fn render(template: &str, context: &Context) -> String {
+        let mut templates: Templates = TemplateExtensions::new_mock();
+        templates.banner = template.to_string();
+        templates.render_context(TemplatesPage::BANNER, context, "en")
+    }
+
+    /// Test conditionals on text, flags and lists, with else and unless
+    #[test]
+    fn test_conditionals() {
+        let context = Context::from([
+            ("name".to_string(), Value::from("Alice")),
+            ("empty".to_string(), Value::from("")),
+            ("admin".to_string(), Value::from(false)),
+            ("items".to_string(), Value::from(Vec::<Context>::new())),
+        ]);
+        assert_eq!(render("{{#if name}}Hi {{name}}{{else}}Hi{{/if}}!", &context), "Hi Alice!");
+        assert_eq!(render("{{#if empty}}x{{else}}none{{/if}}", &context), "none");
+        assert_eq!(render("{{#if admin}}admin{{/if}}{{#unless admin}}user{{/unless}}", &context), "user");
+        assert_eq!(render("{{#if items}}list{{else}}no list{{/if}}", &context), "no list");
+        assert_eq!(render("{{#if missing}}{{#if name}}x{{/if}}{{else}}[{{empty}}]{{/if}}", &context), "[]");
+    }
+
+    /// Test loops, whose items see the values outside them
+    #[test]
+    fn test_loops() {
+        let item = |name: &str| Context::from([("name".to_string(), Value::from(name))]);
+        let context = Context::from([
+            ("files".to_string(), Value::from(vec![item("a.txt"), item("b.txt")])),
+            ("dir".to_string(), Value::from("/docs")),
+        ]);
+        assert_eq!(render("{{#each files}}<li>{{dir}}/{{name}}</li>{{/each}}", &context), "<li>/docs/a.txt</li><li>/docs/b.txt</li>");
+        assert_eq!(render("{{#each missing}}x{{else}}empty{{/each}}", &context), "empty");
+        assert_eq!(render("{{name}} {{#each files}}{{/each}}", &context), "{{name}} ", "unknown names stay");
+    }
+
+    /// Test that values are inserted as given and never parsed as template syntax
+    #[test]
+    fn test_values_are_not_parsed() {
+        let context = Context::from([
+            ("a".to_string(), Value::from("{{b}} {{#if b}}x{{/if}}")),
+            ("b".to_string(), Value::from("<b>")),
+        ]);
+        assert_eq!(render("{{a}}|{{b}}", &context), "{{b}} {{#if b}}x{{/if}}|<b>");
+        assert_eq!(render("{{#if b}}unclosed", &context), "unclosed");
+    }
+
+    /// Test that a templates directory replaces the files and texts it has
+    #[test]
+    fn test_load_dir() {
+        let dir = std::env::temp_dir().join("template_test_load_dir");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("messages")).unwrap();
+        std::fs::write(dir.join("error.html"), "<p>{{status_code}}: {{t.error.home}}</p>").unwrap();
+        std::fs::write(dir.join("messages").join("it.txt"), "error.home = Torna alla home\n").unwrap();
+
+        let templates = Templates::load_dir(&dir).unwrap();
+        let params = HashMap::from([("status_code".to_string(), "404".to_string())]);
+        assert_eq!(templates.render_in(TemplatesPage::ERROR, params, "it"), "<p>404: Torna alla home</p>");
+        assert_eq!(templates.directory, Templates::load().directory);
+
+        std::fs::write(dir.join("messages").join("de.txt"), "broken line").unwrap();
+        assert!(Templates::load_dir(&dir).is_err());
+    }
 }
@@ -0,0 +1,49 @@
+use katana::tus::Tus;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tus() -> Tus {
+        Tus {
+            endpoint: Some("/uploads".to_string()),
+            ..Tus::default()
+        }
+    }
+
+    /// Test that only generated ids under the endpoint name an upload
+    #[test]
+    fn test_upload_id() {
+        let tus = tus();
+        assert_eq!(tus.upload_id("/uploads/0123456789abcdef"), Some("0123456789abcdef"));
+        assert_eq!(tus.upload_id("/uploads"), None);
+        assert_eq!(tus.upload_id("/uploads/../etc/passwd"), None);
+        assert_eq!(tus.upload_id("/uploads/0123456789abcdef/x"), None);
+        assert_eq!(tus.upload_id("/other/0123456789abcdef"), None);
+        assert_eq!(Tus::default().upload_id("/uploads/0123456789abcdef"), None);
+    }
+
+    /// Test that the endpoint covers itself and its uploads but nothing beside it
+    #[test]
+    fn test_handles() {
+        let tus = tus();
+        assert!(tus.handles("/uploads"));
+        assert!(tus.handles("/uploads/0123456789abcdef"));
+        assert!(!tus.handles("/uploadsx"));
+        assert!(tus.is_endpoint("/uploads/"));
+        assert!(!tus.is_endpoint("/uploads/0123456789abcdef"));
+        assert!(!Tus::default().handles("/uploads"));
+    }
+
+    /// Test that uploads are kept in a dot directory of the root unless told otherwise
+    #[test]
+    fn test_dir() {
+        assert_eq!(tus().dir(Path::new("public")), PathBuf::from("public/.tus"));
+        let tus = Tus {
+            dir: Some(PathBuf::from("/var/uploads")),
+            ..tus()
+        };
+        assert_eq!(tus.dir(Path::new("public")), PathBuf::from("/var/uploads"));
+    }
+}
@@ -0,0 +1,68 @@
+use katana::userdir::UserDir;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    /// Test that /~user paths are split into the user and the rest, valid names only
+    #[test]
+    fn test_split() {
+        assert_eq!(UserDir::split("/~alice/notes/a.html"), Some(("alice", "/notes/a.html")));
+        assert_eq!(UserDir::split("/~alice/"), Some(("alice", "/")));
+        assert_eq!(UserDir::split("/~bob.smith_2"), Some(("bob.smith_2", "")));
+        assert_eq!(UserDir::split("/alice/"), None);
+        assert_eq!(UserDir::split("/~/"), None);
+        assert_eq!(UserDir::split("/~../etc"), None);
+        assert_eq!(UserDir::split("/~-rf/"), None);
+        assert_eq!(UserDir::split("/~al ice/"), None);
+        assert_eq!(UserDir::split(&format!("/~{}/", "a".repeat(33))), None);
+    }
+
+    /// Test that the directory must stay inside the home directory
+    #[test]
+    fn test_set_dir() {
+        let mut userdir = UserDir::default();
+        assert_eq!(userdir.dir, "public_html");
+        assert!(userdir.set_dir("www/site/").is_ok());
+        assert_eq!(userdir.dir, "www/site");
+        assert!(userdir.set_dir("/srv/www").is_err());
+        assert!(userdir.set_dir("../other").is_err());
+        assert!(userdir.set_dir(".config").is_err());
+        assert!(userdir.set_dir("").is_err());
+        assert_eq!(userdir.dir, "www/site");
+    }
+
+    /// Test that homes come from the passwd file and system accounts have no directory
+    #[test]
+    fn test_root() {
+        let base = env::temp_dir().join("userdir_test_root");
+        let _ = fs::remove_dir_all(&base);
+        for home in ["alice", "root", "bob"] {
+            fs::create_dir_all(base.join(home).join("public_html")).unwrap();
+        }
+        fs::create_dir_all(base.join("carol")).unwrap();
+        let passwd = base.join("passwd");
+        let home = |name: &str| base.join(name).display().to_string();
+        fs::write(
+            &passwd,
+            format!(
+                "root:x:0:0:root:{}:/bin/sh\nalice:x:1000:1000:Alice:{}:/bin/sh\nbob:x:1001:1001::relative/bob:/bin/sh\n\
+                 carol:x:1002:1002::{}:/bin/sh\nbroken line\n",
+                home("root"),
+                home("alice"),
+                home("carol")
+            ),
+        )
+        .unwrap();
+        let userdir = UserDir { enabled: true, passwd, ..UserDir::default() };
+
+        assert_eq!(userdir.root("alice"), Some(base.join("alice/public_html")));
+        assert_eq!(userdir.root("root"), None, "system accounts have no user directory");
+        assert_eq!(userdir.root("bob"), None, "homes must be absolute");
+        assert_eq!(userdir.root("carol"), None, "no public_html");
+        assert_eq!(userdir.root("dave"), None);
+        assert_eq!(UserDir { passwd: base.join("missing"), ..userdir }.root("alice"), None);
+    }
+}
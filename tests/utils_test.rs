@@ -1,3 +1,4 @@
+use katana::http::HttpStatus;
 use katana::utils::Utils;
 use std::env;
 use std::fs::{self, File};
@@ -134,6 +135,155 @@ mod tests {
         );
     }
 
+    /// Test `parse_size` with plain and suffixed sizes
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(Utils::parse_size("1024"), Some(1024));
+        assert_eq!(Utils::parse_size("512K"), Some(512 * 1024));
+        assert_eq!(Utils::parse_size("10m"), Some(10 * 1024 * 1024));
+        assert_eq!(Utils::parse_size("1G"), Some(1024 * 1024 * 1024));
+        assert_eq!(Utils::parse_size("ten"), None, "Non numeric size should be rejected");
+        assert_eq!(Utils::parse_size(""), None, "Empty size should be rejected");
+    }
+
+    /// Test `host_port` brackets IPv6 literals only
+    #[test]
+    fn test_host_port() {
+        assert_eq!(Utils::host_port("127.0.0.1", 80), "127.0.0.1:80");
+        assert_eq!(Utils::host_port("localhost", 80), "localhost:80");
+        assert_eq!(Utils::host_port("::1", 80), "[::1]:80");
+        assert_eq!(Utils::host_port("[::]", 80), "[::]:80");
+    }
+
+    /// Test `format_addr` with IPv4, IPv6 and IPv4-mapped addresses
+    #[test]
+    fn test_format_addr() {
+        let v4: std::net::SocketAddr = "10.0.0.1:5000".parse().unwrap();
+        let v6: std::net::SocketAddr = "[2001:db8::1]:5000".parse().unwrap();
+        let mapped: std::net::SocketAddr = "[::ffff:10.0.0.1]:5000".parse().unwrap();
+
+        assert_eq!(Utils::format_addr(&v4), "10.0.0.1:5000");
+        assert_eq!(Utils::format_addr(&v6), "[2001:db8::1]:5000");
+        assert_eq!(Utils::format_addr(&mapped), "10.0.0.1:5000");
+    }
+
+    /// Test `parse_duration` with plain and suffixed values
+    #[test]
+    fn test_parse_duration() {
+        use std::time::Duration;
+        assert_eq!(Utils::parse_duration("250"), Some(Duration::from_millis(250)));
+        assert_eq!(Utils::parse_duration("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(Utils::parse_duration("2s"), Some(Duration::from_secs(2)));
+        assert_eq!(Utils::parse_duration("1m"), Some(Duration::from_secs(60)));
+        assert_eq!(Utils::parse_duration("2h"), Some(Duration::from_secs(2 * 3600)));
+        assert_eq!(Utils::parse_duration("7d"), Some(Duration::from_secs(7 * 24 * 3600)));
+        assert_eq!(Utils::parse_duration("fast"), None);
+        assert_eq!(Utils::parse_duration("-1s"), None);
+    }
+
+    /// Test `under_prefix` matches whole path segments only
+    #[test]
+    fn test_under_prefix() {
+        assert!(Utils::under_prefix("/api", "/api"));
+        assert!(Utils::under_prefix("/api/users", "/api/"));
+        assert!(!Utils::under_prefix("/apix", "/api"));
+        assert!(Utils::under_prefix("/anything", "/"));
+    }
+
+    /// Test `encode_url_path` keeps separators and escapes everything else
+    #[test]
+    fn test_encode_url_path() {
+        assert_eq!(Utils::encode_url_path("/files/a-b_c.~txt"), "/files/a-b_c.~txt");
+        assert_eq!(Utils::encode_url_path("/my report?.pdf"), "/my%20report%3F.pdf");
+        assert_eq!(Utils::encode_url_path("/café"), "/caf%C3%A9");
+    }
+
+    /// Test `resolve_under` refuses the root, hidden entries and anything outside the root
+    #[test]
+    fn test_resolve_under() {
+        let root = env::temp_dir().join("utils_test_resolve_under");
+        fs::create_dir_all(root.join("docs")).unwrap();
+        File::create(root.join("docs/a.txt")).unwrap();
+
+        let resolved = Utils::resolve_under(&root, "/docs/a.txt").unwrap();
+        assert!(resolved.ends_with("docs/a.txt"));
+        assert!(Utils::resolve_under(&root, "/docs/").unwrap().ends_with("docs"));
+        assert_eq!(Utils::resolve_under(&root, "/docs/missing.txt"), Err(HttpStatus::NotFound));
+        assert_eq!(Utils::resolve_under(&root, "/"), Err(HttpStatus::Forbidden));
+        assert_eq!(Utils::resolve_under(&root, "/docs/../../etc/passwd"), Err(HttpStatus::Forbidden));
+        assert_eq!(Utils::resolve_under(&root, "/.git/config"), Err(HttpStatus::Forbidden));
+    }
+
+    /// Test `json_string` escapes quotes, backslashes and control characters
+    #[test]
+    fn test_json_string() {
+        assert_eq!(Utils::json_string("plain"), "\"plain\"");
+        assert_eq!(Utils::json_string("say \"hi\"\\"), "\"say \\\"hi\\\"\\\\\"");
+        assert_eq!(Utils::json_string("a\r\nb\x01"), "\"a\\r\\nb\\u0001\"");
+    }
+
+    /// Test `search_dir` matches names below, skips hidden and refused trees and stops at its limits
+    #[test]
+    fn test_search_dir() {
+        let root = env::temp_dir().join("utils_test_search_dir");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("docs/2024/deep")).unwrap();
+        fs::create_dir_all(root.join(".git/reports")).unwrap();
+        File::create(root.join("Report.txt")).unwrap();
+        File::create(root.join("docs/report-draft.md")).unwrap();
+        File::create(root.join("docs/2024/deep/report.pdf")).unwrap();
+        File::create(root.join(".git/reports/report")).unwrap();
+
+        let mut names: Vec<String> = Utils::search_dir(&root, "REPORT", 8, 100, |_, _| true).into_iter().map(|(_, name, _)| name).collect();
+        names.sort();
+        assert_eq!(names, vec!["Report.txt", "docs/2024/deep/report.pdf", "docs/report-draft.md"]);
+
+        assert_eq!(Utils::search_dir(&root, "report", 2, 100, |_, _| true).len(), 2);
+        assert_eq!(Utils::search_dir(&root, "report", 8, 1, |_, _| true).len(), 1);
+        assert_eq!(Utils::search_dir(&root, "2024", 8, 100, |_, _| true)[0].0, "directory");
+        let outside_docs = Utils::search_dir(&root, "report", 8, 100, |name, is_dir| !(is_dir && name == "docs"));
+        assert_eq!(outside_docs.len(), 1);
+    }
+
+    /// Test `human_size` picks the largest unit below 1024
+    #[test]
+    fn test_human_size() {
+        assert_eq!(Utils::human_size(0), "0 B");
+        assert_eq!(Utils::human_size(1023), "1023 B");
+        assert_eq!(Utils::human_size(1536), "1.5 KB");
+        assert_eq!(Utils::human_size(5 * 1024 * 1024 * 1024), "5.0 GB");
+    }
+
+    /// Test `datetime_rfc_3339` across epochs, leap days and centuries
+    #[test]
+    fn test_datetime_rfc_3339() {
+        assert_eq!(Utils::datetime_rfc_3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(Utils::datetime_rfc_3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(Utils::datetime_rfc_3339(1_709_251_199), "2024-02-29T23:59:59Z");
+        assert_eq!(Utils::datetime_rfc_3339(4_102_444_800), "2100-01-01T00:00:00Z");
+    }
+
+    /// Test `parse_http_date` against the dates `datetime_rfc_3339` formats
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(Utils::parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+        assert_eq!(Utils::parse_http_date("Tue, 29 Feb 2000 00:00:00 GMT"), Some(951_782_400));
+        assert_eq!(Utils::parse_http_date("Thu, 29 Feb 2024 23:59:59 GMT"), Some(1_709_251_199));
+        assert_eq!(Utils::parse_http_date("Fri, 01 Jan 2100 00:00:00 GMT"), Some(4_102_444_800));
+        assert_eq!(Utils::parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(Utils::parse_http_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+        assert_eq!(Utils::parse_http_date("yesterday"), None);
+    }
+
+    /// Test that `http_date` formats what `parse_http_date` reads
+    #[test]
+    fn test_http_date() {
+        assert_eq!(Utils::http_date(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(Utils::http_date(784_111_777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(Utils::http_date(1_709_251_199), "Thu, 29 Feb 2024 23:59:59 GMT");
+        assert_eq!(Utils::parse_http_date(&Utils::http_date(4_102_444_800)), Some(4_102_444_800));
+    }
+
     /// Clean up created temporary directory after tests
     fn cleanup_temp_dir() {
         let temp_dir = env::temp_dir().join("utils_test_temp_dir");
@@ -0,0 +1,91 @@
+use katana::config::Config;
+use katana::vhost::VirtualHost;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Helper function that loads the global options and one virtual host file
+    fn parse(name: &str, content: &str, global: &[&str]) -> (Config, Vec<String>) {
+        let file = env::temp_dir().join(format!("vhost_test_{}.conf", name));
+        fs::write(&file, content).unwrap();
+        let mut args = vec!["".to_string()];
+        args.extend(global.iter().map(|arg| arg.to_string()));
+        args.extend(["--vhost".to_string(), format!("example.com={}", file.display())]);
+        Config::parse(args)
+    }
+
+    /// Test the names=file syntax
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            VirtualHost::parse("Example.com., www.example.com=sites/example.conf"),
+            Some((vec!["example.com".to_string(), "www.example.com".to_string()], PathBuf::from("sites/example.conf")))
+        );
+        assert_eq!(VirtualHost::parse("sites/example.conf"), None);
+        assert_eq!(VirtualHost::parse("=sites/example.conf"), None);
+        assert_eq!(VirtualHost::parse("example.com="), None);
+    }
+
+    /// Test that a site is layered over the global options
+    #[test]
+    fn test_load() {
+        let (config, errors) = parse(
+            "load",
+            "dir = \"/srv/example\"\nno_listing = true\n",
+            &["--dir", "/srv/default", "--header", "X-Global: 1"],
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(config.root_dir, PathBuf::from("/srv/default"));
+        assert!(config.listing);
+
+        let site = config.for_host("example.com").unwrap();
+        assert_eq!(site.root_dir, PathBuf::from("/srv/example"));
+        assert!(!site.listing);
+        assert_eq!(site.headers, vec![("X-Global".to_string(), "1".to_string())]);
+        assert!(site.vhosts.is_empty());
+
+        assert!(config.for_host("other.com").is_none());
+
+        // a site certificate is refused like a global one
+        let (_, errors) = parse("certificate", "tls_cert = \"example.crt,example.key\"\n", &[]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].ends_with("--tls-cert needs TLS termination, which this build does not have"), "{:?}", errors);
+    }
+
+    /// Test that only site settings are accepted and errors name the file
+    #[test]
+    fn test_load_errors() {
+        let (_, errors) = parse("refused", "port = 9000\nwritable = true\n", &[]);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].ends_with("--port cannot be set per virtual host"), "{:?}", errors);
+
+        let (_, errors) = parse("invalid", "header = \"no colon\"\n", &[]);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("vhost_test_invalid.conf"), "{:?}", errors);
+
+        let (_, errors) = Config::parse(vec!["".to_string(), "--vhost".to_string(), "a.com=/missing.conf".to_string()]);
+        assert_eq!(errors.len(), 1);
+    }
+
+    /// Test that exact names win over wildcards and the port is ignored
+    #[test]
+    fn test_select() {
+        let (config, errors) = parse("select", "dir = \"/srv/exact\"\n", &[]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let mut wildcard = config.vhosts[0].clone();
+        wildcard.names = vec!["*.example.com".to_string()];
+        let hosts = vec![wildcard, config.vhosts[0].clone()];
+
+        let select = |host: &str| VirtualHost::select(&hosts, host).map(|vhost| vhost.names[0].clone());
+        assert_eq!(select("example.com:8080"), Some("example.com".to_string()));
+        assert_eq!(select("EXAMPLE.com."), Some("example.com".to_string()));
+        assert_eq!(select("www.example.com"), Some("*.example.com".to_string()));
+        assert_eq!(select("a.b.example.com"), None);
+        assert_eq!(select("[::1]:8080"), None);
+        assert_eq!(select(""), None);
+    }
+}
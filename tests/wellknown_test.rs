@@ -0,0 +1,55 @@
+use katana::wellknown::{Favicon, Robots, SecurityTxt};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    /// Test the robots.txt policies
+    #[test]
+    fn test_robots() {
+        assert_eq!(Robots::from_str("Allow"), Robots::AllowAll);
+        assert_eq!(Robots::from_str("deny"), Robots::DenyAll);
+        assert_eq!(Robots::from_str("robots.custom"), Robots::File(PathBuf::from("robots.custom")));
+        assert_eq!(Robots::DenyAll.body().unwrap(), "User-agent: *\nDisallow: /\n");
+        assert_eq!(Robots::AllowAll.body().unwrap(), "User-agent: *\nDisallow:\n");
+
+        let file = env::temp_dir().join("wellknown_test_robots.txt");
+        fs::write(&file, "User-agent: BadBot\nDisallow: /\n").unwrap();
+        assert_eq!(Robots::File(file).body().unwrap(), "User-agent: BadBot\nDisallow: /\n");
+        assert_eq!(Robots::File(PathBuf::from("/missing/robots.txt")).body(), None);
+    }
+
+    /// Test that the built-in favicon is an icon and can be replaced or turned off
+    #[test]
+    fn test_favicon() {
+        assert_eq!(Favicon::from_str("off"), Favicon::Off);
+        assert_eq!(Favicon::from_str("builtin"), Favicon::BuiltIn);
+        assert_eq!(Favicon::from_str("icon.png"), Favicon::File(PathBuf::from("icon.png")));
+
+        let built_in = Favicon::BuiltIn.body().unwrap();
+        assert_eq!(&built_in[..4], &[0, 0, 1, 0]);
+        assert_eq!(Favicon::Off.body(), None);
+        assert_eq!(Favicon::File(PathBuf::from("/missing/icon.png")).body(), None);
+    }
+
+    /// Test that security.txt lists every contact and expires ahead of now
+    #[test]
+    fn test_security_txt() {
+        let security_txt = SecurityTxt {
+            contacts: vec!["mailto:security@example.com".to_string(), "https://example.com/report".to_string()],
+            policy: Some("https://example.com/policy".to_string()),
+            expires: Duration::from_secs(86400),
+        };
+        assert!(security_txt.enabled());
+        assert_eq!(
+            security_txt.body(1_700_000_000),
+            "Contact: mailto:security@example.com\nContact: https://example.com/report\n\
+             Expires: 2023-11-15T22:13:20Z\nPolicy: https://example.com/policy\n"
+        );
+        assert!(!SecurityTxt::default().enabled());
+    }
+}